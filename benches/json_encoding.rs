@@ -0,0 +1,49 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Stream Load JSON encoding: serde_json against sonic-rs, the encoder
+//! `to_json_vec` switches to with `--features simd-json`, on a wide batch.
+//!
+//! Run with `cargo bench --features simd-json --bench json_encoding`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+fn rows() -> Vec<serde_json::Value> {
+    (0..50_000)
+        .map(|i| {
+            serde_json::json!({
+                "id": i,
+                "customer_id": i % 997,
+                "status": "shipped",
+                "total": i as f64 * 1.25,
+                "note": "lorem ipsum dolor sit amet, consectetur adipiscing elit",
+                "created_at": "2025-01-01 00:00:00",
+                "dbmazz_op_type": 0,
+                "dbmazz_is_deleted": false,
+                "dbmazz_synced_at": "2025-01-01 00:00:00",
+                "dbmazz_cdc_version": 0x10 + i,
+            })
+        })
+        .collect()
+}
+
+fn json_encoding(c: &mut Criterion) {
+    let rows = rows();
+    let bytes = serde_json::to_vec(&rows).unwrap().len();
+    // Both encoders produce the same JSON
+    assert_eq!(sonic_rs::to_vec(&rows).unwrap().len(), bytes);
+
+    let mut group = c.benchmark_group("json_encoding");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.sample_size(20);
+    group.bench_function("serde_json", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&rows)).unwrap())
+    });
+    group.bench_function("sonic_rs", |b| {
+        b.iter(|| sonic_rs::to_vec(black_box(&rows)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, json_encoding);
+criterion_main!(benches);
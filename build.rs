@@ -2,6 +2,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
     tonic_build::configure()
         .file_descriptor_set_path(out_dir.join("descriptor.bin"))
-        .compile_protos(&["src/proto/dbmazz.proto"], &["src/proto"])?;
+        // Batch payloads are cloned on retry; Bytes keeps that cheap
+        .bytes([".dbmazz.remote.RecordBatch.payload"])
+        .compile_protos(
+            &["src/proto/dbmazz.proto", "src/proto/remote_sink.proto"],
+            &["src/proto"],
+        )?;
     Ok(())
 }
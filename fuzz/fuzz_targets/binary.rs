@@ -0,0 +1,20 @@
+//! Decodes arbitrary bytes as a binary-format tuple value of every supported
+//! type, arrays included. Values come straight off the wire, so every input
+//! must decode or fail with an error, without panicking or allocating more
+//! than the value holds.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/source/binary.rs"]
+mod binary;
+
+/// Higher than every supported type and array type
+const MAX_TYPE_ID: u32 = 4000;
+
+fuzz_target!(|data: &[u8]| {
+    for type_id in (0..MAX_TYPE_ID).filter(|&t| binary::is_supported(t)) {
+        let _ = binary::to_text(type_id, data);
+    }
+});
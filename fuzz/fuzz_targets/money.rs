@@ -0,0 +1,25 @@
+//! `strip_money_symbol` keeps only the characters of a decimal number, with
+//! at most one decimal point, for any input.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/utils.rs"]
+mod utils;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let stripped = utils::strip_money_symbol(text);
+    assert!(stripped
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '-' || c == '.'));
+    assert!(
+        stripped.matches('.').count() <= 1,
+        "{:?} -> {:?}",
+        text,
+        stripped
+    );
+});
@@ -0,0 +1,63 @@
+//! `pg_types::value_json` output goes into JSON columns as is, so it must be
+//! valid JSON for any input, whatever type the text claims to be of.
+#![no_main]
+
+use std::sync::{Arc, Once};
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/utils.rs"]
+mod utils;
+
+#[allow(dead_code)]
+#[path = "../../src/source/pg_types.rs"]
+mod pg_types;
+
+use pg_types::PgType;
+
+/// OIDs of the types registered below, as if read from `pg_type`
+const COMPOSITE: u32 = 90_000;
+const COMPOSITE_ARRAY: u32 = 90_001;
+const HSTORE: u32 = 90_002;
+
+/// Arrays of typed and untyped elements, a multidimensional `box` array with
+/// its `;` delimiter, ranges, and the catalog types registered below
+const TYPES: &[u32] = &[
+    1000, 1007, 1009, 1020, 1021, 1185, 1231, 199, 3807, 3905, 3906, 3910, COMPOSITE,
+    COMPOSITE_ARRAY, HSTORE,
+];
+
+fuzz_target!(|data: &[u8]| {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        pg_types::register(
+            COMPOSITE,
+            PgType::Composite {
+                fields: Arc::from(vec![
+                    (Arc::from("id"), 23),
+                    (Arc::from("tags"), 1009),
+                    (Arc::from("self"), COMPOSITE),
+                ]),
+            },
+        );
+        pg_types::register(
+            COMPOSITE_ARRAY,
+            PgType::Array {
+                element: COMPOSITE,
+                delimiter: ',',
+            },
+        );
+        pg_types::register(HSTORE, PgType::Hstore);
+    });
+
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    for &oid in TYPES {
+        let json = pg_types::value_json(text, oid);
+        if let Err(e) = serde_json::from_str::<serde_json::Value>(&json) {
+            panic!("{:?} as type {} gave invalid JSON {:?}: {}", text, oid, json, e);
+        }
+    }
+});
@@ -0,0 +1,32 @@
+//! Decodes arbitrary bytes as the payload of an XLogData frame: a pgoutput
+//! tag followed by the message body. Every input must decode or fail with an
+//! error; a panic here would take the replication loop down.
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/source/binary.rs"]
+mod binary;
+
+#[allow(dead_code)]
+#[path = "../../src/source/parser.rs"]
+mod parser;
+
+/// parser.rs reaches binary.rs as `crate::source::binary`
+mod source {
+    pub use crate::binary;
+}
+
+use parser::PgOutputParser;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&tag, body)) = data.split_first() else {
+        return;
+    };
+    if let Ok(Some(message)) = PgOutputParser::parse(tag, Bytes::copy_from_slice(body)) {
+        // Decoded messages are archived and sent to the remote sink as JSON
+        serde_json::to_string(&message).unwrap();
+    }
+});
@@ -0,0 +1,17 @@
+//! `normalize_timestamptz` must return something for any text, and normalize
+//! its own output to itself.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/utils.rs"]
+mod utils;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let normalized = utils::normalize_timestamptz(text);
+    assert_eq!(utils::normalize_timestamptz(&normalized), normalized);
+});
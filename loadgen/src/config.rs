@@ -0,0 +1,299 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Load generator configuration, read from the environment.
+//!
+//! The source and sink variables are the engine's own (`SOURCE_URL`,
+//! `SINK_TYPE`, `SINK_URL`, ...), so the `.env` of the dbmazz instance under
+//! test works as is. The workload itself is set with `LOADGEN_*` variables.
+
+use anyhow::{Context, Result};
+use std::env;
+use std::time::Duration;
+
+/// Prefix of the tables the generator owns (`loadgen_1`, `loadgen_2`, ...)
+pub const TABLE_PREFIX: &str = "loadgen_";
+
+/// Target rates of each kind of change, across all tables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rates {
+    pub inserts_per_sec: f64,
+    pub updates_per_sec: f64,
+    pub deletes_per_sec: f64,
+    /// `ALTER TABLE ADD COLUMN` statements
+    pub ddl_per_hour: f64,
+}
+
+/// Where the replicated tables can be read back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkTarget {
+    StarRocks {
+        host: String,
+        port: u16,
+        user: String,
+        password: String,
+        database: String,
+    },
+    ClickHouse {
+        url: String,
+        user: String,
+        password: String,
+        database: String,
+    },
+    Sqlite {
+        path: String,
+    },
+    /// A remote sink server: nothing to read back
+    Remote,
+}
+
+/// How source schemas appear in sink table names (`SINK_SCHEMA_MODE`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaMode {
+    Strip,
+    Flatten,
+    Preserve,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadConfig {
+    /// `SOURCE_URL` without `replication=database`
+    pub source_url: String,
+    pub tables: usize,
+    /// Rows each table is seeded with by `setup`
+    pub initial_rows: u64,
+    pub rates: Rates,
+    /// Length of the `payload` text column; above ~2 KB values are TOASTed
+    pub payload_bytes: usize,
+    /// Columns `ADD COLUMN` may add to one table
+    pub max_added_columns: usize,
+    /// How long `run` generates load; `None` runs until Ctrl-C
+    pub duration: Option<Duration>,
+    pub seed: u64,
+    pub report_interval: Duration,
+    /// How long validation waits for the sink to catch up
+    pub validate_timeout: Duration,
+    pub sink: SinkTarget,
+    pub schema_mode: SchemaMode,
+}
+
+fn optional_env(name: &str, default: &str) -> String {
+    env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, default: &str) -> Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = optional_env(name, default);
+    value
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid {}='{}': {}", name, value, e))
+}
+
+fn rate(name: &str, default: &str) -> Result<f64> {
+    let rate: f64 = parse_env(name, default)?;
+    if !rate.is_finite() || rate < 0.0 {
+        anyhow::bail!("{} must be a non-negative number, got {}", name, rate);
+    }
+    Ok(rate)
+}
+
+impl LoadConfig {
+    pub fn from_env() -> Result<Self> {
+        let source_url = env::var("SOURCE_URL").context("SOURCE_URL is required")?;
+
+        let tables: usize = parse_env("LOADGEN_TABLES", "4")?;
+        if tables == 0 {
+            anyhow::bail!("LOADGEN_TABLES must be at least 1");
+        }
+
+        let duration = match parse_env::<u64>("LOADGEN_DURATION_SECS", "300")? {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+
+        // A random seed is printed at startup, so a run can be repeated
+        let seed = match env::var("LOADGEN_SEED") {
+            Ok(seed) => seed
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid LOADGEN_SEED='{}'", seed))?,
+            Err(_) => rand::random(),
+        };
+
+        Ok(Self {
+            source_url: plain_connection_url(&source_url)?,
+            tables,
+            initial_rows: parse_env("LOADGEN_INITIAL_ROWS", "10000")?,
+            rates: Rates {
+                inserts_per_sec: rate("LOADGEN_INSERTS_PER_SEC", "100")?,
+                updates_per_sec: rate("LOADGEN_UPDATES_PER_SEC", "100")?,
+                deletes_per_sec: rate("LOADGEN_DELETES_PER_SEC", "10")?,
+                ddl_per_hour: rate("LOADGEN_DDL_PER_HOUR", "6")?,
+            },
+            payload_bytes: parse_env("LOADGEN_PAYLOAD_BYTES", "100")?,
+            max_added_columns: parse_env("LOADGEN_MAX_ADDED_COLUMNS", "10")?,
+            duration,
+            seed,
+            report_interval: Duration::from_secs(
+                parse_env::<u64>("LOADGEN_REPORT_INTERVAL_SECS", "10")?.max(1),
+            ),
+            validate_timeout: Duration::from_secs(parse_env(
+                "LOADGEN_VALIDATE_TIMEOUT_SECS",
+                "300",
+            )?),
+            sink: sink_from_env()?,
+            schema_mode: match optional_env("SINK_SCHEMA_MODE", "strip")
+                .to_lowercase()
+                .as_str()
+            {
+                "strip" => SchemaMode::Strip,
+                "flatten" => SchemaMode::Flatten,
+                "preserve" => SchemaMode::Preserve,
+                other => anyhow::bail!(
+                    "Unsupported SINK_SCHEMA_MODE: '{}'. Supported: strip, flatten, preserve",
+                    other
+                ),
+            },
+        })
+    }
+
+    /// Names of the generated tables, in `public`
+    pub fn table_names(&self) -> Vec<String> {
+        (1..=self.tables)
+            .map(|i| format!("{}{}", TABLE_PREFIX, i))
+            .collect()
+    }
+
+    /// Sink database (when it isn't `SINK_DATABASE`) and table of a
+    /// generated table, as the engine names it.
+    pub fn sink_table(&self, table: &str) -> (Option<&'static str>, String) {
+        match self.schema_mode {
+            SchemaMode::Strip => (None, table.to_string()),
+            SchemaMode::Flatten => (None, format!("public__{}", table)),
+            SchemaMode::Preserve => (Some("public"), table.to_string()),
+        }
+    }
+}
+
+fn sink_from_env() -> Result<SinkTarget> {
+    let sink_type = optional_env("SINK_TYPE", "starrocks").to_lowercase();
+    let url = env::var("SINK_URL").context("SINK_URL is required")?;
+    let database = || env::var("SINK_DATABASE").context("SINK_DATABASE is required");
+    Ok(match sink_type.as_str() {
+        "starrocks" => SinkTarget::StarRocks {
+            // SINK_URL is the FE HTTP address; queries go to its MySQL port
+            host: url
+                .trim_start_matches("http://")
+                .trim_start_matches("https://")
+                .split([':', '/'])
+                .next()
+                .unwrap_or("localhost")
+                .to_string(),
+            port: parse_env("SINK_PORT", "9030")?,
+            user: optional_env("SINK_USER", "root"),
+            password: optional_env("SINK_PASSWORD", ""),
+            database: database()?,
+        },
+        "clickhouse" => SinkTarget::ClickHouse {
+            url: if url.contains("://") {
+                url.trim_end_matches('/').to_string()
+            } else {
+                format!("http://{}", url.trim_end_matches('/'))
+            },
+            user: optional_env("SINK_USER", "default"),
+            password: optional_env("SINK_PASSWORD", ""),
+            database: database()?,
+        },
+        "sqlite" => SinkTarget::Sqlite { path: url },
+        "remote" => SinkTarget::Remote,
+        other => anyhow::bail!(
+            "Unsupported SINK_TYPE: '{}'. Supported: starrocks, clickhouse, sqlite, remote",
+            other
+        ),
+    })
+}
+
+/// Drops `replication=database` from a connection URL: the generator runs
+/// plain SQL, which a walsender connection only partly accepts.
+fn plain_connection_url(source_url: &str) -> Result<String> {
+    let mut url = url::Url::parse(source_url).context("SOURCE_URL is not a valid URL")?;
+    let params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| name != "replication")
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if params.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(params);
+    }
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    fn clear_env_vars() {
+        for (name, _) in env::vars() {
+            if name.starts_with("LOADGEN_") || name.starts_with("SINK_") || name == "SOURCE_URL" {
+                env::remove_var(name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_plain_connection_url() {
+        assert_eq!(
+            plain_connection_url("postgres://u:p@pg:5432/db?replication=database").unwrap(),
+            "postgres://u:p@pg:5432/db"
+        );
+        assert_eq!(
+            plain_connection_url("postgres://pg/db?sslmode=require&replication=database").unwrap(),
+            "postgres://pg/db?sslmode=require"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_from_env() {
+        clear_env_vars();
+        env::set_var("SOURCE_URL", "postgres://pg/db?replication=database");
+        env::set_var("SINK_URL", "http://starrocks:8030");
+        env::set_var("SINK_DATABASE", "demo");
+        env::set_var("SINK_SCHEMA_MODE", "flatten");
+        env::set_var("LOADGEN_TABLES", "2");
+        env::set_var("LOADGEN_DURATION_SECS", "0");
+        env::set_var("LOADGEN_SEED", "42");
+
+        let config = LoadConfig::from_env().unwrap();
+        assert_eq!(config.source_url, "postgres://pg/db");
+        assert_eq!(config.table_names(), vec!["loadgen_1", "loadgen_2"]);
+        assert_eq!(config.duration, None);
+        assert_eq!(config.seed, 42);
+        assert_eq!(config.rates.inserts_per_sec, 100.0);
+        assert_eq!(
+            config.sink,
+            SinkTarget::StarRocks {
+                host: "starrocks".into(),
+                port: 9030,
+                user: "root".into(),
+                password: String::new(),
+                database: "demo".into(),
+            }
+        );
+        assert_eq!(
+            config.sink_table("loadgen_1"),
+            (None, "public__loadgen_1".to_string())
+        );
+
+        env::set_var("LOADGEN_DELETES_PER_SEC", "-1");
+        assert!(LoadConfig::from_env().is_err());
+
+        clear_env_vars();
+    }
+}
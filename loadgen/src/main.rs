@@ -0,0 +1,107 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+#![warn(clippy::all)]
+
+//! dbmazz-loadgen: synthetic workloads for soak and performance tests.
+//!
+//! Writes inserts, updates, deletes and `ADD COLUMN`s at configured rates
+//! into tables it owns in the source database, then checks that the sink
+//! holds the same rows. Configured from the same environment as dbmazz
+//! (see `config.rs` for the `LOADGEN_*` settings).
+//!
+//! ```text
+//! dbmazz-loadgen setup      # create and seed loadgen_1..N
+//! dbmazz-loadgen run        # generate load, then validate (default)
+//! dbmazz-loadgen validate   # compare the sink with the source
+//! dbmazz-loadgen teardown   # drop the tables
+//! ```
+
+mod config;
+mod validate;
+mod workload;
+
+use anyhow::{Context, Result};
+use dotenvy::dotenv;
+use tokio_postgres::{Client, NoTls};
+use tracing::{info, warn};
+
+use crate::config::LoadConfig;
+use crate::workload::Workload;
+
+const USAGE: &str = "Usage: dbmazz-loadgen [setup|run|validate|teardown]";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    dotenv().ok();
+
+    let command = std::env::args().nth(1).unwrap_or_else(|| "run".to_string());
+    if !matches!(command.as_str(), "setup" | "run" | "validate" | "teardown") {
+        anyhow::bail!("Unknown command '{}'. {}", command, USAGE);
+    }
+    let config = LoadConfig::from_env()?;
+    let mut client = connect(&config).await?;
+
+    match command.as_str() {
+        "setup" => {
+            workload::setup(&client, &config).await?;
+            info!(
+                "Tables ready; start dbmazz with TABLES={}",
+                config.table_names().join(",")
+            );
+            Ok(())
+        }
+        "teardown" => workload::teardown(&client, &config).await,
+        "validate" => validate(&client, &config).await,
+        _ => {
+            info!(
+                "Seed {} (LOADGEN_SEED={} repeats this run), {}",
+                config.seed,
+                config.seed,
+                match config.duration {
+                    Some(d) => format!("running for {:?}", d),
+                    None => "running until Ctrl-C".to_string(),
+                }
+            );
+            workload::setup(&client, &config).await?;
+            let totals = Workload::load(&mut client, &config).await?.run().await?;
+            info!(
+                "Load done: {} inserts, {} updates, {} deletes, {} columns added",
+                totals.inserts, totals.updates, totals.deletes, totals.ddl
+            );
+            validate(&client, &config).await
+        }
+    }
+}
+
+async fn connect(config: &LoadConfig) -> Result<Client> {
+    let (client, connection) = tokio_postgres::connect(&config.source_url, NoTls)
+        .await
+        .context("Failed to connect to SOURCE_URL")?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            warn!("Source connection error: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+async fn validate(client: &Client, config: &LoadConfig) -> Result<()> {
+    info!(
+        "Validating the sink copy (waiting up to {:?} for it to catch up)",
+        config.validate_timeout
+    );
+    let matched = tokio::select! {
+        matched = validate::validate(client, config) => matched?,
+        _ = tokio::signal::ctrl_c() => anyhow::bail!("Validation interrupted"),
+    };
+    if !matched {
+        anyhow::bail!("The sink does not match the source");
+    }
+    info!("The sink matches the source");
+    Ok(())
+}
@@ -0,0 +1,372 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Compares the sink's copy of the generated tables with the source.
+//!
+//! Both sides are summarized per bucket of [`BUCKET_SIZE`] keys: live rows,
+//! the sum of `version` (a missed update lowers it) and the non-NULL values
+//! of the newest `extra_<n>` column (a missed `ADD COLUMN` fails the query).
+//! Soft-deleted rows are left out on StarRocks and ClickHouse, and the
+//! latter is read with `FINAL`. Since the sink lags the source, mismatches
+//! are retried until they clear or the timeout runs out.
+
+use anyhow::{Context, Result};
+use mysql_async::prelude::Queryable;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio_postgres::Client;
+use tracing::{info, warn};
+
+use crate::config::{LoadConfig, SinkTarget};
+use crate::workload::added_columns;
+
+/// Keys per compared bucket
+pub const BUCKET_SIZE: i64 = 10_000;
+
+/// Mismatched buckets printed per table
+const MAX_REPORTED: usize = 10;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Summary of the rows of one bucket
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Bucket {
+    pub rows: i64,
+    pub version_sum: i64,
+    /// Non-NULL values of the newest added column
+    pub extra_values: i64,
+}
+
+pub type Buckets = BTreeMap<i64, Bucket>;
+
+/// A bucket whose summary differs; a bucket missing on one side is zero
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub bucket: i64,
+    pub source: Bucket,
+    pub sink: Bucket,
+}
+
+pub fn diff(source: &Buckets, sink: &Buckets) -> Vec<Mismatch> {
+    let mut keys: Vec<i64> = source.keys().chain(sink.keys()).copied().collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|bucket| {
+            let source = source.get(&bucket).copied().unwrap_or_default();
+            let sink = sink.get(&bucket).copied().unwrap_or_default();
+            (source != sink).then_some(Mismatch {
+                bucket,
+                source,
+                sink,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dialect {
+    Postgres,
+    StarRocks,
+    ClickHouse,
+    Sqlite,
+}
+
+/// Per-bucket summary query of `table`, an already quoted reference.
+fn summary_sql(dialect: Dialect, table: &str, extra: Option<&str>) -> String {
+    let (bucket, count, live) = match dialect {
+        Dialect::Postgres => (format!("id / {}", BUCKET_SIZE), "count(*)", ""),
+        Dialect::StarRocks => (
+            format!("id DIV {}", BUCKET_SIZE),
+            "count(*)",
+            " WHERE dbmazz_is_deleted = false",
+        ),
+        Dialect::ClickHouse => (
+            format!("intDiv(id, {})", BUCKET_SIZE),
+            "count()",
+            " FINAL WHERE dbmazz_is_deleted = 0",
+        ),
+        Dialect::Sqlite => (format!("id / {}", BUCKET_SIZE), "count(*)", ""),
+    };
+    let extra = match (extra, dialect) {
+        (Some(column), _) => format!("count({})", column),
+        (None, Dialect::Postgres) => "0::int8".to_string(),
+        (None, _) => "0".to_string(),
+    };
+    let version_sum = match dialect {
+        Dialect::Postgres => "sum(version)::int8",
+        _ => "sum(version)",
+    };
+    format!(
+        "SELECT {} AS bucket, {}, {}, {} FROM {}{} GROUP BY 1",
+        bucket, count, version_sum, extra, table, live
+    )
+}
+
+async fn source_buckets(client: &Client, table: &str, extra: Option<&str>) -> Result<Buckets> {
+    let sql = summary_sql(Dialect::Postgres, &format!("public.{}", table), extra);
+    let rows = client
+        .query(&sql, &[])
+        .await
+        .with_context(|| format!("Failed to summarize {} in the source", table))?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            (
+                row.get(0),
+                Bucket {
+                    rows: row.get(1),
+                    version_sum: row.get(2),
+                    extra_values: row.get(3),
+                },
+            )
+        })
+        .collect())
+}
+
+async fn sink_buckets(
+    config: &LoadConfig,
+    http: &reqwest::Client,
+    table: &str,
+    extra: Option<&str>,
+) -> Result<Buckets> {
+    let (database, name) = config.sink_table(table);
+    match &config.sink {
+        SinkTarget::StarRocks {
+            host,
+            port,
+            user,
+            password,
+            database: default_db,
+        } => {
+            let opts = mysql_async::OptsBuilder::default()
+                .ip_or_hostname(host.clone())
+                .tcp_port(*port)
+                .user(Some(user.clone()))
+                .pass(Some(password.clone()))
+                .prefer_socket(false);
+            let mut conn = mysql_async::Conn::new(opts).await?;
+            let reference = format!("`{}`.`{}`", database.unwrap_or(default_db), name);
+            let rows: Vec<(i64, i64, Option<i64>, i64)> = conn
+                .query(summary_sql(Dialect::StarRocks, &reference, extra))
+                .await?;
+            conn.disconnect().await?;
+            Ok(rows
+                .into_iter()
+                .map(|(bucket, rows, version_sum, extra_values)| {
+                    (
+                        bucket,
+                        Bucket {
+                            rows,
+                            version_sum: version_sum.unwrap_or(0),
+                            extra_values,
+                        },
+                    )
+                })
+                .collect())
+        }
+        SinkTarget::ClickHouse {
+            url,
+            user,
+            password,
+            database: default_db,
+        } => {
+            let reference = format!("`{}`.`{}`", database.unwrap_or(default_db), name);
+            let response = http
+                .post(url)
+                .header("X-ClickHouse-User", user)
+                .header("X-ClickHouse-Key", password)
+                .body(format!(
+                    "{} FORMAT TabSeparated",
+                    summary_sql(Dialect::ClickHouse, &reference, extra)
+                ))
+                .send()
+                .await?;
+            let status = response.status();
+            let text = response.text().await?;
+            if !status.is_success() {
+                anyhow::bail!("ClickHouse returned {}: {}", status, text.trim());
+            }
+            parse_tab_separated(&text)
+        }
+        SinkTarget::Sqlite { path } => {
+            let path = path.clone();
+            let sql = summary_sql(Dialect::Sqlite, &format!("\"{}\"", name), extra);
+            tokio::task::spawn_blocking(move || -> Result<Buckets> {
+                let conn = rusqlite::Connection::open_with_flags(
+                    &path,
+                    rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+                )?;
+                let mut stmt = conn.prepare(&sql)?;
+                let rows = stmt.query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        Bucket {
+                            rows: row.get(1)?,
+                            version_sum: row.get(2)?,
+                            extra_values: row.get(3)?,
+                        },
+                    ))
+                })?;
+                Ok(rows.collect::<rusqlite::Result<_>>()?)
+            })
+            .await?
+        }
+        SinkTarget::Remote => unreachable!("remote sinks are not validated"),
+    }
+}
+
+fn parse_tab_separated(text: &str) -> Result<Buckets> {
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let fields = line
+                .split('\t')
+                .map(|f| f.parse::<i64>())
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("Unexpected ClickHouse row '{}'", line))?;
+            let [bucket, rows, version_sum, extra_values] = fields[..] else {
+                anyhow::bail!("Unexpected ClickHouse row '{}'", line);
+            };
+            Ok((
+                bucket,
+                Bucket {
+                    rows,
+                    version_sum,
+                    extra_values,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Compares every generated table until the sink matches the source or
+/// `validate_timeout` passes. Returns whether all tables matched.
+pub async fn validate(client: &Client, config: &LoadConfig) -> Result<bool> {
+    if config.sink == SinkTarget::Remote {
+        warn!("The remote sink can't be read back; compare on the sink server instead");
+        return Ok(true);
+    }
+    let http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()?;
+
+    let started = Instant::now();
+    let mut matched = true;
+    for table in config.table_names() {
+        let extra = added_columns(client, &table).await?.pop();
+        // The load has stopped, so the source summary holds still
+        let source = source_buckets(client, &table, extra.as_deref()).await?;
+        let rows: i64 = source.values().map(|b| b.rows).sum();
+
+        loop {
+            let outcome = sink_buckets(config, &http, &table, extra.as_deref())
+                .await
+                .map(|sink| diff(&source, &sink));
+            match outcome {
+                Ok(mismatches) if mismatches.is_empty() => {
+                    info!("{}: {} rows match", table, rows);
+                    break;
+                }
+                _ if started.elapsed() < config.validate_timeout => {
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+                Ok(mismatches) => {
+                    warn!(
+                        "{}: {} of {} buckets differ after {:?}",
+                        table,
+                        mismatches.len(),
+                        source.len(),
+                        config.validate_timeout
+                    );
+                    for m in mismatches.iter().take(MAX_REPORTED) {
+                        warn!(
+                            "  ids {}..{}: source {:?}, sink {:?}",
+                            m.bucket * BUCKET_SIZE,
+                            (m.bucket + 1) * BUCKET_SIZE - 1,
+                            m.source,
+                            m.sink
+                        );
+                    }
+                    matched = false;
+                    break;
+                }
+                Err(e) => {
+                    warn!("{}: failed to read the sink copy: {:#}", table, e);
+                    matched = false;
+                    break;
+                }
+            }
+        }
+    }
+    Ok(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(rows: i64, version_sum: i64) -> Bucket {
+        Bucket {
+            rows,
+            version_sum,
+            extra_values: 0,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let source = Buckets::from([(0, bucket(10, 12)), (1, bucket(5, 5)), (2, bucket(1, 1))]);
+        let sink = Buckets::from([(0, bucket(10, 12)), (1, bucket(5, 4)), (3, bucket(1, 1))]);
+        assert_eq!(
+            diff(&source, &sink),
+            vec![
+                Mismatch {
+                    bucket: 1,
+                    source: bucket(5, 5),
+                    sink: bucket(5, 4)
+                },
+                Mismatch {
+                    bucket: 2,
+                    source: bucket(1, 1),
+                    sink: Bucket::default()
+                },
+                Mismatch {
+                    bucket: 3,
+                    source: Bucket::default(),
+                    sink: bucket(1, 1)
+                },
+            ]
+        );
+        assert!(diff(&source, &source).is_empty());
+    }
+
+    #[test]
+    fn test_summary_sql() {
+        assert_eq!(
+            summary_sql(Dialect::ClickHouse, "`db`.`loadgen_1`", Some("extra_2")),
+            "SELECT intDiv(id, 10000) AS bucket, count(), sum(version), count(extra_2) \
+             FROM `db`.`loadgen_1` FINAL WHERE dbmazz_is_deleted = 0 GROUP BY 1"
+        );
+        assert_eq!(
+            summary_sql(Dialect::Postgres, "public.loadgen_1", None),
+            "SELECT id / 10000 AS bucket, count(*), sum(version)::int8, 0::int8 \
+             FROM public.loadgen_1 GROUP BY 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_tab_separated() {
+        let buckets = parse_tab_separated("0\t9999\t10020\t3\n1\t1\t1\t0\n").unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(
+            buckets[&0],
+            Bucket {
+                rows: 9999,
+                version_sum: 10020,
+                extra_values: 3
+            }
+        );
+        assert!(parse_tab_separated("0\t1\n").is_err());
+    }
+}
@@ -0,0 +1,405 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Synthetic write workload against the source database.
+//!
+//! Every table the generator owns has the same shape: a `bigint` key, a
+//! `version` bumped by each update (validation compares its sum), a few
+//! typed columns and a `payload` text that updates leave alone, so a payload
+//! longer than ~2 KB is stored out of line and replicated as an unchanged
+//! TOAST value. `ADD COLUMN` adds `extra_<n>` text columns, and later
+//! updates fill the newest one.
+//!
+//! Changes are issued in ticks of [`TICK`]: each tick owes every kind of
+//! change its rate times the tick length, and the whole tick runs as one
+//! transaction spanning several tables. Which rows are touched and what
+//! they are set to comes from a seeded RNG, so a run with the same seed,
+//! starting from the same tables, issues the same changes.
+
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::seq::index;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+use tokio_postgres::Client;
+use tracing::info;
+
+use crate::config::{LoadConfig, Rates};
+
+/// Length of one batch of changes
+pub const TICK: Duration = Duration::from_millis(100);
+
+const STATUSES: &[&str] = &["new", "paid", "packed", "shipped", "delivered", "returned"];
+
+/// Creates the generated tables and seeds the empty ones.
+pub async fn setup(client: &Client, config: &LoadConfig) -> Result<()> {
+    for table in config.table_names() {
+        client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS public.{} (
+                    id bigint PRIMARY KEY,
+                    version bigint NOT NULL DEFAULT 1,
+                    amount numeric(12,2) NOT NULL,
+                    status text NOT NULL,
+                    payload text,
+                    updated_at timestamptz NOT NULL DEFAULT now()
+                )",
+                table
+            ))
+            .await
+            .with_context(|| format!("Failed to create {}", table))?;
+
+        let row = client
+            .query_one(&format!("SELECT count(*) FROM public.{}", table), &[])
+            .await?;
+        if row.get::<_, i64>(0) > 0 || config.initial_rows == 0 {
+            continue;
+        }
+        client
+            .execute(
+                &format!(
+                    "INSERT INTO public.{} (id, amount, status, payload)
+                     SELECT g, (g % 100000) / 10.0, 'new', repeat('x', $2::int4)
+                     FROM generate_series(1, $1::int8) AS g",
+                    table
+                ),
+                &[
+                    &(config.initial_rows as i64),
+                    &(config.payload_bytes as i32),
+                ],
+            )
+            .await
+            .with_context(|| format!("Failed to seed {}", table))?;
+        info!("Seeded {} with {} rows", table, config.initial_rows);
+    }
+    Ok(())
+}
+
+/// Drops the generated tables.
+pub async fn teardown(client: &Client, config: &LoadConfig) -> Result<()> {
+    for table in config.table_names() {
+        client
+            .batch_execute(&format!("DROP TABLE IF EXISTS public.{}", table))
+            .await
+            .with_context(|| format!("Failed to drop {}", table))?;
+        info!("Dropped {}", table);
+    }
+    Ok(())
+}
+
+/// Changes owed to each kind of change, carried over between ticks so
+/// fractional rates add up.
+#[derive(Debug, Default)]
+struct Credits {
+    per_tick: [f64; 4],
+    owed: [f64; 4],
+}
+
+impl Credits {
+    fn new(rates: &Rates) -> Self {
+        let secs = TICK.as_secs_f64();
+        Self {
+            per_tick: [
+                rates.inserts_per_sec * secs,
+                rates.updates_per_sec * secs,
+                rates.deletes_per_sec * secs,
+                rates.ddl_per_hour / 3600.0 * secs,
+            ],
+            owed: [0.0; 4],
+        }
+    }
+
+    /// Whole changes of each kind due this tick: inserts, updates, deletes,
+    /// `ADD COLUMN`s.
+    fn take(&mut self) -> [usize; 4] {
+        let mut due = [0; 4];
+        for ((due, owed), per_tick) in due.iter_mut().zip(&mut self.owed).zip(self.per_tick) {
+            *owed += per_tick;
+            // The epsilon keeps sums like ten times 0.1 from rounding down
+            *due = (*owed + 1e-9).floor() as usize;
+            *owed -= *due as f64;
+        }
+        due
+    }
+}
+
+/// Changes issued so far
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Totals {
+    pub inserts: u64,
+    pub updates: u64,
+    pub deletes: u64,
+    pub ddl: u64,
+}
+
+struct TableState {
+    name: String,
+    /// Keys of the rows that exist, as far as the generator knows
+    live: Vec<i64>,
+    next_id: i64,
+    added_columns: usize,
+}
+
+pub struct Workload<'a> {
+    client: &'a mut Client,
+    config: &'a LoadConfig,
+    rng: StdRng,
+    tables: Vec<TableState>,
+    credits: Credits,
+    totals: Totals,
+}
+
+impl<'a> Workload<'a> {
+    /// Reads the current keys and added columns of every table.
+    pub async fn load(client: &'a mut Client, config: &'a LoadConfig) -> Result<Workload<'a>> {
+        let mut tables = Vec::new();
+        for name in config.table_names() {
+            let live: Vec<i64> = client
+                .query(&format!("SELECT id FROM public.{} ORDER BY id", name), &[])
+                .await
+                .with_context(|| format!("Failed to read the keys of {} (run setup first)", name))?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            let added_columns = added_columns(client, &name).await?.len();
+            tables.push(TableState {
+                next_id: live.last().map_or(1, |id| id + 1),
+                name,
+                live,
+                added_columns,
+            });
+        }
+        Ok(Self {
+            client,
+            config,
+            rng: StdRng::seed_from_u64(config.seed),
+            tables,
+            credits: Credits::new(&config.rates),
+            totals: Totals::default(),
+        })
+    }
+
+    /// Generates load until `duration` has passed or Ctrl-C is pressed.
+    pub async fn run(&mut self) -> Result<Totals> {
+        let started = Instant::now();
+        let mut ticks = tokio::time::interval(TICK);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+        let mut last_report = (Instant::now(), self.totals);
+        let mut ticks_done: u32 = 0;
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = &mut ctrl_c => {
+                    info!("Interrupted, stopping the load");
+                    break;
+                }
+            }
+            if self.config.duration.is_some_and(|d| started.elapsed() >= d) {
+                break;
+            }
+
+            let due = self.credits.take();
+            self.tick(due).await?;
+            ticks_done += 1;
+
+            if last_report.0.elapsed() >= self.config.report_interval {
+                // Late ticks are caught up in bursts; more than a few owed
+                // means the source can't take the configured rates
+                let behind = started.elapsed() > TICK * (ticks_done + 5);
+                self.report(last_report.0.elapsed(), &last_report.1, behind);
+                last_report = (Instant::now(), self.totals);
+            }
+        }
+        Ok(self.totals)
+    }
+
+    fn report(&self, elapsed: Duration, before: &Totals, behind: bool) {
+        let per_sec = |now: u64, then: u64| (now - then) as f64 / elapsed.as_secs_f64();
+        info!(
+            "inserts {} ({:.1}/s), updates {} ({:.1}/s), deletes {} ({:.1}/s), columns added {}, live rows {}{}",
+            self.totals.inserts,
+            per_sec(self.totals.inserts, before.inserts),
+            self.totals.updates,
+            per_sec(self.totals.updates, before.updates),
+            self.totals.deletes,
+            per_sec(self.totals.deletes, before.deletes),
+            self.totals.ddl,
+            self.tables.iter().map(|t| t.live.len()).sum::<usize>(),
+            if behind {
+                " (the source is slower than the configured rates)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    /// Issues one tick of changes in a single transaction.
+    async fn tick(&mut self, due: [usize; 4]) -> Result<()> {
+        let [inserts, updates, deletes, ddl] = due;
+        if inserts + updates + deletes + ddl == 0 {
+            return Ok(());
+        }
+
+        // Spread the changes over the tables
+        let n = self.tables.len();
+        let mut per_table = vec![[0usize; 4]; n];
+        for (kind, count) in due.iter().enumerate() {
+            for _ in 0..*count {
+                per_table[self.rng.gen_range(0..n)][kind] += 1;
+            }
+        }
+
+        let txn = self.client.transaction().await?;
+        for (table, [inserts, updates, deletes, ddl]) in self.tables.iter_mut().zip(per_table) {
+            if inserts > 0 {
+                let ids: Vec<i64> = (table.next_id..table.next_id + inserts as i64).collect();
+                let amounts: Vec<f64> = ids
+                    .iter()
+                    .map(|_| self.rng.gen_range(1..1_000_000) as f64 / 100.0)
+                    .collect();
+                let statuses: Vec<&str> = ids.iter().map(|_| STATUSES[0]).collect();
+                let payloads: Vec<String> = ids
+                    .iter()
+                    .map(|_| random_text(&mut self.rng, self.config.payload_bytes))
+                    .collect();
+                txn.execute(
+                    &format!(
+                        "INSERT INTO public.{} (id, amount, status, payload)
+                         SELECT id, amount::numeric(12,2), status, payload
+                         FROM unnest($1::int8[], $2::float8[], $3::text[], $4::text[])
+                              AS r(id, amount, status, payload)",
+                        table.name
+                    ),
+                    &[&ids, &amounts, &statuses, &payloads],
+                )
+                .await
+                .with_context(|| format!("Failed to insert into {}", table.name))?;
+                table.next_id += inserts as i64;
+                table.live.extend(ids);
+                self.totals.inserts += inserts as u64;
+            }
+
+            let updates = updates.min(table.live.len());
+            if updates > 0 {
+                let ids: Vec<i64> = index::sample(&mut self.rng, table.live.len(), updates)
+                    .iter()
+                    .map(|i| table.live[i])
+                    .collect();
+                let statuses: Vec<&str> = ids
+                    .iter()
+                    .map(|_| STATUSES[self.rng.gen_range(1..STATUSES.len())])
+                    .collect();
+                let extra = match table.added_columns {
+                    0 => String::new(),
+                    n => format!(", extra_{} = u.status || '-' || t.version", n),
+                };
+                txn.execute(
+                    &format!(
+                        "UPDATE public.{} AS t
+                         SET version = t.version + 1, amount = t.amount + 1,
+                             status = u.status, updated_at = now(){}
+                         FROM unnest($1::int8[], $2::text[]) AS u(id, status)
+                         WHERE t.id = u.id",
+                        table.name, extra
+                    ),
+                    &[&ids, &statuses],
+                )
+                .await
+                .with_context(|| format!("Failed to update {}", table.name))?;
+                self.totals.updates += updates as u64;
+            }
+
+            let deletes = deletes.min(table.live.len());
+            if deletes > 0 {
+                let mut picked = index::sample(&mut self.rng, table.live.len(), deletes).into_vec();
+                // Highest first, so swap_remove never moves a picked row
+                picked.sort_unstable_by(|a, b| b.cmp(a));
+                let ids: Vec<i64> = picked
+                    .into_iter()
+                    .map(|i| table.live.swap_remove(i))
+                    .collect();
+                txn.execute(
+                    &format!("DELETE FROM public.{} WHERE id = ANY($1)", table.name),
+                    &[&ids],
+                )
+                .await
+                .with_context(|| format!("Failed to delete from {}", table.name))?;
+                self.totals.deletes += deletes as u64;
+            }
+
+            for _ in 0..ddl {
+                if table.added_columns >= self.config.max_added_columns {
+                    break;
+                }
+                table.added_columns += 1;
+                txn.batch_execute(&format!(
+                    "ALTER TABLE public.{} ADD COLUMN IF NOT EXISTS extra_{} text",
+                    table.name, table.added_columns
+                ))
+                .await
+                .with_context(|| format!("Failed to add a column to {}", table.name))?;
+                info!("Added extra_{} to {}", table.added_columns, table.name);
+                self.totals.ddl += 1;
+            }
+        }
+        txn.commit().await.context("Failed to commit a tick")?;
+        Ok(())
+    }
+}
+
+/// `extra_<n>` columns of a generated table, in the order they were added.
+pub async fn added_columns(client: &Client, table: &str) -> Result<Vec<String>> {
+    let rows = client
+        .query(
+            "SELECT column_name::text FROM information_schema.columns
+             WHERE table_schema = 'public' AND table_name = $1 AND column_name LIKE 'extra\\_%'
+             ORDER BY ordinal_position",
+            &[&table],
+        )
+        .await
+        .with_context(|| format!("Failed to read the columns of {}", table))?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+fn random_text(rng: &mut StdRng, len: usize) -> String {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ";
+    (0..len)
+        .map(|_| CHARS[rng.gen_range(0..CHARS.len())] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credits_add_up_fractional_rates() {
+        let mut credits = Credits::new(&Rates {
+            inserts_per_sec: 25.0,
+            updates_per_sec: 3.0,
+            deletes_per_sec: 0.0,
+            ddl_per_hour: 3600.0,
+        });
+        let mut totals = [0usize; 4];
+        // Ten seconds of ticks
+        for _ in 0..100 {
+            let due = credits.take();
+            for (total, n) in totals.iter_mut().zip(due) {
+                *total += n;
+            }
+        }
+        assert_eq!(totals, [250, 30, 0, 10]);
+    }
+
+    #[test]
+    fn test_random_text_is_seeded() {
+        let a = random_text(&mut StdRng::seed_from_u64(7), 32);
+        let b = random_text(&mut StdRng::seed_from_u64(7), 32);
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 32);
+    }
+}
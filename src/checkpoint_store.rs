@@ -0,0 +1,405 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Where the checkpoint lives (`CHECKPOINT_STORE`).
+//!
+//! The checkpoint is the last LSN the sink durably has, saved before it is
+//! confirmed to PostgreSQL, plus the schema cache. By default it is kept in
+//! the `dbmazz_checkpoints` table of the source ([`StateStore`]), which goes
+//! away with the source database. The file and S3 stores keep it outside,
+//! one JSON document per slot, so a replacement process, or one whose slot
+//! was recreated, still knows where replication stopped.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::config::{CheckpointStoreTarget, Config, HttpClientConfig, S3Location};
+use crate::connectors::s3::S3Client;
+use crate::pipeline::schema_cache::RelationVersion;
+use crate::source::session::PgSession;
+use crate::state_format::{FormatStamp, CHECKPOINT_FORMAT};
+use crate::state_store::{decode_relations, encode_relations, relations_stamp, StateStore};
+
+/// Durable storage of the checkpoint of a slot.
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    /// Saves the slot's checkpoint; replication halts when this fails.
+    async fn save_checkpoint(&self, slot: &str, lsn: u64) -> Result<()>;
+
+    /// The slot's last saved checkpoint, `None` when it has none.
+    async fn load_checkpoint(&self, slot: &str) -> Result<Option<u64>>;
+
+    /// Format and writer of the slot's checkpoint, `None` when it has none.
+    async fn checkpoint_stamp(&self, slot: &str) -> Result<Option<CheckpointStamp>>;
+
+    /// Stores the relation versions of schema `version` next to the slot's
+    /// checkpoint. Skipped when that version is already stored.
+    async fn save_relations(
+        &self,
+        slot: &str,
+        version: u64,
+        relations: &[RelationVersion],
+    ) -> Result<()>;
+
+    /// Relation versions stored by `save_relations`, empty when there are none.
+    /// An unreadable cache is logged and ignored: PostgreSQL re-sends every
+    /// Relation message before the first DML of a table anyway.
+    async fn load_relations(&self, slot: &str) -> Result<Vec<RelationVersion>>;
+}
+
+/// Format and writer of a stored checkpoint, and how the writer confirmed
+/// the slot's position.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckpointStamp {
+    pub stamp: FormatStamp,
+    /// The writer never lets the slot's `confirmed_flush_lsn` run ahead of
+    /// the checkpoint. Older writers confirmed the server's WAL end in
+    /// keepalive replies, so their slot is normally ahead of the checkpoint.
+    /// Not part of the format: older binaries read such checkpoints as is
+    pub slot_follows_checkpoint: bool,
+}
+
+/// Opens the store `CHECKPOINT_STORE` names. The PostgreSQL store connects
+/// over `session`.
+pub async fn open(config: &Config, session: &PgSession) -> Result<Box<dyn CheckpointStore>> {
+    Ok(match &config.checkpoint_store {
+        CheckpointStoreTarget::Postgres => {
+            Box::new(StateStore::new(session, &config.database_url).await?)
+        }
+        CheckpointStoreTarget::File { dir } => Box::new(DocumentStore::file(dir)?),
+        CheckpointStoreTarget::S3(location) => Box::new(DocumentStore::s3(
+            location.clone(),
+            config.sink.request_timeout,
+            &config.sink.http,
+        )?),
+    })
+}
+
+/// The document kept per slot by the file and S3 stores
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredCheckpoint {
+    #[serde(flatten)]
+    stamp: FormatStamp,
+    /// See [`CheckpointStamp::slot_follows_checkpoint`]
+    #[serde(default)]
+    slot_follows_checkpoint: bool,
+    slot: String,
+    lsn: u64,
+    updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schema_cache: Option<serde_json::Value>,
+}
+
+enum Location {
+    /// `<dir>/<slot>.json`, replaced through a temporary file
+    File { dir: PathBuf },
+    /// `<prefix>/<slot>.json`
+    S3(S3Client),
+}
+
+/// Checkpoint store keeping one JSON document per slot, in a local directory
+/// (`CHECKPOINT_STORE=file`) or under an S3 prefix (`CHECKPOINT_STORE=s3`).
+pub struct DocumentStore {
+    location: Location,
+    /// Last document written or read, so a save doesn't lose the other half
+    current: Mutex<Option<StoredCheckpoint>>,
+    /// Schema version last written by `save_relations`
+    saved_schema_version: AtomicU64,
+}
+
+impl DocumentStore {
+    fn new(location: Location) -> Self {
+        Self {
+            location,
+            current: Mutex::new(None),
+            saved_schema_version: AtomicU64::new(0),
+        }
+    }
+
+    /// Store in the local directory `dir`, created if missing.
+    pub fn file(dir: &str) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create checkpoint directory {}", dir))?;
+        Ok(Self::new(Location::File {
+            dir: PathBuf::from(dir),
+        }))
+    }
+
+    /// Store under an S3 prefix. `request_timeout` bounds each request, and
+    /// requests go through the sink's proxy.
+    pub fn s3(
+        location: S3Location,
+        request_timeout: Duration,
+        http: &HttpClientConfig,
+    ) -> Result<Self> {
+        Ok(Self::new(Location::S3(S3Client::new(
+            location,
+            request_timeout,
+            http,
+        )?)))
+    }
+
+    fn name(slot: &str) -> String {
+        format!("{}.json", slot)
+    }
+
+    async fn read(&self, slot: &str) -> Result<Option<StoredCheckpoint>> {
+        let bytes = match &self.location {
+            Location::File { dir } => {
+                let path = dir.join(Self::name(slot));
+                match tokio::fs::read(&path).await {
+                    Ok(bytes) => bytes,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                    Err(e) => {
+                        return Err(e).with_context(|| format!("Failed to read {}", path.display()))
+                    }
+                }
+            }
+            Location::S3(client) => {
+                match client.get_object(&client.key(&Self::name(slot))).await? {
+                    Some(bytes) => bytes,
+                    None => return Ok(None),
+                }
+            }
+        };
+        let unreadable = || format!("Unreadable checkpoint document for slot {}", slot);
+        let document: serde_json::Value =
+            serde_json::from_slice(&bytes).with_context(unreadable)?;
+        FormatStamp::deserialize(&document)
+            .with_context(unreadable)?
+            .check(&format!("Checkpoint of slot {}", slot), CHECKPOINT_FORMAT)?;
+        let checkpoint = serde_json::from_value(document).with_context(unreadable)?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Writes the document, stamped with this binary's format.
+    async fn write(&self, checkpoint: &mut StoredCheckpoint) -> Result<()> {
+        checkpoint.stamp = FormatStamp::current(CHECKPOINT_FORMAT);
+        checkpoint.slot_follows_checkpoint = true;
+        let body = serde_json::to_vec_pretty(checkpoint)?;
+        match &self.location {
+            Location::File { dir } => {
+                // A crash mid-write leaves the previous checkpoint in place
+                let path = dir.join(Self::name(&checkpoint.slot));
+                let tmp = dir.join(format!("{}.tmp", Self::name(&checkpoint.slot)));
+                let file = tokio::fs::File::create(&tmp).await;
+                let mut file =
+                    file.with_context(|| format!("Failed to create {}", tmp.display()))?;
+                tokio::io::AsyncWriteExt::write_all(&mut file, &body)
+                    .await
+                    .with_context(|| format!("Failed to write {}", tmp.display()))?;
+                file.sync_all()
+                    .await
+                    .with_context(|| format!("Failed to sync {}", tmp.display()))?;
+                tokio::fs::rename(&tmp, &path)
+                    .await
+                    .with_context(|| format!("Failed to replace {}", path.display()))?;
+            }
+            Location::S3(client) => {
+                client
+                    .put_object(
+                        &client.key(&Self::name(&checkpoint.slot)),
+                        body,
+                        "application/json",
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The slot's document as last written or read, reading it on first use.
+    async fn current(&self, slot: &str) -> Result<Option<StoredCheckpoint>> {
+        let mut current = self.current.lock().await;
+        if current.as_ref().map_or(true, |c| c.slot != slot) {
+            *current = self.read(slot).await?;
+        }
+        Ok(current.clone())
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for DocumentStore {
+    async fn save_checkpoint(&self, slot: &str, lsn: u64) -> Result<()> {
+        let mut checkpoint = self
+            .current(slot)
+            .await?
+            .unwrap_or_else(|| StoredCheckpoint {
+                stamp: FormatStamp::default(),
+                slot_follows_checkpoint: false,
+                slot: slot.to_string(),
+                lsn,
+                updated_at: Utc::now(),
+                schema_cache: None,
+            });
+        checkpoint.lsn = lsn;
+        checkpoint.updated_at = Utc::now();
+        self.write(&mut checkpoint).await?;
+        *self.current.lock().await = Some(checkpoint);
+        Ok(())
+    }
+
+    async fn load_checkpoint(&self, slot: &str) -> Result<Option<u64>> {
+        Ok(self.current(slot).await?.map(|c| c.lsn))
+    }
+
+    async fn checkpoint_stamp(&self, slot: &str) -> Result<Option<CheckpointStamp>> {
+        Ok(self.current(slot).await?.map(|c| CheckpointStamp {
+            stamp: c.stamp,
+            slot_follows_checkpoint: c.slot_follows_checkpoint,
+        }))
+    }
+
+    /// Does nothing until the slot has a checkpoint.
+    async fn save_relations(
+        &self,
+        slot: &str,
+        version: u64,
+        relations: &[RelationVersion],
+    ) -> Result<()> {
+        if relations.is_empty() || self.saved_schema_version.load(Ordering::Relaxed) == version {
+            return Ok(());
+        }
+        let Some(mut checkpoint) = self.current(slot).await? else {
+            return Ok(());
+        };
+        checkpoint.schema_cache = Some(encode_relations(relations)?);
+        self.write(&mut checkpoint).await?;
+        *self.current.lock().await = Some(checkpoint);
+        self.saved_schema_version.store(version, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn load_relations(&self, slot: &str) -> Result<Vec<RelationVersion>> {
+        let Some(cache) = self.current(slot).await?.and_then(|c| c.schema_cache) else {
+            return Ok(Vec::new());
+        };
+        relations_stamp(&cache)
+            .check(&format!("Schema cache of slot {}", slot), CHECKPOINT_FORMAT)?;
+        match decode_relations(cache) {
+            Ok(relations) => Ok(relations),
+            Err(e) => {
+                warn!("Ignoring unreadable schema cache for slot {}: {}", slot, e);
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::{CdcMessage, Column};
+    use std::sync::Arc;
+
+    fn relation() -> RelationVersion {
+        RelationVersion::initial(CdcMessage::Relation {
+            id: 16384,
+            namespace: Arc::from("public"),
+            name: Arc::from("orders"),
+            replica_identity: b'd',
+            columns: vec![Column {
+                flags: 1,
+                name: Arc::from("id"),
+                type_id: 23,
+                type_mod: -1,
+            }],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_file_store_survives_a_new_process() {
+        let dir =
+            std::env::temp_dir().join(format!("dbmazz-checkpoint-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir = dir.to_str().unwrap();
+
+        let store = DocumentStore::file(dir).unwrap();
+        assert_eq!(store.load_checkpoint("dbmazz_slot").await.unwrap(), None);
+        // Nothing to attach the schema cache to yet
+        store
+            .save_relations("dbmazz_slot", 1, &[relation()])
+            .await
+            .unwrap();
+        assert!(store
+            .load_relations("dbmazz_slot")
+            .await
+            .unwrap()
+            .is_empty());
+
+        store.save_checkpoint("dbmazz_slot", 0x1000).await.unwrap();
+        store
+            .save_relations("dbmazz_slot", 2, &[relation()])
+            .await
+            .unwrap();
+        store.save_checkpoint("dbmazz_slot", 0x2000).await.unwrap();
+
+        let reopened = DocumentStore::file(dir).unwrap();
+        assert_eq!(
+            reopened.load_checkpoint("dbmazz_slot").await.unwrap(),
+            Some(0x2000)
+        );
+        assert_eq!(
+            reopened.load_relations("dbmazz_slot").await.unwrap().len(),
+            1
+        );
+        assert_eq!(reopened.load_checkpoint("other_slot").await.unwrap(), None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_newer_checkpoint_format_is_refused() {
+        let dir = std::env::temp_dir().join(format!(
+            "dbmazz-checkpoint-format-test-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // Written before formats were versioned
+        std::fs::write(
+            dir.join("old_slot.json"),
+            r#"{"slot":"old_slot","lsn":4096,"updated_at":"2025-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("new_slot.json"),
+            format!(
+                r#"{{"format":{},"written_by":"9.0.0","slot":"new_slot","lsn":8192}}"#,
+                CHECKPOINT_FORMAT + 1
+            ),
+        )
+        .unwrap();
+
+        let store = DocumentStore::file(dir.to_str().unwrap()).unwrap();
+        assert_eq!(store.load_checkpoint("old_slot").await.unwrap(), Some(4096));
+        assert_eq!(
+            store.checkpoint_stamp("old_slot").await.unwrap(),
+            Some(CheckpointStamp::default())
+        );
+        store.save_checkpoint("old_slot", 8192).await.unwrap();
+        let saved = std::fs::read_to_string(dir.join("old_slot.json")).unwrap();
+        assert!(saved.contains(&format!("\"format\": {}", CHECKPOINT_FORMAT)));
+        assert!(saved.contains("\"slot_follows_checkpoint\": true"));
+        assert_eq!(
+            store.checkpoint_stamp("old_slot").await.unwrap(),
+            Some(CheckpointStamp {
+                stamp: FormatStamp::current(CHECKPOINT_FORMAT),
+                slot_follows_checkpoint: true,
+            })
+        );
+
+        let err = store.load_checkpoint("new_slot").await.unwrap_err();
+        assert!(err.to_string().contains("written by dbmazz 9.0.0"));
+        // Nor is it overwritten
+        assert!(store.save_checkpoint("new_slot", 1).await.is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -16,5 +16,7 @@
 //! # Sinks
 //! - `starrocks` - StarRocks via Stream Load HTTP API
 
+pub(crate) mod http;
+pub(crate) mod s3;
 pub mod sinks;
 pub mod sources;
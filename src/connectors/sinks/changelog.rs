@@ -0,0 +1,376 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Changelog tables of the Delta and object store sinks
+//!
+//! Both sinks write every change as a row of its table's changelog, with
+//! the CDC columns `_op`, `_lsn` and `_ts` appended, and encode the rows as
+//! Arrow batches the same way. Column types are the Iceberg sink's, whose
+//! conversions build the arrays.
+
+use anyhow::{Context, Result};
+use arrow_array::{ArrayRef, RecordBatch, TimestampMicrosecondArray};
+use arrow_schema::{DataType as ArrowType, SchemaRef, TimeUnit};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::connectors::sinks::iceberg::types::IcebergType;
+use crate::core::dead_letter::RejectedData;
+use crate::core::sink_error::SinkError;
+use crate::core::{CdcRecord, ColumnValue, SourcePosition, Value};
+
+/// CDC columns added to all tables
+pub(crate) const CDC_COLUMNS: &[(&str, IcebergType, &str)] = &[
+    (
+        "_op",
+        IcebergType::String,
+        "Change type: I insert, U update, D delete",
+    ),
+    ("_lsn", IcebergType::Long, "Source LSN of the change"),
+    (
+        "_ts",
+        IcebergType::TimestampTz,
+        "Timestamp when the change was written",
+    ),
+];
+
+/// Column type of a table column read as Arrow. `None` for types the sink
+/// can't write (nested types, small integers, other time units).
+pub(crate) fn from_arrow(arrow_type: &ArrowType) -> Option<IcebergType> {
+    Some(match arrow_type {
+        ArrowType::Boolean => IcebergType::Boolean,
+        ArrowType::Int32 => IcebergType::Int,
+        ArrowType::Int64 => IcebergType::Long,
+        ArrowType::Float32 => IcebergType::Float,
+        ArrowType::Float64 => IcebergType::Double,
+        ArrowType::Decimal128(precision, scale) => IcebergType::Decimal {
+            precision: *precision,
+            scale: u8::try_from(*scale).ok()?,
+        },
+        ArrowType::Date32 => IcebergType::Date,
+        ArrowType::Timestamp(TimeUnit::Microsecond, None) => IcebergType::Timestamp,
+        ArrowType::Timestamp(TimeUnit::Microsecond, Some(_)) => IcebergType::TimestampTz,
+        ArrowType::Utf8 => IcebergType::String,
+        ArrowType::Binary => IcebergType::Binary,
+        _ => return None,
+    })
+}
+
+/// Builds the column `name`, of Arrow type `arrow_type`, from its values.
+/// Fails on a value the type can't hold, naming the column.
+pub(crate) fn to_array<'a>(
+    arrow_type: &ArrowType,
+    name: &str,
+    values: impl ExactSizeIterator<Item = &'a Value>,
+) -> Result<ArrayRef> {
+    let column_type = from_arrow(arrow_type)
+        .with_context(|| format!("Column {} has unsupported type {}", name, arrow_type))?;
+    let array = column_type.to_array(name, values)?;
+    // The table's time zone (delta-rs reads `timestamp` as UTC), not the
+    // `+00:00` Iceberg files are written with
+    Ok(match arrow_type {
+        ArrowType::Timestamp(_, Some(tz)) => {
+            let timestamps = array
+                .as_any()
+                .downcast_ref::<TimestampMicrosecondArray>()
+                .context("Timestamp column built as another type")?;
+            Arc::new(timestamps.clone().with_timezone(tz.clone()))
+        }
+        _ => array,
+    })
+}
+
+/// Columns of a changelog table, as read from the table or file schema.
+#[derive(Debug, Clone)]
+pub(crate) struct Layout {
+    /// Sink named in errors
+    sink: &'static str,
+    schema: SchemaRef,
+    /// Index of each column name
+    index: HashMap<String, usize>,
+    op: Option<usize>,
+    lsn: Option<usize>,
+    ts: Option<usize>,
+}
+
+impl Layout {
+    pub(crate) fn new(schema: SchemaRef, sink: &'static str) -> Result<Self> {
+        for field in schema.fields() {
+            if from_arrow(field.data_type()).is_none() {
+                return Err(SinkError::terminal(
+                    sink,
+                    format!(
+                        "column {} has type {}, which the sink doesn't write",
+                        field.name(),
+                        field.data_type()
+                    ),
+                )
+                .into());
+            }
+        }
+        let index: HashMap<String, usize> = schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| (field.name().clone(), i))
+            .collect();
+        Ok(Self {
+            op: index.get(CDC_COLUMNS[0].0).copied(),
+            lsn: index.get(CDC_COLUMNS[1].0).copied(),
+            ts: index.get(CDC_COLUMNS[2].0).copied(),
+            sink,
+            schema,
+            index,
+        })
+    }
+
+    /// A row of the table from a change's columns. Columns the table lacks
+    /// are a schema mismatch; columns the change lacks are NULL.
+    fn row(&self, columns: &[ColumnValue], table: &str) -> Result<Vec<Value>> {
+        let mut row = vec![Value::Null; self.index.len()];
+        for column in columns {
+            match self.index.get(&*column.name) {
+                Some(&i) => row[i] = column.value.clone(),
+                None => {
+                    return Err(SinkError::schema_mismatch(
+                        self.sink,
+                        format!("table {} has no column {}", table, column.name),
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(row)
+    }
+
+    fn stamp(&self, row: &mut [Value], op: &str, synced_at: i64, position: &SourcePosition) {
+        if let Some(i) = self.op {
+            row[i] = Value::String(op.to_string());
+        }
+        if let (Some(i), SourcePosition::Lsn(lsn)) = (self.lsn, position) {
+            row[i] = Value::Int64(*lsn as i64);
+        }
+        if let Some(i) = self.ts {
+            row[i] = Value::Timestamp(synced_at);
+        }
+    }
+
+    /// The changelog rows of `records`, as one Arrow batch.
+    pub(crate) fn encode(
+        &self,
+        records: &[CdcRecord],
+        synced_at: i64,
+        table: &str,
+    ) -> Result<RecordBatch> {
+        let mut rows = Vec::with_capacity(records.len());
+        for record in records {
+            let row = match record {
+                CdcRecord::Insert {
+                    columns, position, ..
+                } => {
+                    let mut row = self.row(columns, table)?;
+                    self.stamp(&mut row, "I", synced_at, position);
+                    row
+                }
+                CdcRecord::Update {
+                    old_columns,
+                    new_columns,
+                    position,
+                    ..
+                } => {
+                    let old = old_columns
+                        .as_deref()
+                        .map(|old| self.row(old, table))
+                        .transpose()?;
+                    let mut row = self.row(new_columns, table)?;
+                    self.fill_unchanged(&mut row, old.as_deref(), table)?;
+                    self.stamp(&mut row, "U", synced_at, position);
+                    row
+                }
+                CdcRecord::Delete {
+                    columns, position, ..
+                } => {
+                    let mut row = self.row(columns, table)?;
+                    self.stamp(&mut row, "D", synced_at, position);
+                    row
+                }
+                _ => continue,
+            };
+            rows.push(row);
+        }
+
+        let arrays = self
+            .schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                to_array(
+                    field.data_type(),
+                    field.name(),
+                    rows.iter().map(|row| &row[i]),
+                )
+                .map_err(|e| anyhow::Error::new(RejectedData::new(self.sink, format!("{:#}", e))))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // Fails on NULL in a non-nullable column
+        RecordBatch::try_new(self.schema.clone(), arrays)
+            .map_err(|e| anyhow::Error::new(RejectedData::new(self.sink, e.to_string())))
+    }
+
+    /// Replaces unchanged TOAST values with the old row image's.
+    fn fill_unchanged(&self, row: &mut [Value], old: Option<&[Value]>, table: &str) -> Result<()> {
+        for (i, value) in row.iter_mut().enumerate() {
+            if !value.is_unchanged() {
+                continue;
+            }
+            // A key-only old image has NULL in every other column, so only
+            // non-NULL old values are known to be the stored ones
+            match old
+                .map(|old| &old[i])
+                .filter(|v| !v.is_null() && !v.is_unchanged())
+            {
+                Some(known) => *value = known.clone(),
+                None => {
+                    return Err(SinkError::terminal(
+                        self.sink,
+                        format!(
+                            "unchanged TOAST value of {}.{} isn't in the change; \
+                             set REPLICA IDENTITY FULL on the source table",
+                            table,
+                            self.schema.field(i).name()
+                        ),
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::TableRef;
+    use arrow_array::{Array, Int64Array, StringArray};
+    use arrow_schema::{Field, Schema};
+
+    fn layout() -> Layout {
+        let schema = Schema::new(vec![
+            Field::new("id", ArrowType::Int64, true),
+            Field::new("body", ArrowType::Utf8, true),
+            Field::new("_op", ArrowType::Utf8, true),
+            Field::new("_lsn", ArrowType::Int64, true),
+            Field::new(
+                "_ts",
+                ArrowType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                true,
+            ),
+        ]);
+        Layout::new(Arc::new(schema), "delta").unwrap()
+    }
+
+    fn columns(id: i64, body: Value) -> Vec<ColumnValue> {
+        vec![
+            ColumnValue::new("id".into(), Value::Int64(id)),
+            ColumnValue::new("body".into(), body),
+        ]
+    }
+
+    fn table() -> TableRef {
+        TableRef::new(Some("public".into()), "docs".into())
+    }
+
+    #[test]
+    fn test_changelog_rows() {
+        let records = vec![
+            CdcRecord::Insert {
+                table: table(),
+                columns: columns(1, Value::String("a".into())),
+                position: SourcePosition::Lsn(0x10),
+            },
+            CdcRecord::Update {
+                table: table(),
+                old_columns: Some(columns(1, Value::String("a".into()))),
+                new_columns: columns(1, Value::Unchanged),
+                position: SourcePosition::Lsn(0x20),
+            },
+            CdcRecord::Commit {
+                xid: 7,
+                position: SourcePosition::Lsn(0x28),
+            },
+            CdcRecord::Delete {
+                table: table(),
+                columns: columns(1, Value::Null),
+                position: SourcePosition::Lsn(0x30),
+            },
+        ];
+        let batch = layout().encode(&records, 1_000, "sales.docs").unwrap();
+        assert_eq!(batch.num_rows(), 3);
+
+        let ops = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            (0..3).map(|i| ops.value(i)).collect::<Vec<_>>(),
+            ["I", "U", "D"]
+        );
+        let lsns = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(lsns.value(2), 0x30);
+        // The unchanged TOAST body comes from the old image
+        let bodies = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(bodies.value(1), "a");
+        assert!(bodies.is_null(2));
+    }
+
+    #[test]
+    fn test_unknown_toast_and_columns_fail() {
+        let update = CdcRecord::Update {
+            table: table(),
+            old_columns: None,
+            new_columns: columns(3, Value::Unchanged),
+            position: SourcePosition::Lsn(0x20),
+        };
+        let err = layout().encode(&[update], 0, "sales.docs").unwrap_err();
+        assert!(err.to_string().contains("REPLICA IDENTITY FULL"), "{}", err);
+
+        let added = CdcRecord::Insert {
+            table: table(),
+            columns: vec![ColumnValue::new("extra".into(), Value::Int64(1))],
+            position: SourcePosition::Lsn(0x20),
+        };
+        let err = layout().encode(&[added], 0, "sales.docs").unwrap_err();
+        assert!(crate::core::sink_error::is_schema_mismatch(&err));
+
+        let bad = CdcRecord::Insert {
+            table: table(),
+            columns: vec![ColumnValue::new("id".into(), Value::String("x".into()))],
+            position: SourcePosition::Lsn(0x20),
+        };
+        let err = layout().encode(&[bad], 0, "sales.docs").unwrap_err();
+        assert!(err.is::<RejectedData>());
+    }
+
+    #[test]
+    fn test_timestamps_take_the_table_time_zone() {
+        let arrow_type = ArrowType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+        let values = [Value::Timestamp(1_709_294_400_500_000), Value::Null];
+        let array = to_array(&arrow_type, "at", values.iter()).unwrap();
+        assert_eq!(array.data_type(), &arrow_type);
+        assert!(array.is_null(1));
+
+        let values = [Value::String("soon".to_string())];
+        assert!(to_array(&arrow_type, "at", values.iter()).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! # In-Process Channel Sink
+//!
+//! This module implements a sink for embedding dbmazz in another process:
+//! batches are handed to caller-provided code through a tokio channel instead
+//! of being written to a database. The consumer acknowledges each batch, and
+//! the pipeline only advances the checkpoint after a successful ack, so the
+//! delivery guarantees match the built-in sinks.
+//!
+//! Two ways to consume batches:
+//!
+//! ```rust,ignore
+//! // 1. Receive batches from a channel
+//! let (sink, mut batches) = ChannelSink::channel(16);
+//! tokio::spawn(async move {
+//!     while let Some(batch) = batches.recv().await {
+//!         let result = write_somewhere(&batch.records).await;
+//!         batch.ack(result);
+//!     }
+//! });
+//!
+//! // 2. Let dbmazz call an async function per batch
+//! let sink = ChannelSink::from_fn(|records| async move { write_somewhere(&records).await });
+//!
+//! dbmazz::CdcEngine::new(config).with_sink(Box::new(sink)).run().await?;
+//! ```
+//!
+//! For a consumer in a separate process on the same host, use the remote sink
+//! with a `unix://` socket URL instead.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::future::Future;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::core::{CdcRecord, LoadingModel, Sink, SinkCapabilities, SinkResult};
+
+/// A batch handed to the consumer, with the handle used to acknowledge it.
+pub struct ChannelBatch {
+    pub records: Vec<CdcRecord>,
+    ack: oneshot::Sender<Result<()>>,
+}
+
+impl ChannelBatch {
+    /// Acknowledges the batch. An `Err` fails the flush, which stops the
+    /// pipeline without advancing the checkpoint.
+    pub fn ack(self, result: Result<()>) {
+        // The sink may have given up waiting (pipeline shutdown); nothing to do then
+        let _ = self.ack.send(result);
+    }
+}
+
+/// Sink that forwards batches to an in-process consumer.
+pub struct ChannelSink {
+    tx: mpsc::Sender<ChannelBatch>,
+}
+
+impl ChannelSink {
+    /// Wraps an existing sender. The consumer must `ack` every batch.
+    pub fn new(tx: mpsc::Sender<ChannelBatch>) -> Self {
+        Self { tx }
+    }
+
+    /// Creates a sink together with the receiving end of its channel.
+    /// `capacity` bounds how many unacked batches can queue up (the pipeline
+    /// sends one at a time, so 1 is enough unless the consumer batches acks).
+    pub fn channel(capacity: usize) -> (Self, mpsc::Receiver<ChannelBatch>) {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        (Self::new(tx), rx)
+    }
+
+    /// Creates a sink that calls `handler` for every batch on a dedicated
+    /// task. Must be called from within a tokio runtime.
+    pub fn from_fn<F, Fut>(mut handler: F) -> Self
+    where
+        F: FnMut(Vec<CdcRecord>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send,
+    {
+        let (sink, mut rx) = Self::channel(1);
+        tokio::spawn(async move {
+            while let Some(batch) = rx.recv().await {
+                let ChannelBatch { records, ack } = batch;
+                let result = handler(records).await;
+                let _ = ack.send(result);
+            }
+        });
+        sink
+    }
+}
+
+#[async_trait]
+impl Sink for ChannelSink {
+    fn name(&self) -> &'static str {
+        "channel"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            supports_upsert: true,
+            supports_delete: true,
+            supports_schema_evolution: true,
+            supports_transactions: false,
+            loading_model: LoadingModel::Streaming,
+            min_batch_size: Some(1),
+            max_batch_size: None,
+            optimal_flush_interval_ms: 1000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        if self.tx.is_closed() {
+            return Err(anyhow!("channel sink consumer has been dropped"));
+        }
+        Ok(())
+    }
+
+    async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult> {
+        let records_written = records.len();
+        let last_position = records.iter().rev().find_map(|r| match r {
+            CdcRecord::Insert { position, .. }
+            | CdcRecord::Update { position, .. }
+            | CdcRecord::Delete { position, .. }
+            | CdcRecord::Commit { position, .. }
+            | CdcRecord::Heartbeat { position, .. } => Some(position.clone()),
+            _ => None,
+        });
+
+        let (ack_tx, ack_rx) = oneshot::channel();
+        self.tx
+            .send(ChannelBatch {
+                records,
+                ack: ack_tx,
+            })
+            .await
+            .map_err(|_| anyhow!("channel sink consumer has been dropped"))?;
+
+        // A dropped batch without an ack counts as a failure
+        ack_rx
+            .await
+            .map_err(|_| anyhow!("channel sink consumer dropped the batch without acking"))??;
+
+        Ok(SinkResult {
+            records_written,
+            bytes_written: 0,
+            last_position,
+            ..Default::default()
+        })
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ColumnValue, SourcePosition, TableRef, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn insert(lsn: u64) -> CdcRecord {
+        CdcRecord::Insert {
+            table: TableRef::new(None, "orders".into()),
+            columns: vec![ColumnValue::new("id".into(), Value::Int64(1))],
+            position: SourcePosition::Lsn(lsn),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_channel_consumer_acks() {
+        let (mut sink, mut rx) = ChannelSink::channel(1);
+        tokio::spawn(async move {
+            while let Some(batch) = rx.recv().await {
+                assert_eq!(batch.records.len(), 2);
+                batch.ack(Ok(()));
+            }
+        });
+
+        let result = sink.write_batch(vec![insert(1), insert(2)]).await.unwrap();
+        assert_eq!(result.records_written, 2);
+        assert!(matches!(result.last_position, Some(SourcePosition::Lsn(2))));
+    }
+
+    #[tokio::test]
+    async fn test_consumer_error_fails_batch() {
+        let (mut sink, mut rx) = ChannelSink::channel(1);
+        tokio::spawn(async move {
+            if let Some(batch) = rx.recv().await {
+                batch.ack(Err(anyhow!("disk full")));
+            }
+        });
+
+        let err = sink.write_batch(vec![insert(1)]).await.unwrap_err();
+        assert!(err.to_string().contains("disk full"));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_consumer_fails_batch() {
+        let (mut sink, rx) = ChannelSink::channel(1);
+        drop(rx);
+
+        assert!(sink.validate_connection().await.is_err());
+        assert!(sink.write_batch(vec![insert(1)]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_from_fn() {
+        let seen = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&seen);
+        let mut sink = ChannelSink::from_fn(move |records| {
+            let counter = Arc::clone(&counter);
+            async move {
+                counter.fetch_add(records.len(), Ordering::SeqCst);
+                Ok(())
+            }
+        });
+
+        sink.write_batch(vec![insert(1), insert(2)]).await.unwrap();
+        sink.write_batch(vec![insert(3)]).await.unwrap();
+        assert_eq!(seen.load(Ordering::SeqCst), 3);
+    }
+}
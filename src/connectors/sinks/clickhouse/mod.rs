@@ -0,0 +1,1266 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! # ClickHouse Sink Connector
+//!
+//! This module implements a CDC sink for ClickHouse over its HTTP interface
+//! (port 8123). Every change is appended as a row version, and the
+//! ReplacingMergeTree engine collapses versions of the same key into the
+//! latest one.
+//!
+//! ## Features
+//!
+//! - **Upserts by key**: tables keyed by the source primary key use
+//!   `ReplacingMergeTree(dbmazz_cdc_version, dbmazz_is_deleted)` ordered by
+//!   that key, so the row with the highest LSN wins
+//! - **Soft deletes**: a delete is a new version with `dbmazz_is_deleted = 1`;
+//!   `SELECT ... FINAL` hides it and merges eventually drop it
+//! - **TOAST handling**: unchanged TOAST columns are filled from the old row
+//!   image, from an earlier change to the same key in the batch, or read back
+//!   from the stored row
+//! - **Schema evolution**: added source columns become
+//!   `ALTER TABLE ADD COLUMN IF NOT EXISTS`; dropped, renamed and retyped
+//!   ones are dropped, renamed, converted or commented as deprecated as
+//!   `SCHEMA_*_COLUMNS` say
+//! - **Checkpoint table**: with `SINK_CHECKPOINT_TABLE=true` the LSN of each
+//!   batch is inserted into `_dbmazz_checkpoints` after its rows
+//! - **Rejected rows**: inserts failing on a value (parse, type or range
+//!   errors) are reported as rejected data, and with
+//!   `DEAD_LETTER_TARGET=table` the rows go to `_dbmazz_dead_letters`
+//!
+//! ## Batches
+//!
+//! ReplacingMergeTree can't order versions that share an LSN, and every
+//! record of a batch carries the batch LSN, so a batch keeps only the last
+//! image of each key. Each table gets one `INSERT ... FORMAT JSONEachRow`
+//! per batch. Tables without a key use plain MergeTree and keep every change.
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use dbmazz::connectors::sinks::clickhouse::ClickHouseSink;
+//!
+//! // SINK_TYPE=clickhouse SINK_URL=http://clickhouse:8123 SINK_DATABASE=cdc
+//! let mut sink = ClickHouseSink::new(&config)?;
+//! sink.write_batch(records).await?;
+//! ```
+
+mod client;
+pub(crate) mod types;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::Utc;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::{is_internal_table, CHECKPOINT_TABLE, DEAD_LETTER_TABLE};
+use crate::config::{
+    BoolEncoding, ColumnChangePolicy, SchemaEvolution, SinkConfig, SinkTableName, TableNaming,
+};
+use crate::core::dead_letter::DeadLetter;
+use crate::core::schema_diff::SchemaDiff;
+use crate::core::sink_error::is_retryable;
+use crate::core::{
+    pool, CdcRecord, ColumnValue, LoadingModel, Sink, SinkCapabilities, SinkResult, SourcePosition,
+};
+use crate::pipeline::schema_cache::SchemaDelta;
+
+pub(crate) use self::client::ClickHouseClient;
+use self::types::{column_type, pg_type_to_clickhouse, value_to_json};
+
+type JsonRow = serde_json::Map<String, serde_json::Value>;
+
+/// Key columns of each sink table, by `SinkTableName::label`
+type KeyMap = HashMap<String, Arc<Vec<String>>>;
+
+/// CDC audit columns added to all tables
+pub(crate) const AUDIT_COLUMNS: &[(&str, &str)] = &[
+    (
+        "dbmazz_op_type",
+        "UInt8 COMMENT '0=INSERT, 1=UPDATE, 2=DELETE'",
+    ),
+    ("dbmazz_is_deleted", "UInt8 COMMENT 'Soft delete flag'"),
+    (
+        "dbmazz_synced_at",
+        "DateTime('UTC') COMMENT 'Timestamp when record was synced'",
+    ),
+    (
+        "dbmazz_cdc_version",
+        "UInt64 COMMENT 'Source LSN/position for ordering'",
+    ),
+];
+
+/// ClickHouse sink connector implementing the Sink trait.
+pub struct ClickHouseSink {
+    client: ClickHouseClient,
+    /// How source tables map to ClickHouse databases and tables
+    naming: TableNaming,
+    /// Column type of booleans added by schema deltas
+    bool_encoding: BoolEncoding,
+    /// What happens to dropped, renamed and retyped columns
+    schema_evolution: SchemaEvolution,
+    /// Sorting key of each table, read on its first write
+    keys: Mutex<KeyMap>,
+    /// Slot whose LSN is written to `_dbmazz_checkpoints` after each batch
+    checkpoint_slot: Option<String>,
+    checkpoint_table_ready: AtomicBool,
+    dead_letter_table_ready: AtomicBool,
+}
+
+impl ClickHouseSink {
+    /// Creates a new ClickHouse sink from the provided configuration.
+    ///
+    /// `SINK_URL` is the HTTP interface (`http://host:8123`) and
+    /// `SINK_DATABASE` the database of the replicated tables.
+    pub fn new(config: &SinkConfig) -> Result<Self> {
+        let client = ClickHouseClient::new(config)?;
+
+        info!("ClickHouseSink initialized:");
+        info!("  HTTP URL: {}", client.url());
+        info!("  Database: {}", client.database());
+
+        Ok(Self {
+            client,
+            naming: config.naming.clone(),
+            bool_encoding: config.bool_encoding,
+            schema_evolution: config.schema_evolution,
+            keys: Mutex::new(HashMap::new()),
+            checkpoint_slot: config.checkpoint_slot.clone(),
+            checkpoint_table_ready: AtomicBool::new(false),
+            dead_letter_table_ready: AtomicBool::new(false),
+        })
+    }
+
+    fn table_ident(&self, table: &SinkTableName) -> String {
+        qualified_table(table, self.client.database())
+    }
+
+    /// Key columns of `tables`, reading the sorting key of tables not seen
+    /// before from `system.columns`.
+    async fn table_keys(&self, tables: &[SinkTableName]) -> Result<KeyMap> {
+        let mut keys = self.keys.lock().clone();
+        for table in tables {
+            let label = table.label();
+            if keys.contains_key(&label) {
+                continue;
+            }
+            let database = table.database.as_deref().unwrap_or(self.client.database());
+            let columns = self
+                .client
+                .query_rows(
+                    "SELECT name, is_in_sorting_key FROM system.columns \
+                     WHERE database = {database:String} AND table = {table:String} \
+                     ORDER BY position",
+                    &[
+                        ("database".to_string(), database.to_string()),
+                        ("table".to_string(), table.table.clone()),
+                    ],
+                )
+                .await
+                .with_context(|| format!("Failed to read columns of ClickHouse table {}", label))?;
+            if columns.is_empty() {
+                anyhow::bail!(
+                    "ClickHouse table {} not found; tables are created by the setup phase",
+                    label
+                );
+            }
+            let key: Vec<String> = columns
+                .iter()
+                .filter(|c| c.get("is_in_sorting_key").and_then(|v| v.as_u64()) == Some(1))
+                .filter_map(|c| c.get("name").and_then(|v| v.as_str()).map(String::from))
+                .collect();
+            let key = Arc::new(key);
+            self.keys.lock().insert(label.clone(), key.clone());
+            keys.insert(label, key);
+        }
+        Ok(keys)
+    }
+
+    /// Reads the unchanged TOAST columns that the batch couldn't fill from
+    /// the stored rows.
+    async fn resolve_unchanged(&self, batch: &mut TableRows) -> Result<()> {
+        let table = self.table_ident(&batch.table);
+        for row in &mut batch.rows {
+            let Some(missing) = row.missing.take() else {
+                continue;
+            };
+            let mut predicate = Vec::with_capacity(missing.key.len());
+            let mut params = Vec::with_capacity(missing.key.len());
+            for (i, (name, value)) in missing.key.iter().enumerate() {
+                // Compared as text so the parameter type doesn't depend on the key type
+                predicate.push(format!(
+                    "toString({}) = {{k{}:String}}",
+                    quote_ident(name),
+                    i
+                ));
+                params.push((format!("k{}", i), param_text(value)));
+            }
+            let columns: Vec<String> = missing.columns.iter().map(|c| quote_ident(c)).collect();
+            let sql = format!(
+                "SELECT {} FROM {} FINAL WHERE {} LIMIT 1",
+                columns.join(", "),
+                table,
+                predicate.join(" AND ")
+            );
+            let stored = self
+                .client
+                .query_rows(&sql, &params)
+                .await
+                .with_context(|| format!("Failed to read unchanged TOAST columns from {}", table))?
+                .into_iter()
+                .next();
+            if stored.is_none() {
+                warn!(
+                    "Row of {} with unchanged TOAST column(s) {} not found in ClickHouse, writing NULL",
+                    batch.table.label(),
+                    missing.columns.join(", ")
+                );
+            }
+            for name in missing.columns {
+                let value = stored
+                    .as_ref()
+                    .and_then(|s| s.get(&name).cloned())
+                    .unwrap_or(serde_json::Value::Null);
+                row.values.insert(name, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts a table's rows with exponential backoff retry. Retrying is
+    /// safe: a replayed version replaces itself. Rejected rows and terminal
+    /// errors would fail again, so those are returned right away.
+    async fn insert_with_retry(&self, table: &str, body: Bytes, max_retries: u32) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match self.client.insert_json_rows(table, body.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(e) if !is_retryable(&e) => return Err(e),
+                Err(e) => {
+                    attempt += 1;
+                    if attempt >= max_retries {
+                        return Err(e.context(format!("Failed after {} attempts", max_retries)));
+                    }
+                    info!("Retry {}/{} for {}: {}", attempt, max_retries, table, e);
+                    // Exponential backoff: 200ms, 400ms...
+                    tokio::time::sleep(Duration::from_millis(100 * 2_u64.pow(attempt))).await;
+                }
+            }
+        }
+    }
+
+    /// Inserts the checkpoint row of `slot`, creating the table on first use.
+    async fn write_checkpoint(&self, slot: &str, lsn: u64) -> Result<()> {
+        let table = format!(
+            "{}.{}",
+            quote_ident(self.client.database()),
+            quote_ident(CHECKPOINT_TABLE)
+        );
+        if !self.checkpoint_table_ready.load(Ordering::Relaxed) {
+            self.client
+                .execute(&checkpoint_table_sql(&table))
+                .await
+                .context("Failed to create ClickHouse checkpoint table")?;
+            self.checkpoint_table_ready.store(true, Ordering::Relaxed);
+        }
+        let row = serde_json::json!({
+            "slot_name": slot,
+            "lsn": lsn,
+            "updated_at": Utc::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        });
+        let mut body = serde_json::to_vec(&row)?;
+        body.push(b'\n');
+        self.client
+            .insert_json_rows(&table, Bytes::from(body))
+            .await
+            .context("Failed to write ClickHouse checkpoint")
+    }
+}
+
+/// Rows of one batch for one ClickHouse table, at most one per key.
+#[derive(Debug)]
+struct TableRows {
+    table: SinkTableName,
+    rows: Vec<Row>,
+    /// Index in `rows` of the latest image of each key
+    by_key: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+struct Row {
+    values: JsonRow,
+    /// Unchanged TOAST columns to read from the stored row
+    missing: Option<Missing>,
+}
+
+#[derive(Debug)]
+struct Missing {
+    /// Key of the stored row
+    key: Vec<(String, serde_json::Value)>,
+    columns: Vec<String>,
+}
+
+impl Row {
+    fn is_missing(&self, column: &str) -> bool {
+        self.missing
+            .as_ref()
+            .is_some_and(|m| m.columns.iter().any(|c| c == column))
+    }
+}
+
+impl TableRows {
+    fn new(table: SinkTableName) -> Self {
+        Self {
+            table,
+            rows: Vec::new(),
+            by_key: HashMap::new(),
+        }
+    }
+
+    /// Adds a row, replacing the earlier image of the same key.
+    fn push(&mut self, key_columns: &[String], row: Row) {
+        match row_key(&row.values, key_columns) {
+            Some((key, _)) => match self.by_key.get(&key) {
+                Some(&i) => self.rows[i] = row,
+                None => {
+                    self.by_key.insert(key, self.rows.len());
+                    self.rows.push(row);
+                }
+            },
+            None => self.rows.push(row),
+        }
+    }
+
+    fn push_update(
+        &mut self,
+        key_columns: &[String],
+        old_columns: Option<&[ColumnValue]>,
+        new_columns: &[ColumnValue],
+        synced_at: &str,
+        position: &SourcePosition,
+    ) {
+        let mut values = columns_to_json(new_columns);
+        let old = old_columns.map(columns_to_json);
+        let new_key = row_key(&values, key_columns);
+        let old_key = old.as_ref().and_then(|o| row_key(o, key_columns));
+        // The stored row is found by its old key when the key changed
+        let stored_key = old_key.as_ref().or(new_key.as_ref());
+
+        let mut missing = Vec::new();
+        for column in new_columns.iter().filter(|c| c.value.is_unchanged()) {
+            let name = &*column.name;
+            // A key-only old image has NULL in every other column, so only
+            // non-NULL old values are known to be the stored ones
+            if let Some(value) = old
+                .as_ref()
+                .and_then(|o| o.get(name))
+                .filter(|v| !v.is_null())
+            {
+                values.insert(name.to_string(), value.clone());
+                continue;
+            }
+            let earlier = stored_key
+                .and_then(|(key, _)| self.by_key.get(key))
+                .map(|&i| &self.rows[i]);
+            match earlier.and_then(|row| row.values.get(name).filter(|_| !row.is_missing(name))) {
+                Some(value) => {
+                    values.insert(name.to_string(), value.clone());
+                }
+                None => missing.push(name.to_string()),
+            }
+        }
+        let missing = match stored_key {
+            _ if missing.is_empty() => None,
+            Some((_, key)) => Some(Missing {
+                key: key.clone(),
+                columns: missing,
+            }),
+            None => {
+                // Without a key the stored row can't be found
+                for name in missing {
+                    values.insert(name, serde_json::Value::Null);
+                }
+                None
+            }
+        };
+
+        // A changed key moves the row: the version under the old key is deleted
+        if let (Some((old, old_values)), Some((new, _))) = (&old_key, &new_key) {
+            if old != new {
+                let mut deleted: JsonRow = old_values.iter().cloned().collect();
+                add_audit_columns(&mut deleted, 2, true, synced_at, position);
+                self.push(
+                    key_columns,
+                    Row {
+                        values: deleted,
+                        missing: None,
+                    },
+                );
+            }
+        }
+
+        add_audit_columns(&mut values, 1, false, synced_at, position);
+        self.push(key_columns, Row { values, missing });
+    }
+
+    /// `JSONEachRow` body: one JSON object per line.
+    fn to_json_lines(&self) -> Result<Vec<u8>> {
+        let mut body = Vec::with_capacity(self.rows.len() * 128);
+        for row in &self.rows {
+            serde_json::to_writer(&mut body, &row.values)?;
+            body.push(b'\n');
+        }
+        Ok(body)
+    }
+}
+
+/// Groups the row changes of a batch by sink table.
+fn encode(
+    records: &[CdcRecord],
+    keys: &KeyMap,
+    naming: &TableNaming,
+    synced_at: &str,
+) -> Vec<TableRows> {
+    let mut tables: HashMap<String, TableRows> = HashMap::new();
+    for record in records {
+        let table = match record {
+            CdcRecord::Insert { table, .. }
+            | CdcRecord::Update { table, .. }
+            | CdcRecord::Delete { table, .. } => table,
+            // Schema changes arrive as deltas; transactions and heartbeats aren't written
+            _ => continue,
+        };
+        if is_internal_table(&table.name) {
+            continue;
+        }
+        let dest = naming.sink_table(&table.qualified_name());
+        let label = dest.label();
+        let key_columns = keys.get(&label).map(|k| k.as_slice()).unwrap_or_default();
+        let batch = tables.entry(label).or_insert_with(|| TableRows::new(dest));
+
+        match record {
+            CdcRecord::Insert {
+                columns, position, ..
+            } => {
+                let mut values = columns_to_json(columns);
+                add_audit_columns(&mut values, 0, false, synced_at, position);
+                batch.push(
+                    key_columns,
+                    Row {
+                        values,
+                        missing: None,
+                    },
+                );
+            }
+            CdcRecord::Update {
+                old_columns,
+                new_columns,
+                position,
+                ..
+            } => batch.push_update(
+                key_columns,
+                old_columns.as_deref(),
+                new_columns,
+                synced_at,
+                position,
+            ),
+            CdcRecord::Delete {
+                columns, position, ..
+            } => {
+                let mut values = columns_to_json(columns);
+                add_audit_columns(&mut values, 2, true, synced_at, position);
+                batch.push(
+                    key_columns,
+                    Row {
+                        values,
+                        missing: None,
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+    tables.into_values().collect()
+}
+
+/// Converts column values to a JSON row, leaving out unchanged TOAST values.
+fn columns_to_json(columns: &[ColumnValue]) -> JsonRow {
+    columns
+        .iter()
+        .filter(|c| !c.value.is_unchanged())
+        .map(|c| (c.name.to_string(), value_to_json(&c.value)))
+        .collect()
+}
+
+/// Key of a row as a lookup string, with the key values. `None` for tables
+/// without a key and rows that don't carry the whole key.
+fn row_key(
+    values: &JsonRow,
+    key_columns: &[String],
+) -> Option<(String, Vec<(String, serde_json::Value)>)> {
+    if key_columns.is_empty() {
+        return None;
+    }
+    let key: Vec<(String, serde_json::Value)> = key_columns
+        .iter()
+        .map(|k| values.get(k).map(|v| (k.clone(), v.clone())))
+        .collect::<Option<_>>()?;
+    let lookup = serde_json::to_string(&key).ok()?;
+    Some((lookup, key))
+}
+
+/// Adds CDC audit columns to a JSON row.
+fn add_audit_columns(
+    row: &mut JsonRow,
+    op_type: u8,
+    is_deleted: bool,
+    synced_at: &str,
+    position: &SourcePosition,
+) {
+    let version = match position {
+        SourcePosition::Lsn(lsn) => *lsn,
+        SourcePosition::Offset(offset) => *offset as u64,
+        _ => 0,
+    };
+    row.insert("dbmazz_op_type".to_string(), serde_json::json!(op_type));
+    row.insert(
+        "dbmazz_is_deleted".to_string(),
+        serde_json::json!(u8::from(is_deleted)),
+    );
+    row.insert("dbmazz_synced_at".to_string(), serde_json::json!(synced_at));
+    row.insert("dbmazz_cdc_version".to_string(), serde_json::json!(version));
+}
+
+/// Text of a key value as a `String` query parameter, matching what
+/// `toString` returns for the stored column.
+fn param_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// A replicated column as setup and schema deltas create it.
+#[derive(Debug, Clone)]
+pub(crate) struct ClickHouseColumn {
+    pub name: String,
+    pub column_type: String,
+    pub comment: Option<String>,
+}
+
+/// `name Type [COMMENT '...']`
+pub(crate) fn column_def(column: &ClickHouseColumn) -> String {
+    let mut def = format!("{} {}", quote_ident(&column.name), column.column_type);
+    if let Some(comment) = &column.comment {
+        def.push_str(&format!(" COMMENT {}", quote_string(comment)));
+    }
+    def
+}
+
+/// Definitions of the audit columns.
+pub(crate) fn audit_column_defs() -> impl Iterator<Item = String> {
+    AUDIT_COLUMNS
+        .iter()
+        .map(|(name, def)| format!("{} {}", quote_ident(name), def))
+}
+
+/// `CREATE TABLE IF NOT EXISTS` for a replicated table. Keyed tables use
+/// ReplacingMergeTree ordered by the key, the others MergeTree.
+pub(crate) fn create_table_sql(
+    table: &str,
+    columns: &[ClickHouseColumn],
+    key_columns: &[String],
+    comment: Option<&str>,
+) -> String {
+    let defs: Vec<String> = columns
+        .iter()
+        .map(column_def)
+        .chain(audit_column_defs())
+        .collect();
+    let (engine, order_by) = if key_columns.is_empty() {
+        ("MergeTree".to_string(), "tuple()".to_string())
+    } else {
+        let keys: Vec<String> = key_columns.iter().map(|k| quote_ident(k)).collect();
+        (
+            "ReplacingMergeTree(`dbmazz_cdc_version`, `dbmazz_is_deleted`)".to_string(),
+            format!("({})", keys.join(", ")),
+        )
+    };
+    let mut sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n  {}\n) ENGINE = {} ORDER BY {}",
+        table,
+        defs.join(",\n  "),
+        engine,
+        order_by
+    );
+    if let Some(comment) = comment {
+        sql.push_str(&format!(" COMMENT {}", quote_string(comment)));
+    }
+    sql
+}
+
+/// `ALTER TABLE ... ADD COLUMN IF NOT EXISTS` for each column definition.
+pub(crate) fn add_columns_sql(table: &str, defs: &[String]) -> String {
+    let actions: Vec<String> = defs
+        .iter()
+        .map(|def| format!("ADD COLUMN IF NOT EXISTS {}", def))
+        .collect();
+    format!("ALTER TABLE {} {}", table, actions.join(", "))
+}
+
+/// DDL for the columns `delta` drops, renames or retypes, as `evolution`
+/// says: renames and drops, which must be made before columns are added,
+/// then type changes and deprecation comments, which are best effort.
+/// Sorting-key columns can't be changed and are left alone.
+fn column_changes_sql(
+    table: &str,
+    delta: &SchemaDelta,
+    evolution: &SchemaEvolution,
+    key: &[String],
+    bool_encoding: BoolEncoding,
+) -> (Vec<String>, Vec<String>) {
+    let mut required = Vec::new();
+    let mut best_effort = Vec::new();
+    let deprecate = |name: &str, comment: &str| {
+        format!(
+            "ALTER TABLE {} COMMENT COLUMN IF EXISTS {} {}",
+            table,
+            quote_ident(name),
+            quote_string(comment)
+        )
+    };
+    let in_key = |name: &str| {
+        let keyed = key.iter().any(|k| k == name);
+        if keyed {
+            warn!(
+                "[SCHEMA] {}.{} changed in the source; sorting key columns are kept",
+                table, name
+            );
+        }
+        keyed
+    };
+
+    for rename in &delta.renamed_columns {
+        match evolution.renamed {
+            ColumnChangePolicy::Ignore => {}
+            ColumnChangePolicy::Apply if in_key(&rename.old_name) => {}
+            ColumnChangePolicy::Apply => required.push(format!(
+                "ALTER TABLE {} RENAME COLUMN IF EXISTS {} TO {}",
+                table,
+                quote_ident(&rename.old_name),
+                quote_ident(&rename.new_name)
+            )),
+            ColumnChangePolicy::Deprecate => best_effort.push(deprecate(
+                &rename.old_name,
+                &format!("Deprecated: renamed to {} in the source", rename.new_name),
+            )),
+        }
+    }
+    for dropped in &delta.dropped_columns {
+        match evolution.dropped {
+            ColumnChangePolicy::Ignore => {}
+            ColumnChangePolicy::Apply if in_key(&dropped.name) => {}
+            ColumnChangePolicy::Apply => required.push(format!(
+                "ALTER TABLE {} DROP COLUMN IF EXISTS {}",
+                table,
+                quote_ident(&dropped.name)
+            )),
+            ColumnChangePolicy::Deprecate => best_effort.push(deprecate(
+                &dropped.name,
+                "Deprecated: dropped from the source",
+            )),
+        }
+    }
+    for retyped in &delta.retyped_columns {
+        match evolution.retyped {
+            ColumnChangePolicy::Ignore => {}
+            ColumnChangePolicy::Apply if in_key(&retyped.name) => {}
+            ColumnChangePolicy::Apply => best_effort.push(format!(
+                "ALTER TABLE {} MODIFY COLUMN IF EXISTS {} {}",
+                table,
+                quote_ident(&retyped.name),
+                column_type(retyped.pg_type_id, bool_encoding, true)
+            )),
+            ColumnChangePolicy::Deprecate => best_effort.push(deprecate(
+                &retyped.name,
+                &format!(
+                    "Deprecated: the source column is now {}",
+                    pg_type_to_clickhouse(retyped.pg_type_id, bool_encoding)
+                ),
+            )),
+        }
+    }
+    (required, best_effort)
+}
+
+/// DDL of the dead-letter table, `table` being the quoted `db`.`name`.
+fn dead_letter_table_sql(table: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (`failed_at` DateTime64(3, 'UTC'), \
+         `table_name` String, `op` LowCardinality(String), `lsn` UInt64, \
+         `row` String, `old_row` Nullable(String), `error` String) \
+         ENGINE = MergeTree ORDER BY (`table_name`, `failed_at`)",
+        table
+    )
+}
+
+/// `JSONEachRow` line of a dead letter; rows are stored as JSON text.
+fn dead_letter_row(letter: &DeadLetter) -> serde_json::Value {
+    serde_json::json!({
+        "failed_at": letter.failed_at.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        "table_name": letter.table,
+        "op": letter.op,
+        "lsn": letter.lsn,
+        "row": serde_json::Value::Object(letter.row.clone()).to_string(),
+        "old_row": letter
+            .old_row
+            .as_ref()
+            .map(|old| serde_json::Value::Object(old.clone()).to_string()),
+        "error": letter.error,
+    })
+}
+
+fn checkpoint_table_sql(table: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (`slot_name` String, `lsn` UInt64, \
+         `updated_at` DateTime64(3, 'UTC')) \
+         ENGINE = ReplacingMergeTree(`updated_at`) ORDER BY `slot_name`",
+        table
+    )
+}
+
+/// `` `database`.`table` ``, in `default_database` unless the schema mode
+/// names one.
+pub(crate) fn qualified_table(table: &SinkTableName, default_database: &str) -> String {
+    format!(
+        "{}.{}",
+        quote_ident(table.database.as_deref().unwrap_or(default_database)),
+        quote_ident(&table.table)
+    )
+}
+
+/// Quotes a ClickHouse identifier with backticks.
+pub(crate) fn quote_ident(name: &str) -> String {
+    format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`"))
+}
+
+/// Quotes a ClickHouse string literal.
+pub(crate) fn quote_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[async_trait]
+impl Sink for ClickHouseSink {
+    fn name(&self) -> &'static str {
+        "clickhouse"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            supports_upsert: true,
+            supports_delete: true,
+            supports_schema_evolution: true,
+            supports_transactions: false,
+            loading_model: LoadingModel::Streaming,
+            min_batch_size: Some(1),
+            max_batch_size: Some(100_000),
+            optimal_flush_interval_ms: 5000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        self.client
+            .execute("SELECT 1")
+            .await
+            .context("ClickHouse is not reachable")
+    }
+
+    async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult> {
+        if records.is_empty() {
+            return Ok(SinkResult {
+                records_written: 0,
+                bytes_written: 0,
+                last_position: None,
+                ..Default::default()
+            });
+        }
+
+        // Cache timestamp for entire batch
+        let synced_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+        // Track last position from records
+        let last_position = records.iter().rev().find_map(|r| match r {
+            CdcRecord::Insert { position, .. }
+            | CdcRecord::Update { position, .. }
+            | CdcRecord::Delete { position, .. }
+            | CdcRecord::Commit { position, .. }
+            | CdcRecord::Heartbeat { position, .. } => Some(position.clone()),
+            _ => None,
+        });
+
+        let mut tables: Vec<SinkTableName> = Vec::new();
+        for record in &records {
+            if let CdcRecord::Insert { table, .. }
+            | CdcRecord::Update { table, .. }
+            | CdcRecord::Delete { table, .. } = record
+            {
+                let dest = self.naming.sink_table(&table.qualified_name());
+                if !is_internal_table(&table.name) && !tables.contains(&dest) {
+                    tables.push(dest);
+                }
+            }
+        }
+        let keys = self.table_keys(&tables).await?;
+
+        // Encode off the async workers, like the StarRocks sink
+        let naming = self.naming.clone();
+        let batches = tokio::task::spawn_blocking(move || {
+            let batches = encode(&records, &keys, &naming, &synced_at);
+            pool::recycle(records);
+            batches
+        })
+        .await
+        .context("ClickHouse encoder task failed")?;
+
+        let mut total_written = 0;
+        let mut total_bytes = 0u64;
+        for mut batch in batches {
+            if batch.rows.iter().any(|r| r.missing.is_some()) {
+                self.resolve_unchanged(&mut batch).await?;
+            }
+            let body = batch.to_json_lines()?;
+            total_bytes += body.len() as u64;
+            self.insert_with_retry(&self.table_ident(&batch.table), Bytes::from(body), 3)
+                .await?;
+            total_written += batch.rows.len();
+        }
+
+        Ok(SinkResult {
+            records_written: total_written,
+            bytes_written: total_bytes,
+            last_position,
+            ..Default::default()
+        })
+    }
+
+    async fn commit_batch(&mut self, checkpoint: &SourcePosition) -> Result<()> {
+        match (&self.checkpoint_slot, checkpoint) {
+            (Some(slot), SourcePosition::Lsn(lsn)) => self.write_checkpoint(slot, *lsn).await,
+            _ => Ok(()),
+        }
+    }
+
+    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+        if is_internal_table(&delta.table_name) {
+            return Ok(());
+        }
+        let dest = self.naming.sink_table(&delta.qualified_name());
+        if delta.has_other_changes() && !self.schema_evolution.is_default() {
+            let key = self.table_keys(std::slice::from_ref(&dest)).await?;
+            let (required, best_effort) = column_changes_sql(
+                &self.table_ident(&dest),
+                delta,
+                &self.schema_evolution,
+                key.get(&dest.label()).map_or(&[][..], |k| k.as_slice()),
+                self.bool_encoding,
+            );
+            for sql in &required {
+                info!("[SCHEMA] {}", sql);
+                self.client.execute(sql).await.with_context(|| {
+                    format!(
+                        "Failed to change columns of ClickHouse table {}",
+                        dest.label()
+                    )
+                })?;
+            }
+            for sql in &best_effort {
+                match self.client.execute(sql).await {
+                    Ok(()) => info!("[SCHEMA] {}", sql),
+                    Err(e) => warn!("[SCHEMA] Could not apply ({}): {:#}", sql, e),
+                }
+            }
+        }
+        if delta.added_columns.is_empty() {
+            return Ok(());
+        }
+        // Existing rows have no value for the new columns, so they are always Nullable
+        let defs: Vec<String> = delta
+            .added_columns
+            .iter()
+            .map(|added| {
+                column_def(&ClickHouseColumn {
+                    name: added.name.clone(),
+                    column_type: column_type(added.pg_type_id, self.bool_encoding, true),
+                    comment: delta.column(&added.name).and_then(|c| c.comment.clone()),
+                })
+            })
+            .collect();
+        for def in &defs {
+            info!("[SCHEMA] Adding column {} to {}", def, dest.label());
+        }
+        self.client
+            .execute(&add_columns_sql(&self.table_ident(&dest), &defs))
+            .await
+            .with_context(|| format!("Failed to add columns to ClickHouse table {}", dest.label()))
+    }
+
+    fn invalidate_schema(&mut self, table: Option<&str>) {
+        match table {
+            Some(table) => {
+                let label = self.naming.sink_table(table).label();
+                self.keys.lock().remove(&label);
+            }
+            None => {
+                self.keys.lock().clear();
+                self.checkpoint_table_ready.store(false, Ordering::Relaxed);
+                self.dead_letter_table_ready.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    async fn schema_diff(&self, schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        let source = schema.qualified_name();
+        let dest = self.naming.sink_table(&source);
+        let label = dest.label();
+        let database = dest.database.as_deref().unwrap_or(self.client.database());
+        let rows = self
+            .client
+            .query_rows(
+                "SELECT name, type FROM system.columns \
+                 WHERE database = {database:String} AND table = {table:String}",
+                &[
+                    ("database".to_string(), database.to_string()),
+                    ("table".to_string(), dest.table.clone()),
+                ],
+            )
+            .await
+            .with_context(|| format!("Failed to read columns of ClickHouse table {}", label))?;
+        let actual: Vec<(String, String)> = rows
+            .iter()
+            .filter_map(|row| {
+                let name = row.get("name")?.as_str()?;
+                let column_type = row.get("type")?.as_str()?;
+                Some((name.to_string(), column_type.to_string()))
+            })
+            .collect();
+        let expected: Vec<(String, String)> = schema
+            .added_columns
+            .iter()
+            .map(|c| {
+                let column_type = pg_type_to_clickhouse(c.pg_type_id, self.bool_encoding);
+                (c.name.clone(), column_type.to_string())
+            })
+            .collect();
+        Ok(Some(SchemaDiff::compare(
+            &source,
+            &label,
+            &expected,
+            (!actual.is_empty()).then_some(actual.as_slice()),
+        )))
+    }
+
+    async fn write_dead_letters(&mut self, letters: &[DeadLetter]) -> Result<()> {
+        let table = format!(
+            "{}.{}",
+            quote_ident(self.client.database()),
+            quote_ident(DEAD_LETTER_TABLE)
+        );
+        if !self.dead_letter_table_ready.load(Ordering::Relaxed) {
+            self.client
+                .execute(&dead_letter_table_sql(&table))
+                .await
+                .context("Failed to create ClickHouse dead-letter table")?;
+            self.dead_letter_table_ready.store(true, Ordering::Relaxed);
+        }
+        let mut body = Vec::new();
+        for letter in letters {
+            serde_json::to_writer(&mut body, &dead_letter_row(letter))?;
+            body.push(b'\n');
+        }
+        self.client
+            .insert_json_rows(&table, Bytes::from(body))
+            .await
+            .context("Failed to write ClickHouse dead letters")
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        // HTTP is stateless, nothing to close
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SchemaMode;
+    use crate::core::{TableRef, Value};
+    use crate::pipeline::schema_cache::{AddedColumn, RenamedColumn, RetypedColumn};
+
+    fn orders() -> TableRef {
+        TableRef::new(Some("public".into()), "orders".into())
+    }
+
+    fn keys() -> KeyMap {
+        HashMap::from([("orders".to_string(), Arc::new(vec!["id".to_string()]))])
+    }
+
+    fn row(id: i64, status: Value, notes: Value) -> Vec<ColumnValue> {
+        vec![
+            ColumnValue::new("id".into(), Value::Int64(id)),
+            ColumnValue::new("status".into(), status),
+            ColumnValue::new("notes".into(), notes),
+        ]
+    }
+
+    fn text(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    fn insert(id: i64, status: &str, notes: &str) -> CdcRecord {
+        CdcRecord::Insert {
+            table: orders(),
+            columns: row(id, text(status), text(notes)),
+            position: SourcePosition::Lsn(0x10),
+        }
+    }
+
+    fn update(old: Option<Vec<ColumnValue>>, new: Vec<ColumnValue>) -> CdcRecord {
+        CdcRecord::Update {
+            table: orders(),
+            old_columns: old,
+            new_columns: new,
+            position: SourcePosition::Lsn(0x20),
+        }
+    }
+
+    fn encode_one(records: &[CdcRecord], keys: &KeyMap) -> TableRows {
+        let mut batches = encode(
+            records,
+            keys,
+            &SchemaMode::Strip.into(),
+            "2025-01-01 00:00:00",
+        );
+        assert_eq!(batches.len(), 1);
+        batches.pop().unwrap()
+    }
+
+    #[test]
+    fn test_create_table_sql() {
+        let columns = vec![
+            ClickHouseColumn {
+                name: "id".to_string(),
+                column_type: "Int64".to_string(),
+                comment: None,
+            },
+            ClickHouseColumn {
+                name: "note".to_string(),
+                column_type: "Nullable(String)".to_string(),
+                comment: Some("Customer's note".to_string()),
+            },
+        ];
+        let sql = create_table_sql(
+            "`cdc`.`orders`",
+            &columns,
+            &["id".to_string()],
+            Some("Orders"),
+        );
+        assert!(sql.starts_with("CREATE TABLE IF NOT EXISTS `cdc`.`orders` (\n  `id` Int64,"));
+        assert!(sql.contains("`note` Nullable(String) COMMENT 'Customer\\'s note'"));
+        assert!(sql.contains("`dbmazz_cdc_version` UInt64"));
+        assert!(sql.ends_with(
+            "ENGINE = ReplacingMergeTree(`dbmazz_cdc_version`, `dbmazz_is_deleted`) \
+             ORDER BY (`id`) COMMENT 'Orders'"
+        ));
+
+        let keyless = create_table_sql("`cdc`.`events`", &columns, &[], None);
+        assert!(keyless.ends_with("ENGINE = MergeTree ORDER BY tuple()"));
+    }
+
+    #[test]
+    fn test_add_columns_sql() {
+        let sql = add_columns_sql(
+            "`cdc`.`orders`",
+            &["`a` Nullable(Int32)".to_string(), "`b` String".to_string()],
+        );
+        assert_eq!(
+            sql,
+            "ALTER TABLE `cdc`.`orders` ADD COLUMN IF NOT EXISTS `a` Nullable(Int32), \
+             ADD COLUMN IF NOT EXISTS `b` String"
+        );
+    }
+
+    #[test]
+    fn test_column_changes_sql() {
+        let mut delta = SchemaDelta {
+            renamed_columns: vec![RenamedColumn {
+                old_name: "qty".into(),
+                new_name: "quantity".into(),
+            }],
+            retyped_columns: vec![RetypedColumn {
+                name: "total".into(),
+                old_type_id: 23,
+                pg_type_id: 20,
+                type_mod: -1,
+            }],
+            ..SchemaDelta::default()
+        };
+        delta.dropped_columns = ["id", "memo"]
+            .iter()
+            .map(|name| AddedColumn {
+                name: name.to_string(),
+                pg_type_id: 25,
+                type_mod: -1,
+            })
+            .collect();
+        let changes = |evolution: SchemaEvolution| {
+            column_changes_sql(
+                "`cdc`.`orders`",
+                &delta,
+                &evolution,
+                &["id".to_string()],
+                BoolEncoding::Bool,
+            )
+        };
+
+        assert_eq!(changes(SchemaEvolution::default()), (vec![], vec![]));
+        let (required, best_effort) = changes(SchemaEvolution {
+            dropped: ColumnChangePolicy::Apply,
+            renamed: ColumnChangePolicy::Apply,
+            retyped: ColumnChangePolicy::Apply,
+        });
+        // The sorting key column stays
+        assert_eq!(
+            required,
+            [
+                "ALTER TABLE `cdc`.`orders` RENAME COLUMN IF EXISTS `qty` TO `quantity`",
+                "ALTER TABLE `cdc`.`orders` DROP COLUMN IF EXISTS `memo`",
+            ]
+        );
+        assert_eq!(
+            best_effort,
+            ["ALTER TABLE `cdc`.`orders` MODIFY COLUMN IF EXISTS `total` Nullable(Int64)"]
+        );
+
+        let (required, best_effort) = changes(SchemaEvolution {
+            dropped: ColumnChangePolicy::Deprecate,
+            renamed: ColumnChangePolicy::Ignore,
+            retyped: ColumnChangePolicy::Deprecate,
+        });
+        assert!(required.is_empty());
+        assert_eq!(
+            best_effort,
+            [
+                "ALTER TABLE `cdc`.`orders` COMMENT COLUMN IF EXISTS `id` \
+                 'Deprecated: dropped from the source'",
+                "ALTER TABLE `cdc`.`orders` COMMENT COLUMN IF EXISTS `memo` \
+                 'Deprecated: dropped from the source'",
+                "ALTER TABLE `cdc`.`orders` COMMENT COLUMN IF EXISTS `total` \
+                 'Deprecated: the source column is now Int64'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_qualified_table() {
+        let strip = SchemaMode::Strip.sink_table("sales.orders");
+        assert_eq!(qualified_table(&strip, "cdc"), "`cdc`.`orders`");
+        let preserve = SchemaMode::Preserve.sink_table("sales.orders");
+        assert_eq!(qualified_table(&preserve, "cdc"), "`sales`.`orders`");
+        assert_eq!(quote_ident("we`ird"), "`we\\`ird`");
+    }
+
+    #[test]
+    fn test_batch_keeps_last_image_per_key() {
+        let delete = CdcRecord::Delete {
+            table: orders(),
+            columns: vec![ColumnValue::new("id".into(), Value::Int64(2))],
+            position: SourcePosition::Lsn(0x30),
+        };
+        let records = vec![
+            insert(1, "new", "a"),
+            insert(2, "new", "b"),
+            update(None, row(1, text("paid"), text("a"))),
+            delete,
+        ];
+        let batch = encode_one(&records, &keys());
+        assert_eq!(batch.rows.len(), 2);
+
+        let first = &batch.rows[0].values;
+        assert_eq!(first["status"], "paid");
+        assert_eq!(first["dbmazz_op_type"], 1);
+        assert_eq!(first["dbmazz_cdc_version"], 0x20);
+        let second = &batch.rows[1].values;
+        assert_eq!(second["id"], 2);
+        assert_eq!(second["dbmazz_is_deleted"], 1);
+        assert!(!second.contains_key("status"));
+
+        // Without a key every change is kept
+        let batch = encode_one(&records, &HashMap::new());
+        assert_eq!(batch.rows.len(), 4);
+
+        let body = String::from_utf8(batch.to_json_lines().unwrap()).unwrap();
+        assert_eq!(body.lines().count(), 4);
+        assert!(body.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_unchanged_toast_columns_are_filled() {
+        // From an earlier image in the batch
+        let records = vec![
+            insert(1, "new", "long text"),
+            update(None, row(1, text("paid"), Value::Unchanged)),
+        ];
+        let batch = encode_one(&records, &keys());
+        assert_eq!(batch.rows[0].values["notes"], "long text");
+        assert!(batch.rows[0].missing.is_none());
+
+        // From the old row image
+        let old = row(1, text("new"), text("from old"));
+        let records = vec![update(Some(old), row(1, text("paid"), Value::Unchanged))];
+        let batch = encode_one(&records, &keys());
+        assert_eq!(batch.rows[0].values["notes"], "from old");
+
+        // From the stored row, by key
+        let records = vec![
+            update(None, row(1, text("paid"), Value::Unchanged)),
+            update(None, row(1, text("shipped"), Value::Unchanged)),
+        ];
+        let batch = encode_one(&records, &keys());
+        assert_eq!(batch.rows.len(), 1);
+        let missing = batch.rows[0].missing.as_ref().unwrap();
+        assert_eq!(missing.key, [("id".to_string(), serde_json::json!(1))]);
+        assert_eq!(missing.columns, ["notes"]);
+        assert!(!batch.rows[0].values.contains_key("notes"));
+    }
+
+    #[test]
+    fn test_key_change_deletes_old_key() {
+        let old = vec![ColumnValue::new("id".into(), Value::Int64(1))];
+        let records = vec![update(Some(old), row(5, text("paid"), Value::Unchanged))];
+        let batch = encode_one(&records, &keys());
+        assert_eq!(batch.rows.len(), 2);
+        assert_eq!(batch.rows[0].values["id"], 1);
+        assert_eq!(batch.rows[0].values["dbmazz_is_deleted"], 1);
+        assert_eq!(batch.rows[1].values["id"], 5);
+        // The unchanged column is read from the row under the old key
+        let missing = batch.rows[1].missing.as_ref().unwrap();
+        assert_eq!(missing.key, [("id".to_string(), serde_json::json!(1))]);
+    }
+
+    #[test]
+    fn test_internal_tables_are_skipped() {
+        let records = vec![CdcRecord::Insert {
+            table: TableRef::new(Some("public".into()), "dbmazz_heartbeat".into()),
+            columns: vec![ColumnValue::new("id".into(), Value::Int64(1))],
+            position: SourcePosition::Lsn(0x10),
+        }];
+        assert!(encode(&records, &keys(), &SchemaMode::Strip.into(), "now").is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! ClickHouse Type Mappings
+//!
+//! Column types are chosen from the PostgreSQL type OID:
+//!
+//! | PostgreSQL | ClickHouse |
+//! |------------|------------|
+//! | boolean | Bool (UInt8 with `SINK_BOOLEAN_ENCODING=int`) |
+//! | smallint, integer, bigint | Int16, Int32, Int64 |
+//! | real, double precision | Float32, Float64 |
+//! | numeric, money | Decimal(38, 9) |
+//! | date | Date32 |
+//! | timestamp, timestamptz | DateTime64(6), DateTime64(6, 'UTC') |
+//! | uuid | UUID |
+//! | int/float/text arrays | Array(Nullable(Int64 / Float64 / String)) |
+//! | enums | LowCardinality(String) |
+//! | domains | as their base type |
+//! | everything else (text, json, bytea, time) | String |
+//!
+//! Rows are sent as `JSONEachRow`. JSON documents are written as their
+//! text, so `json`/`jsonb` columns hold exactly what PostgreSQL sent.
+
+use crate::config::BoolEncoding;
+use crate::core::Value;
+
+/// Maps a PostgreSQL type OID to a ClickHouse column type, without
+/// `Nullable`.
+pub(crate) fn pg_type_to_clickhouse(pg_type_id: u32, bool_encoding: BoolEncoding) -> &'static str {
+    match pg_type_id {
+        16 => match bool_encoding {
+            BoolEncoding::Int => "UInt8",
+            BoolEncoding::Bool | BoolEncoding::Char => "Bool",
+        },
+        21 => "Int16",
+        23 => "Int32",
+        20 => "Int64",
+        700 => "Float32",
+        701 => "Float64",
+        790 | 1700 => "Decimal(38, 9)",
+        1082 => "Date32",
+        1114 => "DateTime64(6)",
+        1184 => "DateTime64(6, 'UTC')",
+        2950 => "UUID",
+        1005 | 1007 | 1016 => "Array(Nullable(Int64))",
+        1021 | 1022 => "Array(Nullable(Float64))",
+        1009 | 1015 => "Array(Nullable(String))",
+        // Not Enum8: labels added to the type would be refused
+        oid if crate::source::pg_types::enum_labels(oid).is_some() => "LowCardinality(String)",
+        oid => match crate::source::pg_types::base_type(oid) {
+            base if base != oid => pg_type_to_clickhouse(base, bool_encoding),
+            _ => "String",
+        },
+    }
+}
+
+/// Column type for a replicated column. Nullable columns are wrapped in
+/// `Nullable`, except arrays, which ClickHouse doesn't allow inside it (an
+/// empty array stands in for NULL), and `LowCardinality`, which goes around it.
+pub(crate) fn column_type(pg_type_id: u32, bool_encoding: BoolEncoding, nullable: bool) -> String {
+    let base = pg_type_to_clickhouse(pg_type_id, bool_encoding);
+    if !nullable || base.starts_with("Array(") {
+        return base.to_string();
+    }
+    match base
+        .strip_prefix("LowCardinality(")
+        .and_then(|t| t.strip_suffix(')'))
+    {
+        Some(inner) => format!("LowCardinality(Nullable({}))", inner),
+        None => format!("Nullable({})", base),
+    }
+}
+
+/// Converts a CDC `Value` to its `JSONEachRow` representation.
+///
+/// `Value::Unchanged` is resolved by the sink before encoding; it maps to
+/// NULL only as a defensive fallback.
+pub(crate) fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null | Value::Unchanged => serde_json::Value::Null,
+        Value::Bool(b) => serde_json::json!(b),
+        Value::Int64(i) => serde_json::json!(i),
+        // ClickHouse parses nan and inf from quoted floats; JSON has no literal for them
+        Value::Float64(f) if f.is_nan() => serde_json::json!("nan"),
+        Value::Float64(f) if f.is_infinite() => {
+            serde_json::json!(if *f > 0.0 { "inf" } else { "-inf" })
+        }
+        Value::Float64(f) => serde_json::json!(f),
+        Value::Bytes(b) => serde_json::json!(hex::encode(b)),
+        Value::Json(s) => {
+            // Arrays are parsed so Array columns can read them; any other
+            // document is kept as text for String columns
+            match serde_json::from_str::<serde_json::Value>(s) {
+                Ok(array @ serde_json::Value::Array(_)) => array,
+                _ => serde_json::json!(s),
+            }
+        }
+        Value::Timestamp(ts) => {
+            // Unix timestamp (micros) to DateTime64(6) text
+            let secs = ts.div_euclid(1_000_000);
+            let nanos = (ts.rem_euclid(1_000_000) * 1000) as u32;
+            match chrono::DateTime::from_timestamp(secs, nanos) {
+                Some(dt) => serde_json::json!(dt.format("%Y-%m-%d %H:%M:%S%.6f").to_string()),
+                None => serde_json::Value::Null,
+            }
+        }
+        // Decimals stay strings to keep their precision
+        Value::String(s) | Value::Decimal(s) | Value::Uuid(s) => serde_json::json!(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pg_type_to_clickhouse() {
+        assert_eq!(pg_type_to_clickhouse(16, BoolEncoding::Bool), "Bool");
+        assert_eq!(pg_type_to_clickhouse(16, BoolEncoding::Int), "UInt8");
+        assert_eq!(pg_type_to_clickhouse(20, BoolEncoding::Bool), "Int64");
+        assert_eq!(
+            pg_type_to_clickhouse(1700, BoolEncoding::Bool),
+            "Decimal(38, 9)"
+        );
+        assert_eq!(
+            pg_type_to_clickhouse(1184, BoolEncoding::Bool),
+            "DateTime64(6, 'UTC')"
+        );
+        assert_eq!(pg_type_to_clickhouse(3802, BoolEncoding::Bool), "String");
+        assert_eq!(
+            pg_type_to_clickhouse(1016, BoolEncoding::Bool),
+            "Array(Nullable(Int64))"
+        );
+    }
+
+    #[test]
+    fn test_column_type() {
+        assert_eq!(column_type(23, BoolEncoding::Bool, false), "Int32");
+        assert_eq!(
+            column_type(25, BoolEncoding::Bool, true),
+            "Nullable(String)"
+        );
+        // Arrays can't be Nullable
+        assert_eq!(
+            column_type(1009, BoolEncoding::Bool, true),
+            "Array(Nullable(String))"
+        );
+    }
+
+    #[test]
+    fn test_enum_and_domain_columns() {
+        use crate::source::pg_types::{self, PgType};
+        use std::sync::Arc;
+
+        pg_types::register(
+            92_001,
+            PgType::Enum {
+                labels: vec![Arc::from("happy"), Arc::from("sad")].into(),
+            },
+        );
+        pg_types::register(92_002, PgType::Domain { base: 1184 });
+        assert_eq!(
+            column_type(92_001, BoolEncoding::Bool, true),
+            "LowCardinality(Nullable(String))"
+        );
+        assert_eq!(
+            column_type(92_001, BoolEncoding::Bool, false),
+            "LowCardinality(String)"
+        );
+        assert_eq!(
+            column_type(92_002, BoolEncoding::Bool, true),
+            "Nullable(DateTime64(6, 'UTC'))"
+        );
+    }
+
+    #[test]
+    fn test_value_to_json() {
+        assert_eq!(value_to_json(&Value::Null), serde_json::Value::Null);
+        assert_eq!(value_to_json(&Value::Int64(-7)), serde_json::json!(-7));
+        assert_eq!(value_to_json(&Value::Float64(1.5)), serde_json::json!(1.5));
+        assert_eq!(
+            value_to_json(&Value::Float64(f64::NEG_INFINITY)),
+            serde_json::json!("-inf")
+        );
+        assert_eq!(
+            value_to_json(&Value::Decimal("12.50".to_string())),
+            serde_json::json!("12.50")
+        );
+        assert_eq!(
+            value_to_json(&Value::Json(r#"{"a": 1}"#.to_string())),
+            serde_json::json!(r#"{"a": 1}"#)
+        );
+        assert_eq!(
+            value_to_json(&Value::Json("[1,null,3]".to_string())),
+            serde_json::json!([1, null, 3])
+        );
+        assert_eq!(
+            value_to_json(&Value::Timestamp(-1)),
+            serde_json::json!("1969-12-31 23:59:59.999999")
+        );
+        assert_eq!(
+            value_to_json(&Value::Bytes(vec![0xde, 0xad])),
+            serde_json::json!("dead")
+        );
+    }
+}
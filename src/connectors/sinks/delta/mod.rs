@@ -0,0 +1,510 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! # Delta Lake Sink Connector
+//!
+//! This module implements a CDC sink appending to Delta Lake tables on S3
+//! through delta-rs. Every change becomes a row of its table's changelog,
+//! tagged with three CDC columns, and readers resolve the current state at
+//! query time (merge-on-read): the row with the highest `_lsn` per key wins,
+//! and is dropped when its `_op` is `D`.
+//!
+//! ## Features
+//!
+//! - **Changelog rows**: inserts and updates append the new row image,
+//!   deletes the old one (the key, or every column with
+//!   `REPLICA IDENTITY FULL`)
+//! - **TOAST handling**: unchanged TOAST columns are filled from the old row
+//!   image; a value it lacks fails the batch, since the stored row can't be
+//!   read back
+//! - **Schema evolution**: added source columns are added to the table
+//!   schema, as nullable columns
+//! - **Checkpoints**: each commit's info records the LSN of its batch
+//!   (`dbmazz.lsn`)
+//!
+//! ## Batches
+//!
+//! Each batch is one commit to the Delta log of every table it touches,
+//! written by delta-rs as Parquet files under the table's location. The
+//! batch's LSN is only returned once every commit succeeded, so a batch that
+//! fails part way is written again, and tables committed the first time get
+//! its rows twice; readers taking the latest row per key and `_lsn` see them
+//! once. Concurrent writers are serialized by S3 conditional writes or a
+//! DynamoDB lock table (`DELTA_LOCK_TABLE`).
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use dbmazz::connectors::sinks::delta::DeltaSink;
+//!
+//! // SINK_TYPE=delta SINK_URL=s3://lake/cdc SINK_DATABASE=sales
+//! let mut sink = DeltaSink::new(&config)?;
+//! sink.write_batch(records).await?;
+//! ```
+
+pub(crate) mod types;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use deltalake::kernel::transaction::CommitProperties;
+use deltalake::kernel::StructField;
+use deltalake::protocol::SaveMode;
+use deltalake::{DeltaOps, DeltaTable, DeltaTableError};
+use parking_lot::Mutex;
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::info;
+
+use super::changelog::{self, Layout, CDC_COLUMNS};
+use super::is_internal_table;
+use crate::config::{DeltaSinkConfig, HttpClientConfig, SinkConfig, SinkTableName, TableNaming};
+use crate::connectors::sinks::iceberg::types::IcebergType;
+use crate::core::schema_diff::SchemaDiff;
+use crate::core::sink_error::SinkError;
+use crate::core::traits::StageFormat;
+use crate::core::{
+    pool, CdcRecord, LoadingModel, Sink, SinkCapabilities, SinkResult, SourcePosition,
+};
+use crate::pipeline::schema_cache::SchemaDelta;
+
+/// A nullable column with `doc` as its comment.
+pub(crate) fn struct_field(
+    name: &str,
+    column_type: IcebergType,
+    doc: Option<&str>,
+) -> Result<StructField> {
+    let field = StructField::new(name, types::delta_type(column_type)?, true);
+    Ok(match doc {
+        Some(doc) => field.with_metadata([("comment", doc)]),
+        None => field,
+    })
+}
+
+/// The CDC columns.
+pub(crate) fn cdc_fields() -> Result<Vec<StructField>> {
+    CDC_COLUMNS
+        .iter()
+        .map(|(name, column_type, doc)| struct_field(name, *column_type, Some(doc)))
+        .collect()
+}
+
+/// delta-rs storage options for the tables of `config`, reached through
+/// `http`'s proxy. A CA bundle isn't supported, so `SINK_TLS_CA_BUNDLE` is
+/// rejected for Delta.
+pub(crate) fn storage_options(
+    config: &DeltaSinkConfig,
+    http: &HttpClientConfig,
+) -> HashMap<String, String> {
+    let storage = &config.storage;
+    let mut options = HashMap::from([
+        ("AWS_REGION".to_string(), storage.region.clone()),
+        (
+            "AWS_ACCESS_KEY_ID".to_string(),
+            storage.access_key_id.clone(),
+        ),
+        (
+            "AWS_SECRET_ACCESS_KEY".to_string(),
+            storage.secret_access_key.clone(),
+        ),
+    ]);
+    if let Some(token) = &storage.session_token {
+        options.insert("AWS_SESSION_TOKEN".to_string(), token.clone());
+    }
+    if let Some(endpoint) = &storage.endpoint {
+        options.insert("AWS_ENDPOINT_URL".to_string(), endpoint.clone());
+        if endpoint.starts_with("http://") {
+            options.insert("AWS_ALLOW_HTTP".to_string(), "true".to_string());
+        }
+    }
+    match &config.lock_table {
+        Some(table) => {
+            options.insert(
+                "AWS_S3_LOCKING_PROVIDER".to_string(),
+                "dynamodb".to_string(),
+            );
+            options.insert("DELTA_DYNAMO_TABLE_NAME".to_string(), table.clone());
+        }
+        // Put-if-absent on the next log entry, without a lock
+        None => {
+            options.insert("aws_conditional_put".to_string(), "etag".to_string());
+        }
+    }
+    if let Some(proxy) = &http.proxy {
+        options.insert("proxy_url".to_string(), proxy.clone());
+        if let Some(no_proxy) = &http.no_proxy {
+            options.insert("proxy_excludes".to_string(), no_proxy.clone());
+        }
+    }
+    if http.insecure_skip_verify {
+        options.insert("allow_invalid_certificates".to_string(), "true".to_string());
+    }
+    options
+}
+
+/// Location of a sink table: `<root>/<database>/<table>`, its database
+/// being `default_database` without one.
+pub(crate) fn table_uri(root: &str, dest: &SinkTableName, default_database: &str) -> String {
+    let database = dest.database.as_deref().unwrap_or(default_database);
+    match database {
+        "" => format!("{}/{}", root.trim_end_matches('/'), dest.table),
+        database => format!(
+            "{}/{}/{}",
+            root.trim_end_matches('/'),
+            database.replace('.', "/"),
+            dest.table
+        ),
+    }
+}
+
+/// The Delta table at `uri`, or `None` when there is none yet.
+pub(crate) async fn load_table(
+    uri: &str,
+    options: &HashMap<String, String>,
+) -> Result<Option<DeltaTable>> {
+    deltalake::aws::register_handlers(None);
+    match deltalake::open_table_with_storage_options(uri, options.clone()).await {
+        Ok(table) => Ok(Some(table)),
+        Err(DeltaTableError::NotATable(_)) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to load Delta table {}", uri)),
+    }
+}
+
+/// Adds the `fields` a table lacks. Returns the table as of the commit and
+/// the names of the columns added.
+pub(crate) async fn add_columns(
+    table: DeltaTable,
+    fields: Vec<StructField>,
+) -> Result<(DeltaTable, Vec<String>)> {
+    let schema = table.snapshot()?.schema();
+    let missing: Vec<StructField> = fields
+        .into_iter()
+        .filter(|f| schema.field(f.name()).is_none())
+        .collect();
+    if missing.is_empty() {
+        return Ok((table, Vec::new()));
+    }
+    let names = missing.iter().map(|f| f.name().to_string()).collect();
+    let table = DeltaOps(table)
+        .add_columns()
+        .with_fields(missing)
+        .await
+        .context("Failed to add columns")?;
+    Ok((table, names))
+}
+
+/// Delta Lake sink connector implementing the Sink trait.
+pub struct DeltaSink {
+    /// `SINK_URL`: the directory the tables are under
+    root: String,
+    /// How source tables map to databases and tables
+    naming: TableNaming,
+    /// `SINK_DATABASE`: directory of tables without their own database
+    database: String,
+    storage_options: HashMap<String, String>,
+    /// Tables as of the last load or commit, by sink table label
+    tables: Mutex<HashMap<String, DeltaTable>>,
+}
+
+impl DeltaSink {
+    pub fn new(config: &SinkConfig) -> Result<Self> {
+        let delta = config
+            .delta
+            .as_ref()
+            .context("Delta sink configuration is missing")?;
+        info!(
+            "Delta sink: location {}, database {}",
+            config.url, config.database
+        );
+        deltalake::aws::register_handlers(None);
+        Ok(Self {
+            root: config.url.trim_end_matches('/').to_string(),
+            naming: config.naming.clone(),
+            database: config.database.clone(),
+            storage_options: storage_options(delta, &config.http),
+            tables: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn table_uri(&self, dest: &SinkTableName) -> String {
+        table_uri(&self.root, dest, &self.database)
+    }
+
+    /// A sink table, loaded on first use.
+    async fn table(&self, dest: &SinkTableName) -> Result<DeltaTable> {
+        if let Some(table) = self.tables.lock().get(&dest.label()) {
+            return Ok(table.clone());
+        }
+        let uri = self.table_uri(dest);
+        let table = load_table(&uri, &self.storage_options)
+            .await?
+            .ok_or_else(|| {
+                SinkError::schema_mismatch("delta", format!("no Delta table at {}", uri))
+            })?;
+        self.tables.lock().insert(dest.label(), table.clone());
+        Ok(table)
+    }
+
+    /// Appends the changes of one table in one commit. Returns the rows
+    /// written.
+    async fn write_table(
+        &self,
+        dest: &SinkTableName,
+        records: Vec<CdcRecord>,
+        position: Option<&SourcePosition>,
+        synced_at: i64,
+    ) -> Result<usize> {
+        let table = self.table(dest).await?;
+        let layout = Layout::new(table.snapshot()?.arrow_schema()?, "delta")?;
+        let label = dest.label();
+        let batch = tokio::task::spawn_blocking(move || {
+            let batch = layout.encode(&records, synced_at, &label);
+            pool::recycle(records);
+            batch
+        })
+        .await
+        .context("Delta encoder task failed")??;
+        let rows = batch.num_rows();
+        if rows == 0 {
+            return Ok(0);
+        }
+
+        let mut properties = CommitProperties::default();
+        if let Some(SourcePosition::Lsn(lsn)) = position {
+            properties =
+                properties.with_metadata([("dbmazz.lsn".to_string(), json!(lsn.to_string()))]);
+        }
+        let written = DeltaOps(table)
+            .write(vec![batch])
+            .with_save_mode(SaveMode::Append)
+            .with_commit_properties(properties)
+            .await;
+        match written {
+            Ok(table) => {
+                self.tables.lock().insert(dest.label(), table);
+                Ok(rows)
+            }
+            Err(e) => {
+                // The next attempt reloads the log
+                self.tables.lock().remove(&dest.label());
+                Err(e).context("Failed to commit to the Delta log")
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for DeltaSink {
+    fn name(&self) -> &'static str {
+        "delta"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            supports_upsert: false,
+            supports_delete: true,
+            supports_schema_evolution: true,
+            supports_transactions: false,
+            loading_model: LoadingModel::StagedBatch {
+                stage_format: StageFormat::Parquet,
+            },
+            min_batch_size: Some(1),
+            max_batch_size: Some(1_000_000),
+            optimal_flush_interval_ms: 60_000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        // delta-rs holds no connection; setup reaches the bucket when it
+        // loads or creates the tables, and each batch when it commits
+        Ok(())
+    }
+
+    async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult> {
+        let last_position = records.iter().rev().find_map(|r| match r {
+            CdcRecord::Insert { position, .. }
+            | CdcRecord::Update { position, .. }
+            | CdcRecord::Delete { position, .. }
+            | CdcRecord::Commit { position, .. }
+            | CdcRecord::Heartbeat { position, .. } => Some(position.clone()),
+            _ => None,
+        });
+        let synced_at = chrono::Utc::now().timestamp_micros();
+
+        // Changes of each table, in order of first appearance
+        let mut tables: Vec<(SinkTableName, Vec<CdcRecord>)> = Vec::new();
+        for record in records {
+            let table = match &record {
+                CdcRecord::Insert { table, .. }
+                | CdcRecord::Update { table, .. }
+                | CdcRecord::Delete { table, .. } => table,
+                // Schema changes arrive as deltas; transactions and heartbeats aren't written
+                _ => continue,
+            };
+            if is_internal_table(&table.name) {
+                continue;
+            }
+            let dest = self.naming.sink_table(&table.qualified_name());
+            match tables.iter_mut().find(|(d, _)| *d == dest) {
+                Some((_, changes)) => changes.push(record),
+                None => tables.push((dest, vec![record])),
+            }
+        }
+
+        // The position is only acknowledged once every table has committed
+        let mut total_written = 0;
+        for (dest, changes) in tables {
+            total_written += self
+                .write_table(&dest, changes, last_position.as_ref(), synced_at)
+                .await
+                .with_context(|| format!("Failed to write Delta table {}", dest.label()))?;
+        }
+
+        Ok(SinkResult {
+            records_written: total_written,
+            // delta-rs doesn't report the size of the files it wrote
+            bytes_written: 0,
+            last_position,
+            ..Default::default()
+        })
+    }
+
+    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+        if is_internal_table(&delta.table_name) || delta.added_columns.is_empty() {
+            return Ok(());
+        }
+        let dest = self.naming.sink_table(&delta.qualified_name());
+        let table = self.table(&dest).await?;
+        let fields = delta
+            .added_columns
+            .iter()
+            .map(|added| {
+                let doc = delta.column(&added.name).and_then(|c| c.comment.as_deref());
+                struct_field(&added.name, types::column_type(added.pg_type_id), doc)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (table, added) = add_columns(table, fields)
+            .await
+            .with_context(|| format!("Failed to add columns to Delta table {}", dest.label()))?;
+        for name in &added {
+            info!("[SCHEMA] Adding column {} to {}", name, dest.label());
+        }
+        self.tables.lock().insert(dest.label(), table);
+        Ok(())
+    }
+
+    fn invalidate_schema(&mut self, table: Option<&str>) {
+        match table {
+            Some(table) => {
+                let label = self.naming.sink_table(table).label();
+                self.tables.lock().remove(&label);
+            }
+            None => self.tables.lock().clear(),
+        }
+    }
+
+    async fn schema_diff(&self, schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        let source = schema.qualified_name();
+        let dest = self.naming.sink_table(&source);
+        let uri = self.table_uri(&dest);
+        let actual: Option<Vec<(String, String)>> =
+            match load_table(&uri, &self.storage_options).await? {
+                Some(table) => Some(
+                    table
+                        .snapshot()?
+                        .arrow_schema()?
+                        .fields()
+                        .iter()
+                        .map(|f| {
+                            let name = changelog::from_arrow(f.data_type())
+                                .map_or_else(|| f.data_type().to_string(), types::type_name);
+                            (f.name().clone(), name)
+                        })
+                        .collect(),
+                ),
+                None => None,
+            };
+        let expected: Vec<(String, String)> = schema
+            .added_columns
+            .iter()
+            .map(|c| {
+                (
+                    c.name.clone(),
+                    types::type_name(types::column_type(c.pg_type_id)),
+                )
+            })
+            .collect();
+        Ok(Some(SchemaDiff::compare(
+            &source,
+            &dest.label(),
+            &expected,
+            actual.as_deref(),
+        )))
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        // Every batch is committed before write_batch returns
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::S3Location;
+
+    #[test]
+    fn test_table_uri() {
+        let dest = SinkTableName {
+            database: None,
+            table: "orders".to_string(),
+        };
+        assert_eq!(
+            table_uri("s3://lake/cdc/", &dest, "sales"),
+            "s3://lake/cdc/sales/orders"
+        );
+        let dest = SinkTableName {
+            database: Some("billing".to_string()),
+            table: "invoices".to_string(),
+        };
+        assert_eq!(
+            table_uri("s3://lake/cdc", &dest, "sales"),
+            "s3://lake/cdc/billing/invoices"
+        );
+    }
+
+    #[test]
+    fn test_storage_options() {
+        let mut config = DeltaSinkConfig {
+            lock_table: None,
+            storage: S3Location {
+                bucket: String::new(),
+                prefix: String::new(),
+                region: "us-east-1".to_string(),
+                endpoint: Some("http://minio:9000".to_string()),
+                access_key_id: "minio".to_string(),
+                secret_access_key: "minio123".to_string(),
+                session_token: None,
+            },
+        };
+        let options = storage_options(&config, &HttpClientConfig::default());
+        assert_eq!(options["AWS_ALLOW_HTTP"], "true");
+        assert_eq!(options["aws_conditional_put"], "etag");
+        assert!(!options.contains_key("AWS_SESSION_TOKEN"));
+        assert!(!options.contains_key("proxy_url"));
+
+        config.lock_table = Some("delta_log".to_string());
+        let http = HttpClientConfig {
+            proxy: Some("http://proxy.corp:3128".to_string()),
+            no_proxy: Some("minio".to_string()),
+            ..Default::default()
+        };
+        let options = storage_options(&config, &http);
+        assert_eq!(options["AWS_S3_LOCKING_PROVIDER"], "dynamodb");
+        assert_eq!(options["DELTA_DYNAMO_TABLE_NAME"], "delta_log");
+        assert!(!options.contains_key("aws_conditional_put"));
+        assert_eq!(options["proxy_url"], "http://proxy.corp:3128");
+        assert_eq!(options["proxy_excludes"], "minio");
+        assert!(!options.contains_key("allow_invalid_certificates"));
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Delta Lake Type Mappings
+//!
+//! Column types are chosen from the PostgreSQL type OID:
+//!
+//! | PostgreSQL | Delta |
+//! |------------|-------|
+//! | boolean | boolean |
+//! | smallint, integer | integer |
+//! | bigint | long |
+//! | real, double precision | float, double |
+//! | numeric, money | decimal(38,9) |
+//! | date | date |
+//! | timestamp, timestamptz | timestamp_ntz, timestamp |
+//! | bytea | binary |
+//! | everything else (text, time, json, uuid, arrays) | string |
+//!
+//! Delta's primitive types are Iceberg's less `time`, so values are turned
+//! into Arrow arrays by the Iceberg sink's conversions (see `changelog`).
+
+use anyhow::{Context, Result};
+use deltalake::kernel::DataType;
+
+use crate::connectors::sinks::iceberg::types::IcebergType;
+
+/// Type of a replicated column, as the Iceberg type whose Arrow encoding
+/// Delta shares.
+pub(crate) fn column_type(pg_type_id: u32) -> IcebergType {
+    match IcebergType::for_pg_type(pg_type_id) {
+        // Delta has no time of day type
+        IcebergType::Time => IcebergType::String,
+        other => other,
+    }
+}
+
+/// The Delta type of a column.
+pub(crate) fn delta_type(column_type: IcebergType) -> Result<DataType> {
+    Ok(match column_type {
+        IcebergType::Boolean => DataType::BOOLEAN,
+        IcebergType::Int => DataType::INTEGER,
+        IcebergType::Long => DataType::LONG,
+        IcebergType::Float => DataType::FLOAT,
+        IcebergType::Double => DataType::DOUBLE,
+        IcebergType::Decimal { precision, scale } => DataType::decimal(precision, scale)
+            .with_context(|| format!("Invalid Delta type decimal({},{})", precision, scale))?,
+        IcebergType::Date => DataType::DATE,
+        IcebergType::Timestamp => DataType::TIMESTAMP_NTZ,
+        IcebergType::TimestampTz => DataType::TIMESTAMP,
+        IcebergType::Time | IcebergType::String => DataType::STRING,
+        IcebergType::Binary => DataType::BINARY,
+    })
+}
+
+/// The type as Delta names it in the table schema.
+pub(crate) fn type_name(column_type: IcebergType) -> String {
+    match column_type {
+        IcebergType::Int => "integer".to_string(),
+        IcebergType::Decimal { precision, scale } => format!("decimal({},{})", precision, scale),
+        IcebergType::Timestamp => "timestamp_ntz".to_string(),
+        IcebergType::TimestampTz => "timestamp".to_string(),
+        IcebergType::Time => "string".to_string(),
+        other => other.name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::sinks::changelog::from_arrow;
+    use arrow_schema::DataType as ArrowType;
+
+    #[test]
+    fn test_column_types() {
+        assert_eq!(column_type(1083), IcebergType::String);
+        assert_eq!(type_name(column_type(23)), "integer");
+        assert_eq!(type_name(column_type(1700)), "decimal(38,9)");
+        assert_eq!(type_name(column_type(1114)), "timestamp_ntz");
+        assert_eq!(type_name(column_type(1184)), "timestamp");
+
+        for oid in [16, 20, 23, 700, 701, 1700, 1082, 1114, 1184, 17, 25] {
+            let column_type = column_type(oid);
+            assert_eq!(
+                from_arrow(&column_type.arrow_type()),
+                Some(column_type),
+                "{}",
+                oid
+            );
+        }
+        assert_eq!(from_arrow(&ArrowType::Int16), None);
+    }
+}
@@ -0,0 +1,519 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Avro object container files, the format of Iceberg manifests and
+//! manifest lists.
+//!
+//! Only what manifests need is implemented: records are encoded against a
+//! JSON schema with the binary encoding, and container files are written
+//! uncompressed. Files are read with their own schema, so manifest lists
+//! written by other engines (deflate-compressed, as Iceberg writes them by
+//! default) can be carried into the next snapshot.
+
+use anyhow::{Context, Result};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::io::Read;
+
+const MAGIC: &[u8; 4] = b"Obj\x01";
+
+/// A decoded Avro value. Unions decode to the branch's value, enums to their
+/// symbol and fixed to bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Datum {
+    Null,
+    Boolean(bool),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bytes(Vec<u8>),
+    String(String),
+    Array(Vec<Datum>),
+    Map(Vec<(String, Datum)>),
+    Record(Vec<(String, Datum)>),
+}
+
+impl Datum {
+    /// Field `name` of a record, `None` when absent or not a record.
+    pub(crate) fn field(&self, name: &str) -> Option<&Datum> {
+        match self {
+            Datum::Record(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, d)| d),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_long(&self) -> Option<i64> {
+        match self {
+            Datum::Int(i) => Some(*i as i64),
+            Datum::Long(l) => Some(*l),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Datum::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `records` as a container file with `schema`, plus `metadata`
+/// (Iceberg stores the table schema and partition spec there).
+pub(crate) fn write_container(
+    schema: &Json,
+    metadata: &[(&str, String)],
+    records: &[Datum],
+) -> Result<Vec<u8>> {
+    let mut out = MAGIC.to_vec();
+    let schema_text = schema.to_string();
+    let mut entries: Vec<(&str, &[u8])> = vec![
+        ("avro.schema", schema_text.as_bytes()),
+        ("avro.codec", b"null"),
+    ];
+    entries.extend(metadata.iter().map(|(k, v)| (*k, v.as_bytes())));
+    write_long(&mut out, entries.len() as i64);
+    for (key, value) in entries {
+        write_bytes(&mut out, key.as_bytes());
+        write_bytes(&mut out, value);
+    }
+    write_long(&mut out, 0);
+
+    let mut sync = [0u8; 16];
+    openssl::rand::rand_bytes(&mut sync).context("Failed to generate Avro sync marker")?;
+    out.extend_from_slice(&sync);
+
+    if !records.is_empty() {
+        let names = Names::collect(schema);
+        let mut block = Vec::new();
+        for record in records {
+            encode(schema, record, &names, &mut block)?;
+        }
+        write_long(&mut out, records.len() as i64);
+        write_long(&mut out, block.len() as i64);
+        out.extend_from_slice(&block);
+        out.extend_from_slice(&sync);
+    }
+    Ok(out)
+}
+
+/// Reads every record of a container file.
+pub(crate) fn read_container(data: &[u8]) -> Result<Vec<Datum>> {
+    let mut input = data
+        .strip_prefix(MAGIC.as_slice())
+        .context("Not an Avro container file")?;
+    let mut metadata = HashMap::new();
+    loop {
+        let count = read_long(&mut input)?;
+        if count == 0 {
+            break;
+        }
+        if count < 0 {
+            // A negative count is followed by the block size
+            read_long(&mut input)?;
+        }
+        for _ in 0..count.unsigned_abs() {
+            let key = String::from_utf8(read_bytes(&mut input)?)?;
+            metadata.insert(key, read_bytes(&mut input)?);
+        }
+    }
+    let schema: Json = serde_json::from_slice(
+        metadata
+            .get("avro.schema")
+            .context("Avro container file has no schema")?,
+    )
+    .context("Invalid Avro schema")?;
+    let codec = metadata
+        .get("avro.codec")
+        .map(|c| String::from_utf8_lossy(c).into_owned())
+        .unwrap_or_else(|| "null".to_string());
+    let sync = take(&mut input, 16)?.to_vec();
+    let names = Names::collect(&schema);
+
+    let mut records = Vec::new();
+    while !input.is_empty() {
+        let count = read_long(&mut input)?;
+        let size = usize::try_from(read_long(&mut input)?).context("Invalid Avro block size")?;
+        let raw = take(&mut input, size)?;
+        let block = match codec.as_str() {
+            "null" => raw.to_vec(),
+            "deflate" => {
+                let mut inflated = Vec::new();
+                flate2::read::DeflateDecoder::new(raw)
+                    .read_to_end(&mut inflated)
+                    .context("Failed to inflate Avro block")?;
+                inflated
+            }
+            other => anyhow::bail!("Unsupported Avro codec '{}'", other),
+        };
+        let mut block = block.as_slice();
+        for _ in 0..count {
+            records.push(decode(&schema, &mut block, &names)?);
+        }
+        if take(&mut input, 16)? != sync.as_slice() {
+            anyhow::bail!("Avro sync marker mismatch");
+        }
+    }
+    Ok(records)
+}
+
+/// Named types of a schema, so later references to them resolve.
+struct Names<'a>(HashMap<&'a str, &'a Json>);
+
+impl<'a> Names<'a> {
+    fn collect(schema: &'a Json) -> Self {
+        let mut names = Names(HashMap::new());
+        names.visit(schema);
+        names
+    }
+
+    fn visit(&mut self, schema: &'a Json) {
+        match schema {
+            Json::Array(branches) => branches.iter().for_each(|b| self.visit(b)),
+            Json::Object(object) => {
+                if let Some(name) = object.get("name").and_then(Json::as_str) {
+                    self.0.insert(name, schema);
+                    // References may use the short name of a namespaced type
+                    if let Some((_, short)) = name.rsplit_once('.') {
+                        self.0.insert(short, schema);
+                    }
+                }
+                if let Some(fields) = object.get("fields").and_then(Json::as_array) {
+                    for field in fields {
+                        if let Some(field_type) = field.get("type") {
+                            self.visit(field_type);
+                        }
+                    }
+                }
+                for nested in ["items", "values"] {
+                    if let Some(nested) = object.get(nested) {
+                        self.visit(nested);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The definition of `schema`, following a reference to a named type.
+    fn resolve(&self, schema: &'a Json) -> &'a Json {
+        match schema.as_str() {
+            Some(name) => self.0.get(name).copied().unwrap_or(schema),
+            None => schema,
+        }
+    }
+}
+
+/// Type name of a schema: a primitive, `record`, `array`, ... or `union`.
+fn type_name(schema: &Json) -> &str {
+    match schema {
+        Json::String(name) => name,
+        Json::Array(_) => "union",
+        Json::Object(object) => match object.get("type") {
+            Some(Json::String(name)) => name,
+            _ => "",
+        },
+        _ => "",
+    }
+}
+
+fn encode(schema: &Json, datum: &Datum, names: &Names, out: &mut Vec<u8>) -> Result<()> {
+    let schema = names.resolve(schema);
+    match (type_name(schema), datum) {
+        ("union", datum) => {
+            let branches = schema.as_array().context("Invalid Avro union")?;
+            let index = branches
+                .iter()
+                .position(|b| (type_name(b) == "null") == (*datum == Datum::Null))
+                .context("No Avro union branch for value")?;
+            write_long(out, index as i64);
+            encode(&branches[index], datum, names, out)?;
+        }
+        // The type itself may be an object, e.g. {"type": "long"}
+        (name, datum) if schema.is_object() && !is_complex(name) => {
+            encode(&Json::String(name.to_string()), datum, names, out)?;
+        }
+        ("null", Datum::Null) => {}
+        ("boolean", Datum::Boolean(b)) => out.push(*b as u8),
+        ("int", Datum::Int(i)) => write_long(out, *i as i64),
+        ("long", Datum::Long(l)) => write_long(out, *l),
+        ("long", Datum::Int(i)) => write_long(out, *i as i64),
+        ("float", Datum::Float(f)) => out.extend_from_slice(&f.to_le_bytes()),
+        ("double", Datum::Double(f)) => out.extend_from_slice(&f.to_le_bytes()),
+        ("bytes", Datum::Bytes(b)) => write_bytes(out, b),
+        ("string", Datum::String(s)) => write_bytes(out, s.as_bytes()),
+        ("fixed", Datum::Bytes(b)) => out.extend_from_slice(b),
+        ("enum", Datum::String(symbol)) => {
+            let index = schema
+                .get("symbols")
+                .and_then(Json::as_array)
+                .and_then(|symbols| symbols.iter().position(|s| s == symbol.as_str()))
+                .with_context(|| format!("Unknown Avro enum symbol '{}'", symbol))?;
+            write_long(out, index as i64);
+        }
+        ("record", Datum::Record(_)) => {
+            let fields = schema
+                .get("fields")
+                .and_then(Json::as_array)
+                .context("Avro record without fields")?;
+            for field in fields {
+                let name = field.get("name").and_then(Json::as_str).unwrap_or_default();
+                let field_type = field.get("type").context("Avro field without type")?;
+                encode(
+                    field_type,
+                    datum.field(name).unwrap_or(&Datum::Null),
+                    names,
+                    out,
+                )
+                .with_context(|| format!("Avro field {}", name))?;
+            }
+        }
+        ("array", Datum::Array(items)) => {
+            let item_type = schema.get("items").context("Avro array without items")?;
+            if !items.is_empty() {
+                write_long(out, items.len() as i64);
+                for item in items {
+                    encode(item_type, item, names, out)?;
+                }
+            }
+            write_long(out, 0);
+        }
+        ("map", Datum::Map(entries)) => {
+            let value_type = schema.get("values").context("Avro map without values")?;
+            if !entries.is_empty() {
+                write_long(out, entries.len() as i64);
+                for (key, value) in entries {
+                    write_bytes(out, key.as_bytes());
+                    encode(value_type, value, names, out)?;
+                }
+            }
+            write_long(out, 0);
+        }
+        (name, datum) => anyhow::bail!("Can't encode {:?} as Avro {}", datum, name),
+    }
+    Ok(())
+}
+
+fn is_complex(name: &str) -> bool {
+    matches!(name, "record" | "enum" | "array" | "map" | "fixed")
+}
+
+fn decode(schema: &Json, input: &mut &[u8], names: &Names) -> Result<Datum> {
+    let schema = names.resolve(schema);
+    let name = type_name(schema);
+    Ok(match name {
+        "union" => {
+            let branches = schema.as_array().context("Invalid Avro union")?;
+            let index = usize::try_from(read_long(input)?).context("Invalid Avro union index")?;
+            decode(
+                branches
+                    .get(index)
+                    .context("Avro union index out of range")?,
+                input,
+                names,
+            )?
+        }
+        "null" => Datum::Null,
+        "boolean" => Datum::Boolean(take(input, 1)?[0] != 0),
+        "int" => Datum::Int(read_long(input)? as i32),
+        "long" => Datum::Long(read_long(input)?),
+        "float" => Datum::Float(f32::from_le_bytes(take(input, 4)?.try_into()?)),
+        "double" => Datum::Double(f64::from_le_bytes(take(input, 8)?.try_into()?)),
+        "bytes" => Datum::Bytes(read_bytes(input)?),
+        "string" => Datum::String(String::from_utf8(read_bytes(input)?)?),
+        "fixed" => {
+            let size = schema
+                .get("size")
+                .and_then(Json::as_u64)
+                .context("Avro fixed without size")?;
+            Datum::Bytes(take(input, size as usize)?.to_vec())
+        }
+        "enum" => {
+            let index = read_long(input)?;
+            let symbol = schema
+                .get("symbols")
+                .and_then(Json::as_array)
+                .and_then(|symbols| symbols.get(index as usize))
+                .and_then(Json::as_str)
+                .context("Avro enum index out of range")?;
+            Datum::String(symbol.to_string())
+        }
+        "record" => {
+            let fields = schema
+                .get("fields")
+                .and_then(Json::as_array)
+                .context("Avro record without fields")?;
+            let mut values = Vec::with_capacity(fields.len());
+            for field in fields {
+                let name = field.get("name").and_then(Json::as_str).unwrap_or_default();
+                let field_type = field.get("type").context("Avro field without type")?;
+                values.push((name.to_string(), decode(field_type, input, names)?));
+            }
+            Datum::Record(values)
+        }
+        "array" => {
+            let item_type = schema.get("items").context("Avro array without items")?;
+            let mut items = Vec::new();
+            while let Some(count) = read_block_count(input)? {
+                for _ in 0..count {
+                    items.push(decode(item_type, input, names)?);
+                }
+            }
+            Datum::Array(items)
+        }
+        "map" => {
+            let value_type = schema.get("values").context("Avro map without values")?;
+            let mut entries = Vec::new();
+            while let Some(count) = read_block_count(input)? {
+                for _ in 0..count {
+                    let key = String::from_utf8(read_bytes(input)?)?;
+                    entries.push((key, decode(value_type, input, names)?));
+                }
+            }
+            Datum::Map(entries)
+        }
+        // {"type": "long", "logicalType": ...} and the like
+        name if schema.is_object() => decode(&Json::String(name.to_string()), input, names)?,
+        name => anyhow::bail!("Unsupported Avro type '{}'", name),
+    })
+}
+
+/// Item count of the next array or map block, `None` at the end.
+fn read_block_count(input: &mut &[u8]) -> Result<Option<u64>> {
+    let count = read_long(input)?;
+    if count == 0 {
+        return Ok(None);
+    }
+    if count < 0 {
+        read_long(input)?;
+    }
+    Ok(Some(count.unsigned_abs()))
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        anyhow::bail!("Avro data is truncated");
+    }
+    let (head, rest) = input.split_at(len);
+    *input = rest;
+    Ok(head)
+}
+
+/// Zigzag varint.
+fn write_long(out: &mut Vec<u8>, value: i64) {
+    let mut n = ((value << 1) ^ (value >> 63)) as u64;
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_long(input: &mut &[u8]) -> Result<i64> {
+    let mut n: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = take(input, 1)?[0];
+        n |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok((n >> 1) as i64 ^ -((n & 1) as i64));
+        }
+    }
+    anyhow::bail!("Avro varint is too long")
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(out, bytes.len() as i64);
+    out.extend_from_slice(bytes);
+}
+
+fn read_bytes(input: &mut &[u8]) -> Result<Vec<u8>> {
+    let len = usize::try_from(read_long(input)?).context("Invalid Avro length")?;
+    Ok(take(input, len)?.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn schema() -> Json {
+        serde_json::json!({
+            "type": "record",
+            "name": "entry",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "path", "type": "string"},
+                {"name": "parent", "type": ["null", "long"], "default": null},
+                {"name": "ids", "type": ["null", {"type": "array", "items": "int"}]},
+                {"name": "partition", "type": {"type": "record", "name": "r102", "fields": []}},
+            ]
+        })
+    }
+
+    fn entry(id: i64, parent: Option<i64>) -> Datum {
+        Datum::Record(vec![
+            ("id".to_string(), Datum::Long(id)),
+            ("path".to_string(), Datum::String(format!("s3://b/{}", id))),
+            (
+                "parent".to_string(),
+                parent.map_or(Datum::Null, Datum::Long),
+            ),
+            (
+                "ids".to_string(),
+                Datum::Array(vec![Datum::Int(1), Datum::Int(-2)]),
+            ),
+            ("partition".to_string(), Datum::Record(Vec::new())),
+        ])
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, -1, 63, -64, 64, 1 << 40, i64::MIN, i64::MAX] {
+            let mut out = Vec::new();
+            write_long(&mut out, value);
+            assert_eq!(read_long(&mut out.as_slice()).unwrap(), value);
+        }
+        let mut out = Vec::new();
+        write_long(&mut out, -2);
+        assert_eq!(out, [3]);
+    }
+
+    #[test]
+    fn test_container_round_trip() {
+        let records = vec![entry(1, None), entry(-7, Some(3))];
+        let file = write_container(&schema(), &[("format-version", "2".into())], &records).unwrap();
+        assert_eq!(read_container(&file).unwrap(), records);
+
+        let empty = write_container(&schema(), &[], &[]).unwrap();
+        assert!(read_container(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_deflate_container() {
+        let names = Names::collect(&schema());
+        let mut block = Vec::new();
+        encode(&schema(), &entry(5, Some(4)), &names, &mut block).unwrap();
+        let mut deflate = flate2::write::DeflateEncoder::new(Vec::new(), Default::default());
+        deflate.write_all(&block).unwrap();
+        let block = deflate.finish().unwrap();
+
+        let mut file = MAGIC.to_vec();
+        write_long(&mut file, 2);
+        for (key, value) in [
+            ("avro.schema", schema().to_string()),
+            ("avro.codec", "deflate".to_string()),
+        ] {
+            write_bytes(&mut file, key.as_bytes());
+            write_bytes(&mut file, value.as_bytes());
+        }
+        write_long(&mut file, 0);
+        file.extend_from_slice(&[9; 16]);
+        write_long(&mut file, 1);
+        write_long(&mut file, block.len() as i64);
+        file.extend_from_slice(&block);
+        file.extend_from_slice(&[9; 16]);
+
+        assert_eq!(read_container(&file).unwrap(), vec![entry(5, Some(4))]);
+    }
+}
@@ -0,0 +1,561 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Iceberg REST catalog client.
+//!
+//! Speaks the REST catalog protocol (`/v1/...`) over reqwest: the catalog's
+//! `prefix` is read from `GET /v1/config` on first use, then tables are
+//! loaded, created and committed to under it. Commits carry requirements
+//! (the branch still points at the snapshot the commit builds on), so a
+//! catalog that moved on answers 409 and the caller retries on the new
+//! state.
+//!
+//! With `ICEBERG_CATALOG=rest`, `SINK_USER` and `SINK_PASSWORD` are an OAuth2
+//! client id and secret exchanged for a token at `/v1/oauth/tokens`, or
+//! without a user `SINK_PASSWORD` is the bearer token itself. With
+//! `ICEBERG_CATALOG=glue`, requests go to the Glue Iceberg endpoint signed
+//! with SigV4 and the `AWS_*` credentials.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::config::{IcebergCatalog, S3Location, SinkConfig};
+use crate::connectors::s3::{amz_date, authorization, SignedRequest};
+use crate::connectors::sinks::shared;
+
+/// A table in the catalog.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct TableIdent {
+    /// Namespace levels, e.g. `["lake", "cdc"]` for `lake.cdc`
+    pub namespace: Vec<String>,
+    pub name: String,
+}
+
+impl TableIdent {
+    /// `namespace.name`, as shown in logs.
+    pub(crate) fn label(&self) -> String {
+        format!("{}.{}", self.namespace.join("."), self.name)
+    }
+
+    /// URL path of the namespace: levels joined by the unit separator.
+    fn namespace_path(&self) -> String {
+        namespace_path(&self.namespace)
+    }
+}
+
+fn namespace_path(namespace: &[String]) -> String {
+    namespace
+        .iter()
+        .map(|level| encode_segment(level))
+        .collect::<Vec<_>>()
+        .join("%1F")
+}
+
+/// A field of a table schema.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub(crate) struct Field {
+    pub id: i32,
+    pub name: String,
+    pub required: bool,
+    /// A primitive type name, or an object for nested types
+    #[serde(rename = "type")]
+    pub field_type: Json,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+/// A table schema.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Schema {
+    #[serde(default)]
+    pub schema_id: i32,
+    pub fields: Vec<Field>,
+    /// Fields that identify a row; the equality delete columns
+    #[serde(default)]
+    pub identifier_field_ids: Vec<i32>,
+}
+
+impl Schema {
+    pub(crate) fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    pub(crate) fn to_json(&self) -> Json {
+        let mut schema = json!({
+            "type": "struct",
+            "schema-id": self.schema_id,
+            "fields": self.fields,
+        });
+        if !self.identifier_field_ids.is_empty() {
+            schema["identifier-field-ids"] = json!(self.identifier_field_ids);
+        }
+        schema
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct PartitionSpec {
+    pub spec_id: i32,
+    #[serde(default)]
+    pub fields: Vec<Json>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Snapshot {
+    pub snapshot_id: i64,
+    pub manifest_list: Option<String>,
+}
+
+/// The parts of table metadata the sink uses.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct TableMetadata {
+    pub format_version: u8,
+    pub location: String,
+    #[serde(default)]
+    pub last_sequence_number: i64,
+    pub last_column_id: i32,
+    #[serde(default)]
+    pub current_schema_id: i32,
+    #[serde(default)]
+    pub schemas: Vec<Schema>,
+    #[serde(default)]
+    pub default_spec_id: i32,
+    #[serde(default)]
+    pub partition_specs: Vec<PartitionSpec>,
+    #[serde(default)]
+    pub current_snapshot_id: Option<i64>,
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl TableMetadata {
+    pub(crate) fn current_schema(&self) -> Result<&Schema> {
+        self.schemas
+            .iter()
+            .find(|s| s.schema_id == self.current_schema_id)
+            .with_context(|| format!("Table metadata lacks schema {}", self.current_schema_id))
+    }
+
+    /// The snapshot `main` points at, `None` for an empty table (v1
+    /// metadata writes -1 for none).
+    pub(crate) fn current_snapshot(&self) -> Option<&Snapshot> {
+        let id = self.current_snapshot_id.filter(|id| *id >= 0)?;
+        self.snapshots.iter().find(|s| s.snapshot_id == id)
+    }
+
+    pub(crate) fn is_partitioned(&self) -> bool {
+        self.partition_specs
+            .iter()
+            .any(|spec| spec.spec_id == self.default_spec_id && !spec.fields.is_empty())
+    }
+}
+
+/// Result of a commit.
+pub(crate) enum CommitOutcome {
+    Committed(Box<TableMetadata>),
+    /// A requirement failed: the table changed since it was loaded
+    Conflict,
+}
+
+pub(crate) struct RestCatalog {
+    http: reqwest::Client,
+    /// `SINK_URL` without a trailing `/`
+    uri: String,
+    warehouse: Option<String>,
+    /// OAuth2 client id; `None` uses `secret` as the bearer token
+    client_id: Option<String>,
+    secret: String,
+    oauth_scope: String,
+    /// Credentials Glue requests are signed with
+    glue: Option<S3Location>,
+    /// `prefix` from `GET /v1/config`, read on first use
+    prefix: OnceCell<String>,
+    /// OAuth2 token, fetched on first use and again after a 401
+    token: Mutex<Option<String>>,
+}
+
+impl RestCatalog {
+    pub(crate) fn new(config: &SinkConfig) -> Result<Self> {
+        let iceberg = config
+            .iceberg
+            .as_ref()
+            .context("Iceberg sink configuration is missing")?;
+        let http =
+            shared::http_client(config).context("Failed to build Iceberg catalog HTTP client")?;
+        Ok(Self {
+            http,
+            uri: config.url.trim_end_matches('/').to_string(),
+            warehouse: iceberg.warehouse.clone(),
+            client_id: Some(config.user.clone()).filter(|u| !u.is_empty()),
+            secret: config.password.clone(),
+            oauth_scope: iceberg.oauth_scope.clone(),
+            glue: (iceberg.catalog == IcebergCatalog::Glue).then(|| iceberg.storage.clone()),
+            prefix: OnceCell::new(),
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Reads the catalog configuration, failing when it isn't reachable.
+    pub(crate) async fn check(&self) -> Result<()> {
+        self.prefix().await.map(|_| ())
+    }
+
+    async fn prefix(&self) -> Result<&str> {
+        self.prefix
+            .get_or_try_init(|| async {
+                let mut url = format!("{}/v1/config", self.uri);
+                if let Some(warehouse) = &self.warehouse {
+                    url = format!("{}?warehouse={}", url, encode_segment(warehouse));
+                }
+                let (status, body) = self.send(Method::GET, &url, None).await?;
+                if !status.is_success() {
+                    anyhow::bail!("GET {} returned {}: {}", url, status, error_message(&body));
+                }
+                let prefix = body
+                    .pointer("/overrides/prefix")
+                    .or_else(|| body.pointer("/defaults/prefix"))
+                    .and_then(Json::as_str)
+                    .unwrap_or_default();
+                Ok(prefix.trim_matches('/').to_string())
+            })
+            .await
+            .map(String::as_str)
+    }
+
+    /// URL of `path` under the catalog prefix.
+    async fn url(&self, path: &str) -> Result<String> {
+        Ok(match self.prefix().await? {
+            "" => format!("{}/v1/{}", self.uri, path),
+            prefix => format!("{}/v1/{}/{}", self.uri, prefix, path),
+        })
+    }
+
+    /// Creates a namespace, doing nothing when it exists.
+    pub(crate) async fn create_namespace(&self, namespace: &[String]) -> Result<()> {
+        let url = self.url("namespaces").await?;
+        let body = json!({ "namespace": namespace });
+        let (status, response) = self.send(Method::POST, &url, Some(&body)).await?;
+        if status.is_success() || status == StatusCode::CONFLICT {
+            return Ok(());
+        }
+        anyhow::bail!(
+            "Failed to create Iceberg namespace {}: {} {}",
+            namespace.join("."),
+            status,
+            error_message(&response)
+        )
+    }
+
+    /// Loads a table, `None` when it doesn't exist.
+    pub(crate) async fn load_table(&self, table: &TableIdent) -> Result<Option<TableMetadata>> {
+        let url = self
+            .url(&format!(
+                "namespaces/{}/tables/{}",
+                table.namespace_path(),
+                encode_segment(&table.name)
+            ))
+            .await?;
+        let (status, body) = self.send(Method::GET, &url, None).await?;
+        match status {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(table_metadata(body)?)),
+            status => anyhow::bail!(
+                "Failed to load Iceberg table {}: {} {}",
+                table.label(),
+                status,
+                error_message(&body)
+            ),
+        }
+    }
+
+    /// Creates an unpartitioned format v2 table with `schema`, writing
+    /// Parquet and merge-on-read deletes.
+    pub(crate) async fn create_table(
+        &self,
+        table: &TableIdent,
+        schema: &Schema,
+        doc: Option<&str>,
+    ) -> Result<TableMetadata> {
+        let url = self
+            .url(&format!("namespaces/{}/tables", table.namespace_path()))
+            .await?;
+        let mut properties = json!({
+            "format-version": "2",
+            "write.format.default": "parquet",
+            "write.delete.mode": "merge-on-read",
+            "write.update.mode": "merge-on-read",
+        });
+        if let Some(doc) = doc {
+            properties["comment"] = json!(doc);
+        }
+        let body = json!({
+            "name": table.name,
+            "schema": schema.to_json(),
+            "properties": properties,
+        });
+        let (status, response) = self.send(Method::POST, &url, Some(&body)).await?;
+        if !status.is_success() {
+            anyhow::bail!(
+                "Failed to create Iceberg table {}: {} {}",
+                table.label(),
+                status,
+                error_message(&response)
+            );
+        }
+        table_metadata(response)
+    }
+
+    /// Commits `updates` to a table if `requirements` hold.
+    pub(crate) async fn commit(
+        &self,
+        table: &TableIdent,
+        requirements: Vec<Json>,
+        updates: Vec<Json>,
+    ) -> Result<CommitOutcome> {
+        let url = self
+            .url(&format!(
+                "namespaces/{}/tables/{}",
+                table.namespace_path(),
+                encode_segment(&table.name)
+            ))
+            .await?;
+        let body = json!({
+            "identifier": {"namespace": table.namespace, "name": table.name},
+            "requirements": requirements,
+            "updates": updates,
+        });
+        let (status, response) = self.send(Method::POST, &url, Some(&body)).await?;
+        match status {
+            StatusCode::CONFLICT => Ok(CommitOutcome::Conflict),
+            status if status.is_success() => Ok(CommitOutcome::Committed(Box::new(
+                table_metadata(response)?,
+            ))),
+            status => anyhow::bail!(
+                "Failed to commit to Iceberg table {}: {} {}",
+                table.label(),
+                status,
+                error_message(&response)
+            ),
+        }
+    }
+
+    /// Sends a request, authenticated for the catalog, and returns the
+    /// status with the JSON body (`null` when empty). An expired OAuth2
+    /// token is fetched again once.
+    async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<&Json>,
+    ) -> Result<(StatusCode, Json)> {
+        let payload = body.map(|b| b.to_string()).unwrap_or_default();
+        for attempt in 0..2 {
+            let mut request = self.http.request(method.clone(), url);
+            if body.is_some() {
+                request = request.header("Content-Type", "application/json");
+            }
+            if let Some(credentials) = &self.glue {
+                for (name, value) in glue_headers(credentials, &method, url, &payload)? {
+                    request = request.header(name, value);
+                }
+            } else if let Some(token) = self.token().await? {
+                request = request.bearer_auth(token);
+            }
+            let response = request
+                .body(payload.clone())
+                .send()
+                .await
+                .with_context(|| format!("Iceberg catalog {} {} failed", method, url))?;
+            let status = response.status();
+            if status == StatusCode::UNAUTHORIZED && self.client_id.is_some() && attempt == 0 {
+                *self.token.lock().await = None;
+                continue;
+            }
+            let text = response.text().await.unwrap_or_default();
+            let json = serde_json::from_str(&text).unwrap_or(Json::String(text));
+            return Ok((status, json));
+        }
+        unreachable!("the second attempt always returns")
+    }
+
+    /// Bearer token: `SINK_PASSWORD`, or one exchanged for the client
+    /// credentials. `None` when the catalog takes no authentication.
+    async fn token(&self) -> Result<Option<String>> {
+        let Some(client_id) = &self.client_id else {
+            return Ok(Some(self.secret.clone()).filter(|s| !s.is_empty()));
+        };
+        let mut token = self.token.lock().await;
+        if token.is_none() {
+            let url = format!("{}/v1/oauth/tokens", self.uri);
+            let response = self
+                .http
+                .post(&url)
+                .form(&[
+                    ("grant_type", "client_credentials"),
+                    ("client_id", client_id.as_str()),
+                    ("client_secret", self.secret.as_str()),
+                    ("scope", self.oauth_scope.as_str()),
+                ])
+                .send()
+                .await
+                .with_context(|| format!("Iceberg catalog token request to {} failed", url))?;
+            let status = response.status();
+            let body: Json = response.json().await.unwrap_or(Json::Null);
+            let access_token = body
+                .get("access_token")
+                .and_then(Json::as_str)
+                .filter(|_| status.is_success())
+                .with_context(|| {
+                    format!(
+                        "Iceberg catalog refused the client credentials: {} {}",
+                        status,
+                        error_message(&body)
+                    )
+                })?;
+            *token = Some(access_token.to_string());
+        }
+        Ok(token.clone())
+    }
+}
+
+fn table_metadata(response: Json) -> Result<TableMetadata> {
+    let metadata = response
+        .get("metadata")
+        .cloned()
+        .context("Iceberg catalog response has no table metadata")?;
+    serde_json::from_value(metadata).context("Invalid Iceberg table metadata")
+}
+
+/// `error.message` of a REST catalog error response, or the body.
+fn error_message(body: &Json) -> String {
+    match body.pointer("/error/message").and_then(Json::as_str) {
+        Some(message) => message.to_string(),
+        None => body.to_string(),
+    }
+}
+
+/// Percent-encodes a URL path segment: everything but unreserved characters.
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// SigV4 headers of a Glue request. Services other than S3 sign the path
+/// with each segment encoded twice.
+fn glue_headers(
+    credentials: &S3Location,
+    method: &Method,
+    url: &str,
+    payload: &str,
+) -> Result<Vec<(String, String)>> {
+    let parsed = url::Url::parse(url).with_context(|| format!("Invalid catalog URL {}", url))?;
+    let host = match parsed.port() {
+        Some(port) => format!("{}:{}", parsed.host_str().unwrap_or_default(), port),
+        None => parsed.host_str().unwrap_or_default().to_string(),
+    };
+    let path = parsed.path().replace('%', "%25");
+    let payload_hash = hex::encode(openssl::sha::sha256(payload.as_bytes()));
+    let now = Utc::now();
+    let mut headers = vec![
+        ("host".to_string(), host),
+        ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+        ("x-amz-date".to_string(), amz_date(now)),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    let authorization = authorization(
+        credentials,
+        &SignedRequest {
+            service: "glue",
+            method: method.as_str(),
+            path: &path,
+            query: parsed.query().unwrap_or_default(),
+            headers: &headers,
+            payload_hash: &payload_hash,
+        },
+        now,
+    )?;
+    // reqwest sets Host itself, from the same URL
+    headers.retain(|(name, _)| name != "host");
+    headers.push(("authorization".to_string(), authorization));
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_metadata() {
+        let response = json!({
+            "metadata-location": "s3://lake/cdc/orders/metadata/00001.metadata.json",
+            "metadata": {
+                "format-version": 2,
+                "table-uuid": "9c12d441-03fe-4693-9a96-a0705ddf69c1",
+                "location": "s3://lake/cdc/orders",
+                "last-sequence-number": 4,
+                "last-updated-ms": 1700000000000i64,
+                "last-column-id": 3,
+                "current-schema-id": 1,
+                "schemas": [
+                    {"type": "struct", "schema-id": 0, "fields": [
+                        {"id": 1, "name": "id", "required": true, "type": "long"}
+                    ]},
+                    {"type": "struct", "schema-id": 1, "identifier-field-ids": [1], "fields": [
+                        {"id": 1, "name": "id", "required": true, "type": "long"},
+                        {"id": 3, "name": "note", "required": false, "type": "string"}
+                    ]}
+                ],
+                "default-spec-id": 0,
+                "partition-specs": [{"spec-id": 0, "fields": []}],
+                "current-snapshot-id": 77,
+                "snapshots": [
+                    {"snapshot-id": 77, "sequence-number": 4, "timestamp-ms": 1,
+                     "manifest-list": "s3://lake/cdc/orders/metadata/snap-77.avro",
+                     "summary": {"operation": "append"}}
+                ]
+            }
+        });
+        let metadata = table_metadata(response).unwrap();
+        let schema = metadata.current_schema().unwrap();
+        assert_eq!(schema.identifier_field_ids, vec![1]);
+        assert_eq!(schema.field("note").unwrap().id, 3);
+        assert!(!metadata.is_partitioned());
+        assert_eq!(
+            metadata
+                .current_snapshot()
+                .unwrap()
+                .manifest_list
+                .as_deref(),
+            Some("s3://lake/cdc/orders/metadata/snap-77.avro")
+        );
+        assert_eq!(schema.to_json()["identifier-field-ids"], json!([1]));
+    }
+
+    #[test]
+    fn test_namespace_path() {
+        let table = TableIdent {
+            namespace: vec!["lake".to_string(), "cdc raw".to_string()],
+            name: "orders".to_string(),
+        };
+        assert_eq!(table.namespace_path(), "lake%1Fcdc%20raw");
+        assert_eq!(table.label(), "lake.cdc raw.orders");
+    }
+}
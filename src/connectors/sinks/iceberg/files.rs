@@ -0,0 +1,134 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Parquet data and delete files.
+//!
+//! Every column carries its Iceberg field id (`PARQUET:field_id`), which is
+//! how readers match file columns to the table schema after renames and
+//! added columns. Position delete files use the reserved `file_path` and
+//! `pos` fields; equality delete files hold the identifier columns.
+
+use anyhow::{Context, Result};
+use arrow_array::RecordBatch;
+use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+use parquet::arrow::{ArrowWriter, PARQUET_FIELD_ID_META_KEY};
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::catalog::Field;
+use super::types::IcebergType;
+use crate::core::dead_letter::RejectedData;
+use crate::core::Value;
+
+/// Reserved field id of `file_path` in position delete files
+const DELETE_FILE_PATH_ID: i32 = 2_147_483_546;
+/// Reserved field id of `pos` in position delete files
+const DELETE_POS_ID: i32 = 2_147_483_545;
+
+/// A column of a Parquet file: its schema field and type.
+pub(crate) type Column = (Field, IcebergType);
+
+/// Encodes `rows`, each with one value per column, as a Parquet file.
+/// Values the column type can't hold are rejected data.
+pub(crate) fn write_parquet(columns: &[Column], rows: &[Vec<Value>]) -> Result<Vec<u8>> {
+    let fields: Vec<ArrowField> = columns
+        .iter()
+        .map(|(field, iceberg_type)| {
+            ArrowField::new(&field.name, iceberg_type.arrow_type(), !field.required).with_metadata(
+                HashMap::from([(PARQUET_FIELD_ID_META_KEY.to_string(), field.id.to_string())]),
+            )
+        })
+        .collect();
+    let schema = Arc::new(ArrowSchema::new(fields));
+    let arrays = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (field, iceberg_type))| {
+            iceberg_type
+                .to_array(&field.name, rows.iter().map(|row| &row[i]))
+                .map_err(|e| anyhow::Error::new(RejectedData::new("iceberg", format!("{:#}", e))))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Fails on NULL in a required column
+    let batch = RecordBatch::try_new(schema.clone(), arrays)
+        .map_err(|e| anyhow::Error::new(RejectedData::new("iceberg", e.to_string())))?;
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut writer = ArrowWriter::try_new(Vec::new(), schema, Some(properties))
+        .context("Failed to start Parquet file")?;
+    writer
+        .write(&batch)
+        .context("Failed to encode Parquet rows")?;
+    writer.into_inner().context("Failed to finish Parquet file")
+}
+
+/// Columns of a position delete file.
+pub(crate) fn position_delete_columns() -> Vec<Column> {
+    let column = |id, name: &str, iceberg_type: IcebergType| {
+        let field = Field {
+            id,
+            name: name.to_string(),
+            required: true,
+            field_type: serde_json::json!(iceberg_type.name()),
+            doc: None,
+        };
+        (field, iceberg_type)
+    };
+    vec![
+        column(DELETE_FILE_PATH_ID, "file_path", IcebergType::String),
+        column(DELETE_POS_ID, "pos", IcebergType::Long),
+    ]
+}
+
+/// Rows of a position delete file deleting `positions` of `data_file`,
+/// sorted as the spec requires.
+pub(crate) fn position_delete_rows(data_file: &str, positions: &[i64]) -> Vec<Vec<Value>> {
+    let mut positions = positions.to_vec();
+    positions.sort_unstable();
+    positions
+        .into_iter()
+        .map(|pos| vec![Value::String(data_file.to_string()), Value::Int64(pos)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    #[test]
+    fn test_position_delete_file() {
+        let rows = position_delete_rows("s3://lake/orders/data/a-data.parquet", &[7, 2]);
+        assert_eq!(rows[0][1], Value::Int64(2));
+
+        let file = write_parquet(&position_delete_columns(), &rows).unwrap();
+        let reader = SerializedFileReader::new(Bytes::from(file)).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let ids: Vec<i32> = (0..schema.num_columns())
+            .map(|i| schema.column(i).self_type().get_basic_info().id())
+            .collect();
+        assert_eq!(ids, [DELETE_FILE_PATH_ID, DELETE_POS_ID]);
+    }
+
+    #[test]
+    fn test_null_in_required_column_is_rejected() {
+        let columns = vec![(
+            Field {
+                id: 1,
+                name: "id".to_string(),
+                required: true,
+                field_type: serde_json::json!("long"),
+                doc: None,
+            },
+            IcebergType::Long,
+        )];
+        let err = write_parquet(&columns, &[vec![Value::Null]]).unwrap_err();
+        assert!(err.is::<RejectedData>());
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Iceberg v2 manifests and manifest lists.
+//!
+//! A snapshot names a manifest list; the list names manifests, each holding
+//! data files or delete files of one kind. A commit writes one manifest for
+//! its data file and one for its delete files, and a manifest list with
+//! those plus every manifest of the parent snapshot. The entries of the new
+//! manifests leave their sequence number empty, so they inherit the one the
+//! manifest list gives their manifest when the commit lands, and a retried
+//! commit only needs a new list.
+
+use anyhow::{Context, Result};
+use serde_json::json;
+
+use super::avro::{self, Datum};
+
+/// Content of a data or delete file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FileContent {
+    Data = 0,
+    PositionDeletes = 1,
+    EqualityDeletes = 2,
+}
+
+/// A Parquet file added by a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DataFile {
+    pub content: FileContent,
+    /// Full `s3://` path
+    pub path: String,
+    pub record_count: i64,
+    pub size_bytes: i64,
+    /// Field ids of the columns an equality delete matches on
+    pub equality_ids: Vec<i32>,
+}
+
+/// An entry of a manifest list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ManifestFile {
+    pub path: String,
+    pub length: i64,
+    pub partition_spec_id: i32,
+    /// 0 = data files, 1 = delete files
+    pub content: i32,
+    pub sequence_number: i64,
+    pub min_sequence_number: i64,
+    pub added_snapshot_id: i64,
+    pub added_files_count: i32,
+    pub existing_files_count: i32,
+    pub deleted_files_count: i32,
+    pub added_rows_count: i64,
+    pub existing_rows_count: i64,
+    pub deleted_rows_count: i64,
+}
+
+/// Avro schema of a manifest entry (v2), for an unpartitioned table.
+fn manifest_entry_schema() -> serde_json::Value {
+    json!({
+        "type": "record",
+        "name": "manifest_entry",
+        "fields": [
+            {"name": "status", "type": "int", "field-id": 0},
+            {"name": "snapshot_id", "type": ["null", "long"], "default": null, "field-id": 1},
+            {"name": "sequence_number", "type": ["null", "long"], "default": null, "field-id": 3},
+            {"name": "file_sequence_number", "type": ["null", "long"], "default": null, "field-id": 4},
+            {"name": "data_file", "field-id": 2, "type": {
+                "type": "record",
+                "name": "r2",
+                "fields": [
+                    {"name": "content", "type": "int", "field-id": 134},
+                    {"name": "file_path", "type": "string", "field-id": 100},
+                    {"name": "file_format", "type": "string", "field-id": 101},
+                    {"name": "partition", "field-id": 102, "type": {
+                        "type": "record", "name": "r102", "fields": []
+                    }},
+                    {"name": "record_count", "type": "long", "field-id": 103},
+                    {"name": "file_size_in_bytes", "type": "long", "field-id": 104},
+                    {"name": "equality_ids", "default": null, "field-id": 135, "type": [
+                        "null",
+                        {"type": "array", "items": "int", "element-id": 136}
+                    ]},
+                    {"name": "sort_order_id", "type": ["null", "int"], "default": null, "field-id": 140},
+                ]
+            }},
+        ]
+    })
+}
+
+/// Avro schema of a manifest list entry (v2).
+fn manifest_file_schema() -> serde_json::Value {
+    json!({
+        "type": "record",
+        "name": "manifest_file",
+        "fields": [
+            {"name": "manifest_path", "type": "string", "field-id": 500},
+            {"name": "manifest_length", "type": "long", "field-id": 501},
+            {"name": "partition_spec_id", "type": "int", "field-id": 502},
+            {"name": "content", "type": "int", "field-id": 517},
+            {"name": "sequence_number", "type": "long", "field-id": 515},
+            {"name": "min_sequence_number", "type": "long", "field-id": 516},
+            {"name": "added_snapshot_id", "type": "long", "field-id": 503},
+            {"name": "added_files_count", "type": "int", "field-id": 504},
+            {"name": "existing_files_count", "type": "int", "field-id": 505},
+            {"name": "deleted_files_count", "type": "int", "field-id": 506},
+            {"name": "added_rows_count", "type": "long", "field-id": 512},
+            {"name": "existing_rows_count", "type": "long", "field-id": 513},
+            {"name": "deleted_rows_count", "type": "long", "field-id": 514},
+        ]
+    })
+}
+
+/// A manifest of `files`, all data files or all delete files, added by
+/// `snapshot_id`. `table_schema` is the table's current schema as JSON.
+pub(crate) fn write_manifest(
+    files: &[DataFile],
+    snapshot_id: i64,
+    table_schema: &serde_json::Value,
+    schema_id: i32,
+) -> Result<Vec<u8>> {
+    let deletes = files.iter().any(|f| f.content != FileContent::Data);
+    let entries: Vec<Datum> = files
+        .iter()
+        .map(|file| {
+            let equality_ids = match file.content {
+                FileContent::EqualityDeletes => {
+                    Datum::Array(file.equality_ids.iter().map(|id| Datum::Int(*id)).collect())
+                }
+                _ => Datum::Null,
+            };
+            Datum::Record(vec![
+                // 1 = ADDED
+                ("status".to_string(), Datum::Int(1)),
+                ("snapshot_id".to_string(), Datum::Long(snapshot_id)),
+                ("sequence_number".to_string(), Datum::Null),
+                ("file_sequence_number".to_string(), Datum::Null),
+                (
+                    "data_file".to_string(),
+                    Datum::Record(vec![
+                        ("content".to_string(), Datum::Int(file.content as i32)),
+                        ("file_path".to_string(), Datum::String(file.path.clone())),
+                        ("file_format".to_string(), Datum::String("PARQUET".into())),
+                        ("partition".to_string(), Datum::Record(Vec::new())),
+                        ("record_count".to_string(), Datum::Long(file.record_count)),
+                        (
+                            "file_size_in_bytes".to_string(),
+                            Datum::Long(file.size_bytes),
+                        ),
+                        ("equality_ids".to_string(), equality_ids),
+                        ("sort_order_id".to_string(), Datum::Null),
+                    ]),
+                ),
+            ])
+        })
+        .collect();
+    avro::write_container(
+        &manifest_entry_schema(),
+        &[
+            ("schema", table_schema.to_string()),
+            ("schema-id", schema_id.to_string()),
+            ("partition-spec", "[]".to_string()),
+            ("partition-spec-id", "0".to_string()),
+            ("format-version", "2".to_string()),
+            (
+                "content",
+                if deletes { "deletes" } else { "data" }.to_string(),
+            ),
+        ],
+        &entries,
+    )
+}
+
+impl ManifestFile {
+    /// Manifest list entry of a manifest written by `write_manifest`.
+    pub(crate) fn added(
+        path: String,
+        length: i64,
+        files: &[DataFile],
+        snapshot_id: i64,
+        sequence_number: i64,
+    ) -> Self {
+        let deletes = files.iter().any(|f| f.content != FileContent::Data);
+        Self {
+            path,
+            length,
+            partition_spec_id: 0,
+            content: deletes as i32,
+            sequence_number,
+            min_sequence_number: sequence_number,
+            added_snapshot_id: snapshot_id,
+            added_files_count: files.len() as i32,
+            existing_files_count: 0,
+            deleted_files_count: 0,
+            added_rows_count: files.iter().map(|f| f.record_count).sum(),
+            existing_rows_count: 0,
+            deleted_rows_count: 0,
+        }
+    }
+
+    fn to_datum(&self) -> Datum {
+        Datum::Record(vec![
+            (
+                "manifest_path".to_string(),
+                Datum::String(self.path.clone()),
+            ),
+            ("manifest_length".to_string(), Datum::Long(self.length)),
+            (
+                "partition_spec_id".to_string(),
+                Datum::Int(self.partition_spec_id),
+            ),
+            ("content".to_string(), Datum::Int(self.content)),
+            (
+                "sequence_number".to_string(),
+                Datum::Long(self.sequence_number),
+            ),
+            (
+                "min_sequence_number".to_string(),
+                Datum::Long(self.min_sequence_number),
+            ),
+            (
+                "added_snapshot_id".to_string(),
+                Datum::Long(self.added_snapshot_id),
+            ),
+            (
+                "added_files_count".to_string(),
+                Datum::Int(self.added_files_count),
+            ),
+            (
+                "existing_files_count".to_string(),
+                Datum::Int(self.existing_files_count),
+            ),
+            (
+                "deleted_files_count".to_string(),
+                Datum::Int(self.deleted_files_count),
+            ),
+            (
+                "added_rows_count".to_string(),
+                Datum::Long(self.added_rows_count),
+            ),
+            (
+                "existing_rows_count".to_string(),
+                Datum::Long(self.existing_rows_count),
+            ),
+            (
+                "deleted_rows_count".to_string(),
+                Datum::Long(self.deleted_rows_count),
+            ),
+        ])
+    }
+
+    /// Reads an entry of a manifest list, written by dbmazz or another
+    /// engine. Counts are optional in v1 lists and some writers use the v1
+    /// names (`added_data_files_count`, ...).
+    fn from_datum(datum: &Datum) -> Result<Self> {
+        let long = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| datum.field(name).and_then(Datum::as_long))
+        };
+        let count = |v2: &str, v1: &str| long(&[v2, v1]).unwrap_or(0);
+        let path = datum
+            .field("manifest_path")
+            .and_then(Datum::as_str)
+            .context("Manifest list entry without manifest_path")?
+            .to_string();
+        let sequence_number = long(&["sequence_number"]).unwrap_or(0);
+        Ok(Self {
+            length: long(&["manifest_length"]).context("Manifest list entry without length")?,
+            partition_spec_id: long(&["partition_spec_id"]).unwrap_or(0) as i32,
+            content: long(&["content"]).unwrap_or(0) as i32,
+            sequence_number,
+            min_sequence_number: long(&["min_sequence_number"]).unwrap_or(sequence_number),
+            added_snapshot_id: long(&["added_snapshot_id"]).unwrap_or(0),
+            added_files_count: count("added_files_count", "added_data_files_count") as i32,
+            existing_files_count: count("existing_files_count", "existing_data_files_count") as i32,
+            deleted_files_count: count("deleted_files_count", "deleted_data_files_count") as i32,
+            added_rows_count: long(&["added_rows_count"]).unwrap_or(0),
+            existing_rows_count: long(&["existing_rows_count"]).unwrap_or(0),
+            deleted_rows_count: long(&["deleted_rows_count"]).unwrap_or(0),
+            path,
+        })
+    }
+}
+
+/// A manifest list of `manifests` for a snapshot.
+pub(crate) fn write_manifest_list(
+    manifests: &[ManifestFile],
+    snapshot_id: i64,
+    parent_snapshot_id: Option<i64>,
+    sequence_number: i64,
+) -> Result<Vec<u8>> {
+    let records: Vec<Datum> = manifests.iter().map(ManifestFile::to_datum).collect();
+    let mut metadata = vec![
+        ("snapshot-id", snapshot_id.to_string()),
+        ("sequence-number", sequence_number.to_string()),
+        ("format-version", "2".to_string()),
+    ];
+    if let Some(parent) = parent_snapshot_id {
+        metadata.push(("parent-snapshot-id", parent.to_string()));
+    }
+    avro::write_container(&manifest_file_schema(), &metadata, &records)
+}
+
+/// Entries of a manifest list.
+pub(crate) fn read_manifest_list(data: &[u8]) -> Result<Vec<ManifestFile>> {
+    avro::read_container(data)?
+        .iter()
+        .map(ManifestFile::from_datum)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_list_round_trip() {
+        let files = [
+            DataFile {
+                content: FileContent::PositionDeletes,
+                path: "s3://lake/orders/data/1-pos.parquet".to_string(),
+                record_count: 2,
+                size_bytes: 900,
+                equality_ids: Vec::new(),
+            },
+            DataFile {
+                content: FileContent::EqualityDeletes,
+                path: "s3://lake/orders/data/1-eq.parquet".to_string(),
+                record_count: 5,
+                size_bytes: 1200,
+                equality_ids: vec![1],
+            },
+        ];
+        let manifest = write_manifest(&files, 42, &json!({"type": "struct"}), 0).unwrap();
+        let entries = avro::read_container(&manifest).unwrap();
+        assert_eq!(entries.len(), 2);
+        let data_file = entries[1].field("data_file").unwrap();
+        assert_eq!(data_file.field("content"), Some(&Datum::Int(2)));
+        assert_eq!(
+            data_file.field("equality_ids"),
+            Some(&Datum::Array(vec![Datum::Int(1)]))
+        );
+        assert_eq!(entries[0].field("sequence_number"), Some(&Datum::Null));
+
+        let added = ManifestFile::added(
+            "s3://lake/orders/metadata/42-m1.avro".to_string(),
+            manifest.len() as i64,
+            &files,
+            42,
+            7,
+        );
+        assert_eq!(added.content, 1);
+        assert_eq!(added.added_rows_count, 7);
+        let list = write_manifest_list(&[added.clone()], 42, Some(41), 7).unwrap();
+        assert_eq!(read_manifest_list(&list).unwrap(), vec![added]);
+    }
+
+    #[test]
+    fn test_reads_v1_names() {
+        let entry = Datum::Record(vec![
+            (
+                "manifest_path".to_string(),
+                Datum::String("s3://lake/m.avro".to_string()),
+            ),
+            ("manifest_length".to_string(), Datum::Long(10)),
+            ("partition_spec_id".to_string(), Datum::Int(0)),
+            ("added_snapshot_id".to_string(), Datum::Long(5)),
+            ("added_data_files_count".to_string(), Datum::Int(3)),
+            ("added_rows_count".to_string(), Datum::Null),
+        ]);
+        let manifest = ManifestFile::from_datum(&entry).unwrap();
+        assert_eq!(manifest.added_files_count, 3);
+        assert_eq!(manifest.sequence_number, 0);
+        assert_eq!(manifest.content, 0);
+    }
+}
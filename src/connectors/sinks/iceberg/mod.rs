@@ -0,0 +1,1137 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! # Iceberg Sink Connector
+//!
+//! This module implements a CDC sink writing Apache Iceberg tables: Parquet
+//! data and delete files on S3, committed as snapshots through an Iceberg
+//! REST catalog (or the AWS Glue Iceberg endpoint). Any engine reading
+//! Iceberg v2 (Spark, Trino, Flink, DuckDB, Snowflake, ...) sees the tables
+//! as of the last committed batch.
+//!
+//! ## Features
+//!
+//! - **Upserts by key**: tables with identifier fields (the source primary
+//!   key, set by setup) replace the row with the same key on every insert
+//!   and update, so a batch replayed after a restart doesn't duplicate rows
+//! - **Row-level deletes**: deletes and updates write delete files
+//!   (merge-on-read): position deletes for rows written earlier in the same
+//!   batch, equality deletes on the key for rows of earlier snapshots. Tables
+//!   without a key delete by every column, which needs
+//!   `REPLICA IDENTITY FULL` (set by setup)
+//! - **TOAST handling**: unchanged TOAST columns are filled from the old row
+//!   image or an earlier change to the same key in the batch; a value found
+//!   in neither fails the batch, since the stored row can't be read back
+//! - **Schema evolution**: added source columns become optional fields via
+//!   `add-schema` / `set-current-schema` table updates
+//! - **Checkpoints**: each snapshot's summary records the LSN of its batch
+//!   (`dbmazz.lsn`)
+//!
+//! ## Batches
+//!
+//! Each batch becomes one snapshot per table it touches: at most one data
+//! file, one position delete file and one equality delete file, their
+//! manifests, and a manifest list carrying the manifests of the previous
+//! snapshot. The commit requires `main` to still point at that snapshot;
+//! when another writer got there first the manifest list is rebuilt on the
+//! new snapshot and the commit retried. Every snapshot is a commit to the
+//! catalog, so larger, less frequent batches make for fewer small files.
+//!
+//! Tables must be unpartitioned and in format version 2, as setup creates
+//! them. Data files go under the table's `location`.
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use dbmazz::connectors::sinks::iceberg::IcebergSink;
+//!
+//! // SINK_TYPE=iceberg SINK_URL=http://polaris:8181/api/catalog SINK_DATABASE=cdc
+//! let mut sink = IcebergSink::new(&config)?;
+//! sink.write_batch(records).await?;
+//! ```
+
+mod avro;
+pub(crate) mod catalog;
+mod files;
+mod manifest;
+pub(crate) mod types;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::is_internal_table;
+use crate::config::{HttpClientConfig, S3Location, SinkConfig, SinkTableName, TableNaming};
+use crate::connectors::s3::S3Client;
+use crate::core::schema_diff::SchemaDiff;
+use crate::core::sink_error::SinkError;
+use crate::core::traits::StageFormat;
+use crate::core::{
+    pool, CdcRecord, ColumnValue, LoadingModel, Sink, SinkCapabilities, SinkResult, SourcePosition,
+    Value,
+};
+use crate::pipeline::schema_cache::SchemaDelta;
+
+pub(crate) use self::catalog::RestCatalog;
+use self::catalog::{CommitOutcome, Field, Schema, TableIdent, TableMetadata};
+use self::files::Column;
+use self::manifest::{DataFile, FileContent, ManifestFile};
+use self::types::IcebergType;
+
+/// CDC audit columns added to all tables
+pub(crate) const AUDIT_COLUMNS: &[(&str, IcebergType, &str)] = &[
+    (
+        "dbmazz_synced_at",
+        IcebergType::TimestampTz,
+        "Timestamp when record was synced",
+    ),
+    (
+        "dbmazz_cdc_version",
+        IcebergType::Long,
+        "Source LSN/position of the change",
+    ),
+];
+
+/// Attempts at a commit that keeps losing to other writers
+const COMMIT_ATTEMPTS: usize = 5;
+
+/// A column to create or add to a table.
+#[derive(Debug, Clone)]
+pub(crate) struct NewColumn {
+    pub name: String,
+    pub iceberg_type: IcebergType,
+    /// Key columns are required; all others optional
+    pub required: bool,
+    pub doc: Option<String>,
+}
+
+/// The audit columns, as optional columns.
+pub(crate) fn audit_columns() -> impl Iterator<Item = NewColumn> {
+    AUDIT_COLUMNS
+        .iter()
+        .map(|(name, iceberg_type, doc)| NewColumn {
+            name: name.to_string(),
+            iceberg_type: *iceberg_type,
+            required: false,
+            doc: Some(doc.to_string()),
+        })
+}
+
+/// Schema of a new table: `columns`, then the audit columns, with ids from
+/// 1 and the required columns as identifier fields.
+pub(crate) fn new_table_schema(columns: &[NewColumn]) -> Schema {
+    let fields: Vec<Field> = columns
+        .iter()
+        .cloned()
+        .chain(audit_columns())
+        .enumerate()
+        .map(|(i, column)| Field {
+            id: i as i32 + 1,
+            name: column.name,
+            required: column.required,
+            field_type: json!(column.iceberg_type.name()),
+            doc: column.doc,
+        })
+        .collect();
+    Schema {
+        schema_id: 0,
+        identifier_field_ids: fields.iter().filter(|f| f.required).map(|f| f.id).collect(),
+        fields,
+    }
+}
+
+/// Adds the `columns` a table lacks, as optional fields of a new current
+/// schema. Returns the names of the columns added.
+pub(crate) async fn add_columns(
+    catalog: &RestCatalog,
+    table: &TableIdent,
+    columns: &[NewColumn],
+) -> Result<Vec<String>> {
+    for _ in 0..COMMIT_ATTEMPTS {
+        let metadata = catalog
+            .load_table(table)
+            .await?
+            .with_context(|| format!("Iceberg table {} doesn't exist", table.label()))?;
+        let current = metadata.current_schema()?;
+        let missing: Vec<&NewColumn> = columns
+            .iter()
+            .filter(|c| current.field(&c.name).is_none())
+            .collect();
+        if missing.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut schema = current.clone();
+        schema.schema_id = metadata
+            .schemas
+            .iter()
+            .map(|s| s.schema_id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let mut last_column_id = metadata.last_column_id;
+        for column in &missing {
+            last_column_id += 1;
+            // Existing rows have no value for it, so it is always optional
+            schema.fields.push(Field {
+                id: last_column_id,
+                name: column.name.clone(),
+                required: false,
+                field_type: json!(column.iceberg_type.name()),
+                doc: column.doc.clone(),
+            });
+        }
+        let requirements = vec![
+            json!({"type": "assert-current-schema-id", "current-schema-id": metadata.current_schema_id}),
+            json!({"type": "assert-last-assigned-field-id", "last-assigned-field-id": metadata.last_column_id}),
+        ];
+        let updates = vec![
+            json!({"action": "add-schema", "schema": schema.to_json(), "last-column-id": last_column_id}),
+            // -1: the schema added by this commit
+            json!({"action": "set-current-schema", "schema-id": -1}),
+        ];
+        match catalog.commit(table, requirements, updates).await? {
+            CommitOutcome::Committed(_) => {
+                return Ok(missing.iter().map(|c| c.name.clone()).collect())
+            }
+            CommitOutcome::Conflict => continue,
+        }
+    }
+    anyhow::bail!(
+        "Failed to evolve the schema of Iceberg table {}: other writers kept changing it",
+        table.label()
+    )
+}
+
+/// Iceberg sink connector implementing the Sink trait.
+pub struct IcebergSink {
+    catalog: RestCatalog,
+    /// How source tables map to namespaces and tables
+    naming: TableNaming,
+    /// `SINK_DATABASE`: namespace of tables without their own
+    namespace: Vec<String>,
+    /// Credentials for the buckets of table locations
+    storage: S3Location,
+    request_timeout: Duration,
+    /// Proxy and TLS trust of the S3 clients
+    http: HttpClientConfig,
+    /// Table metadata as of the last load or commit, by sink table label
+    tables: Mutex<HashMap<String, Arc<TableMetadata>>>,
+    /// S3 clients by bucket
+    buckets: Mutex<HashMap<String, Arc<S3Client>>>,
+}
+
+impl IcebergSink {
+    pub fn new(config: &SinkConfig) -> Result<Self> {
+        let iceberg = config
+            .iceberg
+            .as_ref()
+            .context("Iceberg sink configuration is missing")?;
+        info!(
+            "Iceberg sink: catalog {}, namespace {}",
+            config.url, config.database
+        );
+        Ok(Self {
+            catalog: RestCatalog::new(config)?,
+            naming: config.naming.clone(),
+            namespace: split_namespace(&config.database),
+            storage: iceberg.storage.clone(),
+            request_timeout: config.request_timeout,
+            http: config.http.clone(),
+            tables: Mutex::new(HashMap::new()),
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn table_ident(&self, dest: &SinkTableName) -> TableIdent {
+        table_ident(dest, &self.namespace)
+    }
+
+    /// Metadata of a sink table, loaded on first use.
+    async fn table(&self, dest: &SinkTableName) -> Result<Arc<TableMetadata>> {
+        let label = dest.label();
+        if let Some(metadata) = self.tables.lock().get(&label) {
+            return Ok(metadata.clone());
+        }
+        let ident = self.table_ident(dest);
+        let metadata = self.catalog.load_table(&ident).await?.ok_or_else(|| {
+            SinkError::schema_mismatch(
+                "iceberg",
+                format!("table {} doesn't exist in the catalog", ident.label()),
+            )
+        })?;
+        if metadata.format_version < 2 {
+            return Err(SinkError::terminal(
+                "iceberg",
+                format!(
+                    "table {} is format version {}; row-level deletes need version 2",
+                    ident.label(),
+                    metadata.format_version
+                ),
+            )
+            .into());
+        }
+        if metadata.is_partitioned() {
+            return Err(SinkError::terminal(
+                "iceberg",
+                format!(
+                    "table {} is partitioned, which the sink doesn't write",
+                    ident.label()
+                ),
+            )
+            .into());
+        }
+        let metadata = Arc::new(metadata);
+        self.tables.lock().insert(label, metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Client for `bucket`.
+    fn bucket(&self, bucket: &str) -> Result<Arc<S3Client>> {
+        let mut buckets = self.buckets.lock();
+        if let Some(client) = buckets.get(bucket) {
+            return Ok(client.clone());
+        }
+        let location = S3Location {
+            bucket: bucket.to_string(),
+            prefix: String::new(),
+            ..self.storage.clone()
+        };
+        let client = Arc::new(S3Client::new(location, self.request_timeout, &self.http)?);
+        buckets.insert(bucket.to_string(), client.clone());
+        Ok(client)
+    }
+
+    async fn put(&self, uri: &str, body: Vec<u8>, content_type: &str) -> Result<()> {
+        let (bucket, key) = split_s3_uri(uri)?;
+        self.bucket(bucket)?
+            .put_object(key, body, content_type)
+            .await
+            .with_context(|| format!("Failed to upload {}", uri))
+    }
+
+    async fn get(&self, uri: &str) -> Result<Vec<u8>> {
+        let (bucket, key) = split_s3_uri(uri)?;
+        self.bucket(bucket)?
+            .get_object(key)
+            .await?
+            .with_context(|| format!("{} doesn't exist", uri))
+    }
+
+    /// Writes the changes of one table as a snapshot. Returns the rows
+    /// written and the bytes uploaded.
+    async fn write_table(
+        &self,
+        dest: &SinkTableName,
+        records: Vec<CdcRecord>,
+        position: Option<&SourcePosition>,
+        synced_at: i64,
+    ) -> Result<(usize, u64)> {
+        let mut metadata = self.table(dest).await?;
+        let layout = Layout::new(metadata.current_schema()?)?;
+        let ident = self.table_ident(dest);
+        let label = ident.label();
+
+        let encode_layout = layout.clone();
+        let changes = tokio::task::spawn_blocking(move || {
+            let changes = TableChanges::encode(&encode_layout, &records, synced_at, &label);
+            pool::recycle(records);
+            changes
+        })
+        .await
+        .context("Iceberg encoder task failed")??;
+
+        let location = metadata.location.trim_end_matches('/').to_string();
+        let file_id = random_hex(16)?;
+        let snapshot_id = random_snapshot_id()?;
+        let mut bytes = 0u64;
+
+        // Data and delete files
+        let data_path = format!("{}/data/{}-data.parquet", location, file_id);
+        let mut data_files = Vec::new();
+        let mut delete_files = Vec::new();
+        let row_count = changes.rows.len();
+        if !changes.rows.is_empty() {
+            let body = files::write_parquet(&layout.columns, &changes.rows)?;
+            data_files.push(
+                self.upload(&data_path, body, FileContent::Data, &[])
+                    .await?,
+            );
+        }
+        if !changes.deleted_positions.is_empty() {
+            let rows = files::position_delete_rows(&data_path, &changes.deleted_positions);
+            let body = files::write_parquet(&files::position_delete_columns(), &rows)?;
+            let path = format!("{}/data/{}-pos-deletes.parquet", location, file_id);
+            delete_files.push(
+                self.upload(&path, body, FileContent::PositionDeletes, &[])
+                    .await?,
+            );
+        }
+        if !changes.equality_deletes.is_empty() {
+            let columns: Vec<Column> = layout
+                .identity
+                .iter()
+                .map(|&i| layout.columns[i].clone())
+                .collect();
+            let body = files::write_parquet(&columns, &changes.equality_deletes)?;
+            let path = format!("{}/data/{}-eq-deletes.parquet", location, file_id);
+            let ids: Vec<i32> = columns.iter().map(|(field, _)| field.id).collect();
+            delete_files.push(
+                self.upload(&path, body, FileContent::EqualityDeletes, &ids)
+                    .await?,
+            );
+        }
+        if data_files.is_empty() && delete_files.is_empty() {
+            return Ok((0, 0));
+        }
+
+        // Manifests, whose entries inherit the sequence number of the commit
+        let schema_json = metadata.current_schema()?.to_json();
+        let mut manifests = Vec::new();
+        for (i, added) in [&data_files, &delete_files].into_iter().enumerate() {
+            if added.is_empty() {
+                continue;
+            }
+            let body = manifest::write_manifest(
+                added,
+                snapshot_id,
+                &schema_json,
+                metadata.current_schema_id,
+            )?;
+            let path = format!("{}/metadata/{}-m{}.avro", location, file_id, i);
+            let length = body.len() as i64;
+            bytes += body.len() as u64;
+            self.put(&path, body, "avro/binary").await?;
+            manifests.push((path, length, added.clone()));
+        }
+        bytes += data_files
+            .iter()
+            .chain(&delete_files)
+            .map(|f| f.size_bytes as u64)
+            .sum::<u64>();
+
+        let mut summary = json!({
+            "operation": if delete_files.is_empty() { "append" } else { "overwrite" },
+            "added-data-files": data_files.len().to_string(),
+            "added-records": row_count.to_string(),
+            "added-delete-files": delete_files.len().to_string(),
+            "added-position-deletes": changes.deleted_positions.len().to_string(),
+            "added-equality-deletes": changes.equality_deletes.len().to_string(),
+        });
+        if let Some(SourcePosition::Lsn(lsn)) = position {
+            summary["dbmazz.lsn"] = json!(lsn.to_string());
+        }
+
+        for attempt in 0..COMMIT_ATTEMPTS {
+            let parent = metadata.current_snapshot();
+            let sequence_number = metadata.last_sequence_number + 1;
+            let mut list: Vec<ManifestFile> = manifests
+                .iter()
+                .map(|(path, length, added)| {
+                    ManifestFile::added(path.clone(), *length, added, snapshot_id, sequence_number)
+                })
+                .collect();
+            if let Some(parent_list) = parent.and_then(|s| s.manifest_list.as_deref()) {
+                let parent_manifests = manifest::read_manifest_list(&self.get(parent_list).await?)
+                    .with_context(|| format!("Failed to read manifest list {}", parent_list))?;
+                list.extend(parent_manifests);
+            }
+            let body = manifest::write_manifest_list(
+                &list,
+                snapshot_id,
+                parent.map(|s| s.snapshot_id),
+                sequence_number,
+            )?;
+            let list_path = format!(
+                "{}/metadata/snap-{}-{}-{}.avro",
+                location, snapshot_id, attempt, file_id
+            );
+            bytes += body.len() as u64;
+            self.put(&list_path, body, "avro/binary").await?;
+
+            let mut snapshot = json!({
+                "snapshot-id": snapshot_id,
+                "sequence-number": sequence_number,
+                "timestamp-ms": chrono::Utc::now().timestamp_millis(),
+                "manifest-list": list_path,
+                "summary": summary,
+                "schema-id": metadata.current_schema_id,
+            });
+            if let Some(parent) = parent {
+                snapshot["parent-snapshot-id"] = json!(parent.snapshot_id);
+            }
+            let requirements = vec![json!({
+                "type": "assert-ref-snapshot-id",
+                "ref": "main",
+                "snapshot-id": parent.map(|s| s.snapshot_id),
+            })];
+            let updates = vec![
+                json!({"action": "add-snapshot", "snapshot": snapshot}),
+                json!({
+                    "action": "set-snapshot-ref",
+                    "ref-name": "main",
+                    "type": "branch",
+                    "snapshot-id": snapshot_id,
+                }),
+            ];
+            match self.catalog.commit(&ident, requirements, updates).await? {
+                CommitOutcome::Committed(committed) => {
+                    self.tables
+                        .lock()
+                        .insert(dest.label(), Arc::from(committed));
+                    return Ok((row_count, bytes));
+                }
+                CommitOutcome::Conflict => {
+                    warn!(
+                        "Iceberg table {} changed during the commit, retrying on its new snapshot",
+                        ident.label()
+                    );
+                    self.tables.lock().remove(&dest.label());
+                    metadata = self.table(dest).await?;
+                }
+            }
+        }
+        Err(SinkError::retryable(
+            "iceberg",
+            format!(
+                "commit to {} kept conflicting with other writers",
+                ident.label()
+            ),
+        )
+        .into())
+    }
+
+    /// Uploads a Parquet file and describes it for its manifest.
+    async fn upload(
+        &self,
+        path: &str,
+        body: Vec<u8>,
+        content: FileContent,
+        equality_ids: &[i32],
+    ) -> Result<DataFile> {
+        let size_bytes = body.len() as i64;
+        let record_count = parquet_row_count(&body)?;
+        self.put(path, body, "application/vnd.apache.parquet")
+            .await?;
+        Ok(DataFile {
+            content,
+            path: path.to_string(),
+            record_count,
+            size_bytes,
+            equality_ids: equality_ids.to_vec(),
+        })
+    }
+}
+
+/// Namespace levels of `a.b`.
+fn split_namespace(namespace: &str) -> Vec<String> {
+    namespace.split('.').map(str::to_string).collect()
+}
+
+/// Catalog identifier of a sink table: its database is the namespace, or
+/// `default_namespace` without one.
+pub(crate) fn table_ident(dest: &SinkTableName, default_namespace: &[String]) -> TableIdent {
+    TableIdent {
+        namespace: dest
+            .database
+            .as_deref()
+            .map(split_namespace)
+            .unwrap_or_else(|| default_namespace.to_vec()),
+        name: dest.table.clone(),
+    }
+}
+
+/// Bucket and key of an `s3://` (or `s3a://`, `s3n://`) URI.
+fn split_s3_uri(uri: &str) -> Result<(&str, &str)> {
+    let path = ["s3://", "s3a://", "s3n://"]
+        .iter()
+        .find_map(|scheme| uri.strip_prefix(scheme))
+        .with_context(|| format!("Iceberg sink only writes to S3, not '{}'", uri))?;
+    path.split_once('/')
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .with_context(|| format!("Invalid S3 location '{}'", uri))
+}
+
+/// Rows in a Parquet file, read back from its footer.
+fn parquet_row_count(file: &[u8]) -> Result<i64> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    let reader = SerializedFileReader::new(bytes::Bytes::copy_from_slice(file))
+        .context("Failed to read back Parquet footer")?;
+    Ok(reader.metadata().file_metadata().num_rows())
+}
+
+fn random_hex(len: usize) -> Result<String> {
+    let mut bytes = vec![0u8; len];
+    openssl::rand::rand_bytes(&mut bytes).context("Failed to generate file id")?;
+    Ok(hex::encode(bytes))
+}
+
+/// Snapshot ids are positive 64-bit integers, chosen at random.
+fn random_snapshot_id() -> Result<i64> {
+    let mut bytes = [0u8; 8];
+    openssl::rand::rand_bytes(&mut bytes).context("Failed to generate snapshot id")?;
+    Ok((i64::from_le_bytes(bytes) & i64::MAX).max(1))
+}
+
+/// Columns of a table as the sink writes them.
+#[derive(Debug, Clone)]
+struct Layout {
+    columns: Vec<Column>,
+    /// Index in `columns` of each column name
+    index: HashMap<String, usize>,
+    /// Indexes of the columns identifying a row: the identifier fields, or
+    /// every source column of a table without them
+    identity: Vec<usize>,
+    /// Identified by identifier fields: a written row replaces the row with
+    /// the same key
+    upsert: bool,
+    synced_at: Option<usize>,
+    cdc_version: Option<usize>,
+}
+
+impl Layout {
+    fn new(schema: &Schema) -> Result<Self> {
+        let columns = schema
+            .fields
+            .iter()
+            .map(|field| {
+                let iceberg_type = IcebergType::parse(&field.field_type).ok_or_else(|| {
+                    SinkError::terminal(
+                        "iceberg",
+                        format!(
+                            "column {} has type {}, which the sink doesn't write",
+                            field.name, field.field_type
+                        ),
+                    )
+                })?;
+                Ok((field.clone(), iceberg_type))
+            })
+            .collect::<Result<Vec<Column>>>()?;
+        let index: HashMap<String, usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, (field, _))| (field.name.clone(), i))
+            .collect();
+        let is_audit = |name: &str| AUDIT_COLUMNS.iter().any(|(audit, ..)| *audit == name);
+        let upsert = !schema.identifier_field_ids.is_empty();
+        let identity = columns
+            .iter()
+            .enumerate()
+            .filter(|(_, (field, _))| match upsert {
+                true => schema.identifier_field_ids.contains(&field.id),
+                false => !is_audit(&field.name),
+            })
+            .map(|(i, _)| i)
+            .collect();
+        Ok(Self {
+            synced_at: index.get(AUDIT_COLUMNS[0].0).copied(),
+            cdc_version: index.get(AUDIT_COLUMNS[1].0).copied(),
+            columns,
+            index,
+            identity,
+            upsert,
+        })
+    }
+
+    /// A row of the table from a change's columns. Columns the table lacks
+    /// are a schema mismatch; columns the change lacks are NULL.
+    fn row(&self, columns: &[ColumnValue], table: &str) -> Result<Vec<Value>> {
+        let mut row = vec![Value::Null; self.columns.len()];
+        for column in columns {
+            match self.index.get(&*column.name) {
+                Some(&i) => row[i] = column.value.clone(),
+                None => {
+                    return Err(SinkError::schema_mismatch(
+                        "iceberg",
+                        format!("table {} has no column {}", table, column.name),
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(row)
+    }
+
+    fn identity_of(&self, row: &[Value]) -> Vec<Value> {
+        self.identity.iter().map(|&i| row[i].clone()).collect()
+    }
+
+    fn stamp(&self, row: &mut [Value], synced_at: i64, position: &SourcePosition) {
+        if let Some(i) = self.synced_at {
+            row[i] = Value::Timestamp(synced_at);
+        }
+        if let (Some(i), SourcePosition::Lsn(lsn)) = (self.cdc_version, position) {
+            row[i] = Value::Int64(*lsn as i64);
+        }
+    }
+}
+
+/// Rows and deletes of one table in a batch.
+#[derive(Debug, Default)]
+struct TableChanges {
+    /// Rows of the data file, in order
+    rows: Vec<Vec<Value>>,
+    /// Positions in `rows` of the live rows of each identity
+    written: HashMap<String, Vec<usize>>,
+    /// Positions in `rows` deleted later in the batch
+    deleted_positions: Vec<i64>,
+    /// Identities to delete from earlier snapshots
+    equality_deletes: Vec<Vec<Value>>,
+    equality_deleted: HashSet<String>,
+}
+
+impl TableChanges {
+    fn encode(layout: &Layout, records: &[CdcRecord], synced_at: i64, table: &str) -> Result<Self> {
+        let mut changes = Self::default();
+        for record in records {
+            match record {
+                CdcRecord::Insert {
+                    columns, position, ..
+                } => {
+                    let mut row = layout.row(columns, table)?;
+                    layout.stamp(&mut row, synced_at, position);
+                    changes.insert(layout, row);
+                }
+                CdcRecord::Update {
+                    old_columns,
+                    new_columns,
+                    position,
+                    ..
+                } => {
+                    let old = old_columns
+                        .as_deref()
+                        .map(|old| layout.row(old, table))
+                        .transpose()?;
+                    let mut row = layout.row(new_columns, table)?;
+                    layout.stamp(&mut row, synced_at, position);
+                    changes.update(layout, old, row, table)?;
+                }
+                CdcRecord::Delete { columns, .. } => {
+                    let row = layout.row(columns, table)?;
+                    changes.delete(layout.identity_of(&row));
+                }
+                _ => {}
+            }
+        }
+        Ok(changes)
+    }
+
+    fn insert(&mut self, layout: &Layout, row: Vec<Value>) {
+        let identity = layout.identity_of(&row);
+        let lookup = lookup_key(&identity);
+        if layout.upsert {
+            self.delete(identity);
+        }
+        self.written
+            .entry(lookup)
+            .or_default()
+            .push(self.rows.len());
+        self.rows.push(row);
+    }
+
+    fn update(
+        &mut self,
+        layout: &Layout,
+        old: Option<Vec<Value>>,
+        mut row: Vec<Value>,
+        table: &str,
+    ) -> Result<()> {
+        let old_identity = layout.identity_of(old.as_deref().unwrap_or(&row));
+        let earlier = self
+            .written
+            .get(&lookup_key(&old_identity))
+            .and_then(|positions| positions.last())
+            .map(|&pos| &self.rows[pos]);
+        for i in 0..row.len() {
+            if !row[i].is_unchanged() {
+                continue;
+            }
+            // A key-only old image has NULL in every other column, so only
+            // non-NULL old values are known to be the stored ones
+            let known = old
+                .as_ref()
+                .map(|old| &old[i])
+                .filter(|v| !v.is_null() && !v.is_unchanged())
+                .or_else(|| earlier.map(|earlier| &earlier[i]));
+            match known {
+                Some(value) => row[i] = value.clone(),
+                None => {
+                    return Err(SinkError::terminal(
+                        "iceberg",
+                        format!(
+                            "unchanged TOAST value of {}.{} isn't in the change; \
+                             set REPLICA IDENTITY FULL on the source table",
+                            table, layout.columns[i].0.name
+                        ),
+                    )
+                    .into())
+                }
+            }
+        }
+        self.delete(old_identity);
+        self.insert(layout, row);
+        Ok(())
+    }
+
+    /// Deletes the row with `identity`: by position when it was written in
+    /// this batch, else by equality from earlier snapshots.
+    fn delete(&mut self, identity: Vec<Value>) {
+        let lookup = lookup_key(&identity);
+        if let Some(pos) = self.written.get_mut(&lookup).and_then(Vec::pop) {
+            self.deleted_positions.push(pos as i64);
+            return;
+        }
+        if self.equality_deleted.insert(lookup) {
+            self.equality_deletes.push(identity);
+        }
+    }
+}
+
+fn lookup_key(identity: &[Value]) -> String {
+    serde_json::to_string(identity).unwrap_or_default()
+}
+
+#[async_trait]
+impl Sink for IcebergSink {
+    fn name(&self) -> &'static str {
+        "iceberg"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            supports_upsert: true,
+            supports_delete: true,
+            supports_schema_evolution: true,
+            supports_transactions: false,
+            loading_model: LoadingModel::StagedBatch {
+                stage_format: StageFormat::Parquet,
+            },
+            min_batch_size: Some(1),
+            max_batch_size: Some(1_000_000),
+            optimal_flush_interval_ms: 60_000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        self.catalog
+            .check()
+            .await
+            .context("Iceberg catalog is not reachable")
+    }
+
+    async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult> {
+        let last_position = records.iter().rev().find_map(|r| match r {
+            CdcRecord::Insert { position, .. }
+            | CdcRecord::Update { position, .. }
+            | CdcRecord::Delete { position, .. }
+            | CdcRecord::Commit { position, .. }
+            | CdcRecord::Heartbeat { position, .. } => Some(position.clone()),
+            _ => None,
+        });
+        let synced_at = chrono::Utc::now().timestamp_micros();
+
+        // Changes of each table, in order of first appearance
+        let mut tables: Vec<(SinkTableName, Vec<CdcRecord>)> = Vec::new();
+        for record in records {
+            let table = match &record {
+                CdcRecord::Insert { table, .. }
+                | CdcRecord::Update { table, .. }
+                | CdcRecord::Delete { table, .. } => table,
+                // Schema changes arrive as deltas; transactions and heartbeats aren't written
+                _ => continue,
+            };
+            if is_internal_table(&table.name) {
+                continue;
+            }
+            let dest = self.naming.sink_table(&table.qualified_name());
+            match tables.iter_mut().find(|(d, _)| *d == dest) {
+                Some((_, changes)) => changes.push(record),
+                None => tables.push((dest, vec![record])),
+            }
+        }
+
+        let mut total_written = 0;
+        let mut total_bytes = 0u64;
+        for (dest, changes) in tables {
+            let (written, bytes) = self
+                .write_table(&dest, changes, last_position.as_ref(), synced_at)
+                .await
+                .with_context(|| format!("Failed to write Iceberg table {}", dest.label()))?;
+            total_written += written;
+            total_bytes += bytes;
+        }
+
+        Ok(SinkResult {
+            records_written: total_written,
+            bytes_written: total_bytes,
+            last_position,
+            ..Default::default()
+        })
+    }
+
+    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+        if is_internal_table(&delta.table_name) || delta.added_columns.is_empty() {
+            return Ok(());
+        }
+        let dest = self.naming.sink_table(&delta.qualified_name());
+        let ident = self.table_ident(&dest);
+        let columns: Vec<NewColumn> = delta
+            .added_columns
+            .iter()
+            .map(|added| NewColumn {
+                name: added.name.clone(),
+                iceberg_type: IcebergType::for_pg_type(added.pg_type_id),
+                required: false,
+                doc: delta.column(&added.name).and_then(|c| c.comment.clone()),
+            })
+            .collect();
+        let added = add_columns(&self.catalog, &ident, &columns)
+            .await
+            .with_context(|| format!("Failed to add columns to Iceberg table {}", ident.label()))?;
+        for name in &added {
+            info!("[SCHEMA] Adding column {} to {}", name, ident.label());
+        }
+        self.tables.lock().remove(&dest.label());
+        Ok(())
+    }
+
+    fn invalidate_schema(&mut self, table: Option<&str>) {
+        match table {
+            Some(table) => {
+                let label = self.naming.sink_table(table).label();
+                self.tables.lock().remove(&label);
+            }
+            None => self.tables.lock().clear(),
+        }
+    }
+
+    async fn schema_diff(&self, schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        let source = schema.qualified_name();
+        let dest = self.naming.sink_table(&source);
+        let ident = self.table_ident(&dest);
+        let metadata = self.catalog.load_table(&ident).await?;
+        let actual: Option<Vec<(String, String)>> = match &metadata {
+            Some(metadata) => Some(
+                metadata
+                    .current_schema()?
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        let field_type = f
+                            .field_type
+                            .as_str()
+                            .map_or_else(|| f.field_type.to_string(), str::to_string);
+                        (f.name.clone(), field_type)
+                    })
+                    .collect(),
+            ),
+            None => None,
+        };
+        let expected: Vec<(String, String)> = schema
+            .added_columns
+            .iter()
+            .map(|c| {
+                (
+                    c.name.clone(),
+                    IcebergType::for_pg_type(c.pg_type_id).name(),
+                )
+            })
+            .collect();
+        Ok(Some(SchemaDiff::compare(
+            &source,
+            &ident.label(),
+            &expected,
+            actual.as_deref(),
+        )))
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        // Every batch is committed before write_batch returns
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::TableRef;
+
+    fn layout(upsert: bool) -> Layout {
+        let schema = new_table_schema(&[
+            NewColumn {
+                name: "id".to_string(),
+                iceberg_type: IcebergType::Long,
+                required: upsert,
+                doc: None,
+            },
+            NewColumn {
+                name: "body".to_string(),
+                iceberg_type: IcebergType::String,
+                required: false,
+                doc: None,
+            },
+        ]);
+        Layout::new(&schema).unwrap()
+    }
+
+    fn columns(id: i64, body: Value) -> Vec<ColumnValue> {
+        vec![
+            ColumnValue::new("id".into(), Value::Int64(id)),
+            ColumnValue::new("body".into(), body),
+        ]
+    }
+
+    fn text(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    fn table() -> TableRef {
+        TableRef::new(Some("public".into()), "docs".into())
+    }
+
+    fn insert(id: i64, body: &str) -> CdcRecord {
+        CdcRecord::Insert {
+            table: table(),
+            columns: columns(id, text(body)),
+            position: SourcePosition::Lsn(0x10),
+        }
+    }
+
+    #[test]
+    fn test_new_table_schema() {
+        let schema = new_table_schema(&[NewColumn {
+            name: "id".to_string(),
+            iceberg_type: IcebergType::Long,
+            required: true,
+            doc: None,
+        }]);
+        let names: Vec<&str> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["id", "dbmazz_synced_at", "dbmazz_cdc_version"]);
+        assert_eq!(schema.identifier_field_ids, vec![1]);
+        assert_eq!(schema.fields[1].field_type, json!("timestamptz"));
+    }
+
+    #[test]
+    fn test_keyed_changes() {
+        let layout = layout(true);
+        let records = vec![
+            insert(1, "a"),
+            insert(2, "b"),
+            // Unchanged TOAST body, filled from the insert above
+            CdcRecord::Update {
+                table: table(),
+                old_columns: None,
+                new_columns: columns(1, Value::Unchanged),
+                position: SourcePosition::Lsn(0x20),
+            },
+            CdcRecord::Delete {
+                table: table(),
+                columns: columns(2, Value::Null),
+                position: SourcePosition::Lsn(0x30),
+            },
+            // Written in an earlier snapshot
+            CdcRecord::Delete {
+                table: table(),
+                columns: columns(9, Value::Null),
+                position: SourcePosition::Lsn(0x30),
+            },
+        ];
+        let changes = TableChanges::encode(&layout, &records, 1_000, "cdc.docs").unwrap();
+
+        assert_eq!(changes.rows.len(), 3);
+        assert_eq!(changes.rows[2][1], text("a"));
+        assert_eq!(changes.rows[2][3], Value::Int64(0x20));
+        // The first image of 1 and the insert of 2
+        assert_eq!(changes.deleted_positions, vec![0, 1]);
+        // Inserts replace rows of earlier snapshots; 9 was never written here
+        assert_eq!(
+            changes.equality_deletes,
+            vec![
+                vec![Value::Int64(1)],
+                vec![Value::Int64(2)],
+                vec![Value::Int64(9)]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyless_changes() {
+        let layout = layout(false);
+        let records = vec![
+            insert(1, "a"),
+            CdcRecord::Update {
+                table: table(),
+                old_columns: Some(columns(7, text("old"))),
+                new_columns: columns(7, text("new")),
+                position: SourcePosition::Lsn(0x20),
+            },
+            CdcRecord::Delete {
+                table: table(),
+                columns: columns(1, text("a")),
+                position: SourcePosition::Lsn(0x30),
+            },
+        ];
+        let changes = TableChanges::encode(&layout, &records, 1_000, "cdc.docs").unwrap();
+
+        assert_eq!(changes.rows.len(), 2);
+        assert_eq!(changes.deleted_positions, vec![0]);
+        // Matched on every source column, not the audit columns
+        assert_eq!(
+            changes.equality_deletes,
+            vec![vec![Value::Int64(7), text("old")]]
+        );
+    }
+
+    #[test]
+    fn test_unknown_toast_and_columns_fail() {
+        let layout = layout(true);
+        let update = CdcRecord::Update {
+            table: table(),
+            old_columns: None,
+            new_columns: columns(3, Value::Unchanged),
+            position: SourcePosition::Lsn(0x20),
+        };
+        let err = TableChanges::encode(&layout, &[update], 0, "cdc.docs").unwrap_err();
+        assert!(err.to_string().contains("REPLICA IDENTITY FULL"), "{}", err);
+
+        let added = CdcRecord::Insert {
+            table: table(),
+            columns: vec![ColumnValue::new("extra".into(), Value::Int64(1))],
+            position: SourcePosition::Lsn(0x20),
+        };
+        let err = TableChanges::encode(&layout, &[added], 0, "cdc.docs").unwrap_err();
+        assert!(crate::core::sink_error::is_schema_mismatch(&err));
+    }
+
+    #[test]
+    fn test_split_s3_uri() {
+        assert_eq!(
+            split_s3_uri("s3://lake/cdc/orders/data/a.parquet").unwrap(),
+            ("lake", "cdc/orders/data/a.parquet")
+        );
+        assert_eq!(split_s3_uri("s3a://lake/x").unwrap(), ("lake", "x"));
+        assert!(split_s3_uri("gs://lake/x").is_err());
+        assert!(split_s3_uri("s3://lake").is_err());
+    }
+
+    #[test]
+    fn test_table_ident() {
+        let namespace = split_namespace("lake.cdc");
+        let dest = SinkTableName {
+            database: None,
+            table: "orders".to_string(),
+        };
+        assert_eq!(table_ident(&dest, &namespace).label(), "lake.cdc.orders");
+        let dest = SinkTableName {
+            database: Some("billing".to_string()),
+            table: "invoices".to_string(),
+        };
+        assert_eq!(table_ident(&dest, &namespace).namespace, vec!["billing"]);
+    }
+}
@@ -0,0 +1,418 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Iceberg Type Mappings
+//!
+//! Column types are chosen from the PostgreSQL type OID:
+//!
+//! | PostgreSQL | Iceberg |
+//! |------------|---------|
+//! | boolean | boolean |
+//! | smallint, integer | int |
+//! | bigint | long |
+//! | real, double precision | float, double |
+//! | numeric, money | decimal(38, 9) |
+//! | date, time | date, time |
+//! | timestamp, timestamptz | timestamp, timestamptz |
+//! | bytea | binary |
+//! | domains | as their base type |
+//! | everything else (text, json, uuid, arrays, enums) | string |
+//!
+//! Values arrive as the text pgoutput sent, or already typed by the
+//! adapter, and are converted to the Arrow arrays the Parquet writer takes.
+
+use anyhow::{Context, Result};
+use arrow_array::builder::{
+    BinaryBuilder, BooleanBuilder, Date32Builder, Decimal128Builder, Float32Builder,
+    Float64Builder, Int32Builder, Int64Builder, StringBuilder, Time64MicrosecondBuilder,
+    TimestampMicrosecondBuilder,
+};
+use arrow_array::ArrayRef;
+use arrow_schema::{DataType as ArrowType, TimeUnit};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use std::sync::Arc;
+
+use crate::core::Value;
+
+/// Precision and scale of the decimals numeric columns become
+const DECIMAL_PRECISION: u8 = 38;
+const DECIMAL_SCALE: u8 = 9;
+
+/// An Iceberg primitive type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IcebergType {
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Decimal { precision: u8, scale: u8 },
+    Date,
+    Time,
+    Timestamp,
+    TimestampTz,
+    String,
+    Binary,
+}
+
+impl IcebergType {
+    /// Type of a replicated column.
+    pub(crate) fn for_pg_type(pg_type_id: u32) -> Self {
+        match pg_type_id {
+            16 => IcebergType::Boolean,
+            21 | 23 => IcebergType::Int,
+            20 => IcebergType::Long,
+            700 => IcebergType::Float,
+            701 => IcebergType::Double,
+            790 | 1700 => IcebergType::Decimal {
+                precision: DECIMAL_PRECISION,
+                scale: DECIMAL_SCALE,
+            },
+            1082 => IcebergType::Date,
+            1083 => IcebergType::Time,
+            1114 => IcebergType::Timestamp,
+            1184 => IcebergType::TimestampTz,
+            17 => IcebergType::Binary,
+            // Domains are written as their base type
+            oid => match crate::source::pg_types::base_type(oid) {
+                base if base != oid => Self::for_pg_type(base),
+                _ => IcebergType::String,
+            },
+        }
+    }
+
+    /// Parses the `type` of a schema field. `None` for types the sink can't
+    /// write (nested types, `uuid`, `fixed[n]`, nanosecond timestamps).
+    pub(crate) fn parse(field_type: &serde_json::Value) -> Option<Self> {
+        let name = field_type.as_str()?;
+        Some(match name {
+            "boolean" => IcebergType::Boolean,
+            "int" => IcebergType::Int,
+            "long" => IcebergType::Long,
+            "float" => IcebergType::Float,
+            "double" => IcebergType::Double,
+            "date" => IcebergType::Date,
+            "time" => IcebergType::Time,
+            "timestamp" => IcebergType::Timestamp,
+            "timestamptz" => IcebergType::TimestampTz,
+            "string" => IcebergType::String,
+            "binary" => IcebergType::Binary,
+            _ => {
+                let args = name.strip_prefix("decimal(")?.strip_suffix(')')?;
+                let (precision, scale) = args.split_once(',')?;
+                IcebergType::Decimal {
+                    precision: precision.trim().parse().ok()?,
+                    scale: scale.trim().parse().ok()?,
+                }
+            }
+        })
+    }
+
+    /// The type as written in table metadata.
+    pub(crate) fn name(&self) -> String {
+        match self {
+            IcebergType::Boolean => "boolean".to_string(),
+            IcebergType::Int => "int".to_string(),
+            IcebergType::Long => "long".to_string(),
+            IcebergType::Float => "float".to_string(),
+            IcebergType::Double => "double".to_string(),
+            IcebergType::Decimal { precision, scale } => {
+                format!("decimal({}, {})", precision, scale)
+            }
+            IcebergType::Date => "date".to_string(),
+            IcebergType::Time => "time".to_string(),
+            IcebergType::Timestamp => "timestamp".to_string(),
+            IcebergType::TimestampTz => "timestamptz".to_string(),
+            IcebergType::String => "string".to_string(),
+            IcebergType::Binary => "binary".to_string(),
+        }
+    }
+
+    /// Arrow type the Parquet writer maps to this type's Parquet encoding.
+    pub(crate) fn arrow_type(&self) -> ArrowType {
+        match self {
+            IcebergType::Boolean => ArrowType::Boolean,
+            IcebergType::Int => ArrowType::Int32,
+            IcebergType::Long => ArrowType::Int64,
+            IcebergType::Float => ArrowType::Float32,
+            IcebergType::Double => ArrowType::Float64,
+            IcebergType::Decimal { precision, scale } => {
+                ArrowType::Decimal128(*precision, *scale as i8)
+            }
+            IcebergType::Date => ArrowType::Date32,
+            IcebergType::Time => ArrowType::Time64(TimeUnit::Microsecond),
+            IcebergType::Timestamp => ArrowType::Timestamp(TimeUnit::Microsecond, None),
+            IcebergType::TimestampTz => {
+                ArrowType::Timestamp(TimeUnit::Microsecond, Some("+00:00".into()))
+            }
+            IcebergType::String => ArrowType::Utf8,
+            IcebergType::Binary => ArrowType::Binary,
+        }
+    }
+
+    /// Builds the column `name` of a data or delete file from its values.
+    /// Fails on a value the type can't hold, naming the column.
+    pub(crate) fn to_array<'a>(
+        &self,
+        name: &str,
+        values: impl ExactSizeIterator<Item = &'a Value>,
+    ) -> Result<ArrayRef> {
+        let len = values.len();
+        let error = |value: &Value| {
+            anyhow::anyhow!(
+                "Value {:?} of column {} doesn't fit Iceberg type {}",
+                value,
+                name,
+                self.name()
+            )
+        };
+        macro_rules! build {
+            ($builder:expr, $convert:expr) => {{
+                let mut builder = $builder;
+                for value in values {
+                    if matches!(value, Value::Null | Value::Unchanged) {
+                        builder.append_null();
+                    } else {
+                        let convert: &dyn Fn(&Value) -> Option<_> = &$convert;
+                        builder.append_value(convert(value).ok_or_else(|| error(value))?);
+                    }
+                }
+                Arc::new(builder.finish()) as ArrayRef
+            }};
+        }
+        let array = match self {
+            IcebergType::Boolean => build!(BooleanBuilder::with_capacity(len), |v| match v {
+                Value::Bool(b) => Some(*b),
+                Value::Int64(i) => Some(*i != 0),
+                Value::String(s) => parse_bool(s),
+                _ => None,
+            }),
+            IcebergType::Int => build!(Int32Builder::with_capacity(len), |v| match v {
+                Value::Int64(i) => i32::try_from(*i).ok(),
+                Value::String(s) => s.parse().ok(),
+                _ => None,
+            }),
+            IcebergType::Long => build!(Int64Builder::with_capacity(len), |v| match v {
+                Value::Int64(i) => Some(*i),
+                Value::String(s) => s.parse().ok(),
+                _ => None,
+            }),
+            IcebergType::Float => build!(Float32Builder::with_capacity(len), |v| {
+                as_f64(v).map(|f| f as f32)
+            }),
+            IcebergType::Double => build!(Float64Builder::with_capacity(len), as_f64),
+            IcebergType::Decimal { precision, scale } => {
+                let scale = *scale;
+                let builder = Decimal128Builder::with_capacity(len)
+                    .with_precision_and_scale(*precision, scale as i8)
+                    .context("Invalid Iceberg decimal type")?;
+                build!(builder, |v| match v {
+                    Value::Decimal(s) | Value::String(s) => parse_decimal(s, scale),
+                    Value::Int64(i) => (*i as i128).checked_mul(10i128.pow(scale as u32)),
+                    _ => None,
+                })
+            }
+            IcebergType::Date => build!(Date32Builder::with_capacity(len), |v| match v {
+                Value::String(s) => parse_date(s),
+                _ => None,
+            }),
+            IcebergType::Time => build!(Time64MicrosecondBuilder::with_capacity(len), |v| {
+                match v {
+                    Value::String(s) => parse_time(s),
+                    _ => None,
+                }
+            }),
+            IcebergType::Timestamp | IcebergType::TimestampTz => {
+                let builder = TimestampMicrosecondBuilder::with_capacity(len);
+                let builder = match self {
+                    IcebergType::TimestampTz => builder.with_timezone("+00:00"),
+                    _ => builder,
+                };
+                build!(builder, |v| match v {
+                    Value::Timestamp(micros) => Some(*micros),
+                    Value::String(s) => parse_timestamp(s),
+                    _ => None,
+                })
+            }
+            IcebergType::String => build!(StringBuilder::with_capacity(len, len * 16), |v| {
+                Some(match v {
+                    Value::String(s) | Value::Json(s) | Value::Decimal(s) | Value::Uuid(s) => {
+                        s.clone()
+                    }
+                    Value::Bool(b) => b.to_string(),
+                    Value::Int64(i) => i.to_string(),
+                    Value::Float64(f) => f.to_string(),
+                    Value::Bytes(b) => format!("\\x{}", hex::encode(b)),
+                    Value::Timestamp(micros) => micros.to_string(),
+                    Value::Null | Value::Unchanged => return None,
+                })
+            }),
+            IcebergType::Binary => build!(BinaryBuilder::with_capacity(len, len * 16), |v| {
+                match v {
+                    Value::Bytes(b) => Some(b.clone()),
+                    // bytea text output: \x followed by hex digits
+                    Value::String(s) => s.strip_prefix("\\x").and_then(|h| hex::decode(h).ok()),
+                    _ => None,
+                }
+            }),
+        };
+        Ok(array)
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "t" | "true" | "1" => Some(true),
+        "f" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float64(f) => Some(*f),
+        Value::Int64(i) => Some(*i as f64),
+        // Custom float formats and non-finite values arrive as text
+        Value::Decimal(s) | Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            s => s.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+/// Parses decimal text into an unscaled integer with `scale` digits after
+/// the point. Extra digits are rounded half away from zero.
+pub(crate) fn parse_decimal(text: &str, scale: u8) -> Option<i128> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    if !whole
+        .bytes()
+        .chain(fraction.bytes())
+        .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let scale = scale as usize;
+    let mut unscaled: i128 = 0;
+    for digit in whole
+        .bytes()
+        .chain(fraction.bytes().chain(std::iter::repeat(b'0')).take(scale))
+    {
+        unscaled = unscaled
+            .checked_mul(10)?
+            .checked_add((digit - b'0') as i128)?;
+    }
+    if fraction.as_bytes().get(scale).is_some_and(|d| *d >= b'5') {
+        unscaled = unscaled.checked_add(1)?;
+    }
+    Some(if negative { -unscaled } else { unscaled })
+}
+
+/// Days since 1970-01-01.
+fn parse_date(text: &str) -> Option<i32> {
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?;
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)?;
+    i32::try_from((date - epoch).num_days()).ok()
+}
+
+/// Microseconds since midnight.
+fn parse_time(text: &str) -> Option<i64> {
+    let time = NaiveTime::parse_from_str(text, "%H:%M:%S%.f").ok()?;
+    Some(time.num_seconds_from_midnight() as i64 * 1_000_000 + time.nanosecond() as i64 / 1_000)
+}
+
+/// Microseconds since the Unix epoch, of `timestamp` text or `timestamptz`
+/// text the adapter normalized to UTC.
+fn parse_timestamp(text: &str) -> Option<i64> {
+    let timestamp = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()?;
+    Some(timestamp.and_utc().timestamp_micros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, Date32Array, Decimal128Array, TimestampMicrosecondArray};
+
+    #[test]
+    fn test_type_names_round_trip() {
+        for oid in [16, 21, 20, 700, 701, 1700, 1082, 1083, 1114, 1184, 17, 25] {
+            let iceberg = IcebergType::for_pg_type(oid);
+            assert_eq!(
+                IcebergType::parse(&serde_json::json!(iceberg.name())),
+                Some(iceberg)
+            );
+        }
+        assert_eq!(IcebergType::for_pg_type(2950), IcebergType::String);
+        assert_eq!(
+            IcebergType::parse(&serde_json::json!("decimal(10,2)")),
+            Some(IcebergType::Decimal {
+                precision: 10,
+                scale: 2
+            })
+        );
+        assert_eq!(IcebergType::parse(&serde_json::json!("uuid")), None);
+        assert_eq!(
+            IcebergType::parse(&serde_json::json!({"type": "list"})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("12.50", 2), Some(1250));
+        assert_eq!(parse_decimal("-0.005", 2), Some(-1));
+        assert_eq!(parse_decimal("7", 3), Some(7000));
+        assert_eq!(parse_decimal(".5", 1), Some(5));
+        assert_eq!(parse_decimal("1e5", 0), None);
+        assert_eq!(parse_decimal("NaN", 2), None);
+    }
+
+    #[test]
+    fn test_to_array() {
+        let values = [
+            Value::String("2024-03-01".to_string()),
+            Value::Null,
+            Value::String("1969-12-31".to_string()),
+        ];
+        let dates = IcebergType::Date.to_array("day", values.iter()).unwrap();
+        let dates = dates.as_any().downcast_ref::<Date32Array>().unwrap();
+        assert_eq!(dates.value(0), 19_783);
+        assert!(dates.is_null(1));
+        assert_eq!(dates.value(2), -1);
+
+        let values = [Value::String("2024-03-01 12:00:00.5".to_string())];
+        let timestamps = IcebergType::TimestampTz
+            .to_array("at", values.iter())
+            .unwrap();
+        let timestamps = timestamps
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(timestamps.value(0), 1_709_294_400_500_000);
+
+        let decimal = IcebergType::for_pg_type(1700);
+        let values = [Value::Decimal("-3.25".to_string())];
+        let decimals = decimal.to_array("amount", values.iter()).unwrap();
+        let decimals = decimals.as_any().downcast_ref::<Decimal128Array>().unwrap();
+        assert_eq!(decimals.value(0), -3_250_000_000);
+
+        let values = [Value::String("not a number".to_string())];
+        let err = IcebergType::Long
+            .to_array("id", values.iter())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("column id"), "{}", err);
+    }
+}
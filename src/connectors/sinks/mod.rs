@@ -10,6 +10,20 @@
 //! ## Available Sinks
 //!
 //! - **StarRocks**: OLAP database with Stream Load API support
+//! - **ClickHouse**: ReplacingMergeTree tables loaded over the HTTP interface
+//! - **SQLite**: Local database file for embedded/edge replication
+//! - **Iceberg**: Parquet data and delete files committed through an Iceberg
+//!   REST catalog
+//! - **Delta**: Changelog rows appended to Delta Lake tables on S3
+//! - **Object store**: Changelog rows written as Parquet or JSON lines files
+//!   to S3, GCS or Azure Blob Storage
+//! - **Remote**: User-provided sink server over a gRPC bidirectional stream
+//! - **Channel**: In-process consumer for embedding (not selectable via `SINK_TYPE`,
+//!   injected with `CdcEngine::with_sink`)
+//!
+//! SQLite, Iceberg, Delta and the object store sink pull in heavy crates
+//! (rusqlite, arrow, parquet, deltalake), so each is built only with its cargo
+//! feature: `sqlite`, `iceberg`, `delta` or `object-store`.
 //!
 //! ## Usage
 //!
@@ -22,10 +36,34 @@
 //! sink.write_batch(records).await?;
 //! ```
 
+#[cfg(any(feature = "delta", feature = "object-store"))]
+pub(crate) mod changelog;
+pub mod channel;
+pub mod clickhouse;
+#[cfg(feature = "delta")]
+pub mod delta;
+#[cfg(feature = "iceberg")]
+pub mod iceberg;
+#[cfg(feature = "object-store")]
+pub mod objectstore;
+pub mod remote;
+pub(crate) mod shared;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 pub mod starrocks;
 
 use anyhow::Result;
 
+use self::clickhouse::ClickHouseSink;
+#[cfg(feature = "delta")]
+use self::delta::DeltaSink;
+#[cfg(feature = "iceberg")]
+use self::iceberg::IcebergSink;
+#[cfg(feature = "object-store")]
+use self::objectstore::ObjectStoreSink;
+use self::remote::RemoteSink;
+#[cfg(feature = "sqlite")]
+use self::sqlite::SqliteSink;
 use self::starrocks::StarRocksSink;
 use crate::config::{SinkConfig, SinkType};
 use crate::core::Sink;
@@ -47,6 +85,7 @@ use crate::core::Sink;
 ///
 /// Returns an error if:
 /// - The sink type is not supported
+/// - The sink type's cargo feature was not enabled in this build
 /// - The sink configuration is invalid
 /// - The sink fails to initialize
 ///
@@ -60,26 +99,95 @@ use crate::core::Sink;
 ///     database: "cdc_db".to_string(),
 ///     user: "root".to_string(),
 ///     password: "".to_string(),
-///     starrocks: Some(StarRocksSinkConfig {}),
+///     starrocks: Some(StarRocksSinkConfig::default()),
+///     iceberg: None,
+///     delta: None,
+///     object_store: None,
+///     shared: None,
+///     naming: Default::default(),
+///     checkpoint_slot: None,
 /// };
 ///
 /// let sink = create_sink(&config)?;
 /// ```
 pub fn create_sink(config: &SinkConfig) -> Result<Box<dyn Sink>> {
+    if let Some(feature) = config.sink_type.missing_feature() {
+        anyhow::bail!(
+            "SINK_TYPE={} is not built into this binary; build dbmazz with `--features {}`",
+            config.sink_type,
+            feature
+        );
+    }
+
     match config.sink_type {
         SinkType::StarRocks => {
             let sink = StarRocksSink::new(config)?;
             Ok(Box::new(sink))
-        } // Future sinks can be added here:
-          // SinkType::ClickHouse => Ok(Box::new(ClickHouseSink::new(config)?)),
-          // SinkType::Snowflake => Ok(Box::new(SnowflakeSink::new(config)?)),
+        }
+        #[cfg(feature = "sqlite")]
+        SinkType::Sqlite => {
+            let sink = SqliteSink::new(config)?;
+            Ok(Box::new(sink))
+        }
+        SinkType::ClickHouse => {
+            let sink = ClickHouseSink::new(config)?;
+            Ok(Box::new(sink))
+        }
+        SinkType::Remote => {
+            let sink = RemoteSink::new(config)?;
+            Ok(Box::new(sink))
+        }
+        #[cfg(feature = "iceberg")]
+        SinkType::Iceberg => {
+            let sink = IcebergSink::new(config)?;
+            Ok(Box::new(sink))
+        }
+        #[cfg(feature = "delta")]
+        SinkType::Delta => {
+            let sink = DeltaSink::new(config)?;
+            Ok(Box::new(sink))
+        }
+        #[cfg(feature = "object-store")]
+        SinkType::ObjectStore => {
+            let sink = ObjectStoreSink::new(config)?;
+            Ok(Box::new(sink))
+        }
+        // Future sinks can be added here:
+        // SinkType::Snowflake => Ok(Box::new(SnowflakeSink::new(config)?)),
+
+        // Sinks left out of the build, refused by `missing_feature` above
+        #[allow(unreachable_patterns)]
+        _ => unreachable!("{} sink is not built into this binary", config.sink_type),
     }
 }
 
+/// Table in the destination holding the last LSN written per slot
+/// (`SINK_CHECKPOINT_TABLE=true`)
+pub(crate) const CHECKPOINT_TABLE: &str = "_dbmazz_checkpoints";
+
+/// Table in the destination holding rows the sink rejected
+/// (`DEAD_LETTER_TARGET=table`)
+pub(crate) const DEAD_LETTER_TABLE: &str = "_dbmazz_dead_letters";
+
+/// Tables internal to dbmazz that should not be replicated
+pub(crate) fn is_internal_table(table_name: &str) -> bool {
+    table_name.starts_with("dbmazz_")
+        || table_name.starts_with("_dbmazz_")
+        || table_name == "dbmazz_checkpoints"
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{SinkConfig, SinkType, StarRocksSinkConfig};
+    #[cfg(feature = "delta")]
+    use crate::config::DeltaSinkConfig;
+    #[cfg(any(feature = "iceberg", feature = "delta"))]
+    use crate::config::S3Location;
+    #[cfg(feature = "object-store")]
+    use crate::config::{FileCompression, FileFormat, FilePartitioning, ObjectStoreSinkConfig};
+    #[cfg(feature = "iceberg")]
+    use crate::config::{IcebergCatalog, IcebergSinkConfig};
+    use crate::config::{SinkConfig, SinkType, StarRocksSinkConfig, DEFAULT_SINK_REQUEST_TIMEOUT};
 
     #[test]
     fn test_create_starrocks_sink() {
@@ -90,10 +198,264 @@ mod tests {
             database: "test_db".to_string(),
             user: "root".to_string(),
             password: "".to_string(),
-            starrocks: Some(StarRocksSinkConfig {}),
+            starrocks: Some(StarRocksSinkConfig::default()),
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
         };
 
         let result = create_sink(&config);
         assert!(result.is_ok());
     }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_create_sqlite_sink() {
+        let config = SinkConfig {
+            sink_type: SinkType::Sqlite,
+            url: ":memory:".to_string(),
+            port: 0,
+            database: "main".to_string(),
+            user: String::new(),
+            password: String::new(),
+            starrocks: None,
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        };
+
+        let sink = create_sink(&config).unwrap();
+        assert_eq!(sink.name(), "sqlite");
+    }
+
+    #[test]
+    #[cfg(not(feature = "sqlite"))]
+    fn test_sink_left_out_of_the_build_is_refused() {
+        let config = SinkConfig {
+            sink_type: SinkType::Sqlite,
+            url: ":memory:".to_string(),
+            port: 0,
+            database: "main".to_string(),
+            user: String::new(),
+            password: String::new(),
+            starrocks: None,
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        };
+
+        let err = create_sink(&config).err().unwrap().to_string();
+        assert!(err.contains("--features sqlite"), "{}", err);
+    }
+
+    #[test]
+    fn test_create_clickhouse_sink() {
+        let config = SinkConfig {
+            sink_type: SinkType::ClickHouse,
+            url: "http://clickhouse:8123".to_string(),
+            port: 0,
+            database: "cdc".to_string(),
+            user: "default".to_string(),
+            password: String::new(),
+            starrocks: None,
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        };
+
+        let sink = create_sink(&config).unwrap();
+        assert_eq!(sink.name(), "clickhouse");
+    }
+
+    #[test]
+    #[cfg(feature = "iceberg")]
+    fn test_create_iceberg_sink() {
+        let storage = S3Location {
+            bucket: String::new(),
+            prefix: String::new(),
+            region: "us-east-1".to_string(),
+            endpoint: Some("http://minio:9000".to_string()),
+            access_key_id: "minio".to_string(),
+            secret_access_key: "minio123".to_string(),
+            session_token: None,
+        };
+        let mut config = SinkConfig {
+            sink_type: SinkType::Iceberg,
+            url: "http://polaris:8181/api/catalog".to_string(),
+            port: 0,
+            database: "cdc".to_string(),
+            user: "root".to_string(),
+            password: "secret".to_string(),
+            starrocks: None,
+            iceberg: Some(IcebergSinkConfig {
+                catalog: IcebergCatalog::Rest,
+                warehouse: Some("lake".to_string()),
+                oauth_scope: "PRINCIPAL_ROLE:ALL".to_string(),
+                storage,
+            }),
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        };
+
+        let sink = create_sink(&config).unwrap();
+        assert_eq!(sink.name(), "iceberg");
+
+        config.iceberg = None;
+        assert!(create_sink(&config).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "delta")]
+    fn test_create_delta_sink() {
+        let storage = S3Location {
+            bucket: String::new(),
+            prefix: String::new(),
+            region: "us-east-1".to_string(),
+            endpoint: Some("http://minio:9000".to_string()),
+            access_key_id: "minio".to_string(),
+            secret_access_key: "minio123".to_string(),
+            session_token: None,
+        };
+        let mut config = SinkConfig {
+            sink_type: SinkType::Delta,
+            url: "s3://lake/cdc".to_string(),
+            port: 0,
+            database: "sales".to_string(),
+            user: String::new(),
+            password: String::new(),
+            starrocks: None,
+            iceberg: None,
+            delta: Some(DeltaSinkConfig {
+                lock_table: None,
+                storage,
+            }),
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        };
+
+        let sink = create_sink(&config).unwrap();
+        assert_eq!(sink.name(), "delta");
+
+        config.delta = None;
+        assert!(create_sink(&config).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "object-store")]
+    fn test_create_object_store_sink() {
+        let mut config = SinkConfig {
+            sink_type: SinkType::ObjectStore,
+            url: "s3://staging/cdc".to_string(),
+            port: 0,
+            database: String::new(),
+            user: String::new(),
+            password: String::new(),
+            starrocks: None,
+            iceberg: None,
+            delta: None,
+            object_store: Some(ObjectStoreSinkConfig {
+                format: FileFormat::Parquet,
+                partitioning: FilePartitioning::Date,
+                target_file_bytes: 1024 * 1024,
+                compression: FileCompression::Zstd,
+                tee: None,
+            }),
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        };
+
+        let sink = create_sink(&config).unwrap();
+        assert_eq!(sink.name(), "object_store");
+
+        config.object_store = None;
+        assert!(create_sink(&config).is_err());
+    }
 }
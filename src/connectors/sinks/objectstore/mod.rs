@@ -0,0 +1,863 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! # Object Store Sink Connector
+//!
+//! This module implements a CDC sink writing changes as files to S3, Google
+//! Cloud Storage or Azure Blob Storage, for lake-house staging: a loader
+//! (Snowpipe, BigQuery, Spark, Trino) picks the files up from there. Every
+//! change becomes a row tagged with the CDC columns of the Delta sink, and
+//! readers resolve the current state the same way: the row with the highest
+//! `_lsn` per key wins, and is dropped when its `_op` is `D`.
+//!
+//! ## Features
+//!
+//! - **Formats**: Parquet with `none`, `snappy`, `gzip` or `zstd` column
+//!   chunks, or newline-delimited JSON, plain, gzip or zstd compressed
+//! - **Partitioning**: files go under `<database>/<table>/`, optionally
+//!   followed by `date=YYYY-MM-DD/` and `hour=HH/` of the write (UTC)
+//! - **File size**: a table's rows of a batch roll over to a new file once
+//!   the current one reaches `OBJECT_STORE_TARGET_FILE_MB`
+//! - **Typed columns**: columns take the types of the table's last
+//!   Relation message; columns of tables seen without one are strings
+//! - **Tee**: `OBJECT_STORE_TEE_URL` writes a second copy of every file in
+//!   another format, e.g. Parquet for the lake and JSON lines for a loader;
+//!   the rows are converted to columns once and both formats are encoded
+//!   from them
+//!
+//! ## Batches
+//!
+//! Each batch writes the files of every table it touches, to every output,
+//! before returning, so its LSN is only acknowledged once they are all
+//! stored. Files are never
+//! appended to: their names start with the batch's LSN, followed by the
+//! write time and the part number, so a batch written again after a failure
+//! adds new files next to those of the first attempt, holding the same rows
+//! with the same `_lsn`.
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use dbmazz::connectors::sinks::objectstore::ObjectStoreSink;
+//!
+//! // SINK_TYPE=object_store SINK_URL=gs://staging/cdc OBJECT_STORE_FORMAT=parquet
+//! let mut sink = ObjectStoreSink::new(&config)?;
+//! sink.write_batch(records).await?;
+//! ```
+
+use anyhow::{Context, Result};
+use arrow_array::RecordBatch;
+use arrow_schema::{Field, Schema, SchemaRef};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path;
+use object_store::{ClientOptions, ObjectStore, PutPayload};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info};
+
+use super::changelog::{Layout, CDC_COLUMNS};
+use super::is_internal_table;
+use crate::config::{
+    FileCompression, FileFormat, FilePartitioning, HttpClientConfig, ObjectStoreSinkConfig,
+    SinkConfig, SinkTableName, TableNaming,
+};
+use crate::connectors::sinks::iceberg::types::IcebergType;
+use crate::core::traits::StageFormat;
+use crate::core::{
+    pool, CdcRecord, DataType, LoadingModel, Sink, SinkCapabilities, SinkResult, SourcePosition,
+};
+
+/// Rows encoded between two checks of a file's size
+const ROWS_PER_CHECK: usize = 4096;
+
+/// Type of a replicated column in the files.
+pub(crate) fn column_type(data_type: &DataType) -> IcebergType {
+    match data_type {
+        DataType::Boolean => IcebergType::Boolean,
+        DataType::Int16 | DataType::Int32 => IcebergType::Int,
+        DataType::Int64 => IcebergType::Long,
+        DataType::Float32 => IcebergType::Float,
+        DataType::Float64 => IcebergType::Double,
+        DataType::Decimal { precision, scale } => IcebergType::Decimal {
+            precision: *precision,
+            scale: *scale,
+        },
+        DataType::Date => IcebergType::Date,
+        DataType::Timestamp => IcebergType::Timestamp,
+        DataType::TimestampTz => IcebergType::TimestampTz,
+        DataType::Bytes => IcebergType::Binary,
+        // No time of day type is shared by Parquet and JSON readers
+        DataType::Time
+        | DataType::String { .. }
+        | DataType::Text
+        | DataType::Json
+        | DataType::Jsonb
+        | DataType::Uuid
+        | DataType::Enum { .. } => IcebergType::String,
+    }
+}
+
+/// The store holding `url`'s bucket (or container), and the key prefix
+/// under it as path segments. Credentials come from the provider's standard
+/// variables; requests go through `http`'s proxy.
+fn open_store(
+    url: &str,
+    request_timeout: Duration,
+    http: &HttpClientConfig,
+) -> Result<(Arc<dyn ObjectStore>, Vec<String>)> {
+    let parsed = url::Url::parse(url).with_context(|| format!("Invalid SINK_URL {}", url))?;
+    let mut client = ClientOptions::new().with_timeout(request_timeout);
+    if let Some(proxy) = &http.proxy {
+        client = client.with_proxy_url(proxy);
+        if let Some(no_proxy) = &http.no_proxy {
+            client = client.with_proxy_excludes(no_proxy);
+        }
+    }
+    if http.insecure_skip_verify {
+        client = client.with_allow_invalid_certificates(true);
+    }
+    let store: Arc<dyn ObjectStore> = match parsed.scheme() {
+        "s3" | "s3a" => Arc::new(
+            AmazonS3Builder::from_env()
+                .with_url(url)
+                .with_client_options(client)
+                .build()
+                .context("Failed to configure S3")?,
+        ),
+        "gs" => Arc::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(url)
+                .with_client_options(client)
+                .build()
+                .context("Failed to configure Google Cloud Storage")?,
+        ),
+        "az" | "azure" | "abfs" | "abfss" => Arc::new(
+            MicrosoftAzureBuilder::from_env()
+                .with_url(url)
+                .with_client_options(client)
+                .build()
+                .context("Failed to configure Azure Blob Storage")?,
+        ),
+        other => anyhow::bail!("Unsupported object store scheme {}://", other),
+    };
+    let prefix = parsed
+        .path()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok((store, prefix))
+}
+
+/// Key of a file: `<prefix>/<database>/<table>/<partition>/<name>`, the
+/// database being `default_database` (possibly empty) without one.
+fn file_path(
+    prefix: &[String],
+    dest: &SinkTableName,
+    default_database: &str,
+    partitioning: FilePartitioning,
+    written_at: DateTime<Utc>,
+    name: &str,
+) -> Path {
+    let database = dest.database.as_deref().unwrap_or(default_database);
+    let mut parts: Vec<String> = prefix.to_vec();
+    parts.extend(database.split('.').map(str::to_string));
+    parts.push(dest.table.clone());
+    if partitioning != FilePartitioning::Table {
+        parts.push(written_at.format("date=%Y-%m-%d").to_string());
+    }
+    if partitioning == FilePartitioning::Hour {
+        parts.push(written_at.format("hour=%H").to_string());
+    }
+    parts.push(name.to_string());
+    // Empty segments (no prefix or database) are dropped
+    Path::from_iter(parts)
+}
+
+/// Name of a file of a batch: its LSN (so names sort in change order), the
+/// write time in microseconds and the part number.
+fn file_name(lsn: u64, written_at: i64, part: usize, files: &ObjectStoreSinkConfig) -> String {
+    let extension = match (files.format, files.compression) {
+        (FileFormat::Parquet, _) => "parquet",
+        (FileFormat::JsonLines, FileCompression::Gzip) => "jsonl.gz",
+        (FileFormat::JsonLines, FileCompression::Zstd) => "jsonl.zst",
+        (FileFormat::JsonLines, _) => "jsonl",
+    };
+    format!("{:016X}-{}-{:03}.{}", lsn, written_at, part, extension)
+}
+
+/// Arrow schema of a table's files: the columns of its last Relation
+/// message (`known`) in table order, then any other column the changes
+/// carry, as strings, then the CDC columns.
+fn file_schema(known: Option<&[(String, IcebergType)]>, changes: &[CdcRecord]) -> SchemaRef {
+    let mut columns: Vec<(String, IcebergType)> = known.map(<[_]>::to_vec).unwrap_or_default();
+    for record in changes {
+        let images = match record {
+            CdcRecord::Insert { columns, .. } | CdcRecord::Delete { columns, .. } => {
+                [Some(columns), None]
+            }
+            CdcRecord::Update {
+                old_columns,
+                new_columns,
+                ..
+            } => [Some(new_columns), old_columns.as_ref()],
+            _ => continue,
+        };
+        for column in images.into_iter().flatten().flatten() {
+            if !columns.iter().any(|(name, _)| **name == *column.name) {
+                columns.push((column.name.to_string(), IcebergType::String));
+            }
+        }
+    }
+    let fields: Vec<Field> = columns
+        .iter()
+        .map(|(name, column_type)| (name.as_str(), *column_type))
+        .chain(
+            CDC_COLUMNS
+                .iter()
+                .map(|(name, column_type, _)| (*name, *column_type)),
+        )
+        .map(|(name, column_type)| Field::new(name, column_type.arrow_type(), true))
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+/// Encodes `batch` as files of about `files.target_file_bytes` each.
+fn encode_files(batch: &RecordBatch, files: &ObjectStoreSinkConfig) -> Result<Vec<Vec<u8>>> {
+    match files.format {
+        FileFormat::Parquet => parquet_files(batch, files.compression, files.target_file_bytes),
+        FileFormat::JsonLines => json_files(batch, files.compression, files.target_file_bytes),
+    }
+}
+
+fn parquet_files(
+    batch: &RecordBatch,
+    compression: FileCompression,
+    target: u64,
+) -> Result<Vec<Vec<u8>>> {
+    let properties = WriterProperties::builder()
+        .set_compression(match compression {
+            FileCompression::None => Compression::UNCOMPRESSED,
+            FileCompression::Snappy => Compression::SNAPPY,
+            FileCompression::Gzip => Compression::GZIP(GzipLevel::default()),
+            FileCompression::Zstd => Compression::ZSTD(ZstdLevel::default()),
+        })
+        .build();
+    let mut files = Vec::new();
+    let mut current: Option<ArrowWriter<Vec<u8>>> = None;
+    let mut offset = 0;
+    while offset < batch.num_rows() {
+        let mut writer = match current.take() {
+            Some(writer) => writer,
+            None => ArrowWriter::try_new(Vec::new(), batch.schema(), Some(properties.clone()))
+                .context("Failed to start Parquet file")?,
+        };
+        let rows = ROWS_PER_CHECK.min(batch.num_rows() - offset);
+        writer
+            .write(&batch.slice(offset, rows))
+            .context("Failed to encode Parquet rows")?;
+        offset += rows;
+        if (writer.bytes_written() + writer.in_progress_size()) as u64 >= target {
+            files.push(
+                writer
+                    .into_inner()
+                    .context("Failed to finish Parquet file")?,
+            );
+        } else {
+            current = Some(writer);
+        }
+    }
+    if let Some(writer) = current {
+        files.push(
+            writer
+                .into_inner()
+                .context("Failed to finish Parquet file")?,
+        );
+    }
+    Ok(files)
+}
+
+fn json_files(
+    batch: &RecordBatch,
+    compression: FileCompression,
+    target: u64,
+) -> Result<Vec<Vec<u8>>> {
+    let mut files = Vec::new();
+    let mut current: Option<JsonFile> = None;
+    let mut offset = 0;
+    while offset < batch.num_rows() {
+        let mut file = match current.take() {
+            Some(file) => file,
+            None => JsonFile::new(compression)?,
+        };
+        let rows = ROWS_PER_CHECK.min(batch.num_rows() - offset);
+        // NULL columns are written as null rather than left out
+        let mut writer = arrow_json::WriterBuilder::new()
+            .with_explicit_nulls(true)
+            .build::<_, arrow_json::writer::LineDelimited>(&mut file);
+        writer
+            .write(&batch.slice(offset, rows))
+            .context("Failed to encode JSON rows")?;
+        writer.finish().context("Failed to encode JSON rows")?;
+        drop(writer);
+        offset += rows;
+        if file.len() as u64 >= target {
+            files.push(file.finish()?);
+        } else {
+            current = Some(file);
+        }
+    }
+    if let Some(file) = current {
+        files.push(file.finish()?);
+    }
+    Ok(files)
+}
+
+/// A JSON lines file being written, compressed as a whole.
+enum JsonFile {
+    Plain(Vec<u8>),
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::Encoder<'static, Vec<u8>>),
+}
+
+impl JsonFile {
+    fn new(compression: FileCompression) -> Result<Self> {
+        Ok(match compression {
+            FileCompression::None => JsonFile::Plain(Vec::new()),
+            FileCompression::Gzip => {
+                JsonFile::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::default()))
+            }
+            FileCompression::Zstd => JsonFile::Zstd(
+                zstd::Encoder::new(Vec::new(), 0).context("Failed to start zstd stream")?,
+            ),
+            FileCompression::Snappy => {
+                anyhow::bail!("snappy compression is only supported for Parquet files")
+            }
+        })
+    }
+
+    /// Bytes of the file so far, less what the compressor still buffers.
+    fn len(&self) -> usize {
+        match self {
+            JsonFile::Plain(buffer) => buffer.len(),
+            JsonFile::Gzip(encoder) => encoder.get_ref().len(),
+            JsonFile::Zstd(encoder) => encoder.get_ref().len(),
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>> {
+        Ok(match self {
+            JsonFile::Plain(buffer) => buffer,
+            JsonFile::Gzip(encoder) => encoder.finish().context("Failed to finish gzip stream")?,
+            JsonFile::Zstd(encoder) => encoder.finish().context("Failed to finish zstd stream")?,
+        })
+    }
+}
+
+impl Write for JsonFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            JsonFile::Plain(buffer) => buffer.write(buf),
+            JsonFile::Gzip(encoder) => encoder.write(buf),
+            JsonFile::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            JsonFile::Plain(buffer) => buffer.flush(),
+            JsonFile::Gzip(encoder) => encoder.flush(),
+            JsonFile::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// A location the sink writes files to, and their format.
+struct Output {
+    store: Arc<dyn ObjectStore>,
+    /// Key prefix of the location, as path segments
+    prefix: Vec<String>,
+    files: ObjectStoreSinkConfig,
+}
+
+/// Object store sink connector implementing the Sink trait.
+pub struct ObjectStoreSink {
+    /// `SINK_URL`, then the tee's location if any
+    outputs: Vec<Output>,
+    /// How source tables map to databases and tables
+    naming: TableNaming,
+    /// `SINK_DATABASE`: directory of tables without their own database,
+    /// possibly empty
+    database: String,
+    /// Column types of each source table, from its last Relation message
+    schemas: HashMap<String, Vec<(String, IcebergType)>>,
+}
+
+impl ObjectStoreSink {
+    pub fn new(config: &SinkConfig) -> Result<Self> {
+        let files = config
+            .object_store
+            .clone()
+            .context("Object store sink configuration is missing")?;
+        let (store, prefix) = open_store(&config.url, config.request_timeout, &config.http)?;
+        info!(
+            "Object store sink: location {}, {} files",
+            config.url, files.format
+        );
+        let mut sink = Self::with_store(store, prefix, config, files.clone());
+        if let (Some(tee), Some(tee_files)) = (&files.tee, files.tee_files()) {
+            let (store, prefix) = open_store(&tee.url, config.request_timeout, &config.http)
+                .context("Invalid OBJECT_STORE_TEE_URL")?;
+            info!(
+                "Object store sink: tee location {}, {} files",
+                tee.url, tee.format
+            );
+            sink.outputs.push(Output {
+                store,
+                prefix,
+                files: tee_files,
+            });
+        }
+        Ok(sink)
+    }
+
+    fn with_store(
+        store: Arc<dyn ObjectStore>,
+        prefix: Vec<String>,
+        config: &SinkConfig,
+        files: ObjectStoreSinkConfig,
+    ) -> Self {
+        Self {
+            outputs: vec![Output {
+                store,
+                prefix,
+                files,
+            }],
+            naming: config.naming.clone(),
+            database: config.database.clone(),
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// Files of `SINK_URL`
+    fn files(&self) -> &ObjectStoreSinkConfig {
+        &self.outputs[0].files
+    }
+
+    /// Writes the changes of one table as one or more files per output. The
+    /// rows are laid out as columns once, and each output's format encoded
+    /// from them. Returns the rows and bytes written.
+    async fn write_table(
+        &self,
+        source: &str,
+        dest: &SinkTableName,
+        records: Vec<CdcRecord>,
+        lsn: u64,
+        written_at: DateTime<Utc>,
+    ) -> Result<(usize, u64)> {
+        let schema = file_schema(self.schemas.get(source).map(Vec::as_slice), &records);
+        let layout = Layout::new(schema, "object_store")?;
+        let label = dest.label();
+        let formats: Vec<ObjectStoreSinkConfig> =
+            self.outputs.iter().map(|o| o.files.clone()).collect();
+        let synced_at = written_at.timestamp_micros();
+        let (rows, encoded) = tokio::task::spawn_blocking(move || {
+            let batch = layout.encode(&records, synced_at, &label);
+            pool::recycle(records);
+            let batch = batch?;
+            let encoded = formats
+                .iter()
+                .map(|files| encode_files(&batch, files))
+                .collect::<Result<Vec<_>>>()?;
+            anyhow::Ok((batch.num_rows(), encoded))
+        })
+        .await
+        .context("Object store encoder task failed")??;
+
+        let mut bytes = 0;
+        for (output, encoded) in self.outputs.iter().zip(encoded) {
+            for (part, file) in encoded.into_iter().enumerate() {
+                let name = file_name(lsn, synced_at, part, &output.files);
+                let path = file_path(
+                    &output.prefix,
+                    dest,
+                    &self.database,
+                    output.files.partitioning,
+                    written_at,
+                    &name,
+                );
+                bytes += file.len() as u64;
+                output
+                    .store
+                    .put(&path, PutPayload::from(file))
+                    .await
+                    .with_context(|| format!("Failed to write {}", path))?;
+                debug!("Object store sink: wrote {}", path);
+            }
+        }
+        Ok((rows, bytes))
+    }
+}
+
+#[async_trait]
+impl Sink for ObjectStoreSink {
+    fn name(&self) -> &'static str {
+        "object_store"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            supports_upsert: false,
+            supports_delete: true,
+            supports_schema_evolution: true,
+            supports_transactions: false,
+            loading_model: LoadingModel::StagedBatch {
+                stage_format: match self.files().format {
+                    FileFormat::Parquet => StageFormat::Parquet,
+                    FileFormat::JsonLines => StageFormat::Json,
+                },
+            },
+            min_batch_size: Some(1),
+            max_batch_size: Some(1_000_000),
+            optimal_flush_interval_ms: 60_000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        for output in &self.outputs {
+            let prefix = Path::from_iter(output.prefix.iter().map(String::as_str));
+            output
+                .store
+                .list_with_delimiter(Some(&prefix))
+                .await
+                .with_context(|| format!("Failed to list {}", prefix))?;
+        }
+        Ok(())
+    }
+
+    async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult> {
+        let last_position = records.iter().rev().find_map(|r| match r {
+            CdcRecord::Insert { position, .. }
+            | CdcRecord::Update { position, .. }
+            | CdcRecord::Delete { position, .. }
+            | CdcRecord::Commit { position, .. }
+            | CdcRecord::Heartbeat { position, .. } => Some(position.clone()),
+            _ => None,
+        });
+        let lsn = match &last_position {
+            Some(SourcePosition::Lsn(lsn)) => *lsn,
+            _ => 0,
+        };
+        let written_at = Utc::now();
+
+        // Changes of each table, by source table, in order of first appearance
+        let mut tables: Vec<(String, Vec<CdcRecord>)> = Vec::new();
+        for record in records {
+            let table = match &record {
+                CdcRecord::Insert { table, .. }
+                | CdcRecord::Update { table, .. }
+                | CdcRecord::Delete { table, .. } => table,
+                CdcRecord::SchemaChange { table, columns, .. } => {
+                    if !is_internal_table(&table.name) {
+                        let columns = columns
+                            .iter()
+                            .map(|c| (c.name.clone(), column_type(&c.data_type)))
+                            .collect();
+                        self.schemas.insert(table.qualified_name(), columns);
+                    }
+                    continue;
+                }
+                // Transactions and heartbeats aren't written
+                _ => continue,
+            };
+            if is_internal_table(&table.name) {
+                continue;
+            }
+            let source = table.qualified_name();
+            match tables.iter_mut().find(|(s, _)| *s == source) {
+                Some((_, changes)) => changes.push(record),
+                None => tables.push((source, vec![record])),
+            }
+        }
+
+        // The position is only acknowledged once every file is stored
+        let mut records_written = 0;
+        let mut bytes_written = 0;
+        for (source, changes) in tables {
+            let dest = self.naming.sink_table(&source);
+            let (rows, bytes) = self
+                .write_table(&source, &dest, changes, lsn, written_at)
+                .await
+                .with_context(|| format!("Failed to write files of {}", dest.label()))?;
+            records_written += rows;
+            bytes_written += bytes;
+        }
+
+        Ok(SinkResult {
+            records_written,
+            bytes_written,
+            last_position,
+            ..Default::default()
+        })
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        // Every file is stored before write_batch returns
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SinkType;
+    use crate::core::{ColumnDef, ColumnValue, TableRef, Value};
+    use bytes::Bytes;
+    use futures::TryStreamExt;
+    use object_store::memory::InMemory;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::io::Read;
+
+    fn files(format: FileFormat, compression: FileCompression) -> ObjectStoreSinkConfig {
+        ObjectStoreSinkConfig {
+            format,
+            partitioning: FilePartitioning::Hour,
+            target_file_bytes: 1024 * 1024,
+            compression,
+            tee: None,
+        }
+    }
+
+    fn table() -> TableRef {
+        TableRef::new(Some("public".into()), "orders".into())
+    }
+
+    fn insert(id: i64, note: &str) -> CdcRecord {
+        CdcRecord::Insert {
+            table: table(),
+            columns: vec![
+                ColumnValue::new("id".into(), Value::String(id.to_string())),
+                ColumnValue::new("note".into(), Value::String(note.to_string())),
+            ],
+            position: SourcePosition::Lsn(0x10 + id as u64),
+        }
+    }
+
+    fn schema_change() -> CdcRecord {
+        CdcRecord::SchemaChange {
+            table: table(),
+            columns: vec![
+                ColumnDef::new("id".to_string(), DataType::Int64, true),
+                ColumnDef::new("note".to_string(), DataType::Text, true),
+            ],
+            position: SourcePosition::Lsn(0x10),
+        }
+    }
+
+    fn sink(files: ObjectStoreSinkConfig) -> (ObjectStoreSink, Arc<InMemory>) {
+        let config = SinkConfig {
+            sink_type: SinkType::ObjectStore,
+            url: "s3://staging/cdc".to_string(),
+            port: 0,
+            database: "sales".to_string(),
+            user: String::new(),
+            password: String::new(),
+            starrocks: None,
+            iceberg: None,
+            delta: None,
+            object_store: Some(files.clone()),
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: crate::config::DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        };
+        let store = Arc::new(InMemory::new());
+        let sink =
+            ObjectStoreSink::with_store(store.clone(), vec!["cdc".to_string()], &config, files);
+        (sink, store)
+    }
+
+    #[test]
+    fn test_file_schema() {
+        let known = vec![("id".to_string(), IcebergType::Long)];
+        let changes = vec![insert(1, "a")];
+        let schema = file_schema(Some(&known), &changes);
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["id", "note", "_op", "_lsn", "_ts"]);
+        assert_eq!(schema.field(0).data_type(), &IcebergType::Long.arrow_type());
+        assert_eq!(
+            schema.field(1).data_type(),
+            &IcebergType::String.arrow_type()
+        );
+
+        assert_eq!(column_type(&DataType::Int16), IcebergType::Int);
+        assert_eq!(column_type(&DataType::Time), IcebergType::String);
+    }
+
+    #[test]
+    fn test_file_paths() {
+        let written_at = DateTime::from_timestamp(1_709_294_400, 0).unwrap();
+        let dest = SinkTableName {
+            database: None,
+            table: "orders".to_string(),
+        };
+        let files = files(FileFormat::JsonLines, FileCompression::Gzip);
+        let name = file_name(0x1A2B, written_at.timestamp_micros(), 1, &files);
+        assert_eq!(name, "0000000000001A2B-1709294400000000-001.jsonl.gz");
+
+        let path = file_path(
+            &["cdc".to_string()],
+            &dest,
+            "sales.eu",
+            FilePartitioning::Hour,
+            written_at,
+            &name,
+        );
+        assert_eq!(
+            path.as_ref(),
+            format!("cdc/sales/eu/orders/date=2024-03-01/hour=12/{}", name)
+        );
+        let path = file_path(&[], &dest, "", FilePartitioning::Table, written_at, "f");
+        assert_eq!(path.as_ref(), "orders/f");
+    }
+
+    #[test]
+    fn test_files_roll_over_at_the_target_size() {
+        let changes: Vec<CdcRecord> = (0..20_000).map(|i| insert(i, "some note")).collect();
+        let schema = file_schema(None, &changes);
+        let batch = Layout::new(schema, "object_store")
+            .unwrap()
+            .encode(&changes, 0, "orders")
+            .unwrap();
+
+        let encoded = parquet_files(&batch, FileCompression::None, 64 * 1024).unwrap();
+        assert!(encoded.len() > 1, "{} files", encoded.len());
+        let rows: usize = encoded
+            .into_iter()
+            .map(|file| {
+                ParquetRecordBatchReaderBuilder::try_new(Bytes::from(file))
+                    .unwrap()
+                    .build()
+                    .unwrap()
+                    .map(|batch| batch.unwrap().num_rows())
+                    .sum::<usize>()
+            })
+            .sum();
+        assert_eq!(rows, 20_000);
+
+        let encoded = json_files(&batch, FileCompression::Gzip, u64::MAX).unwrap();
+        assert_eq!(encoded.len(), 1);
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(encoded[0].as_slice())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text.lines().count(), 20_000);
+        assert!(text.starts_with("{\"id\":\"0\",\"note\":\"some note\",\"_op\":\"I\""));
+
+        assert!(json_files(&batch, FileCompression::Snappy, u64::MAX).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_batch_stores_typed_files() {
+        let (mut sink, store) = sink(files(FileFormat::Parquet, FileCompression::Zstd));
+        let result = sink
+            .write_batch(vec![schema_change(), insert(1, "a"), insert(2, "b")])
+            .await
+            .unwrap();
+        assert_eq!(result.records_written, 2);
+        assert!(result.bytes_written > 0);
+
+        let objects: Vec<_> = store.list(None).try_collect().await.unwrap();
+        assert_eq!(objects.len(), 1);
+        let path = objects[0].location.to_string();
+        assert!(path.starts_with("cdc/sales/orders/date="), "{}", path);
+        assert!(path.ends_with("-000.parquet"), "{}", path);
+
+        let file = store.get(&objects[0].location).await.unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file.bytes().await.unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.into_iter().next().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        // Typed by the Relation message, not left as the text pgoutput sent
+        assert_eq!(
+            batch.schema().field(0).data_type(),
+            &IcebergType::Long.arrow_type()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tee_writes_both_formats() {
+        let mut files = files(FileFormat::Parquet, FileCompression::Zstd);
+        files.tee = Some(crate::config::FileTee {
+            url: "gs://lake/cdc".to_string(),
+            format: FileFormat::JsonLines,
+            compression: FileCompression::None,
+        });
+        let (mut sink, store) = sink(files.clone());
+        let tee_store = Arc::new(InMemory::new());
+        sink.outputs.push(Output {
+            store: tee_store.clone(),
+            prefix: vec!["lake".to_string()],
+            files: files.tee_files().unwrap(),
+        });
+
+        let result = sink
+            .write_batch(vec![schema_change(), insert(1, "a"), insert(2, "b")])
+            .await
+            .unwrap();
+        assert_eq!(result.records_written, 2);
+
+        let objects: Vec<_> = store.list(None).try_collect().await.unwrap();
+        assert_eq!(objects.len(), 1);
+        assert!(objects[0].location.as_ref().ends_with("-000.parquet"));
+        let tees: Vec<_> = tee_store.list(None).try_collect().await.unwrap();
+        assert_eq!(tees.len(), 1);
+        let path = tees[0].location.to_string();
+        assert!(path.starts_with("lake/sales/orders/date="), "{}", path);
+        assert!(path.ends_with("-000.jsonl"), "{}", path);
+        // Same name but the extension, so the copies are easy to pair
+        assert_eq!(
+            path.rsplit('/').next().unwrap().split('.').next(),
+            objects[0]
+                .location
+                .as_ref()
+                .rsplit('/')
+                .next()
+                .unwrap()
+                .split('.')
+                .next()
+        );
+
+        let file = tee_store.get(&tees[0].location).await.unwrap();
+        let text = String::from_utf8(file.bytes().await.unwrap().to_vec()).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        // Typed like the Parquet file
+        assert!(
+            text.starts_with("{\"id\":1,\"note\":\"a\",\"_op\":\"I\""),
+            "{}",
+            text
+        );
+    }
+}
@@ -0,0 +1,553 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! # Remote Sink Connector
+//!
+//! This module implements a sink that hands batches to a user-provided sink
+//! server over a gRPC bidirectional stream (`src/proto/remote_sink.proto`).
+//! It lets teams write the destination side in any language while dbmazz
+//! keeps ownership of batching, retries and checkpointing.
+//!
+//! ## Protocol
+//!
+//! ```text
+//! dbmazz                                  sink server
+//!   |--- Handshake ------------------------>|
+//!   |<-- SinkAck(batch_id=0) ---------------|
+//!   |--- RecordBatch(batch_id=1, lsn) ----->|  write to destination
+//!   |<-- SinkAck(batch_id=1, success) ------|
+//!   |           ... one batch in flight ... |
+//! ```
+//!
+//! The pipeline only advances the replication checkpoint once `write_batch`
+//! returns, i.e. after the server acked the batch. A failed stream is reopened
+//! and the batch is resent with the same `batch_id`, so servers must tolerate
+//! redelivery (at-least-once).
+//!
+//! ## Payload
+//!
+//! `RecordBatch.payload` is a JSON array of `CdcRecord` values, the same
+//! serde representation used everywhere else in dbmazz.
+//!
+//! ## Transports
+//!
+//! `SINK_URL` is either a TCP endpoint (`http://host:port`, `host:port`) or a
+//! Unix domain socket (`unix:///run/dbmazz/sink.sock`). The socket variant
+//! skips the network stack for sink servers running on the same host, e.g.
+//! as a sidecar sharing a volume with dbmazz.
+
+pub mod proto {
+    tonic::include_proto!("dbmazz.remote");
+}
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{Channel, Endpoint, Uri};
+use tonic::Streaming;
+use tracing::{info, warn};
+
+use crate::config::SinkConfig;
+use crate::core::sink_error::SinkError;
+use crate::core::timeout::SinkTimeout;
+use crate::core::{
+    pool, CdcRecord, LoadingModel, Sink, SinkCapabilities, SinkResult, SourcePosition,
+};
+
+use self::proto::remote_sink_service_client::RemoteSinkServiceClient;
+use self::proto::sink_request::Payload;
+use self::proto::{Handshake, RecordBatch, SinkAck, SinkRequest};
+
+/// Version of the remote sink protocol spoken by this build
+const PROTOCOL_VERSION: u32 = 1;
+/// Only supported payload encoding for now
+const ENCODING_JSON: &str = "json";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Scheme selecting the Unix domain socket transport
+const UNIX_SCHEME: &str = "unix://";
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Sink that streams batches to a remote sink server and waits for acks.
+pub struct RemoteSink {
+    /// gRPC endpoint of the sink server (e.g. `http://sink-server:50061` or
+    /// `unix:///run/dbmazz/sink.sock`)
+    endpoint: String,
+    /// How long the server may take to write and ack a single batch
+    /// (`SINK_REQUEST_TIMEOUT_SECS`)
+    ack_timeout: Duration,
+    /// Open stream, created lazily and reset after transport errors
+    session: Option<Session>,
+    /// Id of the next batch to send
+    next_batch_id: u64,
+}
+
+/// One open `Replicate` stream.
+struct Session {
+    tx: mpsc::Sender<SinkRequest>,
+    acks: Streaming<SinkAck>,
+}
+
+/// Outcome of a failed delivery attempt.
+enum DeliveryError {
+    /// Transport failure or a rejection the server marked as retryable
+    Retryable(anyhow::Error),
+    /// Rejection the server marked as terminal
+    Fatal(anyhow::Error),
+}
+
+impl RemoteSink {
+    /// Creates a new remote sink. No connection is made until the first batch
+    /// (or `validate_connection`).
+    pub fn new(config: &SinkConfig) -> Result<Self> {
+        let endpoint = normalize_endpoint(&config.url);
+        match unix_socket_path(&endpoint) {
+            Some("") => bail!(
+                "Invalid remote sink endpoint: {} (missing socket path)",
+                endpoint
+            ),
+            Some(_) => {}
+            None => {
+                Endpoint::from_shared(endpoint.clone())
+                    .with_context(|| format!("Invalid remote sink endpoint: {}", endpoint))?;
+            }
+        }
+
+        info!("RemoteSink initialized:");
+        info!("  Endpoint: {}", endpoint);
+
+        Ok(Self {
+            endpoint,
+            session: None,
+            next_batch_id: 1,
+            ack_timeout: config.request_timeout,
+        })
+    }
+
+    /// Sends one batch on the current session (opening it if needed) and
+    /// waits for its ack.
+    async fn deliver(&mut self, batch: &RecordBatch) -> Result<(), DeliveryError> {
+        if self.session.is_none() {
+            let session = open_session(&self.endpoint, self.ack_timeout)
+                .await
+                .map_err(DeliveryError::Retryable)?;
+            self.session = Some(session);
+        }
+        let Some(session) = self.session.as_mut() else {
+            return Err(DeliveryError::Retryable(anyhow!(
+                "remote sink session unavailable"
+            )));
+        };
+
+        let request = SinkRequest {
+            payload: Some(Payload::Batch(batch.clone())),
+        };
+        session
+            .tx
+            .send(request)
+            .await
+            .map_err(|_| DeliveryError::Retryable(anyhow!("remote sink request stream closed")))?;
+
+        let ack = next_ack(&mut session.acks, self.ack_timeout)
+            .await
+            .map_err(DeliveryError::Retryable)?;
+
+        if ack.batch_id != batch.batch_id {
+            return Err(DeliveryError::Retryable(anyhow!(
+                "expected ack for batch {}, got batch {}",
+                batch.batch_id,
+                ack.batch_id
+            )));
+        }
+
+        if ack.success {
+            Ok(())
+        } else {
+            let reason = format!(
+                "rejected batch {} (LSN 0x{:X}): {}",
+                batch.batch_id, batch.lsn, ack.error
+            );
+            if ack.retryable {
+                Err(DeliveryError::Retryable(
+                    SinkError::retryable("remote", reason).into(),
+                ))
+            } else {
+                Err(DeliveryError::Fatal(
+                    SinkError::terminal("remote", reason).into(),
+                ))
+            }
+        }
+    }
+}
+
+/// Adds an `http://` scheme when the configured URL has none.
+fn normalize_endpoint(url: &str) -> String {
+    if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("http://{}", url)
+    }
+}
+
+/// Returns the socket path when the endpoint uses the `unix://` scheme.
+fn unix_socket_path(endpoint: &str) -> Option<&str> {
+    endpoint.strip_prefix(UNIX_SCHEME)
+}
+
+/// Opens a gRPC channel over TCP or a Unix domain socket.
+async fn connect(endpoint: &str) -> Result<Channel> {
+    let Some(path) = unix_socket_path(endpoint) else {
+        return Endpoint::from_shared(endpoint.to_string())
+            .with_context(|| format!("Invalid remote sink endpoint: {}", endpoint))?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect()
+            .await
+            .with_context(|| format!("Failed to connect to remote sink {}", endpoint));
+    };
+
+    // The URI is required by tonic but ignored: the connector always dials the socket
+    let path = path.to_string();
+    Endpoint::from_static("http://localhost")
+        .connect_timeout(CONNECT_TIMEOUT)
+        .connect_with_connector(tower::service_fn(move |_: Uri| {
+            let path = path.clone();
+            async move {
+                let stream = tokio::net::UnixStream::connect(path).await?;
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(stream))
+            }
+        }))
+        .await
+        .with_context(|| format!("Failed to connect to remote sink {}", endpoint))
+}
+
+/// Connects, opens a `Replicate` stream and completes the handshake.
+async fn open_session(endpoint: &str, ack_timeout: Duration) -> Result<Session> {
+    let channel = connect(endpoint).await?;
+    let mut client = RemoteSinkServiceClient::new(channel);
+
+    // One batch in flight at a time, so a single slot is enough
+    let (tx, rx) = mpsc::channel(1);
+    let handshake = SinkRequest {
+        payload: Some(Payload::Handshake(Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            encoding: ENCODING_JSON.to_string(),
+        })),
+    };
+    tx.send(handshake)
+        .await
+        .map_err(|_| anyhow!("remote sink request stream closed"))?;
+
+    let mut acks = client
+        .replicate(ReceiverStream::new(rx))
+        .await
+        .context("Remote sink refused the replication stream")?
+        .into_inner();
+
+    let ack = next_ack(&mut acks, ack_timeout).await?;
+    if ack.batch_id != 0 || !ack.success {
+        bail!("Remote sink rejected the handshake: {}", ack.error);
+    }
+
+    info!("Remote sink stream opened ({})", endpoint);
+    Ok(Session { tx, acks })
+}
+
+/// Waits for the next ack on the stream. A timeout leaves the stream
+/// unusable, so the caller drops the session and retries on a new one.
+async fn next_ack(acks: &mut Streaming<SinkAck>, ack_timeout: Duration) -> Result<SinkAck> {
+    match tokio::time::timeout(ack_timeout, acks.message()).await {
+        Err(_) => Err(SinkTimeout::record("remote", "waiting for an ack", ack_timeout).into()),
+        Ok(Err(status)) => bail!("remote sink stream failed: {}", status.message()),
+        Ok(Ok(None)) => bail!("remote sink closed the stream"),
+        Ok(Ok(Some(ack))) => Ok(ack),
+    }
+}
+
+#[async_trait]
+impl Sink for RemoteSink {
+    fn name(&self) -> &'static str {
+        "remote"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        // What the server does with the records is opaque to dbmazz
+        SinkCapabilities {
+            supports_upsert: true,
+            supports_delete: true,
+            supports_schema_evolution: true,
+            supports_transactions: false,
+            loading_model: LoadingModel::Streaming,
+            min_batch_size: Some(1),
+            max_batch_size: Some(50_000),
+            optimal_flush_interval_ms: 1000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        // A completed handshake proves the server speaks the protocol
+        open_session(&self.endpoint, self.ack_timeout)
+            .await
+            .map(|_| ())
+    }
+
+    async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult> {
+        if records.is_empty() {
+            return Ok(SinkResult {
+                records_written: 0,
+                bytes_written: 0,
+                last_position: None,
+                ..Default::default()
+            });
+        }
+
+        // Track last position from records
+        let last_position = records.iter().rev().find_map(|r| match r {
+            CdcRecord::Insert { position, .. }
+            | CdcRecord::Update { position, .. }
+            | CdcRecord::Delete { position, .. }
+            | CdcRecord::Commit { position, .. }
+            | CdcRecord::Heartbeat { position, .. } => Some(position.clone()),
+            _ => None,
+        });
+        let lsn = match &last_position {
+            Some(SourcePosition::Lsn(lsn)) => *lsn,
+            _ => 0,
+        };
+
+        let payload =
+            serde_json::to_vec(&records).context("Failed to encode batch for remote sink")?;
+        let bytes_written = payload.len() as u64;
+        let batch = RecordBatch {
+            batch_id: self.next_batch_id,
+            lsn,
+            record_count: records.len() as u32,
+            encoding: ENCODING_JSON.to_string(),
+            payload: payload.into(),
+        };
+        pool::recycle(records);
+        self.next_batch_id += 1;
+
+        let mut attempt = 0;
+        loop {
+            match self.deliver(&batch).await {
+                Ok(()) => break,
+                Err(DeliveryError::Fatal(e)) => return Err(e),
+                Err(DeliveryError::Retryable(e)) => {
+                    // Start over on a fresh stream; the server sees the same batch_id again
+                    self.session = None;
+                    attempt += 1;
+                    if attempt >= MAX_ATTEMPTS {
+                        return Err(e.context(format!(
+                            "Remote sink failed after {} attempts",
+                            MAX_ATTEMPTS
+                        )));
+                    }
+
+                    warn!(
+                        "Retry {}/{} for remote batch {}: {}",
+                        attempt, MAX_ATTEMPTS, batch.batch_id, e
+                    );
+
+                    // Exponential backoff: 200ms, 400ms...
+                    tokio::time::sleep(Duration::from_millis(100 * 2_u64.pow(attempt))).await;
+                }
+            }
+        }
+
+        Ok(SinkResult {
+            records_written: batch.record_count as usize,
+            bytes_written,
+            last_position,
+            ..Default::default()
+        })
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        // Dropping the sender ends the request stream cleanly
+        self.session = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::remote_sink_service_server::{RemoteSinkService, RemoteSinkServiceServer};
+    use super::*;
+    use crate::config::{SinkType, DEFAULT_SINK_REQUEST_TIMEOUT};
+    use crate::core::{ColumnValue, TableRef, Value};
+    use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
+    use tonic::{Request, Response, Status};
+
+    /// In-process sink server that acks (or rejects) every batch.
+    struct MockSinkServer {
+        reject: bool,
+    }
+
+    #[tonic::async_trait]
+    impl RemoteSinkService for MockSinkServer {
+        type ReplicateStream = ReceiverStream<Result<SinkAck, Status>>;
+
+        async fn replicate(
+            &self,
+            request: Request<Streaming<SinkRequest>>,
+        ) -> Result<Response<Self::ReplicateStream>, Status> {
+            let mut inbound = request.into_inner();
+            let (tx, rx) = mpsc::channel(4);
+            let reject = self.reject;
+
+            tokio::spawn(async move {
+                while let Ok(Some(request)) = inbound.message().await {
+                    let ack = match request.payload {
+                        Some(Payload::Handshake(_)) => SinkAck {
+                            batch_id: 0,
+                            success: true,
+                            ..Default::default()
+                        },
+                        Some(Payload::Batch(batch)) => {
+                            let records: Vec<CdcRecord> =
+                                serde_json::from_slice(&batch.payload).unwrap();
+                            assert_eq!(records.len(), batch.record_count as usize);
+                            SinkAck {
+                                batch_id: batch.batch_id,
+                                lsn: batch.lsn,
+                                success: !reject,
+                                error: if reject {
+                                    "bad row".into()
+                                } else {
+                                    String::new()
+                                },
+                                retryable: false,
+                            }
+                        }
+                        None => continue,
+                    };
+                    if tx.send(Ok(ack)).await.is_err() {
+                        break;
+                    }
+                }
+            });
+
+            Ok(Response::new(ReceiverStream::new(rx)))
+        }
+    }
+
+    async fn start_server(reject: bool) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(RemoteSinkServiceServer::new(MockSinkServer { reject }))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        format!("http://{}", addr)
+    }
+
+    fn test_config(url: &str) -> SinkConfig {
+        SinkConfig {
+            sink_type: SinkType::Remote,
+            url: url.to_string(),
+            port: 0,
+            database: String::new(),
+            user: String::new(),
+            password: String::new(),
+            starrocks: None,
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        }
+    }
+
+    fn insert(id: i64, lsn: u64) -> CdcRecord {
+        CdcRecord::Insert {
+            table: TableRef::new(Some("public".into()), "orders".into()),
+            columns: vec![ColumnValue::new("id".into(), Value::Int64(id))],
+            position: SourcePosition::Lsn(lsn),
+        }
+    }
+
+    #[test]
+    fn test_normalize_endpoint() {
+        assert_eq!(normalize_endpoint("sink:50061"), "http://sink:50061");
+        assert_eq!(normalize_endpoint("https://sink:443"), "https://sink:443");
+        assert_eq!(
+            normalize_endpoint("unix:///tmp/sink.sock"),
+            "unix:///tmp/sink.sock"
+        );
+        assert_eq!(
+            unix_socket_path("unix:///tmp/sink.sock"),
+            Some("/tmp/sink.sock")
+        );
+        assert_eq!(unix_socket_path("http://sink:50061"), None);
+        assert!(RemoteSink::new(&test_config("unix://")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_acked() {
+        let url = start_server(false).await;
+        let mut sink = RemoteSink::new(&test_config(&url)).unwrap();
+
+        sink.validate_connection().await.unwrap();
+
+        let result = sink
+            .write_batch(vec![insert(1, 10), insert(2, 20)])
+            .await
+            .unwrap();
+        assert_eq!(result.records_written, 2);
+        assert!(matches!(
+            result.last_position,
+            Some(SourcePosition::Lsn(20))
+        ));
+
+        // Second batch reuses the open stream
+        sink.write_batch(vec![insert(3, 30)]).await.unwrap();
+        assert_eq!(sink.next_batch_id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_terminal_rejection_is_not_retried() {
+        let url = start_server(true).await;
+        let mut sink = RemoteSink::new(&test_config(&url)).unwrap();
+
+        let err = sink.write_batch(vec![insert(1, 10)]).await.unwrap_err();
+        assert!(err.to_string().contains("bad row"));
+        assert!(!crate::core::sink_error::is_retryable(&err));
+        // The stream stays usable after a clean rejection
+        assert!(sink.session.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_transport() {
+        let path = std::env::temp_dir().join(format!("dbmazz-sink-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(RemoteSinkServiceServer::new(MockSinkServer {
+                    reject: false,
+                }))
+                .serve_with_incoming(UnixListenerStream::new(listener)),
+        );
+
+        let url = format!("unix://{}", path.display());
+        let mut sink = RemoteSink::new(&test_config(&url)).unwrap();
+        let result = sink.write_batch(vec![insert(1, 10)]).await.unwrap();
+        assert_eq!(result.records_written, 1);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
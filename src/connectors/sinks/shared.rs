@@ -0,0 +1,146 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Connections shared by the sinks of a named destination (`DESTINATIONS`).
+//!
+//! Sinks whose config names the same destination write to the same endpoint
+//! with the same credentials, so they take one HTTP client (and its
+//! keep-alive connections) or one MySQL pool per database from here instead
+//! of opening their own. Sinks without a destination get private ones, as
+//! before.
+
+use anyhow::Result;
+use mysql_async::{OptsBuilder, Pool, PoolConstraints, PoolOpts};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::config::{SharedConnections, SinkConfig};
+use crate::connectors::http::client_builder;
+
+/// HTTP clients by destination
+static HTTP_CLIENTS: OnceLock<Mutex<HashMap<String, reqwest::Client>>> = OnceLock::new();
+
+/// MySQL pools by destination and database
+static MYSQL_POOLS: OnceLock<Mutex<HashMap<(String, String), Pool>>> = OnceLock::new();
+
+/// HTTP client of `config`'s sink: its destination's, created on first use,
+/// or a new one. Every sink of a destination has the same request timeout
+/// and proxy, since routes take them from the default sink.
+pub(crate) fn http_client(config: &SinkConfig) -> Result<reqwest::Client> {
+    let build = || -> Result<reqwest::Client> {
+        let mut builder = client_builder(&config.http)?.timeout(config.request_timeout);
+        if let Some(size) = config.shared.as_ref().and_then(|s| s.pool_size) {
+            builder = builder.pool_max_idle_per_host(size);
+        }
+        Ok(builder.build()?)
+    };
+    let Some(shared) = &config.shared else {
+        return build();
+    };
+    let mut clients = HTTP_CLIENTS.get_or_init(Default::default).lock();
+    if let Some(client) = clients.get(&shared.destination) {
+        return Ok(client.clone());
+    }
+    let client = build()?;
+    clients.insert(shared.destination.clone(), client.clone());
+    Ok(client)
+}
+
+/// MySQL pool for `opts`, which connect to `database`: the one `shared`'s
+/// sinks use for that database, or a new pool.
+pub(crate) fn mysql_pool(
+    shared: Option<&SharedConnections>,
+    database: &str,
+    opts: OptsBuilder,
+) -> Pool {
+    let Some(shared) = shared else {
+        return Pool::new(opts);
+    };
+    let mut pools = MYSQL_POOLS.get_or_init(Default::default).lock();
+    pools
+        .entry((shared.destination.clone(), database.to_string()))
+        .or_insert_with(|| {
+            let opts = match shared
+                .pool_size
+                .and_then(|size| PoolConstraints::new(1, size))
+            {
+                Some(constraints) => {
+                    opts.pool_opts(PoolOpts::default().with_constraints(constraints))
+                }
+                None => opts,
+            };
+            Pool::new(opts)
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SinkType, DEFAULT_SINK_REQUEST_TIMEOUT};
+
+    fn sink(shared: Option<SharedConnections>) -> SinkConfig {
+        SinkConfig {
+            sink_type: SinkType::ClickHouse,
+            url: "http://clickhouse:8123".to_string(),
+            port: 0,
+            database: "cdc".to_string(),
+            user: "default".to_string(),
+            password: String::new(),
+            starrocks: None,
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_http_client_is_shared_by_destination() {
+        let shared = SharedConnections {
+            destination: "test_http_warehouse".to_string(),
+            pool_size: Some(4),
+        };
+        http_client(&sink(None)).unwrap();
+        let clients = HTTP_CLIENTS.get_or_init(Default::default).lock();
+        assert!(!clients.contains_key("test_http_warehouse"));
+        drop(clients);
+
+        http_client(&sink(Some(shared.clone()))).unwrap();
+        http_client(&sink(Some(shared))).unwrap();
+        let clients = HTTP_CLIENTS.get().unwrap().lock();
+        assert!(clients.contains_key("test_http_warehouse"));
+    }
+
+    #[test]
+    fn test_mysql_pool_is_shared_per_database() {
+        let shared = SharedConnections {
+            destination: "test_mysql_warehouse".to_string(),
+            pool_size: Some(2),
+        };
+        let opts = || OptsBuilder::default().ip_or_hostname("starrocks");
+        for database in ["cdc", "audit", "cdc"] {
+            mysql_pool(Some(&shared), database, opts());
+        }
+        mysql_pool(None, "cdc", opts());
+        let pools = MYSQL_POOLS.get().unwrap().lock();
+        let keys: Vec<&(String, String)> = pools
+            .keys()
+            .filter(|(destination, _)| destination == "test_mysql_warehouse")
+            .collect();
+        assert_eq!(keys.len(), 2);
+    }
+}
@@ -0,0 +1,1418 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! # SQLite Sink Connector
+//!
+//! This module implements a CDC sink that replicates into a local SQLite
+//! database file. It targets embedded and edge deployments (devices, kiosks,
+//! branch servers) that need a queryable local copy of a few upstream tables
+//! without running a warehouse next to them.
+//!
+//! ## Features
+//!
+//! - **WAL journal mode**: local readers can query the file while dbmazz writes
+//! - **Upsert by primary key**: `INSERT ... ON CONFLICT (pk) DO UPDATE`
+//! - **Key changes**: an update that changes the primary key deletes the row
+//!   under the old key before writing the new one
+//! - **Hard deletes**: rows are removed by primary key
+//! - **Partial updates**: unchanged TOAST columns are left untouched
+//! - **Schema evolution**: missing tables are created and new columns are
+//!   added with `ALTER TABLE ADD COLUMN`
+//! - **Atomic batches**: every batch is applied in a single SQLite transaction,
+//!   which spans the whole pushed batch between `begin_batch` and `commit_batch`
+//! - **Checkpoint table**: with `SINK_CHECKPOINT_TABLE=true` the batch LSN is
+//!   upserted into `_dbmazz_checkpoints` in that same transaction. With
+//!   `EXACTLY_ONCE=true` replication resumes after that LSN, so no batch
+//!   the file holds is written again
+//! - **Rejected rows**: constraint and type errors are reported as rejected
+//!   data, and with `DEAD_LETTER_TARGET=table` the rows end up in
+//!   `_dbmazz_dead_letters`
+//! - **Schema mismatches**: a table or column dropped from the file out of
+//!   band is reported as a schema mismatch, and the table's columns are
+//!   compared with the source by affinity
+//!
+//! ## Primary Keys
+//!
+//! The sink reads the key of each table from `PRAGMA table_info`. The engine
+//! setup phase pre-creates tables with the source primary key, so upserts work
+//! out of the box. Tables without a primary key fall back to append semantics
+//! for inserts and match deletes on the full old row.
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use dbmazz::connectors::sinks::sqlite::SqliteSink;
+//!
+//! // SINK_TYPE=sqlite SINK_URL=/var/lib/dbmazz/replica.db
+//! let mut sink = SqliteSink::new(&config)?;
+//! sink.write_batch(records).await?;
+//! ```
+
+pub(crate) mod types;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rusqlite::{params, params_from_iter, Connection, ErrorCode, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::{is_internal_table, CHECKPOINT_TABLE, DEAD_LETTER_TABLE};
+use crate::config::{SinkConfig, TableNaming};
+use crate::core::dead_letter::{DeadLetter, RejectedData};
+use crate::core::schema_diff::SchemaDiff;
+use crate::core::sink_error::SinkError;
+use crate::core::{
+    pool, CdcRecord, ColumnValue, LoadingModel, Sink, SinkCapabilities, SinkCheckpoint, SinkResult,
+    SourcePosition, TableRef,
+};
+use crate::pipeline::schema_cache::SchemaDelta;
+
+use self::types::{
+    data_type_to_affinity, declared_affinity, pg_type_id_to_affinity, value_to_affinity,
+    value_to_sql,
+};
+
+/// How long a write waits on a lock held by a local reader before failing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SQLite sink connector implementing the Sink trait.
+///
+/// The connection lives behind a mutex so batches can be applied on the
+/// blocking thread pool without holding up the async runtime.
+pub struct SqliteSink {
+    /// Path of the database file
+    path: String,
+    /// Connection and per-table metadata, shared with blocking writer tasks
+    state: Arc<Mutex<SqliteState>>,
+    /// Slot whose LSN is written to `_dbmazz_checkpoints` on commit
+    checkpoint_slot: Option<String>,
+}
+
+/// Columns and primary key of a table in the SQLite file.
+#[derive(Debug, Clone, Default)]
+struct TableInfo {
+    columns: HashSet<String>,
+    key_columns: Vec<String>,
+}
+
+impl TableInfo {
+    fn exists(&self) -> bool {
+        !self.columns.is_empty()
+    }
+
+    fn is_key(&self, column: &str) -> bool {
+        self.key_columns.iter().any(|k| k == column)
+    }
+}
+
+struct SqliteState {
+    conn: Connection,
+    /// Table metadata keyed by sink table name, loaded lazily
+    tables: HashMap<String, TableInfo>,
+    /// How source tables map to table names in the file
+    naming: TableNaming,
+    /// A transaction opened by `begin_batch` is pending
+    in_batch: bool,
+}
+
+impl SqliteSink {
+    /// Creates a new SQLite sink from the provided configuration.
+    ///
+    /// `SINK_URL` is the path of the database file; an optional `sqlite://`
+    /// prefix is accepted. The file is created if it doesn't exist.
+    pub fn new(config: &SinkConfig) -> Result<Self> {
+        let mut sink = Self::open(sqlite_path(&config.url), config.naming.clone())?;
+        sink.checkpoint_slot = config.checkpoint_slot.clone();
+
+        info!("SqliteSink initialized:");
+        info!("  File: {}", sink.path);
+
+        Ok(sink)
+    }
+
+    /// Opens (or creates) the database file at `path`.
+    fn open(path: &str, naming: TableNaming) -> Result<Self> {
+        let conn = open_database(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            state: Arc::new(Mutex::new(SqliteState {
+                conn,
+                tables: HashMap::new(),
+                naming,
+                in_batch: false,
+            })),
+            checkpoint_slot: None,
+        })
+    }
+}
+
+impl SqliteState {
+    /// Applies a batch of records inside one transaction, or inside the one
+    /// opened by `begin_batch`.
+    /// Returns the number of rows inserted, updated or deleted.
+    fn apply_batch(&mut self, records: &[CdcRecord]) -> Result<usize> {
+        let Self {
+            conn,
+            tables,
+            naming,
+            in_batch,
+        } = self;
+        if *in_batch {
+            let mut written = 0;
+            for record in records {
+                written += apply_record(conn, tables, naming, record)?;
+            }
+            return Ok(written);
+        }
+
+        let tx = conn
+            .transaction()
+            .context("Failed to begin SQLite transaction")?;
+
+        let mut written = 0;
+        for record in records {
+            written += apply_record(&tx, tables, naming, record)?;
+        }
+
+        tx.commit().context("Failed to commit SQLite transaction")?;
+        Ok(written)
+    }
+
+    /// Runs a transaction control statement for the batch hooks.
+    fn control(&mut self, sql: &str) -> Result<()> {
+        self.conn
+            .execute_batch(sql)
+            .with_context(|| format!("SQLite {} failed", sql))
+    }
+}
+
+/// Applies a single record. Returns the number of rows affected.
+fn apply_record(
+    conn: &Connection,
+    tables: &mut HashMap<String, TableInfo>,
+    naming: &TableNaming,
+    record: &CdcRecord,
+) -> Result<usize> {
+    let sink_name = |table: &TableRef| naming.sink_table(&table.qualified_name()).table;
+    match record {
+        CdcRecord::Insert { table, columns, .. } => {
+            if is_internal_table(&table.name) {
+                return Ok(0);
+            }
+            let name = sink_name(table);
+            let meta = table_info(conn, tables, &name)?;
+            ensure_row_columns(conn, &name, meta, columns)?;
+            upsert_row(conn, &name, meta, columns)
+        }
+
+        CdcRecord::Update {
+            table,
+            old_columns,
+            new_columns,
+            ..
+        } => {
+            if is_internal_table(&table.name) {
+                return Ok(0);
+            }
+            let name = sink_name(table);
+            let meta = table_info(conn, tables, &name)?;
+            ensure_row_columns(conn, &name, meta, new_columns)?;
+
+            let Some(old) = old_columns else {
+                return upsert_row(conn, &name, meta, new_columns);
+            };
+            // Without a key there is no conflict target: replace the old row
+            if meta.key_columns.is_empty() {
+                delete_row(conn, &name, meta, old)?;
+                return upsert_row(conn, &name, meta, new_columns);
+            }
+            if !key_changed(meta, old, new_columns) {
+                return upsert_row(conn, &name, meta, new_columns);
+            }
+
+            // A changed key moves the row: the row under the old key is
+            // deleted, and unchanged TOAST columns are taken from the old
+            // image (a key-only image has NULL in every other column, so only
+            // non-NULL old values are known to be the stored ones)
+            delete_row(conn, &name, meta, old)?;
+            let moved: Vec<ColumnValue> = new_columns
+                .iter()
+                .map(|c| match old.iter().find(|o| o.name == c.name) {
+                    Some(o) if c.value.is_unchanged() && !o.value.is_null() => o.clone(),
+                    _ => c.clone(),
+                })
+                .collect();
+            upsert_row(conn, &name, meta, &moved)
+        }
+
+        CdcRecord::Delete { table, columns, .. } => {
+            if is_internal_table(&table.name) {
+                return Ok(0);
+            }
+            let name = sink_name(table);
+            let meta = table_info(conn, tables, &name)?;
+            if !meta.exists() {
+                return Ok(0);
+            }
+            delete_row(conn, &name, meta, columns)
+        }
+
+        CdcRecord::SchemaChange { table, columns, .. } => {
+            if is_internal_table(&table.name) {
+                return Ok(0);
+            }
+            let name = sink_name(table);
+            let meta = table_info(conn, tables, &name)?;
+            let defs: Vec<(&str, &'static str)> = columns
+                .iter()
+                .map(|c| (c.name.as_str(), data_type_to_affinity(&c.data_type)))
+                .collect();
+            ensure_columns(conn, &name, meta, &defs)?;
+            Ok(0)
+        }
+
+        // Transaction markers and heartbeats don't need sink writes
+        CdcRecord::Begin { .. } | CdcRecord::Commit { .. } | CdcRecord::Heartbeat { .. } => Ok(0),
+    }
+}
+
+/// Whether an update moves the row to another primary key. Key columns
+/// missing from either image are treated as unchanged.
+fn key_changed(meta: &TableInfo, old: &[ColumnValue], new: &[ColumnValue]) -> bool {
+    let value = |row: &[ColumnValue], key: &str| {
+        row.iter()
+            .find(|c| &*c.name == key)
+            .map(|c| c.value.clone())
+    };
+    meta.key_columns
+        .iter()
+        .any(|key| match (value(old, key), value(new, key)) {
+            (Some(old), Some(new)) => old != new,
+            _ => false,
+        })
+}
+
+/// Returns cached metadata for `table`, loading it from the file on first use.
+fn table_info<'a>(
+    conn: &Connection,
+    tables: &'a mut HashMap<String, TableInfo>,
+    table: &str,
+) -> Result<&'a mut TableInfo> {
+    if !tables.contains_key(table) {
+        let meta = load_table_info(conn, table)?;
+        tables.insert(table.to_string(), meta);
+    }
+    tables
+        .get_mut(table)
+        .ok_or_else(|| anyhow::anyhow!("table metadata for {} missing", table))
+}
+
+/// Reads columns and primary key of `table` via `PRAGMA table_info`.
+/// Returns an empty `TableInfo` when the table doesn't exist.
+fn load_table_info(conn: &Connection, table: &str) -> Result<TableInfo> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", quote_ident(table)))
+        .with_context(|| format!("Failed to read schema of SQLite table {}", table))?;
+
+    // Columns: cid, name, type, notnull, dflt_value, pk (1-based key position)
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(1)?, row.get::<_, i64>(5)?))
+    })?;
+
+    let mut meta = TableInfo::default();
+    let mut keys: Vec<(i64, String)> = Vec::new();
+    for row in rows {
+        let (name, pk) = row?;
+        if pk > 0 {
+            keys.push((pk, name.clone()));
+        }
+        meta.columns.insert(name);
+    }
+    keys.sort();
+    meta.key_columns = keys.into_iter().map(|(_, name)| name).collect();
+
+    Ok(meta)
+}
+
+/// Columns of `table` with the affinity of their declared type, `None` when
+/// the table doesn't exist.
+fn column_affinities(conn: &Connection, table: &str) -> Result<Option<Vec<(String, String)>>> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", quote_ident(table)))
+        .with_context(|| format!("Failed to read schema of SQLite table {}", table))?;
+    let columns = stmt
+        .query_map([], |row| {
+            let decl_type: String = row.get(2)?;
+            Ok((
+                row.get::<_, String>(1)?,
+                declared_affinity(&decl_type).to_string(),
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok((!columns.is_empty()).then_some(columns))
+}
+
+/// Adds any column of `row` that the table doesn't have yet, inferring the
+/// affinity from the value.
+fn ensure_row_columns(
+    conn: &Connection,
+    table: &str,
+    meta: &mut TableInfo,
+    row: &[ColumnValue],
+) -> Result<()> {
+    if row.iter().all(|c| meta.columns.contains(&*c.name)) {
+        return Ok(());
+    }
+    let defs: Vec<(&str, &'static str)> = row
+        .iter()
+        .map(|c| (&*c.name, value_to_affinity(&c.value)))
+        .collect();
+    ensure_columns(conn, table, meta, &defs)
+}
+
+/// Creates the table if it doesn't exist, otherwise adds missing columns.
+fn ensure_columns(
+    conn: &Connection,
+    table: &str,
+    meta: &mut TableInfo,
+    columns: &[(&str, &'static str)],
+) -> Result<()> {
+    if !meta.exists() {
+        warn!(
+            "SQLite table {} not found, creating it without a primary key",
+            table
+        );
+        let defs: Vec<(String, &str)> = columns
+            .iter()
+            .map(|(name, affinity)| (name.to_string(), *affinity))
+            .collect();
+        conn.execute(&create_table_sql(table, &defs, &[]), [])
+            .with_context(|| format!("Failed to create SQLite table {}", table))?;
+        meta.columns = columns.iter().map(|(name, _)| name.to_string()).collect();
+        return Ok(());
+    }
+
+    for (name, affinity) in columns {
+        if meta.columns.contains(*name) {
+            continue;
+        }
+        info!("[SCHEMA] Adding column {} {} to {}", name, affinity, table);
+        let sql = format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            quote_ident(table),
+            quote_ident(name),
+            affinity
+        );
+        conn.execute(&sql, [])
+            .with_context(|| format!("Failed to add column {} to {}", name, table))?;
+        meta.columns.insert(name.to_string());
+    }
+    Ok(())
+}
+
+/// Inserts a row, updating it in place when the primary key already exists.
+/// Unchanged TOAST columns are left out so the stored value is preserved.
+fn upsert_row(
+    conn: &Connection,
+    table: &str,
+    meta: &TableInfo,
+    columns: &[ColumnValue],
+) -> Result<usize> {
+    let present: Vec<&ColumnValue> = columns.iter().filter(|c| !c.value.is_unchanged()).collect();
+    if present.is_empty() {
+        return Ok(0);
+    }
+
+    let names: Vec<String> = present.iter().map(|c| quote_ident(&c.name)).collect();
+    let placeholders: Vec<String> = (1..=present.len()).map(|i| format!("?{}", i)).collect();
+    let mut sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote_ident(table),
+        names.join(", "),
+        placeholders.join(", ")
+    );
+
+    if !meta.key_columns.is_empty() {
+        let keys: Vec<String> = meta.key_columns.iter().map(|k| quote_ident(k)).collect();
+        let updates: Vec<String> = present
+            .iter()
+            .filter(|c| !meta.is_key(&c.name))
+            .map(|c| {
+                let col = quote_ident(&c.name);
+                format!("{} = excluded.{}", col, col)
+            })
+            .collect();
+
+        if updates.is_empty() {
+            sql.push_str(&format!(" ON CONFLICT ({}) DO NOTHING", keys.join(", ")));
+        } else {
+            sql.push_str(&format!(
+                " ON CONFLICT ({}) DO UPDATE SET {}",
+                keys.join(", "),
+                updates.join(", ")
+            ));
+        }
+    }
+
+    let mut stmt = conn
+        .prepare_cached(&sql)
+        .map_err(row_error)
+        .with_context(|| format!("Failed to prepare upsert for {}", table))?;
+    let affected = stmt
+        .execute(params_from_iter(
+            present.iter().map(|c| value_to_sql(&c.value)),
+        ))
+        .map_err(row_error)
+        .with_context(|| format!("Failed to upsert row into {}", table))?;
+    Ok(affected)
+}
+
+/// Deletes the row identified by the primary key. Tables without a key match
+/// on every known column of the old row and remove a single occurrence.
+fn delete_row(
+    conn: &Connection,
+    table: &str,
+    meta: &TableInfo,
+    columns: &[ColumnValue],
+) -> Result<usize> {
+    let matching: Vec<&ColumnValue> = if meta.key_columns.is_empty() {
+        columns.iter().filter(|c| !c.value.is_unchanged()).collect()
+    } else {
+        columns.iter().filter(|c| meta.is_key(&c.name)).collect()
+    };
+    if matching.is_empty() {
+        return Ok(0);
+    }
+
+    // IS instead of = so NULL column values still match
+    let predicate: Vec<String> = matching
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{} IS ?{}", quote_ident(&c.name), i + 1))
+        .collect();
+    let table_ident = quote_ident(table);
+    let sql = if meta.key_columns.is_empty() {
+        format!(
+            "DELETE FROM {t} WHERE rowid IN (SELECT rowid FROM {t} WHERE {p} LIMIT 1)",
+            t = table_ident,
+            p = predicate.join(" AND ")
+        )
+    } else {
+        format!(
+            "DELETE FROM {} WHERE {}",
+            table_ident,
+            predicate.join(" AND ")
+        )
+    };
+
+    let mut stmt = conn
+        .prepare_cached(&sql)
+        .map_err(row_error)
+        .with_context(|| format!("Failed to prepare delete for {}", table))?;
+    let affected = stmt
+        .execute(params_from_iter(
+            matching.iter().map(|c| value_to_sql(&c.value)),
+        ))
+        .map_err(row_error)
+        .with_context(|| format!("Failed to delete row from {}", table))?;
+    Ok(affected)
+}
+
+/// Error of a row write. Constraint and type errors come from the row's
+/// values, so they are reported as rejected data; a table or column that is
+/// gone (dropped from the file behind the sink's back) is a schema
+/// mismatch; a file that can't be written at all is terminal.
+fn row_error(err: rusqlite::Error) -> anyhow::Error {
+    let message = err.to_string();
+    if ["has no column named", "no such column", "no such table"]
+        .iter()
+        .any(|m| message.contains(m))
+    {
+        return SinkError::schema_mismatch("sqlite", message).into();
+    }
+    match err.sqlite_error_code() {
+        Some(ErrorCode::ConstraintViolation | ErrorCode::TypeMismatch | ErrorCode::TooBig) => {
+            RejectedData::new("sqlite", err.to_string()).into()
+        }
+        Some(
+            ErrorCode::ReadOnly
+            | ErrorCode::CannotOpen
+            | ErrorCode::PermissionDenied
+            | ErrorCode::NotADatabase
+            | ErrorCode::DatabaseCorrupt,
+        ) => SinkError::terminal("sqlite", err.to_string()).into(),
+        _ => err.into(),
+    }
+}
+
+/// Strips the optional `sqlite://` / `sqlite:` scheme from a sink URL.
+pub(crate) fn sqlite_path(url: &str) -> &str {
+    url.strip_prefix("sqlite://")
+        .or_else(|| url.strip_prefix("sqlite:"))
+        .unwrap_or(url)
+}
+
+/// Opens a database file with the pragmas used by the sink.
+pub(crate) fn open_database(path: &str) -> Result<Connection> {
+    let conn = Connection::open(path)
+        .with_context(|| format!("Failed to open SQLite database {}", path))?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+
+    let journal_mode: String =
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        // In-memory databases report "memory"; anything else is worth a warning
+        if path != ":memory:" {
+            warn!(
+                "SQLite journal_mode is {} (WAL not available for {})",
+                journal_mode, path
+            );
+        }
+    }
+    // NORMAL is durable across application crashes in WAL mode
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+    Ok(conn)
+}
+
+/// Builds a `CREATE TABLE IF NOT EXISTS` statement from (name, affinity) pairs.
+pub(crate) fn create_table_sql(
+    table: &str,
+    columns: &[(String, &str)],
+    key_columns: &[String],
+) -> String {
+    create_documented_table_sql(table, columns, key_columns, None, &[])
+}
+
+/// Like [`create_table_sql`], with the source table and column comments.
+///
+/// SQLite has no `COMMENT` clause, but it keeps the original `CREATE TABLE`
+/// text in `sqlite_master.sql`, so comments are written as `--` lines that
+/// show up in `.schema` and schema browsers. `column_comments` is aligned with
+/// `columns`; without any comment the statement is the plain single-line form.
+pub(crate) fn create_documented_table_sql(
+    table: &str,
+    columns: &[(String, &str)],
+    key_columns: &[String],
+    table_comment: Option<&str>,
+    column_comments: &[Option<&str>],
+) -> String {
+    let mut defs: Vec<String> = columns
+        .iter()
+        .map(|(name, affinity)| format!("{} {}", quote_ident(name), affinity))
+        .collect();
+    if !key_columns.is_empty() {
+        let keys: Vec<String> = key_columns.iter().map(|k| quote_ident(k)).collect();
+        defs.push(format!("PRIMARY KEY ({})", keys.join(", ")));
+    }
+
+    let documented = table_comment.is_some() || column_comments.iter().any(Option::is_some);
+    if !documented {
+        return format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            quote_ident(table),
+            defs.join(", ")
+        );
+    }
+
+    let mut sql = format!("CREATE TABLE IF NOT EXISTS {} (", quote_ident(table));
+    if let Some(comment) = table_comment {
+        sql.push_str(&format!(" -- {}", single_line(comment)));
+    }
+    let last = defs.len().saturating_sub(1);
+    for (i, def) in defs.iter().enumerate() {
+        sql.push_str("\n  ");
+        sql.push_str(def);
+        if i < last {
+            sql.push(',');
+        }
+        if let Some(Some(comment)) = column_comments.get(i) {
+            sql.push_str(&format!(" -- {}", single_line(comment)));
+        }
+    }
+    sql.push_str("\n)");
+    sql
+}
+
+/// A `--` comment runs to the end of the line, so newlines must not leak
+/// into the DDL.
+fn single_line(comment: &str) -> String {
+    comment.replace(['\r', '\n'], " ")
+}
+
+/// Upserts the LSN of `slot` into the checkpoint table, creating it on first
+/// use. Runs inside the batch transaction.
+fn write_checkpoint(conn: &Connection, slot: &str, lsn: u64) -> Result<()> {
+    let create = create_table_sql(
+        CHECKPOINT_TABLE,
+        &[
+            ("slot_name".to_string(), "TEXT"),
+            ("lsn".to_string(), "INTEGER"),
+            ("updated_at".to_string(), "TEXT"),
+        ],
+        &["slot_name".to_string()],
+    );
+    conn.execute_batch(&create)
+        .context("Failed to create SQLite checkpoint table")?;
+    conn.execute(
+        &format!(
+            "INSERT INTO {} (slot_name, lsn, updated_at) \
+             VALUES (?1, ?2, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')) \
+             ON CONFLICT (slot_name) DO UPDATE SET lsn = excluded.lsn, updated_at = excluded.updated_at",
+            quote_ident(CHECKPOINT_TABLE)
+        ),
+        params![slot, lsn as i64],
+    )
+    .context("Failed to write SQLite checkpoint")?;
+    Ok(())
+}
+
+/// The LSN of `slot` in the checkpoint table, `None` without a row or table.
+fn read_checkpoint(conn: &Connection, slot: &str) -> Result<Option<u64>> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            params![CHECKPOINT_TABLE],
+            |r| r.get(0),
+        )
+        .context("Failed to look up SQLite checkpoint table")?;
+    if !exists {
+        return Ok(None);
+    }
+    let lsn: Option<i64> = conn
+        .query_row(
+            &format!(
+                "SELECT lsn FROM {} WHERE slot_name = ?1",
+                quote_ident(CHECKPOINT_TABLE)
+            ),
+            params![slot],
+            |r| r.get(0),
+        )
+        .optional()
+        .context("Failed to read SQLite checkpoint")?;
+    Ok(lsn.map(|lsn| lsn as u64))
+}
+
+/// Appends rejected rows to the dead-letter table in one transaction,
+/// creating the table on first use.
+fn insert_dead_letters(conn: &mut Connection, letters: &[DeadLetter]) -> Result<()> {
+    let create = create_table_sql(
+        DEAD_LETTER_TABLE,
+        &[
+            ("failed_at".to_string(), "TEXT"),
+            ("table_name".to_string(), "TEXT"),
+            ("op".to_string(), "TEXT"),
+            ("lsn".to_string(), "INTEGER"),
+            ("row".to_string(), "TEXT"),
+            ("old_row".to_string(), "TEXT"),
+            ("error".to_string(), "TEXT"),
+        ],
+        &[],
+    );
+    let tx = conn
+        .transaction()
+        .context("Failed to begin SQLite transaction")?;
+    tx.execute_batch(&create)
+        .context("Failed to create SQLite dead-letter table")?;
+    {
+        let mut stmt = tx
+            .prepare_cached(&format!(
+                "INSERT INTO {} (failed_at, table_name, op, lsn, row, old_row, error) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                quote_ident(DEAD_LETTER_TABLE)
+            ))
+            .context("Failed to prepare dead-letter insert")?;
+        for letter in letters {
+            stmt.execute(params![
+                letter.failed_at.to_rfc3339(),
+                letter.table,
+                letter.op,
+                letter.lsn as i64,
+                serde_json::Value::Object(letter.row.clone()).to_string(),
+                letter
+                    .old_row
+                    .as_ref()
+                    .map(|old| serde_json::Value::Object(old.clone()).to_string()),
+                letter.error,
+            ])
+            .context("Failed to write SQLite dead letter")?;
+        }
+    }
+    tx.commit().context("Failed to commit SQLite transaction")?;
+    Ok(())
+}
+
+/// Quotes a SQLite identifier, doubling embedded quotes.
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[async_trait]
+impl Sink for SqliteSink {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            supports_upsert: true,
+            supports_delete: true,
+            supports_schema_evolution: true,
+            supports_transactions: true,
+            loading_model: LoadingModel::Streaming,
+            min_batch_size: Some(1),
+            max_batch_size: Some(50_000),
+            optimal_flush_interval_ms: 1000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || {
+            state
+                .lock()
+                .conn
+                .query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+                .context("SQLite database is not readable")
+        })
+        .await
+        .context("SQLite validation task failed")??;
+        Ok(())
+    }
+
+    async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult> {
+        if records.is_empty() {
+            return Ok(SinkResult {
+                records_written: 0,
+                bytes_written: 0,
+                last_position: None,
+                ..Default::default()
+            });
+        }
+
+        // Track last position from records
+        let last_position = records.iter().rev().find_map(|r| match r {
+            CdcRecord::Insert { position, .. }
+            | CdcRecord::Update { position, .. }
+            | CdcRecord::Delete { position, .. }
+            | CdcRecord::Commit { position, .. }
+            | CdcRecord::Heartbeat { position, .. } => Some(position.clone()),
+            _ => None,
+        });
+
+        // rusqlite is synchronous: run the whole transaction off the runtime
+        let state = Arc::clone(&self.state);
+        let written = tokio::task::spawn_blocking(move || {
+            let written = state.lock().apply_batch(&records);
+            pool::recycle(records);
+            written
+        })
+        .await
+        .context("SQLite writer task failed")??;
+
+        Ok(SinkResult {
+            records_written: written,
+            bytes_written: 0,
+            last_position,
+            ..Default::default()
+        })
+    }
+
+    async fn begin_batch(&mut self) -> Result<()> {
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || {
+            let mut state = state.lock();
+            if !state.in_batch {
+                // Take the write lock up front rather than on the first row
+                state.control("BEGIN IMMEDIATE")?;
+                state.in_batch = true;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await
+        .context("SQLite writer task failed")?
+    }
+
+    async fn commit_batch(&mut self, checkpoint: &SourcePosition) -> Result<()> {
+        let checkpoint = match (&self.checkpoint_slot, checkpoint) {
+            (Some(slot), SourcePosition::Lsn(lsn)) => Some((slot.clone(), *lsn)),
+            _ => None,
+        };
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || {
+            let mut state = state.lock();
+            if state.in_batch {
+                if let Some((slot, lsn)) = checkpoint {
+                    write_checkpoint(&state.conn, &slot, lsn)?;
+                }
+                state.control("COMMIT")?;
+                state.in_batch = false;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await
+        .context("SQLite writer task failed")?
+    }
+
+    async fn abort_batch(&mut self) -> Result<()> {
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || {
+            let mut state = state.lock();
+            if !state.in_batch {
+                return Ok(());
+            }
+            state.in_batch = false;
+            // Columns added in the batch are rolled back too
+            state.tables.clear();
+            state.control("ROLLBACK")
+        })
+        .await
+        .context("SQLite writer task failed")?
+    }
+
+    /// Batches commit whole with their checkpoint, so none is ever pending.
+    async fn read_checkpoint(&self, slot: &str) -> Result<Option<SinkCheckpoint>> {
+        let state = Arc::clone(&self.state);
+        let slot = slot.to_string();
+        let lsn = tokio::task::spawn_blocking(move || read_checkpoint(&state.lock().conn, &slot))
+            .await
+            .context("SQLite writer task failed")??;
+        Ok(lsn.map(|lsn| SinkCheckpoint {
+            lsn,
+            pending_lsn: None,
+        }))
+    }
+
+    fn invalidate_schema(&mut self, table: Option<&str>) {
+        let mut state = self.state.lock();
+        match table {
+            Some(table) => {
+                let name = state.naming.sink_table(table).table;
+                state.tables.remove(&name);
+            }
+            None => state.tables.clear(),
+        }
+        // Cached statements name the columns they were prepared with
+        state.conn.flush_prepared_statement_cache();
+    }
+
+    async fn schema_diff(&self, schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        let table = schema.qualified_name();
+        let expected: Vec<(String, String)> = schema
+            .added_columns
+            .iter()
+            .map(|c| {
+                (
+                    c.name.clone(),
+                    pg_type_id_to_affinity(c.pg_type_id).to_string(),
+                )
+            })
+            .collect();
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || {
+            let state = state.lock();
+            let name = state.naming.sink_table(&table).table;
+            let actual = column_affinities(&state.conn, &name)?;
+            Ok(Some(SchemaDiff::compare(
+                &table,
+                &name,
+                &expected,
+                actual.as_deref(),
+            )))
+        })
+        .await
+        .context("SQLite writer task failed")?
+    }
+
+    async fn write_dead_letters(&mut self, letters: &[DeadLetter]) -> Result<()> {
+        let letters = letters.to_vec();
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || insert_dead_letters(&mut state.lock().conn, &letters))
+            .await
+            .context("SQLite writer task failed")?
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        // Fold the WAL back into the main file so the copy on disk is self-contained
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || {
+            state
+                .lock()
+                .conn
+                .execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")
+                .context("Failed to checkpoint SQLite WAL")
+        })
+        .await
+        .context("SQLite close task failed")??;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SchemaMode;
+    use crate::core::{ColumnDef, DataType, TableRef, Value};
+
+    fn test_sink() -> SqliteSink {
+        let sink = SqliteSink::open(":memory:", SchemaMode::Strip.into()).unwrap();
+        let sql = create_table_sql(
+            "orders",
+            &[
+                ("id".to_string(), "INTEGER"),
+                ("status".to_string(), "TEXT"),
+                ("notes".to_string(), "TEXT"),
+            ],
+            &["id".to_string()],
+        );
+        sink.state.lock().conn.execute(&sql, []).unwrap();
+        sink
+    }
+
+    fn orders() -> TableRef {
+        TableRef::new(Some("public".into()), "orders".into())
+    }
+
+    fn row(id: i64, status: Value, notes: Value) -> Vec<ColumnValue> {
+        vec![
+            ColumnValue::new("id".into(), Value::Int64(id)),
+            ColumnValue::new("status".into(), status),
+            ColumnValue::new("notes".into(), notes),
+        ]
+    }
+
+    fn query_status(sink: &SqliteSink, id: i64) -> Option<String> {
+        sink.state
+            .lock()
+            .conn
+            .query_row("SELECT status FROM orders WHERE id = ?1", [id], |r| {
+                r.get(0)
+            })
+            .ok()
+    }
+
+    #[test]
+    fn test_sqlite_path() {
+        assert_eq!(sqlite_path("sqlite:///data/cdc.db"), "/data/cdc.db");
+        assert_eq!(sqlite_path("sqlite:cdc.db"), "cdc.db");
+        assert_eq!(sqlite_path("/data/cdc.db"), "/data/cdc.db");
+    }
+
+    #[test]
+    fn test_create_table_sql() {
+        let sql = create_table_sql(
+            "orders",
+            &[("id".to_string(), "INTEGER"), ("name".to_string(), "TEXT")],
+            &["id".to_string()],
+        );
+        assert_eq!(
+            sql,
+            "CREATE TABLE IF NOT EXISTS \"orders\" (\"id\" INTEGER, \"name\" TEXT, PRIMARY KEY (\"id\"))"
+        );
+    }
+
+    #[test]
+    fn test_create_documented_table_sql() {
+        let columns = [("id".to_string(), "INTEGER"), ("name".to_string(), "TEXT")];
+        let keys = ["id".to_string()];
+        assert_eq!(
+            create_documented_table_sql("orders", &columns, &keys, None, &[None, None]),
+            create_table_sql("orders", &columns, &keys)
+        );
+
+        let sql = create_documented_table_sql(
+            "orders",
+            &columns,
+            &keys,
+            Some("Customer orders"),
+            &[None, Some("Display name\nshown on invoices")],
+        );
+        assert_eq!(
+            sql,
+            "CREATE TABLE IF NOT EXISTS \"orders\" ( -- Customer orders\n  \"id\" INTEGER,\n  \"name\" TEXT, -- Display name shown on invoices\n  PRIMARY KEY (\"id\")\n)"
+        );
+
+        // The comments survive in the stored schema
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(&sql, []).unwrap();
+        let stored: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE name = 'orders'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert!(stored.contains("-- Display name shown on invoices"));
+    }
+
+    #[tokio::test]
+    async fn test_insert_update_delete_by_primary_key() {
+        let mut sink = test_sink();
+        let pos = SourcePosition::Lsn(100);
+
+        sink.write_batch(vec![
+            CdcRecord::Insert {
+                table: orders(),
+                columns: row(1, Value::String("new".into()), Value::Null),
+                position: pos.clone(),
+            },
+            CdcRecord::Update {
+                table: orders(),
+                old_columns: None,
+                new_columns: row(1, Value::String("paid".into()), Value::Null),
+                position: pos.clone(),
+            },
+            CdcRecord::Insert {
+                table: orders(),
+                columns: row(2, Value::String("new".into()), Value::Null),
+                position: pos.clone(),
+            },
+        ])
+        .await
+        .unwrap();
+
+        assert_eq!(query_status(&sink, 1).as_deref(), Some("paid"));
+        assert_eq!(query_status(&sink, 2).as_deref(), Some("new"));
+
+        sink.write_batch(vec![CdcRecord::Delete {
+            table: orders(),
+            columns: row(2, Value::String("new".into()), Value::Null),
+            position: pos,
+        }])
+        .await
+        .unwrap();
+
+        assert_eq!(query_status(&sink, 2), None);
+        let count: i64 = sink
+            .state
+            .lock()
+            .conn
+            .query_row("SELECT COUNT(*) FROM orders", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_toast_column_is_preserved() {
+        let mut sink = test_sink();
+        let pos = SourcePosition::Lsn(1);
+
+        sink.write_batch(vec![
+            CdcRecord::Insert {
+                table: orders(),
+                columns: row(1, Value::String("new".into()), Value::String("big".into())),
+                position: pos.clone(),
+            },
+            CdcRecord::Update {
+                table: orders(),
+                old_columns: None,
+                new_columns: row(1, Value::String("paid".into()), Value::Unchanged),
+                position: pos,
+            },
+        ])
+        .await
+        .unwrap();
+
+        let notes: String = sink
+            .state
+            .lock()
+            .conn
+            .query_row("SELECT notes FROM orders WHERE id = 1", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(notes, "big");
+        assert_eq!(query_status(&sink, 1).as_deref(), Some("paid"));
+    }
+
+    #[tokio::test]
+    async fn test_update_changing_primary_key_moves_row() {
+        let mut sink = test_sink();
+        let pos = SourcePosition::Lsn(1);
+
+        // UPDATE orders SET id = 2 WHERE id = 1, with an unchanged TOAST column
+        sink.write_batch(vec![
+            CdcRecord::Insert {
+                table: orders(),
+                columns: row(1, Value::String("new".into()), Value::String("big".into())),
+                position: pos.clone(),
+            },
+            CdcRecord::Update {
+                table: orders(),
+                old_columns: Some(row(
+                    1,
+                    Value::String("new".into()),
+                    Value::String("big".into()),
+                )),
+                new_columns: row(2, Value::String("new".into()), Value::Unchanged),
+                position: pos,
+            },
+        ])
+        .await
+        .unwrap();
+
+        assert_eq!(query_status(&sink, 1), None);
+        assert_eq!(query_status(&sink, 2).as_deref(), Some("new"));
+        let (count, notes): (i64, String) = sink
+            .state
+            .lock()
+            .conn
+            .query_row("SELECT COUNT(*), MAX(notes) FROM orders", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(notes, "big");
+    }
+
+    #[tokio::test]
+    async fn test_schema_change_adds_column() {
+        let mut sink = test_sink();
+        let pos = SourcePosition::Lsn(1);
+
+        sink.write_batch(vec![
+            CdcRecord::SchemaChange {
+                table: orders(),
+                columns: vec![
+                    ColumnDef::new("id".into(), DataType::Int64, false),
+                    ColumnDef::new(
+                        "status".into(),
+                        DataType::String { max_len: Some(16) },
+                        true,
+                    ),
+                    ColumnDef::new("notes".into(), DataType::String { max_len: None }, true),
+                    ColumnDef::new("amount".into(), DataType::Float64, true),
+                ],
+                position: pos.clone(),
+            },
+            CdcRecord::Insert {
+                table: orders(),
+                columns: vec![
+                    ColumnValue::new("id".into(), Value::Int64(7)),
+                    ColumnValue::new("amount".into(), Value::Float64(9.5)),
+                ],
+                position: pos,
+            },
+        ])
+        .await
+        .unwrap();
+
+        let amount: f64 = sink
+            .state
+            .lock()
+            .conn
+            .query_row("SELECT amount FROM orders WHERE id = 7", [], |r| r.get(0))
+            .unwrap();
+        assert!((amount - 9.5).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_internal_tables_are_skipped() {
+        let mut sink = test_sink();
+        let result = sink
+            .write_batch(vec![CdcRecord::Insert {
+                table: TableRef::new(None, "dbmazz_checkpoints".into()),
+                columns: vec![ColumnValue::new("slot".into(), Value::String("s".into()))],
+                position: SourcePosition::Lsn(1),
+            }])
+            .await
+            .unwrap();
+
+        assert_eq!(result.records_written, 0);
+        assert!(
+            !load_table_info(&sink.state.lock().conn, "dbmazz_checkpoints")
+                .unwrap()
+                .exists()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flatten_schema_mode_keeps_same_named_tables_apart() {
+        let mut sink = SqliteSink::open(":memory:", SchemaMode::Flatten.into()).unwrap();
+        let pos = SourcePosition::Lsn(1);
+        sink.write_batch(vec![
+            CdcRecord::Insert {
+                table: TableRef::new(Some("sales".into()), "orders".into()),
+                columns: row(1, Value::String("sales".into()), Value::Null),
+                position: pos.clone(),
+            },
+            CdcRecord::Insert {
+                table: TableRef::new(Some("ops".into()), "orders".into()),
+                columns: row(1, Value::String("ops".into()), Value::Null),
+                position: pos,
+            },
+        ])
+        .await
+        .unwrap();
+
+        let state = sink.state.lock();
+        assert!(load_table_info(&state.conn, "sales__orders")
+            .unwrap()
+            .exists());
+        assert!(load_table_info(&state.conn, "ops__orders")
+            .unwrap()
+            .exists());
+        assert!(!load_table_info(&state.conn, "orders").unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn test_aborted_batch_is_rolled_back() {
+        let mut sink = test_sink();
+        let pos = SourcePosition::Lsn(7);
+        let insert = |id| CdcRecord::Insert {
+            table: orders(),
+            columns: row(id, Value::String("new".into()), Value::Null),
+            position: pos.clone(),
+        };
+
+        sink.begin_batch().await.unwrap();
+        sink.write_batch(vec![insert(1)]).await.unwrap();
+        sink.abort_batch().await.unwrap();
+        assert_eq!(query_status(&sink, 1), None);
+
+        sink.begin_batch().await.unwrap();
+        sink.write_batch(vec![insert(2)]).await.unwrap();
+        sink.commit_batch(&pos).await.unwrap();
+        assert_eq!(query_status(&sink, 2).as_deref(), Some("new"));
+        assert!(sink.state.lock().conn.is_autocommit());
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_committed_with_batch() {
+        let mut sink = test_sink();
+        sink.checkpoint_slot = Some("dbmazz_slot".to_string());
+        let checkpoint_lsn = |sink: &SqliteSink| -> Option<i64> {
+            sink.state
+                .lock()
+                .conn
+                .query_row(
+                    "SELECT lsn FROM _dbmazz_checkpoints WHERE slot_name = 'dbmazz_slot'",
+                    [],
+                    |r| r.get(0),
+                )
+                .ok()
+        };
+        let insert = |id, lsn| CdcRecord::Insert {
+            table: orders(),
+            columns: row(id, Value::String("new".into()), Value::Null),
+            position: SourcePosition::Lsn(lsn),
+        };
+
+        for lsn in [100, 200] {
+            sink.begin_batch().await.unwrap();
+            sink.write_batch(vec![insert(lsn as i64, lsn)])
+                .await
+                .unwrap();
+            sink.commit_batch(&SourcePosition::Lsn(lsn)).await.unwrap();
+        }
+        assert_eq!(checkpoint_lsn(&sink), Some(200));
+
+        // An aborted batch moves neither the rows nor the checkpoint
+        sink.begin_batch().await.unwrap();
+        sink.write_batch(vec![insert(300, 300)]).await.unwrap();
+        sink.abort_batch().await.unwrap();
+        assert_eq!(checkpoint_lsn(&sink), Some(200));
+        assert_eq!(
+            sink.read_checkpoint("dbmazz_slot").await.unwrap(),
+            Some(SinkCheckpoint {
+                lsn: 200,
+                pending_lsn: None
+            })
+        );
+        assert_eq!(sink.read_checkpoint("other_slot").await.unwrap(), None);
+        assert_eq!(query_status(&sink, 300), None);
+    }
+
+    #[tokio::test]
+    async fn test_constraint_violation_is_rejected_and_dead_lettered() {
+        let mut sink = test_sink();
+        sink.state
+            .lock()
+            .conn
+            .execute_batch(
+                "CREATE TABLE items (id INTEGER PRIMARY KEY, qty INTEGER CHECK (qty > 0))",
+            )
+            .unwrap();
+        let items = TableRef::new(Some("public".into()), "items".into());
+        let insert = CdcRecord::Insert {
+            table: items,
+            columns: vec![
+                ColumnValue::new("id".into(), Value::Int64(1)),
+                ColumnValue::new("qty".into(), Value::Int64(-3)),
+            ],
+            position: SourcePosition::Lsn(7),
+        };
+
+        let err = sink.write_batch(vec![insert]).await.unwrap_err();
+        assert!(crate::core::dead_letter::is_rejected(&err));
+        assert!(!crate::core::sink_error::is_retryable(&err));
+
+        let mut row = serde_json::Map::new();
+        row.insert("id".into(), serde_json::json!("1"));
+        row.insert("qty".into(), serde_json::json!("-3"));
+        let letter = DeadLetter {
+            failed_at: chrono::Utc::now(),
+            table: "public.items".into(),
+            op: "insert",
+            lsn: 7,
+            row,
+            old_row: None,
+            error: format!("{:#}", err),
+        };
+        sink.write_dead_letters(&[letter.clone(), letter])
+            .await
+            .unwrap();
+        let (count, stored): (i64, String) = sink
+            .state
+            .lock()
+            .conn
+            .query_row(
+                "SELECT COUNT(*), MAX(row) FROM _dbmazz_dead_letters WHERE table_name = 'public.items'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(stored, r#"{"id":"1","qty":"-3"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_column_is_a_schema_mismatch() {
+        use crate::pipeline::schema_cache::AddedColumn;
+
+        let mut sink = test_sink();
+        let insert = |id| CdcRecord::Insert {
+            table: orders(),
+            columns: row(id, Value::String("new".into()), Value::String("n".into())),
+            position: SourcePosition::Lsn(1),
+        };
+        sink.write_batch(vec![insert(1)]).await.unwrap();
+        sink.state
+            .lock()
+            .conn
+            .execute_batch("ALTER TABLE orders DROP COLUMN notes")
+            .unwrap();
+
+        let err = sink.write_batch(vec![insert(2)]).await.unwrap_err();
+        assert!(crate::core::sink_error::is_schema_mismatch(&err));
+
+        let column = |name: &str, pg_type_id| AddedColumn {
+            name: name.into(),
+            pg_type_id,
+            type_mod: -1,
+        };
+        let schema = SchemaDelta {
+            table_name: "orders".into(),
+            namespace: "public".into(),
+            added_columns: vec![column("id", 20), column("status", 25), column("notes", 25)],
+            ..SchemaDelta::default()
+        };
+        let diff = sink.schema_diff(&schema).await.unwrap().unwrap();
+        assert_eq!(
+            diff.to_string(),
+            "public.orders -> orders: missing column notes (TEXT)"
+        );
+    }
+}
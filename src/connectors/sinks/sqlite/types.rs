@@ -0,0 +1,160 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! SQLite Type Mappings
+//!
+//! SQLite uses dynamic typing with per-column *type affinity* rather than a
+//! fixed type system, so the mapping only has to pick one of five affinities:
+//!
+//! | Affinity | Used for |
+//! |----------|----------|
+//! | INTEGER | booleans (0/1), smallint, integer, bigint |
+//! | REAL | real, double precision |
+//! | NUMERIC | numeric/decimal, money |
+//! | BLOB | bytea |
+//! | TEXT | everything else (text, json, uuid, timestamps, arrays) |
+//!
+//! Timestamps are stored as the ISO-8601 text PostgreSQL emits, which keeps
+//! them compatible with SQLite's built-in date and time functions.
+
+use rusqlite::types::Value as SqlValue;
+
+use crate::core::{DataType, Value};
+
+/// Maps a generic `DataType` to a SQLite column affinity.
+pub(crate) fn data_type_to_affinity(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Boolean | DataType::Int16 | DataType::Int32 | DataType::Int64 => "INTEGER",
+        DataType::Float32 | DataType::Float64 => "REAL",
+        DataType::Decimal { .. } => "NUMERIC",
+        DataType::Bytes => "BLOB",
+        _ => "TEXT",
+    }
+}
+
+/// Maps a PostgreSQL `information_schema.columns.data_type` name to a SQLite
+/// column affinity. Used when pre-creating tables during setup.
+pub(crate) fn pg_type_name_to_affinity(pg_type: &str) -> &'static str {
+    match pg_type {
+        "smallint" | "integer" | "bigint" | "boolean" => "INTEGER",
+        "real" | "double precision" => "REAL",
+        "numeric" | "money" => "NUMERIC",
+        "bytea" => "BLOB",
+        _ => "TEXT",
+    }
+}
+
+/// Maps a PostgreSQL type OID to a SQLite column affinity, as
+/// [`pg_type_name_to_affinity`] does for type names.
+pub(crate) fn pg_type_id_to_affinity(pg_type_id: u32) -> &'static str {
+    match pg_type_id {
+        16 | 20 | 21 | 23 => "INTEGER",
+        700 | 701 => "REAL",
+        790 | 1700 => "NUMERIC",
+        17 => "BLOB",
+        oid => match crate::source::pg_types::base_type(oid) {
+            base if base != oid => pg_type_id_to_affinity(base),
+            _ => "TEXT",
+        },
+    }
+}
+
+/// Affinity SQLite gives a column declared with `decl_type`, by its rules:
+/// `INT` makes it INTEGER, `CHAR`, `CLOB` or `TEXT` TEXT, `BLOB` or no type
+/// BLOB, `REAL`, `FLOA` or `DOUB` REAL, and anything else NUMERIC.
+pub(crate) fn declared_affinity(decl_type: &str) -> &'static str {
+    let decl = decl_type.to_uppercase();
+    if decl.contains("INT") {
+        "INTEGER"
+    } else if ["CHAR", "CLOB", "TEXT"].iter().any(|t| decl.contains(t)) {
+        "TEXT"
+    } else if decl.is_empty() || decl.contains("BLOB") {
+        "BLOB"
+    } else if ["REAL", "FLOA", "DOUB"].iter().any(|t| decl.contains(t)) {
+        "REAL"
+    } else {
+        "NUMERIC"
+    }
+}
+
+/// Infers a column affinity from a value, used when a column shows up in a
+/// row before any schema information for it has been seen.
+pub(crate) fn value_to_affinity(value: &Value) -> &'static str {
+    match value {
+        Value::Bool(_) | Value::Int64(_) | Value::Timestamp(_) => "INTEGER",
+        Value::Float64(_) => "REAL",
+        Value::Decimal(_) => "NUMERIC",
+        Value::Bytes(_) => "BLOB",
+        _ => "TEXT",
+    }
+}
+
+/// Converts a generic `Value` into a SQLite value for parameter binding.
+///
+/// `Value::Unchanged` is never bound by the sink (TOAST columns are left out
+/// of the statement); it maps to NULL only as a defensive fallback.
+pub(crate) fn value_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null | Value::Unchanged => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+        Value::Int64(i) => SqlValue::Integer(*i),
+        Value::Float64(f) => SqlValue::Real(*f),
+        Value::Timestamp(ts) => SqlValue::Integer(*ts),
+        Value::Bytes(b) => SqlValue::Blob(b.clone()),
+        Value::String(s) | Value::Json(s) | Value::Decimal(s) | Value::Uuid(s) => {
+            SqlValue::Text(s.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declared_affinity() {
+        assert_eq!(declared_affinity("BIGINT"), "INTEGER");
+        assert_eq!(declared_affinity("varchar(20)"), "TEXT");
+        assert_eq!(declared_affinity(""), "BLOB");
+        assert_eq!(declared_affinity("DOUBLE PRECISION"), "REAL");
+        assert_eq!(declared_affinity("DECIMAL(10,2)"), "NUMERIC");
+    }
+
+    #[test]
+    fn test_data_type_to_affinity() {
+        assert_eq!(data_type_to_affinity(&DataType::Boolean), "INTEGER");
+        assert_eq!(data_type_to_affinity(&DataType::Int64), "INTEGER");
+        assert_eq!(data_type_to_affinity(&DataType::Float64), "REAL");
+        assert_eq!(
+            data_type_to_affinity(&DataType::Decimal {
+                precision: 10,
+                scale: 2
+            }),
+            "NUMERIC"
+        );
+        assert_eq!(data_type_to_affinity(&DataType::Bytes), "BLOB");
+        assert_eq!(data_type_to_affinity(&DataType::Jsonb), "TEXT");
+        assert_eq!(data_type_to_affinity(&DataType::TimestampTz), "TEXT");
+    }
+
+    #[test]
+    fn test_pg_type_name_to_affinity() {
+        assert_eq!(pg_type_name_to_affinity("bigint"), "INTEGER");
+        assert_eq!(pg_type_name_to_affinity("double precision"), "REAL");
+        assert_eq!(pg_type_name_to_affinity("numeric"), "NUMERIC");
+        assert_eq!(pg_type_name_to_affinity("bytea"), "BLOB");
+        assert_eq!(pg_type_name_to_affinity("character varying"), "TEXT");
+    }
+
+    #[test]
+    fn test_value_to_sql() {
+        assert_eq!(value_to_sql(&Value::Null), SqlValue::Null);
+        assert_eq!(value_to_sql(&Value::Bool(true)), SqlValue::Integer(1));
+        assert_eq!(value_to_sql(&Value::Int64(-7)), SqlValue::Integer(-7));
+        assert_eq!(value_to_sql(&Value::Float64(1.5)), SqlValue::Real(1.5));
+        assert_eq!(
+            value_to_sql(&Value::Decimal("12.50".to_string())),
+            SqlValue::Text("12.50".to_string())
+        );
+    }
+}
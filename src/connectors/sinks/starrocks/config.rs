@@ -8,8 +8,10 @@
 //! `SinkConfig` and legacy environment variables.
 
 use anyhow::{anyhow, Result};
+use std::time::Duration;
 
-use crate::config::SinkConfig;
+use super::stream_load::{LoadLimiter, StreamLoadClient};
+use crate::config::{HttpClientConfig, SharedConnections, SinkAuth, SinkConfig};
 
 /// Default HTTP port for StarRocks Stream Load API
 const DEFAULT_HTTP_PORT: u16 = 8040;
@@ -38,13 +40,27 @@ pub struct StarRocksSinkConfig {
     /// Password for authentication
     pub password: String,
 
-    /// Connection timeout in seconds (default: 30)
-    #[allow(dead_code)]
+    /// Limit on each Stream Load request in seconds (default: 30)
     pub timeout_secs: u64,
 
     /// Maximum filter ratio for Stream Load (default: 0.2)
     #[allow(dead_code)]
     pub max_filter_ratio: f64,
+
+    /// Stream Loads in flight at once across all tables (default: unlimited)
+    pub max_concurrent_loads: Option<usize>,
+
+    /// Stream Loads in flight at once into one table (default: unlimited)
+    pub max_concurrent_loads_per_table: Option<usize>,
+
+    /// Destination whose MySQL pool is shared with other sinks (default: none)
+    pub shared: Option<SharedConnections>,
+
+    /// Proxy and TLS trust of the Stream Load requests
+    pub http: HttpClientConfig,
+
+    /// Scheme and extra headers of the Stream Load requests
+    pub auth: SinkAuth,
 }
 
 impl std::fmt::Debug for StarRocksSinkConfig {
@@ -57,6 +73,14 @@ impl std::fmt::Debug for StarRocksSinkConfig {
             .field("password", &"[REDACTED]")
             .field("timeout_secs", &self.timeout_secs)
             .field("max_filter_ratio", &self.max_filter_ratio)
+            .field("max_concurrent_loads", &self.max_concurrent_loads)
+            .field(
+                "max_concurrent_loads_per_table",
+                &self.max_concurrent_loads_per_table,
+            )
+            .field("shared", &self.shared)
+            .field("http", &self.http)
+            .field("auth", &self.auth)
             .finish()
     }
 }
@@ -79,6 +103,7 @@ impl StarRocksSinkConfig {
     pub fn from_sink_config(config: &SinkConfig) -> Result<Self> {
         // Parse HTTP URL - ensure it has the correct port
         let http_url = Self::normalize_http_url(&config.url)?;
+        let limits = config.starrocks.clone().unwrap_or_default();
 
         Ok(Self {
             http_url,
@@ -86,11 +111,33 @@ impl StarRocksSinkConfig {
             database: config.database.clone(),
             user: config.user.clone(),
             password: config.password.clone(),
-            timeout_secs: 30,
+            timeout_secs: config.request_timeout.as_secs(),
             max_filter_ratio: 0.2,
+            max_concurrent_loads: limits.max_concurrent_loads,
+            max_concurrent_loads_per_table: limits.max_concurrent_loads_per_table,
+            shared: config.shared.clone(),
+            http: config.http.clone(),
+            auth: config.auth.clone(),
         })
     }
 
+    /// Stream Load client for this sink, sharing the process-wide load limits.
+    pub fn stream_load_client(&self) -> StreamLoadClient {
+        StreamLoadClient::new(
+            self.http_url.clone(),
+            self.database.clone(),
+            self.user.clone(),
+            self.password.clone(),
+        )
+        .with_limiter(LoadLimiter::shared(
+            self.max_concurrent_loads,
+            self.max_concurrent_loads_per_table,
+        ))
+        .with_timeout(Duration::from_secs(self.timeout_secs))
+        .with_http(self.http.clone())
+        .with_auth(self.auth.clone())
+    }
+
     /// Normalizes the HTTP URL for Stream Load.
     ///
     /// - Adds `http://` prefix if missing
@@ -179,6 +226,11 @@ impl Default for StarRocksSinkConfig {
             password: String::new(),
             timeout_secs: 30,
             max_filter_ratio: 0.2,
+            max_concurrent_loads: None,
+            max_concurrent_loads_per_table: None,
+            shared: None,
+            http: HttpClientConfig::default(),
+            auth: SinkAuth::default(),
         }
     }
 }
@@ -187,7 +239,7 @@ impl Default for StarRocksSinkConfig {
 mod tests {
     use super::*;
     use crate::config::StarRocksSinkConfig as ConfigStarRocksSinkConfig;
-    use crate::config::{SinkConfig, SinkType};
+    use crate::config::{SinkConfig, SinkType, DEFAULT_SINK_REQUEST_TIMEOUT};
 
     #[test]
     fn test_from_sink_config() {
@@ -198,7 +250,23 @@ mod tests {
             database: "cdc_db".to_string(),
             user: "admin".to_string(),
             password: "secret".to_string(),
-            starrocks: Some(ConfigStarRocksSinkConfig {}),
+            starrocks: Some(ConfigStarRocksSinkConfig::default()),
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
         };
 
         let sr_config = StarRocksSinkConfig::from_sink_config(&config).unwrap();
@@ -208,6 +276,18 @@ mod tests {
         assert_eq!(sr_config.database, "cdc_db");
         assert_eq!(sr_config.user, "admin");
         assert_eq!(sr_config.password, "secret");
+        assert_eq!(sr_config.max_concurrent_loads, None);
+
+        let config = SinkConfig {
+            starrocks: Some(ConfigStarRocksSinkConfig {
+                max_concurrent_loads: Some(4),
+                max_concurrent_loads_per_table: Some(1),
+            }),
+            ..config
+        };
+        let sr_config = StarRocksSinkConfig::from_sink_config(&config).unwrap();
+        assert_eq!(sr_config.max_concurrent_loads, Some(4));
+        assert_eq!(sr_config.max_concurrent_loads_per_table, Some(1));
     }
 
     #[test]
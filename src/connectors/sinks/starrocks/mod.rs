@@ -14,6 +14,23 @@
 //! - **Partial updates**: TOAST column optimization for PostgreSQL large values
 //! - **Schema evolution**: Automatic column addition when source schema changes
 //! - **Soft deletes**: CDC audit columns track operation type and deletion status
+//! - **Checkpoint table**: with `SINK_CHECKPOINT_TABLE=true` the LSN of each
+//!   batch is upserted into `_dbmazz_checkpoints` once all its Stream Loads
+//!   succeeded. Stream Load is per table, so this is not atomic with the data,
+//!   but the stored LSN never runs ahead of it
+//! - **Exactly-once**: with `EXACTLY_ONCE=true` each load is labelled after
+//!   its batch and table, and the batch's end LSN is recorded as pending
+//!   before its first load. A restart replays that same batch; the loads
+//!   that went through are refused as known labels and the others are
+//!   loaded, so no row of the batch is written twice
+//! - **Rejected rows**: loads failing on data quality are reported as
+//!   rejected data, and with `DEAD_LETTER_TARGET=table` the rows are inserted
+//!   into `_dbmazz_dead_letters`
+//! - **Batch results**: rows loaded and filtered (within `max_filter_ratio`)
+//!   and the load labels are reported per batch, with warnings for filtered
+//!   rows, publish timeouts and labels loaded before
+//! - **Text overflow**: values longer than their `VARCHAR` column are
+//!   rejected, truncated or make the column wider (`SINK_STRING_OVERFLOW`)
 //!
 //! ## Architecture
 //!
@@ -52,30 +69,56 @@
 //! ```
 
 mod config;
+mod overflow;
 mod setup;
 pub mod stream_load;
 pub(crate) mod types;
 
-use anyhow::{anyhow, Result};
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use chrono::Utc;
-use std::collections::HashMap;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::info;
 
-use crate::config::SinkConfig;
+use super::is_internal_table;
+use crate::config::{SinkConfig, SinkTableName, StringOverflow, TableNaming};
+use crate::core::dead_letter::DeadLetter;
+use crate::core::pool;
+use crate::core::schema_diff::SchemaDiff;
+use crate::core::sink_error::is_retryable;
 use crate::core::{
-    CdcRecord, ColumnValue, LoadingModel, Sink, SinkCapabilities, SinkResult, SourcePosition,
+    BatchId, CdcRecord, ColumnValue, LoadingModel, Sink, SinkCapabilities, SinkCheckpoint,
+    SinkResult, SourcePosition, TableRef,
 };
+use crate::pipeline::schema_cache::SchemaDelta;
+use crate::utils::to_json_vec;
 
 pub use self::config::StarRocksSinkConfig;
-use self::stream_load::{StreamLoadClient, StreamLoadOptions};
+use self::overflow::ColumnLimits;
+use self::setup::StarRocksSetup;
+use self::stream_load::{StreamLoadClient, StreamLoadOptions, StreamLoadResult};
 use self::types::TypeMapper;
 
 /// Batch of JSON rows with optional partial column list per table.
 type TableBatchMap = HashMap<String, (Vec<serde_json::Value>, Option<Vec<String>>)>;
 
+/// Text column lengths of the sink tables a batch writes to
+type TableLimits = HashMap<SinkTableName, Arc<ColumnLimits>>;
+
+/// Stream Load body of one table, and what has to happen before it's sent.
+#[derive(Debug)]
+struct EncodedBatch {
+    table: SinkTableName,
+    body: Arc<Vec<u8>>,
+    /// Partial update column list
+    partial_columns: Option<Vec<String>>,
+    /// Columns to widen first, with their new length in bytes
+    widen: HashMap<String, u32>,
+}
+
 /// CDC audit columns added to all tables
 const AUDIT_COLUMNS: &[&str] = &[
     "dbmazz_op_type",
@@ -84,13 +127,6 @@ const AUDIT_COLUMNS: &[&str] = &[
     "dbmazz_cdc_version",
 ];
 
-/// Tables internal to dbmazz that should not be replicated
-fn is_internal_table(table_name: &str) -> bool {
-    table_name.starts_with("dbmazz_")
-        || table_name.starts_with("_dbmazz_")
-        || table_name == "dbmazz_checkpoints"
-}
-
 /// StarRocks sink connector implementing the Sink trait.
 ///
 /// This sink writes CDC records to StarRocks using the Stream Load HTTP API.
@@ -102,8 +138,20 @@ pub struct StarRocksSink {
     config: StarRocksSinkConfig,
     /// HTTP Stream Load client
     stream_load: StreamLoadClient,
-    /// Type mapper for converting CDC types to StarRocks types
-    type_mapper: TypeMapper,
+    /// Row encoding, run on the blocking pool
+    encoder: RowEncoder,
+    /// How source tables map to StarRocks databases and tables
+    naming: TableNaming,
+    /// DDL over the MySQL protocol, for schema deltas and checkpoints
+    setup: StarRocksSetup,
+    /// Slot whose LSN is written to `_dbmazz_checkpoints` after each batch
+    checkpoint_slot: Option<String>,
+    /// ID of the batch being written (`EXACTLY_ONCE=true`); its loads are
+    /// labelled after it
+    batch_id: Option<BatchId>,
+    /// Text column lengths per table, read on the table's first write and
+    /// forgotten when its columns change
+    string_limits: Mutex<TableLimits>,
 }
 
 impl StarRocksSink {
@@ -122,25 +170,166 @@ impl StarRocksSink {
     /// Returns an error if the configuration is invalid
     pub fn new(config: &SinkConfig) -> Result<Self> {
         let sr_config = StarRocksSinkConfig::from_sink_config(config)?;
-        let stream_load = StreamLoadClient::new(
-            sr_config.http_url.clone(),
-            sr_config.database.clone(),
-            sr_config.user.clone(),
-            sr_config.password.clone(),
-        );
+        let stream_load = sr_config.stream_load_client();
 
         info!("StarRocksSink initialized:");
         info!("  HTTP URL: {}", sr_config.http_url);
         info!("  Database: {}", sr_config.database);
         info!("  MySQL Port: {}", sr_config.mysql_port);
 
+        let type_mapper = TypeMapper::new().with_bool_encoding(config.bool_encoding);
+        let setup = StarRocksSetup::new(sr_config.clone())?
+            .with_type_mapper(type_mapper.clone())
+            .with_schema_evolution(config.schema_evolution);
+
         Ok(Self {
             config: sr_config,
             stream_load,
-            type_mapper: TypeMapper::new(),
+            encoder: RowEncoder {
+                type_mapper,
+                string_overflow: config.string_overflow.clone(),
+            },
+            naming: config.naming.clone(),
+            setup,
+            checkpoint_slot: config.checkpoint_slot.clone(),
+            batch_id: None,
+            string_limits: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Text column lengths of the tables `records` write to.
+    async fn limits_for(&self, records: &[CdcRecord]) -> Result<TableLimits> {
+        let tables: HashSet<&TableRef> = records
+            .iter()
+            .filter_map(|record| match record {
+                CdcRecord::Insert { table, .. }
+                | CdcRecord::Update { table, .. }
+                | CdcRecord::Delete { table, .. } => Some(table),
+                _ => None,
+            })
+            .filter(|table| !is_internal_table(&table.name))
+            .collect();
+
+        let mut limits = HashMap::with_capacity(tables.len());
+        for table in tables {
+            let dest = self.naming.sink_table(&table.qualified_name());
+            let cached = self.string_limits.lock().get(&dest).cloned();
+            let table_limits = match cached {
+                Some(table_limits) => table_limits,
+                None => {
+                    let table_limits = Arc::new(self.setup.string_limits(&dest).await?);
+                    self.string_limits
+                        .lock()
+                        .insert(dest.clone(), table_limits.clone());
+                    table_limits
+                }
+            };
+            limits.insert(dest, table_limits);
+        }
+        Ok(limits)
+    }
+
+    /// Sends a batch with exponential backoff retry.
+    async fn send_with_retry(
+        &self,
+        table: &SinkTableName,
+        body: Arc<Vec<u8>>,
+        partial_columns: Option<Vec<String>>,
+        max_retries: u32,
+    ) -> Result<StreamLoadResult> {
+        let mut attempt = 0;
+        // Retries keep the label, so a load that went through before its
+        // response was lost isn't repeated
+        let label = self.batch_id.as_ref().map(|id| id.label(&table.table));
+
+        loop {
+            let options = StreamLoadOptions {
+                partial_columns: partial_columns.clone(),
+                max_filter_ratio: Some(0.2),
+                database: table.database.clone(),
+                label: label.clone(),
+            };
+
+            match self
+                .stream_load
+                .send(&table.table, body.clone(), options)
+                .await
+            {
+                Ok(result) => return Ok(result),
+                // Rejected rows and bad credentials would fail again
+                Err(e) if !is_retryable(&e) => return Err(e),
+                Err(e) => {
+                    attempt += 1;
+                    if attempt >= max_retries {
+                        return Err(e.context(format!("Failed after {} attempts", max_retries)));
+                    }
+
+                    info!(
+                        "Retry {}/{} for {}: {}",
+                        attempt,
+                        max_retries,
+                        table.label(),
+                        e
+                    );
+
+                    // Exponential backoff: 100ms, 200ms, 400ms...
+                    tokio::time::sleep(Duration::from_millis(100 * 2_u64.pow(attempt))).await;
+                }
+            }
+        }
+    }
+}
+
+/// Turns CDC records into Stream Load JSON bodies.
+///
+/// Encoding a large batch is CPU-bound, so `write_batch` runs it on the
+/// blocking pool instead of the pipeline task. The pipeline awaits each flush,
+/// so at most one batch per sink is being encoded at a time.
+#[derive(Debug, Clone)]
+struct RowEncoder {
+    /// Type mapper for converting CDC types to StarRocks types
+    type_mapper: TypeMapper,
+    /// What happens to text longer than its column
+    string_overflow: StringOverflow,
+}
+
+impl RowEncoder {
+    /// Encodes records into one JSON array body per sink table, fitting
+    /// text values to the column lengths in `limits`.
+    fn encode(
+        &self,
+        records: &[CdcRecord],
+        synced_at: &str,
+        naming: &TableNaming,
+        limits: &TableLimits,
+    ) -> Result<Vec<EncodedBatch>> {
+        let batches = self.records_to_json_batches(records, synced_at)?;
+        let mut encoded = Vec::with_capacity(batches.len());
+        for (table, (mut rows, partial_columns)) in batches {
+            if rows.is_empty() {
+                continue;
+            }
+            // Name the sink table per SINK_SCHEMA_MODE and TABLE_RENAMES
+            let sink_table = naming.sink_table(&table);
+            let widen = match limits.get(&sink_table) {
+                Some(columns) => overflow::fit_rows(
+                    &mut rows,
+                    &sink_table.label(),
+                    columns,
+                    &self.string_overflow,
+                )?,
+                None => HashMap::new(),
+            };
+            encoded.push(EncodedBatch {
+                table: sink_table,
+                body: Arc::new(to_json_vec(&rows)?),
+                partial_columns,
+                widen,
+            });
+        }
+        Ok(encoded)
+    }
+
     /// Converts CDC records to JSON rows grouped by table.
     ///
     /// Returns a map of table name to (rows, optional partial columns).
@@ -241,7 +430,7 @@ impl StarRocksSink {
         let mut obj = serde_json::Map::new();
         for col in columns {
             let json_value = self.type_mapper.value_to_json(&col.value);
-            obj.insert(col.name.clone(), json_value);
+            obj.insert(col.name.to_string(), json_value);
         }
         Ok(serde_json::Value::Object(obj))
     }
@@ -261,8 +450,8 @@ impl StarRocksSink {
                 continue;
             }
             let json_value = self.type_mapper.value_to_json(&col.value);
-            obj.insert(col.name.clone(), json_value);
-            included.push(col.name.clone());
+            obj.insert(col.name.to_string(), json_value);
+            included.push(col.name.to_string());
         }
 
         Ok((serde_json::Value::Object(obj), included))
@@ -293,39 +482,6 @@ impl StarRocksSink {
             obj.insert("dbmazz_cdc_version".to_string(), serde_json::json!(version));
         }
     }
-
-    /// Sends a batch with exponential backoff retry.
-    async fn send_with_retry(
-        &self,
-        table: &str,
-        body: Arc<Vec<u8>>,
-        partial_columns: Option<Vec<String>>,
-        max_retries: u32,
-    ) -> Result<u64> {
-        let mut attempt = 0;
-
-        loop {
-            let options = StreamLoadOptions {
-                partial_columns: partial_columns.clone(),
-                max_filter_ratio: Some(0.2),
-            };
-
-            match self.stream_load.send(table, body.clone(), options).await {
-                Ok(result) => return Ok(result.loaded_rows),
-                Err(e) => {
-                    attempt += 1;
-                    if attempt >= max_retries {
-                        return Err(anyhow!("Failed after {} attempts: {}", max_retries, e));
-                    }
-
-                    info!("Retry {}/{} for {}: {}", attempt, max_retries, table, e);
-
-                    // Exponential backoff: 100ms, 200ms, 400ms...
-                    tokio::time::sleep(Duration::from_millis(100 * 2_u64.pow(attempt))).await;
-                }
-            }
-        }
-    }
 }
 
 #[async_trait]
@@ -344,6 +500,7 @@ impl Sink for StarRocksSink {
             min_batch_size: Some(1),
             max_batch_size: Some(100_000),
             optimal_flush_interval_ms: 5000,
+            micro_batch: false,
         }
     }
 
@@ -357,6 +514,7 @@ impl Sink for StarRocksSink {
                 records_written: 0,
                 bytes_written: 0,
                 last_position: None,
+                ..Default::default()
             });
         }
 
@@ -373,39 +531,121 @@ impl Sink for StarRocksSink {
             _ => None,
         });
 
-        // Convert records to JSON batches grouped by table
-        let batches = self.records_to_json_batches(&records, &synced_at)?;
+        let limits = self.limits_for(&records).await?;
+
+        // Encode off the async workers; a 50k-row batch takes long enough to
+        // delay WAL reading and keepalive replies
+        let encoder = self.encoder.clone();
+        let naming = self.naming.clone();
+        let batches = tokio::task::spawn_blocking(move || {
+            let batches = encoder.encode(&records, &synced_at, &naming, &limits);
+            pool::recycle(records);
+            batches
+        })
+        .await
+        .context("StarRocks encoder task failed")??;
+
+        // A restart replays exactly this batch, under the same labels
+        if let (Some(slot), Some(id)) = (&self.checkpoint_slot, &self.batch_id) {
+            self.setup
+                .write_checkpoint_intent(slot, id.first_lsn, id.last_lsn)
+                .await?;
+        }
 
-        let mut total_written = 0u64;
-        let mut total_bytes = 0u64;
+        let mut written = SinkResult {
+            last_position,
+            ..Default::default()
+        };
 
         // Send each table batch
-        for (table, (rows, partial_cols)) in batches {
-            if rows.is_empty() {
-                continue;
+        for batch in batches {
+            for (column, len) in &batch.widen {
+                self.setup.widen_column(&batch.table, column, *len).await?;
+            }
+            if !batch.widen.is_empty() {
+                self.string_limits.lock().remove(&batch.table);
             }
 
-            // Serialize to JSON array
-            let body = serde_json::to_vec(&rows)?;
-            let body_len = body.len() as u64;
-            let body = Arc::new(body);
+            let body_len = batch.body.len() as u64;
+            let loaded = self
+                .send_with_retry(&batch.table, batch.body, batch.partial_columns, 3)
+                .await?;
 
-            // Extract table name (remove schema prefix if present)
-            let table_name = table.split('.').next_back().unwrap_or(&table);
+            written.records_written += loaded.loaded_rows as usize;
+            written.rows_filtered += loaded.filtered_rows as usize;
+            written.bytes_written += body_len;
+            written
+                .warnings
+                .extend(loaded.warnings(&batch.table.label()));
+            written.labels.extend(loaded.label);
+        }
 
-            let written = self
-                .send_with_retry(table_name, body, partial_cols, 3)
-                .await?;
+        Ok(written)
+    }
 
-            total_written += written;
-            total_bytes += body_len;
+    async fn commit_batch(&mut self, checkpoint: &SourcePosition) -> Result<()> {
+        match (&self.checkpoint_slot, checkpoint) {
+            (Some(slot), SourcePosition::Lsn(lsn)) => self.setup.write_checkpoint(slot, *lsn).await,
+            _ => Ok(()),
         }
+    }
 
-        Ok(SinkResult {
-            records_written: total_written as usize,
-            bytes_written: total_bytes,
-            last_position,
-        })
+    fn identify_batch(&mut self, id: &BatchId) {
+        self.batch_id = Some(id.clone());
+    }
+
+    async fn read_checkpoint(&self, slot: &str) -> Result<Option<SinkCheckpoint>> {
+        self.setup.read_checkpoint(slot).await
+    }
+
+    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+        if is_internal_table(&delta.table_name) {
+            return Ok(());
+        }
+        let dest = self.naming.sink_table(&delta.qualified_name());
+        let applied = self.setup.apply_schema_delta(&dest, delta).await;
+        // Added and changed columns have lengths of their own
+        self.string_limits.lock().remove(&dest);
+        applied
+    }
+
+    fn invalidate_schema(&mut self, table: Option<&str>) {
+        // Deltas read the table's columns every time; the column lengths
+        // and internal tables are remembered
+        match table {
+            Some(table) => {
+                let dest = self.naming.sink_table(table);
+                self.string_limits.lock().remove(&dest);
+            }
+            None => {
+                self.string_limits.lock().clear();
+                self.setup.forget_internal_tables();
+            }
+        }
+    }
+
+    async fn schema_diff(&self, schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        let source = schema.qualified_name();
+        let dest = self.naming.sink_table(&source);
+        let actual = self.setup.column_types(&dest).await?;
+        let expected: Vec<(String, String)> = schema
+            .added_columns
+            .iter()
+            .map(|c| {
+                let column_type = self.encoder.type_mapper.pg_type_to_starrocks(c.pg_type_id);
+                (c.name.clone(), column_type.to_string())
+            })
+            .collect();
+        Ok(Some(SchemaDiff::compare(
+            &source,
+            &dest.label(),
+            &expected,
+            (!actual.is_empty()).then_some(actual.as_slice()),
+        )))
+    }
+
+    async fn write_dead_letters(&mut self, letters: &[DeadLetter]) -> Result<()> {
+        self.setup.write_dead_letters(letters).await
     }
 
     async fn close(&mut self) -> Result<()> {
@@ -418,7 +658,7 @@ impl Sink for StarRocksSink {
 mod tests {
     use super::*;
     use crate::config::StarRocksSinkConfig as ConfigStarRocksSinkConfig;
-    use crate::config::{SinkConfig, SinkType};
+    use crate::config::{SchemaMode, SinkConfig, SinkType, DEFAULT_SINK_REQUEST_TIMEOUT};
 
     fn test_config() -> SinkConfig {
         SinkConfig {
@@ -428,7 +668,23 @@ mod tests {
             database: "test_db".to_string(),
             user: "root".to_string(),
             password: "".to_string(),
-            starrocks: Some(ConfigStarRocksSinkConfig {}),
+            starrocks: Some(ConfigStarRocksSinkConfig::default()),
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
         }
     }
 
@@ -452,6 +708,88 @@ mod tests {
         assert!(matches!(caps.loading_model, LoadingModel::Streaming));
     }
 
+    #[test]
+    fn test_encode_groups_rows_by_sink_table() {
+        use crate::core::{TableRef, Value};
+
+        let encoder = RowEncoder {
+            type_mapper: TypeMapper::new(),
+            string_overflow: StringOverflow::Error,
+        };
+        let insert = |schema: &str, id: i64| CdcRecord::Insert {
+            table: TableRef::new(Some(schema.into()), "orders".into()),
+            columns: vec![ColumnValue::new("id".into(), Value::Int64(id))],
+            position: SourcePosition::Lsn(0x10),
+        };
+        let records = vec![insert("public", 1), insert("public", 2), insert("sales", 3)];
+
+        let mut batches = encoder
+            .encode(
+                &records,
+                "2025-01-01 00:00:00",
+                &SchemaMode::Flatten.into(),
+                &HashMap::new(),
+            )
+            .unwrap();
+        batches.sort_by(|a, b| a.table.table.cmp(&b.table.table));
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].table.table, "public__orders");
+        assert_eq!(batches[1].table.table, "sales__orders");
+
+        let rows: Vec<serde_json::Value> = serde_json::from_slice(&batches[0].body).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["dbmazz_cdc_version"], 0x10);
+        assert!(batches[0].partial_columns.is_none());
+    }
+
+    #[test]
+    fn test_encode_fits_text_to_column_lengths() {
+        use crate::core::dead_letter::RejectedData;
+        use crate::core::{TableRef, Value};
+
+        let records = vec![CdcRecord::Insert {
+            table: TableRef::new(Some("public".into()), "orders".into()),
+            columns: vec![
+                ColumnValue::new("id".into(), Value::Int64(1)),
+                ColumnValue::new("status".into(), Value::String("backordered".into())),
+            ],
+            position: SourcePosition::Lsn(0x10),
+        }];
+        let limits: TableLimits = HashMap::from([(
+            SchemaMode::Strip.sink_table("public.orders"),
+            Arc::new(HashMap::from([("status".to_string(), 8)])),
+        )]);
+        let encode = |string_overflow: StringOverflow| {
+            let encoder = RowEncoder {
+                type_mapper: TypeMapper::new(),
+                string_overflow,
+            };
+            encoder.encode(
+                &records,
+                "2025-01-01 00:00:00",
+                &SchemaMode::Strip.into(),
+                &limits,
+            )
+        };
+
+        // One oversized value rejects the rows, for the dead-letter queue
+        let err = encode(StringOverflow::Error).unwrap_err();
+        assert!(err.is::<RejectedData>());
+
+        let batches = encode(StringOverflow::Truncate {
+            marker: "~".to_string(),
+        })
+        .unwrap();
+        let rows: Vec<serde_json::Value> = serde_json::from_slice(&batches[0].body).unwrap();
+        assert_eq!(rows[0]["status"], "backord~");
+
+        let batches = encode(StringOverflow::Widen).unwrap();
+        assert_eq!(
+            batches[0].widen,
+            HashMap::from([("status".to_string(), 16)])
+        );
+    }
+
     #[test]
     fn test_is_internal_table() {
         assert!(is_internal_table("dbmazz_checkpoints"));
@@ -0,0 +1,198 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Text values longer than their StarRocks column (`SINK_STRING_OVERFLOW`).
+//!
+//! StarRocks measures `VARCHAR(n)` in bytes, and Stream Load fails the whole
+//! load when one value is too long. Before a batch is sent, every text value
+//! is checked against its column's length, read from
+//! `information_schema.columns`. Depending on the policy, an oversized value
+//! rejects its row (so the dead-letter queue can park just that row), is cut
+//! to fit with a marker at the end, or has its column widened first.
+
+use std::collections::HashMap;
+
+use tracing::warn;
+
+use crate::config::StringOverflow;
+use crate::core::dead_letter::RejectedData;
+
+/// Longest `VARCHAR` StarRocks accepts, in bytes
+pub(crate) const MAX_VARCHAR_BYTES: u32 = 1_048_576;
+
+/// Byte length of each `VARCHAR`/`CHAR` column of a table
+pub(crate) type ColumnLimits = HashMap<String, u32>;
+
+/// Length of a `varchar(n)` or `char(n)` column type, `None` for other types.
+pub(crate) fn varchar_len(column_type: &str) -> Option<u32> {
+    let column_type = column_type.to_ascii_lowercase();
+    let args = column_type
+        .strip_prefix("varchar(")
+        .or_else(|| column_type.strip_prefix("char("))?;
+    args.strip_suffix(')')?.trim().parse().ok()
+}
+
+/// Byte length a column is widened to for a `needed`-byte value: the next
+/// power of two, so a column isn't altered for every slightly longer value.
+/// `None` past the longest `VARCHAR`.
+pub(crate) fn widened_len(needed: usize) -> Option<u32> {
+    let needed = u32::try_from(needed).ok()?;
+    (needed <= MAX_VARCHAR_BYTES).then(|| needed.next_power_of_two().min(MAX_VARCHAR_BYTES))
+}
+
+/// `text` cut to at most `max_bytes`, on a character boundary, ending in
+/// `marker`. A marker longer than the column is left out.
+pub(crate) fn truncate(text: &str, max_bytes: usize, marker: &str) -> String {
+    let marker = if marker.len() < max_bytes { marker } else { "" };
+    let mut end = max_bytes - marker.len();
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut cut = String::with_capacity(end + marker.len());
+    cut.push_str(&text[..end]);
+    cut.push_str(marker);
+    cut
+}
+
+/// Makes the text values of `rows` (JSON objects bound for one table) fit
+/// `limits`. Returns the columns to widen with the length each needs; only
+/// the widen policy has any.
+pub(crate) fn fit_rows(
+    rows: &mut [serde_json::Value],
+    table: &str,
+    limits: &ColumnLimits,
+    policy: &StringOverflow,
+) -> Result<HashMap<String, u32>, RejectedData> {
+    let mut widen: HashMap<String, u32> = HashMap::new();
+    if limits.is_empty() {
+        return Ok(widen);
+    }
+    let mut truncated = 0usize;
+
+    for row in rows.iter_mut() {
+        let serde_json::Value::Object(obj) = row else {
+            continue;
+        };
+        for (column, value) in obj.iter_mut() {
+            let serde_json::Value::String(text) = value else {
+                continue;
+            };
+            let Some(&limit) = limits.get(column) else {
+                continue;
+            };
+            if text.len() <= limit as usize {
+                continue;
+            }
+            match policy {
+                StringOverflow::Error => {
+                    return Err(RejectedData::new(
+                        "starrocks",
+                        format!(
+                            "value of {}.{} is {} bytes, the column holds {} \
+                             (SINK_STRING_OVERFLOW=truncate or widen accepts it)",
+                            table,
+                            column,
+                            text.len(),
+                            limit
+                        ),
+                    ));
+                }
+                StringOverflow::Truncate { marker } => {
+                    *text = truncate(text, limit as usize, marker);
+                    truncated += 1;
+                }
+                StringOverflow::Widen => {
+                    let Some(len) = widened_len(text.len()) else {
+                        return Err(RejectedData::new(
+                            "starrocks",
+                            format!(
+                                "value of {}.{} is {} bytes, longer than any VARCHAR ({})",
+                                table,
+                                column,
+                                text.len(),
+                                MAX_VARCHAR_BYTES
+                            ),
+                        ));
+                    };
+                    let entry = widen.entry(column.clone()).or_default();
+                    *entry = (*entry).max(len);
+                }
+            }
+        }
+    }
+
+    if truncated > 0 {
+        warn!(
+            "[SINK] Truncated {} text value(s) in {} to fit their columns",
+            truncated, table
+        );
+    }
+    Ok(widen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_varchar_len() {
+        assert_eq!(varchar_len("varchar(65533)"), Some(65533));
+        assert_eq!(varchar_len("CHAR(10)"), Some(10));
+        assert_eq!(varchar_len("varbinary(100)"), None);
+        assert_eq!(varchar_len("bigint(20)"), None);
+        assert_eq!(varchar_len("varchar"), None);
+    }
+
+    #[test]
+    fn test_widened_len() {
+        assert_eq!(widened_len(300), Some(512));
+        assert_eq!(widened_len(512), Some(512));
+        assert_eq!(widened_len(70_000), Some(131_072));
+        assert_eq!(widened_len(1_000_000), Some(MAX_VARCHAR_BYTES));
+        assert_eq!(widened_len(MAX_VARCHAR_BYTES as usize + 1), None);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abcdefghij", 8, "..."), "abcde...");
+        // Never splits a character: "é" is 2 bytes
+        assert_eq!(truncate("ééééé", 6, "…"), "é…");
+        assert_eq!(truncate("ééééé", 5, ""), "éé");
+        // A marker that doesn't fit is left out
+        assert_eq!(truncate("abcdef", 3, "[truncated]"), "abc");
+    }
+
+    fn rows() -> Vec<serde_json::Value> {
+        vec![
+            json!({"id": 1, "name": "short", "note": "x".repeat(20)}),
+            json!({"id": 2, "name": "a much longer name", "note": null}),
+        ]
+    }
+
+    fn limits() -> ColumnLimits {
+        HashMap::from([("name".to_string(), 10), ("note".to_string(), 65533)])
+    }
+
+    #[test]
+    fn test_fit_rows() {
+        let mut batch = rows();
+        let err = fit_rows(&mut batch, "orders", &limits(), &StringOverflow::Error).unwrap_err();
+        assert!(err
+            .reason
+            .contains("orders.name is 18 bytes, the column holds 10"));
+
+        let truncate = StringOverflow::Truncate {
+            marker: "...".to_string(),
+        };
+        let widen = fit_rows(&mut batch, "orders", &limits(), &truncate).unwrap();
+        assert!(widen.is_empty());
+        assert_eq!(batch[0]["name"], "short");
+        assert_eq!(batch[1]["name"], "a much ...");
+
+        let mut batch = rows();
+        let widen = fit_rows(&mut batch, "orders", &limits(), &StringOverflow::Widen).unwrap();
+        assert_eq!(widen, HashMap::from([("name".to_string(), 32)]));
+        assert_eq!(batch[1]["name"], "a much longer name");
+    }
+}
@@ -7,18 +7,29 @@
 //! including:
 //! - Table existence verification
 //! - CDC audit column creation
-//! - Schema evolution (adding columns)
+//! - Schema evolution (adding columns, relaxing nullability, comments, and
+//!   dropped, renamed or retyped columns as `SCHEMA_*_COLUMNS` say)
+//! - The `_dbmazz_checkpoints` table (`SINK_CHECKPOINT_TABLE=true`)
+//! - The `_dbmazz_dead_letters` table (`DEAD_LETTER_TARGET=table`)
 //!
 //! All DDL operations use the MySQL protocol (port 9030).
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use anyhow::{anyhow, Result};
 use mysql_async::{prelude::Queryable, Conn, OptsBuilder, Pool};
-use tracing::info;
+use tracing::{debug, info, warn};
 
 use super::config::StarRocksSinkConfig;
+use super::overflow::{varchar_len, ColumnLimits};
 use super::types::TypeMapper;
-use crate::core::DataType;
-use crate::utils::validate_sql_identifier;
+use crate::config::{ColumnChangePolicy, SchemaEvolution, SinkTableName};
+use crate::connectors::sinks::{shared, CHECKPOINT_TABLE, DEAD_LETTER_TABLE};
+use crate::core::dead_letter::DeadLetter;
+use crate::core::{DataType, SinkCheckpoint};
+use crate::pipeline::schema_cache::SchemaDelta;
+use crate::utils::{escape_sql_string, validate_sql_identifier};
 
 /// CDC audit columns that must exist in all replicated StarRocks tables.
 #[allow(dead_code)]
@@ -38,6 +49,28 @@ const AUDIT_COLUMNS: &[(&str, &str)] = &[
     ),
 ];
 
+/// A column as reported by StarRocks' `information_schema.columns`.
+#[derive(Debug, Clone)]
+struct SinkColumn {
+    /// Full type, e.g. `varchar(65533)`
+    column_type: String,
+    nullable: bool,
+    comment: String,
+    /// Key columns are NOT NULL in StarRocks and cannot be relaxed
+    is_key: bool,
+}
+
+/// DDL needed to bring a table in line with a schema delta.
+#[derive(Debug, Default, PartialEq)]
+struct DeltaStatements {
+    /// `RENAME COLUMN` and `DROP COLUMN`, made before columns are added
+    change_columns: Vec<String>,
+    /// One `ADD COLUMN (...)` for every added column the table lacks
+    add_columns: Option<String>,
+    /// `MODIFY COLUMN` for nullability, comment and type changes
+    modify_columns: Vec<String>,
+}
+
 /// StarRocks schema setup and management.
 ///
 /// Handles DDL operations via MySQL protocol including:
@@ -53,6 +86,12 @@ pub struct StarRocksSetup {
     config: StarRocksSinkConfig,
     /// Type mapper for data type conversions
     type_mapper: TypeMapper,
+    /// What happens to dropped, renamed and retyped columns
+    schema_evolution: SchemaEvolution,
+    /// The checkpoint table has been created by this process
+    checkpoint_table_ready: AtomicBool,
+    /// The dead-letter table has been created by this process
+    dead_letter_table_ready: AtomicBool,
 }
 
 #[allow(dead_code)]
@@ -73,10 +112,26 @@ impl StarRocksSetup {
             pool,
             config,
             type_mapper: TypeMapper::new(),
+            schema_evolution: SchemaEvolution::default(),
+            checkpoint_table_ready: AtomicBool::new(false),
+            dead_letter_table_ready: AtomicBool::new(false),
         })
     }
 
-    /// Creates a MySQL connection pool for DDL operations.
+    /// Type mapping for columns added on schema changes
+    pub fn with_type_mapper(mut self, type_mapper: TypeMapper) -> Self {
+        self.type_mapper = type_mapper;
+        self
+    }
+
+    /// Policies for columns dropped, renamed or retyped in the source
+    pub fn with_schema_evolution(mut self, schema_evolution: SchemaEvolution) -> Self {
+        self.schema_evolution = schema_evolution;
+        self
+    }
+
+    /// Creates a MySQL connection pool for DDL operations, or takes the one
+    /// of the configured destination.
     fn create_pool(config: &StarRocksSinkConfig) -> Result<Pool> {
         let hostname = config.hostname();
 
@@ -88,7 +143,11 @@ impl StarRocksSetup {
             .db_name(Some(config.database.clone()))
             .prefer_socket(false); // Force TCP, StarRocks doesn't support Unix socket
 
-        Ok(Pool::new(opts))
+        Ok(shared::mysql_pool(
+            config.shared.as_ref(),
+            &config.database,
+            opts,
+        ))
     }
 
     /// Runs the complete setup process.
@@ -275,6 +334,294 @@ impl StarRocksSetup {
         }
     }
 
+    /// Applies a schema delta to `dest`.
+    ///
+    /// Idempotent: columns the table already has are skipped, so a delta
+    /// replayed after a restart is a no-op. Added columns are created as
+    /// NULL, since existing rows have no value for them. Columns the source
+    /// made nullable are relaxed, and changed comments are copied; both are
+    /// best effort. StarRocks cannot make a column NOT NULL, so tightened
+    /// nullability is left alone.
+    ///
+    /// Dropped, renamed and retyped columns follow `SCHEMA_*_COLUMNS`.
+    /// Renames and drops are skipped once made; a failed type change or
+    /// deprecation comment is only logged, as StarRocks refuses many
+    /// conversions. Key columns are never dropped.
+    pub async fn apply_schema_delta(
+        &self,
+        dest: &SinkTableName,
+        delta: &SchemaDelta,
+    ) -> Result<()> {
+        let database = self.database(dest);
+        validate_sql_identifier(database)
+            .map_err(|e| anyhow!("Invalid database name '{}': {}", database, e))?;
+        validate_sql_identifier(&dest.table)
+            .map_err(|e| anyhow!("Invalid table name '{}': {}", dest.table, e))?;
+
+        let mut conn = self.get_connection().await?;
+        let rows: Vec<(String, String, String, Option<String>, Option<String>)> = conn
+            .exec(
+                "SELECT COLUMN_NAME, COLUMN_TYPE, IS_NULLABLE, COLUMN_COMMENT, COLUMN_KEY
+                 FROM information_schema.columns
+                 WHERE table_schema = ? AND table_name = ?",
+                (database, &dest.table),
+            )
+            .await
+            .map_err(|e| anyhow!("Failed to get columns for {}: {}", dest.label(), e))?;
+        if rows.is_empty() {
+            warn!(
+                "[SCHEMA] Table {} not found in StarRocks, not applying schema change",
+                dest.label()
+            );
+            return Ok(());
+        }
+        let existing: HashMap<String, SinkColumn> = rows
+            .into_iter()
+            .map(|(name, column_type, nullable, comment, key)| {
+                let column = SinkColumn {
+                    column_type,
+                    nullable: nullable == "YES",
+                    comment: comment.unwrap_or_default(),
+                    is_key: key.is_some_and(|k| !k.is_empty()),
+                };
+                (name.to_lowercase(), column)
+            })
+            .collect();
+
+        let table = quoted_table(database, &dest.table);
+        let statements = delta_statements(
+            &table,
+            delta,
+            &existing,
+            &self.type_mapper,
+            &self.schema_evolution,
+        );
+        for sql in &statements.change_columns {
+            conn.query_drop(sql)
+                .await
+                .map_err(|e| anyhow!("Failed to change columns of {}: {}", dest.label(), e))?;
+            info!("Schema evolution: {}", sql);
+        }
+        if let Some(sql) = &statements.add_columns {
+            match conn.query_drop(sql).await {
+                Ok(()) => info!("Schema evolution: {}", sql),
+                // Another writer added it between the lookup and the ALTER
+                Err(e)
+                    if e.to_string().contains("Duplicate column")
+                        || e.to_string().contains("already exists") =>
+                {
+                    info!("Columns already exist in {}, skipping", dest.label())
+                }
+                Err(e) => return Err(anyhow!("Failed to add columns to {}: {}", dest.label(), e)),
+            }
+        }
+        for sql in &statements.modify_columns {
+            match conn.query_drop(sql).await {
+                Ok(()) => info!("Schema evolution: {}", sql),
+                Err(e) => warn!("[SCHEMA] Could not apply ({}): {}", sql, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Upserts the LSN of `slot` into the checkpoint table, creating the
+    /// table on first use. It uses the primary key model, so each insert
+    /// replaces the slot's previous row.
+    pub async fn write_checkpoint(&self, slot: &str, lsn: u64) -> Result<()> {
+        self.upsert_checkpoint(slot, lsn, None).await
+    }
+
+    /// Records that the batch after `lsn` up to `pending_lsn` is being
+    /// loaded (`EXACTLY_ONCE=true`), so a restart replays exactly that
+    /// batch and its loads keep their labels.
+    pub async fn write_checkpoint_intent(
+        &self,
+        slot: &str,
+        lsn: u64,
+        pending_lsn: u64,
+    ) -> Result<()> {
+        self.upsert_checkpoint(slot, lsn, Some(pending_lsn)).await
+    }
+
+    /// The checkpoint row of `slot`, `None` when it has none.
+    pub async fn read_checkpoint(&self, slot: &str) -> Result<Option<SinkCheckpoint>> {
+        let (mut conn, table) = self.checkpoint_table().await?;
+        let row: Option<(i64, Option<i64>)> = conn
+            .exec_first(
+                format!("SELECT lsn, pending_lsn FROM {} WHERE slot_name = ?", table),
+                (slot,),
+            )
+            .await
+            .map_err(|e| anyhow!("Failed to read checkpoint from {}: {}", table, e))?;
+        Ok(row.map(|(lsn, pending_lsn)| SinkCheckpoint {
+            lsn: lsn as u64,
+            pending_lsn: pending_lsn.map(|lsn| lsn as u64),
+        }))
+    }
+
+    async fn upsert_checkpoint(
+        &self,
+        slot: &str,
+        lsn: u64,
+        pending_lsn: Option<u64>,
+    ) -> Result<()> {
+        let (mut conn, table) = self.checkpoint_table().await?;
+        conn.exec_drop(
+            format!(
+                "INSERT INTO {} (slot_name, lsn, pending_lsn, updated_at) VALUES (?, ?, ?, NOW())",
+                table
+            ),
+            (slot, lsn as i64, pending_lsn.map(|lsn| lsn as i64)),
+        )
+        .await
+        .map_err(|e| anyhow!("Failed to write checkpoint to {}: {}", table, e))
+    }
+
+    /// A connection and the quoted checkpoint table, created on first use.
+    /// Tables created before exactly-once mode get its `pending_lsn` column.
+    async fn checkpoint_table(&self) -> Result<(Conn, String)> {
+        validate_sql_identifier(&self.config.database)
+            .map_err(|e| anyhow!("Invalid database name '{}': {}", self.config.database, e))?;
+        let table = format!("`{}`.`{}`", self.config.database, CHECKPOINT_TABLE);
+
+        let mut conn = self.get_connection().await?;
+        if !self.checkpoint_table_ready.load(Ordering::Relaxed) {
+            conn.query_drop(checkpoint_table_sql(&table))
+                .await
+                .map_err(|e| anyhow!("Failed to create {}: {}", table, e))?;
+            let columns = self.get_table_columns(&mut conn, CHECKPOINT_TABLE).await?;
+            if !columns.iter().any(|c| c == "pending_lsn") {
+                conn.query_drop(format!(
+                    "ALTER TABLE {} ADD COLUMN `pending_lsn` BIGINT NULL",
+                    table
+                ))
+                .await
+                .map_err(|e| anyhow!("Failed to add pending_lsn to {}: {}", table, e))?;
+            }
+            self.checkpoint_table_ready.store(true, Ordering::Relaxed);
+        }
+        Ok((conn, table))
+    }
+
+    /// Appends rejected rows to the dead-letter table, creating it on first
+    /// use. Rejections are rare, so plain `INSERT`s are fine here.
+    pub async fn write_dead_letters(&self, letters: &[DeadLetter]) -> Result<()> {
+        validate_sql_identifier(&self.config.database)
+            .map_err(|e| anyhow!("Invalid database name '{}': {}", self.config.database, e))?;
+        let table = format!("`{}`.`{}`", self.config.database, DEAD_LETTER_TABLE);
+
+        let mut conn = self.get_connection().await?;
+        if !self.dead_letter_table_ready.load(Ordering::Relaxed) {
+            conn.query_drop(dead_letter_table_sql(&table))
+                .await
+                .map_err(|e| anyhow!("Failed to create {}: {}", table, e))?;
+            self.dead_letter_table_ready.store(true, Ordering::Relaxed);
+        }
+        let rows = letters.iter().map(|letter| {
+            (
+                letter.failed_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                letter.table.clone(),
+                letter.op,
+                letter.lsn as i64,
+                serde_json::Value::Object(letter.row.clone()).to_string(),
+                letter
+                    .old_row
+                    .as_ref()
+                    .map(|old| serde_json::Value::Object(old.clone()).to_string()),
+                letter.error.clone(),
+            )
+        });
+        conn.exec_batch(
+            format!(
+                "INSERT INTO {} (failed_at, table_name, op, lsn, `row`, old_row, error) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                table
+            ),
+            rows,
+        )
+        .await
+        .map_err(|e| anyhow!("Failed to write dead letters to {}: {}", table, e))
+    }
+
+    /// Byte lengths of the `VARCHAR` and `CHAR` columns of `dest`; empty if
+    /// the table doesn't exist.
+    pub async fn string_limits(&self, dest: &SinkTableName) -> Result<ColumnLimits> {
+        let mut conn = self.get_connection().await?;
+        let rows: Vec<(String, String)> = conn
+            .exec(
+                "SELECT COLUMN_NAME, COLUMN_TYPE FROM information_schema.columns
+                 WHERE table_schema = ? AND table_name = ?",
+                (self.database(dest), &dest.table),
+            )
+            .await
+            .map_err(|e| anyhow!("Failed to get columns for {}: {}", dest.label(), e))?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(name, column_type)| Some((name, varchar_len(&column_type)?)))
+            .collect())
+    }
+
+    /// Name and `COLUMN_TYPE` of every column of `dest`; empty if the table
+    /// doesn't exist.
+    pub async fn column_types(&self, dest: &SinkTableName) -> Result<Vec<(String, String)>> {
+        let mut conn = self.get_connection().await?;
+        conn.exec(
+            "SELECT COLUMN_NAME, COLUMN_TYPE FROM information_schema.columns
+             WHERE table_schema = ? AND table_name = ?",
+            (self.database(dest), &dest.table),
+        )
+        .await
+        .map_err(|e| anyhow!("Failed to get columns for {}: {}", dest.label(), e))
+    }
+
+    /// Widens the `VARCHAR` column `column` of `dest` to `len` bytes,
+    /// keeping its nullability and comment (`SINK_STRING_OVERFLOW=widen`).
+    pub async fn widen_column(&self, dest: &SinkTableName, column: &str, len: u32) -> Result<()> {
+        let database = self.database(dest);
+        validate_sql_identifier(database)
+            .map_err(|e| anyhow!("Invalid database name '{}': {}", database, e))?;
+        validate_sql_identifier(&dest.table)
+            .map_err(|e| anyhow!("Invalid table name '{}': {}", dest.table, e))?;
+
+        let mut conn = self.get_connection().await?;
+        let current: Option<(String, Option<String>)> = conn
+            .exec_first(
+                "SELECT IS_NULLABLE, COLUMN_COMMENT FROM information_schema.columns
+                 WHERE table_schema = ? AND table_name = ? AND column_name = ?",
+                (database, &dest.table, column),
+            )
+            .await
+            .map_err(|e| anyhow!("Failed to get column {}.{}: {}", dest.label(), column, e))?;
+        let Some((nullable, comment)) = current else {
+            return Err(anyhow!("Column {}.{} not found", dest.label(), column));
+        };
+
+        let sql = widen_column_sql(
+            &quoted_table(database, &dest.table),
+            column,
+            len,
+            nullable == "YES",
+            comment.as_deref().unwrap_or_default(),
+        );
+        conn.query_drop(&sql)
+            .await
+            .map_err(|e| anyhow!("Failed to widen {}.{}: {}", dest.label(), column, e))?;
+        info!(
+            "Schema evolution: widened {}.{} to VARCHAR({}) for a longer value",
+            dest.label(),
+            column,
+            len
+        );
+        Ok(())
+    }
+
+    /// Makes the next checkpoint and dead-letter writes create their tables
+    /// again if they are missing.
+    pub fn forget_internal_tables(&self) {
+        self.checkpoint_table_ready.store(false, Ordering::Relaxed);
+        self.dead_letter_table_ready.store(false, Ordering::Relaxed);
+    }
+
     /// Gets the list of columns for a table.
     async fn get_table_columns(&self, conn: &mut Conn, table: &str) -> Result<Vec<String>> {
         let rows: Vec<(String,)> = conn
@@ -289,6 +636,12 @@ impl StarRocksSetup {
         Ok(rows.into_iter().map(|(name,)| name).collect())
     }
 
+    /// Database of `dest`: its own with `SINK_SCHEMA_MODE=preserve`, else
+    /// `SINK_DATABASE`.
+    fn database<'a>(&'a self, dest: &'a SinkTableName) -> &'a str {
+        dest.database.as_deref().unwrap_or(&self.config.database)
+    }
+
     /// Gets a connection from the pool.
     async fn get_connection(&self) -> Result<Conn> {
         self.pool
@@ -304,9 +657,227 @@ impl StarRocksSetup {
     }
 }
 
+/// DDL of the checkpoint table, `table` being the quoted `db`.`name`.
+fn checkpoint_table_sql(table: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n  \
+         `slot_name` VARCHAR(255) NOT NULL,\n  \
+         `lsn` BIGINT NOT NULL COMMENT 'Last source LSN written to this database',\n  \
+         `pending_lsn` BIGINT NULL COMMENT 'End LSN of the batch being loaded (EXACTLY_ONCE)',\n  \
+         `updated_at` DATETIME NOT NULL\n\
+         ) PRIMARY KEY (`slot_name`)\n\
+         DISTRIBUTED BY HASH(`slot_name`)",
+        table
+    )
+}
+
+/// DDL of the dead-letter table, `table` being the quoted `db`.`name`.
+fn dead_letter_table_sql(table: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n  \
+         `failed_at` DATETIME NOT NULL,\n  \
+         `table_name` VARCHAR(255) NOT NULL,\n  \
+         `op` VARCHAR(16) NOT NULL,\n  \
+         `lsn` BIGINT NOT NULL COMMENT 'LSN of the batch the row belonged to',\n  \
+         `row` STRING NOT NULL COMMENT 'Column values as JSON',\n  \
+         `old_row` STRING NULL,\n  \
+         `error` STRING NOT NULL\n\
+         ) DUPLICATE KEY (`failed_at`, `table_name`)\n\
+         DISTRIBUTED BY HASH(`table_name`)",
+        table
+    )
+}
+
+/// `database`.`table`, quoted for DDL.
+fn quoted_table(database: &str, table: &str) -> String {
+    format!("`{}`.`{}`", database, table)
+}
+
+/// `MODIFY COLUMN` giving `column` of `table` (quoted) a `len`-byte
+/// `VARCHAR`. The statement restates the whole definition, so nullability
+/// and comment are kept.
+fn widen_column_sql(table: &str, column: &str, len: u32, nullable: bool, comment: &str) -> String {
+    let mut sql = format!(
+        "ALTER TABLE {} MODIFY COLUMN `{}` VARCHAR({}) {}",
+        table,
+        column.replace('`', "``"),
+        len,
+        if nullable { "NULL" } else { "NOT NULL" }
+    );
+    if !comment.is_empty() {
+        sql.push_str(&format!(" COMMENT '{}'", escape_sql_string(comment)));
+    }
+    sql
+}
+
+/// Plans the DDL for `delta` against the current columns of `table`
+/// (quoted), keyed by lowercase name: StarRocks column names are
+/// case-insensitive, so `UserId` is the column `userid` too.
+fn delta_statements(
+    table: &str,
+    delta: &SchemaDelta,
+    existing: &HashMap<String, SinkColumn>,
+    type_mapper: &TypeMapper,
+    evolution: &SchemaEvolution,
+) -> DeltaStatements {
+    let quote = |name: &str| format!("`{}`", name.replace('`', "``"));
+    let comment_clause = |comment: Option<&str>| match comment {
+        Some(c) if !c.is_empty() => format!(" COMMENT '{}'", escape_sql_string(c)),
+        _ => String::new(),
+    };
+    let has = |name: &str| existing.get(&name.to_lowercase());
+
+    let mut change_columns = Vec::new();
+    // Columns given a comment saying they are deprecated
+    let mut deprecated: Vec<(&str, String)> = Vec::new();
+    let kept_key = |name: &str| {
+        warn!(
+            "[SCHEMA] {}.{} changed in the source; key columns are kept",
+            table, name
+        )
+    };
+    let mut renamed_to = Vec::new();
+    for rename in &delta.renamed_columns {
+        let Some(current) = has(&rename.old_name) else {
+            continue;
+        };
+        if has(&rename.new_name).is_some() {
+            continue;
+        }
+        match evolution.renamed {
+            ColumnChangePolicy::Ignore => {}
+            ColumnChangePolicy::Apply if current.is_key => kept_key(&rename.old_name),
+            ColumnChangePolicy::Apply => {
+                change_columns.push(format!(
+                    "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                    table,
+                    quote(&rename.old_name),
+                    quote(&rename.new_name)
+                ));
+                renamed_to.push(rename.new_name.to_lowercase());
+            }
+            ColumnChangePolicy::Deprecate => deprecated.push((
+                rename.old_name.as_str(),
+                format!("Deprecated: renamed to {} in the source", rename.new_name),
+            )),
+        }
+    }
+    for dropped in &delta.dropped_columns {
+        let Some(current) = has(&dropped.name) else {
+            continue;
+        };
+        match evolution.dropped {
+            ColumnChangePolicy::Ignore => {}
+            ColumnChangePolicy::Apply if current.is_key => kept_key(&dropped.name),
+            ColumnChangePolicy::Apply => change_columns.push(format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                table,
+                quote(&dropped.name)
+            )),
+            ColumnChangePolicy::Deprecate => deprecated.push((
+                dropped.name.as_str(),
+                "Deprecated: dropped from the source".to_string(),
+            )),
+        }
+    }
+
+    let added: Vec<String> = delta
+        .added_columns
+        .iter()
+        .filter(|c| has(&c.name).is_none() && !renamed_to.contains(&c.name.to_lowercase()))
+        .map(|c| {
+            let comment = delta.column(&c.name).and_then(|a| a.comment.as_deref());
+            format!(
+                "{} {} NULL{}",
+                quote(&c.name),
+                type_mapper.pg_type_to_starrocks(c.pg_type_id),
+                comment_clause(comment)
+            )
+        })
+        .collect();
+    let add_columns = (!added.is_empty())
+        .then(|| format!("ALTER TABLE {} ADD COLUMN ({})", table, added.join(", ")));
+
+    let mut modify_columns = Vec::new();
+    for retyped in &delta.retyped_columns {
+        let Some(current) = has(&retyped.name) else {
+            continue;
+        };
+        let new_type = type_mapper.pg_type_to_starrocks(retyped.pg_type_id);
+        match evolution.retyped {
+            ColumnChangePolicy::Ignore => {}
+            ColumnChangePolicy::Apply if current.is_key => kept_key(&retyped.name),
+            ColumnChangePolicy::Apply => modify_columns.push(format!(
+                "ALTER TABLE {} MODIFY COLUMN {} {} {}{}",
+                table,
+                quote(&retyped.name),
+                new_type,
+                if current.nullable { "NULL" } else { "NOT NULL" },
+                comment_clause(Some(current.comment.as_str()))
+            )),
+            ColumnChangePolicy::Deprecate => deprecated.push((
+                retyped.name.as_str(),
+                format!("Deprecated: the source column is now {}", new_type),
+            )),
+        }
+    }
+    for attrs in &delta.columns {
+        if AUDIT_COLUMNS.iter().any(|(name, _)| *name == attrs.name)
+            || deprecated.iter().any(|(name, _)| *name == attrs.name)
+        {
+            continue;
+        }
+        let Some(current) = existing.get(&attrs.name.to_lowercase()) else {
+            continue;
+        };
+        let comment = attrs.comment.as_deref().unwrap_or(&current.comment);
+        let relax = attrs.nullable && !current.nullable && !current.is_key;
+        if !attrs.nullable && current.nullable {
+            debug!(
+                "[SCHEMA] {}.{} is NOT NULL in the source; StarRocks keeps it nullable",
+                table, attrs.name
+            );
+        }
+        if relax {
+            modify_columns.push(format!(
+                "ALTER TABLE {} MODIFY COLUMN {} {} NULL{}",
+                table,
+                quote(&attrs.name),
+                current.column_type,
+                comment_clause(Some(comment))
+            ));
+        } else if comment != current.comment {
+            modify_columns.push(format!(
+                "ALTER TABLE {} MODIFY COLUMN {} COMMENT '{}'",
+                table,
+                quote(&attrs.name),
+                escape_sql_string(comment)
+            ));
+        }
+    }
+
+    for (name, comment) in deprecated {
+        modify_columns.push(format!(
+            "ALTER TABLE {} MODIFY COLUMN {} COMMENT '{}'",
+            table,
+            quote(name),
+            escape_sql_string(&comment)
+        ));
+    }
+
+    DeltaStatements {
+        change_columns,
+        add_columns,
+        modify_columns,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pipeline::schema_cache::{
+        AddedColumn, ColumnAttributes, RenamedColumn, RetypedColumn,
+    };
 
     #[test]
     fn test_audit_columns_definition() {
@@ -320,4 +891,288 @@ mod tests {
         assert!(col_names.contains(&"dbmazz_synced_at"));
         assert!(col_names.contains(&"dbmazz_cdc_version"));
     }
+
+    #[test]
+    fn test_checkpoint_table_sql() {
+        let sql = checkpoint_table_sql("`cdc`.`_dbmazz_checkpoints`");
+        assert!(sql.starts_with("CREATE TABLE IF NOT EXISTS `cdc`.`_dbmazz_checkpoints` (\n"));
+        assert!(sql.contains("\n) PRIMARY KEY (`slot_name`)\n"));
+        assert!(sql.contains("`pending_lsn` BIGINT NULL"));
+        assert!(sql.ends_with("DISTRIBUTED BY HASH(`slot_name`)"));
+    }
+
+    #[test]
+    fn test_dead_letter_table_sql() {
+        let sql = dead_letter_table_sql("`cdc`.`_dbmazz_dead_letters`");
+        assert!(sql.starts_with("CREATE TABLE IF NOT EXISTS `cdc`.`_dbmazz_dead_letters` (\n"));
+        assert!(sql.contains("\n) DUPLICATE KEY (`failed_at`, `table_name`)\n"));
+        assert!(sql.ends_with("DISTRIBUTED BY HASH(`table_name`)"));
+    }
+
+    #[test]
+    fn test_widen_column_sql() {
+        assert_eq!(
+            widen_column_sql(&table(), "note", 512, true, "Customer's note"),
+            "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `note` VARCHAR(512) NULL \
+             COMMENT 'Customer\\'s note'"
+        );
+        assert_eq!(
+            widen_column_sql(&table(), "code", 64, false, ""),
+            "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `code` VARCHAR(64) NOT NULL"
+        );
+    }
+
+    fn table() -> String {
+        quoted_table("analytics", "orders")
+    }
+
+    fn sink_column(column_type: &str, nullable: bool, comment: &str) -> SinkColumn {
+        SinkColumn {
+            column_type: column_type.to_string(),
+            nullable,
+            comment: comment.to_string(),
+            is_key: false,
+        }
+    }
+
+    fn attrs(name: &str, nullable: bool, comment: Option<&str>) -> ColumnAttributes {
+        ColumnAttributes {
+            name: name.to_string(),
+            nullable,
+            comment: comment.map(str::to_string),
+        }
+    }
+
+    fn delta(added: &[&str], columns: Vec<ColumnAttributes>) -> SchemaDelta {
+        SchemaDelta {
+            table_name: "orders".to_string(),
+            namespace: "public".to_string(),
+            added_columns: added
+                .iter()
+                .map(|name| AddedColumn {
+                    name: name.to_string(),
+                    pg_type_id: 25,
+                    type_mod: -1,
+                })
+                .collect(),
+            columns,
+            ..SchemaDelta::default()
+        }
+    }
+
+    #[test]
+    fn test_delta_adds_missing_columns_as_nullable() {
+        let existing = HashMap::from([("id".to_string(), sink_column("bigint(20)", false, ""))]);
+        let delta = delta(
+            &["note", "tag"],
+            vec![
+                attrs("note", false, Some("Customer's note")),
+                attrs("tag", true, None),
+            ],
+        );
+        let statements = delta_statements(
+            &table(),
+            &delta,
+            &existing,
+            &TypeMapper::new(),
+            &SchemaEvolution::default(),
+        );
+        assert_eq!(
+            statements.add_columns.as_deref(),
+            Some(
+                "ALTER TABLE `analytics`.`orders` ADD COLUMN \
+                 (`note` STRING NULL COMMENT 'Customer\\'s note', `tag` STRING NULL)"
+            )
+        );
+        assert!(statements.modify_columns.is_empty());
+    }
+
+    #[test]
+    fn test_delta_keeps_column_case() {
+        // `userid` exists already; StarRocks would reject `UserId` as a duplicate
+        let existing = HashMap::from([("userid".to_string(), sink_column("bigint(20)", true, ""))]);
+        let delta = delta(
+            &["UserId", "EventType"],
+            vec![attrs("UserId", true, None), attrs("EventType", true, None)],
+        );
+        let statements = delta_statements(
+            &table(),
+            &delta,
+            &existing,
+            &TypeMapper::new(),
+            &SchemaEvolution::default(),
+        );
+        assert_eq!(
+            statements.add_columns.as_deref(),
+            Some("ALTER TABLE `analytics`.`orders` ADD COLUMN (`EventType` STRING NULL)")
+        );
+        assert!(statements.modify_columns.is_empty());
+    }
+
+    #[test]
+    fn test_replayed_delta_is_a_noop() {
+        let existing = HashMap::from([
+            ("id".to_string(), sink_column("bigint(20)", false, "")),
+            (
+                "note".to_string(),
+                sink_column("varchar(65533)", true, "Note"),
+            ),
+        ]);
+        let delta = delta(&["note"], vec![attrs("note", true, Some("Note"))]);
+        let statements = delta_statements(
+            &table(),
+            &delta,
+            &existing,
+            &TypeMapper::new(),
+            &SchemaEvolution::default(),
+        );
+        assert_eq!(statements, DeltaStatements::default());
+    }
+
+    #[test]
+    fn test_delta_relaxes_nullability_and_syncs_comments() {
+        let mut key = sink_column("bigint(20)", false, "");
+        key.is_key = true;
+        let existing = HashMap::from([
+            ("id".to_string(), key),
+            (
+                "status".to_string(),
+                sink_column("varchar(65533)", false, "Old"),
+            ),
+            ("total".to_string(), sink_column("decimal(38,9)", true, "")),
+            ("note".to_string(), sink_column("varchar(65533)", true, "")),
+        ]);
+        let delta = delta(
+            &[],
+            vec![
+                attrs("id", true, None),
+                attrs("status", true, Some("Order status")),
+                attrs("total", false, Some("Gross total")),
+                attrs("note", true, None),
+            ],
+        );
+        let statements = delta_statements(
+            &table(),
+            &delta,
+            &existing,
+            &TypeMapper::new(),
+            &SchemaEvolution::default(),
+        );
+        assert_eq!(statements.add_columns, None);
+        assert_eq!(
+            statements.modify_columns,
+            vec![
+                "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `status` varchar(65533) NULL \
+                 COMMENT 'Order status'"
+                    .to_string(),
+                // Tightening is not supported; only the comment is copied
+                "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `total` COMMENT 'Gross total'"
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_delta_drops_renames_and_retypes_by_policy() {
+        let mut key = sink_column("bigint(20)", false, "");
+        key.is_key = true;
+        let existing = HashMap::from([
+            ("id".to_string(), key),
+            ("qty".to_string(), sink_column("int(11)", true, "")),
+            ("memo".to_string(), sink_column("varchar(65533)", true, "")),
+            ("total".to_string(), sink_column("int(11)", true, "Gross")),
+        ]);
+        let mut delta = delta(&["quantity"], Vec::new());
+        delta.dropped_columns = ["id", "memo", "gone"]
+            .iter()
+            .map(|name| AddedColumn {
+                name: name.to_string(),
+                pg_type_id: 25,
+                type_mod: -1,
+            })
+            .collect();
+        delta.renamed_columns = vec![RenamedColumn {
+            old_name: "qty".to_string(),
+            new_name: "quantity".to_string(),
+        }];
+        delta.retyped_columns = vec![RetypedColumn {
+            name: "total".to_string(),
+            old_type_id: 23,
+            pg_type_id: 20,
+            type_mod: -1,
+        }];
+
+        // By default the renamed column is added and the others are kept
+        let statements = delta_statements(
+            &table(),
+            &delta,
+            &existing,
+            &TypeMapper::new(),
+            &SchemaEvolution::default(),
+        );
+        assert!(statements.change_columns.is_empty());
+        assert_eq!(
+            statements.add_columns.as_deref(),
+            Some("ALTER TABLE `analytics`.`orders` ADD COLUMN (`quantity` STRING NULL)")
+        );
+        assert!(statements.modify_columns.is_empty());
+
+        let apply = SchemaEvolution {
+            dropped: ColumnChangePolicy::Apply,
+            renamed: ColumnChangePolicy::Apply,
+            retyped: ColumnChangePolicy::Apply,
+        };
+        let statements = delta_statements(&table(), &delta, &existing, &TypeMapper::new(), &apply);
+        // The key column stays, and the column gone already is skipped
+        assert_eq!(
+            statements.change_columns,
+            vec![
+                "ALTER TABLE `analytics`.`orders` RENAME COLUMN `qty` TO `quantity`".to_string(),
+                "ALTER TABLE `analytics`.`orders` DROP COLUMN `memo`".to_string(),
+            ]
+        );
+        assert_eq!(statements.add_columns, None);
+        assert_eq!(
+            statements.modify_columns,
+            vec![
+                "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `total` BIGINT NULL COMMENT 'Gross'"
+                    .to_string()
+            ]
+        );
+
+        let deprecate = SchemaEvolution {
+            dropped: ColumnChangePolicy::Deprecate,
+            renamed: ColumnChangePolicy::Deprecate,
+            retyped: ColumnChangePolicy::Deprecate,
+        };
+        let statements =
+            delta_statements(&table(), &delta, &existing, &TypeMapper::new(), &deprecate);
+        assert!(statements.change_columns.is_empty());
+        assert!(statements.add_columns.is_some());
+        assert_eq!(
+            statements.modify_columns,
+            vec![
+                "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `qty` COMMENT \
+                 'Deprecated: renamed to quantity in the source'"
+                    .to_string(),
+                "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `id` COMMENT \
+                 'Deprecated: dropped from the source'"
+                    .to_string(),
+                "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `memo` COMMENT \
+                 'Deprecated: dropped from the source'"
+                    .to_string(),
+                "ALTER TABLE `analytics`.`orders` MODIFY COLUMN `total` COMMENT \
+                 'Deprecated: the source column is now BIGINT'"
+                    .to_string(),
+            ]
+        );
+
+        // Replayed after the rename was made
+        let mut renamed = existing.clone();
+        let qty = renamed.remove("qty").unwrap();
+        renamed.insert("quantity".to_string(), qty);
+        let statements = delta_statements(&table(), &delta, &renamed, &TypeMapper::new(), &apply);
+        assert_eq!(statements.change_columns.len(), 1);
+        assert_eq!(statements.add_columns, None);
+    }
 }
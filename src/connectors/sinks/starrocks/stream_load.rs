@@ -28,22 +28,87 @@
 
 use anyhow::{anyhow, Result};
 use curl::easy::{Easy, List};
-use std::sync::Arc;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tracing::debug;
 
+use crate::config::{HttpClientConfig, SinkAuth, SinkAuthScheme};
+use crate::connectors::http::configure_curl;
+use crate::core::dead_letter::RejectedData;
+use crate::core::sink_error::SinkError;
+use crate::core::timeout::SinkTimeout;
+
+/// Limiter shared by every Stream Load client of the process
+static LOAD_LIMITER: OnceLock<Arc<LoadLimiter>> = OnceLock::new();
+
+/// Waits for a load slot longer than this are logged
+const SLOW_SLOT_WAIT: Duration = Duration::from_secs(1);
+
+/// Parts of StarRocks error messages caused by too many concurrent loads
+const THROTTLE_MARKERS: &[&str] = &[
+    "too many versions",
+    "too many tablet versions",
+    "running txns on db",
+    "txn limit",
+];
+
+/// Parts of StarRocks error messages about columns the table doesn't have
+const SCHEMA_MARKERS: &[&str] = &["unknown column", "column not found", "no such column"];
+
 /// Result of a successful Stream Load operation.
 #[derive(Debug, Clone)]
 pub struct StreamLoadResult {
     /// Status from StarRocks (e.g., "Success", "Publish Timeout")
-    #[allow(dead_code)]
     pub status: String,
     /// Number of rows successfully loaded
     pub loaded_rows: u64,
+    /// Rows StarRocks filtered out within `max_filter_ratio`
+    pub filtered_rows: u64,
+    /// Label the load ran under, if it was given one
+    pub label: Option<String>,
+    /// Where StarRocks lists the filtered rows
+    pub error_url: Option<String>,
     /// Message from StarRocks
     #[allow(dead_code)]
     pub message: String,
 }
 
+impl StreamLoadResult {
+    /// What didn't fail the load into `table_name` but is worth knowing:
+    /// filtered rows, a load not visible yet, a label loaded before.
+    pub fn warnings(&self, table_name: &str) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.filtered_rows > 0 {
+            warnings.push(format!(
+                "Stream Load into {} filtered {} rows{}",
+                table_name,
+                self.filtered_rows,
+                self.error_url
+                    .as_deref()
+                    .map(|url| format!(", details at {}", url))
+                    .unwrap_or_default()
+            ));
+        }
+        match self.status.as_str() {
+            "Publish Timeout" => warnings.push(format!(
+                "Stream Load into {} timed out publishing: the rows are written but may not \
+                 be visible yet",
+                table_name
+            )),
+            "Label Already Exists" => warnings.push(format!(
+                "Stream Load into {} skipped: label {} was already loaded",
+                table_name,
+                self.label.as_deref().unwrap_or_default()
+            )),
+            _ => {}
+        }
+        warnings
+    }
+}
+
 /// Options for a Stream Load request.
 #[derive(Debug, Clone, Default)]
 pub struct StreamLoadOptions {
@@ -53,6 +118,76 @@ pub struct StreamLoadOptions {
     /// Maximum ratio of filtered (rejected) rows. Default: 0.0
     #[allow(dead_code)]
     pub max_filter_ratio: Option<f64>,
+    /// Target database. If None, the client's database.
+    pub database: Option<String>,
+    /// Label of the load (`EXACTLY_ONCE=true`). StarRocks refuses a second
+    /// load under a label it has finished, which is taken as already loaded.
+    pub label: Option<String>,
+}
+
+/// Caps on simultaneous Stream Loads, in total and per table.
+///
+/// StarRocks throttles a table that receives too many concurrent loads and
+/// reports it as an ordinary load failure. The CDC sink and the snapshot and
+/// backfill workers share one limiter, so the caps hold across all of them.
+/// Waiters are served in arrival order. A load first takes a slot of its
+/// table, then a global one, so a table with a long queue never holds more
+/// global slots than its own cap.
+pub struct LoadLimiter {
+    /// `None` when the total is unlimited
+    global: Option<Arc<Semaphore>>,
+    /// Slots per table, `None` when unlimited
+    per_table: Option<usize>,
+    /// One semaphore per `database.table`, created on first use
+    tables: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+/// Slots held by one Stream Load, released when dropped.
+pub struct LoadPermit {
+    _table: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+impl LoadLimiter {
+    pub fn new(global: Option<usize>, per_table: Option<usize>) -> Self {
+        Self {
+            global: global.map(|n| Arc::new(Semaphore::new(n))),
+            per_table,
+            tables: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The process-wide limiter. The limits of the first caller win; every
+    /// client is built from the same sink configuration.
+    pub fn shared(global: Option<usize>, per_table: Option<usize>) -> Arc<Self> {
+        LOAD_LIMITER
+            .get_or_init(|| Arc::new(Self::new(global, per_table)))
+            .clone()
+    }
+
+    /// Waits for a slot to load into `table` (`database.table`).
+    pub async fn acquire(&self, table: &str) -> LoadPermit {
+        let table_slots = self.per_table.map(|n| {
+            self.tables
+                .lock()
+                .entry(table.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(n)))
+                .clone()
+        });
+        // The semaphores are never closed, so acquiring only fails on a bug
+        let table_permit = match table_slots {
+            Some(slots) => slots.acquire_owned().await.ok(),
+            None => None,
+        };
+        let global_permit = match &self.global {
+            Some(slots) => slots.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        LoadPermit {
+            _table: table_permit,
+            _global: global_permit,
+        }
+    }
 }
 
 /// HTTP client for StarRocks Stream Load API.
@@ -70,6 +205,52 @@ pub struct StreamLoadClient {
     user: String,
     /// Password for authentication
     password: String,
+    /// Concurrency caps, `None` for unlimited loads
+    limiter: Option<Arc<LoadLimiter>>,
+    /// Limit on each HTTP request of a load; curl aborts it when reached
+    timeout: Duration,
+    /// Proxy and TLS trust of the FE and BE requests
+    http: HttpClientConfig,
+    /// Scheme and extra headers of the FE and BE requests
+    auth: SinkAuth,
+}
+
+/// How the curl handles of one load reach StarRocks
+struct Transport {
+    timeout: Duration,
+    http: HttpClientConfig,
+    user: String,
+    password: String,
+    auth: SinkAuth,
+}
+
+impl Transport {
+    /// A handle with the limit, proxy, trust and credentials of every
+    /// request of a load.
+    fn easy(&self) -> Result<Easy> {
+        let mut easy = Easy::new();
+        configure_curl(&mut easy, &self.http)?;
+        easy.timeout(self.timeout)?;
+        // A token goes in a header instead
+        if self.auth.scheme != SinkAuthScheme::Token {
+            easy.username(&self.user)?;
+            easy.password(&self.password)?;
+        }
+        Ok(easy)
+    }
+
+    /// The Stream Load headers of `options`, plus the bearer token and the
+    /// `SINK_HTTP_HEADERS`.
+    fn headers(&self, options: &StreamLoadOptions) -> Result<List> {
+        let mut headers = StreamLoadClient::build_headers(options)?;
+        if let (SinkAuthScheme::Token, Some(token)) = (self.auth.scheme, &self.auth.token) {
+            headers.append(&format!("Authorization: Bearer {}", token))?;
+        }
+        for (name, value) in &self.auth.headers {
+            headers.append(&format!("{}: {}", name, value))?;
+        }
+        Ok(headers)
+    }
 }
 
 impl StreamLoadClient {
@@ -80,17 +261,49 @@ impl StreamLoadClient {
             database,
             user,
             password,
+            limiter: None,
+            timeout: Duration::from_secs(30),
+            http: HttpClientConfig::default(),
+            auth: SinkAuth::default(),
         }
     }
 
+    /// Limits each HTTP request of a load (`SINK_REQUEST_TIMEOUT_SECS`).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the requests through the sink's proxy, trusting its CA bundle
+    /// (`SINK_HTTP_PROXY`, `SINK_TLS_*`).
+    pub fn with_http(mut self, http: HttpClientConfig) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Authenticates the requests with `auth` (`SINK_AUTH*`,
+    /// `SINK_HTTP_HEADERS`) instead of plain basic auth.
+    pub fn with_auth(mut self, auth: SinkAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Makes every load wait for a slot of `limiter` first.
+    pub fn with_limiter(mut self, limiter: Arc<LoadLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
     /// Verifies connectivity to the StarRocks HTTP endpoint.
     ///
     /// Performs a simple GET request to validate network connectivity.
     pub async fn verify_connection(&self) -> Result<()> {
         let url = self.base_url.clone();
+        let http = self.http.clone();
 
         tokio::task::spawn_blocking(move || {
             let mut easy = Easy::new();
+            configure_curl(&mut easy, &http)?;
             easy.url(&url)?;
             easy.timeout(std::time::Duration::from_secs(10))?;
             easy.connect_timeout(std::time::Duration::from_secs(5))?;
@@ -133,17 +346,37 @@ impl StreamLoadClient {
         body: Arc<Vec<u8>>,
         options: StreamLoadOptions,
     ) -> Result<StreamLoadResult> {
+        let database = options.database.as_deref().unwrap_or(&self.database);
         let url = format!(
             "{}/api/{}/{}/_stream_load",
-            self.base_url, self.database, table_name
+            self.base_url, database, table_name
         );
-        let user = self.user.clone();
-        let password = self.password.clone();
-        let table = table_name.to_string();
+        let table = format!("{}.{}", database, table_name);
+        let transport = Transport {
+            timeout: self.timeout,
+            http: self.http.clone(),
+            user: self.user.clone(),
+            password: self.password.clone(),
+            auth: self.auth.clone(),
+        };
+
+        // Held until the load returns, including its redirect to a BE
+        let _permit = match &self.limiter {
+            Some(limiter) => {
+                let started = Instant::now();
+                let permit = limiter.acquire(&table).await;
+                let waited = started.elapsed();
+                if waited >= SLOW_SLOT_WAIT {
+                    debug!("Stream Load into {} waited {:?} for a slot", table, waited);
+                }
+                Some(permit)
+            }
+            None => None,
+        };
 
         // Execute in blocking context to avoid blocking async runtime
         tokio::task::spawn_blocking(move || {
-            Self::send_sync(&url, &user, &password, &table, body, options)
+            Self::send_sync(&url, &table, &transport, body, options)
         })
         .await
         .map_err(|e| anyhow!("Task join error: {}", e))?
@@ -152,16 +385,15 @@ impl StreamLoadClient {
     /// Synchronous Stream Load implementation.
     fn send_sync(
         url: &str,
-        user: &str,
-        password: &str,
         table_name: &str,
+        transport: &Transport,
         body: Arc<Vec<u8>>,
         options: StreamLoadOptions,
     ) -> Result<StreamLoadResult> {
         // Extract hostname for redirect rewriting
         let original_hostname = Self::extract_hostname(url)?;
 
-        let mut easy = Easy::new();
+        let mut easy = transport.easy()?;
 
         // Don't follow redirects automatically - we handle them manually
         easy.follow_location(false)?;
@@ -169,11 +401,9 @@ impl StreamLoadClient {
         // Configure request
         easy.url(url)?;
         easy.put(true)?;
-        easy.username(user)?;
-        easy.password(password)?;
 
         // Build headers
-        let headers = Self::build_headers(&options)?;
+        let headers = transport.headers(&options)?;
         easy.http_headers(headers)?;
 
         // Configure body upload
@@ -194,8 +424,6 @@ impl StreamLoadClient {
             Ok(to_copy)
         })?;
 
-        easy.timeout(std::time::Duration::from_secs(30))?;
-
         // Execute request and capture response
         let mut response_body = Vec::new();
         let mut redirect_location = None;
@@ -217,7 +445,9 @@ impl StreamLoadClient {
                 Ok(data.len())
             })?;
 
-            transfer.perform()?;
+            transfer
+                .perform()
+                .map_err(|e| Self::transfer_error(e, table_name, transport.timeout))?;
         }
 
         let response_code = easy.response_code()?;
@@ -235,7 +465,7 @@ impl StreamLoadClient {
                 };
 
                 // Follow redirect to BE
-                return Self::send_to_be(&corrected_location, user, password, options, body);
+                return Self::send_to_be(&corrected_location, table_name, transport, options, body);
             }
         }
 
@@ -246,19 +476,17 @@ impl StreamLoadClient {
     /// Sends data to BE after redirect.
     fn send_to_be(
         be_url: &str,
-        user: &str,
-        password: &str,
+        table_name: &str,
+        transport: &Transport,
         options: StreamLoadOptions,
         body: Arc<Vec<u8>>,
     ) -> Result<StreamLoadResult> {
-        let mut easy = Easy::new();
+        let mut easy = transport.easy()?;
 
         easy.url(be_url)?;
         easy.put(true)?;
-        easy.username(user)?;
-        easy.password(password)?;
 
-        let headers = Self::build_headers(&options)?;
+        let headers = transport.headers(&options)?;
         easy.http_headers(headers)?;
 
         let body_len = body.len();
@@ -278,8 +506,6 @@ impl StreamLoadClient {
             Ok(to_copy)
         })?;
 
-        easy.timeout(std::time::Duration::from_secs(30))?;
-
         let mut response_body = Vec::new();
         {
             let mut transfer = easy.transfer();
@@ -287,11 +513,28 @@ impl StreamLoadClient {
                 response_body.extend_from_slice(data);
                 Ok(data.len())
             })?;
-            transfer.perform()?;
+            transfer
+                .perform()
+                .map_err(|e| Self::transfer_error(e, table_name, transport.timeout))?;
         }
 
         let response_code = easy.response_code()?;
-        Self::parse_response(&response_body, response_code, "BE", &options)
+        Self::parse_response(&response_body, response_code, table_name, &options)
+    }
+
+    /// Reports a request curl gave up on as a [`SinkTimeout`], so it is
+    /// counted and retried like any other timeout.
+    fn transfer_error(e: curl::Error, table_name: &str, timeout: Duration) -> anyhow::Error {
+        if e.is_operation_timedout() {
+            SinkTimeout::record(
+                "starrocks",
+                format!("Stream Load into {}", table_name),
+                timeout,
+            )
+            .into()
+        } else {
+            anyhow!("Stream Load into {} failed: {}", table_name, e)
+        }
     }
 
     /// Builds HTTP headers for Stream Load request.
@@ -309,6 +552,10 @@ impl StreamLoadClient {
             headers.append(&format!("max_filter_ratio: {}", ratio))?;
         }
 
+        if let Some(ref label) = options.label {
+            headers.append(&format!("label: {}", label))?;
+        }
+
         // Partial update headers
         if let Some(ref cols) = options.partial_columns {
             headers.append("partial_update: true")?;
@@ -339,17 +586,96 @@ impl StreamLoadClient {
             .unwrap_or("Unknown")
             .to_string();
         let loaded_rows = resp_json["NumberLoadedRows"].as_u64().unwrap_or(0);
+        let filtered_rows = resp_json["NumberFilteredRows"].as_u64().unwrap_or(0);
+        let error_url = resp_json["ErrorURL"].as_str().map(str::to_string);
         let message = resp_json["Message"].as_str().unwrap_or("").to_string();
 
-        // Validate HTTP response
+        // Validate HTTP response; bad credentials won't get better on retry
+        if response_code == 401 || response_code == 403 {
+            return Err(SinkError::terminal(
+                "starrocks",
+                format!(
+                    "Stream Load into {} was refused with HTTP {}: {} - {}",
+                    table_name, response_code, status, message
+                ),
+            )
+            .into());
+        }
         if response_code >= 400 {
-            return Err(anyhow!("HTTP {}: {} - {}", response_code, status, message));
+            return Err(anyhow!(
+                "Stream Load into {} failed with HTTP {}: {} - {}{}",
+                table_name,
+                response_code,
+                status,
+                message,
+                throttle_hint(&message)
+            ));
+        }
+
+        // A replayed batch: the load under its label already finished
+        if status == "Label Already Exists" {
+            let existing = resp_json["ExistingJobStatus"].as_str().unwrap_or("Unknown");
+            if existing != "FINISHED" {
+                return Err(anyhow!(
+                    "Stream Load into {} waits for the earlier load labelled {} ({})",
+                    table_name,
+                    options.label.as_deref().unwrap_or_default(),
+                    existing
+                ));
+            }
+            debug!(
+                "Stream Load into {} skipped: label {} was already loaded",
+                table_name,
+                options.label.as_deref().unwrap_or_default()
+            );
+            return Ok(StreamLoadResult {
+                status,
+                loaded_rows: 0,
+                filtered_rows: 0,
+                label: options.label.clone(),
+                error_url: None,
+                message,
+            });
         }
 
         // Validate StarRocks response
         // "Publish Timeout" is acceptable - data was written
         if status != "Success" && status != "Publish Timeout" {
-            return Err(anyhow!("Stream Load failed: {} - {}", status, message));
+            let lower = message.to_lowercase();
+            if SCHEMA_MARKERS.iter().any(|m| lower.contains(m)) {
+                return Err(SinkError::schema_mismatch(
+                    "starrocks",
+                    format!(
+                        "Stream Load into {} failed: {} - {}",
+                        table_name, status, message
+                    ),
+                )
+                .into());
+            }
+            if is_data_quality_failure(&resp_json, &message) {
+                return Err(RejectedData::new(
+                    "starrocks",
+                    format!(
+                        "Stream Load into {} failed: {} - {} ({} rows filtered{})",
+                        table_name,
+                        status,
+                        message,
+                        resp_json["NumberFilteredRows"].as_u64().unwrap_or(0),
+                        resp_json["ErrorURL"]
+                            .as_str()
+                            .map(|url| format!(", details at {}", url))
+                            .unwrap_or_default()
+                    ),
+                )
+                .into());
+            }
+            return Err(anyhow!(
+                "Stream Load into {} failed: {} - {}{}",
+                table_name,
+                status,
+                message,
+                throttle_hint(&message)
+            ));
         }
 
         let update_type = if options.partial_columns.is_some() {
@@ -366,6 +692,9 @@ impl StreamLoadClient {
         Ok(StreamLoadResult {
             status,
             loaded_rows,
+            filtered_rows,
+            label: options.label.clone(),
+            error_url,
             message,
         })
     }
@@ -384,6 +713,33 @@ impl StreamLoadClient {
     }
 }
 
+/// Whether a failed load was refused for the rows it carried: StarRocks
+/// filtered rows that don't fit their columns and reports them behind an
+/// `ErrorURL`. Throttling and cluster errors are not about the rows.
+fn is_data_quality_failure(response: &serde_json::Value, message: &str) -> bool {
+    let message = message.to_lowercase();
+    if THROTTLE_MARKERS.iter().any(|m| message.contains(m)) {
+        return false;
+    }
+    response["NumberFilteredRows"].as_u64().unwrap_or(0) > 0
+        || response["ErrorURL"]
+            .as_str()
+            .is_some_and(|url| !url.is_empty())
+        || message.contains("too many filtered rows")
+        || message.contains("data quality error")
+}
+
+/// Points at the concurrency settings when StarRocks rejected a load for
+/// being one too many.
+fn throttle_hint(message: &str) -> &'static str {
+    let message = message.to_lowercase();
+    if THROTTLE_MARKERS.iter().any(|m| message.contains(m)) {
+        " (StarRocks is throttling concurrent loads; lower SINK_MAX_CONCURRENT_LOADS_PER_TABLE)"
+    } else {
+        ""
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -415,11 +771,37 @@ mod tests {
         let options = StreamLoadOptions {
             partial_columns: Some(vec!["col1".to_string(), "col2".to_string()]),
             max_filter_ratio: Some(0.1),
+            database: None,
+            label: Some("dbmazz_dbmazz_slot_10_20_orders".to_string()),
         };
         let headers = StreamLoadClient::build_headers(&options);
         assert!(headers.is_ok());
     }
 
+    #[test]
+    fn test_transport_headers_with_token() {
+        let transport = Transport {
+            timeout: Duration::from_secs(30),
+            http: HttpClientConfig::default(),
+            user: "root".to_string(),
+            password: String::new(),
+            auth: SinkAuth {
+                scheme: SinkAuthScheme::Token,
+                token: Some("eyJ0".to_string()),
+                headers: vec![("X-Tenant".to_string(), "acme".to_string())],
+            },
+        };
+        let headers: Vec<String> = transport
+            .headers(&StreamLoadOptions::default())
+            .unwrap()
+            .iter()
+            .map(|h| String::from_utf8_lossy(h).into_owned())
+            .collect();
+        assert!(headers.contains(&"Authorization: Bearer eyJ0".to_string()));
+        assert_eq!(headers.last().unwrap(), "X-Tenant: acme");
+        assert!(transport.easy().is_ok());
+    }
+
     #[test]
     fn test_parse_response_success() {
         let response = r#"{"Status": "Success", "NumberLoadedRows": 100, "Message": "OK"}"#;
@@ -434,6 +816,61 @@ mod tests {
         let result = result.unwrap();
         assert_eq!(result.status, "Success");
         assert_eq!(result.loaded_rows, 100);
+        assert!(result.warnings("test_table").is_empty());
+    }
+
+    #[test]
+    fn test_parse_response_filtered_within_ratio() {
+        let response = r#"{"Status": "Success", "Message": "OK",
+            "NumberTotalRows": 10, "NumberLoadedRows": 9, "NumberFilteredRows": 1,
+            "ErrorURL": "http://be1:8040/api/_load_error_log?file=error_log_2"}"#;
+        let result = StreamLoadClient::parse_response(
+            response.as_bytes(),
+            200,
+            "cdc.orders",
+            &StreamLoadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!((result.loaded_rows, result.filtered_rows), (9, 1));
+        assert_eq!(
+            result.warnings("cdc.orders"),
+            ["Stream Load into cdc.orders filtered 1 rows, details at \
+              http://be1:8040/api/_load_error_log?file=error_log_2"]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_label_already_loaded() {
+        let options = StreamLoadOptions {
+            label: Some("dbmazz_dbmazz_slot_10_20_orders".to_string()),
+            ..Default::default()
+        };
+        let loaded = StreamLoadClient::parse_response(
+            br#"{"Status": "Label Already Exists", "ExistingJobStatus": "FINISHED",
+                "Message": "Label has already been used"}"#,
+            200,
+            "cdc.orders",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(loaded.loaded_rows, 0);
+        assert_eq!(
+            loaded.warnings("cdc.orders"),
+            [
+                "Stream Load into cdc.orders skipped: label dbmazz_dbmazz_slot_10_20_orders was \
+              already loaded"
+            ]
+        );
+
+        // Still running: retried until it is over
+        let err = StreamLoadClient::parse_response(
+            br#"{"Status": "Label Already Exists", "ExistingJobStatus": "RUNNING"}"#,
+            200,
+            "cdc.orders",
+            &options,
+        )
+        .unwrap_err();
+        assert!(crate::core::sink_error::is_retryable(&err));
     }
 
     #[test]
@@ -460,5 +897,102 @@ mod tests {
         );
 
         assert!(result.is_err());
+        assert!(crate::core::sink_error::is_retryable(&result.unwrap_err()));
+
+        let err = StreamLoadClient::parse_response(
+            br#"{"Status": "Fail", "Message": "Access denied for user 'cdc'"}"#,
+            401,
+            "test_table",
+            &StreamLoadOptions::default(),
+        )
+        .unwrap_err();
+        assert!(!crate::core::sink_error::is_retryable(&err));
+        assert!(err.to_string().contains("HTTP 401"));
+    }
+
+    #[test]
+    fn test_parse_response_names_table_and_throttling() {
+        let response = r#"{"Status": "Fail", "Message": "Too many versions. tablet_id: 10012"}"#;
+        let err = StreamLoadClient::parse_response(
+            response.as_bytes(),
+            200,
+            "cdc.orders",
+            &StreamLoadOptions::default(),
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(err.contains("cdc.orders"));
+        assert!(err.contains("SINK_MAX_CONCURRENT_LOADS_PER_TABLE"));
+        assert_eq!(throttle_hint("Column not found"), "");
+    }
+
+    #[test]
+    fn test_parse_response_filtered_rows_are_rejected() {
+        let response = r#"{"Status": "Fail", "Message": "too many filtered rows",
+            "NumberTotalRows": 3, "NumberLoadedRows": 2, "NumberFilteredRows": 1,
+            "ErrorURL": "http://be1:8040/api/_load_error_log?file=error_log_1"}"#;
+        let err = StreamLoadClient::parse_response(
+            response.as_bytes(),
+            200,
+            "cdc.orders",
+            &StreamLoadOptions::default(),
+        )
+        .unwrap_err();
+        assert!(crate::core::dead_letter::is_rejected(&err));
+        assert!(err.to_string().contains("1 rows filtered"));
+        assert!(err.to_string().contains("_load_error_log"));
+
+        // Not about the rows: a schema mismatch, and throttling
+        for response in [
+            r#"{"Status": "Fail", "Message": "Column not found"}"#,
+            r#"{"Status": "Fail", "Message": "Too many versions. tablet_id: 10012"}"#,
+        ] {
+            let err = StreamLoadClient::parse_response(
+                response.as_bytes(),
+                200,
+                "cdc.orders",
+                &StreamLoadOptions::default(),
+            )
+            .unwrap_err();
+            assert!(!crate::core::dead_letter::is_rejected(&err));
+        }
+    }
+
+    #[test]
+    fn test_parse_response_unknown_column_is_a_schema_mismatch() {
+        let response = r#"{"Status": "Fail", "Message": "Unknown column 'note' in 'orders'"}"#;
+        let err = StreamLoadClient::parse_response(
+            response.as_bytes(),
+            200,
+            "cdc.orders",
+            &StreamLoadOptions::default(),
+        )
+        .unwrap_err();
+        assert!(crate::core::sink_error::is_schema_mismatch(&err));
+        assert!(!crate::core::sink_error::is_retryable(&err));
+    }
+
+    #[tokio::test]
+    async fn test_load_limiter_caps_per_table_and_total() {
+        let limiter = LoadLimiter::new(Some(2), Some(1));
+
+        let orders = limiter.acquire("cdc.orders").await;
+        // Second load into the same table waits for the first
+        let waiting =
+            tokio::time::timeout(Duration::from_millis(20), limiter.acquire("cdc.orders")).await;
+        assert!(waiting.is_err());
+
+        // Other tables go ahead until the total cap is reached
+        let _users = limiter.acquire("cdc.users").await;
+        let waiting =
+            tokio::time::timeout(Duration::from_millis(20), limiter.acquire("cdc.items")).await;
+        assert!(waiting.is_err());
+
+        drop(orders);
+        let _orders =
+            tokio::time::timeout(Duration::from_millis(200), limiter.acquire("cdc.orders"))
+                .await
+                .expect("slot freed by the first load");
     }
 }
@@ -26,9 +26,12 @@
 //!
 //! - Prefer larger types to avoid overflow (e.g., INT64 -> BIGINT)
 //! - Use STRING for unbounded text to avoid VARCHAR length issues
+//! - `varchar(n)` becomes VARCHAR sized in bytes, 4 per character
 //! - DECIMAL preserves precision for financial data
 //! - JSON for semi-structured data
 
+use super::overflow::MAX_VARCHAR_BYTES;
+use crate::config::BoolEncoding;
 use crate::core::{DataType, Value};
 
 /// Type mapper for converting CDC types to StarRocks types.
@@ -38,6 +41,8 @@ pub struct TypeMapper {
     default_decimal_precision: u8,
     /// Default DECIMAL scale
     default_decimal_scale: u8,
+    /// Column type of booleans, per `SINK_BOOLEAN_ENCODING`
+    bool_type: &'static str,
 }
 
 impl TypeMapper {
@@ -46,9 +51,19 @@ impl TypeMapper {
         Self {
             default_decimal_precision: 38,
             default_decimal_scale: 9,
+            bool_type: "BOOLEAN",
         }
     }
 
+    /// Booleans loaded as `1`/`0` get TINYINT columns.
+    pub fn with_bool_encoding(mut self, bool_encoding: BoolEncoding) -> Self {
+        self.bool_type = match bool_encoding {
+            BoolEncoding::Int => "TINYINT",
+            BoolEncoding::Bool | BoolEncoding::Char => "BOOLEAN",
+        };
+        self
+    }
+
     /// Converts a CDC `DataType` to a StarRocks type string.
     ///
     /// # Arguments
@@ -60,7 +75,7 @@ impl TypeMapper {
     /// StarRocks SQL type string
     pub fn to_starrocks_type(&self, data_type: &DataType) -> String {
         match data_type {
-            DataType::Boolean => "BOOLEAN".to_string(),
+            DataType::Boolean => self.bool_type.to_string(),
             DataType::Int16 => "SMALLINT".to_string(),
             DataType::Int32 => "INT".to_string(),
             DataType::Int64 => "BIGINT".to_string(),
@@ -72,7 +87,11 @@ impl TypeMapper {
                 let s = (*scale).min(p);
                 format!("DECIMAL({},{})", p, s)
             }
-            DataType::String => "STRING".to_string(),
+            // StarRocks lengths are in bytes, up to 4 per character
+            DataType::String { max_len: Some(n) } => {
+                format!("VARCHAR({})", n.saturating_mul(4).min(MAX_VARCHAR_BYTES))
+            }
+            DataType::String { max_len: None } => "STRING".to_string(),
             DataType::Text => "STRING".to_string(),
             DataType::Bytes => "VARBINARY".to_string(),
             DataType::Json | DataType::Jsonb => "JSON".to_string(),
@@ -81,6 +100,9 @@ impl TypeMapper {
             DataType::Time => "STRING".to_string(), // StarRocks doesn't have TIME type
             DataType::Timestamp => "DATETIME".to_string(),
             DataType::TimestampTz => "DATETIME".to_string(), // StarRocks DATETIME doesn't store TZ
+            // Labels can be added to the type; STRING columns get a
+            // low-cardinality dictionary from StarRocks on their own
+            DataType::Enum { .. } => "STRING".to_string(),
         }
     }
 
@@ -97,7 +119,7 @@ impl TypeMapper {
     /// StarRocks SQL type string
     pub fn pg_type_to_starrocks(&self, pg_type_id: u32) -> &'static str {
         match pg_type_id {
-            16 => "BOOLEAN",         // bool
+            16 => self.bool_type,    // bool
             21 => "SMALLINT",        // int2
             23 => "INT",             // int4
             20 => "BIGINT",          // int8
@@ -116,7 +138,15 @@ impl TypeMapper {
             1082 => "DATE",          // date
             1083 => "STRING",        // time
             1266 => "STRING",        // timetz
-            _ => "STRING",           // default fallback
+            oid if crate::source::pg_types::is_composite(oid)
+                || crate::source::pg_types::is_hstore_or_range(oid) =>
+            {
+                "JSON"
+            }
+            oid => match crate::source::pg_types::base_type(oid) {
+                base if base != oid => self.pg_type_to_starrocks(base), // domain
+                _ => "STRING",                                          // default fallback
+            },
         }
     }
 
@@ -147,8 +177,9 @@ impl TypeMapper {
             }
             Value::Timestamp(ts) => {
                 // Convert Unix timestamp (micros) to datetime string
-                let secs = ts / 1_000_000;
-                let nanos = ((ts % 1_000_000) * 1000) as u32;
+                // (floored, so times before 1970 keep a positive fraction)
+                let secs = ts.div_euclid(1_000_000);
+                let nanos = (ts.rem_euclid(1_000_000) * 1000) as u32;
                 if let Some(dt) = chrono::DateTime::from_timestamp(secs, nanos) {
                     serde_json::json!(dt.format("%Y-%m-%d %H:%M:%S%.6f").to_string())
                 } else {
@@ -181,7 +212,8 @@ impl TypeMapper {
     ///
     /// JSON value suitable for Stream Load
     pub fn pg_text_to_json(&self, text: &str, pg_type_id: u32) -> serde_json::Value {
-        use crate::utils::{normalize_timestamptz, parse_pg_array, strip_money_symbol};
+        use crate::source::pg_types;
+        use crate::utils::{normalize_timestamptz, strip_money_symbol};
 
         match pg_type_id {
             // Boolean
@@ -215,19 +247,10 @@ impl TypeMapper {
             }
             // JSON/JSONB
             114 | 3802 => serde_json::from_str(text).unwrap_or(serde_json::json!(text)),
-            // Integer arrays
-            1005 | 1007 | 1016 => {
-                let json_str = parse_pg_array(text, "int");
-                serde_json::from_str(&json_str).unwrap_or_else(|_| serde_json::json!(text))
-            }
-            // Float arrays
-            1021 | 1022 => {
-                let json_str = parse_pg_array(text, "float");
-                serde_json::from_str(&json_str).unwrap_or_else(|_| serde_json::json!(text))
-            }
-            // Text/varchar arrays
-            1009 | 1015 => {
-                let json_str = parse_pg_array(text, "text");
+            // Arrays of any element type and dimension; composites, hstore
+            // and ranges as objects
+            oid if pg_types::is_json(oid) => {
+                let json_str = pg_types::value_json(text, oid);
                 serde_json::from_str(&json_str).unwrap_or_else(|_| serde_json::json!(text))
             }
             // Default: string
@@ -256,7 +279,14 @@ mod tests {
         assert_eq!(mapper.to_starrocks_type(&DataType::Int64), "BIGINT");
         assert_eq!(mapper.to_starrocks_type(&DataType::Float32), "FLOAT");
         assert_eq!(mapper.to_starrocks_type(&DataType::Float64), "DOUBLE");
-        assert_eq!(mapper.to_starrocks_type(&DataType::String), "STRING");
+        assert_eq!(
+            mapper.to_starrocks_type(&DataType::String { max_len: None }),
+            "STRING"
+        );
+        assert_eq!(
+            mapper.to_starrocks_type(&DataType::String { max_len: Some(20) }),
+            "VARCHAR(80)"
+        );
         assert_eq!(mapper.to_starrocks_type(&DataType::Text), "STRING");
         assert_eq!(mapper.to_starrocks_type(&DataType::Json), "JSON");
         assert_eq!(mapper.to_starrocks_type(&DataType::Jsonb), "JSON");
@@ -268,6 +298,17 @@ mod tests {
         assert_eq!(mapper.to_starrocks_type(&DataType::Bytes), "VARBINARY");
     }
 
+    #[test]
+    fn test_bool_encoding_column_type() {
+        let ints = TypeMapper::new().with_bool_encoding(BoolEncoding::Int);
+        assert_eq!(ints.pg_type_to_starrocks(16), "TINYINT");
+        assert_eq!(ints.to_starrocks_type(&DataType::Boolean), "TINYINT");
+        assert_eq!(ints.pg_type_to_starrocks(23), "INT");
+
+        let bools = TypeMapper::new().with_bool_encoding(BoolEncoding::Bool);
+        assert_eq!(bools.pg_type_to_starrocks(16), "BOOLEAN");
+    }
+
     #[test]
     fn test_decimal_mapping() {
         let mapper = TypeMapper::new();
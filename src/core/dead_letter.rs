@@ -0,0 +1,85 @@
+//! Rows a sink refuses to store.
+//!
+//! A sink that rejects data because of what is in it (a value its column
+//! can't hold, a violated constraint) fails with [`RejectedData`] rather than
+//! a plain error. The pipeline can then narrow the batch down to the rows at
+//! fault and park them as [`DeadLetter`]s with the error, while the rest of
+//! the batch is written. Any other failure (connection, auth, timeout) stops
+//! the pipeline as before: retrying those rows one by one would not help.
+
+use serde::Serialize;
+use std::fmt;
+
+/// A write the sink refused because of the data it carried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedData {
+    /// Sink name, as returned by `Sink::name`
+    pub sink: &'static str,
+    /// The sink's own explanation
+    pub reason: String,
+}
+
+impl RejectedData {
+    pub fn new(sink: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            sink,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for RejectedData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sink rejected the data: {}", self.sink, self.reason)
+    }
+}
+
+impl std::error::Error for RejectedData {}
+
+/// Whether `err`, or any error it wraps, is a [`RejectedData`].
+pub fn is_rejected(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<RejectedData>())
+}
+
+/// A row change the sink rejected, with the error it gave.
+///
+/// Column values are the PostgreSQL text forms received from the WAL, NULL
+/// as `null`; unchanged TOAST columns are left out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeadLetter {
+    /// When the row was rejected
+    pub failed_at: chrono::DateTime<chrono::Utc>,
+    /// `schema.table`
+    pub table: String,
+    /// `insert`, `update` or `delete`
+    pub op: &'static str,
+    /// LSN of the batch the row belonged to
+    pub lsn: u64,
+    /// New row for inserts and updates, old key or row for deletes
+    pub row: serde_json::Map<String, serde_json::Value>,
+    /// Old key or row of an update, when PostgreSQL sent one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_row: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Error of the write that held only this row
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_is_rejected_through_context() {
+        let err = Err::<(), _>(anyhow::Error::new(RejectedData::new(
+            "sqlite",
+            "CHECK constraint failed: qty > 0",
+        )))
+        .context("Failed to upsert row into orders")
+        .unwrap_err();
+        assert!(is_rejected(&err));
+        assert!(format!("{:#}", err).contains("sqlite sink rejected the data"));
+
+        assert!(!is_rejected(&anyhow::anyhow!("connection refused")));
+    }
+}
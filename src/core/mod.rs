@@ -1,8 +1,13 @@
+pub mod dead_letter;
 pub mod error;
+pub mod pool;
 pub mod position;
 pub mod record;
+pub mod schema_diff;
+pub mod sink_error;
+pub mod timeout;
 pub mod traits;
 
-pub use position::SourcePosition;
+pub use position::{BatchId, SinkCheckpoint, SourcePosition};
 pub use record::{CdcRecord, ColumnDef, ColumnValue, DataType, TableRef, Value};
 pub use traits::{LoadingModel, Sink, SinkCapabilities, SinkResult};
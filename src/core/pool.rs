@@ -0,0 +1,186 @@
+//! Buffer pools for the record conversion hot path.
+//!
+//! Converting a batch of pgoutput messages allocates one `Vec<CdcRecord>` for
+//! the batch and one `Vec<ColumnValue>` per row. The sink adapter takes those
+//! vectors from the pools below and sinks hand them back with [`recycle`] once
+//! the records are encoded or applied, so in steady state a batch reuses the
+//! vectors of an earlier one. Column values themselves (text, JSON) are still
+//! allocated per event. Sinks that pass records on, like the channel sink,
+//! simply don't return them.
+
+use std::ops::Add;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::{const_mutex, Mutex};
+
+use crate::core::record::{CdcRecord, ColumnValue};
+
+/// Batch vectors kept for reuse; only one or two batches are in flight
+const MAX_POOLED_BATCHES: usize = 4;
+/// Row vectors kept for reuse: a full batch at the default `FLUSH_SIZE`
+const MAX_POOLED_ROWS: usize = 10_000;
+
+pub static RECORD_BUFFERS: BufferPool<CdcRecord> = BufferPool::new(MAX_POOLED_BATCHES);
+pub static COLUMN_BUFFERS: BufferPool<ColumnValue> = BufferPool::new(MAX_POOLED_ROWS);
+
+/// Hit and miss counters of a pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl PoolStats {
+    /// Share of `take` calls served from the pool, 0 before the first call.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl Add for PoolStats {
+    type Output = PoolStats;
+
+    fn add(self, other: PoolStats) -> PoolStats {
+        PoolStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+        }
+    }
+}
+
+/// A bounded free list of cleared vectors.
+pub struct BufferPool<T> {
+    free: Mutex<Vec<Vec<T>>>,
+    max_buffers: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<T> BufferPool<T> {
+    pub const fn new(max_buffers: usize) -> Self {
+        Self {
+            free: const_mutex(Vec::new()),
+            max_buffers,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns an empty vector with room for at least `capacity` items.
+    pub fn take(&self, capacity: usize) -> Vec<T> {
+        let pooled = self.free.lock().pop();
+        match pooled {
+            Some(mut buf) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                buf.reserve(capacity);
+                buf
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Vec::with_capacity(capacity)
+            }
+        }
+    }
+
+    /// Clears `buf` and keeps it for a later `take`, unless the pool is full.
+    pub fn give(&self, mut buf: Vec<T>) {
+        if buf.capacity() == 0 {
+            return;
+        }
+        // Drop the contents before taking the lock
+        buf.clear();
+        let mut free = self.free.lock();
+        if free.len() < self.max_buffers {
+            free.push(buf);
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Returns the batch and row vectors of written records to the pools.
+pub fn recycle(mut records: Vec<CdcRecord>) {
+    for record in records.drain(..) {
+        match record {
+            CdcRecord::Insert { columns, .. } | CdcRecord::Delete { columns, .. } => {
+                COLUMN_BUFFERS.give(columns)
+            }
+            CdcRecord::Update {
+                old_columns,
+                new_columns,
+                ..
+            } => {
+                if let Some(old) = old_columns {
+                    COLUMN_BUFFERS.give(old);
+                }
+                COLUMN_BUFFERS.give(new_columns);
+            }
+            _ => {}
+        }
+    }
+    RECORD_BUFFERS.give(records);
+}
+
+/// Combined counters of the batch and row pools.
+pub fn stats() -> PoolStats {
+    RECORD_BUFFERS.stats() + COLUMN_BUFFERS.stats()
+}
+
+/// Zeroes the counters of both pools (`ResetMetrics`).
+pub fn reset_stats() {
+    RECORD_BUFFERS.reset_stats();
+    COLUMN_BUFFERS.reset_stats();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_reuses_given_buffer() {
+        let pool: BufferPool<u64> = BufferPool::new(2);
+        let mut buf = pool.take(16);
+        buf.extend([1, 2, 3]);
+        let ptr = buf.as_ptr();
+        pool.give(buf);
+
+        let reused = pool.take(8);
+        assert!(reused.is_empty());
+        assert_eq!(reused.as_ptr(), ptr);
+        assert_eq!(pool.stats(), PoolStats { hits: 1, misses: 1 });
+        assert_eq!(pool.stats().hit_rate(), 0.5);
+
+        pool.reset_stats();
+        assert_eq!(pool.stats(), PoolStats::default());
+    }
+
+    #[test]
+    fn test_pool_is_bounded() {
+        let pool: BufferPool<u64> = BufferPool::new(1);
+        pool.give(Vec::with_capacity(4));
+        pool.give(Vec::with_capacity(4));
+        // Empty vectors hold no allocation worth keeping
+        pool.give(Vec::new());
+        assert_eq!(pool.free.lock().len(), 1);
+    }
+
+    #[test]
+    fn test_hit_rate_without_calls() {
+        assert_eq!(PoolStats::default().hit_rate(), 0.0);
+    }
+}
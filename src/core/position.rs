@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::utils::{fnv1a, FNV_OFFSET_BASIS};
+
+/// Longest label StarRocks accepts for a load
+const MAX_LABEL_LEN: usize = 128;
+
 /// Checkpoint position for different source types
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum SourcePosition {
@@ -83,6 +88,56 @@ impl fmt::Display for SourcePosition {
     }
 }
 
+/// Identity of a batch in exactly-once mode (`EXACTLY_ONCE=true`): the
+/// slot and the LSNs the batch starts after and ends at. Batches end on
+/// commits, so replaying the same WAL range yields the same batch and the
+/// same ID, which the sink recognises as already written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchId {
+    pub slot: String,
+    /// LSN the previous batch ended at
+    pub first_lsn: u64,
+    /// LSN of the batch's last Commit
+    pub last_lsn: u64,
+}
+
+impl BatchId {
+    /// Load label of the batch's rows for `part` (a table), made of the
+    /// characters StarRocks allows. A part that would make it too long or
+    /// needs escaping is replaced by its hash.
+    pub fn label(&self, part: &str) -> String {
+        let label = format!("{}_{}", self, part);
+        let plain = part
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+        if plain && label.len() <= MAX_LABEL_LEN {
+            label
+        } else {
+            format!("{}_{:016x}", self, fnv1a(FNV_OFFSET_BASIS, part.as_bytes()))
+        }
+    }
+}
+
+impl fmt::Display for BatchId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "dbmazz_{}_{:X}_{:X}",
+            self.slot, self.first_lsn, self.last_lsn
+        )
+    }
+}
+
+/// What a sink recorded in its `_dbmazz_checkpoints` table for a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SinkCheckpoint {
+    /// End LSN of the last batch the sink fully wrote
+    pub lsn: u64,
+    /// End LSN of the batch being written when the row was last updated,
+    /// `None` once it was committed
+    pub pending_lsn: Option<u64>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,4 +194,25 @@ mod tests {
             "File:binlog.001:Pos:500"
         );
     }
+
+    #[test]
+    fn test_batch_id_labels() {
+        let id = BatchId {
+            slot: "dbmazz_slot".to_string(),
+            first_lsn: 0x16B3740,
+            last_lsn: 0x16B3A08,
+        };
+        assert_eq!(id.to_string(), "dbmazz_dbmazz_slot_16B3740_16B3A08");
+        assert_eq!(
+            id.label("orders"),
+            "dbmazz_dbmazz_slot_16B3740_16B3A08_orders"
+        );
+
+        // Replays name their loads the same; other batches and tables don't
+        let hashed = id.label("Order Lines");
+        assert_eq!(hashed, id.clone().label("Order Lines"));
+        assert_ne!(hashed, id.label("order_lines"));
+        assert!(hashed.len() <= MAX_LABEL_LEN);
+        assert!(id.label(&"t".repeat(120)).len() <= MAX_LABEL_LEN);
+    }
 }
@@ -1,5 +1,6 @@
 use crate::core::position::SourcePosition;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 /// Database-agnostic CDC record representing a change event
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,34 +39,39 @@ pub enum CdcRecord {
 }
 
 /// Reference to a database table
+///
+/// Names are shared with the schema cache, so cloning one per event is a
+/// reference count bump rather than a string copy.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct TableRef {
-    pub schema: Option<String>,
-    pub name: String,
+    pub schema: Option<Arc<str>>,
+    pub name: Arc<str>,
 }
 
 impl TableRef {
-    pub fn new(schema: Option<String>, name: String) -> Self {
+    pub fn new(schema: Option<Arc<str>>, name: Arc<str>) -> Self {
         Self { schema, name }
     }
 
     pub fn qualified_name(&self) -> String {
         match &self.schema {
             Some(schema) => format!("{}.{}", schema, self.name),
-            None => self.name.clone(),
+            None => self.name.to_string(),
         }
     }
 }
 
 /// A column value in a CDC record
+///
+/// JSON shape: `{"name":"id","value":{"Int64":1}}`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ColumnValue {
-    pub name: String,
+    pub name: Arc<str>,
     pub value: Value,
 }
 
 impl ColumnValue {
-    pub fn new(name: String, value: Value) -> Self {
+    pub fn new(name: Arc<str>, value: Value) -> Self {
         Self { name, value }
     }
 }
@@ -89,7 +95,11 @@ impl ColumnDef {
 }
 
 /// Generic value type supporting common database types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Serializes externally tagged: `{"Int64":1}`, `{"String":"abc"}`,
+/// `{"Bytes":[1,2]}`; unit variants are bare strings (`"Null"`, `"Unchanged"`).
+/// Renaming a variant changes the wire format of the remote sink.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Value {
     Null,
     Bool(bool),
@@ -125,8 +135,14 @@ pub enum DataType {
     Int64,
     Float32,
     Float64,
-    Decimal { precision: u8, scale: u8 },
-    String,
+    Decimal {
+        precision: u8,
+        scale: u8,
+    },
+    /// `varchar(n)` and `char(n)`; `max_len` is `n`, in characters
+    String {
+        max_len: Option<u32>,
+    },
     Text,
     Bytes,
     Json,
@@ -136,6 +152,12 @@ pub enum DataType {
     Time,
     Timestamp,
     TimestampTz,
+    /// A PostgreSQL enum; values are one of `labels`, in sort order as of
+    /// when the type was first read. Sinks may store it as a dictionary
+    /// column, but labels can be added to the type later.
+    Enum {
+        labels: Vec<String>,
+    },
 }
 
 #[allow(dead_code)]
@@ -153,7 +175,10 @@ impl DataType {
     }
 
     pub fn is_text(&self) -> bool {
-        matches!(self, DataType::String | DataType::Text)
+        matches!(
+            self,
+            DataType::String { .. } | DataType::Text | DataType::Enum { .. }
+        )
     }
 
     pub fn is_temporal(&self) -> bool {
@@ -0,0 +1,188 @@
+//! Differences between a source table and its sink table.
+//!
+//! A sink that refuses a batch because of its table's columns says so with
+//! [`SinkError::SchemaMismatch`](crate::core::sink_error::SinkError). The
+//! pipeline then asks the sink to compare each table of the batch with the
+//! columns the schema cache has for it, and pauses the tables that differ.
+//! The diff is what the operator gets to see: which column is missing, or
+//! which has another type than the sink would have created it with.
+
+use std::fmt;
+
+/// A source column its sink table doesn't have as expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMismatch {
+    pub column: String,
+    /// Type the sink creates for the source column
+    pub expected_type: String,
+    /// Type of the sink column, `None` when the sink table lacks it
+    pub actual_type: Option<String>,
+}
+
+/// How a sink table differs from its source table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDiff {
+    /// Source table, `schema.table`
+    pub table: String,
+    /// Sink table, as the sink names it
+    pub sink_table: String,
+    /// The sink table doesn't exist
+    pub table_missing: bool,
+    pub columns: Vec<ColumnMismatch>,
+}
+
+impl SchemaDiff {
+    /// Compares `expected` (each source column with the type the sink would
+    /// create for it) with `actual` (the sink table's columns and types,
+    /// `None` when it doesn't exist). Names are compared case-insensitively
+    /// and types by [`base_type`]. Sink columns the source doesn't have, such
+    /// as audit columns, are not a difference.
+    pub fn compare(
+        table: &str,
+        sink_table: &str,
+        expected: &[(String, String)],
+        actual: Option<&[(String, String)]>,
+    ) -> Self {
+        let Some(actual) = actual else {
+            return Self {
+                table: table.to_string(),
+                sink_table: sink_table.to_string(),
+                table_missing: true,
+                columns: Vec::new(),
+            };
+        };
+        let columns = expected
+            .iter()
+            .filter_map(|(column, expected_type)| {
+                let found = actual
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(column));
+                let actual_type = match found {
+                    Some((_, actual_type))
+                        if base_type(actual_type) == base_type(expected_type) =>
+                    {
+                        return None
+                    }
+                    Some((_, actual_type)) => Some(actual_type.clone()),
+                    None => None,
+                };
+                Some(ColumnMismatch {
+                    column: column.clone(),
+                    expected_type: expected_type.clone(),
+                    actual_type,
+                })
+            })
+            .collect();
+        Self {
+            table: table.to_string(),
+            sink_table: sink_table.to_string(),
+            table_missing: false,
+            columns,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.table_missing && self.columns.is_empty()
+    }
+}
+
+/// `public.orders -> orders: missing column note (String); column qty is Int32, expected Int64`
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}: ", self.table, self.sink_table)?;
+        if self.table_missing {
+            return write!(f, "sink table does not exist");
+        }
+        if self.columns.is_empty() {
+            return write!(f, "no difference");
+        }
+        for (i, c) in self.columns.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            match &c.actual_type {
+                None => write!(f, "missing column {} ({})", c.column, c.expected_type)?,
+                Some(actual) => write!(
+                    f,
+                    "column {} is {}, expected {}",
+                    c.column, actual, c.expected_type
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The part of a column type that tells types apart: lowercase, without
+/// `Nullable(...)`/`LowCardinality(...)` and without parameters, so
+/// `Nullable(DateTime64(6))` and `DateTime64(3)` are alike, as are
+/// `varchar(65533)` and `STRING`.
+pub fn base_type(column_type: &str) -> String {
+    let mut t = column_type.trim().to_lowercase();
+    for wrapper in ["nullable(", "lowcardinality("] {
+        while let Some(inner) = t.strip_prefix(wrapper).and_then(|t| t.strip_suffix(')')) {
+            t = inner.trim().to_string();
+        }
+    }
+    let base = t.split('(').next().unwrap_or_default().trim();
+    match base {
+        "integer" => "int",
+        "boolean" => "bool",
+        "string" | "text" => "varchar",
+        other => other,
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(cols: &[(&str, &str)]) -> Vec<(String, String)> {
+        cols.iter()
+            .map(|(n, t)| (n.to_string(), t.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_base_type() {
+        assert_eq!(base_type("Nullable(DateTime64(6, 'UTC'))"), "datetime64");
+        assert_eq!(base_type("LowCardinality(Nullable(String))"), "varchar");
+        assert_eq!(base_type("varchar(65533)"), "varchar");
+        assert_eq!(base_type("DECIMAL(38,9)"), "decimal");
+        assert_eq!(base_type("INTEGER"), "int");
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected = columns(&[
+            ("id", "BIGINT"),
+            ("qty", "BIGINT"),
+            ("note", "STRING"),
+            ("Status", "STRING"),
+        ]);
+        let actual = columns(&[
+            ("id", "bigint(20)"),
+            ("qty", "int(11)"),
+            ("status", "varchar(65533)"),
+            ("dbmazz_op_type", "tinyint(4)"),
+        ]);
+        let diff = SchemaDiff::compare("public.orders", "orders", &expected, Some(&actual));
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.to_string(),
+            "public.orders -> orders: column qty is int(11), expected BIGINT; \
+             missing column note (STRING)"
+        );
+
+        let same = SchemaDiff::compare("public.orders", "orders", &expected[..1], Some(&actual));
+        assert!(same.is_empty());
+
+        let missing = SchemaDiff::compare("public.orders", "orders", &expected, None);
+        assert!(missing.table_missing);
+        assert_eq!(
+            missing.to_string(),
+            "public.orders -> orders: sink table does not exist"
+        );
+    }
+}
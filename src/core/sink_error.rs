@@ -0,0 +1,136 @@
+//! Classification of sink failures.
+//!
+//! The pipeline retries a batch the sink failed to write, with backoff, as
+//! long as the failure is worth retrying: the sink is down, slow, overloaded
+//! or restarting. A terminal failure (bad credentials, a missing table, data
+//! the sink rejects) would fail the same way again, so it is returned right
+//! away. Sinks that know which case they hit say so with [`SinkError`];
+//! anything else is classified by the errors it wraps, and an unknown error
+//! counts as retryable so an outage never stops the pipeline on its own.
+//!
+//! A schema mismatch (the sink table lacks a column, or has it with another
+//! type) is terminal too, but the pipeline handles it apart: it pauses the
+//! tables that differ instead of stopping.
+
+use std::fmt;
+
+use crate::core::dead_letter::RejectedData;
+use crate::core::timeout::SinkTimeout;
+
+/// A sink failure its sink has classified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkError {
+    /// Worth another attempt later
+    Retryable { sink: &'static str, reason: String },
+    /// Retrying the same batch can't succeed
+    Terminal { sink: &'static str, reason: String },
+    /// The sink table's columns don't fit the rows; terminal until the sink
+    /// table is fixed
+    SchemaMismatch { sink: &'static str, reason: String },
+}
+
+impl SinkError {
+    pub fn retryable(sink: &'static str, reason: impl Into<String>) -> Self {
+        SinkError::Retryable {
+            sink,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn terminal(sink: &'static str, reason: impl Into<String>) -> Self {
+        SinkError::Terminal {
+            sink,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn schema_mismatch(sink: &'static str, reason: impl Into<String>) -> Self {
+        SinkError::SchemaMismatch {
+            sink,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for SinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkError::Retryable { sink, reason }
+            | SinkError::Terminal { sink, reason }
+            | SinkError::SchemaMismatch { sink, reason } => write!(f, "{} sink: {}", sink, reason),
+        }
+    }
+}
+
+impl std::error::Error for SinkError {}
+
+/// Whether writing the batch again may succeed. The outermost classified
+/// error in `err`'s chain decides: a [`SinkError`], a [`RejectedData`]
+/// (terminal) or a [`SinkTimeout`] (retryable). Unclassified errors are
+/// retryable.
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<SinkError>() {
+            return matches!(e, SinkError::Retryable { .. });
+        }
+        if cause.is::<RejectedData>() {
+            return false;
+        }
+        if cause.is::<SinkTimeout>() {
+            return true;
+        }
+    }
+    true
+}
+
+/// Whether the outermost [`SinkError`] in `err`'s chain is a schema mismatch.
+pub fn is_schema_mismatch(err: &anyhow::Error) -> bool {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<SinkError>())
+        .is_some_and(|e| matches!(e, SinkError::SchemaMismatch { .. }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use std::time::Duration;
+
+    #[test]
+    fn test_is_retryable() {
+        let terminal = Err::<(), _>(anyhow::Error::new(SinkError::terminal(
+            "clickhouse",
+            "Code: 516. Authentication failed",
+        )))
+        .context("Failed to insert into orders")
+        .unwrap_err();
+        assert!(!is_retryable(&terminal));
+        assert!(format!("{:#}", terminal).contains("clickhouse sink: Code: 516"));
+
+        let rejected = anyhow::Error::new(RejectedData::new("sqlite", "CHECK constraint failed"));
+        assert!(!is_retryable(&rejected));
+
+        let timeout = anyhow::Error::new(SinkTimeout {
+            sink: "starrocks",
+            operation: "batch".into(),
+            after: Duration::from_secs(1),
+        });
+        assert!(is_retryable(&timeout));
+
+        assert!(is_retryable(&anyhow::anyhow!("connection refused")));
+        assert!(is_retryable(&anyhow::Error::new(SinkError::retryable(
+            "remote",
+            "server restarting"
+        ))));
+
+        let mismatch = Err::<(), _>(anyhow::Error::new(SinkError::schema_mismatch(
+            "sqlite",
+            "table orders has no column named note",
+        )))
+        .context("sink route archive")
+        .unwrap_err();
+        assert!(!is_retryable(&mismatch));
+        assert!(is_schema_mismatch(&mismatch));
+        assert!(!is_schema_mismatch(&terminal));
+    }
+}
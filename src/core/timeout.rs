@@ -0,0 +1,114 @@
+//! Sink operation timeouts.
+//!
+//! Sinks bound each request they make by `SINK_REQUEST_TIMEOUT_SECS`, and the
+//! sink adapter bounds a whole batch by `SINK_BATCH_TIMEOUT_SECS`. A request
+//! that runs out of time is aborted (its future is dropped, or curl gives up)
+//! and fails with [`SinkTimeout`], which retry loops treat as retryable.
+//! Timeouts are counted per sink for `/metrics`, like the buffer pool stats.
+
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+
+/// Timeouts since startup or the last `ResetMetrics`, by sink name
+static TIMEOUTS: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+
+/// A sink operation that did not finish in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkTimeout {
+    /// Sink name, as returned by `Sink::name`
+    pub sink: &'static str,
+    /// What was cut short, e.g. `batch` or `Stream Load into db.orders`
+    pub operation: String,
+    pub after: Duration,
+}
+
+impl SinkTimeout {
+    /// Counts the timeout and returns it as an error.
+    pub fn record(sink: &'static str, operation: impl Into<String>, after: Duration) -> Self {
+        *TIMEOUTS.lock().entry(sink).or_insert(0) += 1;
+        Self {
+            sink,
+            operation: operation.into(),
+            after,
+        }
+    }
+}
+
+impl fmt::Display for SinkTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} sink: {} timed out after {:?}",
+            self.sink, self.operation, self.after
+        )
+    }
+}
+
+impl std::error::Error for SinkTimeout {}
+
+/// Runs `operation`, dropping it and failing with [`SinkTimeout`] once
+/// `after` has passed. `None` waits forever.
+pub async fn with_timeout<T>(
+    sink: &'static str,
+    operation: &str,
+    after: Option<Duration>,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(after) = after else {
+        return future.await;
+    };
+    match tokio::time::timeout(after, future).await {
+        Ok(result) => result,
+        Err(_) => Err(SinkTimeout::record(sink, operation, after).into()),
+    }
+}
+
+/// Whether `err`, or any error it wraps, is a [`SinkTimeout`].
+pub fn is_timeout(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<SinkTimeout>())
+}
+
+/// Timeouts by sink name, sorted by name.
+pub fn counts() -> Vec<(&'static str, u64)> {
+    TIMEOUTS.lock().iter().map(|(k, v)| (*k, *v)).collect()
+}
+
+/// Zeroes the per-sink counters (`ResetMetrics`).
+pub fn reset_counts() {
+    for count in TIMEOUTS.lock().values_mut() {
+        *count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[tokio::test]
+    async fn test_with_timeout_aborts_and_counts() {
+        let hung = with_timeout(
+            "test_timeout",
+            "batch",
+            Some(Duration::from_millis(10)),
+            std::future::pending::<Result<()>>(),
+        )
+        .await
+        .context("flush failed")
+        .unwrap_err();
+        assert!(is_timeout(&hung));
+        assert!(hung.root_cause().to_string().contains("batch timed out"));
+
+        let quick = with_timeout("test_timeout", "batch", None, async { Ok(7) }).await;
+        assert_eq!(quick.unwrap(), 7);
+        assert!(!is_timeout(&anyhow::anyhow!("connection refused")));
+
+        // Other tests may reset the counters, but never drop a sink's entry
+        assert!(counts().iter().any(|(sink, _)| *sink == "test_timeout"));
+    }
+}
@@ -1,5 +1,8 @@
-use crate::core::position::SourcePosition;
+use crate::core::dead_letter::DeadLetter;
+use crate::core::position::{BatchId, SinkCheckpoint, SourcePosition};
 use crate::core::record::CdcRecord;
+use crate::core::schema_diff::SchemaDiff;
+use crate::pipeline::schema_cache::SchemaDelta;
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
@@ -15,6 +18,10 @@ pub struct SinkCapabilities {
     pub min_batch_size: Option<usize>,
     pub max_batch_size: Option<usize>,
     pub optimal_flush_interval_ms: u64,
+    /// The sink wants each event delivered on its own, as webhooks and
+    /// queues do, rather than in batches of `FLUSH_SIZE`
+    #[serde(default)]
+    pub micro_batch: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,12 +40,19 @@ pub enum StageFormat {
 }
 
 /// Result returned from sink write operations
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 #[allow(dead_code)]
 pub struct SinkResult {
     pub records_written: usize,
     pub bytes_written: u64,
     pub last_position: Option<SourcePosition>,
+    /// Rows the sink accepted but didn't store, e.g. filtered by StarRocks
+    /// within its `max_filter_ratio`
+    pub rows_filtered: usize,
+    /// IDs the sink gave its writes, e.g. Stream Load labels
+    pub labels: Vec<String>,
+    /// Problems that didn't fail the write
+    pub warnings: Vec<String>,
 }
 
 #[async_trait]
@@ -75,6 +89,71 @@ pub trait Sink: Send + Sync {
     /// Writes a batch of CDC records to the sink
     async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult>;
 
+    /// Opens a transaction for the next `write_batch`. Sinks reporting
+    /// `supports_transactions` override the three batch hooks so a batch and
+    /// its checkpoint commit atomically; others keep the no-op defaults and
+    /// make each `write_batch` durable on its own.
+    async fn begin_batch(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Commits the batch opened by `begin_batch`. `checkpoint` is the source
+    /// position the batch ends at, for sinks that store it alongside the data.
+    async fn commit_batch(&mut self, _checkpoint: &SourcePosition) -> Result<()> {
+        Ok(())
+    }
+
+    /// Discards the batch opened by `begin_batch` after a failed write.
+    async fn abort_batch(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Names the batch the next `begin_batch` opens (`EXACTLY_ONCE=true`).
+    /// Sinks that support exactly-once write the ID with the batch, so the
+    /// same batch replayed after a crash is recognised instead of written
+    /// twice.
+    fn identify_batch(&mut self, _id: &BatchId) {}
+
+    /// The slot's row of the sink's `_dbmazz_checkpoints` table, read on
+    /// startup in exactly-once mode; `None` when the sink has none.
+    async fn read_checkpoint(&self, _slot: &str) -> Result<Option<SinkCheckpoint>> {
+        Ok(None)
+    }
+
+    /// Applies columns added to a source table. Called before the records
+    /// that use them; must be idempotent, since a delta can be replayed after
+    /// a restart. Sinks that evolve their schema from `SchemaChange` records
+    /// keep the default.
+    ///
+    /// The delta also lists dropped, renamed and retyped columns; what the
+    /// sink does with each kind is its own choice, set by `SCHEMA_*_COLUMNS`
+    /// (`SinkConfig::schema_evolution`). A renamed column is listed as added
+    /// too, so a sink ignoring renames still gets a column to write to.
+    async fn apply_schema_delta(&self, _delta: &SchemaDelta) -> Result<()> {
+        Ok(())
+    }
+
+    /// Forgets what the sink has cached about the destination of `table`
+    /// (`schema.table`), or of every table and the internal tables when
+    /// `None`, so it is read from the sink again on next use. Called by
+    /// `InvalidateSinkSchema` after sink tables were changed out of band.
+    fn invalidate_schema(&mut self, _table: Option<&str>) {}
+
+    /// Compares the sink table of `schema`'s table with the source columns
+    /// it lists (a full delta). Called after a write failed with a schema
+    /// mismatch, and before a table paused for one resumes; `None` when the
+    /// sink can't describe its tables.
+    async fn schema_diff(&self, _schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        Ok(None)
+    }
+
+    /// Appends rejected rows to the sink's `_dbmazz_dead_letters` table
+    /// (`DEAD_LETTER_TARGET=table`), creating it on first use. Called outside
+    /// `begin_batch`/`commit_batch`.
+    async fn write_dead_letters(&mut self, _letters: &[DeadLetter]) -> Result<()> {
+        anyhow::bail!("the {} sink has no dead-letter table", self.name())
+    }
+
     /// Closes the sink and flushes any remaining data
     async fn close(&mut self) -> Result<()>;
 }
@@ -639,7 +639,13 @@ pub async fn start_replication(
         database: sink.database.clone(),
         user: sink.user.clone(),
         password: sink.password.clone(),
-        starrocks: Some(StarRocksSinkConfig {}),
+        starrocks: Some(StarRocksSinkConfig::default()),
+        iceberg: None,
+        delta: None,
+        object_store: None,
+        shared: None,
+        http: Default::default(),
+        auth: Default::default(),
     };
 
     let config = Config {
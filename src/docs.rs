@@ -0,0 +1,735 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! `dbmazz docs`: documentation of what each sink table will contain.
+//!
+//! Reads the same environment as the engine, describes the configured tables
+//! from the PostgreSQL catalog and writes, per sink table, its columns with
+//! source and sink types, nullability, key and comments, plus the metadata
+//! columns and envelope dbmazz adds. The output is Markdown for people or a
+//! JSON Schema for tooling, meant to be handed to downstream consumers.
+//!
+//! ```text
+//! dbmazz docs [--format markdown|json] [--output PATH]
+//! ```
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value as JsonValue};
+use tracing::info;
+
+use crate::config::{
+    BoolEncoding, Config, FloatFormat, NullEncoding, SinkConfig, SinkType, TableModel,
+};
+#[cfg(any(feature = "delta", feature = "object-store"))]
+use crate::connectors::sinks::changelog::CDC_COLUMNS;
+use crate::connectors::sinks::clickhouse::AUDIT_COLUMNS as CLICKHOUSE_AUDIT_COLUMNS;
+#[cfg(feature = "delta")]
+use crate::connectors::sinks::delta::types::type_name as delta_type_name;
+#[cfg(feature = "iceberg")]
+use crate::connectors::sinks::iceberg::AUDIT_COLUMNS as ICEBERG_AUDIT_COLUMNS;
+use crate::connectors::sinks::CHECKPOINT_TABLE;
+use crate::engine::setup::catalog::{describe_tables, SourceTable};
+use crate::engine::setup::postgres::create_postgres_client;
+use crate::engine::setup::starrocks::{table_model, AUDIT_COLUMNS};
+use crate::pipeline::lineage::{sink_column_type, sink_table_name};
+use crate::source::session::PgSession;
+
+const USAGE: &str = "Usage: dbmazz docs [--format markdown|json] [--output PATH]";
+
+/// Example remote sink payload: a batch is a JSON array of records
+const REMOTE_ENVELOPE_EXAMPLE: &str = r#"[
+  {"Insert": {"table": {"schema": "public", "name": "orders"},
+              "columns": [{"name": "id", "value": {"Int64": 1}}],
+              "position": {"Lsn": 24023128}}},
+  {"Update": {"table": {...}, "old_columns": null, "new_columns": [...], "position": {...}}},
+  {"Delete": {"table": {...}, "columns": [...], "position": {...}}}
+]"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocsFormat {
+    Markdown,
+    JsonSchema,
+}
+
+/// Command line of `dbmazz docs`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DocsArgs {
+    pub format: DocsFormat,
+    /// File to write; stdout when unset
+    pub output: Option<String>,
+}
+
+impl DocsArgs {
+    /// Parses the arguments after `docs`.
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut parsed = DocsArgs {
+            format: DocsFormat::Markdown,
+            output: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .with_context(|| format!("{} needs a value\n{}", flag, USAGE))
+            };
+            match flag {
+                "--format" | "-f" => {
+                    parsed.format = match value()?.to_lowercase().as_str() {
+                        "markdown" | "md" => DocsFormat::Markdown,
+                        "json" | "json-schema" => DocsFormat::JsonSchema,
+                        other => anyhow::bail!("Unsupported docs format: '{}'\n{}", other, USAGE),
+                    }
+                }
+                "--output" | "-o" => parsed.output = Some(value()?),
+                other => anyhow::bail!("Unknown argument: '{}'\n{}", other, USAGE),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Runs `dbmazz docs`: describes the configured tables and writes the docs.
+pub async fn run(args: DocsArgs) -> Result<()> {
+    let config = Config::from_env()?;
+    if let Some(feature) = config.sink.sink_type.missing_feature() {
+        anyhow::bail!(
+            "SINK_TYPE={} is not built into this binary; build dbmazz with `--features {}`",
+            config.sink.sink_type,
+            feature
+        );
+    }
+    let session = PgSession::from_config(&config)?;
+    let client = create_postgres_client(&session, &config.database_url).await?;
+    let mut tables = describe_tables(&client, &config.tables).await?;
+    for table in &mut tables {
+        table.apply_column_transforms(&config.column_transforms);
+    }
+    let docs = SinkDocs::build(
+        &config.sink,
+        &config.sink_key_columns,
+        &config.sink_table_models,
+        &tables,
+    )?;
+
+    let text = match args.format {
+        DocsFormat::Markdown => docs.to_markdown(),
+        DocsFormat::JsonSchema => serde_json::to_string_pretty(&docs.to_json_schema())? + "\n",
+    };
+    match &args.output {
+        Some(path) => {
+            tokio::fs::write(path, text)
+                .await
+                .with_context(|| format!("Failed to write {}", path))?;
+            info!("[OK] Documented {} tables in {}", docs.tables.len(), path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// Everything `dbmazz docs` knows about the sink tables.
+#[derive(Debug)]
+struct SinkDocs {
+    sink_type: String,
+    database: String,
+    tables: Vec<TableDoc>,
+    /// `_dbmazz_checkpoints`, when `SINK_CHECKPOINT_TABLE` is on
+    checkpoint_table: Option<String>,
+    null_encoding: NullEncoding,
+}
+
+#[derive(Debug)]
+struct TableDoc {
+    source_table: String,
+    sink_table: String,
+    comment: Option<String>,
+    /// StarRocks table model or ClickHouse engine used when setup creates
+    /// the table
+    model: Option<String>,
+    key: Vec<String>,
+    key_origin: Option<String>,
+    columns: Vec<ColumnDoc>,
+}
+
+#[derive(Debug)]
+struct ColumnDoc {
+    name: String,
+    /// `information_schema` type name; `None` for metadata columns
+    source_type: Option<String>,
+    /// Column type in the sink (remote sink: `Value` variant)
+    sink_type: String,
+    /// JSON Schema type of the value
+    json_type: &'static str,
+    nullable: bool,
+    key: bool,
+    /// Added by dbmazz rather than replicated from the source
+    metadata: bool,
+    description: Option<String>,
+}
+
+impl SinkDocs {
+    fn build(
+        sink: &SinkConfig,
+        key_overrides: &HashMap<String, Vec<String>>,
+        models: &HashMap<String, TableModel>,
+        tables: &[SourceTable],
+    ) -> Result<Self> {
+        let sink_type = &sink.sink_type;
+        let mut docs = Vec::with_capacity(tables.len());
+        for table in tables {
+            let key = table.sink_key(key_overrides)?;
+            let key_columns = key.as_ref().map(|k| k.columns.clone()).unwrap_or_default();
+            let model = match sink_type {
+                SinkType::StarRocks => Some(table_model(table, key.as_ref(), models).to_string()),
+                SinkType::ClickHouse if key.is_some() => Some("ReplacingMergeTree".to_string()),
+                SinkType::ClickHouse => Some("MergeTree".to_string()),
+                _ => None,
+            };
+
+            let mut columns: Vec<ColumnDoc> = table
+                .columns
+                .iter()
+                .map(|c| {
+                    let is_key = key_columns.contains(&c.name);
+                    ColumnDoc {
+                        name: c.name.clone(),
+                        source_type: Some(c.data_type.clone()),
+                        sink_type: sink_column_type(
+                            sink_type,
+                            c.type_oid,
+                            &c.data_type,
+                            is_key,
+                            sink.bool_encoding,
+                            sink.float_format,
+                            sink.geometry_format,
+                        ),
+                        json_type: json_type(c.type_oid, sink.bool_encoding, sink.float_format),
+                        nullable: c.nullable && !is_key,
+                        key: is_key,
+                        metadata: false,
+                        description: c.comment.clone(),
+                    }
+                })
+                .collect();
+            // StarRocks tables list the key columns first and end with the
+            // audit columns
+            match sink_type {
+                SinkType::StarRocks => {
+                    columns
+                        .sort_by_key(|c| (!c.key, key_columns.iter().position(|k| *k == c.name)));
+                    columns.extend(audit_columns(AUDIT_COLUMNS));
+                }
+                SinkType::ClickHouse => columns.extend(audit_columns(CLICKHOUSE_AUDIT_COLUMNS)),
+                #[cfg(feature = "iceberg")]
+                SinkType::Iceberg => {
+                    let types: Vec<String> = ICEBERG_AUDIT_COLUMNS
+                        .iter()
+                        .map(|(_, t, _)| t.name())
+                        .collect();
+                    let defs: Vec<(&str, &str)> = ICEBERG_AUDIT_COLUMNS
+                        .iter()
+                        .zip(&types)
+                        .map(|((name, ..), t)| (*name, t.as_str()))
+                        .collect();
+                    columns.extend(audit_columns(&defs));
+                }
+                #[cfg(feature = "delta")]
+                SinkType::Delta => {
+                    let types: Vec<String> = CDC_COLUMNS
+                        .iter()
+                        .map(|(_, t, _)| delta_type_name(*t))
+                        .collect();
+                    let defs: Vec<(&str, &str)> = CDC_COLUMNS
+                        .iter()
+                        .zip(&types)
+                        .map(|((name, ..), t)| (*name, t.as_str()))
+                        .collect();
+                    columns.extend(audit_columns(&defs));
+                }
+                #[cfg(feature = "object-store")]
+                SinkType::ObjectStore => {
+                    let types: Vec<String> = CDC_COLUMNS.iter().map(|(_, t, _)| t.name()).collect();
+                    let defs: Vec<(&str, &str)> = CDC_COLUMNS
+                        .iter()
+                        .zip(&types)
+                        .map(|((name, ..), t)| (*name, t.as_str()))
+                        .collect();
+                    columns.extend(audit_columns(&defs));
+                }
+                _ => {}
+            }
+
+            docs.push(TableDoc {
+                source_table: table.qualified_name(),
+                sink_table: sink_table_name(sink_type, &sink.naming, &table.qualified_name()),
+                comment: table.comment.clone(),
+                model,
+                key: key_columns,
+                key_origin: key.map(|k| k.origin.to_string()),
+                columns,
+            });
+        }
+
+        Ok(Self {
+            sink_type: sink_type.to_string(),
+            database: sink.database.clone(),
+            tables: docs,
+            checkpoint_table: sink
+                .checkpoint_slot
+                .as_ref()
+                .map(|_| CHECKPOINT_TABLE.to_string()),
+            null_encoding: sink.null_encoding,
+        })
+    }
+
+    /// How changes land in the sink, one paragraph per fact.
+    fn semantics(&self) -> Vec<String> {
+        let mut notes = match self.sink_type.as_str() {
+            "starrocks" => vec![
+                "Inserts and updates are loaded with Stream Load. Primary key tables keep the \
+                 latest version of each row; duplicate tables keep every change."
+                    .to_string(),
+                "Deletes are soft: the row is kept with `dbmazz_is_deleted = true` and \
+                 `dbmazz_op_type = 2`."
+                    .to_string(),
+            ],
+            "clickhouse" => vec![
+                "Every change is inserted as a new row version. ReplacingMergeTree tables keep \
+                 the version with the highest `dbmazz_cdc_version` per key, so query them with \
+                 `FINAL`; MergeTree tables (no key) keep every change."
+                    .to_string(),
+                "Deletes are soft: a version with `dbmazz_is_deleted = 1` and \
+                 `dbmazz_op_type = 2`, which `FINAL` hides."
+                    .to_string(),
+            ],
+            "iceberg" => vec![
+                "Each batch is committed as one snapshot per table. Inserts and updates \
+                 replace the row with the same key through equality deletes; tables without a \
+                 key are appended to."
+                    .to_string(),
+                "Deletes remove the row with merge-on-read delete files. Each snapshot's \
+                 summary records the LSN it was written at as `dbmazz.lsn`."
+                    .to_string(),
+            ],
+            "delta" => vec![
+                "Every change is appended as a changelog row, one commit per table and batch. \
+                 `_op` is `I`, `U` or `D`, `_lsn` the source LSN; the current state of a row is \
+                 its row with the highest `_lsn`, unless that row is a `D`."
+                    .to_string(),
+                "Delete rows carry the key columns, or every column with \
+                 `REPLICA IDENTITY FULL`. Each commit's info records the LSN it was written at \
+                 as `dbmazz.lsn`."
+                    .to_string(),
+            ],
+            "object_store" => vec![
+                "Every change is written as a row of a Parquet or JSON lines file, one or more \
+                 files per table and batch. `_op` is `I`, `U` or `D`, `_lsn` the source LSN; \
+                 the current state of a row is its row with the highest `_lsn`, unless that \
+                 row is a `D`."
+                    .to_string(),
+                "File names start with the LSN of their batch in hex. A batch written again \
+                 after a failure adds files with the same rows, which the `_lsn` rule reads \
+                 once."
+                    .to_string(),
+            ],
+            "sqlite" => vec![
+                "Inserts and updates are upserts by key; tables without a key are appended to."
+                    .to_string(),
+                "Deletes remove the row. No metadata columns are added.".to_string(),
+            ],
+            _ => vec![
+                "Batches are sent to the sink server as a JSON array of change records. Each \
+                 record is externally tagged by its kind (`Insert`, `Update`, `Delete`, \
+                 `SchemaChange`, `Begin`, `Commit`, `Heartbeat`) and carries the table, its \
+                 columns as `{\"name\", \"value\"}` pairs and the source position."
+                    .to_string(),
+                "Column values are tagged with the variant listed as the sink type, e.g. \
+                 `{\"Int64\": 1}`. `\"Null\"` is a NULL and `\"Unchanged\"` an unchanged \
+                 TOAST value left out of the update."
+                    .to_string(),
+            ],
+        };
+        if let Some(table) = &self.checkpoint_table {
+            notes.push(format!(
+                "`{}` holds the last committed LSN per replication slot.",
+                table
+            ));
+        }
+        match self.null_encoding {
+            NullEncoding::Null => {}
+            NullEncoding::Omit => notes.push(
+                "NULL columns are left out of inserted and updated rows, except in updates \
+                 with unchanged TOAST values."
+                    .to_string(),
+            ),
+            NullEncoding::TypeDefault => notes.push(
+                "NULL columns of new rows hold their type's zero value (`0`, `false`, `\"\"`, \
+                 `{}`, `[]` or the epoch); deletes keep NULL."
+                    .to_string(),
+            ),
+        }
+        notes
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::from("# dbmazz sink tables\n\n");
+        out.push_str(&format!(
+            "Sink: `{}`, database `{}`. {} tables.\n\n",
+            self.sink_type,
+            self.database,
+            self.tables.len()
+        ));
+        for note in self.semantics() {
+            out.push_str(&note);
+            out.push_str("\n\n");
+        }
+        if self.sink_type == "remote" {
+            out.push_str("```json\n");
+            out.push_str(REMOTE_ENVELOPE_EXAMPLE);
+            out.push_str("\n```\n\n");
+        }
+
+        for table in &self.tables {
+            out.push_str(&format!("## `{}`\n\n", table.sink_table));
+            let mut facts = vec![format!("Source: `{}`", table.source_table)];
+            if let Some(model) = &table.model {
+                facts.push(format!("Model: {}", model));
+            }
+            match &table.key_origin {
+                Some(origin) => facts.push(format!("Key: {} ({})", code_list(&table.key), origin)),
+                None => facts.push("Key: none".to_string()),
+            }
+            out.push_str(&facts.join(" · "));
+            out.push_str("\n\n");
+            if let Some(comment) = &table.comment {
+                out.push_str(&format!("> {}\n\n", comment.replace('\n', "\n> ")));
+            }
+
+            out.push_str("| Column | Source type | Sink type | Nullable | Description |\n");
+            out.push_str("|--------|-------------|-----------|----------|-------------|\n");
+            for column in &table.columns {
+                let mut description = Vec::new();
+                if column.key {
+                    description.push("Key.".to_string());
+                }
+                if column.metadata {
+                    description.push("Added by dbmazz.".to_string());
+                }
+                if let Some(text) = &column.description {
+                    description.push(text.clone());
+                }
+                out.push_str(&format!(
+                    "| `{}` | {} | {} | {} | {} |\n",
+                    column.name,
+                    column.source_type.as_deref().unwrap_or("-"),
+                    column.sink_type,
+                    if column.nullable { "yes" } else { "no" },
+                    table_cell(&description.join(" "))
+                ));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// JSON Schema (draft 2020-12) with one `$defs` entry per sink table.
+    fn to_json_schema(&self) -> JsonValue {
+        let mut defs = Map::new();
+        for table in &self.tables {
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            for column in &table.columns {
+                let mut property = Map::new();
+                property.insert(
+                    "type".into(),
+                    if column.nullable {
+                        json!([column.json_type, "null"])
+                    } else {
+                        json!(column.json_type)
+                    },
+                );
+                if let Some(description) = &column.description {
+                    property.insert("description".into(), json!(description));
+                }
+                if let Some(source_type) = &column.source_type {
+                    property.insert("x-source-type".into(), json!(source_type));
+                }
+                property.insert("x-sink-type".into(), json!(column.sink_type));
+                if column.metadata {
+                    property.insert("x-dbmazz-metadata".into(), json!(true));
+                }
+                properties.insert(column.name.clone(), JsonValue::Object(property));
+                required.push(column.name.clone());
+            }
+
+            let mut def = Map::new();
+            def.insert("type".into(), json!("object"));
+            if let Some(comment) = &table.comment {
+                def.insert("description".into(), json!(comment));
+            }
+            def.insert("x-source-table".into(), json!(table.source_table));
+            if let Some(model) = &table.model {
+                def.insert("x-table-model".into(), json!(model));
+            }
+            def.insert("x-primary-key".into(), json!(table.key));
+            def.insert("properties".into(), JsonValue::Object(properties));
+            def.insert("required".into(), json!(required));
+            def.insert("additionalProperties".into(), json!(false));
+            defs.insert(table.sink_table.clone(), JsonValue::Object(def));
+        }
+
+        let mut schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "dbmazz sink tables",
+            "description": self.semantics().join(" "),
+            "x-sink-type": self.sink_type,
+            "x-database": self.database,
+            "$defs": defs,
+        });
+        if self.sink_type == "remote" {
+            schema["x-envelope"] = json!(REMOTE_ENVELOPE_EXAMPLE);
+        }
+        if let Some(table) = &self.checkpoint_table {
+            schema["x-checkpoint-table"] = json!(table);
+        }
+        schema
+    }
+}
+
+/// JSON type of a column value in exported rows and remote payloads.
+fn json_type(
+    type_oid: u32,
+    bool_encoding: BoolEncoding,
+    float_format: FloatFormat,
+) -> &'static str {
+    match type_oid {
+        16 => match bool_encoding {
+            BoolEncoding::Bool => "boolean",
+            BoolEncoding::Int => "integer",
+            BoolEncoding::Char => "string",
+        },
+        20 | 21 | 23 => "integer",
+        // Formatted floats are decimal text
+        700 | 701 if float_format.text(0.0).is_some() => "string",
+        700 | 701 => "number",
+        // NUMERIC and money stay strings so no precision is lost
+        _ => "string",
+    }
+}
+
+/// Audit columns, given as name and column definition, as documented columns.
+fn audit_columns<'a>(defs: &'a [(&'a str, &'a str)]) -> impl Iterator<Item = ColumnDoc> + 'a {
+    defs.iter().map(|(name, def)| {
+        let sink_type = def.split_once(' ').map_or(*def, |(t, _)| t);
+        let (json_type, description) = match *name {
+            "dbmazz_op_type" => (
+                "integer",
+                "Last change: 0 = insert, 1 = update, 2 = delete.",
+            ),
+            "dbmazz_is_deleted" => ("boolean", "True once the row was deleted in the source."),
+            "dbmazz_synced_at" => ("string", "When dbmazz loaded the change."),
+            "dbmazz_cdc_version" => (
+                "integer",
+                "Source LSN of the change; orders versions of a row.",
+            ),
+            "_op" => ("string", "Change type: I = insert, U = update, D = delete."),
+            "_lsn" => (
+                "integer",
+                "Source LSN of the change; orders the changes of a row.",
+            ),
+            "_ts" => ("string", "When dbmazz wrote the change."),
+            _ => ("string", ""),
+        };
+        ColumnDoc {
+            name: name.to_string(),
+            source_type: None,
+            sink_type: sink_type.to_string(),
+            json_type,
+            nullable: true,
+            key: false,
+            metadata: true,
+            description: (!description.is_empty()).then(|| description.to_string()),
+        }
+    })
+}
+
+fn code_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|n| format!("`{}`", n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Keeps free text on one Markdown table row.
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_SINK_REQUEST_TIMEOUT;
+    use crate::engine::setup::catalog::SourceColumn;
+    use crate::engine::setup::starrocks::KEY_VARCHAR;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn column(name: &str, data_type: &str, type_oid: u32, nullable: bool) -> SourceColumn {
+        SourceColumn {
+            name: name.into(),
+            data_type: data_type.into(),
+            type_oid,
+            nullable,
+            comment: None,
+        }
+    }
+
+    fn orders() -> SourceTable {
+        let mut note = column("note", "text", 25, true);
+        note.comment = Some("Free | text\nfrom the customer".into());
+        SourceTable {
+            schema: "public".into(),
+            name: "orders".into(),
+            comment: Some("Customer orders".into()),
+            primary_key: vec!["code".into()],
+            columns: vec![
+                column("amount", "numeric", 1700, false),
+                column("code", "text", 25, false),
+                note,
+            ],
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+        }
+    }
+
+    fn docs(sink_type: SinkType) -> SinkDocs {
+        let sink = SinkConfig {
+            sink_type,
+            url: "http://starrocks:8040".to_string(),
+            port: 9030,
+            database: "analytics".to_string(),
+            user: "root".to_string(),
+            password: "".to_string(),
+            starrocks: None,
+            iceberg: None,
+            delta: None,
+            object_store: None,
+            shared: None,
+            naming: Default::default(),
+            checkpoint_slot: None,
+            null_encoding: Default::default(),
+            bool_encoding: Default::default(),
+            float_format: Default::default(),
+            geometry_format: Default::default(),
+            string_overflow: Default::default(),
+            schema_evolution: Default::default(),
+            request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+            batch_timeout: None,
+            http: Default::default(),
+            auth: Default::default(),
+        };
+        SinkDocs::build(&sink, &HashMap::new(), &HashMap::new(), &[orders()]).unwrap()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            DocsArgs::parse(&[]).unwrap(),
+            DocsArgs {
+                format: DocsFormat::Markdown,
+                output: None
+            }
+        );
+        assert_eq!(
+            DocsArgs::parse(&args(&["--format", "json", "-o", "sink.json"])).unwrap(),
+            DocsArgs {
+                format: DocsFormat::JsonSchema,
+                output: Some("sink.json".into())
+            }
+        );
+        assert_eq!(
+            DocsArgs::parse(&args(&["--format=md"])).unwrap().format,
+            DocsFormat::Markdown
+        );
+        assert!(DocsArgs::parse(&args(&["--format", "yaml"])).is_err());
+        assert!(DocsArgs::parse(&args(&["--output"])).is_err());
+        assert!(DocsArgs::parse(&args(&["--verbose"])).is_err());
+    }
+
+    #[test]
+    fn test_starrocks_docs() {
+        let docs = docs(SinkType::StarRocks);
+        let table = &docs.tables[0];
+        assert_eq!(table.sink_table, "orders");
+        assert_eq!(table.model.as_deref(), Some("primary_key"));
+        assert_eq!(table.key, ["code"]);
+
+        let names: Vec<&str> = table.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "code",
+                "amount",
+                "note",
+                "dbmazz_op_type",
+                "dbmazz_is_deleted",
+                "dbmazz_synced_at",
+                "dbmazz_cdc_version"
+            ]
+        );
+        assert_eq!(table.columns[0].sink_type, KEY_VARCHAR);
+        assert_eq!(table.columns[2].sink_type, "STRING");
+        assert!(table.columns[3].metadata);
+
+        let markdown = docs.to_markdown();
+        assert!(markdown.contains("## `orders`"));
+        assert!(markdown.contains("Key: `code` (primary key)"));
+        assert!(
+            markdown.contains("| `note` | text | STRING | yes | Free \\| text from the customer |")
+        );
+        assert!(markdown.contains("soft"));
+    }
+
+    #[test]
+    fn test_clickhouse_docs() {
+        let docs = docs(SinkType::ClickHouse);
+        let table = &docs.tables[0];
+        assert_eq!(table.model.as_deref(), Some("ReplacingMergeTree"));
+        assert_eq!(table.columns.len(), 7);
+        assert_eq!(table.columns[0].sink_type, "Decimal(38, 9)");
+        assert_eq!(table.columns[6].name, "dbmazz_cdc_version");
+        assert_eq!(table.columns[6].sink_type, "UInt64");
+        assert!(docs.to_markdown().contains("`FINAL`"));
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema = docs(SinkType::Remote).to_json_schema();
+        let orders = &schema["$defs"]["public.orders"];
+        assert_eq!(orders["x-source-table"], "public.orders");
+        assert_eq!(orders["x-primary-key"], json!(["code"]));
+        assert_eq!(orders["properties"]["amount"]["type"], "string");
+        assert_eq!(orders["properties"]["amount"]["x-sink-type"], "Decimal");
+        assert_eq!(
+            orders["properties"]["note"]["type"],
+            json!(["string", "null"])
+        );
+        // The remote sink adds no metadata columns, only the envelope
+        assert_eq!(orders["properties"].as_object().unwrap().len(), 3);
+        assert!(schema["x-envelope"]
+            .as_str()
+            .unwrap()
+            .contains("\"Insert\""));
+    }
+}
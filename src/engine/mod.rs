@@ -1,40 +1,77 @@
 // Copyright 2025
 // Licensed under the Elastic License v2.0
 
-mod setup;
+mod probe;
+mod publication;
+pub(crate) mod setup;
 pub mod snapshot;
+mod standby;
+mod start_check;
+mod wal_guard;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use futures::{SinkExt, StreamExt};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-use crate::config::Config;
+use crate::checkpoint_store::{self, CheckpointStore};
+use crate::config::{Config, SinkConfig, SlotTakeover, StartPosition, WalRetentionAction};
 use crate::connectors::sinks::create_sink;
-use crate::grpc::state::SharedState;
-use crate::grpc::{self, CdcConfig, CdcState, Stage};
+use crate::core::Sink as CoreSink;
+use crate::grpc::state::{ErrorCategory, SharedState};
+use crate::grpc::{self, CdcConfig, CdcState, GrpcServerConfig, Stage};
+use crate::pipeline::archive::WalArchive;
+use crate::pipeline::dead_letter::DeadLetterQueue;
+use crate::pipeline::lineage::LineageExport;
+use crate::pipeline::paused::PauseNotifier;
+use crate::pipeline::retry::RetryPolicy;
+use crate::pipeline::schema_cache::{schema_at, RelationVersion};
+use crate::pipeline::source_catalog::SourceCatalog;
+use crate::pipeline::spill::{self, SpillQueue};
 use crate::pipeline::Pipeline;
 use crate::replication::{
-    handle_keepalive, handle_xlog_data, parse_replication_message, WalMessage,
+    handle_keepalive, handle_xlog_data, parse_replication_message, ParseStage, StartCutoff,
+    TransactionStreams, WalMessage,
 };
-use crate::sink::NewSinkAdapter;
-use crate::source::postgres::{build_standby_status_update, PostgresSource};
-use crate::state_store::StateStore;
+use crate::runtime;
+use crate::sink::{Batching, NewSinkAdapter, ParallelSink, Route, Sink, SinkRouter};
+use crate::source::clock;
+use crate::source::parser::CdcEvent;
+use crate::source::pg_error::{self, PgErrorClass};
+use crate::source::postgres::{
+    build_standby_status_update, ExportedSnapshot, PostgresSource, SlotInUseError,
+};
+use crate::source::session::PgSession;
+use crate::utils::{format_pg_lsn, parse_pg_lsn};
 use setup::SetupManager;
+use standby::{Handover, StandbyOutcome};
+use start_check::StartPositionError;
+
+/// Attempts to reopen the replication stream before the engine stops
+const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+
+/// Replication stream, boxed so a reconnect can replace it
+type ReplicationStream = std::pin::Pin<Box<tokio_postgres::CopyBothDuplex<bytes::Bytes>>>;
 
 /// Main CDC engine that orchestrates all components
 pub struct CdcEngine {
     config: Config,
+    /// Settings of the PostgreSQL connections, built in run()
+    session: PgSession,
     shared_state: Arc<SharedState>,
-    state_store: Option<StateStore>,
+    checkpoint_store: Option<Box<dyn CheckpointStore>>,
+    /// Caller-provided sink used instead of the one described by `config.sink`
+    custom_sink: Option<Box<dyn CoreSink>>,
 }
 
 impl CdcEngine {
     /// Create new CdcEngine
     /// NOTE: Does NOT connect to PostgreSQL here. The gRPC server must start first
-    /// so the worker-agent health check can succeed. StateStore is initialized lazily
+    /// so the worker-agent health check can succeed. The checkpoint store is opened lazily
     /// in run() after the gRPC server is listening.
     pub fn new(config: Config) -> Self {
         let cdc_config = CdcConfig {
@@ -44,43 +81,83 @@ impl CdcEngine {
             slot_name: config.slot_name.clone(),
         };
         let shared_state = SharedState::new(cdc_config);
+        shared_state.set_effective_config(config.clone());
+        shared_state.tail.configure(config.tail);
 
         Self {
             config,
+            session: PgSession::default(),
             shared_state,
-            state_store: None,
+            checkpoint_store: None,
+            custom_sink: None,
         }
     }
 
+    /// Replaces the configured sink with a caller-provided one (e.g. a
+    /// `ChannelSink` when embedding dbmazz). Sink setup is skipped: the caller
+    /// owns the destination.
+    pub fn with_sink(mut self, sink: Box<dyn CoreSink>) -> Self {
+        self.custom_sink = Some(sink);
+        self
+    }
+
     /// Returns a clone of the SharedState Arc.
     /// Used by the demo mode to read metrics while the engine runs.
-    #[allow(dead_code)]
     pub fn shared_state(&self) -> Arc<SharedState> {
         Arc::clone(&self.shared_state)
     }
 
     /// Execute CDC engine
     pub async fn run(mut self) -> Result<()> {
+        self.session = PgSession::from_config(&self.config)?;
+        clock::configure(
+            self.config.clock_skew_warn_ms,
+            self.config.clock_skew_correction,
+        );
+        if self.config.backfill_only {
+            return self.run_backfill().await;
+        }
+
         // Stage: SETUP - gRPC Server (start FIRST so health checks respond immediately)
         self.shared_state
             .set_stage(Stage::Setup, "Starting gRPC server")
             .await;
-        self.start_grpc_server();
+        grpc::start_grpc_server(
+            GrpcServerConfig::from_config(&self.config),
+            self.shared_state.clone(),
+        )
+        .await?;
+
+        // Stage: STANDBY - Follow the active instance until it is lost
+        let mut handover: Option<Handover> = None;
+        if let Some(standby) = self.config.standby.clone() {
+            self.shared_state
+                .set_stage(Stage::Standby, &format!("Following {}", standby.leader_url))
+                .await;
+            match standby::follow_leader(&standby, &self.config.slot_name, &self.shared_state)
+                .await?
+            {
+                StandbyOutcome::TakeOver(state) => handover = Some(state),
+                StandbyOutcome::LeaderStopped | StandbyOutcome::Shutdown => return Ok(()),
+            }
+        }
 
-        // Stage: SETUP - Initialize StateStore (connects to PostgreSQL for checkpoints)
+        // Stage: SETUP - Open the checkpoint store (CHECKPOINT_STORE)
         self.shared_state
             .set_stage(Stage::Setup, "Connecting to checkpoint store")
             .await;
-        let state_store = StateStore::new(&self.config.database_url).await?;
-        self.state_store = Some(state_store);
+        self.checkpoint_store = Some(checkpoint_store::open(&self.config, &self.session).await?);
 
         // Stage: SETUP - Execute automatic setup
         self.shared_state
             .set_stage(Stage::Setup, "Running automatic setup")
             .await;
-        if let Err(e) = self.run_setup().await {
+        if let Err(e) = self.run_setup(handover.is_some()).await {
             // Save error in SharedState for Health Check
             self.shared_state.set_setup_error(Some(e.to_string())).await;
+            self.shared_state
+                .record_error(ErrorCategory::Setup, e.to_string(), None, 0)
+                .await;
             self.shared_state
                 .set_stage(Stage::Setup, "Setup failed")
                 .await;
@@ -95,41 +172,53 @@ impl CdcEngine {
         self.shared_state
             .set_stage(Stage::Setup, "Loading checkpoint")
             .await;
-        let start_lsn = self.load_checkpoint().await?;
+        let mut start_cutoff = None;
+        let start_lsn = match self.config.start_position {
+            StartPosition::Checkpoint => self.load_checkpoint(handover.as_ref()).await?,
+            StartPosition::Now => self.start_from_current_wal().await?,
+            StartPosition::Timestamp(ts) => {
+                start_cutoff = self.preflight_start_timestamp(ts).await?;
+                self.load_checkpoint(None).await?
+            }
+        };
 
         // Stage: SETUP - Source Connection
         self.shared_state
             .set_stage(Stage::Setup, "Connecting to PostgreSQL")
             .await;
-        let source = self.init_source().await?;
-
-        // Stage: SETUP - Replication Stream
-        self.shared_state
-            .set_stage(Stage::Setup, "Starting replication stream")
-            .await;
-        let replication_stream = source.start_replication_from(start_lsn).await?;
-        tokio::pin!(replication_stream);
+        let (source, exported_snapshot) = self.init_source().await?;
 
         // Stage: SETUP - Sink Connection
         self.shared_state
             .set_stage(Stage::Setup, "Connecting to sink")
             .await;
-        let sink_adapter = self.init_sink()?;
+        let custom_sink = self.custom_sink.is_some();
+        let mut sink_adapter = self.init_sink()?;
 
-        // Verify HTTP connectivity BEFORE declaring CDC ready
-        if let Err(e) = sink_adapter.verify_http_connection().await {
-            let error_msg = format!("Sink HTTP connection failed: {}", e);
-            self.shared_state
-                .set_setup_error(Some(error_msg.clone()))
-                .await;
-            self.shared_state
-                .set_stage(Stage::Setup, "Setup failed")
-                .await;
-            error!("{}", error_msg);
-            loop {
-                tokio::time::sleep(Duration::from_secs(60)).await;
+        // Verify HTTP connectivity BEFORE declaring CDC ready, of the
+        // SINK_ROUTES sinks too
+        let verified = match sink_adapter.verify_http_connection().await {
+            Ok(()) => self.init_routes().await.map_err(|e| format!("{:#}", e)),
+            Err(e) => Err(format!("Sink HTTP connection failed: {}", e)),
+        };
+        let routes = match verified {
+            Ok(routes) => routes,
+            Err(error_msg) => {
+                self.shared_state
+                    .set_setup_error(Some(error_msg.clone()))
+                    .await;
+                self.shared_state
+                    .record_error(ErrorCategory::Sink, error_msg.clone(), None, 0)
+                    .await;
+                self.shared_state
+                    .set_stage(Stage::Setup, "Setup failed")
+                    .await;
+                error!("{}", error_msg);
+                loop {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
             }
-        }
+        };
         info!("  [OK] Sink HTTP endpoint accessible");
 
         // Log sink capabilities
@@ -147,25 +236,139 @@ impl CdcEngine {
             info!("    - max_batch_size: {}", max);
         }
 
+        // Exactly-once: resume after the last batch the sink holds, and cut
+        // the batch it was writing where that one ended
+        let mut start_lsn = start_lsn;
+        let mut replay_end = None;
+        if self.config.exactly_once {
+            let checkpoint = sink_adapter
+                .resume_exactly_once(&self.config.slot_name, start_lsn)
+                .await
+                .context("Failed to read the sink checkpoint")?;
+            if let Some(checkpoint) = checkpoint.filter(|c| c.lsn > start_lsn) {
+                info!(
+                    "Exactly-once: resuming from the sink checkpoint at LSN 0x{:X}",
+                    checkpoint.lsn
+                );
+                start_lsn = checkpoint.lsn;
+                self.shared_state.update_lsn(start_lsn);
+                self.shared_state.confirm_lsn(start_lsn);
+            }
+            replay_end = checkpoint.and_then(|c| c.pending_lsn);
+            if let Some(end) = replay_end {
+                info!(
+                    "Exactly-once: replaying the interrupted batch up to LSN 0x{:X}",
+                    end
+                );
+            }
+        }
+
         // Stage: SETUP - Pipeline
         self.shared_state
             .set_stage(Stage::Setup, "Initializing pipeline")
             .await;
-        let (tx, feedback_rx) = self.init_pipeline(sink_adapter, &caps);
+        let relations = self.restore_relations(handover, start_lsn).await;
+        let (tx, mut feedback_rx) =
+            self.init_pipeline(sink_adapter, routes, &caps, relations, replay_end)?;
+
+        // Stage: SNAPSHOT - Copy the exported snapshot, then stream from where it was taken
+        if let Some(exported) = exported_snapshot {
+            start_lsn = self
+                .copy_exported_snapshot(&exported, &tx, &mut feedback_rx)
+                .await?;
+            if self.config.initial_snapshot_only {
+                info!("Initial snapshot only mode: waiting for the sink, then exiting");
+                // The pipeline flushes what is left and hangs up
+                drop(tx);
+                while feedback_rx.recv().await.is_some() {}
+                drop(source);
+                self.release_slot().await;
+                return Ok(());
+            }
+        } else if self.config.exports_snapshot() {
+            info!("SNAPSHOT_METHOD=export: the slot already existed, nothing to copy");
+        }
+
+        // Stage: SETUP - Replication Stream
+        self.shared_state
+            .set_stage(Stage::Setup, "Starting replication stream")
+            .await;
+        let replication_stream = match self.start_replication(&source, start_lsn).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                let detail = if e.downcast_ref::<SlotInUseError>().is_some() {
+                    "Replication slot in use"
+                } else if e.downcast_ref::<StartPositionError>().is_some() {
+                    "Start position unavailable"
+                } else {
+                    return Err(e);
+                };
+                // Report through Health Check / GetStatus like a setup failure
+                self.shared_state
+                    .set_setup_error(Some(format!("{:#}", e)))
+                    .await;
+                self.shared_state
+                    .record_error(ErrorCategory::Source, format!("{:#}", e), None, start_lsn)
+                    .await;
+                self.shared_state.set_stage(Stage::Setup, detail).await;
+                error!("{:#}", e);
+                loop {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+            }
+        };
+        // Boxed so a reconnect can swap in a new stream
+        let replication_stream: ReplicationStream = Box::pin(replication_stream);
 
         // Stage: CDC - Ready to replicate
         self.shared_state.set_stage(Stage::Cdc, "Replicating").await;
         info!("Connected! Streaming CDC events...");
 
+        tokio::spawn(wal_guard::run_wal_guard(
+            self.config.wal_retention.clone(),
+            self.config.sink.http.clone(),
+            self.config.slot_monitor_interval_secs,
+            self.session.clone(),
+            self.config.database_url.clone(),
+            self.config.slot_name.clone(),
+            self.config.pgoutput_protocol_version,
+            self.shared_state.clone(),
+        ));
+
+        if let Some(probe) = self.config.probe.clone() {
+            tokio::spawn(probe::run_probe(
+                probe,
+                self.session.clone(),
+                self.config.database_url.clone(),
+                self.shared_state.clone(),
+            ));
+        }
+
+        if let Some(interval) = self.config.publication_check_interval {
+            tokio::spawn(publication::run_publication_watcher(
+                self.config.clone(),
+                self.session.clone(),
+                interval,
+                custom_sink,
+                self.shared_state.clone(),
+            ));
+        }
+
         // Spawn snapshot worker concurrently if enabled (DO_SNAPSHOT=true)
         // The WAL consumer continues running in parallel; deduplication is handled
         // via should_emit() in wal_handler using the finished_chunks BTreeMap.
-        if self.config.do_snapshot {
+        // An exported snapshot was already copied before streaming
+        let chunked_snapshot = self.config.do_snapshot && !self.config.exports_snapshot();
+        if chunked_snapshot && custom_sink {
+            // The snapshot worker loads straight into the configured sink
+            warn!("DO_SNAPSHOT ignored: snapshots are not supported with a custom sink");
+        } else if chunked_snapshot {
             let snap_config = Arc::new(self.config.clone());
+            let snap_session = self.session.clone();
             let snap_state = self.shared_state.clone();
             let initial_snapshot_only = self.config.initial_snapshot_only;
-            tokio::spawn(async move {
-                match snapshot::run_snapshot(snap_config, snap_state.clone()).await {
+            runtime::spawn_worker(async move {
+                match snapshot::run_snapshot(snap_config, snap_session, snap_state.clone()).await {
                     Ok(()) => {
                         snap_state.set_snapshot_active(false);
                         info!("Snapshot completed successfully");
@@ -177,6 +380,9 @@ impl CdcEngine {
                     Err(e) => {
                         snap_state.set_snapshot_active(false);
                         snap_state.set_snapshot_error(Some(format!("{}", e))).await;
+                        snap_state
+                            .record_error(ErrorCategory::Snapshot, format!("{:#}", e), None, 0)
+                            .await;
                         error!("Snapshot worker error: {}", e);
                     }
                 }
@@ -185,23 +391,89 @@ impl CdcEngine {
         }
 
         // 6. Execute main loop
-        self.run_main_loop(replication_stream, tx, feedback_rx, start_lsn)
+        self.run_main_loop(source, replication_stream, tx, feedback_rx, start_cutoff)
             .await
     }
 
-    /// Execute automatic setup (PostgreSQL + StarRocks)
-    async fn run_setup(&self) -> Result<(), setup::SetupError> {
-        let setup_manager = SetupManager::new(self.config.clone());
+    /// Execute automatic setup (PostgreSQL + StarRocks). A standby taking
+    /// over skips the sink part: the active instance already prepared it.
+    async fn run_setup(&self, taking_over: bool) -> Result<(), setup::SetupError> {
+        let mut setup_manager = SetupManager::new(self.config.clone(), self.session.clone());
+        if self.custom_sink.is_some() || taking_over {
+            setup_manager = setup_manager.without_sink_setup();
+        }
         setup_manager.run().await
     }
 
-    /// Load checkpoint from StateStore
-    async fn load_checkpoint(&self) -> Result<u64> {
-        let state_store = self.state_store.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("state_store must be initialized before load_checkpoint")
+    /// Backfill-only mode: load the configured tables once, log a summary and
+    /// return. No replication slot, publication or gRPC server is created.
+    async fn run_backfill(self) -> Result<()> {
+        if self.custom_sink.is_some() {
+            anyhow::bail!("backfill-only mode is not supported with a custom sink");
+        }
+
+        SetupManager::new(self.config.clone(), self.session.clone())
+            .run_sink_only()
+            .await?;
+
+        let summary = snapshot::run_backfill(&self.config, &self.session).await?;
+        summary.log();
+
+        if !summary.is_complete() {
+            anyhow::bail!("backfill incomplete: some tables were skipped or had failed chunks");
+        }
+        info!("Backfill complete, exiting");
+        Ok(())
+    }
+
+    /// Relation versions to prime the pipeline's schema cache with: the
+    /// handed-over Relation messages after a standby takeover, else the
+    /// versions stored with the checkpoint by the previous run that were in
+    /// effect at `start_lsn`.
+    async fn restore_relations(
+        &self,
+        handover: Option<Handover>,
+        start_lsn: u64,
+    ) -> Vec<RelationVersion> {
+        if let Some(h) = handover.filter(|h| !h.relations.is_empty()) {
+            return h
+                .relations
+                .into_iter()
+                .map(RelationVersion::initial)
+                .collect();
+        }
+        let Some(store) = self.checkpoint_store.as_ref() else {
+            return Vec::new();
+        };
+        match store.load_relations(&self.config.slot_name).await {
+            Ok(versions) => {
+                let relations = schema_at(versions, start_lsn);
+                if !relations.is_empty() {
+                    info!(
+                        "Schema cache: restored {} relation(s) as of LSN 0x{:X}",
+                        relations.len(),
+                        start_lsn
+                    );
+                }
+                relations
+            }
+            Err(e) => {
+                warn!("Failed to load schema cache: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Load checkpoint from the checkpoint store. After a standby takeover, the
+    /// handed-over LSN wins if it is ahead of the stored checkpoint.
+    async fn load_checkpoint(&self, handover: Option<&Handover>) -> Result<u64> {
+        let store = self.checkpoint_store.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("checkpoint store must be initialized before load_checkpoint")
         })?;
-        let last_lsn = state_store.load_checkpoint(&self.config.slot_name).await?;
-        let start_lsn = last_lsn.unwrap_or(0);
+        let last_lsn = store.load_checkpoint(&self.config.slot_name).await?;
+        let start_lsn = last_lsn
+            .unwrap_or(0)
+            .max(handover.map_or(0, |h| h.confirmed_lsn));
 
         if start_lsn > 0 {
             info!("Checkpoint: Resuming from LSN 0x{:X}", start_lsn);
@@ -215,45 +487,440 @@ impl CdcEngine {
         Ok(start_lsn)
     }
 
-    /// Start gRPC server in background
-    fn start_grpc_server(&self) {
-        let grpc_state = self.shared_state.clone();
-        let grpc_port = self.config.grpc_port;
+    /// Streaming-only start (`START_POSITION=now`): jump to the current WAL
+    /// position and record the range that will never be replicated. Only the
+    /// first start jumps; once a checkpoint is stored, streaming resumes from it.
+    async fn start_from_current_wal(&self) -> Result<u64> {
+        let store = self.checkpoint_store.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("checkpoint store must be initialized before start_from_current_wal")
+        })?;
+        if store
+            .load_checkpoint(&self.config.slot_name)
+            .await?
+            .is_some()
+        {
+            info!("START_POSITION=now: checkpoint found, the start position was already applied");
+            return self.load_checkpoint(None).await;
+        }
+
+        let client =
+            setup::postgres::create_postgres_client(&self.session, &self.config.database_url)
+                .await?;
+        let row = client
+            .query_one(
+                "SELECT pg_current_wal_lsn()::text,
+                        (SELECT confirmed_flush_lsn::text FROM pg_replication_slots WHERE slot_name = $1)",
+                &[&self.config.slot_name],
+            )
+            .await
+            .context("failed to read the current WAL position")?;
+        let current: String = row.get(0);
+        let slot_confirmed: Option<String> = row.get(1);
+
+        let now_lsn = parse_pg_lsn(&current)
+            .ok_or_else(|| anyhow::anyhow!("failed to parse WAL LSN '{}'", current))?;
+        // Where a checkpoint start would have resumed: with no checkpoint,
+        // the slot's confirmed position
+        let resume_lsn = slot_confirmed
+            .as_deref()
+            .and_then(parse_pg_lsn)
+            .unwrap_or(0);
+
+        if now_lsn > resume_lsn {
+            warn!(
+                "START_POSITION=now: skipping WAL {} -> {} (data gap acknowledged)",
+                format_pg_lsn(resume_lsn),
+                format_pg_lsn(now_lsn)
+            );
+            self.shared_state.set_skipped_range(resume_lsn, now_lsn);
+        } else {
+            info!(
+                "START_POSITION=now: slot is already at the current WAL position, nothing skipped"
+            );
+        }
+
+        // Persist the new position so a later restart from checkpoint does not
+        // replay the skipped range
+        let start_lsn = now_lsn.max(resume_lsn);
+        store
+            .save_checkpoint(&self.config.slot_name, start_lsn)
+            .await?;
+
+        self.shared_state.update_lsn(start_lsn);
+        self.shared_state.confirm_lsn(start_lsn);
+
+        Ok(start_lsn)
+    }
+
+    /// Time-based start: verify that commit timestamps are tracked and that the
+    /// slot still holds the transactions committed since the requested time,
+    /// and return the cutoff to apply while decoding (see
+    /// `replication::StartCutoff`). Only the first start applies it: once a
+    /// checkpoint is stored, streaming resumes from it and this returns `None`.
+    async fn preflight_start_timestamp(&self, ts: DateTime<Utc>) -> Result<Option<StartCutoff>> {
+        let store = self.checkpoint_store.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("checkpoint store must be initialized before preflight_start_timestamp")
+        })?;
+        if store
+            .load_checkpoint(&self.config.slot_name)
+            .await?
+            .is_some()
+        {
+            info!(
+                "Start position {}: checkpoint found, the start position was already applied",
+                ts.to_rfc3339()
+            );
+            return Ok(None);
+        }
 
-        tokio::spawn(async move {
-            if let Err(e) = grpc::start_grpc_server(grpc_port, grpc_state).await {
-                error!("gRPC server error: {}", e);
+        let client =
+            setup::postgres::create_postgres_client(&self.session, &self.config.database_url)
+                .await?;
+
+        let row = client
+            .query_one("SELECT current_setting('track_commit_timestamp')", &[])
+            .await
+            .context("failed to read track_commit_timestamp")?;
+        let tracking: String = row.get(0);
+        if tracking != "on" {
+            anyhow::bail!(
+                "START_POSITION={} requires track_commit_timestamp = on in postgresql.conf",
+                ts.to_rfc3339()
+            );
+        }
+
+        let slot = client
+            .query_opt(
+                "SELECT wal_status, active_pid, confirmed_flush_lsn::text
+                 FROM pg_replication_slots WHERE slot_name = $1",
+                &[&self.config.slot_name],
+            )
+            .await
+            .context("failed to inspect the replication slot")?
+            .ok_or_else(|| {
+                anyhow::anyhow!("replication slot {} not found", self.config.slot_name)
+            })?;
+        let wal_status: Option<String> = slot.get(0);
+        let active_pid: Option<i32> = slot.get(1);
+        let confirmed: Option<String> = slot.get(2);
+        let confirmed = confirmed.unwrap_or_default();
+
+        if wal_status.as_deref() == Some("lost") {
+            anyhow::bail!(
+                "replication slot {} has lost required WAL; a time-based start is impossible",
+                self.config.slot_name
+            );
+        }
+        if let Some(pid) = active_pid {
+            warn!(
+                "Start position {}: slot {} is in use by PID {}, \
+                 skipping the check that it still holds transactions since then",
+                ts.to_rfc3339(),
+                self.config.slot_name,
+                pid
+            );
+            return Ok(Some(StartCutoff::new(ts)));
+        }
+
+        // Streaming starts at the slot's confirmed_flush_lsn: the first
+        // transaction the slot still returns must not have committed after
+        // the requested time, or the ones in between were already consumed
+        let first_commit = client
+            .query_opt(
+                "SELECT pg_xact_commit_timestamp(xid)
+                 FROM pg_logical_slot_peek_binary_changes($1, NULL, 1,
+                      'proto_version', '1', 'publication_names', $2)
+                 LIMIT 1",
+                &[&self.config.slot_name, &self.config.publication_name],
+            )
+            .await
+            .context("failed to read the first transaction held by the replication slot")?;
+        match first_commit.map(|row| row.get::<_, Option<DateTime<Utc>>>(0)) {
+            Some(Some(first)) if first > ts => anyhow::bail!(
+                "slot {} resumes at LSN {} with a transaction committed at {}; \
+                 earlier ones were already consumed, so cannot start from {}",
+                self.config.slot_name,
+                confirmed,
+                first.to_rfc3339(),
+                ts.to_rfc3339()
+            ),
+            Some(Some(first)) => info!(
+                "Start position {}: slot resumes at LSN {} with a transaction committed at {}",
+                ts.to_rfc3339(),
+                confirmed,
+                first.to_rfc3339()
+            ),
+            Some(None) => warn!(
+                "Start position {}: commit time at the slot's LSN {} is unknown, \
+                 transactions before it are skipped from the slot position onward",
+                ts.to_rfc3339(),
+                confirmed
+            ),
+            None => {
+                // Nothing left to stream: fine as long as nothing committed
+                // since the requested time
+                let row = client
+                    .query_one("SELECT (pg_last_committed_xact()).timestamp", &[])
+                    .await
+                    .context("failed to read the last commit time")?;
+                let last_commit: Option<DateTime<Utc>> = row.get(0);
+                if let Some(last) = last_commit.filter(|last| *last >= ts) {
+                    anyhow::bail!(
+                        "slot {} has already consumed every transaction up to LSN {}, \
+                         the last committed at {}; cannot start from {}",
+                        self.config.slot_name,
+                        confirmed,
+                        last.to_rfc3339(),
+                        ts.to_rfc3339()
+                    );
+                }
+                info!(
+                    "Start position {}: nothing committed since then, streaming from LSN {}",
+                    ts.to_rfc3339(),
+                    confirmed
+                );
             }
-        });
+        }
+
+        Ok(Some(StartCutoff::new(ts)))
+    }
+
+    /// Starts streaming from the slot, applying `SLOT_TAKEOVER` when another
+    /// connection already streams from it.
+    async fn start_replication(
+        &self,
+        source: &PostgresSource,
+        start_lsn: u64,
+    ) -> Result<tokio_postgres::CopyBothDuplex<bytes::Bytes>> {
+        self.check_start_position(start_lsn).await?;
+        let slot_name = &self.config.slot_name;
+        let timeout = Duration::from_secs(self.config.slot_takeover_timeout_secs);
+        let started = std::time::Instant::now();
+        let mut terminated = None;
+
+        loop {
+            let err = match source.start_replication_from(start_lsn).await {
+                Ok(stream) => {
+                    self.shared_state.set_slot_holder_pid(None);
+                    return Ok(stream);
+                }
+                Err(e) if e.downcast_ref::<SlotInUseError>().is_some() => e,
+                Err(e) => return Err(e),
+            };
+
+            let client =
+                setup::postgres::create_postgres_client(&self.session, &self.config.database_url)
+                    .await?;
+            let holder = slot_holder_pid(&client, slot_name).await?;
+            self.shared_state.set_slot_holder_pid(holder);
+            let holder_label = holder.map_or_else(|| "unknown".to_string(), |p| p.to_string());
+
+            match self.config.slot_takeover {
+                SlotTakeover::Fail => {
+                    return Err(err.context(format!(
+                        "slot {} is held by PID {}; stop the other consumer or set \
+                         SLOT_TAKEOVER=wait|steal",
+                        slot_name, holder_label
+                    )));
+                }
+                SlotTakeover::Steal if holder.is_some() && terminated != holder => {
+                    warn!(
+                        "Slot {} is held by PID {}, terminating it (SLOT_TAKEOVER=steal)",
+                        slot_name, holder_label
+                    );
+                    client
+                        .execute(
+                            "SELECT pg_terminate_backend(active_pid) FROM pg_replication_slots
+                             WHERE slot_name = $1 AND active_pid = $2",
+                            &[slot_name, &(holder.unwrap_or(0) as i32)],
+                        )
+                        .await
+                        .context("pg_terminate_backend failed")?;
+                    terminated = holder;
+                }
+                _ => {}
+            }
+
+            if started.elapsed() >= timeout {
+                return Err(err.context(format!(
+                    "slot {} still held by PID {} after {}s (SLOT_TAKEOVER={})",
+                    slot_name,
+                    holder_label,
+                    timeout.as_secs(),
+                    self.config.slot_takeover
+                )));
+            }
+            let detail = format!(
+                "Waiting for slot {} (held by PID {})",
+                slot_name, holder_label
+            );
+            info!("{}", detail);
+            self.shared_state.set_stage(Stage::Setup, &detail).await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Refuses a start position the slot can't serve (see `start_check`).
+    /// When the slot can't be read, `START_REPLICATION` has the last word.
+    async fn check_start_position(&self, start_lsn: u64) -> Result<()> {
+        let slot_name = &self.config.slot_name;
+        let slot = async {
+            let client =
+                setup::postgres::create_postgres_client(&self.session, &self.config.database_url)
+                    .await?;
+            start_check::read_slot_position(&client, slot_name).await
+        };
+        match slot.await {
+            Ok(slot) => {
+                let Err(e) = start_check::check_start_position(slot_name, start_lsn, slot.as_ref())
+                else {
+                    return Ok(());
+                };
+                let stamp = match &self.checkpoint_store {
+                    Some(store) => store.checkpoint_stamp(slot_name).await?,
+                    None => None,
+                };
+                // Logged like before the check existed; the next checkpoint
+                // is marked slot_follows_checkpoint and checked strictly
+                let Some(msg) = start_check::expected_after_upgrade(&e, stamp.as_ref()) else {
+                    return Err(e.into());
+                };
+                warn!("Checkpoint: {}", msg);
+                self.shared_state
+                    .record_error(ErrorCategory::Checkpoint, msg, None, start_lsn)
+                    .await;
+                Ok(())
+            }
+            Err(e) => {
+                warn!(
+                    "Cannot check the start position against slot {}: {:#}",
+                    slot_name, e
+                );
+                Ok(())
+            }
+        }
     }
 
     /// Initialize PostgreSQL source
-    async fn init_source(&self) -> Result<PostgresSource> {
+    /// Connects the source. With `SNAPSHOT_METHOD=export` it also creates the
+    /// slot, returning the snapshot to copy when the slot is new.
+    async fn init_source(&self) -> Result<(PostgresSource, Option<ExportedSnapshot>)> {
+        let protocol_version = self.config.pgoutput_protocol_version;
+        if self.config.exports_snapshot() {
+            let (source, snapshot) = PostgresSource::new_exporting_snapshot(
+                &self.session,
+                &self.config.database_url,
+                self.config.slot_name.clone(),
+                self.config.publication_name.clone(),
+            )
+            .await?;
+            let source = source
+                .with_protocol_version(protocol_version)
+                .with_binary_tuples(self.config.binary_tuples);
+            return Ok((source, snapshot));
+        }
         let source = PostgresSource::new(
+            &self.session,
             &self.config.database_url,
             self.config.slot_name.clone(),
             self.config.publication_name.clone(),
         )
         .await?;
 
-        Ok(source)
+        Ok((
+            source
+                .with_protocol_version(protocol_version)
+                .with_binary_tuples(self.config.binary_tuples),
+            None,
+        ))
     }
 
     /// Initialize sink using trait-based connectors
-    fn init_sink(&self) -> Result<NewSinkAdapter> {
-        let core_sink = create_sink(&self.config.sink)?;
-        Ok(NewSinkAdapter::new(core_sink))
+    fn init_sink(&mut self) -> Result<NewSinkAdapter> {
+        let core_sink = match self.custom_sink.take() {
+            Some(_) if self.config.pipeline_workers > 1 => anyhow::bail!(
+                "PIPELINE_WORKERS is not supported with a custom sink, which has one connection"
+            ),
+            Some(sink) => sink,
+            None => create_sink(&self.config.sink)?,
+        };
+        Ok(self.sink_adapter(core_sink, &self.config.sink))
+    }
+
+    /// Creates the sinks of `SINK_ROUTES` and checks they can be reached.
+    async fn init_routes(&self) -> Result<Vec<Route>> {
+        let mut routes = Vec::with_capacity(self.config.sink_routes.len());
+        for route in &self.config.sink_routes {
+            let batching =
+                (route.flush_size.is_some() || route.flush_interval_ms.is_some()).then(|| {
+                    Batching {
+                        max_events: route.flush_size,
+                        max_delay: route.flush_interval_ms.map(Duration::from_millis),
+                    }
+                });
+            let adapter = self
+                .sink_adapter(create_sink(&route.sink)?, &route.sink)
+                .with_batching(batching);
+            adapter
+                .verify_http_connection()
+                .await
+                .with_context(|| format!("Sink route {} connection failed", route.name))?;
+            info!(
+                "  [OK] Sink route {} ({}) accessible",
+                route.name, route.sink.sink_type
+            );
+            routes.push(Route {
+                name: route.name.clone(),
+                tables: route.tables.clone(),
+                sink: Box::new(adapter),
+            });
+        }
+        Ok(routes)
+    }
+
+    /// The pipeline's sink: `sink` alone, or one sink per `PIPELINE_WORKERS`
+    /// worker, `sink` being the first.
+    fn init_workers(&self, sink: NewSinkAdapter) -> Result<Box<dyn Sink + Send>> {
+        if self.config.pipeline_workers == 1 {
+            return Ok(Box::new(sink));
+        }
+        let mut workers: Vec<Box<dyn Sink + Send>> = vec![Box::new(sink)];
+        for _ in 1..self.config.pipeline_workers {
+            let adapter = self.sink_adapter(create_sink(&self.config.sink)?, &self.config.sink);
+            workers.push(Box::new(adapter));
+        }
+        info!(
+            "    - sink workers: {}, sharded by {}",
+            self.config.pipeline_workers, self.config.pipeline_shard
+        );
+        Ok(Box::new(ParallelSink::new(
+            workers,
+            self.config.pipeline_shard,
+        )))
+    }
+
+    /// Wraps a sink in the legacy adapter, with the value encodings of `sink`.
+    fn sink_adapter(&self, core_sink: Box<dyn CoreSink>, sink: &SinkConfig) -> NewSinkAdapter {
+        NewSinkAdapter::new(core_sink)
+            .with_null_encoding(sink.null_encoding)
+            .with_bool_encoding(sink.bool_encoding)
+            .with_float_format(sink.float_format)
+            .with_text_normalization(self.config.text_normalization)
+            .with_batch_timeout(sink.batch_timeout)
     }
 
     /// Initialize pipeline with sink adapter
     fn init_pipeline(
         &self,
         sink: NewSinkAdapter,
+        routes: Vec<Route>,
         caps: &crate::core::SinkCapabilities,
-    ) -> (
+        relations: Vec<RelationVersion>,
+        replay_end: Option<u64>,
+    ) -> Result<(
         mpsc::Sender<crate::source::parser::CdcEvent>,
         mpsc::Receiver<u64>,
-    ) {
+    )> {
         // Job/config values always win. Sink capabilities are only fallback/advisory.
         let batch_size = if self.config.flush_size > 0 {
             self.config.flush_size
@@ -278,38 +945,153 @@ impl CdcEngine {
             flush_interval_ms, self.config.flush_interval_ms, caps.optimal_flush_interval_ms
         );
 
-        let (tx, rx) = mpsc::channel(batch_size * 2);
+        let (tx, mut rx) = mpsc::channel(batch_size * 2);
+        // Events the pipeline can't take yet go to disk, so a slow sink
+        // doesn't hold up the WAL reader
+        if let Some(spill) = &self.config.spill {
+            let queue = SpillQueue::open(spill).context("Failed to open the spill queue")?;
+            let (pipeline_tx, pipeline_rx) = mpsc::channel(batch_size * 2);
+            runtime::spawn_worker(spill::relay(
+                rx,
+                pipeline_tx,
+                queue,
+                self.shared_state.clone(),
+            ));
+            rx = pipeline_rx;
+        }
         let (feedback_tx, feedback_rx) = mpsc::channel::<u64>(100);
 
+        let sink = self.init_workers(sink)?;
+        let sink: Box<dyn Sink + Send> = if routes.is_empty() {
+            sink
+        } else {
+            Box::new(
+                SinkRouter::new(sink, routes)
+                    .with_pipeline_batching(batch_size, Duration::from_millis(flush_interval_ms)),
+            )
+        };
+        // Sinks asking for smaller batches get them; the router holds rows
+        // back for the routes asking for larger ones
+        let (batch_size, flush_interval_ms) = match sink.batching() {
+            Some(batching) => {
+                let (events, delay) =
+                    batching.resolve(batch_size, Duration::from_millis(flush_interval_ms));
+                let effective = (
+                    events.min(batch_size),
+                    (delay.as_millis() as u64).min(flush_interval_ms),
+                );
+                info!(
+                    "    - sink batching: {} events, {}ms",
+                    effective.0, effective.1
+                );
+                effective
+            }
+            None => (batch_size, flush_interval_ms),
+        };
         let pipeline = Pipeline::new(
             rx,
-            Box::new(sink),
+            sink,
             batch_size,
             Duration::from_millis(flush_interval_ms),
         )
         .with_feedback_channel(feedback_tx)
-        .with_shared_state(self.shared_state.clone());
+        .with_shared_state(self.shared_state.clone())
+        .with_source_catalog(SourceCatalog::new(
+            &self.session,
+            &self.config.database_url,
+            self.config.column_transforms.clone(),
+        ))
+        .with_freshness_sla(self.config.freshness_sla.clone())
+        .with_probe_table(self.config.probe.as_ref().map(|p| p.table.clone()))
+        .with_lineage(LineageExport::from_config(&self.config))
+        .with_dead_letter_queue(
+            self.config
+                .dead_letter
+                .as_ref()
+                .map(|dlq| {
+                    DeadLetterQueue::new(
+                        dlq,
+                        self.config.sink.request_timeout,
+                        &self.config.sink.http,
+                    )
+                })
+                .transpose()?,
+        )
+        .with_retry_policy(RetryPolicy::from(&self.config.sink_retry))
+        .with_paused_table_probes(self.config.paused_table_probe)
+        .with_pause_notifier(
+            self.config
+                .paused_table_webhook_url
+                .as_deref()
+                .map(|url| PauseNotifier::new(url, &self.config.sink.http))
+                .transpose()?,
+        )
+        .with_table_filter(self.config.table_filter.clone())
+        .with_commit_boundaries(self.config.flush_on_commit.then_some(
+            // A transaction cut in two would be replayed whole
+            if self.config.exactly_once {
+                usize::MAX
+            } else {
+                self.config.max_transaction_events
+            },
+        ))
+        .with_replay_end(replay_end)
+        .with_column_transforms(self.config.column_transforms.clone())
+        .with_change_suppression(self.config.change_suppression.clone())
+        .with_toast_hydration(
+            self.config.toast_hydration,
+            &self.session,
+            &self.config.database_url,
+        )
+        .with_relations(relations);
+        let pipeline = match &self.config.wal_retention {
+            Some(guard) if guard.action == WalRetentionAction::ArchiveTee => {
+                pipeline.with_archive(WalArchive::new(&guard.archive_dir))
+            }
+            _ => pipeline,
+        };
+
+        // Sink encoding runs here, off the WAL thread when one is configured
+        runtime::spawn_worker(pipeline.run());
 
-        tokio::spawn(pipeline.run());
+        Ok((tx, feedback_rx))
+    }
 
-        (tx, feedback_rx)
+    /// Holds streamed transactions as `STREAMED_TXN_MEMORY_MB` and
+    /// `STREAMED_TXN_SPILL_DIR` say.
+    fn transaction_streams(&self) -> TransactionStreams {
+        TransactionStreams::new(
+            self.config.streamed_txn_memory_bytes,
+            &self.config.streamed_txn_spill_dir,
+        )
     }
 
     /// Main replication loop
-    async fn run_main_loop<S>(
+    async fn run_main_loop(
         &self,
-        mut replication_stream: S,
+        mut source: PostgresSource,
+        mut replication_stream: ReplicationStream,
         tx: mpsc::Sender<crate::source::parser::CdcEvent>,
         mut feedback_rx: mpsc::Receiver<u64>,
-        _start_lsn: u64,
-    ) -> Result<()>
-    where
-        S: StreamExt<Item = Result<bytes::Bytes, tokio_postgres::Error>>
-            + SinkExt<bytes::Bytes>
-            + Unpin,
-        S::Error: std::error::Error + Send + Sync + 'static,
-    {
+        mut start_cutoff: Option<StartCutoff>,
+    ) -> Result<()> {
         let mut shutdown_rx = self.shared_state.shutdown_tx.subscribe();
+        // Stream blocks of in-progress transactions (PGOUTPUT_PROTOCOL_VERSION >= 2)
+        if self.config.pgoutput_protocol_version > 1 {
+            TransactionStreams::clear_spill_dir(Path::new(&self.config.streamed_txn_spill_dir))?;
+        }
+        let mut streams = self.transaction_streams();
+        // PARSE_WORKERS > 1: decode frames on parser tasks instead of inline
+        let mut parse_stage = (self.config.parse_workers > 1).then(|| {
+            ParseStage::spawn(
+                self.config.parse_workers,
+                tx.clone(),
+                self.shared_state.clone(),
+                self.config.flush_size,
+                start_cutoff.take(),
+                self.transaction_streams(),
+            )
+        });
         // Subscribe to on-demand snapshot trigger (fired by StartSnapshot gRPC RPC)
         let mut snapshot_trigger_rx = self.shared_state.subscribe_snapshot_trigger();
         let mut iteration = 0u64;
@@ -320,7 +1102,7 @@ impl CdcEngine {
             // 1. Check state changes every 256 iterations to reduce overhead
             // With ~287 events/s, this checks state ~1x/second instead of 287x/second
             if iteration & 0xFF == 0 {
-                if let Some(flow) = self.check_state_control_sync(&tx) {
+                if let Some(flow) = self.check_state_control_sync(&tx, parse_stage.as_ref()) {
                     match flow {
                         ControlFlow::Break => break,
                         ControlFlow::Continue => {
@@ -349,9 +1131,10 @@ impl CdcEngine {
                         // Reset trigger so it doesn't fire again
                         let _ = self.shared_state.snapshot_trigger.send(false);
                         let snap_config = Arc::new(self.config.clone());
+                        let snap_session = self.session.clone();
                         let snap_state = self.shared_state.clone();
-                        tokio::spawn(async move {
-                            match snapshot::run_snapshot(snap_config, snap_state.clone()).await {
+                        runtime::spawn_worker(async move {
+                            match snapshot::run_snapshot(snap_config, snap_session, snap_state.clone()).await {
                                 Ok(()) => {
                                     snap_state.set_snapshot_active(false);
                                     info!("On-demand snapshot completed successfully");
@@ -359,6 +1142,9 @@ impl CdcEngine {
                                 Err(e) => {
                                     snap_state.set_snapshot_active(false);
                                     snap_state.set_snapshot_error(Some(format!("{}", e))).await;
+                                    snap_state
+                                        .record_error(ErrorCategory::Snapshot, format!("{:#}", e), None, 0)
+                                        .await;
                                     error!("On-demand snapshot worker error: {}", e);
                                 }
                             }
@@ -375,12 +1161,30 @@ impl CdcEngine {
                                     msg,
                                     &tx,
                                     &mut replication_stream,
+                                    &mut start_cutoff,
+                                    &mut streams,
+                                    parse_stage.as_ref(),
                                 ).await?;
                             }
                         }
                         Some(Err(e)) => {
-                            error!("Replication stream error: {}", e);
-                            break;
+                            let class = pg_error::record(&e);
+                            error!("Replication stream error ({}): {}", class, e);
+                            self.shared_state
+                                .record_error(
+                                    ErrorCategory::Source,
+                                    format!("Replication stream error ({}): {}", class, e),
+                                    None,
+                                    self.shared_state.current_lsn(),
+                                )
+                                .await;
+                            if !class.is_retryable() {
+                                break;
+                            }
+                            // Keeps the slot on failure: returning skips cleanup
+                            replication_stream = self.reconnect_replication(&mut source, class).await?;
+                            // A stream block cut off is not continued on the new stream
+                            streams = self.transaction_streams();
                         }
                         None => {
                             warn!("Replication stream ended");
@@ -389,6 +1193,12 @@ impl CdcEngine {
                     }
                 }
 
+                // Parser tasks stopped (parse error or pipeline gone)
+                result = parse_stage_finished(&mut parse_stage) => {
+                    result?;
+                    break;
+                }
+
                 // Checkpoint feedback
                 Some(confirmed_lsn) = feedback_rx.recv() => {
                     self.handle_checkpoint_feedback(
@@ -399,25 +1209,131 @@ impl CdcEngine {
             }
         }
 
-        // Cleanup PostgreSQL resources (drop replication slot) - unless skip_slot_cleanup is set
+        self.release_slot().await;
+
+        info!("CDC shutdown complete");
+        Ok(())
+    }
+
+    /// Cleanup PostgreSQL resources (drop replication slot) - unless skip_slot_cleanup is set
+    async fn release_slot(&self) {
         if self.shared_state.should_skip_slot_cleanup() {
             info!("[SKIP] Skipping slot cleanup (upgrade/restart mode)");
-        } else if let Err(e) =
-            setup::cleanup_postgres_resources(&self.config.database_url, &self.config.slot_name)
-                .await
+        } else if let Err(e) = setup::cleanup_postgres_resources(
+            &self.session,
+            &self.config.database_url,
+            &self.config.slot_name,
+        )
+        .await
         {
             warn!("Cleanup warning: {}", e);
             // Non-fatal - continue shutdown
         }
+    }
 
-        info!("CDC shutdown complete");
-        Ok(())
+    /// Copies the slot's exported snapshot through the pipeline and returns
+    /// its consistent point, where streaming must start.
+    async fn copy_exported_snapshot(
+        &self,
+        exported: &ExportedSnapshot,
+        tx: &mpsc::Sender<CdcEvent>,
+        feedback_rx: &mut mpsc::Receiver<u64>,
+    ) -> Result<u64> {
+        self.shared_state
+            .set_stage(Stage::Snapshot, "Copying exported snapshot")
+            .await;
+        let copy = snapshot::run_export_snapshot(
+            &self.config,
+            &self.session,
+            exported,
+            tx,
+            &self.shared_state,
+        );
+        tokio::pin!(copy);
+        // Batches of the copy confirm the consistent point, which the new
+        // slot is already at; nothing to checkpoint
+        let result = loop {
+            tokio::select! {
+                result = &mut copy => break result,
+                Some(_) = feedback_rx.recv() => {}
+            }
+        };
+        if let Err(e) = result {
+            self.shared_state.set_snapshot_active(false);
+            self.shared_state
+                .set_snapshot_error(Some(format!("{}", e)))
+                .await;
+            self.shared_state
+                .record_error(ErrorCategory::Snapshot, format!("{:#}", e), None, 0)
+                .await;
+            // The exported snapshot dies with the replication connection; the
+            // next start recreates the slot and copies again
+            return Err(e.context("exported snapshot copy failed"));
+        }
+
+        self.shared_state.update_lsn(exported.consistent_point);
+        Ok(exported.consistent_point)
+    }
+
+    /// Reopens the replication stream after a retryable error broke it,
+    /// resuming from the last confirmed LSN. PostgreSQL re-sends the changes
+    /// the pipeline had not confirmed yet, which the sink may see twice
+    /// (at-least-once). Gives up after `MAX_RECONNECT_ATTEMPTS`.
+    async fn reconnect_replication(
+        &self,
+        source: &mut PostgresSource,
+        mut class: PgErrorClass,
+    ) -> Result<ReplicationStream> {
+        for attempt in 1..=MAX_RECONNECT_ATTEMPTS {
+            let delay = class.delay(attempt);
+            let detail = format!(
+                "Reconnecting to PostgreSQL after {} (attempt {}/{})",
+                class, attempt, MAX_RECONNECT_ATTEMPTS
+            );
+            warn!("{}, waiting {:?}", detail, delay);
+            self.shared_state.set_stage(Stage::Setup, &detail).await;
+            tokio::time::sleep(delay).await;
+
+            let resume_lsn = self.shared_state.confirmed_lsn();
+            let reopened = match self.init_source().await {
+                Ok(new_source) => self
+                    .start_replication(&new_source, resume_lsn)
+                    .await
+                    .map(|stream| (new_source, stream)),
+                Err(e) => Err(e),
+            };
+            let err = match reopened {
+                Ok((new_source, stream)) => {
+                    *source = new_source;
+                    info!(
+                        "Replication stream reopened at LSN {}",
+                        format_pg_lsn(resume_lsn)
+                    );
+                    self.shared_state.set_stage(Stage::Cdc, "Replicating").await;
+                    return Ok(Box::pin(stream));
+                }
+                Err(e) => e,
+            };
+            // The old walsender keeps the slot until PostgreSQL notices it is gone
+            if err.downcast_ref::<SlotInUseError>().is_some() {
+                continue;
+            }
+            match pg_error::find(&err).map(pg_error::record) {
+                Some(next) if next.is_retryable() => class = next,
+                _ => return Err(err.context("failed to reopen the replication stream")),
+            }
+        }
+        anyhow::bail!(
+            "replication stream not reopened after {} attempts",
+            MAX_RECONNECT_ATTEMPTS
+        )
     }
 
     /// Check CDC state (Pause/Stop/Draining) - Synchronous
     fn check_state_control_sync(
         &self,
         tx: &mpsc::Sender<crate::source::parser::CdcEvent>,
+        parse_stage: Option<&ParseStage>,
     ) -> Option<ControlFlow> {
         let current_state = self.shared_state.state();
 
@@ -427,8 +1343,10 @@ impl CdcEngine {
                 Some(ControlFlow::Break)
             }
             CdcState::Draining => {
-                // Check if channel is empty
-                if tx.capacity() == self.config.flush_size * 2 {
+                // Check if channel and parser queue are empty
+                if tx.capacity() == self.config.flush_size * 2
+                    && parse_stage.map_or(true, |stage| stage.is_idle())
+                {
                     info!("CDC drained. Exiting gracefully.");
                     self.shared_state.set_state(CdcState::Stopped);
                     Some(ControlFlow::Break)
@@ -450,6 +1368,9 @@ impl CdcEngine {
         msg: WalMessage,
         tx: &mpsc::Sender<crate::source::parser::CdcEvent>,
         replication_stream: &mut S,
+        start_cutoff: &mut Option<StartCutoff>,
+        streams: &mut TransactionStreams,
+        parse_stage: Option<&ParseStage>,
     ) -> Result<u64>
     where
         S: SinkExt<bytes::Bytes> + Unpin,
@@ -457,14 +1378,47 @@ impl CdcEngine {
     {
         match msg {
             WalMessage::XLogData { lsn, data } => {
-                handle_xlog_data(data, lsn, tx, &self.shared_state, self.config.flush_size).await?;
+                match parse_stage {
+                    Some(stage) => {
+                        self.shared_state.update_lsn(lsn);
+                        let in_block = streams.in_block(&data);
+                        stage.push(lsn, data, in_block).await?;
+                    }
+                    None => {
+                        handle_xlog_data(
+                            data,
+                            lsn,
+                            tx,
+                            &self.shared_state,
+                            self.config.flush_size,
+                            start_cutoff,
+                            streams,
+                        )
+                        .await?
+                    }
+                }
                 Ok(lsn)
             }
             WalMessage::KeepAlive {
                 lsn,
+                server_time,
                 reply_requested,
             } => {
-                handle_keepalive(lsn, reply_requested, replication_stream).await?;
+                clock::observe_server_time(server_time);
+                // Acknowledging the server's position moves the slot's
+                // confirmed_flush_lsn. Only do it when everything received is
+                // confirmed, and checkpoint it first: the slot is never ahead
+                // of the checkpoint (see `start_check`)
+                let confirmed = self.shared_state.confirmed_lsn();
+                if reply_requested
+                    && lsn > confirmed
+                    && confirmed >= self.shared_state.current_lsn()
+                {
+                    self.handle_checkpoint_feedback(lsn, replication_stream)
+                        .await?;
+                } else {
+                    handle_keepalive(confirmed, reply_requested, replication_stream).await?;
+                }
                 Ok(lsn)
             }
             WalMessage::Unknown(tag) => {
@@ -491,10 +1445,10 @@ impl CdcEngine {
         // 3. WAL data is gone → permanent data loss
 
         // 1. Save checkpoint to persistent storage
-        let state_store = self.state_store.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("state_store must be initialized before checkpoint feedback")
+        let store = self.checkpoint_store.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("checkpoint store must be initialized before checkpoint feedback")
         })?;
-        if let Err(e) = state_store
+        if let Err(e) = store
             .save_checkpoint(&self.config.slot_name, confirmed_lsn)
             .await
         {
@@ -503,6 +1457,14 @@ impl CdcEngine {
                 confirmed_lsn, e
             );
             error!("NOT confirming to PostgreSQL to prevent data loss");
+            self.shared_state
+                .record_error(
+                    ErrorCategory::Checkpoint,
+                    format!("Failed to save checkpoint: {:#}", e),
+                    None,
+                    confirmed_lsn,
+                )
+                .await;
             // Return error to halt replication loop
             return Err(e);
         }
@@ -510,11 +1472,29 @@ impl CdcEngine {
         // 2. Update SharedState (after successful persistence)
         self.shared_state.confirm_lsn(confirmed_lsn);
 
+        // Keep the stored schema cache in step; a failure only costs decoding
+        // until PostgreSQL re-sends the Relation messages after a restart
+        let (schema_version, relations) = self.shared_state.relation_versions().await;
+        if let Err(e) = store
+            .save_relations(&self.config.slot_name, schema_version, &relations)
+            .await
+        {
+            warn!("Failed to save schema cache: {}", e);
+        }
+
         // 3. Confirm to PostgreSQL (only after checkpoint is safely persisted)
         let status = build_standby_status_update(confirmed_lsn);
         if let Err(e) = replication_stream.send(status).await {
             error!("Failed to send status update to PostgreSQL: {}", e);
             error!("Checkpoint saved but not confirmed - may cause duplicate events on restart");
+            self.shared_state
+                .record_error(
+                    ErrorCategory::Checkpoint,
+                    format!("Failed to confirm checkpoint to PostgreSQL: {}", e),
+                    None,
+                    confirmed_lsn,
+                )
+                .await;
             return Err(anyhow::Error::new(e));
         }
 
@@ -523,6 +1503,28 @@ impl CdcEngine {
     }
 }
 
+/// Backend PID currently streaming from the slot, if any.
+async fn slot_holder_pid(client: &tokio_postgres::Client, slot_name: &str) -> Result<Option<u32>> {
+    let row = client
+        .query_opt(
+            "SELECT active_pid FROM pg_replication_slots WHERE slot_name = $1",
+            &[&slot_name],
+        )
+        .await
+        .context("failed to query pg_replication_slots")?;
+    Ok(row
+        .and_then(|r| r.get::<_, Option<i32>>(0))
+        .map(|pid| pid as u32))
+}
+
+/// Resolves when the parser tasks stop; never without a parse stage.
+async fn parse_stage_finished(stage: &mut Option<ParseStage>) -> Result<()> {
+    match stage {
+        Some(stage) => stage.finished().await,
+        None => std::future::pending().await,
+    }
+}
+
 /// Flow control for the loop
 enum ControlFlow {
     Continue,
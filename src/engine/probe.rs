@@ -0,0 +1,130 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Read-your-writes probe.
+//!
+//! Replication lag is measured from commit timestamps, which says nothing
+//! when no transaction commits, and nothing about the part of the trip after
+//! dbmazz reads the change. The probe closes that gap: every
+//! `PROBE_INTERVAL_SECS` it writes a marker row to `PROBE_TABLE` on the
+//! source, stamped with the time it was written. The table is replicated like
+//! any other, and once the sink has taken the batch carrying the row the
+//! pipeline reports it. The time in between is the probe latency, published
+//! as `dbmazz_probe_latency_ms`.
+//!
+//! One row is in flight at a time; while it is, its age counts as the
+//! latency, so a stalled pipeline shows up even though nothing arrives. Over
+//! `PROBE_ALERT_SECS` the probe logs an error and records it in
+//! `GetRecentErrors`, once per breach.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio_postgres::Client;
+use tracing::{error, info, warn};
+
+use super::setup;
+use crate::config::ProbeConfig;
+use crate::grpc::state::{unix_ms, ErrorCategory, SharedState};
+use crate::source::session::PgSession;
+use crate::utils::quote_ident;
+
+/// Writes probe rows until the engine shuts down. Spawned by the engine when
+/// `PROBE_INTERVAL_SECS` is set.
+pub(super) async fn run_probe(
+    probe: ProbeConfig,
+    session: PgSession,
+    database_url: String,
+    state: Arc<SharedState>,
+) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut interval = tokio::time::interval(probe.interval);
+    let mut client: Option<Client> = None;
+    let alert_ms = probe.alert_after.as_millis() as u64;
+
+    info!(
+        "Read-your-writes probe started: {} every {}s",
+        probe.table,
+        probe.interval.as_secs()
+    );
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                if *shutdown_rx.borrow() {
+                    return;
+                }
+            }
+            _ = interval.tick() => {}
+        }
+
+        let latency_ms = state.probe_latency_ms();
+        let breached = latency_ms > alert_ms;
+        if state.set_probe_breached(breached) {
+            if breached {
+                error!(
+                    "[PROBE] End-to-end latency {}ms is over {}ms",
+                    latency_ms, alert_ms
+                );
+                state
+                    .record_error(
+                        ErrorCategory::Probe,
+                        format!(
+                            "probe row took {}ms or more to reach the sink, threshold is {}ms",
+                            latency_ms, alert_ms
+                        ),
+                        Some(&probe.table),
+                        0,
+                    )
+                    .await;
+            } else {
+                info!(
+                    "[PROBE] End-to-end latency back under {}ms: {}ms",
+                    alert_ms, latency_ms
+                );
+            }
+        }
+
+        // The next row waits until the last one has arrived
+        if state.probe_in_flight().is_some() {
+            continue;
+        }
+
+        // Keep one connection open; reconnect after any failure
+        if client.as_ref().map_or(true, |c| c.is_closed()) {
+            match setup::postgres::create_postgres_client(&session, &database_url).await {
+                Ok(c) => client = Some(c),
+                Err(e) => {
+                    warn!("Probe: cannot connect to PostgreSQL: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some(pg) = client.as_ref() else { continue };
+
+        let sent_ms = unix_ms();
+        match write_probe(pg, &probe.table, sent_ms).await {
+            Ok(()) => state.probe_sent(sent_ms),
+            Err(e) => {
+                warn!("Probe: {:#}", e);
+                client = None;
+            }
+        }
+    }
+}
+
+/// Upserts the probe row, so the table keeps a single row.
+async fn write_probe(client: &Client, table: &str, sent_ms: u64) -> Result<()> {
+    client
+        .execute(
+            &format!(
+                "INSERT INTO {} (id, sent_ms, sent_at) VALUES (1, $1, now()) \
+                 ON CONFLICT (id) DO UPDATE SET sent_ms = EXCLUDED.sent_ms, sent_at = EXCLUDED.sent_at",
+                quote_ident(table)
+            ),
+            &[&(sent_ms as i64)],
+        )
+        .await
+        .with_context(|| format!("failed to write the probe row to {}", table))?;
+    Ok(())
+}
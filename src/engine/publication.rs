@@ -0,0 +1,394 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Publication change watcher.
+//!
+//! PostgreSQL decides which tables the slot streams from the publications, so
+//! `ALTER PUBLICATION ... ADD TABLE / DROP TABLE` takes effect mid-stream.
+//! This watcher polls `pg_publication_tables` every
+//! `PUBLICATION_CHECK_INTERVAL_SECS`, and right away when a Relation message
+//! names a table it has not seen, then:
+//!
+//! - added tables: prepares the sink and, with `SNAPSHOT_NEW_TABLES`, copies
+//!   their existing rows while their changes stream
+//! - removed tables: the pipeline stops routing them once it reaches the WAL
+//!   position where the removal was noticed
+//!
+//! `ReloadConfig` with a table list changes the publication the other way
+//! round: the watcher adds the tables missing from it (checked and given
+//! `REPLICA IDENTITY FULL` as at startup, in the publication
+//! `PUBLICATION_<NAME>_TABLES` assigns them to), then handles the difference
+//! like any other publication change. The tables not listed are only dropped
+//! with `drop_unlisted_tables`; otherwise they are kept, with a warning.
+//! PostgreSQL sends a Relation message for an added table before its first
+//! change, which registers it in the schema cache and routes it.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio_postgres::Client;
+use tracing::{error, info, warn};
+
+use super::setup::postgres::PostgresSetup;
+use super::setup::{self, SetupManager};
+use super::snapshot;
+use crate::config::{Config, Publication, SinkType};
+use crate::grpc::state::{ErrorCategory, SharedState, TableListRequest};
+use crate::runtime;
+use crate::source::parser::CdcMessage;
+use crate::source::session::PgSession;
+use crate::utils::parse_pg_lsn;
+
+/// Tables added to and removed from the publication between two checks,
+/// each sorted.
+pub(super) fn diff(
+    known: &BTreeSet<String>,
+    current: &BTreeSet<String>,
+) -> (Vec<String>, Vec<String>) {
+    let added = current.difference(known).cloned().collect();
+    let removed = known.difference(current).cloned().collect();
+    (added, removed)
+}
+
+/// `schema.table` form of a `TABLES` entry; bare names are in `public`.
+fn qualified(table: &str) -> String {
+    if table.contains('.') {
+        table.to_string()
+    } else {
+        format!("public.{}", table)
+    }
+}
+
+/// Watches the publication until the engine shuts down. Spawned by the
+/// engine unless `PUBLICATION_CHECK_INTERVAL_SECS=0`. `custom_sink` means the
+/// destination is not prepared or snapshotted by dbmazz.
+pub(super) async fn run_publication_watcher(
+    config: Config,
+    session: PgSession,
+    check_interval: Duration,
+    custom_sink: bool,
+    state: Arc<SharedState>,
+) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut handover_rx = state.subscribe_handover();
+    let mut requested_rx = state.subscribe_requested_tables();
+    let mut requested: Option<TableListRequest> = None;
+    let mut interval = tokio::time::interval(check_interval);
+    let mut client: Option<Client> = None;
+    // The first check is the baseline: setup has just reconciled the publication
+    let mut known: Option<BTreeSet<String>> = None;
+    let mut seen_schema_version = state.schema_version.load(Ordering::Relaxed);
+    let mut pending_snapshot: Vec<String> = Vec::new();
+
+    info!(
+        "Publication watcher started: {} checked every {}s",
+        config.publication_name,
+        check_interval.as_secs()
+    );
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                if *shutdown_rx.borrow() {
+                    return;
+                }
+            }
+            _ = interval.tick() => {}
+            Ok(()) = requested_rx.changed() => {
+                requested = requested_rx.borrow_and_update().clone();
+            }
+            Ok(()) = handover_rx.changed() => {
+                // Only a Relation message for a table we don't know is worth
+                // an early check
+                let version = state.schema_version.load(Ordering::Relaxed);
+                if version == seen_schema_version {
+                    continue;
+                }
+                seen_schema_version = version;
+                let Some(tables) = known.as_ref() else { continue };
+                let (_, relations) = state.relations().await;
+                let unknown = relations.iter().any(|relation| {
+                    matches!(relation, CdcMessage::Relation { namespace, name, .. }
+                        if !tables.contains(&format!("{}.{}", namespace, name)))
+                });
+                if !unknown {
+                    continue;
+                }
+            }
+        }
+
+        // Keep one connection open; reconnect after any failure
+        if client.as_ref().map_or(true, |c| c.is_closed()) {
+            match setup::postgres::create_postgres_client(&session, &config.database_url).await {
+                Ok(c) => client = Some(c),
+                Err(e) => {
+                    warn!("Publication watcher: cannot connect to PostgreSQL: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some(pg) = client.as_ref() else { continue };
+
+        // Applied once the baseline is known, so the tables it adds are
+        // handled as added below
+        if known.is_some() {
+            if let Some(request) = requested.take() {
+                if let Err(e) = apply_table_list(pg, &config, &request).await {
+                    error!("Publication watcher: {:#}", e);
+                    state
+                        .record_error(ErrorCategory::Setup, format!("{:#}", e), None, 0)
+                        .await;
+                }
+            }
+        }
+
+        let current = match query_publication_tables(pg, &config.publications).await {
+            Ok(tables) => tables,
+            Err(e) => {
+                warn!("Publication watcher: {:#}", e);
+                client = None;
+                continue;
+            }
+        };
+        let Some(previous) = known.as_ref() else {
+            known = Some(current);
+            continue;
+        };
+        let (mut added, mut removed) = diff(previous, &current);
+        // Tables left out by TABLE_INCLUDE/TABLE_EXCLUDE are not replicated either way
+        added.retain(|table| config.table_filter.allows(table));
+        removed.retain(|table| config.table_filter.allows(table));
+
+        if !removed.is_empty() {
+            // Changes committed before the removal are still streaming in
+            let lsn = match current_wal_lsn(pg).await {
+                Ok(lsn) => lsn,
+                Err(e) => {
+                    warn!("Publication watcher: {:#}", e);
+                    client = None;
+                    continue;
+                }
+            };
+            warn!(
+                "Publication {}: table(s) removed: {}. Their changes are no longer replicated",
+                config.publication_name,
+                removed.join(", ")
+            );
+            state
+                .config
+                .write()
+                .await
+                .tables
+                .retain(|table| !removed.contains(&qualified(table)));
+            pending_snapshot.retain(|table| !removed.contains(table));
+            state.remove_tables(removed, lsn);
+        }
+
+        if !added.is_empty() {
+            info!(
+                "Publication {}: table(s) added: {}",
+                config.publication_name,
+                added.join(", ")
+            );
+            state.config.write().await.tables.extend(added.clone());
+            if !custom_sink {
+                prepare_sink(&config, &session, &added, &state).await;
+            }
+            if !config.snapshot_new_tables {
+                info!("SNAPSHOT_NEW_TABLES=false: only changes from now on are replicated");
+            } else if custom_sink || config.sink.sink_type != SinkType::StarRocks {
+                warn!(
+                    "SNAPSHOT_NEW_TABLES ignored: snapshots are not supported with this sink, \
+                     only changes from now on are replicated"
+                );
+            } else {
+                pending_snapshot.extend(added);
+            }
+        }
+        known = Some(current);
+
+        // A running snapshot keeps its table list; retry on the next check
+        if !pending_snapshot.is_empty() && !state.is_snapshot_active() {
+            let mut snap_config = config.clone();
+            snap_config.tables = std::mem::take(&mut pending_snapshot);
+            spawn_snapshot(snap_config, session.clone(), state.clone());
+        }
+    }
+}
+
+/// Adds the tables of a `ReloadConfig` list missing from the publications
+/// and, with `drop_unlisted`, drops the ones not listed. Tables left out by
+/// `TABLE_INCLUDE`/`TABLE_EXCLUDE` are neither added nor dropped, and the
+/// probe table stays.
+async fn apply_table_list(
+    client: &Client,
+    config: &Config,
+    request: &TableListRequest,
+) -> Result<()> {
+    let mut wanted: BTreeSet<String> = BTreeSet::new();
+    for table in request.tables.iter().map(|t| qualified(t)) {
+        if config.table_filter.allows(&table) {
+            wanted.insert(table);
+        } else {
+            warn!(
+                "ReloadConfig: {} is left out by TABLE_INCLUDE/TABLE_EXCLUDE, not adding it",
+                table
+            );
+        }
+    }
+    if let Some(probe) = &config.probe {
+        wanted.insert(probe.table.clone());
+    }
+    let current: BTreeSet<String> = query_publication_tables(client, &config.publications)
+        .await?
+        .into_iter()
+        .filter(|table| config.table_filter.allows(table))
+        .collect();
+    let (added, unlisted) = diff(&current, &wanted);
+    let removed = if request.drop_unlisted {
+        unlisted
+    } else {
+        if !unlisted.is_empty() {
+            warn!(
+                "ReloadConfig: keeping {} in the publication; set drop_unlisted_tables to drop them",
+                unlisted.join(", ")
+            );
+        }
+        Vec::new()
+    };
+
+    if !added.is_empty() {
+        info!(
+            "ReloadConfig: adding {} to the publication",
+            added.join(", ")
+        );
+        let mut setup_config = config.clone();
+        setup_config.tables = added;
+        PostgresSetup::new(client, &setup_config)
+            .add_tables()
+            .await
+            .context("failed to add tables to the publication")?;
+    }
+    if !removed.is_empty() {
+        info!(
+            "ReloadConfig: dropping {} from the publication",
+            removed.join(", ")
+        );
+        PostgresSetup::new(client, config)
+            .remove_tables(&removed)
+            .await
+            .context("failed to drop tables from the publication")?;
+    }
+    Ok(())
+}
+
+/// Creates or checks the sink tables of `tables`, as setup does at startup.
+async fn prepare_sink(
+    config: &Config,
+    session: &PgSession,
+    tables: &[String],
+    state: &SharedState,
+) {
+    let mut setup_config = config.clone();
+    setup_config.tables = tables.to_vec();
+    // The export files describe every table; don't overwrite them with the new ones only
+    setup_config.schema_export_path = None;
+    setup_config.lineage_export_path = None;
+    if let Err(e) = SetupManager::new(setup_config, session.clone())
+        .run_sink_only()
+        .await
+    {
+        error!(
+            "Publication watcher: sink setup for {} failed: {}",
+            tables.join(", "),
+            e
+        );
+        state
+            .record_error(
+                ErrorCategory::Setup,
+                format!("sink setup for {} failed: {}", tables.join(", "), e),
+                None,
+                0,
+            )
+            .await;
+    }
+}
+
+/// Snapshots the tables of `config` alongside the stream.
+fn spawn_snapshot(config: Config, session: PgSession, state: Arc<SharedState>) {
+    let tables = config.tables.join(", ");
+    info!("Snapshot of new table(s) {} spawned", tables);
+    // Claimed here so the next check doesn't start a second one
+    state.set_snapshot_active(true);
+    runtime::spawn_worker(async move {
+        match snapshot::run_snapshot(Arc::new(config), session, state.clone()).await {
+            Ok(()) => {
+                state.set_snapshot_active(false);
+                info!("Snapshot of new table(s) {} completed", tables);
+            }
+            Err(e) => {
+                state.set_snapshot_active(false);
+                state.set_snapshot_error(Some(format!("{}", e))).await;
+                state
+                    .record_error(ErrorCategory::Snapshot, format!("{:#}", e), None, 0)
+                    .await;
+                error!("Snapshot of new table(s) {} failed: {}", tables, e);
+            }
+        }
+    });
+}
+
+/// Tables of any of `publications`; a table in two of them is streamed once.
+async fn query_publication_tables(
+    client: &Client,
+    publications: &[Publication],
+) -> Result<BTreeSet<String>> {
+    let names: Vec<&str> = publications.iter().map(|p| p.name.as_str()).collect();
+    let rows = client
+        .query(
+            "SELECT DISTINCT schemaname || '.' || tablename FROM pg_publication_tables \
+             WHERE pubname = ANY($1)",
+            &[&names],
+        )
+        .await
+        .context("failed to query pg_publication_tables")?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+async fn current_wal_lsn(client: &Client) -> Result<u64> {
+    let row = client
+        .query_one("SELECT pg_current_wal_lsn()::text", &[])
+        .await
+        .context("failed to read the current WAL position")?;
+    let lsn: String = row.get(0);
+    parse_pg_lsn(&lsn).ok_or_else(|| anyhow::anyhow!("failed to parse WAL LSN '{}'", lsn))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let known = tables(&["public.orders", "public.users"]);
+        let (added, removed) = diff(&known, &tables(&["public.users", "sales.invoices"]));
+        assert_eq!(added, vec!["sales.invoices"]);
+        assert_eq!(removed, vec!["public.orders"]);
+
+        let (added, removed) = diff(&known, &known);
+        assert!(added.is_empty() && removed.is_empty());
+    }
+
+    #[test]
+    fn test_qualified() {
+        assert_eq!(qualified("orders"), "public.orders");
+        assert_eq!(qualified("sales.orders"), "sales.orders");
+    }
+}
@@ -0,0 +1,505 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use tokio_postgres::Client;
+use tracing::{info, warn};
+
+use super::error::SetupError;
+use super::postgres::pg_error_message;
+use crate::config::{ColumnTransform, ColumnTransforms};
+use crate::source::pg_types;
+
+/// A source column as described by the PostgreSQL catalog.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceColumn {
+    pub name: String,
+    /// `information_schema.columns.data_type` (e.g. `integer`, `character varying`)
+    pub data_type: String,
+    /// `pg_type` OID, used to map the column to sink types
+    pub type_oid: u32,
+    pub nullable: bool,
+    /// `COMMENT ON COLUMN` text, if any
+    pub comment: Option<String>,
+}
+
+/// A replicated source table with its columns and documentation.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceTable {
+    pub schema: String,
+    pub name: String,
+    /// `COMMENT ON TABLE` text, if any
+    pub comment: Option<String>,
+    /// Primary key columns, in key order
+    pub primary_key: Vec<String>,
+    pub columns: Vec<SourceColumn>,
+    pub indexes: Vec<SourceIndex>,
+    pub foreign_keys: Vec<SourceForeignKey>,
+}
+
+/// An index on a source table.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceIndex {
+    pub name: String,
+    /// Plain key columns in index order (expression entries are omitted)
+    pub columns: Vec<String>,
+    pub unique: bool,
+    pub primary: bool,
+    /// Partial (`WHERE`) or expression index: cannot identify rows by itself
+    pub partial_or_expression: bool,
+}
+
+/// A foreign key constraint, exported as a modelling hint.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceForeignKey {
+    pub name: String,
+    pub columns: Vec<String>,
+    /// `schema.table` of the referenced table
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+}
+
+/// Where a sink key came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyOrigin {
+    /// `SINK_KEY_COLUMNS`
+    Override,
+    PrimaryKey,
+    /// A unique index over NOT NULL columns (name of the index)
+    UniqueIndex(String),
+}
+
+impl std::fmt::Display for KeyOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyOrigin::Override => write!(f, "SINK_KEY_COLUMNS"),
+            KeyOrigin::PrimaryKey => write!(f, "primary key"),
+            KeyOrigin::UniqueIndex(name) => write!(f, "unique index {}", name),
+        }
+    }
+}
+
+/// Columns that identify a row in the sink (StarRocks primary key model
+/// columns, SQLite `PRIMARY KEY`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkKey {
+    pub columns: Vec<String>,
+    pub origin: KeyOrigin,
+}
+
+impl SourceTable {
+    /// `schema.name`
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.schema, self.name)
+    }
+
+    pub fn column(&self, name: &str) -> Option<&SourceColumn> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Looks up a per-table setting keyed by `schema.table`, falling back to
+    /// the bare table name.
+    pub fn table_setting<'m, T>(&self, settings: &'m HashMap<String, T>) -> Option<&'m T> {
+        settings
+            .get(&self.qualified_name())
+            .or_else(|| settings.get(&self.name))
+    }
+
+    /// Applies `COLUMN_TRANSFORMS`, so the table describes what reaches the
+    /// sink: dropped columns are gone and masked ones are `text`. Keys and
+    /// unique indexes over columns that no longer tell rows apart (dropped,
+    /// redacted or truncated) are forgotten.
+    pub fn apply_column_transforms(&mut self, transforms: &ColumnTransforms) {
+        let Some(columns) = transforms.for_table(&self.qualified_name()) else {
+            return;
+        };
+        self.columns
+            .retain(|c| columns.get(&c.name) != Some(&ColumnTransform::Drop));
+        for column in &mut self.columns {
+            if columns.contains_key(&column.name) {
+                column.data_type = "text".to_string();
+                column.type_oid = 25;
+            }
+        }
+        let identifies = |name: &String| columns.get(name).map_or(true, |t| t.keeps_identity());
+        if !self.primary_key.iter().all(identifies) {
+            self.primary_key.clear();
+        }
+        self.indexes.retain(|i| i.columns.iter().all(identifies));
+        self.foreign_keys
+            .retain(|fk| fk.columns.iter().all(identifies));
+    }
+
+    /// Chooses the sink key: an override from `SINK_KEY_COLUMNS` (keyed by
+    /// `schema.table` or bare table name), else the primary key, else the
+    /// narrowest unique index whose columns are all NOT NULL. `None` when the
+    /// table has nothing that identifies a row.
+    pub fn sink_key(
+        &self,
+        overrides: &HashMap<String, Vec<String>>,
+    ) -> Result<Option<SinkKey>, SetupError> {
+        if let Some(columns) = self.table_setting(overrides) {
+            if let Some(missing) = columns.iter().find(|c| self.column(c).is_none()) {
+                return Err(SetupError::SinkKeyInvalid {
+                    table: self.qualified_name(),
+                    error: format!("SINK_KEY_COLUMNS names unknown column '{}'", missing),
+                });
+            }
+            return Ok(Some(SinkKey {
+                columns: columns.clone(),
+                origin: KeyOrigin::Override,
+            }));
+        }
+
+        if !self.primary_key.is_empty() {
+            return Ok(Some(SinkKey {
+                columns: self.primary_key.clone(),
+                origin: KeyOrigin::PrimaryKey,
+            }));
+        }
+
+        // NULLs are distinct in a unique index, so only NOT NULL columns qualify
+        let best = self
+            .indexes
+            .iter()
+            .filter(|i| i.unique && !i.partial_or_expression && !i.columns.is_empty())
+            .filter(|i| {
+                i.columns
+                    .iter()
+                    .all(|c| self.column(c).is_some_and(|col| !col.nullable))
+            })
+            .min_by(|a, b| {
+                a.columns
+                    .len()
+                    .cmp(&b.columns.len())
+                    .then_with(|| a.name.cmp(&b.name))
+            });
+        Ok(best.map(|i| SinkKey {
+            columns: i.columns.clone(),
+            origin: KeyOrigin::UniqueIndex(i.name.clone()),
+        }))
+    }
+}
+
+/// Splits `schema.table` (schema defaults to `public`).
+pub fn split_table_name(table: &str) -> (&str, &str) {
+    match table.split_once('.') {
+        Some((schema, name)) => (schema, name),
+        None => ("public", table),
+    }
+}
+
+/// Describes every configured table from the PostgreSQL catalog.
+pub async fn describe_tables(
+    client: &Client,
+    tables: &[String],
+) -> Result<Vec<SourceTable>, SetupError> {
+    let mut described = Vec::with_capacity(tables.len());
+    for table in tables {
+        described.push(describe_table(client, table).await?);
+    }
+    Ok(described)
+}
+
+/// Columns, primary key and comments (`pg_description`) of one table.
+pub async fn describe_table(client: &Client, table: &str) -> Result<SourceTable, SetupError> {
+    let (schema, name) = split_table_name(table);
+
+    // information_schema's ordinal_position is the attnum
+    let rows = client
+        .query(
+            "SELECT c.column_name, c.data_type, c.is_nullable = 'YES',
+                    col_description(a.attrelid, a.attnum), a.atttypid
+             FROM information_schema.columns c
+             JOIN pg_attribute a
+               ON a.attrelid = (quote_ident(c.table_schema) || '.' || quote_ident(c.table_name))::regclass
+              AND a.attnum = c.ordinal_position
+             WHERE c.table_schema = $1 AND c.table_name = $2
+             ORDER BY c.ordinal_position",
+            &[&schema, &name],
+        )
+        .await
+        .map_err(pg_error)?;
+    let columns: Vec<SourceColumn> = rows
+        .iter()
+        .map(|r| SourceColumn {
+            name: r.get(0),
+            data_type: r.get(1),
+            nullable: r.get(2),
+            comment: non_empty(r.get(3)),
+            type_oid: r.get(4),
+        })
+        .collect();
+
+    // Enums, domains and arrays of them, before sink tables are created
+    let type_oids: Vec<u32> = columns.iter().map(|c| c.type_oid).collect();
+    if let Err(e) = pg_types::lookup(client, &type_oids).await {
+        warn!("Cannot read the column types of {}: {:#}", table, e);
+    }
+
+    let comment: Option<String> = client
+        .query_one(
+            "SELECT obj_description(
+                 (quote_ident($1::text) || '.' || quote_ident($2::text))::regclass, 'pg_class')",
+            &[&schema, &name],
+        )
+        .await
+        .map_err(pg_error)?
+        .get(0);
+
+    let primary_key = client
+        .query(
+            "SELECT a.attname
+             FROM pg_index i
+             JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+             JOIN pg_class c ON c.oid = i.indrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE i.indisprimary AND n.nspname = $1 AND c.relname = $2
+             ORDER BY array_position(i.indkey::int2[], a.attnum)",
+            &[&schema, &name],
+        )
+        .await
+        .map_err(pg_error)?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+
+    let indexes = client
+        .query(
+            "SELECT ic.relname, i.indisunique, i.indisprimary,
+                    i.indpred IS NOT NULL OR i.indexprs IS NOT NULL,
+                    ARRAY(
+                        SELECT a.attname::text
+                        FROM unnest(i.indkey::int2[]) WITH ORDINALITY AS k(attnum, ord)
+                        JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = k.attnum
+                        ORDER BY k.ord
+                    )
+             FROM pg_index i
+             JOIN pg_class ic ON ic.oid = i.indexrelid
+             WHERE i.indrelid = (quote_ident($1::text) || '.' || quote_ident($2::text))::regclass
+             ORDER BY ic.relname",
+            &[&schema, &name],
+        )
+        .await
+        .map_err(pg_error)?
+        .iter()
+        .map(|r| SourceIndex {
+            name: r.get(0),
+            unique: r.get(1),
+            primary: r.get(2),
+            partial_or_expression: r.get(3),
+            columns: r.get(4),
+        })
+        .collect();
+
+    let foreign_keys = client
+        .query(
+            "SELECT con.conname,
+                    ARRAY(
+                        SELECT a.attname::text
+                        FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, ord)
+                        JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                        ORDER BY k.ord
+                    ),
+                    rn.nspname || '.' || rc.relname,
+                    ARRAY(
+                        SELECT a.attname::text
+                        FROM unnest(con.confkey) WITH ORDINALITY AS k(attnum, ord)
+                        JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum
+                        ORDER BY k.ord
+                    )
+             FROM pg_constraint con
+             JOIN pg_class rc ON rc.oid = con.confrelid
+             JOIN pg_namespace rn ON rn.oid = rc.relnamespace
+             WHERE con.contype = 'f'
+               AND con.conrelid = (quote_ident($1::text) || '.' || quote_ident($2::text))::regclass
+             ORDER BY con.conname",
+            &[&schema, &name],
+        )
+        .await
+        .map_err(pg_error)?
+        .iter()
+        .map(|r| SourceForeignKey {
+            name: r.get(0),
+            columns: r.get(1),
+            referenced_table: r.get(2),
+            referenced_columns: r.get(3),
+        })
+        .collect();
+
+    Ok(SourceTable {
+        schema: schema.to_string(),
+        name: name.to_string(),
+        comment: non_empty(comment),
+        primary_key,
+        columns,
+        indexes,
+        foreign_keys,
+    })
+}
+
+/// Writes the described tables as JSON (`{"tables": [...]}`) to `path`.
+pub async fn export_schema(path: &str, tables: &[SourceTable]) -> Result<(), SetupError> {
+    let export_error = |error: String| SetupError::SchemaExportFailed {
+        path: path.to_string(),
+        error,
+    };
+
+    let json = serde_json::to_vec_pretty(&serde_json::json!({ "tables": tables }))
+        .map_err(|e| export_error(e.to_string()))?;
+    tokio::fs::write(path, json)
+        .await
+        .map_err(|e| export_error(e.to_string()))?;
+
+    info!("  [OK] Source schema exported to {}", path);
+    Ok(())
+}
+
+fn non_empty(text: Option<String>) -> Option<String> {
+    text.filter(|t| !t.trim().is_empty())
+}
+
+fn pg_error(e: tokio_postgres::Error) -> SetupError {
+    SetupError::PgConnectionFailed {
+        host: "PostgreSQL".to_string(),
+        error: pg_error_message(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_table_name() {
+        assert_eq!(split_table_name("orders"), ("public", "orders"));
+        assert_eq!(split_table_name("sales.orders"), ("sales", "orders"));
+    }
+
+    fn column(name: &str, nullable: bool) -> SourceColumn {
+        SourceColumn {
+            name: name.into(),
+            data_type: "integer".into(),
+            type_oid: 23,
+            nullable,
+            comment: None,
+        }
+    }
+
+    fn unique_index(name: &str, columns: &[&str]) -> SourceIndex {
+        SourceIndex {
+            name: name.into(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            unique: true,
+            primary: false,
+            partial_or_expression: false,
+        }
+    }
+
+    fn table(primary_key: &[&str], indexes: Vec<SourceIndex>) -> SourceTable {
+        SourceTable {
+            schema: "public".into(),
+            name: "orders".into(),
+            comment: None,
+            primary_key: primary_key.iter().map(|c| c.to_string()).collect(),
+            columns: vec![
+                column("id", false),
+                column("tenant_id", false),
+                column("code", false),
+                column("email", true),
+            ],
+            indexes,
+            foreign_keys: vec![],
+        }
+    }
+
+    #[test]
+    fn test_sink_key_prefers_primary_key() {
+        let t = table(&["id"], vec![unique_index("orders_code_key", &["code"])]);
+        let key = t.sink_key(&HashMap::new()).unwrap().unwrap();
+        assert_eq!(key.columns, vec!["id"]);
+        assert_eq!(key.origin, KeyOrigin::PrimaryKey);
+    }
+
+    #[test]
+    fn test_sink_key_falls_back_to_not_null_unique_index() {
+        let mut partial = unique_index("orders_id_partial", &["id"]);
+        partial.partial_or_expression = true;
+        let t = table(
+            &[],
+            vec![
+                partial,
+                unique_index("orders_email_key", &["email"]),
+                unique_index("orders_tenant_code_key", &["tenant_id", "code"]),
+                unique_index("orders_code_key", &["code"]),
+            ],
+        );
+        let key = t.sink_key(&HashMap::new()).unwrap().unwrap();
+        assert_eq!(key.columns, vec!["code"]);
+        assert_eq!(key.origin, KeyOrigin::UniqueIndex("orders_code_key".into()));
+
+        assert_eq!(table(&[], vec![]).sink_key(&HashMap::new()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_column_transforms() {
+        let mut t = table(
+            &["id"],
+            vec![
+                unique_index("orders_code_key", &["code"]),
+                unique_index("orders_email_key", &["email"]),
+            ],
+        );
+        let transforms = ColumnTransforms {
+            tables: HashMap::from([(
+                "orders".to_string(),
+                HashMap::from([
+                    ("id".to_string(), ColumnTransform::Hash),
+                    ("code".to_string(), ColumnTransform::Truncate(2)),
+                    ("email".to_string(), ColumnTransform::Drop),
+                ]),
+            )]),
+            hash_salt: String::new(),
+        };
+        t.apply_column_transforms(&transforms);
+
+        let names: Vec<&str> = t.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "tenant_id", "code"]);
+        assert_eq!(
+            (t.columns[0].type_oid, t.columns[0].data_type.as_str()),
+            (25, "text")
+        );
+        assert_eq!(t.columns[1].type_oid, 23);
+        // A hashed key still tells rows apart; truncated and dropped ones don't
+        assert_eq!(t.primary_key, vec!["id"]);
+        assert!(t.indexes.is_empty());
+    }
+
+    #[test]
+    fn test_sink_key_override() {
+        let t = table(&["id"], vec![]);
+        let mut overrides = HashMap::new();
+        overrides.insert(
+            "public.orders".to_string(),
+            vec!["tenant_id".to_string(), "id".to_string()],
+        );
+        let key = t.sink_key(&overrides).unwrap().unwrap();
+        assert_eq!(key.columns, vec!["tenant_id", "id"]);
+        assert_eq!(key.origin, KeyOrigin::Override);
+
+        overrides.clear();
+        overrides.insert("orders".to_string(), vec!["missing".to_string()]);
+        assert!(t.sink_key(&overrides).is_err());
+    }
+
+    #[test]
+    fn test_export_shape() {
+        let mut table = table(&["id"], vec![unique_index("orders_code_key", &["code"])]);
+        table.comment = Some("Customer orders".into());
+
+        let json = serde_json::json!({ "tables": [table] });
+        assert_eq!(json["tables"][0]["comment"], "Customer orders");
+        assert_eq!(json["tables"][0]["primary_key"][0], "id");
+        assert!(json["tables"][0]["columns"][0]["comment"].is_null());
+        assert_eq!(json["tables"][0]["indexes"][0]["unique"], true);
+    }
+}
@@ -0,0 +1,183 @@
+use tracing::info;
+
+use super::catalog::{KeyOrigin, SinkKey, SourceTable};
+use super::error::SetupError;
+use crate::config::{BoolEncoding, Config};
+use crate::connectors::sinks::clickhouse::types::column_type;
+use crate::connectors::sinks::clickhouse::{
+    add_columns_sql, audit_column_defs, column_def, create_table_sql, qualified_table, quote_ident,
+    ClickHouseClient, ClickHouseColumn,
+};
+
+/// Prepares ClickHouse: creates the databases and every replicated table that
+/// doesn't exist yet, and adds missing source and audit columns to tables
+/// that do. Keyed tables are ReplacingMergeTree ordered by the sink key (see
+/// `SourceTable::sink_key`), keyless ones MergeTree.
+pub struct ClickHouseSetup<'a> {
+    tables: &'a [SourceTable],
+    config: &'a Config,
+}
+
+impl<'a> ClickHouseSetup<'a> {
+    pub fn new(tables: &'a [SourceTable], config: &'a Config) -> Self {
+        Self { tables, config }
+    }
+
+    /// Execute complete ClickHouse setup.
+    pub async fn run(&self) -> Result<(), SetupError> {
+        info!("ClickHouse Setup:");
+
+        let sink = &self.config.sink;
+        let client = ClickHouseClient::new(sink).map_err(|e| self.connection_error(e))?;
+        client
+            .execute("SELECT 1")
+            .await
+            .map_err(|e| self.connection_error(e))?;
+
+        // 1. Databases: SINK_DATABASE, plus those of SINK_SCHEMA_MODE=preserve and TABLE_RENAMES
+        let mut databases = vec![client.database().to_string()];
+        for table in self.tables {
+            if let Some(db) = sink.naming.sink_table(&table.qualified_name()).database {
+                if !databases.contains(&db) {
+                    databases.push(db);
+                }
+            }
+        }
+        for db in &databases {
+            client
+                .execute(&format!(
+                    "CREATE DATABASE IF NOT EXISTS {}",
+                    quote_ident(db)
+                ))
+                .await
+                .map_err(|e| self.table_error(db, e))?;
+        }
+
+        // 2. Tables
+        for table in self.tables {
+            let key = table.sink_key(&self.config.sink_key_columns)?;
+            match &key {
+                None => info!(
+                    "  {} has no primary key or NOT NULL unique index, changes will be appended to a MergeTree table",
+                    table.qualified_name()
+                ),
+                Some(k) if k.origin != KeyOrigin::PrimaryKey => info!(
+                    "  {} keyed by ({}) from {}",
+                    table.qualified_name(),
+                    k.columns.join(", "),
+                    k.origin
+                ),
+                Some(_) => {}
+            }
+            let dest = sink.naming.sink_table(&table.qualified_name());
+            let name = qualified_table(&dest, client.database());
+            let columns = table_columns(table, key.as_ref(), sink.bool_encoding);
+
+            // Tables created earlier may lack columns added since, or the audit columns
+            let defs: Vec<String> = columns
+                .iter()
+                .map(column_def)
+                .chain(audit_column_defs())
+                .collect();
+            for sql in [
+                create_table_sql(
+                    &name,
+                    &columns,
+                    key_columns(key.as_ref()),
+                    table.comment.as_deref(),
+                ),
+                add_columns_sql(&name, &defs),
+            ] {
+                client
+                    .execute(&sql)
+                    .await
+                    .map_err(|e| self.table_error(&dest.label(), e))?;
+            }
+            info!("  [OK] Table {} ready", dest.label());
+        }
+
+        info!("[OK] ClickHouse setup complete");
+        Ok(())
+    }
+
+    fn connection_error(&self, error: anyhow::Error) -> SetupError {
+        SetupError::ChConnectionFailed {
+            url: self.config.sink.url.clone(),
+            error: format!("{:#}", error),
+        }
+    }
+
+    fn table_error(&self, table: &str, error: anyhow::Error) -> SetupError {
+        SetupError::ChCreateTableFailed {
+            table: table.to_string(),
+            error: format!("{:#}", error),
+        }
+    }
+}
+
+fn key_columns(key: Option<&SinkKey>) -> &[String] {
+    key.map(|k| k.columns.as_slice()).unwrap_or_default()
+}
+
+/// ClickHouse columns of a source table. Key columns are never Nullable,
+/// since ClickHouse doesn't allow Nullable sorting keys.
+fn table_columns(
+    table: &SourceTable,
+    key: Option<&SinkKey>,
+    bool_encoding: BoolEncoding,
+) -> Vec<ClickHouseColumn> {
+    let keys = key_columns(key);
+    table
+        .columns
+        .iter()
+        .map(|c| ClickHouseColumn {
+            name: c.name.clone(),
+            column_type: column_type(
+                c.type_oid,
+                bool_encoding,
+                c.nullable && !keys.contains(&c.name),
+            ),
+            comment: c.comment.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::setup::catalog::SourceColumn;
+
+    #[test]
+    fn test_table_columns() {
+        let column = |name: &str, type_oid: u32, nullable: bool| SourceColumn {
+            name: name.into(),
+            data_type: String::new(),
+            type_oid,
+            nullable,
+            comment: None,
+        };
+        let table = SourceTable {
+            schema: "public".into(),
+            name: "orders".into(),
+            comment: None,
+            primary_key: vec!["id".into()],
+            columns: vec![
+                column("id", 20, true),
+                column("paid", 16, false),
+                column("note", 25, true),
+            ],
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+        };
+        let key = SinkKey {
+            columns: vec!["id".into()],
+            origin: KeyOrigin::PrimaryKey,
+        };
+
+        let types: Vec<String> = table_columns(&table, Some(&key), BoolEncoding::Int)
+            .into_iter()
+            .map(|c| c.column_type)
+            .collect();
+        assert_eq!(types, ["Int64", "UInt8", "Nullable(String)"]);
+    }
+}
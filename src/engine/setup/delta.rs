@@ -0,0 +1,129 @@
+use deltalake::kernel::StructField;
+use deltalake::DeltaOps;
+use tracing::info;
+
+use super::catalog::SourceTable;
+use super::error::SetupError;
+use crate::config::Config;
+use crate::connectors::sinks::delta::types::column_type;
+use crate::connectors::sinks::delta::{
+    add_columns, cdc_fields, load_table, storage_options, struct_field, table_uri,
+};
+
+/// Prepares the Delta tables: creates every replicated table that doesn't
+/// exist yet, unpartitioned and with the CDC columns, and adds missing
+/// source and CDC columns to tables that do. Every column is nullable, since
+/// delete rows carry only the key.
+pub struct DeltaSetup<'a> {
+    tables: &'a [SourceTable],
+    config: &'a Config,
+}
+
+impl<'a> DeltaSetup<'a> {
+    pub fn new(tables: &'a [SourceTable], config: &'a Config) -> Self {
+        Self { tables, config }
+    }
+
+    /// Execute complete Delta Lake setup.
+    pub async fn run(&self) -> Result<(), SetupError> {
+        info!("Delta Lake Setup:");
+
+        let sink = &self.config.sink;
+        let Some(delta) = &sink.delta else {
+            return Err(self.table_error(&sink.url, "Delta sink configuration is missing"));
+        };
+        let options = storage_options(delta, &sink.http);
+
+        for table in self.tables {
+            let dest = sink.naming.sink_table(&table.qualified_name());
+            let uri = table_uri(&sink.url, &dest, &sink.database);
+            let fields = table_fields(table)
+                .and_then(|mut fields| {
+                    fields.extend(cdc_fields()?);
+                    Ok(fields)
+                })
+                .map_err(|e| self.table_error(&uri, e))?;
+
+            let existing = load_table(&uri, &options)
+                .await
+                .map_err(|e| self.table_error(&uri, e))?;
+            match existing {
+                // Tables created earlier may lack columns added since, or the CDC columns
+                Some(existing) => {
+                    let (_, added) = add_columns(existing, fields)
+                        .await
+                        .map_err(|e| self.table_error(&uri, e))?;
+                    if !added.is_empty() {
+                        info!("  Added {} to {}", added.join(", "), uri);
+                    }
+                }
+                None => {
+                    let mut create =
+                        DeltaOps::try_from_uri_with_storage_options(&uri, options.clone())
+                            .await
+                            .map_err(|e| self.table_error(&uri, e))?
+                            .create()
+                            .with_table_name(&dest.table)
+                            .with_columns(fields);
+                    if let Some(comment) = &table.comment {
+                        create = create.with_comment(comment);
+                    }
+                    create.await.map_err(|e| self.table_error(&uri, e))?;
+                }
+            }
+            info!("  [OK] Table {} ready at {}", dest.label(), uri);
+        }
+
+        info!("[OK] Delta Lake setup complete");
+        Ok(())
+    }
+
+    fn table_error(&self, table: &str, error: impl std::fmt::Display) -> SetupError {
+        SetupError::DeltaCreateTableFailed {
+            table: table.to_string(),
+            error: format!("{:#}", error),
+        }
+    }
+}
+
+/// Delta columns of a source table.
+fn table_fields(table: &SourceTable) -> anyhow::Result<Vec<StructField>> {
+    table
+        .columns
+        .iter()
+        .map(|c| struct_field(&c.name, column_type(c.type_oid), c.comment.as_deref()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::setup::catalog::SourceColumn;
+
+    #[test]
+    fn test_table_fields() {
+        let column = |name: &str, type_oid: u32| SourceColumn {
+            name: name.into(),
+            data_type: String::new(),
+            type_oid,
+            nullable: false,
+            comment: Some(format!("The {}", name)),
+        };
+        let table = SourceTable {
+            schema: "public".into(),
+            name: "orders".into(),
+            comment: None,
+            primary_key: vec!["id".into()],
+            columns: vec![column("id", 20), column("placed_at", 1083)],
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+        };
+
+        let fields = table_fields(&table).unwrap();
+        let names: Vec<&str> = fields.iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["id", "placed_at"]);
+        assert!(fields.iter().all(|f| f.is_nullable()));
+        assert_eq!(fields[1].data_type(), &deltalake::kernel::DataType::STRING);
+        assert_eq!(cdc_fields().unwrap().len(), 3);
+    }
+}
@@ -23,6 +23,14 @@ pub enum SetupError {
         name: String,
         error: String,
     },
+    PgProbeTableFailed {
+        table: String,
+        error: String,
+    },
+    PgBinaryTypesUnsupported {
+        table: String,
+        columns: String,
+    },
 
     // StarRocks
     SrConnectionFailed {
@@ -36,8 +44,66 @@ pub enum SetupError {
         table: String,
         error: String,
     },
+    SrCreateTableFailed {
+        table: String,
+        error: String,
+    },
+
+    // SQLite
+    #[cfg(feature = "sqlite")]
+    SqliteFailed {
+        path: String,
+        error: String,
+    },
+
+    // ClickHouse
+    ChConnectionFailed {
+        url: String,
+        error: String,
+    },
+    ChCreateTableFailed {
+        table: String,
+        error: String,
+    },
+
+    // Iceberg
+    #[cfg(feature = "iceberg")]
+    IcebergConnectionFailed {
+        url: String,
+        error: String,
+    },
+    #[cfg(feature = "iceberg")]
+    IcebergCreateTableFailed {
+        table: String,
+        error: String,
+    },
+
+    // Delta Lake
+    #[cfg(feature = "delta")]
+    DeltaCreateTableFailed {
+        table: String,
+        error: String,
+    },
+
+    // Source catalog
+    SinkKeyInvalid {
+        table: String,
+        error: String,
+    },
+    SchemaExportFailed {
+        path: String,
+        error: String,
+    },
+    LineageExportFailed {
+        path: String,
+        error: String,
+    },
 
     // General
+    SinkNotBuilt {
+        sink: String,
+        feature: &'static str,
+    },
     #[allow(dead_code)]
     CheckpointFailed {
         error: String,
@@ -66,12 +132,21 @@ impl SetupError {
             SetupError::PgSlotFailed { name, error } => {
                 format!("Failed to setup replication slot '{}': {}", name, error)
             }
+            SetupError::PgProbeTableFailed { table, error } => {
+                format!("Failed to create probe table '{}': {}", table, error)
+            }
+            SetupError::PgBinaryTypesUnsupported { table, columns } => {
+                format!(
+                    "SOURCE_BINARY_TUPLES can't decode columns of '{}': {}. Unset it to stream values as text.",
+                    table, columns
+                )
+            }
             SetupError::SrConnectionFailed { host, error } => {
                 format!("StarRocks connection failed to '{}': {}", host, error)
             }
             SetupError::SrTableNotFound { table } => {
                 format!(
-                    "Table '{}' not found in StarRocks. Create the table before starting CDC.",
+                    "Table '{}' not found in StarRocks. Create the table before starting CDC or set SINK_AUTO_CREATE_TABLES=true.",
                     table
                 )
             }
@@ -81,6 +156,46 @@ impl SetupError {
                     table, error
                 )
             }
+            #[cfg(feature = "sqlite")]
+            SetupError::SqliteFailed { path, error } => {
+                format!("SQLite setup failed for '{}': {}", path, error)
+            }
+            SetupError::SrCreateTableFailed { table, error } => {
+                format!("Failed to create StarRocks table '{}': {}", table, error)
+            }
+            SetupError::ChConnectionFailed { url, error } => {
+                format!("ClickHouse connection failed to '{}': {}", url, error)
+            }
+            SetupError::ChCreateTableFailed { table, error } => {
+                format!("Failed to prepare ClickHouse table '{}': {}", table, error)
+            }
+            #[cfg(feature = "iceberg")]
+            SetupError::IcebergConnectionFailed { url, error } => {
+                format!("Iceberg catalog connection failed to '{}': {}", url, error)
+            }
+            #[cfg(feature = "iceberg")]
+            SetupError::IcebergCreateTableFailed { table, error } => {
+                format!("Failed to prepare Iceberg table '{}': {}", table, error)
+            }
+            #[cfg(feature = "delta")]
+            SetupError::DeltaCreateTableFailed { table, error } => {
+                format!("Failed to prepare Delta table '{}': {}", table, error)
+            }
+            SetupError::SinkKeyInvalid { table, error } => {
+                format!("Cannot choose a sink key for '{}': {}", table, error)
+            }
+            SetupError::SchemaExportFailed { path, error } => {
+                format!("Failed to export source schema to '{}': {}", path, error)
+            }
+            SetupError::LineageExportFailed { path, error } => {
+                format!("Failed to export column lineage to '{}': {}", path, error)
+            }
+            SetupError::SinkNotBuilt { sink, feature } => {
+                format!(
+                    "SINK_TYPE={} is not built into this binary. Build dbmazz with `--features {}`.",
+                    sink, feature
+                )
+            }
             SetupError::CheckpointFailed { error } => {
                 format!("Checkpoint load failed: {}", error)
             }
@@ -0,0 +1,177 @@
+use tracing::info;
+
+use super::catalog::{KeyOrigin, SinkKey, SourceTable};
+use super::error::SetupError;
+use crate::config::Config;
+use crate::connectors::sinks::iceberg::types::IcebergType;
+use crate::connectors::sinks::iceberg::{
+    add_columns, audit_columns, new_table_schema, table_ident, NewColumn, RestCatalog,
+};
+
+/// Prepares an Iceberg catalog: creates the namespaces and every replicated
+/// table that doesn't exist yet, and adds missing source and audit columns
+/// to tables that do. New tables are unpartitioned, format version 2, with
+/// the sink key (see `SourceTable::sink_key`) as identifier fields.
+pub struct IcebergSetup<'a> {
+    tables: &'a [SourceTable],
+    config: &'a Config,
+}
+
+impl<'a> IcebergSetup<'a> {
+    pub fn new(tables: &'a [SourceTable], config: &'a Config) -> Self {
+        Self { tables, config }
+    }
+
+    /// Execute complete Iceberg setup.
+    pub async fn run(&self) -> Result<(), SetupError> {
+        info!("Iceberg Setup:");
+
+        let sink = &self.config.sink;
+        let catalog = RestCatalog::new(sink).map_err(|e| self.connection_error(e))?;
+        catalog
+            .check()
+            .await
+            .map_err(|e| self.connection_error(e))?;
+        let default_namespace: Vec<String> = sink.database.split('.').map(str::to_string).collect();
+
+        // 1. Namespaces: SINK_DATABASE, plus those of SINK_SCHEMA_MODE=preserve and TABLE_RENAMES
+        let mut namespaces = vec![default_namespace.clone()];
+        for table in self.tables {
+            let dest = sink.naming.sink_table(&table.qualified_name());
+            let namespace = table_ident(&dest, &default_namespace).namespace;
+            if !namespaces.contains(&namespace) {
+                namespaces.push(namespace);
+            }
+        }
+        for namespace in &namespaces {
+            catalog
+                .create_namespace(namespace)
+                .await
+                .map_err(|e| self.table_error(&namespace.join("."), e))?;
+        }
+
+        // 2. Tables
+        for table in self.tables {
+            let key = table.sink_key(&self.config.sink_key_columns)?;
+            match &key {
+                None => info!(
+                    "  {} has no primary key or NOT NULL unique index, deletes will match on every column",
+                    table.qualified_name()
+                ),
+                Some(k) if k.origin != KeyOrigin::PrimaryKey => info!(
+                    "  {} keyed by ({}) from {}",
+                    table.qualified_name(),
+                    k.columns.join(", "),
+                    k.origin
+                ),
+                Some(_) => {}
+            }
+            let dest = sink.naming.sink_table(&table.qualified_name());
+            let ident = table_ident(&dest, &default_namespace);
+            let columns = table_columns(table, key.as_ref());
+
+            let exists = catalog
+                .load_table(&ident)
+                .await
+                .map_err(|e| self.table_error(&ident.label(), e))?
+                .is_some();
+            if exists {
+                // Tables created earlier may lack columns added since, or the audit columns
+                let wanted: Vec<NewColumn> = columns.into_iter().chain(audit_columns()).collect();
+                let added = add_columns(&catalog, &ident, &wanted)
+                    .await
+                    .map_err(|e| self.table_error(&ident.label(), e))?;
+                if !added.is_empty() {
+                    info!("  Added {} to {}", added.join(", "), ident.label());
+                }
+            } else {
+                catalog
+                    .create_table(
+                        &ident,
+                        &new_table_schema(&columns),
+                        table.comment.as_deref(),
+                    )
+                    .await
+                    .map_err(|e| self.table_error(&ident.label(), e))?;
+            }
+            info!("  [OK] Table {} ready", ident.label());
+        }
+
+        info!("[OK] Iceberg setup complete");
+        Ok(())
+    }
+
+    fn connection_error(&self, error: anyhow::Error) -> SetupError {
+        SetupError::IcebergConnectionFailed {
+            url: self.config.sink.url.clone(),
+            error: format!("{:#}", error),
+        }
+    }
+
+    fn table_error(&self, table: &str, error: anyhow::Error) -> SetupError {
+        SetupError::IcebergCreateTableFailed {
+            table: table.to_string(),
+            error: format!("{:#}", error),
+        }
+    }
+}
+
+/// Iceberg columns of a source table. Key columns are required, since
+/// identifier fields can't be optional.
+fn table_columns(table: &SourceTable, key: Option<&SinkKey>) -> Vec<NewColumn> {
+    let keys = key.map(|k| k.columns.as_slice()).unwrap_or_default();
+    table
+        .columns
+        .iter()
+        .map(|c| NewColumn {
+            name: c.name.clone(),
+            iceberg_type: IcebergType::for_pg_type(c.type_oid),
+            required: keys.contains(&c.name),
+            doc: c.comment.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::setup::catalog::SourceColumn;
+
+    #[test]
+    fn test_table_columns() {
+        let column = |name: &str, type_oid: u32, nullable: bool| SourceColumn {
+            name: name.into(),
+            data_type: String::new(),
+            type_oid,
+            nullable,
+            comment: None,
+        };
+        let table = SourceTable {
+            schema: "public".into(),
+            name: "orders".into(),
+            comment: None,
+            primary_key: vec!["id".into()],
+            columns: vec![
+                column("id", 20, true),
+                column("total", 1700, false),
+                column("note", 25, true),
+            ],
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+        };
+        let key = SinkKey {
+            columns: vec!["id".into()],
+            origin: KeyOrigin::PrimaryKey,
+        };
+
+        let columns = table_columns(&table, Some(&key));
+        let types: Vec<String> = columns.iter().map(|c| c.iceberg_type.name()).collect();
+        assert_eq!(types, ["long", "decimal(38, 9)", "string"]);
+        let required: Vec<bool> = columns.iter().map(|c| c.required).collect();
+        assert_eq!(required, [true, false, false]);
+
+        let schema = new_table_schema(&columns);
+        assert_eq!(schema.identifier_field_ids, vec![1]);
+        assert_eq!(schema.fields.len(), 5);
+    }
+}
@@ -1,22 +1,46 @@
+pub mod catalog;
+pub mod clickhouse;
+#[cfg(feature = "delta")]
+pub mod delta;
 pub mod error;
+#[cfg(feature = "iceberg")]
+pub mod iceberg;
 pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 pub mod starrocks;
 
 use anyhow::Result;
 use tracing::info;
 
-use crate::config::Config;
+use crate::config::{Config, SinkType};
+use crate::pipeline::lineage::LineageExport;
+use crate::source::session::PgSession;
+use catalog::SourceTable;
 pub use error::SetupError;
 pub use postgres::cleanup_postgres_resources;
 
 /// Main manager for the SETUP process
 pub struct SetupManager {
     config: Config,
+    session: PgSession,
+    /// Skip the sink step (the engine was given a sink it doesn't manage)
+    skip_sink: bool,
 }
 
 impl SetupManager {
-    pub fn new(config: Config) -> Self {
-        Self { config }
+    pub fn new(config: Config, session: PgSession) -> Self {
+        Self {
+            config,
+            session,
+            skip_sink: false,
+        }
+    }
+
+    /// Only prepare PostgreSQL; the caller is responsible for the destination.
+    pub fn without_sink_setup(mut self) -> Self {
+        self.skip_sink = true;
+        self
     }
 
     /// Execute complete setup.
@@ -26,14 +50,18 @@ impl SetupManager {
         info!("═══════════════════════════════════════\n");
 
         // 1. Setup PostgreSQL
-        let pg_client = postgres::create_postgres_client(&self.config.database_url).await?;
+        let pg_client =
+            postgres::create_postgres_client(&self.session, &self.config.database_url).await?;
         let pg_setup = postgres::PostgresSetup::new(&pg_client, &self.config);
         pg_setup.run().await?;
+        let tables = self.describe_source(&pg_client).await?;
 
-        // 2. Setup StarRocks (verify tables exist + ensure audit columns)
-        let pool = starrocks::create_starrocks_pool(&self.config)?;
-        let sr_setup = starrocks::StarRocksSetup::new(&pool, &self.config);
-        sr_setup.run().await?;
+        // 2. Setup sink
+        if self.skip_sink {
+            info!("Skipping sink setup (destination prepared elsewhere)");
+        } else {
+            self.run_sink_setup(&tables).await?;
+        }
 
         info!("\n═══════════════════════════════════════");
         info!("    [OK] SETUP COMPLETE");
@@ -41,4 +69,128 @@ impl SetupManager {
 
         Ok(())
     }
+
+    /// Prepare only the sink, leaving PostgreSQL untouched (no publication,
+    /// no slot). Used by backfill-only mode.
+    pub async fn run_sink_only(&self) -> Result<(), SetupError> {
+        let pg_client =
+            postgres::create_postgres_client(&self.session, &self.config.database_url).await?;
+        let tables = self.describe_source(&pg_client).await?;
+        self.run_sink_setup(&tables).await
+    }
+
+    /// Read columns, keys and comments of the replicated tables, exporting
+    /// them when `SCHEMA_EXPORT_PATH` is set and their column lineage when
+    /// `LINEAGE_EXPORT_PATH` is set.
+    async fn describe_source(
+        &self,
+        pg_client: &tokio_postgres::Client,
+    ) -> Result<Vec<SourceTable>, SetupError> {
+        let mut tables = catalog::describe_tables(pg_client, &self.config.tables).await?;
+        // Describe the tables as they reach the sink
+        for table in &mut tables {
+            table.apply_column_transforms(&self.config.column_transforms);
+        }
+        if let Some(path) = &self.config.schema_export_path {
+            catalog::export_schema(path, &tables).await?;
+        }
+        if let Some(lineage) = LineageExport::from_config(&self.config) {
+            lineage
+                .write(&tables)
+                .await
+                .map_err(|e| SetupError::LineageExportFailed {
+                    path: self.config.lineage_export_path.clone().unwrap_or_default(),
+                    error: e.to_string(),
+                })?;
+        }
+        Ok(tables)
+    }
+
+    /// Prepare the configured sink, and the sinks of `SINK_ROUTES` with the
+    /// tables routed to each.
+    async fn run_sink_setup(&self, tables: &[SourceTable]) -> Result<(), SetupError> {
+        if self.config.sink_routes.is_empty() {
+            return prepare_sink(&self.config, tables).await;
+        }
+        for route in std::iter::once(None).chain(self.config.sink_routes.iter().map(Some)) {
+            let config = self.config.for_route(route);
+            let routed: Vec<SourceTable> = tables
+                .iter()
+                .filter(|t| {
+                    self.config.route_of(&t.qualified_name()).map(|r| &r.name)
+                        == route.map(|r| &r.name)
+                })
+                .cloned()
+                .collect();
+            if routed.is_empty() {
+                continue;
+            }
+            if let Some(route) = route {
+                info!("Sink route {}:", route.name);
+            }
+            prepare_sink(&config, &routed).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Prepares the sink of `config` for `tables`.
+async fn prepare_sink(config: &Config, tables: &[SourceTable]) -> Result<(), SetupError> {
+    if let Some(feature) = config.sink.sink_type.missing_feature() {
+        return Err(SetupError::SinkNotBuilt {
+            sink: config.sink.sink_type.to_string(),
+            feature,
+        });
+    }
+
+    match config.sink.sink_type {
+        SinkType::StarRocks => {
+            // Verify (or create) tables + ensure audit columns
+            let pool = starrocks::create_starrocks_pool(config)?;
+            let sr_setup = starrocks::StarRocksSetup::new(&pool, config);
+            sr_setup.run(tables).await?;
+            if config.sync_column_comments {
+                sr_setup.sync_comments(tables).await?;
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        SinkType::Sqlite => {
+            // Create missing tables with the source primary key and comments
+            let sqlite_setup = sqlite::SqliteSetup::new(tables, config);
+            sqlite_setup.run().await?;
+        }
+        SinkType::ClickHouse => {
+            // Create databases and tables, add missing and audit columns
+            let ch_setup = clickhouse::ClickHouseSetup::new(tables, config);
+            ch_setup.run().await?;
+        }
+        #[cfg(feature = "iceberg")]
+        SinkType::Iceberg => {
+            // Create namespaces and tables, add missing and audit columns
+            let iceberg_setup = iceberg::IcebergSetup::new(tables, config);
+            iceberg_setup.run().await?;
+        }
+        #[cfg(feature = "delta")]
+        SinkType::Delta => {
+            // Create tables with the CDC columns, add missing columns
+            let delta_setup = delta::DeltaSetup::new(tables, config);
+            delta_setup.run().await?;
+        }
+        SinkType::ObjectStore => {
+            // Every file carries its own schema
+            info!("Object store sink: nothing to prepare, files are written under the sink URL");
+        }
+        SinkType::Remote => {
+            // The remote sink server owns its destination schema
+            info!("Remote sink: nothing to prepare, destination is managed by the sink server");
+        }
+        // Sinks left out of the build, refused by `missing_feature` above
+        #[allow(unreachable_patterns)]
+        _ => unreachable!(
+            "{} sink is not built into this binary",
+            config.sink.sink_type
+        ),
+    }
+
+    Ok(())
 }
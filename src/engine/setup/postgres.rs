@@ -1,15 +1,17 @@
 use anyhow::Result;
-use tokio_postgres::{Client, NoTls};
+use tokio_postgres::Client;
 use tracing::{info, warn};
 
 use super::error::SetupError;
 use crate::config::Config;
-use crate::utils::validate_sql_identifier;
+use crate::source::binary;
+use crate::source::session::PgSession;
+use crate::utils::{quote_ident, validate_sql_identifier};
 
 /// Extract a detailed error message from a tokio_postgres error.
 /// tokio_postgres::Error::Display only prints the error kind (e.g. "db error")
 /// without the actual PostgreSQL message. This function extracts the full detail.
-fn pg_error_message(e: &tokio_postgres::Error) -> String {
+pub(super) fn pg_error_message(e: &tokio_postgres::Error) -> String {
     if let Some(db_err) = e.as_db_error() {
         let mut msg = format!("{}: {}", db_err.severity(), db_err.message());
         if let Some(detail) = db_err.detail() {
@@ -38,14 +40,18 @@ impl<'a> PostgresSetup<'a> {
     pub async fn run(&self) -> Result<(), SetupError> {
         info!("PostgreSQL Setup:");
 
+        // 0. Create the probe table, which is replicated like the others
+        self.ensure_probe_table().await?;
+
         // 1. Verify that tables exist
         self.verify_tables_exist().await?;
+        self.verify_binary_types().await?;
 
         // 2. Configure REPLICA IDENTITY FULL
         self.ensure_replica_identity().await?;
 
-        // 3. Create/verify Publication
-        self.ensure_publication().await?;
+        // 3. Create/verify Publications
+        self.ensure_publications().await?;
 
         // 4. Create/verify Replication Slot
         self.ensure_replication_slot().await?;
@@ -54,6 +60,92 @@ impl<'a> PostgresSetup<'a> {
         Ok(())
     }
 
+    /// Adds the tables of `TABLES` to their publication while dbmazz runs,
+    /// checked and configured as at startup (`ReloadConfig`).
+    pub async fn add_tables(&self) -> Result<(), SetupError> {
+        self.verify_tables_exist().await?;
+        self.verify_binary_types().await?;
+        self.ensure_replica_identity().await?;
+        self.ensure_publications().await
+    }
+
+    /// Drops `tables` (`schema.table`) from whichever of the publications
+    /// holds them.
+    pub async fn remove_tables(&self, tables: &[String]) -> Result<(), SetupError> {
+        let names: Vec<String> = self
+            .config
+            .publications
+            .iter()
+            .map(|p| p.name.clone())
+            .collect();
+        for table in tables {
+            let (schema, name) = table.split_once('.').unwrap_or(("public", table));
+            let rows = self
+                .client
+                .query(
+                    "SELECT pubname::text FROM pg_publication_tables
+                     WHERE pubname = ANY($1) AND schemaname = $2 AND tablename = $3",
+                    &[&names, &schema, &name],
+                )
+                .await
+                .map_err(|e| SetupError::PgPublicationFailed {
+                    name: self.config.publication_name.clone(),
+                    error: pg_error_message(&e),
+                })?;
+            for row in rows {
+                let pub_name: String = row.get(0);
+                validate_sql_identifier(&pub_name).map_err(|e| {
+                    SetupError::PgPublicationFailed {
+                        name: pub_name.clone(),
+                        error: format!("Invalid publication name: {}", e),
+                    }
+                })?;
+                info!("  Dropping {} from publication {}", table, pub_name);
+                self.client
+                    .execute(
+                        &format!(
+                            "ALTER PUBLICATION {} DROP TABLE {}",
+                            pub_name,
+                            quote_ident(table)
+                        ),
+                        &[],
+                    )
+                    .await
+                    .map_err(|e| SetupError::PgPublicationFailed {
+                        name: pub_name.clone(),
+                        error: pg_error_message(&e),
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the read-your-writes probe table when the probe is enabled
+    async fn ensure_probe_table(&self) -> Result<(), SetupError> {
+        let Some(probe) = &self.config.probe else {
+            return Ok(());
+        };
+        self.client
+            .execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (
+                        id integer PRIMARY KEY,
+                        sent_ms bigint NOT NULL,
+                        sent_at timestamptz NOT NULL
+                    )",
+                    quote_ident(&probe.table)
+                ),
+                &[],
+            )
+            .await
+            .map_err(|e| SetupError::PgProbeTableFailed {
+                table: probe.table.clone(),
+                error: pg_error_message(&e),
+            })?;
+        info!("  [OK] Probe table {} ready", probe.table);
+        Ok(())
+    }
+
     /// Verify that all tables exist
     async fn verify_tables_exist(&self) -> Result<(), SetupError> {
         for table in &self.config.tables {
@@ -88,15 +180,46 @@ impl<'a> PostgresSetup<'a> {
         Ok(())
     }
 
+    /// With `SOURCE_BINARY_TUPLES`, check every column has a type whose
+    /// binary format can be decoded
+    async fn verify_binary_types(&self) -> Result<(), SetupError> {
+        if !self.config.binary_tuples {
+            return Ok(());
+        }
+        for table in &self.config.tables {
+            let rows = self
+                .client
+                .query(
+                    "SELECT a.attname::text, a.atttypid, format_type(a.atttypid, a.atttypmod)
+                     FROM pg_attribute a
+                     WHERE a.attrelid = $1::text::regclass AND a.attnum > 0 AND NOT a.attisdropped
+                     ORDER BY a.attnum",
+                    &[&quote_ident(table)],
+                )
+                .await
+                .map_err(|e| SetupError::PgConnectionFailed {
+                    host: "PostgreSQL".to_string(),
+                    error: pg_error_message(&e),
+                })?;
+            let unsupported: Vec<String> = rows
+                .iter()
+                .filter(|row| !binary::is_supported(row.get::<_, u32>(1)))
+                .map(|row| format!("{} ({})", row.get::<_, String>(0), row.get::<_, String>(2)))
+                .collect();
+            if !unsupported.is_empty() {
+                return Err(SetupError::PgBinaryTypesUnsupported {
+                    table: table.clone(),
+                    columns: unsupported.join(", "),
+                });
+            }
+        }
+        info!("  [OK] Column types can be streamed in binary format");
+        Ok(())
+    }
+
     /// Configure REPLICA IDENTITY FULL on all tables
     async fn ensure_replica_identity(&self) -> Result<(), SetupError> {
         for table in &self.config.tables {
-            // Validate table name to prevent SQL injection
-            validate_sql_identifier(table).map_err(|e| SetupError::PgConnectionFailed {
-                host: "PostgreSQL".to_string(),
-                error: format!("Invalid table name: {}", e),
-            })?;
-
             let parts: Vec<&str> = table.split('.').collect();
             let schema = if parts.len() > 1 { parts[0] } else { "public" };
             let table_name = if parts.len() > 1 { parts[1] } else { parts[0] };
@@ -124,7 +247,10 @@ impl<'a> PostgresSetup<'a> {
             if identity_char != 'f' {
                 info!("  Setting REPLICA IDENTITY FULL on {}", table);
                 self.client
-                    .execute(&format!("ALTER TABLE {} REPLICA IDENTITY FULL", table), &[])
+                    .execute(
+                        &format!("ALTER TABLE {} REPLICA IDENTITY FULL", quote_ident(table)),
+                        &[],
+                    )
                     .await
                     .map_err(|e| SetupError::PgReplicaIdentityFailed {
                         table: table.clone(),
@@ -138,15 +264,47 @@ impl<'a> PostgresSetup<'a> {
         Ok(())
     }
 
-    /// Create/verify Publication
-    async fn ensure_publication(&self) -> Result<(), SetupError> {
-        let pub_name = &self.config.publication_name;
+    /// Create/verify the publications. A table of `TABLES` that is already in
+    /// one of them stays there; the others are added to the publication
+    /// `PUBLICATION_<NAME>_TABLES` assigns them to.
+    async fn ensure_publications(&self) -> Result<(), SetupError> {
+        let existing = self.get_publication_tables().await?;
+        for publication in &self.config.publications {
+            let missing: Vec<String> = self
+                .config
+                .tables
+                .iter()
+                .filter(|table| self.config.publication_of(table).name == publication.name)
+                .filter(|table| {
+                    // Normalize names for comparison
+                    let normalized = if table.contains('.') {
+                        table.to_string()
+                    } else {
+                        format!("public.{}", table)
+                    };
+                    !existing.contains(&normalized) && !existing.contains(table)
+                })
+                .cloned()
+                .collect();
+            self.ensure_publication(&publication.name, &missing).await?;
+        }
+        Ok(())
+    }
+
+    /// Create/verify Publication, adding `missing` to it
+    async fn ensure_publication(
+        &self,
+        pub_name: &str,
+        missing: &[String],
+    ) -> Result<(), SetupError> {
+        let publication_failed = |error: String| SetupError::PgPublicationFailed {
+            name: pub_name.to_string(),
+            error,
+        };
 
         // Validate publication name to prevent SQL injection
-        validate_sql_identifier(pub_name).map_err(|e| SetupError::PgPublicationFailed {
-            name: pub_name.clone(),
-            error: format!("Invalid publication name: {}", e),
-        })?;
+        validate_sql_identifier(pub_name)
+            .map_err(|e| publication_failed(format!("Invalid publication name: {}", e)))?;
 
         // Check if it exists
         let exists: bool = self
@@ -156,104 +314,75 @@ impl<'a> PostgresSetup<'a> {
                 &[&pub_name],
             )
             .await
-            .map_err(|e| SetupError::PgPublicationFailed {
-                name: pub_name.clone(),
-                error: pg_error_message(&e),
-            })?
+            .map_err(|e| publication_failed(pg_error_message(&e)))?
             .get(0);
 
         if exists {
             info!("  [OK] Publication {} exists", pub_name);
 
-            // Verify that it includes all tables
-            let missing = self.get_missing_tables_in_publication(pub_name).await?;
-
             for table in missing {
-                // Validate each table name before adding
-                validate_sql_identifier(&table).map_err(|e| SetupError::PgPublicationFailed {
-                    name: pub_name.clone(),
-                    error: format!("Invalid table name '{}': {}", table, e),
-                })?;
-
                 info!("  Adding {} to publication {}", table, pub_name);
                 self.client
                     .execute(
-                        &format!("ALTER PUBLICATION {} ADD TABLE {}", pub_name, table),
+                        &format!(
+                            "ALTER PUBLICATION {} ADD TABLE {}",
+                            pub_name,
+                            quote_ident(table)
+                        ),
                         &[],
                     )
                     .await
-                    .map_err(|e| SetupError::PgPublicationFailed {
-                        name: pub_name.clone(),
-                        error: pg_error_message(&e),
-                    })?;
+                    .map_err(|e| publication_failed(pg_error_message(&e)))?;
                 info!("  [OK] Table {} added to publication", table);
             }
         } else {
-            // Validate all table names before creating publication
-            for table in &self.config.tables {
-                validate_sql_identifier(table).map_err(|e| SetupError::PgPublicationFailed {
-                    name: pub_name.clone(),
-                    error: format!("Invalid table name '{}': {}", table, e),
-                })?;
-            }
-
-            // Create new publication
+            // Create new publication; table names are quoted, so
+            // mixed-case names keep their case. With no table of its own
+            // it starts empty, for tables added later.
             info!("  Creating publication {}", pub_name);
-            let tables = self.config.tables.join(", ");
-            self.client
-                .execute(
-                    &format!("CREATE PUBLICATION {} FOR TABLE {}", pub_name, tables),
-                    &[],
+            let tables: Vec<String> = missing.iter().map(|t| quote_ident(t)).collect();
+            let query = if tables.is_empty() {
+                format!("CREATE PUBLICATION {}", pub_name)
+            } else {
+                format!(
+                    "CREATE PUBLICATION {} FOR TABLE {}",
+                    pub_name,
+                    tables.join(", ")
                 )
+            };
+            self.client
+                .execute(&query, &[])
                 .await
-                .map_err(|e| SetupError::PgPublicationFailed {
-                    name: pub_name.clone(),
-                    error: pg_error_message(&e),
-                })?;
+                .map_err(|e| publication_failed(pg_error_message(&e)))?;
             info!("  [OK] Publication {} created", pub_name);
         }
 
         Ok(())
     }
 
-    /// Get tables missing from the publication
-    async fn get_missing_tables_in_publication(
-        &self,
-        pub_name: &str,
-    ) -> Result<Vec<String>, SetupError> {
+    /// Tables already in any of the publications, as `schema.table`
+    async fn get_publication_tables(&self) -> Result<Vec<String>, SetupError> {
+        let names: Vec<String> = self
+            .config
+            .publications
+            .iter()
+            .map(|p| p.name.clone())
+            .collect();
         let rows = self
             .client
             .query(
                 "SELECT schemaname || '.' || tablename as full_name
                  FROM pg_publication_tables 
-                 WHERE pubname = $1",
-                &[&pub_name],
+                 WHERE pubname = ANY($1)",
+                &[&names],
             )
             .await
             .map_err(|e| SetupError::PgPublicationFailed {
-                name: pub_name.to_string(),
+                name: self.config.publication_name.clone(),
                 error: pg_error_message(&e),
             })?;
 
-        let existing: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
-
-        let missing: Vec<String> = self
-            .config
-            .tables
-            .iter()
-            .filter(|table| {
-                // Normalize names for comparison
-                let normalized = if table.contains('.') {
-                    table.to_string()
-                } else {
-                    format!("public.{}", table)
-                };
-                !existing.contains(&normalized) && !existing.contains(table)
-            })
-            .cloned()
-            .collect();
-
-        Ok(missing)
+        Ok(rows.iter().map(|row| row.get(0)).collect())
     }
 
     /// Create/verify Replication Slot
@@ -300,18 +429,7 @@ impl<'a> PostgresSetup<'a> {
                                 pg_error_message(&e)
                             ),
                         })?;
-                    info!("  Creating replication slot {}", slot_name);
-                    self.client
-                        .execute(
-                            "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
-                            &[&slot_name],
-                        )
-                        .await
-                        .map_err(|e| SetupError::PgSlotFailed {
-                            name: slot_name.clone(),
-                            error: pg_error_message(&e),
-                        })?;
-                    info!("  [OK] Replication slot {} created", slot_name);
+                    self.create_replication_slot().await?;
 
                     // Clear stale snapshot state — the old slot's progress is invalid
                     self.clear_snapshot_state(slot_name).await;
@@ -319,18 +437,7 @@ impl<'a> PostgresSetup<'a> {
             }
             None => {
                 // Slot doesn't exist - create it
-                info!("  Creating replication slot {}", slot_name);
-                self.client
-                    .execute(
-                        "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
-                        &[&slot_name],
-                    )
-                    .await
-                    .map_err(|e| SetupError::PgSlotFailed {
-                        name: slot_name.clone(),
-                        error: pg_error_message(&e),
-                    })?;
-                info!("  [OK] Replication slot {} created", slot_name);
+                self.create_replication_slot().await?;
 
                 // Clear stale snapshot state — new slot means fresh start
                 self.clear_snapshot_state(slot_name).await;
@@ -340,6 +447,33 @@ impl<'a> PostgresSetup<'a> {
         Ok(())
     }
 
+    /// Creates the replication slot. With `SNAPSHOT_METHOD=export` the source
+    /// creates it instead, on its replication connection, so the snapshot it
+    /// exports can be copied before streaming.
+    async fn create_replication_slot(&self) -> Result<(), SetupError> {
+        let slot_name = &self.config.slot_name;
+        if self.config.exports_snapshot() {
+            info!(
+                "  Replication slot {} will be created with an exported snapshot",
+                slot_name
+            );
+            return Ok(());
+        }
+        info!("  Creating replication slot {}", slot_name);
+        self.client
+            .execute(
+                "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
+                &[&slot_name],
+            )
+            .await
+            .map_err(|e| SetupError::PgSlotFailed {
+                name: slot_name.clone(),
+                error: pg_error_message(&e),
+            })?;
+        info!("  [OK] Replication slot {} created", slot_name);
+        Ok(())
+    }
+
     /// Clear snapshot state for a slot when the slot is (re)created.
     /// A new replication slot means the snapshot must run from scratch;
     /// stale COMPLETE records would cause the snapshot worker to skip all chunks.
@@ -365,39 +499,35 @@ impl<'a> PostgresSetup<'a> {
 }
 
 /// Helper to create normal PostgreSQL client (non-replication)
-pub async fn create_postgres_client(database_url: &str) -> Result<Client, SetupError> {
+pub async fn create_postgres_client(
+    session: &PgSession,
+    database_url: &str,
+) -> Result<Client, SetupError> {
     // Remove replication parameter for normal connection
     let clean_url = database_url
         .replace("?replication=database", "")
         .replace("&replication=database", "")
         .replace("replication=database&", "");
 
-    let (client, connection) = tokio_postgres::connect(&clean_url, NoTls)
+    session
+        .connect_helper(&clean_url, "PostgreSQL setup")
         .await
         .map_err(|e| SetupError::PgConnectionFailed {
             host: "PostgreSQL".to_string(),
             error: pg_error_message(&e),
-        })?;
-
-    // Spawn connection in background
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            info!("PostgreSQL setup connection error: {}", e);
-        }
-    });
-
-    Ok(client)
+        })
 }
 
 /// Cleanup PostgreSQL resources on daemon shutdown
 /// Drops the replication slot to free the resource
 pub async fn cleanup_postgres_resources(
+    session: &PgSession,
     database_url: &str,
     slot_name: &str,
 ) -> Result<(), SetupError> {
     info!("Cleaning up PostgreSQL resources...");
 
-    let client = create_postgres_client(database_url).await?;
+    let client = create_postgres_client(session, database_url).await?;
 
     // Check if slot exists and get info
     let slot_info = client
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use tracing::info;
+
+use super::catalog::{KeyOrigin, SinkKey, SourceTable};
+use super::error::SetupError;
+use crate::config::Config;
+use crate::connectors::sinks::sqlite::types::pg_type_name_to_affinity;
+use crate::connectors::sinks::sqlite::{create_documented_table_sql, open_database, sqlite_path};
+
+/// Prepares the SQLite file: creates every replicated table that doesn't
+/// exist yet, mirroring the source columns and comments. The primary key is
+/// the sink key chosen from the source catalog (see `SourceTable::sink_key`),
+/// so the sink can upsert by key from the first event.
+pub struct SqliteSetup<'a> {
+    tables: &'a [SourceTable],
+    config: &'a Config,
+}
+
+impl<'a> SqliteSetup<'a> {
+    pub fn new(tables: &'a [SourceTable], config: &'a Config) -> Self {
+        Self { tables, config }
+    }
+
+    /// Execute complete SQLite setup.
+    pub async fn run(&self) -> Result<(), SetupError> {
+        info!("SQLite Setup:");
+
+        // 1. Build DDL from the source catalog
+        let mut statements = Vec::with_capacity(self.tables.len());
+        for table in self.tables {
+            let key = table.sink_key(&self.config.sink_key_columns)?;
+            match &key {
+                None => info!(
+                    "  {} has no primary key or NOT NULL unique index, rows will be appended instead of upserted",
+                    table.qualified_name()
+                ),
+                Some(k) if k.origin != KeyOrigin::PrimaryKey => info!(
+                    "  {} keyed by ({}) from {}",
+                    table.qualified_name(),
+                    k.columns.join(", "),
+                    k.origin
+                ),
+                Some(_) => {}
+            }
+            let name = self
+                .config
+                .sink
+                .naming
+                .sink_table(&table.qualified_name())
+                .table;
+            let sql = create_table_ddl(&name, table, key.as_ref());
+            statements.push((name, sql));
+        }
+
+        // 2. Apply it on the blocking pool (rusqlite is synchronous)
+        let path = sqlite_path(&self.config.sink.url).to_string();
+        let task_path = path.clone();
+        let created = tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+            let conn = open_database(&task_path)?;
+            let mut tables = Vec::with_capacity(statements.len());
+            for (table, sql) in statements {
+                conn.execute(&sql, [])?;
+                tables.push(table);
+            }
+            Ok(tables)
+        })
+        .await
+        .map_err(|e| self.sqlite_error(&path, e.to_string()))?
+        .map_err(|e| self.sqlite_error(&path, e.to_string()))?;
+
+        for table in created {
+            info!("  [OK] Table {} ready in {}", table, path);
+        }
+
+        info!("[OK] SQLite setup complete");
+        Ok(())
+    }
+
+    fn sqlite_error(&self, path: &str, error: String) -> SetupError {
+        SetupError::SqliteFailed {
+            path: path.to_string(),
+            error,
+        }
+    }
+}
+
+/// `CREATE TABLE` for a source table, mapping PostgreSQL types to SQLite
+/// affinities and carrying over the source comments.
+fn create_table_ddl(name: &str, table: &SourceTable, key: Option<&SinkKey>) -> String {
+    let columns: Vec<(String, &str)> = table
+        .columns
+        .iter()
+        .map(|c| (c.name.clone(), pg_type_name_to_affinity(&c.data_type)))
+        .collect();
+    let comments: Vec<Option<&str>> = table.columns.iter().map(|c| c.comment.as_deref()).collect();
+    create_documented_table_sql(
+        name,
+        &columns,
+        key.map(|k| k.columns.as_slice()).unwrap_or_default(),
+        table.comment.as_deref(),
+        &comments,
+    )
+}
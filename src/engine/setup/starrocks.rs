@@ -1,15 +1,23 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use anyhow::Result;
 use mysql_async::{prelude::Queryable, Conn, Pool};
-use tracing::info;
+use tracing::{info, warn};
 
+use super::catalog::{SinkKey, SourceTable};
 use super::error::SetupError;
-use crate::config::Config;
-use crate::utils::validate_sql_identifier;
+use crate::config::{Config, DdlTemplate, TableModel};
+use crate::connectors::sinks::starrocks::types::TypeMapper;
+use crate::utils::{escape_sql_string, validate_sql_identifier};
+
+/// StarRocks types allowed in primary key columns; the source type is used as is
+const KEY_TYPES: &[&str] = &["BOOLEAN", "SMALLINT", "INT", "BIGINT", "DATE", "DATETIME"];
+
+/// Text key columns are VARCHAR: StarRocks caps a primary key at 128 bytes by default
+pub(crate) const KEY_VARCHAR: &str = "VARCHAR(128)";
 
 /// CDC audit columns that must exist in StarRocks
-const AUDIT_COLUMNS: &[(&str, &str)] = &[
+pub(crate) const AUDIT_COLUMNS: &[(&str, &str)] = &[
     (
         "dbmazz_op_type",
         "TINYINT COMMENT '0=INSERT, 1=UPDATE, 2=DELETE'",
@@ -30,7 +38,7 @@ impl<'a> StarRocksSetup<'a> {
     }
 
     /// Execute complete StarRocks setup.
-    pub async fn run(&self) -> Result<(), SetupError> {
+    pub async fn run(&self, tables: &[SourceTable]) -> Result<(), SetupError> {
         info!("StarRocks Setup:");
 
         let mut conn = self.get_conn().await?;
@@ -42,39 +50,109 @@ impl<'a> StarRocksSetup<'a> {
             .map_err(|e| self.sr_error(e.to_string()))?;
         info!("  [OK] StarRocks connection OK");
 
-        // 2. Verify all tables exist
-        self.verify_tables_exist(&mut conn).await?;
+        // 2. With SINK_SCHEMA_MODE=preserve or TABLE_RENAMES, tables may live
+        // in databases of their own
+        if self.config.auto_create_tables && self.config.sink.naming.uses_databases() {
+            self.create_databases(&mut conn).await?;
+        }
+
+        // 3. Verify all tables exist, creating missing ones when enabled
+        let missing = self.verify_tables_exist(&mut conn).await?;
+        for table in tables
+            .iter()
+            .filter(|t| missing.contains(&self.destination(&t.qualified_name())))
+        {
+            self.create_table(&mut conn, table).await?;
+        }
 
-        // 3. Batch ensure audit columns
-        let tables: Vec<&str> = self
+        // 4. Warn when an existing table's key differs from the source key
+        self.check_table_keys(&mut conn, tables, &missing).await?;
+
+        // 5. Batch ensure audit columns
+        let destinations: Vec<(String, String)> = self
             .config
             .tables
             .iter()
-            .map(|t| t.split('.').next_back().unwrap_or(t))
+            .map(|t| self.destination(t))
             .collect();
 
-        self.ensure_audit_columns_batch(&mut conn, &tables).await?;
+        self.ensure_audit_columns_batch(&mut conn, &destinations)
+            .await?;
 
         info!("[OK] StarRocks setup complete");
         Ok(())
     }
 
-    /// Verify that all configured tables exist in StarRocks.
-    async fn verify_tables_exist(&self, conn: &mut Conn) -> Result<(), SetupError> {
-        let rows: Vec<(String,)> = conn
+    /// StarRocks database and table a configured or qualified source table
+    /// is replicated to, following `SINK_SCHEMA_MODE` and `TABLE_RENAMES`.
+    fn destination(&self, table: &str) -> (String, String) {
+        let sink = self.config.sink.naming.sink_table(table);
+        let database = sink
+            .database
+            .unwrap_or_else(|| self.config.starrocks_db.clone());
+        (database, sink.table)
+    }
+
+    /// `table_schema IN (...)` over every database the tables map to, with
+    /// its parameters.
+    fn schema_filter(&self) -> (String, Vec<String>) {
+        let databases: BTreeSet<String> = self
+            .config
+            .tables
+            .iter()
+            .map(|t| self.destination(t).0)
+            .collect();
+        let placeholders = vec!["?"; databases.len()].join(", ");
+        (
+            format!("table_schema IN ({})", placeholders),
+            databases.into_iter().collect(),
+        )
+    }
+
+    /// Creates the databases of `SINK_SCHEMA_MODE=preserve` and `TABLE_RENAMES`.
+    async fn create_databases(&self, conn: &mut Conn) -> Result<(), SetupError> {
+        let (_, databases) = self.schema_filter();
+        for database in databases {
+            validate_sql_identifier(&database)
+                .map_err(|e| self.sr_error(format!("Invalid database name: {}", e)))?;
+            conn.query_drop(format!("CREATE DATABASE IF NOT EXISTS `{}`", database))
+                .await
+                .map_err(|e| self.sr_error(e.to_string()))?;
+            info!("  [OK] Database {} ready", database);
+        }
+        Ok(())
+    }
+
+    /// Verify that all configured tables exist in StarRocks. Returns the
+    /// missing ones when `SINK_AUTO_CREATE_TABLES` is set, fails otherwise.
+    async fn verify_tables_exist(
+        &self,
+        conn: &mut Conn,
+    ) -> Result<HashSet<(String, String)>, SetupError> {
+        let (filter, params) = self.schema_filter();
+        let rows: Vec<(String, String)> = conn
             .exec(
-                "SELECT table_name FROM information_schema.tables WHERE table_schema = ?",
-                (&self.config.starrocks_db,),
+                format!(
+                    "SELECT table_schema, table_name FROM information_schema.tables WHERE {}",
+                    filter
+                ),
+                params,
             )
             .await
             .map_err(|e| self.sr_error(e.to_string()))?;
 
-        let existing: HashSet<String> = rows.into_iter().map(|(n,)| n).collect();
+        let existing: HashSet<(String, String)> = rows.into_iter().collect();
 
+        let mut missing = HashSet::new();
         for table in &self.config.tables {
-            let table_name = table.split('.').next_back().unwrap_or(table);
-            if existing.contains(table_name) {
-                info!("  [OK] Table {} exists in StarRocks", table_name);
+            let destination = self.destination(table);
+            if existing.contains(&destination) {
+                info!(
+                    "  [OK] Table {}.{} exists in StarRocks",
+                    destination.0, destination.1
+                );
+            } else if self.config.auto_create_tables {
+                missing.insert(destination);
             } else {
                 return Err(SetupError::SrTableNotFound {
                     table: table.clone(),
@@ -82,6 +160,112 @@ impl<'a> StarRocksSetup<'a> {
             }
         }
 
+        Ok(missing)
+    }
+
+    /// Creates a missing table with the model chosen by [`table_model`],
+    /// from the table's DDL template if there is one.
+    async fn create_table(&self, conn: &mut Conn, table: &SourceTable) -> Result<(), SetupError> {
+        let (database, name) = self.destination(&table.qualified_name());
+        let key = table.sink_key(&self.config.sink_key_columns)?;
+        let model = table_model(table, key.as_ref(), &self.config.sink_table_models);
+        let template = table
+            .table_setting(&self.config.sink_table_ddl_templates)
+            .or(self.config.sink_ddl_template.as_ref());
+        match &key {
+            Some(key) => info!(
+                "  Creating {}.{} as a {} table keyed by ({}) from {}",
+                database,
+                name,
+                model,
+                key.columns.join(", "),
+                key.origin
+            ),
+            None => info!(
+                "  Creating {}.{} as a {} table without a key (no primary key or NOT NULL unique index)",
+                database, name, model
+            ),
+        }
+
+        if let Some(template) = template {
+            info!("  Using the DDL template {}", template.path);
+        }
+
+        let mapper = TypeMapper::new().with_bool_encoding(self.config.sink.bool_encoding);
+        let sql = create_table_sql(
+            &database,
+            &name,
+            table,
+            key.as_ref(),
+            model,
+            &mapper,
+            template,
+        )?;
+        conn.query_drop(sql)
+            .await
+            .map_err(|e| SetupError::SrCreateTableFailed {
+                table: name.clone(),
+                error: e.to_string(),
+            })?;
+        info!("  [OK] Table {}.{} created", database, name);
+        Ok(())
+    }
+
+    /// Compares each existing table's key columns with the sink key chosen
+    /// from the source catalog. A mismatch only warns: the table may have
+    /// been modelled that way on purpose.
+    async fn check_table_keys(
+        &self,
+        conn: &mut Conn,
+        tables: &[SourceTable],
+        created: &HashSet<(String, String)>,
+    ) -> Result<(), SetupError> {
+        let (filter, params) = self.schema_filter();
+        let rows: Vec<(String, String, String, Option<String>)> = conn
+            .exec(
+                format!(
+                    "SELECT table_schema, table_name, column_name, column_key
+                     FROM information_schema.columns
+                     WHERE {} ORDER BY table_schema, table_name, ordinal_position",
+                    filter
+                ),
+                params,
+            )
+            .await
+            .map_err(|e| self.sr_error(e.to_string()))?;
+
+        let mut keys: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+        for (database, table, column, column_key) in &rows {
+            if column_key.as_deref().is_some_and(|k| !k.is_empty()) {
+                keys.entry((database.as_str(), table.as_str()))
+                    .or_default()
+                    .push(column.as_str());
+            }
+        }
+
+        for table in tables {
+            let (database, name) = self.destination(&table.qualified_name());
+            if created.contains(&(database.clone(), name.clone())) {
+                continue;
+            }
+            let Some(key) = table.sink_key(&self.config.sink_key_columns)? else {
+                continue;
+            };
+            let dest_key = keys
+                .get(&(database.as_str(), name.as_str()))
+                .cloned()
+                .unwrap_or_default();
+            if dest_key != key.columns {
+                warn!(
+                    "  Table {}.{} is keyed by ({}) in StarRocks but the source {} is ({}); updates may duplicate rows",
+                    database,
+                    name,
+                    dest_key.join(", "),
+                    key.origin,
+                    key.columns.join(", ")
+                );
+            }
+        }
         Ok(())
     }
 
@@ -106,36 +290,39 @@ impl<'a> StarRocksSetup<'a> {
     async fn ensure_audit_columns_batch(
         &self,
         conn: &mut Conn,
-        tables: &[&str],
+        tables: &[(String, String)],
     ) -> Result<(), SetupError> {
-        validate_sql_identifier(&self.config.starrocks_db)
-            .map_err(|e| self.sr_error(format!("Invalid database name: {}", e)))?;
-
         // 1 query: get all columns for all tables at once
-        let rows: Vec<(String, String)> = conn
+        let (filter, params) = self.schema_filter();
+        let rows: Vec<(String, String, String)> = conn
             .exec(
-                "SELECT table_name, COLUMN_NAME FROM information_schema.columns
-                 WHERE table_schema = ?",
-                (&self.config.starrocks_db,),
+                format!(
+                    "SELECT table_schema, table_name, COLUMN_NAME FROM information_schema.columns
+                     WHERE {}",
+                    filter
+                ),
+                params,
             )
             .await
             .map_err(|e| self.sr_error(e.to_string()))?;
 
-        // Build lookup: table_name -> set of column names
-        let mut table_columns: HashMap<&str, HashSet<String>> = HashMap::new();
-        for (tbl, col) in &rows {
+        // Build lookup: (database, table) -> set of column names
+        let mut table_columns: HashMap<(&str, &str), HashSet<String>> = HashMap::new();
+        for (db, tbl, col) in &rows {
             table_columns
-                .entry(tbl.as_str())
+                .entry((db.as_str(), tbl.as_str()))
                 .or_default()
                 .insert(col.clone());
         }
 
         // Only ALTER what's actually missing
-        for table in tables {
+        for (db, table) in tables {
+            validate_sql_identifier(db)
+                .map_err(|e| self.sr_error(format!("Invalid database name: {}", e)))?;
             validate_sql_identifier(table)
                 .map_err(|e| self.sr_error(format!("Invalid table name: {}", e)))?;
 
-            let existing = table_columns.get(table);
+            let existing = table_columns.get(&(db.as_str(), table.as_str()));
 
             for (col_name, col_def) in AUDIT_COLUMNS {
                 let has_col = existing.is_some_and(|cols| cols.contains(*col_name));
@@ -144,7 +331,7 @@ impl<'a> StarRocksSetup<'a> {
                     info!("  Adding audit column {} to {}", col_name, table);
                     let sql = format!(
                         "ALTER TABLE `{}`.`{}` ADD COLUMN `{}` {}",
-                        self.config.starrocks_db, table, col_name, col_def
+                        db, table, col_name, col_def
                     );
                     conn.query_drop(sql)
                         .await
@@ -159,6 +346,255 @@ impl<'a> StarRocksSetup<'a> {
 
         Ok(())
     }
+
+    /// Copies source table and column comments onto the StarRocks tables
+    /// (`SYNC_COLUMN_COMMENTS=true`). Only differing comments are altered.
+    /// Best effort: a comment change StarRocks rejects is logged and skipped.
+    pub async fn sync_comments(&self, tables: &[SourceTable]) -> Result<(), SetupError> {
+        let mut conn = self.get_conn().await?;
+        let (filter, params) = self.schema_filter();
+
+        let table_rows: Vec<(String, String, Option<String>)> = conn
+            .exec(
+                format!(
+                    "SELECT table_schema, table_name, table_comment FROM information_schema.tables
+                     WHERE {}",
+                    filter
+                ),
+                params.clone(),
+            )
+            .await
+            .map_err(|e| self.sr_error(e.to_string()))?;
+        let column_rows: Vec<(String, String, String, Option<String>)> = conn
+            .exec(
+                format!(
+                    "SELECT table_schema, table_name, column_name, column_comment
+                     FROM information_schema.columns WHERE {}",
+                    filter
+                ),
+                params,
+            )
+            .await
+            .map_err(|e| self.sr_error(e.to_string()))?;
+
+        let table_comments: HashMap<(&str, &str), &str> = table_rows
+            .iter()
+            .map(|(db, t, c)| ((db.as_str(), t.as_str()), c.as_deref().unwrap_or("")))
+            .collect();
+        let column_comments: HashMap<(&str, &str, &str), &str> = column_rows
+            .iter()
+            .map(|(db, t, col, c)| {
+                (
+                    (db.as_str(), t.as_str(), col.as_str()),
+                    c.as_deref().unwrap_or(""),
+                )
+            })
+            .collect();
+
+        let mut statements = Vec::new();
+        for table in tables {
+            let (db, name) = self.destination(&table.qualified_name());
+            if let Some(comment) = &table.comment {
+                if table_comments.get(&(db.as_str(), name.as_str())) != Some(&comment.as_str()) {
+                    statements.push(format!(
+                        "ALTER TABLE `{}`.`{}` COMMENT = '{}'",
+                        db,
+                        name,
+                        escape_sql_string(comment)
+                    ));
+                }
+            }
+            for column in &table.columns {
+                let Some(comment) = &column.comment else {
+                    continue;
+                };
+                // Columns missing in StarRocks are left to schema evolution
+                match column_comments.get(&(db.as_str(), name.as_str(), column.name.as_str())) {
+                    Some(current) if *current != comment => statements.push(format!(
+                        "ALTER TABLE `{}`.`{}` MODIFY COLUMN `{}` COMMENT '{}'",
+                        db,
+                        name,
+                        column.name.replace('`', "``"),
+                        escape_sql_string(comment)
+                    )),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut synced = 0;
+        for sql in &statements {
+            match conn.query_drop(sql).await {
+                Ok(()) => synced += 1,
+                Err(e) => warn!("  Could not sync comment ({}): {}", sql, e),
+            }
+        }
+        info!(
+            "  [OK] Comments synced ({} of {} changes applied)",
+            synced,
+            statements.len()
+        );
+        Ok(())
+    }
+}
+
+/// `CREATE TABLE` for the given table model. StarRocks requires the key
+/// columns first, in key order, and NOT NULL. Primary key and aggregate tables
+/// need a key; a duplicate table without one is distributed randomly. A
+/// template gets the same parts to lay out with its own properties.
+fn create_table_sql(
+    db: &str,
+    name: &str,
+    table: &SourceTable,
+    key: Option<&SinkKey>,
+    model: TableModel,
+    mapper: &TypeMapper,
+    template: Option<&DdlTemplate>,
+) -> Result<String, SetupError> {
+    validate_sql_identifier(name).map_err(|e| SetupError::SrCreateTableFailed {
+        table: name.to_string(),
+        error: format!("Invalid table name: {}", e),
+    })?;
+    if key.is_none() && model != TableModel::Duplicate {
+        return Err(SetupError::SinkKeyInvalid {
+            table: table.qualified_name(),
+            error: format!(
+                "a {} table needs a primary key or NOT NULL unique index; set SINK_KEY_COLUMNS",
+                model
+            ),
+        });
+    }
+    let quote = |name: &str| format!("`{}`", name.replace('`', "``"));
+    // Aggregate tables keep the latest value of every non-key column
+    let aggregation = |sr_type: &str| match model {
+        TableModel::Aggregate => format!("{} REPLACE", sr_type),
+        _ => sr_type.to_string(),
+    };
+
+    let key_columns: &[String] = key.map_or(&[], |k| &k.columns);
+    let mut defs = Vec::with_capacity(table.columns.len() + AUDIT_COLUMNS.len());
+    for name in key_columns {
+        let Some(column) = table.column(name) else {
+            return Err(SetupError::SinkKeyInvalid {
+                table: table.qualified_name(),
+                error: format!("key column '{}' not found", name),
+            });
+        };
+        let sr_type = match mapper.pg_type_to_starrocks(column.type_oid) {
+            "STRING" => KEY_VARCHAR,
+            t if KEY_TYPES.contains(&t) => t,
+            t => {
+                return Err(SetupError::SinkKeyInvalid {
+                    table: table.qualified_name(),
+                    error: format!(
+                        "key column '{}' maps to {}, which StarRocks does not allow in a key",
+                        name, t
+                    ),
+                })
+            }
+        };
+        defs.push(column_def(
+            &quote(name),
+            sr_type,
+            false,
+            column.comment.as_deref(),
+        ));
+    }
+    for column in table
+        .columns
+        .iter()
+        .filter(|c| !key_columns.contains(&c.name))
+    {
+        defs.push(column_def(
+            &quote(&column.name),
+            &aggregation(mapper.pg_type_to_starrocks(column.type_oid)),
+            column.nullable,
+            column.comment.as_deref(),
+        ));
+    }
+    for (name, def) in AUDIT_COLUMNS {
+        let (sr_type, rest) = def.split_once(' ').unwrap_or((def, ""));
+        defs.push(format!("{} {} {}", quote(name), aggregation(sr_type), rest));
+    }
+
+    let keys = key_columns
+        .iter()
+        .map(|k| quote(k))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let qualified_name = format!("`{}`.{}", db, quote(name));
+    let columns = defs.join(",\n  ");
+    let key_clause = match model {
+        _ if keys.is_empty() => String::new(),
+        TableModel::PrimaryKey => format!("PRIMARY KEY ({})", keys),
+        TableModel::Duplicate => format!("DUPLICATE KEY ({})", keys),
+        TableModel::Aggregate => format!("AGGREGATE KEY ({})", keys),
+    };
+    let comment = table
+        .comment
+        .as_ref()
+        .map(|c| format!("COMMENT '{}'", escape_sql_string(c)));
+    let distribution = match keys.as_str() {
+        "" => "DISTRIBUTED BY RANDOM".to_string(),
+        keys => format!("DISTRIBUTED BY HASH({})", keys),
+    };
+
+    if let Some(template) = template {
+        return template
+            .render(|placeholder| match placeholder {
+                "table" => qualified_name.clone(),
+                "columns" => columns.clone(),
+                "key" => key_clause.clone(),
+                "key_columns" => keys.clone(),
+                "distribution" => distribution.clone(),
+                "comment" => comment.clone().unwrap_or_default(),
+                _ => String::new(),
+            })
+            .map_err(|e| SetupError::SrCreateTableFailed {
+                table: name.to_string(),
+                error: format!("DDL template {}: {}", template.path, e),
+            });
+    }
+
+    let mut sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n  {}\n)",
+        qualified_name, columns
+    );
+    if !key_clause.is_empty() {
+        sql.push_str(&format!(" {}", key_clause));
+    }
+    if let Some(comment) = &comment {
+        sql.push_str(&format!("\n{}", comment));
+    }
+    sql.push_str(&format!("\n{}", distribution));
+    Ok(sql)
+}
+
+/// Model of a table created by setup: `SINK_TABLE_MODELS`, else primary key
+/// when the source has a sink key, else duplicate.
+pub(crate) fn table_model(
+    table: &SourceTable,
+    key: Option<&SinkKey>,
+    models: &HashMap<String, TableModel>,
+) -> TableModel {
+    match (table.table_setting(models), key) {
+        (Some(model), _) => *model,
+        (None, Some(_)) => TableModel::PrimaryKey,
+        (None, None) => TableModel::Duplicate,
+    }
+}
+
+fn column_def(name: &str, sr_type: &str, nullable: bool, comment: Option<&str>) -> String {
+    let mut def = format!(
+        "{} {} {}",
+        name,
+        sr_type,
+        if nullable { "NULL" } else { "NOT NULL" }
+    );
+    if let Some(comment) = comment {
+        def.push_str(&format!(" COMMENT '{}'", escape_sql_string(comment)));
+    }
+    def
 }
 
 /// Helper to create StarRocks connection pool
@@ -182,3 +618,186 @@ pub fn create_starrocks_pool(config: &Config) -> Result<Pool, SetupError> {
 
     Ok(Pool::new(opts))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::setup::catalog::{KeyOrigin, SourceColumn};
+
+    fn column(name: &str, type_oid: u32, nullable: bool) -> SourceColumn {
+        SourceColumn {
+            name: name.into(),
+            data_type: String::new(),
+            type_oid,
+            nullable,
+            comment: None,
+        }
+    }
+
+    fn orders() -> SourceTable {
+        SourceTable {
+            schema: "public".into(),
+            name: "orders".into(),
+            comment: Some("Customer's orders".into()),
+            primary_key: vec![],
+            columns: vec![
+                column("total", 1700, true),
+                column("code", 25, false),
+                column("tenant_id", 20, false),
+            ],
+            indexes: vec![],
+            foreign_keys: vec![],
+        }
+    }
+
+    fn key(columns: &[&str]) -> SinkKey {
+        SinkKey {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            origin: KeyOrigin::Override,
+        }
+    }
+
+    #[test]
+    fn test_create_table_sql_puts_key_columns_first() {
+        let sql = create_table_sql(
+            "analytics",
+            "orders",
+            &orders(),
+            Some(&key(&["tenant_id", "code"])),
+            TableModel::PrimaryKey,
+            &TypeMapper::new(),
+            None,
+        )
+        .unwrap();
+        assert!(sql.starts_with(
+            "CREATE TABLE IF NOT EXISTS `analytics`.`orders` (\n  \
+             `tenant_id` BIGINT NOT NULL,\n  \
+             `code` VARCHAR(128) NOT NULL,\n  \
+             `total` DECIMAL(38,9) NULL,\n  \
+             `dbmazz_op_type` TINYINT"
+        ));
+        assert!(sql.contains(") PRIMARY KEY (`tenant_id`, `code`)"));
+        assert!(sql.contains("COMMENT 'Customer\\'s orders'"));
+        assert!(sql.ends_with("DISTRIBUTED BY HASH(`tenant_id`, `code`)"));
+    }
+
+    #[test]
+    fn test_create_table_sql_rejects_unsupported_key_type() {
+        let err = create_table_sql(
+            "analytics",
+            "orders",
+            &orders(),
+            Some(&key(&["total"])),
+            TableModel::PrimaryKey,
+            &TypeMapper::new(),
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("DECIMAL(38,9)"));
+    }
+
+    #[test]
+    fn test_create_table_sql_duplicate_without_key() {
+        let sql = create_table_sql(
+            "analytics",
+            "orders",
+            &orders(),
+            None,
+            TableModel::Duplicate,
+            &TypeMapper::new(),
+            None,
+        )
+        .unwrap();
+        assert!(sql.contains("`total` DECIMAL(38,9) NULL,"));
+        assert!(!sql.contains(" KEY ("));
+        assert!(sql.ends_with("DISTRIBUTED BY RANDOM"));
+
+        // Upserts need a key to upsert by
+        assert!(create_table_sql(
+            "analytics",
+            "orders",
+            &orders(),
+            None,
+            TableModel::PrimaryKey,
+            &TypeMapper::new(),
+            None,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_create_table_sql_aggregate_replaces_values() {
+        let sql = create_table_sql(
+            "analytics",
+            "orders",
+            &orders(),
+            Some(&key(&["tenant_id"])),
+            TableModel::Aggregate,
+            &TypeMapper::new(),
+            None,
+        )
+        .unwrap();
+        assert!(sql.contains("`tenant_id` BIGINT NOT NULL,"));
+        assert!(sql.contains("`total` DECIMAL(38,9) REPLACE NULL,"));
+        assert!(sql.contains("`dbmazz_synced_at` DATETIME REPLACE COMMENT 'Timestamp CDC'"));
+        assert!(sql.contains(") AGGREGATE KEY (`tenant_id`)"));
+        assert!(sql.ends_with("DISTRIBUTED BY HASH(`tenant_id`)"));
+    }
+
+    #[test]
+    fn test_create_table_sql_from_template() {
+        let template = DdlTemplate {
+            path: "orders.sql".to_string(),
+            text: "CREATE TABLE IF NOT EXISTS {table} (\n  {columns}\n) {key}\n{comment}\n\
+                   {distribution} BUCKETS 8\n\
+                   PROPERTIES (\"replication_num\" = \"3\", \"tags\" = \"{{a}}\")"
+                .to_string(),
+        };
+        let sql = create_table_sql(
+            "analytics",
+            "orders",
+            &orders(),
+            Some(&key(&["tenant_id", "code"])),
+            TableModel::PrimaryKey,
+            &TypeMapper::new(),
+            Some(&template),
+        )
+        .unwrap();
+        let default = create_table_sql(
+            "analytics",
+            "orders",
+            &orders(),
+            Some(&key(&["tenant_id", "code"])),
+            TableModel::PrimaryKey,
+            &TypeMapper::new(),
+            None,
+        )
+        .unwrap();
+        // The same table, with the template's properties
+        assert_eq!(
+            sql,
+            format!(
+                "{} BUCKETS 8\nPROPERTIES (\"replication_num\" = \"3\", \"tags\" = \"{{a}}\")",
+                default
+            )
+        );
+
+        let template = DdlTemplate {
+            path: "events.sql".to_string(),
+            text: "CREATE TABLE {table} ({columns}) {key}{distribution}".to_string(),
+        };
+        let sql = create_table_sql(
+            "analytics",
+            "orders",
+            &orders(),
+            None,
+            TableModel::Duplicate,
+            &TypeMapper::new(),
+            Some(&template),
+        )
+        .unwrap();
+        // No key: the key clause is empty
+        assert!(sql.starts_with("CREATE TABLE `analytics`.`orders` (`total` DECIMAL(38,9) NULL,"));
+        assert!(sql.ends_with(") DISTRIBUTED BY RANDOM"));
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Backfill-only mode: one-shot bulk load of the configured tables.
+//!
+//! Reuses the snapshot chunking and Stream Load path, but without anything
+//! replication-related: no slot or publication, no LW/HW watermarks and no
+//! `dbmazz_snapshot_state` bookkeeping. Rows are upserted, so re-running after
+//! a partial failure is safe.
+//!
+//! The run ends with a per-table summary. Each table gets an order-independent
+//! checksum (wrapping sum of a 64-bit FNV-1a hash of every row's text values),
+//! so two exports of the same data produce the same value regardless of chunk
+//! order or parallelism.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures::stream::{self, StreamExt};
+use tokio::sync::Mutex;
+use tokio_postgres::Client;
+use tracing::{error, info, warn};
+
+use super::chunker::{chunk_table, Chunk};
+use super::utils::find_integer_pk_column;
+use super::worker::{
+    chunk_select_query, get_columns, serialize_text_rows_to_json, strip_replication_param,
+    TableMeta,
+};
+use crate::config::{Config, SinkType};
+use crate::connectors::sinks::starrocks::stream_load::{StreamLoadClient, StreamLoadOptions};
+use crate::connectors::sinks::starrocks::StarRocksSinkConfig;
+use crate::source::session::PgSession;
+use crate::utils::{fnv1a, FNV_OFFSET_BASIS};
+
+/// Outcome of backfilling one table.
+#[derive(Debug, Clone, Default)]
+pub struct TableSummary {
+    pub table: String,
+    pub rows: u64,
+    pub chunks: u64,
+    pub failed_chunks: u64,
+    pub checksum: u64,
+    /// Reason the table was not loaded at all
+    pub skipped: Option<String>,
+}
+
+/// Outcome of a backfill-only run.
+#[derive(Debug, Clone)]
+pub struct BackfillSummary {
+    pub tables: Vec<TableSummary>,
+    pub duration: Duration,
+}
+
+impl BackfillSummary {
+    pub fn total_rows(&self) -> u64 {
+        self.tables.iter().map(|t| t.rows).sum()
+    }
+
+    /// True when every table was loaded without failed chunks.
+    pub fn is_complete(&self) -> bool {
+        self.tables
+            .iter()
+            .all(|t| t.skipped.is_none() && t.failed_chunks == 0)
+    }
+
+    /// Logs the summary table.
+    pub fn log(&self) {
+        info!("═══════════════════════════════════════");
+        info!("        BACKFILL SUMMARY");
+        info!("═══════════════════════════════════════");
+        for t in &self.tables {
+            match &t.skipped {
+                Some(reason) => info!("  {:<40} skipped: {}", t.table, reason),
+                None => info!(
+                    "  {:<40} rows={:<12} chunks={:<6} failed={:<4} checksum={:016x}",
+                    t.table, t.rows, t.chunks, t.failed_chunks, t.checksum
+                ),
+            }
+        }
+        let secs = self.duration.as_secs_f64();
+        info!(
+            "  Total: {} rows in {:.1}s ({:.0} rows/sec)",
+            self.total_rows(),
+            secs,
+            self.total_rows() as f64 / secs.max(0.001)
+        );
+    }
+}
+
+/// Loads every configured table into the sink once and returns the summary.
+///
+/// Chunk failures are recorded in the summary rather than aborting the run;
+/// connection and configuration errors are returned as `Err`.
+pub async fn run_backfill(config: &Config, session: &PgSession) -> Result<BackfillSummary> {
+    // Chunks are loaded through Stream Load, which only StarRocks speaks
+    if config.sink.sink_type != SinkType::StarRocks {
+        anyhow::bail!(
+            "backfill-only mode is not supported for the {} sink",
+            config.sink.sink_type
+        );
+    }
+
+    let started = Instant::now();
+    info!(
+        "Backfill starting: {} tables (chunk_size={}, workers={})",
+        config.tables.len(),
+        config.snapshot_chunk_size,
+        config.snapshot_parallel_workers
+    );
+
+    let plain_url = strip_replication_param(&config.database_url);
+    let client = connect(session, &plain_url, "catalog").await?;
+
+    let sr_config = StarRocksSinkConfig::from_sink_config(&config.sink)
+        .context("backfill: failed to build StarRocks config")?;
+    let sl_client = sr_config.stream_load_client();
+
+    // One dedicated connection per worker so chunk SELECTs run in parallel
+    let n_workers = (config.snapshot_parallel_workers as usize).max(1);
+    let mut conns = Vec::with_capacity(n_workers);
+    for i in 0..n_workers {
+        conns.push(connect(session, &plain_url, &format!("worker {}", i)).await?);
+    }
+    let pool = Mutex::new(conns);
+
+    // All chunks share one synced_at so the load is recognizable in the sink
+    let synced_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let mut tables = Vec::with_capacity(config.tables.len());
+    for table in &config.tables {
+        let mut summary = TableSummary {
+            table: table.clone(),
+            ..Default::default()
+        };
+
+        let Some(pk_col) = find_integer_pk_column(&client, table).await? else {
+            warn!("Table {} has no integer primary key, skipping", table);
+            summary.skipped = Some("no integer primary key to chunk on".to_string());
+            tables.push(summary);
+            continue;
+        };
+        let meta = TableMeta::new(table, pk_col, get_columns(&client, table).await?, config);
+        let chunks = chunk_table(&client, table, config.snapshot_chunk_size).await?;
+        summary.chunks = chunks.len() as u64;
+
+        let results: Vec<Result<(u64, u64)>> = stream::iter(chunks)
+            .map(|chunk| {
+                let pool = &pool;
+                let sl_client = &sl_client;
+                let meta = &meta;
+                let synced_at = synced_at.as_str();
+                async move {
+                    let pg_client = pool
+                        .lock()
+                        .await
+                        .pop()
+                        .ok_or_else(|| anyhow::anyhow!("backfill pool exhausted"))?;
+                    let result =
+                        load_chunk(&pg_client, sl_client, table, meta, &chunk, synced_at).await;
+                    pool.lock().await.push(pg_client);
+                    result.with_context(|| format!("{} chunk {}", table, chunk.partition_id))
+                }
+            })
+            .buffer_unordered(n_workers)
+            .collect()
+            .await;
+
+        for result in results {
+            match result {
+                Ok((rows, checksum)) => {
+                    summary.rows += rows;
+                    summary.checksum = summary.checksum.wrapping_add(checksum);
+                }
+                Err(e) => {
+                    error!("Backfill chunk failed: {:#}", e);
+                    summary.failed_chunks += 1;
+                }
+            }
+        }
+
+        info!(
+            "Table {}: {} rows loaded ({} chunks, {} failed)",
+            table, summary.rows, summary.chunks, summary.failed_chunks
+        );
+        tables.push(summary);
+    }
+
+    Ok(BackfillSummary {
+        tables,
+        duration: started.elapsed(),
+    })
+}
+
+/// SELECTs one chunk, loads it into StarRocks and returns (rows, checksum).
+async fn load_chunk(
+    client: &Client,
+    sl_client: &StreamLoadClient,
+    table: &str,
+    meta: &TableMeta,
+    chunk: &Chunk,
+    synced_at: &str,
+) -> Result<(u64, u64)> {
+    let rows = client
+        .query(
+            &chunk_select_query(table, meta),
+            &[&chunk.start_pk, &chunk.end_pk],
+        )
+        .await
+        .context("SELECT failed")?;
+    if rows.is_empty() {
+        return Ok((0, 0));
+    }
+
+    // Hashing and encoding are CPU-bound; keep them off the async workers
+    let row_count = rows.len() as u64;
+    let format = meta.format.clone();
+    let synced_at = synced_at.to_string();
+    let (checksum, body) = tokio::task::spawn_blocking(move || -> Result<(u64, Vec<u8>)> {
+        let checksum = rows.iter().fold(0u64, |acc, row| {
+            let values = (0..format.col_names.len()).map(|i| row.get::<_, Option<&str>>(i));
+            acc.wrapping_add(row_checksum(values))
+        });
+        // No replication position exists in this mode; version 0 lets any later
+        // CDC event for the same key win
+        let body = serialize_text_rows_to_json(&rows, &format, &synced_at, 0)?;
+        Ok((checksum, body))
+    })
+    .await
+    .context("backfill serializer task failed")??;
+    let options = StreamLoadOptions {
+        database: meta.dest.database.clone(),
+        ..Default::default()
+    };
+    sl_client
+        .send(&meta.dest.table, Arc::new(body), options)
+        .await
+        .context("Stream Load failed")?;
+
+    Ok((row_count, checksum))
+}
+
+/// 64-bit FNV-1a hash of one row's text values. Each value is prefixed with a
+/// NULL marker and its length so `NULL`, `''` and shifted column boundaries
+/// all hash differently.
+fn row_checksum<'a>(values: impl IntoIterator<Item = Option<&'a str>>) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    for value in values {
+        match value {
+            None => hash = fnv1a(hash, &[0]),
+            Some(s) => {
+                hash = fnv1a(hash, &[1]);
+                hash = fnv1a(hash, &(s.len() as u64).to_le_bytes());
+                hash = fnv1a(hash, s.as_bytes());
+            }
+        }
+    }
+    hash
+}
+
+async fn connect(session: &PgSession, url: &str, label: &str) -> Result<Client> {
+    session
+        .connect_helper(url, &format!("backfill PG ({})", label))
+        .await
+        .with_context(|| format!("backfill: failed to open PG connection ({})", label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a_reference_vectors() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_row_checksum_distinguishes_null_and_boundaries() {
+        let null = row_checksum([None]);
+        let empty = row_checksum([Some("")]);
+        assert_ne!(null, empty);
+
+        let split_a = row_checksum([Some("ab"), Some("c")]);
+        let split_b = row_checksum([Some("a"), Some("bc")]);
+        assert_ne!(split_a, split_b);
+    }
+
+    #[test]
+    fn test_table_checksum_is_order_independent() {
+        let r1 = row_checksum([Some("1"), Some("alice")]);
+        let r2 = row_checksum([Some("2"), None]);
+        assert_eq!(r1.wrapping_add(r2), r2.wrapping_add(r1));
+        assert_ne!(r1, r2);
+    }
+
+    #[test]
+    fn test_summary_completeness() {
+        let mut summary = BackfillSummary {
+            tables: vec![TableSummary {
+                table: "orders".into(),
+                rows: 10,
+                chunks: 2,
+                ..Default::default()
+            }],
+            duration: Duration::from_secs(1),
+        };
+        assert!(summary.is_complete());
+        assert_eq!(summary.total_rows(), 10);
+
+        summary.tables.push(TableSummary {
+            table: "events".into(),
+            skipped: Some("no integer primary key to chunk on".into()),
+            ..Default::default()
+        });
+        assert!(!summary.is_complete());
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Exported-snapshot copy (`SNAPSHOT_METHOD=export`).
+//!
+//! The source creates the slot with `EXPORT_SNAPSHOT` on its replication
+//! connection. Before that connection starts streaming, the tables are
+//! copied over `SNAPSHOT_PARALLEL_WORKERS` connections, each in a
+//! `REPEATABLE READ` transaction importing the snapshot, so the rows are
+//! exactly those committed up to the slot's consistent point whichever
+//! connection reads them. Streaming then starts at that point: each change is
+//! either in the copy or in the stream, never both and never neither.
+//!
+//! Rows are read with `COPY ... TO STDOUT` (text format) and sent down the
+//! pipeline channel as a Relation message followed by one Insert per row, so
+//! they go through the same schema cache, batching and sink as CDC events.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use bytes::{Bytes, BytesMut};
+use futures::{pin_mut, stream, StreamExt};
+use tokio::sync::{mpsc, Mutex};
+use tokio_postgres::Client;
+use tracing::info;
+
+use super::quote_ident;
+use super::worker::strip_replication_param;
+use crate::config::Config;
+use crate::grpc::state::{SharedState, Stage};
+use crate::source::parser::{CdcEvent, CdcMessage, Column, Tuple, TupleData};
+use crate::source::postgres::ExportedSnapshot;
+use crate::source::session::PgSession;
+
+/// Copies the configured tables as of `snapshot` into `tx`. Every event
+/// carries the consistent point as its LSN. Returns the number of rows.
+pub async fn run_export_snapshot(
+    config: &Config,
+    session: &PgSession,
+    snapshot: &ExportedSnapshot,
+    tx: &mpsc::Sender<CdcEvent>,
+    state: &SharedState,
+) -> Result<u64> {
+    // Interpolated into SET TRANSACTION SNAPSHOT; names look like 00000003-00000002-1
+    if snapshot.name.is_empty()
+        || !snapshot
+            .name
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() || c == '-')
+    {
+        anyhow::bail!("unexpected exported snapshot name '{}'", snapshot.name);
+    }
+
+    state.set_snapshot_active(true);
+    state.clear_table_progress().await;
+    let started = std::time::Instant::now();
+
+    // Every connection imports the snapshot before the replication
+    // connection runs another command, which would end the export
+    let plain_url = strip_replication_param(&config.database_url);
+    let n_workers =
+        (config.snapshot_parallel_workers as usize).clamp(1, config.tables.len().max(1));
+    let mut clients = Vec::with_capacity(n_workers);
+    for worker in 0..n_workers {
+        clients.push(import_snapshot(session, &plain_url, snapshot, worker).await?);
+    }
+    info!(
+        "Snapshot: copying {} tables over {} connection(s) sharing snapshot {}",
+        config.tables.len(),
+        n_workers,
+        snapshot.name
+    );
+    let pool = Mutex::new(clients);
+
+    let total = config.tables.len() as u64;
+    let mut copies = stream::iter(&config.tables)
+        .map(|table| {
+            let pool = &pool;
+            async move {
+                let client = pool
+                    .lock()
+                    .await
+                    .pop()
+                    .ok_or_else(|| anyhow::anyhow!("export snapshot pool exhausted"))?;
+                state.set_table_chunks_total(table, 1).await;
+                let copied = match describe_relation(&client, table).await {
+                    Ok(relation) => {
+                        copy_table(&client, table, relation, snapshot.consistent_point, tx).await
+                    }
+                    Err(e) => Err(e),
+                };
+                pool.lock().await.push(client);
+                copied.map(|copied| (table, copied))
+            }
+        })
+        .buffer_unordered(n_workers);
+
+    let mut rows = 0u64;
+    let mut done = 0u64;
+    while let Some(copied) = copies.next().await {
+        let (table, copied) = copied?;
+        rows += copied;
+        done += 1;
+        state.update_table_progress(table, 1, copied).await;
+        state.update_snapshot_progress(total, done, rows);
+        state
+            .set_stage(
+                Stage::Snapshot,
+                &format!("Copied {} of {} tables", done, total),
+            )
+            .await;
+        info!("Snapshot: {} copied ({} rows)", table, copied);
+    }
+    drop(copies);
+    for client in pool.into_inner() {
+        client.batch_execute("COMMIT").await?;
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    info!(
+        "Snapshot complete: {} tables, {} rows in {:.1}s ({:.0} rows/sec)",
+        total,
+        rows,
+        elapsed,
+        rows as f64 / elapsed.max(0.001)
+    );
+    state.set_snapshot_active(false);
+    Ok(rows)
+}
+
+/// Connection `worker` of the copy, in a transaction importing `snapshot`.
+async fn import_snapshot(
+    session: &PgSession,
+    url: &str,
+    snapshot: &ExportedSnapshot,
+    worker: usize,
+) -> Result<Client> {
+    let client = session
+        .connect_helper(url, &format!("export snapshot {}", worker))
+        .await
+        .context("export snapshot: failed to connect to PostgreSQL")?;
+    // A long copy is expected here; the helper statement timeout would cut it short
+    client
+        .batch_execute(&format!(
+            "BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY; \
+             SET TRANSACTION SNAPSHOT '{}'; \
+             SET LOCAL statement_timeout = 0",
+            snapshot.name
+        ))
+        .await
+        .with_context(|| format!("failed to import snapshot {}", snapshot.name))?;
+    Ok(client)
+}
+
+/// Relation message for `table`, as pgoutput would send it: the columns it
+/// publishes (no generated ones), with the replica identity columns flagged
+/// as keys.
+async fn describe_relation(client: &Client, table: &str) -> Result<CdcMessage> {
+    let (schema, name) = table.split_once('.').unwrap_or(("public", table));
+    let rows = client
+        .query(
+            "SELECT c.oid, c.relreplident, a.attname, a.atttypid, a.atttypmod,
+                    c.relreplident = 'f' OR COALESCE(a.attnum = ANY(i.indkey), false)
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_attribute a ON a.attrelid = c.oid
+             LEFT JOIN pg_index i ON i.indrelid = c.oid
+                 AND ((c.relreplident = 'd' AND i.indisprimary)
+                   OR (c.relreplident = 'i' AND i.indisreplident))
+             WHERE n.nspname = $1 AND c.relname = $2
+               AND a.attnum > 0 AND NOT a.attisdropped AND a.attgenerated = ''
+             ORDER BY a.attnum",
+            &[&schema, &name],
+        )
+        .await
+        .with_context(|| format!("failed to describe {}", table))?;
+    let first = rows
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("table {} not found or has no columns", table))?;
+
+    Ok(CdcMessage::Relation {
+        id: first.get(0),
+        namespace: Arc::from(schema),
+        name: Arc::from(name),
+        replica_identity: first.get::<_, i8>(1) as u8,
+        columns: rows
+            .iter()
+            .map(|row| Column {
+                flags: u8::from(row.get::<_, bool>(5)),
+                name: Arc::from(row.get::<_, &str>(2)),
+                type_id: row.get(3),
+                type_mod: row.get(4),
+            })
+            .collect(),
+    })
+}
+
+/// Streams the rows of `table` into `tx` after its Relation message.
+async fn copy_table(
+    client: &Client,
+    table: &str,
+    relation: CdcMessage,
+    lsn: u64,
+    tx: &mpsc::Sender<CdcEvent>,
+) -> Result<u64> {
+    let CdcMessage::Relation {
+        id: relation_id,
+        ref columns,
+        ..
+    } = relation
+    else {
+        unreachable!("describe_relation returns a Relation message");
+    };
+    let column_list = columns
+        .iter()
+        .map(|c| quote_ident(&c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let statement = format!("COPY {} ({}) TO STDOUT", quote_ident(table), column_list);
+    send(tx, lsn, relation).await?;
+
+    let stream = client
+        .copy_out(&statement)
+        .await
+        .with_context(|| format!("COPY of {} failed", table))?;
+    pin_mut!(stream);
+
+    let mut buf = BytesMut::new();
+    let mut rows = 0u64;
+    while let Some(chunk) = stream.next().await {
+        buf.extend_from_slice(&chunk.with_context(|| format!("COPY of {} failed", table))?);
+        // Newlines inside values are escaped, so every raw one ends a row
+        while let Some(end) = memchr::memchr(b'\n', &buf) {
+            let line = buf.split_to(end + 1).freeze();
+            let tuple = Tuple {
+                cols: parse_copy_line(&line.slice(..end)),
+                toast_bitmap: 0,
+            };
+            send(tx, lsn, CdcMessage::Insert { relation_id, tuple }).await?;
+            rows += 1;
+        }
+    }
+    Ok(rows)
+}
+
+async fn send(tx: &mpsc::Sender<CdcEvent>, lsn: u64, message: CdcMessage) -> Result<()> {
+    tx.send(CdcEvent { lsn, message })
+        .await
+        .map_err(|_| anyhow::anyhow!("pipeline stopped during the snapshot"))
+}
+
+/// Splits a row of `COPY` text output into column values: tab-separated,
+/// `\N` for NULL, backslash escapes in values. Values without escapes share
+/// the line's buffer.
+fn parse_copy_line(line: &Bytes) -> Vec<TupleData> {
+    line.split(|b| *b == b'\t')
+        .map(|field| {
+            if field == b"\\N" {
+                TupleData::Null
+            } else if field.contains(&b'\\') {
+                TupleData::Text(Bytes::from(unescape_copy_text(field)))
+            } else {
+                TupleData::Text(line.slice_ref(field))
+            }
+        })
+        .collect()
+}
+
+/// Decodes the backslash escapes of a `COPY` text value: `\b \f \n \r \t \v`,
+/// octal `\ooo`, hex `\xhh`, and `\c` for any other character `c`.
+fn unescape_copy_text(field: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        let b = field[i];
+        i += 1;
+        if b != b'\\' || i == field.len() {
+            out.push(b);
+            continue;
+        }
+        let c = field[i];
+        i += 1;
+        match c {
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0c),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'v' => out.push(0x0b),
+            b'0'..=b'7' => {
+                let mut value = u32::from(c - b'0');
+                for _ in 0..2 {
+                    match field.get(i) {
+                        Some(d @ b'0'..=b'7') => {
+                            value = value * 8 + u32::from(d - b'0');
+                            i += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                out.push(value as u8);
+            }
+            b'x' if field.get(i).is_some_and(u8::is_ascii_hexdigit) => {
+                let mut value = 0u8;
+                for _ in 0..2 {
+                    match field.get(i).and_then(|d| (*d as char).to_digit(16)) {
+                        Some(d) => {
+                            value = value * 16 + d as u8;
+                            i += 1;
+                        }
+                        None => break,
+                    }
+                }
+                out.push(value);
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> TupleData {
+        TupleData::Text(Bytes::copy_from_slice(value.as_bytes()))
+    }
+
+    fn assert_cols(line: &str, expected: &[TupleData]) {
+        let cols = parse_copy_line(&Bytes::copy_from_slice(line.as_bytes()));
+        assert_eq!(cols.len(), expected.len(), "columns of {:?}", line);
+        for (col, want) in cols.iter().zip(expected) {
+            match (col, want) {
+                (TupleData::Null, TupleData::Null) => {}
+                (TupleData::Text(a), TupleData::Text(b)) => assert_eq!(a, b),
+                _ => panic!("{:?} != {:?} in {:?}", col, want, line),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_copy_line() {
+        assert_cols(
+            "42\tAlice\t\\N\t",
+            &[text("42"), text("Alice"), TupleData::Null, text("")],
+        );
+        // Escaped tabs and newlines stay inside their value
+        assert_cols(
+            "1\tline one\\nline two\\ttabbed\t\\\\N",
+            &[text("1"), text("line one\nline two\ttabbed"), text("\\N")],
+        );
+        assert_cols("{\"a\": \"b\\\\c\"}", &[text("{\"a\": \"b\\c\"}")]);
+    }
+
+    #[test]
+    fn test_unescape_copy_text() {
+        assert_eq!(unescape_copy_text(b"a\\101\\x42c"), b"aABc");
+        assert_eq!(unescape_copy_text(b"\\r\\v\\f\\b"), b"\r\x0b\x0c\x08");
+        // Not an escape sequence: the character itself
+        assert_eq!(unescape_copy_text(b"\\xyz\\q"), b"xyzq");
+        assert_eq!(unescape_copy_text(b"trailing\\"), b"trailing\\");
+    }
+}
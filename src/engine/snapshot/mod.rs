@@ -8,24 +8,20 @@
 //! - Watermarks (LW/HW) via `pg_logical_emit_message`
 //! - `SharedState::should_emit()` for O(log n) deduplication
 //! - Resumable: completed chunks are stored in `dbmazz_snapshot_state`
+//!
+//! `backfill` reuses the chunked reader for the one-shot backfill-only mode.
+//! `export` is the other snapshot method: a copy of the slot's exported
+//! snapshot, sent through the pipeline before streaming starts.
 
+pub mod backfill;
 pub mod chunker;
+pub mod export;
 pub mod state_store;
 pub mod utils;
 pub mod worker;
 
+pub use backfill::run_backfill;
+pub use export::run_export_snapshot;
 pub use worker::run_snapshot;
 
-/// Quote a SQL identifier to prevent SQL injection.
-/// Wraps in double quotes and escapes embedded double quotes.
-/// Handles schema-qualified names like "public.orders" → "public"."orders".
-pub fn quote_ident(name: &str) -> String {
-    if name.contains('.') {
-        name.split('.')
-            .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
-            .collect::<Vec<_>>()
-            .join(".")
-    } else {
-        format!("\"{}\"", name.replace('"', "\"\""))
-    }
-}
+pub use crate::utils::quote_ident;
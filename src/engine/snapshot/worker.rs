@@ -24,35 +24,130 @@ use anyhow::{Context, Result};
 use chrono::Utc;
 use tokio::sync::{mpsc, Semaphore};
 use tokio::task::JoinSet;
-use tokio_postgres::{Client, NoTls};
+use tokio_postgres::Client;
 use tracing::{debug, error, info, warn};
 
 use super::chunker::{chunk_table, Chunk};
 use super::quote_ident;
 use super::state_store;
 use super::utils::find_integer_pk_column;
-use crate::config::Config;
+use crate::config::{
+    BoolEncoding, ColumnTransform, Config, FloatFormat, GeometryFormat, NonFiniteFloats,
+    NullEncoding, SinkTableName, SinkType, TextNormalization,
+};
 use crate::connectors::sinks::starrocks::stream_load::{StreamLoadClient, StreamLoadOptions};
 use crate::connectors::sinks::starrocks::StarRocksSinkConfig;
-use crate::grpc::state::{CdcState, SharedState, Stage};
+use crate::grpc::state::{CdcState, ErrorCategory, SharedState, Stage};
+use crate::sink::adapter::null_default_text;
+use crate::source::geometry;
+use crate::source::session::PgSession;
+use crate::utils::parse_pg_lsn;
 use tokio::time::Duration;
 
 /// Pre-computed metadata for a snapshot table (avoids redundant catalog queries per chunk).
-struct TableMeta {
-    pk_col: String,
-    col_names: Vec<String>,
+pub(super) struct TableMeta {
+    pub(super) pk_col: String,
+    pub(super) format: Arc<RowFormat>,
+    /// Destination in StarRocks, per `SINK_SCHEMA_MODE`
+    pub(super) dest: SinkTableName,
+}
+
+impl TableMeta {
+    pub(super) fn new(
+        table: &str,
+        pk_col: String,
+        columns: Vec<(String, u32)>,
+        config: &Config,
+    ) -> Self {
+        // Dropped columns are not read; masked ones are text in the sink
+        let transforms = config.column_transforms.for_table(table);
+        let transform = |name: &str| transforms.and_then(|t| t.get(name)).copied();
+        let (columns, masks): (Vec<(String, u32)>, Vec<Option<ColumnTransform>>) = columns
+            .into_iter()
+            .filter(|(name, _)| transform(name) != Some(ColumnTransform::Drop))
+            .map(|(name, type_oid)| match transform(&name) {
+                Some(mask) => ((name, 25), Some(mask)),
+                None => ((name, type_oid), None),
+            })
+            .unzip();
+
+        let bools_as_ints = config.sink.bool_encoding == BoolEncoding::Int;
+        let col_nulls = columns
+            .iter()
+            .map(|(_, type_oid)| null_json(config.sink.null_encoding, bools_as_ints, *type_oid))
+            .collect();
+        let (col_names, col_types) = columns.into_iter().unzip();
+        Self {
+            pk_col,
+            format: Arc::new(RowFormat {
+                col_names,
+                col_types,
+                col_nulls,
+                masks,
+                hash_salt: config.column_transforms.hash_salt.clone(),
+                bools_as_ints,
+                float: config.sink.float_format,
+                geometry: config.sink.geometry_format,
+                text: config.text_normalization,
+            }),
+            dest: config.sink.naming.sink_table(table),
+        }
+    }
+}
+
+/// How the rows of a snapshot table are written for Stream Load.
+pub(super) struct RowFormat {
+    pub(super) col_names: Vec<String>,
+    col_types: Vec<u32>,
+    /// JSON written for a NULL in each column, `None` to leave it out
+    col_nulls: Vec<Option<String>>,
+    /// `COLUMN_TRANSFORMS` mask of each column
+    masks: Vec<Option<ColumnTransform>>,
+    hash_salt: String,
+    /// Booleans are written as `1`/`0` (`SINK_BOOLEAN_ENCODING=int`)
+    bools_as_ints: bool,
+    /// `SINK_FLOAT_*`; the default keeps PostgreSQL's float text
+    float: FloatFormat,
+    /// `SINK_GEOMETRY_FORMAT`, written as text either way
+    geometry: GeometryFormat,
+    /// `char(n)` values are already trimmed by the `::text` cast
+    text: TextNormalization,
+}
+
+/// Snapshot rows are new row images, so `SINK_NULL_ENCODING` applies as it
+/// does to CDC inserts.
+fn null_json(null_encoding: NullEncoding, bools_as_ints: bool, type_oid: u32) -> Option<String> {
+    match null_encoding {
+        NullEncoding::Null => Some("null".to_string()),
+        NullEncoding::Omit => None,
+        NullEncoding::TypeDefault if bools_as_ints && type_oid == 16 => Some("0".to_string()),
+        // Loaded as text like every other snapshot value; none need escaping
+        NullEncoding::TypeDefault => Some(format!("\"{}\"", null_default_text(type_oid))),
+    }
 }
 
 /// Run the full snapshot for all configured tables.
 ///
 /// This function is spawned as a concurrent task alongside the WAL consumer.
 /// It exits when all chunks are complete or on a non-retriable error.
-pub async fn run_snapshot(config: Arc<Config>, shared_state: Arc<SharedState>) -> Result<()> {
+pub async fn run_snapshot(
+    config: Arc<Config>,
+    session: PgSession,
+    shared_state: Arc<SharedState>,
+) -> Result<()> {
     info!(
         "Snapshot worker starting (chunk_size={}, workers={})",
         config.snapshot_chunk_size, config.snapshot_parallel_workers
     );
 
+    // Chunks are loaded through Stream Load, which only StarRocks speaks
+    if config.sink.sink_type != SinkType::StarRocks {
+        anyhow::bail!(
+            "snapshot backfill is not supported for the {} sink",
+            config.sink.sink_type
+        );
+    }
+
     shared_state.set_snapshot_active(true);
     shared_state
         .set_stage(Stage::Snapshot, "Connecting to source")
@@ -62,32 +157,34 @@ pub async fn run_snapshot(config: Arc<Config>, shared_state: Arc<SharedState>) -
     // Connect to PostgreSQL (regular connection, not replication).
     // Strip `replication=database` from the URL — DDL is not allowed in replication mode.
     let plain_url = strip_replication_param(&config.database_url);
-    let (client, connection) = tokio_postgres::connect(&plain_url, NoTls)
+    let client = session
+        .connect_helper(&plain_url, "snapshot worker: postgres")
         .await
         .context("snapshot worker: failed to connect to PostgreSQL")?;
     let client = Arc::new(client);
 
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            error!("snapshot worker: postgres connection error: {}", e);
-        }
-    });
-
     // Ensure state table exists
     state_store::ensure_state_table(&client).await?;
 
     // Build the Stream Load client using the normalized HTTP URL from the sink config
     let sr_config = StarRocksSinkConfig::from_sink_config(&config.sink)
         .context("snapshot worker: failed to build StarRocks config")?;
-    let sl_client = Arc::new(StreamLoadClient::new(
-        sr_config.http_url.clone(),
-        sr_config.database.clone(),
-        sr_config.user.clone(),
-        sr_config.password.clone(),
-    ));
+    let sl_client = Arc::new(sr_config.stream_load_client());
 
     let slot_name = config.slot_name.clone();
-    let tables = config.tables.clone();
+    // Chunks are loaded into the default sink; tables routed to another one
+    // can only be copied with SNAPSHOT_METHOD=export
+    let (tables, routed): (Vec<String>, Vec<String>) = config
+        .tables
+        .iter()
+        .cloned()
+        .partition(|table| config.route_of(table).is_none());
+    if !routed.is_empty() {
+        warn!(
+            "Snapshot skips {}: tables routed by SINK_ROUTES are not loaded by chunked snapshots",
+            routed.join(", ")
+        );
+    }
     let chunk_size = config.snapshot_chunk_size;
 
     // Pre-compute table metadata to avoid redundant catalog queries per chunk.
@@ -95,8 +192,11 @@ pub async fn run_snapshot(config: Arc<Config>, shared_state: Arc<SharedState>) -
     let mut table_meta: HashMap<String, TableMeta> = HashMap::new();
     for table in &tables {
         if let Some(pk_col) = find_integer_pk_column(&client, table).await? {
-            let col_names = get_column_names(&client, table).await?;
-            table_meta.insert(table.clone(), TableMeta { pk_col, col_names });
+            let columns = get_columns(&client, table).await?;
+            table_meta.insert(
+                table.clone(),
+                TableMeta::new(table, pk_col, columns, &config),
+            );
         }
     }
     let table_meta = Arc::new(table_meta);
@@ -135,14 +235,10 @@ pub async fn run_snapshot(config: Arc<Config>, shared_state: Arc<SharedState>) -
     let n_workers = (config.snapshot_parallel_workers as usize).max(1);
     let mut pool_conns: Vec<Arc<Client>> = Vec::with_capacity(n_workers);
     for i in 0..n_workers {
-        let (c, conn) = tokio_postgres::connect(&plain_url, NoTls)
+        let c = session
+            .connect_helper(&plain_url, &format!("snapshot PG {}", i))
             .await
             .with_context(|| format!("snapshot worker: failed to open PG connection {}", i))?;
-        tokio::spawn(async move {
-            if let Err(e) = conn.await {
-                error!("snapshot PG connection {} error: {}", i, e);
-            }
-        });
         pool_conns.push(Arc::new(c));
     }
     info!("Opened {} PG connections for parallel snapshot", n_workers);
@@ -265,6 +361,16 @@ pub async fn run_snapshot(config: Arc<Config>, shared_state: Arc<SharedState>) -
             )
             .await;
             pool.lock().await.push(pg_client);
+            if let Err(e) = &result {
+                shared_state
+                    .record_error(
+                        ErrorCategory::Snapshot,
+                        format!("Chunk {} failed: {:#}", chunk.partition_id, e),
+                        Some(&table),
+                        0,
+                    )
+                    .await;
+            }
             result
         });
     }
@@ -341,25 +447,8 @@ async fn process_chunk(
         .context("failed to emit LW watermark")?;
 
     // Step 2: SELECT rows for this chunk
-    // Extract table name for the destination (strip schema prefix)
-    let dest_table = table.rsplit('.').next().unwrap_or(table);
-
     // Use pre-computed metadata (avoids redundant catalog queries per chunk)
-    let col_names = &meta.col_names;
-
-    // Build SELECT with all columns cast to ::text for universal type handling
-    let cols_sql: String = col_names
-        .iter()
-        .map(|c| format!("{}::text AS {}", quote_ident(c), quote_ident(c)))
-        .collect::<Vec<_>>()
-        .join(", ");
-    let quoted_pk = quote_ident(&meta.pk_col);
-    let select_query = format!(
-        "SELECT {cols} FROM {table} WHERE {pk} >= $1::bigint AND {pk} < $2::bigint",
-        cols = cols_sql,
-        table = quote_ident(table),
-        pk = quoted_pk,
-    );
+    let select_query = chunk_select_query(table, meta);
     let rows = client
         .query(&select_query, &[&chunk.start_pk, &chunk.end_pk])
         .await
@@ -383,20 +472,29 @@ async fn process_chunk(
     let hw_lsn = parse_pg_lsn(&hw_lsn_str)
         .ok_or_else(|| anyhow::anyhow!("failed to parse HW LSN: '{}'", hw_lsn_str))?;
 
-    // Step 4: Serialize rows to JSON (for Stream Load)
-    // All columns are text thanks to ::text cast, so we just read Option<String>
-    let synced_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let body = if rows.is_empty() {
-        b"[]".to_vec()
-    } else {
-        serialize_text_rows_to_json(&rows, col_names, &synced_at, hw_lsn)?
-    };
-
-    // Step 5: Stream Load to StarRocks (only if there are rows)
     if !rows.is_empty() {
+        // Step 4: Serialize rows to JSON (for Stream Load)
+        // All columns are text thanks to ::text cast, so we just read Option<String>.
+        // Runs on the blocking pool so large chunks don't hold up other tasks.
+        let synced_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let format = meta.format.clone();
+        let body = tokio::task::spawn_blocking(move || {
+            serialize_text_rows_to_json(&rows, &format, &synced_at, hw_lsn)
+        })
+        .await
+        .context("snapshot serializer task failed")??;
+
+        // Step 5: Stream Load to StarRocks (only if there are rows)
         let body_arc = Arc::new(body);
         let result: crate::connectors::sinks::starrocks::stream_load::StreamLoadResult = sl_client
-            .send(dest_table, body_arc, StreamLoadOptions::default())
+            .send(
+                &meta.dest.table,
+                body_arc,
+                StreamLoadOptions {
+                    database: meta.dest.database.clone(),
+                    ..Default::default()
+                },
+            )
             .await
             .with_context(|| {
                 format!(
@@ -455,14 +553,33 @@ async fn process_chunk(
     Ok(())
 }
 
+/// Build the chunk SELECT with all columns cast to ::text for universal type handling.
+/// Binds the chunk range as `$1` (inclusive) and `$2` (exclusive).
+pub(super) fn chunk_select_query(table: &str, meta: &TableMeta) -> String {
+    let cols_sql: String = meta
+        .format
+        .col_names
+        .iter()
+        .map(|c| format!("{}::text AS {}", quote_ident(c), quote_ident(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "SELECT {cols} FROM {table} WHERE {pk} >= $1::bigint AND {pk} < $2::bigint",
+        cols = cols_sql,
+        table = quote_ident(table),
+        pk = quote_ident(&meta.pk_col),
+    )
+}
+
 /// Serialize tokio_postgres rows to a JSON array string for Stream Load.
 /// Format: `[{"col1":"val1","col2":"val2"}, ...]`
 /// Serialize rows to JSON where all columns were cast to ::text in the query.
 /// Each column is read as Option<String> — no type-specific conversions needed.
+/// NULLs and text values are written as `format` says.
 /// Appends CDC audit columns (dbmazz_op_type, dbmazz_is_deleted, dbmazz_synced_at, dbmazz_cdc_version).
-fn serialize_text_rows_to_json(
+pub(super) fn serialize_text_rows_to_json(
     rows: &[tokio_postgres::Row],
-    col_names: &[String],
+    format: &RowFormat,
     synced_at: &str,
     hw_lsn: u64,
 ) -> Result<Vec<u8>> {
@@ -476,28 +593,51 @@ fn serialize_text_rows_to_json(
             out.push(b',');
         }
         out.push(b'{');
-        for (col_idx, col_name) in col_names.iter().enumerate() {
-            if col_idx > 0 {
-                out.push(b',');
+        for (col_idx, col_name) in format.col_names.iter().enumerate() {
+            let mut val: Option<String> = row.get(col_idx);
+            if let (Some(mask), Some(s)) = (format.masks[col_idx], &val) {
+                val = mask.mask(s, &format.hash_salt).map(|m| m.into_owned());
+            }
+            let null = format.col_nulls[col_idx].as_deref();
+            if val.is_none() && null.is_none() {
+                continue;
             }
-            let val: Option<String> = row.get(col_idx);
 
             out.push(b'"');
             out.extend_from_slice(col_name.as_bytes());
             out.extend_from_slice(b"\":");
             match val {
+                // `bool::text` is `true` or `false`
+                Some(s) if format.bools_as_ints && format.col_types[col_idx] == 16 => {
+                    out.push(if s == "true" { b'1' } else { b'0' });
+                }
+                Some(s)
+                    if format.float.is_custom()
+                        && matches!(format.col_types[col_idx], 700 | 701) =>
+                {
+                    write_float(&mut out, &s, &format.float);
+                }
+                Some(s) if geometry::is_geometric(format.col_types[col_idx]) => {
+                    let converted =
+                        geometry::convert(&s, format.col_types[col_idx], format.geometry);
+                    out.push(b'"');
+                    write_json_escaped(&mut out, converted.as_deref().unwrap_or(&s));
+                    out.push(b'"');
+                }
                 Some(s) => {
                     out.push(b'"');
+                    let s = format.text.apply(&s, format.col_types[col_idx]);
                     // Escape JSON-special chars at byte level (avoids UTF-8 decode/re-encode)
                     write_json_escaped(&mut out, &s);
                     out.push(b'"');
                 }
-                None => out.extend_from_slice(b"null"),
+                None => out.extend_from_slice(null.unwrap_or("null").as_bytes()),
             }
+            out.push(b',');
         }
 
         // Append CDC audit columns
-        out.extend_from_slice(b",\"dbmazz_op_type\":0");
+        out.extend_from_slice(b"\"dbmazz_op_type\":0");
         out.extend_from_slice(b",\"dbmazz_is_deleted\":false");
         out.extend_from_slice(b",\"dbmazz_synced_at\":\"");
         out.extend_from_slice(synced_at.as_bytes());
@@ -512,6 +652,23 @@ fn serialize_text_rows_to_json(
     Ok(out)
 }
 
+/// Writes the `::text` of a float column as `SINK_FLOAT_*` says, like the
+/// CDC path does: formatted text, or NULL for NaN and infinities with
+/// `SINK_FLOAT_NON_FINITE=null`.
+fn write_float(out: &mut Vec<u8>, text: &str, float: &FloatFormat) {
+    let formatted = match text.parse::<f64>() {
+        Ok(f) if f.is_finite() => float.text(f),
+        Ok(_) if float.non_finite == NonFiniteFloats::Null => {
+            out.extend_from_slice(b"null");
+            return;
+        }
+        _ => None,
+    };
+    out.push(b'"');
+    out.extend_from_slice(formatted.as_deref().unwrap_or(text).as_bytes());
+    out.push(b'"');
+}
+
 /// Write a JSON-escaped string directly at the byte level.
 ///
 /// JSON-special characters (`"`, `\`, `\n`, `\r`, `\t`) are all ASCII (< 0x80).
@@ -537,8 +694,8 @@ fn write_json_escaped(out: &mut Vec<u8>, s: &str) {
     out.extend_from_slice(&bytes[start..]);
 }
 
-/// Get column names for a table.
-async fn get_column_names(client: &Client, table_name: &str) -> Result<Vec<String>> {
+/// Get column names and type OIDs for a table.
+pub(super) async fn get_columns(client: &Client, table_name: &str) -> Result<Vec<(String, u32)>> {
     let (schema, table) = if table_name.contains('.') {
         let parts: Vec<&str> = table_name.splitn(2, '.').collect();
         (parts[0].to_string(), parts[1].to_string())
@@ -548,7 +705,7 @@ async fn get_column_names(client: &Client, table_name: &str) -> Result<Vec<Strin
 
     let rows = client
         .query(
-            "SELECT a.attname
+            "SELECT a.attname, a.atttypid
          FROM pg_attribute a
          JOIN pg_class c ON c.oid = a.attrelid
          JOIN pg_namespace n ON n.oid = c.relnamespace
@@ -559,11 +716,14 @@ async fn get_column_names(client: &Client, table_name: &str) -> Result<Vec<Strin
         )
         .await?;
 
-    Ok(rows.iter().map(|r| r.get::<_, String>(0)).collect())
+    Ok(rows
+        .iter()
+        .map(|r| (r.get::<_, String>(0), r.get::<_, u32>(1)))
+        .collect())
 }
 
 /// Strip the `replication=database` query parameter from a PostgreSQL URL.
-fn strip_replication_param(url_str: &str) -> String {
+pub(super) fn strip_replication_param(url_str: &str) -> String {
     match url::Url::parse(url_str) {
         Ok(mut parsed) => {
             let pairs: Vec<(String, String)> = parsed
@@ -616,14 +776,3 @@ async fn get_relation_id(client: &Client, table_name: &str) -> Result<u32> {
     let oid: i32 = row.get(0);
     Ok(oid as u32)
 }
-
-/// Parse a PostgreSQL LSN string like "0/1234AB" into a u64.
-fn parse_pg_lsn(s: &str) -> Option<u64> {
-    let parts: Vec<&str> = s.split('/').collect();
-    if parts.len() != 2 {
-        return None;
-    }
-    let hi = u64::from_str_radix(parts[0], 16).ok()?;
-    let lo = u64::from_str_radix(parts[1], 16).ok()?;
-    Some((hi << 32) | lo)
-}
@@ -0,0 +1,299 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Warm standby (`STANDBY_LEADER_URL`).
+//!
+//! A standby instance starts its gRPC server but does not touch the slot.
+//! Instead it subscribes to the active instance's `StandbyService` stream and
+//! keeps a copy of its confirmed LSN and schema cache. When the active
+//! instance has been unreachable for `STANDBY_FAILOVER_TIMEOUT_SECS`, the
+//! standby takes over: it resumes from the handed-over LSN with a primed
+//! schema cache and skips sink setup, which the active instance already did.
+//!
+//! A standby only takes over after it has heard from the active instance at
+//! least once, so a wrong URL never produces a second active instance. If the
+//! active instance shuts down on purpose and drops its slot, the standby
+//! exits instead.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use tonic::transport::Endpoint;
+use tracing::{info, warn};
+
+use crate::config::StandbyConfig;
+use crate::grpc::proto::{
+    standby_service_client::StandbyServiceClient, StandbyState, StandbyStateRequest,
+};
+use crate::grpc::relation_from_proto;
+use crate::grpc::state::SharedState;
+use crate::source::parser::CdcMessage;
+
+/// Replication state received from the active instance.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Handover {
+    pub confirmed_lsn: u64,
+    /// Relation messages to prime the schema cache with
+    pub relations: Vec<CdcMessage>,
+}
+
+pub(super) enum StandbyOutcome {
+    /// The active instance is gone; continue as the active instance
+    TakeOver(Handover),
+    /// The active instance shut down and dropped its slot
+    LeaderStopped,
+    /// This instance was asked to shut down while following
+    Shutdown,
+}
+
+/// How one connection to the active instance ended.
+enum StreamEnd {
+    LeaderStopped,
+    Disconnected(String),
+}
+
+/// Handover state accumulated from the stream.
+struct Follower {
+    slot_name: String,
+    schema_version: u64,
+    handover: Option<Handover>,
+}
+
+impl Follower {
+    fn new(slot_name: &str) -> Self {
+        Self {
+            slot_name: slot_name.to_string(),
+            schema_version: 0,
+            handover: None,
+        }
+    }
+
+    /// Applies one state message. Returns true when the active instance is
+    /// stopping for good.
+    async fn apply(&mut self, message: StandbyState, state: &SharedState) -> Result<bool> {
+        if message.slot_name != self.slot_name {
+            anyhow::bail!(
+                "active instance replicates slot '{}', this standby is configured for '{}'",
+                message.slot_name,
+                self.slot_name
+            );
+        }
+
+        let handover = self.handover.get_or_insert_with(Handover::default);
+        // Relations are only sent when they changed, and always on a new stream
+        if !message.relations.is_empty() || message.schema_version != self.schema_version {
+            handover.relations = message
+                .relations
+                .into_iter()
+                .map(relation_from_proto)
+                .collect();
+            self.schema_version = message.schema_version;
+            state.publish_relations(handover.relations.clone()).await;
+        }
+        if message.confirmed_lsn != handover.confirmed_lsn {
+            handover.confirmed_lsn = message.confirmed_lsn;
+            state.update_lsn(message.confirmed_lsn);
+            state.confirm_lsn(message.confirmed_lsn);
+        }
+
+        Ok(message.stopping)
+    }
+
+    /// Follows one connection until it ends. Errors are fatal misconfigurations.
+    async fn follow(
+        &mut self,
+        endpoint: &Endpoint,
+        heartbeat_ms: u32,
+        timeout: Duration,
+        state: &SharedState,
+        last_contact: &mut Option<Instant>,
+    ) -> Result<StreamEnd> {
+        let channel = match endpoint.connect().await {
+            Ok(channel) => channel,
+            Err(e) => return Ok(StreamEnd::Disconnected(format!("connect failed: {}", e))),
+        };
+        let request = StandbyStateRequest {
+            standby_id: standby_id(),
+            heartbeat_ms,
+        };
+        let mut stream = match StandbyServiceClient::new(channel)
+            .stream_state(request)
+            .await
+        {
+            Ok(response) => response.into_inner(),
+            Err(status) => return Ok(StreamEnd::Disconnected(status.to_string())),
+        };
+
+        loop {
+            let message = match tokio::time::timeout(timeout, stream.message()).await {
+                Ok(Ok(Some(message))) => message,
+                Ok(Ok(None)) => return Ok(StreamEnd::Disconnected("stream closed".to_string())),
+                Ok(Err(status)) => return Ok(StreamEnd::Disconnected(status.to_string())),
+                Err(_) => {
+                    return Ok(StreamEnd::Disconnected(format!(
+                        "no message for {}s",
+                        timeout.as_secs()
+                    )))
+                }
+            };
+            if last_contact.is_none() {
+                info!("Standby: following active instance");
+            }
+            *last_contact = Some(Instant::now());
+            if self.apply(message, state).await? {
+                return Ok(StreamEnd::LeaderStopped);
+            }
+        }
+    }
+}
+
+/// Follows the active instance until it is lost, stops, or this instance is
+/// shut down.
+pub(super) async fn follow_leader(
+    standby: &StandbyConfig,
+    slot_name: &str,
+    state: &SharedState,
+) -> Result<StandbyOutcome> {
+    let timeout = Duration::from_secs(standby.failover_timeout_secs);
+    let endpoint = Endpoint::from_shared(standby.leader_url.clone())
+        .with_context(|| format!("invalid STANDBY_LEADER_URL '{}'", standby.leader_url))?
+        .connect_timeout(timeout);
+    // Several heartbeats per timeout so one late message does not trigger a takeover
+    let heartbeat_ms = (timeout.as_millis() / 3).min(1000) as u32;
+
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut follower = Follower::new(slot_name);
+    let mut last_contact: Option<Instant> = None;
+
+    info!(
+        "Standby: following {} (failover after {}s)",
+        standby.leader_url, standby.failover_timeout_secs
+    );
+
+    loop {
+        let end = tokio::select! {
+            end = follower.follow(&endpoint, heartbeat_ms, timeout, state, &mut last_contact) => end?,
+            _ = shutdown_rx.changed() => return Ok(StandbyOutcome::Shutdown),
+        };
+
+        match end {
+            StreamEnd::LeaderStopped => {
+                info!("Standby: active instance stopped and dropped its slot, exiting");
+                return Ok(StandbyOutcome::LeaderStopped);
+            }
+            StreamEnd::Disconnected(reason) => match last_contact {
+                Some(at) if at.elapsed() >= timeout => {
+                    let handover = follower.handover.take().unwrap_or_default();
+                    warn!(
+                        "Standby: active instance unreachable for {}s ({}), taking over at LSN 0x{:X}",
+                        at.elapsed().as_secs(),
+                        reason,
+                        handover.confirmed_lsn
+                    );
+                    return Ok(StandbyOutcome::TakeOver(handover));
+                }
+                Some(_) => warn!("Standby: lost active instance ({}), retrying", reason),
+                None => warn!(
+                    "Standby: active instance not reachable yet ({}), retrying",
+                    reason
+                ),
+            },
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+            _ = shutdown_rx.changed() => return Ok(StandbyOutcome::Shutdown),
+        }
+    }
+}
+
+/// Identifies this standby in the active instance's logs.
+fn standby_id() -> String {
+    std::env::var("HOSTNAME").unwrap_or_else(|_| format!("pid-{}", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::proto::{RelationColumn, RelationState};
+    use crate::grpc::state::CdcConfig;
+    use std::sync::Arc;
+
+    fn make_state() -> Arc<SharedState> {
+        SharedState::new(CdcConfig {
+            flush_size: 1000,
+            flush_interval_ms: 5000,
+            tables: vec!["public.orders".to_string()],
+            slot_name: "dbmazz_slot".to_string(),
+        })
+    }
+
+    fn message(confirmed_lsn: u64, schema_version: u64, with_relations: bool) -> StandbyState {
+        let relations = if with_relations {
+            vec![RelationState {
+                id: 16384,
+                namespace: "public".into(),
+                name: "orders".into(),
+                replica_identity: u32::from(b'd'),
+                columns: vec![RelationColumn {
+                    name: "id".into(),
+                    type_id: 20,
+                    type_mod: -1,
+                    flags: 1,
+                }],
+            }]
+        } else {
+            vec![]
+        };
+        StandbyState {
+            slot_name: "dbmazz_slot".into(),
+            confirmed_lsn,
+            schema_version,
+            relations,
+            sent_at_ms: 0,
+            stopping: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_keeps_relations_between_changes() {
+        let state = make_state();
+        let mut follower = Follower::new("dbmazz_slot");
+
+        assert!(!follower
+            .apply(message(0x1000, 1, true), &state)
+            .await
+            .unwrap());
+        // Heartbeat: same schema version, no relations
+        follower
+            .apply(message(0x2000, 1, false), &state)
+            .await
+            .unwrap();
+
+        let handover = follower.handover.as_ref().unwrap();
+        assert_eq!(handover.confirmed_lsn, 0x2000);
+        assert_eq!(handover.relations.len(), 1);
+        assert_eq!(state.confirmed_lsn(), 0x2000);
+        assert_eq!(state.relations().await.1.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_apply_rejects_other_slot() {
+        let state = make_state();
+        let mut follower = Follower::new("other_slot");
+        let err = follower
+            .apply(message(0x1000, 1, true), &state)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("other_slot"));
+    }
+
+    #[tokio::test]
+    async fn test_apply_reports_stopping() {
+        let state = make_state();
+        let mut follower = Follower::new("dbmazz_slot");
+        let mut stopping = message(0x1000, 0, false);
+        stopping.stopping = true;
+        assert!(follower.apply(stopping, &state).await.unwrap());
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! WAL position check before `START_REPLICATION`.
+//!
+//! PostgreSQL answers a start position it can't serve either with an opaque
+//! error ("requested WAL segment has already been removed") or not at all: a
+//! position behind the slot's `confirmed_flush_lsn` silently streams from the
+//! slot's position instead, and one past the end of WAL silently skips every
+//! transaction until the server gets there. Before each start, the engine
+//! reads the slot and the current WAL position and refuses those cases with
+//! what happened and what to do about it.
+//!
+//! Checkpoints not marked `slot_follows_checkpoint` are the exception: their
+//! writer confirmed the server's WAL end in keepalive replies, so their slot
+//! is normally ahead of them. That is logged, and streaming starts from
+//! the slot's position as it did before the check existed.
+
+use std::fmt;
+
+use anyhow::{Context, Result};
+use tokio_postgres::Client;
+
+use crate::checkpoint_store::CheckpointStamp;
+use crate::utils::{format_pg_lsn, parse_pg_lsn};
+
+/// The slot and the server's WAL, as read before starting.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct SlotPosition {
+    pub confirmed_flush_lsn: u64,
+    /// `lost` once PostgreSQL removed WAL the slot needs
+    pub wal_status: Option<String>,
+    /// `pg_current_wal_lsn()`
+    pub current_wal_lsn: u64,
+}
+
+/// Why streaming can't start from the requested position
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum StartPositionError {
+    SlotMissing {
+        slot_name: String,
+    },
+    SlotLost {
+        slot_name: String,
+        start_lsn: u64,
+    },
+    /// The slot already confirmed past the start: what is in between can't
+    /// be streamed again
+    BehindSlot {
+        slot_name: String,
+        start_lsn: u64,
+        confirmed_flush_lsn: u64,
+    },
+    /// The start is past the end of WAL, e.g. the source was restored from a
+    /// backup or replaced while the checkpoint was kept
+    AheadOfWal {
+        start_lsn: u64,
+        current_wal_lsn: u64,
+    },
+}
+
+impl fmt::Display for StartPositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartPositionError::SlotMissing { slot_name } => write!(
+                f,
+                "replication slot {} does not exist: it was dropped, or the source database \
+                 was replaced. Re-snapshot: remove the stored checkpoint and restart with \
+                 DO_SNAPSHOT=true to create the slot again",
+                slot_name
+            ),
+            StartPositionError::SlotLost {
+                slot_name,
+                start_lsn,
+            } => write!(
+                f,
+                "replication slot {} was invalidated (wal_status lost): PostgreSQL removed WAL \
+                 it needed to stream from {}, usually after a downtime longer than \
+                 max_slot_wal_keep_size allows. Re-snapshot: drop the slot, remove the stored \
+                 checkpoint and restart with DO_SNAPSHOT=true",
+                slot_name,
+                format_pg_lsn(*start_lsn)
+            ),
+            StartPositionError::BehindSlot {
+                slot_name,
+                start_lsn,
+                confirmed_flush_lsn,
+            } => write!(
+                f,
+                "cannot stream from {}: replication slot {} already confirmed up to {}, and \
+                 changes in between cannot be replayed (the slot was recreated or advanced by \
+                 another consumer). Re-snapshot: remove the stored checkpoint and restart with \
+                 DO_SNAPSHOT=true, or remove it alone to accept the gap and stream from the slot",
+                format_pg_lsn(*start_lsn),
+                slot_name,
+                format_pg_lsn(*confirmed_flush_lsn)
+            ),
+            StartPositionError::AheadOfWal {
+                start_lsn,
+                current_wal_lsn,
+            } => write!(
+                f,
+                "cannot stream from {}: the source's WAL ends at {}, so the checkpoint belongs \
+                 to another server or to the source before it was restored from a backup. \
+                 Re-snapshot: remove the stored checkpoint and restart with DO_SNAPSHOT=true",
+                format_pg_lsn(*start_lsn),
+                format_pg_lsn(*current_wal_lsn)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StartPositionError {}
+
+/// Checks `start_lsn` against the slot. 0 means the slot's own position,
+/// which is always valid while the slot is.
+pub(super) fn check_start_position(
+    slot_name: &str,
+    start_lsn: u64,
+    slot: Option<&SlotPosition>,
+) -> Result<(), StartPositionError> {
+    let Some(slot) = slot else {
+        return Err(StartPositionError::SlotMissing {
+            slot_name: slot_name.to_string(),
+        });
+    };
+    if slot.wal_status.as_deref() == Some("lost") {
+        return Err(StartPositionError::SlotLost {
+            slot_name: slot_name.to_string(),
+            start_lsn: start_lsn.max(slot.confirmed_flush_lsn),
+        });
+    }
+    if start_lsn == 0 {
+        return Ok(());
+    }
+    if start_lsn < slot.confirmed_flush_lsn {
+        return Err(StartPositionError::BehindSlot {
+            slot_name: slot_name.to_string(),
+            start_lsn,
+            confirmed_flush_lsn: slot.confirmed_flush_lsn,
+        });
+    }
+    if start_lsn > slot.current_wal_lsn {
+        return Err(StartPositionError::AheadOfWal {
+            start_lsn,
+            current_wal_lsn: slot.current_wal_lsn,
+        });
+    }
+    Ok(())
+}
+
+/// What to log instead of `error` when it is expected with a checkpoint
+/// stamped `checkpoint`: a slot ahead of a checkpoint whose writer didn't
+/// keep the slot behind it was moved there by keepalive replies, over WAL
+/// without changes to replicate. `None` when `error` stands.
+pub(super) fn expected_after_upgrade(
+    error: &StartPositionError,
+    checkpoint: Option<&CheckpointStamp>,
+) -> Option<String> {
+    let StartPositionError::BehindSlot {
+        slot_name,
+        start_lsn,
+        confirmed_flush_lsn,
+    } = error
+    else {
+        return None;
+    };
+    let checkpoint = checkpoint.filter(|c| !c.slot_follows_checkpoint)?;
+    Some(format!(
+        "slot {} is at {}, past the stored checkpoint {} written by dbmazz {}, whose \
+         keepalive replies confirmed WAL without changes to replicate; streaming from the \
+         slot's position",
+        slot_name,
+        format_pg_lsn(*confirmed_flush_lsn),
+        format_pg_lsn(*start_lsn),
+        checkpoint
+            .stamp
+            .written_by
+            .as_deref()
+            .unwrap_or("(unknown)"),
+    ))
+}
+
+/// Reads the slot's position, `None` when there is no such slot.
+pub(super) async fn read_slot_position(
+    client: &Client,
+    slot_name: &str,
+) -> Result<Option<SlotPosition>> {
+    let row = client
+        .query_opt(
+            "SELECT confirmed_flush_lsn::text, wal_status, pg_current_wal_lsn()::text
+             FROM pg_replication_slots WHERE slot_name = $1",
+            &[&slot_name],
+        )
+        .await
+        .context("failed to query pg_replication_slots")?;
+
+    let lsn = |text: Option<String>| text.as_deref().and_then(parse_pg_lsn).unwrap_or(0);
+    Ok(row.map(|row| SlotPosition {
+        confirmed_flush_lsn: lsn(row.get(0)),
+        wal_status: row.get(1),
+        current_wal_lsn: lsn(row.get(2)),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_format::FormatStamp;
+
+    fn slot(wal_status: &str) -> SlotPosition {
+        SlotPosition {
+            confirmed_flush_lsn: 0x1800,
+            wal_status: Some(wal_status.to_string()),
+            current_wal_lsn: 0x4000,
+        }
+    }
+
+    #[test]
+    fn test_start_within_slot_range() {
+        let slot = slot("reserved");
+        for start_lsn in [0, 0x1800, 0x2000, 0x4000] {
+            assert_eq!(check_start_position("s", start_lsn, Some(&slot)), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_start_outside_slot_range() {
+        let slot = slot("extended");
+        assert_eq!(
+            check_start_position("s", 0x1000, Some(&slot)),
+            Err(StartPositionError::BehindSlot {
+                slot_name: "s".to_string(),
+                start_lsn: 0x1000,
+                confirmed_flush_lsn: 0x1800,
+            })
+        );
+        let ahead = check_start_position("s", 0x5000, Some(&slot)).unwrap_err();
+        assert_eq!(
+            ahead,
+            StartPositionError::AheadOfWal {
+                start_lsn: 0x5000,
+                current_wal_lsn: 0x4000,
+            }
+        );
+        assert!(ahead
+            .to_string()
+            .contains("the source's WAL ends at 0/4000"));
+    }
+
+    #[test]
+    fn test_slot_ahead_of_checkpoint_before_upgrade() {
+        let behind = check_start_position("s", 0x1000, Some(&slot("reserved"))).unwrap_err();
+        let stamp = |slot_follows_checkpoint| CheckpointStamp {
+            stamp: FormatStamp {
+                format: 1,
+                written_by: Some("0.1.0".to_string()),
+            },
+            slot_follows_checkpoint,
+        };
+        // Written while keepalive replies still moved the slot
+        assert!(expected_after_upgrade(&behind, Some(&CheckpointStamp::default())).is_some());
+        let msg = expected_after_upgrade(&behind, Some(&stamp(false))).unwrap();
+        assert!(msg.starts_with("slot s is at 0/1800, past the stored checkpoint 0/1000"));
+        assert!(msg.contains("written by dbmazz 0.1.0"));
+        assert_eq!(expected_after_upgrade(&behind, Some(&stamp(true))), None);
+        // No stored checkpoint, e.g. a handed-over position
+        assert_eq!(expected_after_upgrade(&behind, None), None);
+
+        let ahead = check_start_position("s", 0x5000, Some(&slot("reserved"))).unwrap_err();
+        assert_eq!(
+            expected_after_upgrade(&ahead, Some(&CheckpointStamp::default())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_slot_lost_or_missing() {
+        // Even from the slot's own position
+        let lost = check_start_position("s", 0, Some(&slot("lost"))).unwrap_err();
+        assert_eq!(
+            lost,
+            StartPositionError::SlotLost {
+                slot_name: "s".to_string(),
+                start_lsn: 0x1800,
+            }
+        );
+        assert!(lost.to_string().contains("DO_SNAPSHOT=true"));
+        assert!(matches!(
+            check_start_position("s", 0x2000, None),
+            Err(StartPositionError::SlotMissing { .. })
+        ));
+    }
+}
@@ -0,0 +1,476 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Slot monitor and WAL retention guard.
+//!
+//! A replication slot pins WAL on the source until its changes are confirmed.
+//! When the sink stalls, the slot stops advancing and `pg_wal` grows until the
+//! primary runs out of disk. The monitor polls `pg_replication_slots` every
+//! `WAL_RETENTION_CHECK_INTERVAL_SECS` and publishes the slot's `restart_lsn`,
+//! `confirmed_flush_lsn` and retained WAL in `SharedState`, so they show up in
+//! `GetStatus`, `/status` and the metrics.
+//!
+//! With `WAL_RETENTION_MAX_MB` set, it also guards the slot: once the retained
+//! WAL crosses the threshold (or PostgreSQL marks the slot `unreserved`), it
+//! posts to `WAL_RETENTION_WEBHOOK_URL` if set, and takes the configured action:
+//!
+//! - `alert`: log an error and report the breach in `GetStatus`
+//! - `archive_tee`: divert batches to a local archive so the slot keeps moving
+//! - `drop_slot`: drop the slot and stop the engine (explicit opt-in)
+//!
+//! The webhook is posted again when the slot is back under the threshold.
+//!
+//! On PostgreSQL 14+, it also reads the slot's decoding counters from
+//! `pg_stat_replication_slots` into the metrics. A transaction larger than
+//! `logical_decoding_work_mem` is spilled to disk by the walsender and sent
+//! all at once after its commit; with `PGOUTPUT_PROTOCOL_VERSION=1`, when
+//! the slot spills, the monitor logs how much next to the throughput dbmazz
+//! had meanwhile, since protocol version 2 would stream those transactions.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::Client;
+use tracing::{error, info, warn};
+
+use super::setup;
+use crate::config::{HttpClientConfig, WalRetentionAction, WalRetentionConfig};
+use crate::connectors::http::client_builder;
+use crate::grpc::state::{CdcState, ErrorCategory, SharedState, SlotStats};
+use crate::source::pg_error;
+use crate::source::session::PgSession;
+use crate::utils::{format_pg_lsn, parse_pg_lsn};
+
+/// One `pg_replication_slots` measurement for the slot.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct SlotWalUsage {
+    /// Oldest WAL the slot still needs
+    pub restart_lsn: u64,
+    /// Position confirmed by dbmazz
+    pub confirmed_flush_lsn: u64,
+    /// Bytes between `restart_lsn` and the current WAL position
+    pub retained_bytes: u64,
+    /// Bytes between `confirmed_flush_lsn` and the current WAL position
+    pub flush_lag_bytes: u64,
+    /// Bytes that can still be written before the slot is invalidated
+    /// (PostgreSQL 13+, `None` when `max_slot_wal_keep_size` is unlimited)
+    pub safe_wal_size: Option<i64>,
+    pub wal_status: Option<String>,
+}
+
+impl SlotWalUsage {
+    /// True when the retained WAL is over the threshold, or when PostgreSQL is
+    /// already about to invalidate the slot.
+    pub fn is_breached(&self, max_bytes: u64) -> bool {
+        self.retained_bytes >= max_bytes || self.wal_status.as_deref() == Some("unreserved")
+    }
+}
+
+/// The spill hint is logged at most this often
+const SPILL_HINT_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Polls the slot until the engine shuts down, guarding it when `guard` is
+/// set (`WAL_RETENTION_MAX_MB`). Spawned by the engine once streaming starts;
+/// alerts go through the sink's proxy (`http`).
+pub(super) async fn run_wal_guard(
+    guard: Option<WalRetentionConfig>,
+    http: HttpClientConfig,
+    check_interval_secs: u64,
+    session: PgSession,
+    database_url: String,
+    slot_name: String,
+    protocol_version: u32,
+    state: Arc<SharedState>,
+) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut interval = tokio::time::interval(Duration::from_secs(check_interval_secs));
+    let mut client: Option<Client> = None;
+    // Cleared on servers without pg_stat_replication_slots
+    let mut slot_stats_supported = true;
+    // Previous counters, with the events processed and when
+    let mut previous_stats: Option<(SlotStats, u64, Instant)> = None;
+    let mut last_spill_hint: Option<Instant> = None;
+
+    match &guard {
+        Some(guard) => info!(
+            "WAL retention guard started: slot {}, threshold {} MB, action {}",
+            slot_name,
+            guard.max_bytes / (1024 * 1024),
+            guard.action
+        ),
+        None => info!(
+            "Slot monitor started: slot {}, every {}s",
+            slot_name, check_interval_secs
+        ),
+    }
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                if *shutdown_rx.borrow() {
+                    return;
+                }
+            }
+            _ = interval.tick() => {}
+        }
+
+        // Keep one connection open; reconnect after any failure
+        if client.as_ref().map_or(true, |c| c.is_closed()) {
+            match setup::postgres::create_postgres_client(&session, &database_url).await {
+                Ok(c) => client = Some(c),
+                Err(e) => {
+                    warn!("WAL retention guard: cannot connect to PostgreSQL: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some(pg) = client.as_ref() else { continue };
+
+        let usage = match query_slot_usage(pg, &slot_name).await {
+            Ok(Some(usage)) => usage,
+            Ok(None) => {
+                warn!("Slot monitor: slot {} not found", slot_name);
+                continue;
+            }
+            Err(e) => {
+                warn!("Slot monitor: {:#}", e);
+                client = None;
+                continue;
+            }
+        };
+
+        state.set_slot_position(
+            usage.restart_lsn,
+            usage.confirmed_flush_lsn,
+            usage.flush_lag_bytes,
+        );
+
+        if slot_stats_supported {
+            match query_slot_stats(pg, &slot_name).await {
+                Ok(Some(stats)) => {
+                    let events = state.events_processed();
+                    let now = Instant::now();
+                    let mut spill_rate = 0;
+                    if let Some((previous, previous_events, at)) = previous_stats {
+                        let elapsed = now.duration_since(at);
+                        spill_rate = per_second(stats.spill_bytes, previous.spill_bytes, elapsed);
+                        let hint_due =
+                            last_spill_hint.map_or(true, |at| at.elapsed() >= SPILL_HINT_INTERVAL);
+                        if protocol_version < 2 && hint_due {
+                            let events_rate = per_second(events, previous_events, elapsed);
+                            if let Some(hint) = spill_hint(&previous, &stats, events_rate, elapsed)
+                            {
+                                info!("Slot {}: {}", slot_name, hint);
+                                last_spill_hint = Some(now);
+                            }
+                        }
+                    }
+                    state.set_slot_stats(stats, spill_rate);
+                    previous_stats = Some((stats, events, now));
+                }
+                Ok(None) => {}
+                Err(e) if is_undefined_table(&e) => {
+                    info!("pg_stat_replication_slots needs PostgreSQL 14+; slot statistics are not reported");
+                    slot_stats_supported = false;
+                }
+                Err(e) => warn!("Slot monitor: {:#}", e),
+            }
+        }
+        let Some(guard) = &guard else {
+            state.set_wal_retention(usage.retained_bytes, false);
+            continue;
+        };
+        let breached = usage.is_breached(guard.max_bytes);
+        if !state.set_wal_retention(usage.retained_bytes, breached) {
+            continue;
+        }
+
+        if let Some(url) = &guard.webhook_url {
+            let alert = webhook_alert(&slot_name, guard, &usage, breached);
+            if let Err(e) = post_alert(url, &http, &alert).await {
+                warn!(
+                    "WAL retention guard: failed to post alert to WAL_RETENTION_WEBHOOK_URL: {}",
+                    e
+                );
+            }
+        }
+
+        if !breached {
+            info!(
+                "WAL retention back under threshold: slot {} retains {} MB",
+                slot_name,
+                usage.retained_bytes / (1024 * 1024)
+            );
+            continue;
+        }
+
+        error!(
+            "WAL retention threshold exceeded: slot {} retains {} MB (limit {} MB, safe_wal_size {:?}, wal_status {:?})",
+            slot_name,
+            usage.retained_bytes / (1024 * 1024),
+            guard.max_bytes / (1024 * 1024),
+            usage.safe_wal_size,
+            usage.wal_status
+        );
+        state
+            .record_error(
+                ErrorCategory::WalRetention,
+                format!(
+                    "slot {} retains {} MB (limit {} MB), action {}",
+                    slot_name,
+                    usage.retained_bytes / (1024 * 1024),
+                    guard.max_bytes / (1024 * 1024),
+                    guard.action
+                ),
+                None,
+                0,
+            )
+            .await;
+
+        match guard.action {
+            WalRetentionAction::Alert => {}
+            WalRetentionAction::ArchiveTee => {
+                if !state.is_archive_tee_active() {
+                    error!(
+                        "Switching to archive-tee mode: batches now go to {} instead of the sink",
+                        guard.archive_dir
+                    );
+                    state.enable_archive_tee();
+                }
+            }
+            WalRetentionAction::DropSlot => {
+                error!("Dropping replication slot {} to release WAL", slot_name);
+                // The slot is gone either way; shutdown must not try again
+                state.set_skip_slot_cleanup(true);
+                state.set_state(CdcState::Stopped);
+                if let Err(e) =
+                    setup::cleanup_postgres_resources(&session, &database_url, &slot_name).await
+                {
+                    error!(
+                        "WAL retention guard: failed to drop slot {}: {}",
+                        slot_name, e
+                    );
+                }
+                let _ = state.shutdown_tx.send(true);
+                return;
+            }
+        }
+    }
+}
+
+async fn query_slot_usage(client: &Client, slot_name: &str) -> Result<Option<SlotWalUsage>> {
+    let row = client
+        .query_opt(
+            "SELECT restart_lsn::text, confirmed_flush_lsn::text,
+                    pg_wal_lsn_diff(pg_current_wal_lsn(), restart_lsn)::bigint,
+                    pg_wal_lsn_diff(pg_current_wal_lsn(), confirmed_flush_lsn)::bigint,
+                    safe_wal_size, wal_status
+             FROM pg_replication_slots WHERE slot_name = $1",
+            &[&slot_name],
+        )
+        .await
+        .context("failed to query pg_replication_slots")?;
+
+    let lsn = |text: Option<String>| text.as_deref().and_then(parse_pg_lsn).unwrap_or(0);
+    let bytes = |diff: Option<i64>| diff.unwrap_or(0).max(0) as u64;
+    Ok(row.map(|row| SlotWalUsage {
+        restart_lsn: lsn(row.get(0)),
+        confirmed_flush_lsn: lsn(row.get(1)),
+        retained_bytes: bytes(row.get(2)),
+        flush_lag_bytes: bytes(row.get(3)),
+        safe_wal_size: row.get(4),
+        wal_status: row.get(5),
+    }))
+}
+
+async fn query_slot_stats(client: &Client, slot_name: &str) -> Result<Option<SlotStats>> {
+    let row = client
+        .query_opt(
+            "SELECT spill_txns, spill_count, spill_bytes, stream_txns, stream_count,
+                    stream_bytes, total_txns, total_bytes
+             FROM pg_stat_replication_slots WHERE slot_name = $1",
+            &[&slot_name],
+        )
+        .await
+        .context("failed to query pg_stat_replication_slots")?;
+
+    let counter =
+        |row: &tokio_postgres::Row, i| row.get::<_, Option<i64>>(i).unwrap_or(0).max(0) as u64;
+    Ok(row.map(|row| SlotStats {
+        spill_txns: counter(&row, 0),
+        spill_count: counter(&row, 1),
+        spill_bytes: counter(&row, 2),
+        stream_txns: counter(&row, 3),
+        stream_count: counter(&row, 4),
+        stream_bytes: counter(&row, 5),
+        total_txns: counter(&row, 6),
+        total_bytes: counter(&row, 7),
+    }))
+}
+
+fn is_undefined_table(err: &anyhow::Error) -> bool {
+    pg_error::find(err).and_then(|e| e.code()) == Some(&SqlState::UNDEFINED_TABLE)
+}
+
+/// Growth of a counter per second; PostgreSQL counters can be reset, which
+/// counts as growth from 0.
+fn per_second(now: u64, previous: u64, elapsed: Duration) -> u64 {
+    let growth = if now >= previous { now - previous } else { now };
+    let millis = elapsed.as_millis().max(1) as u64;
+    growth.saturating_mul(1000) / millis
+}
+
+/// What the slot spilled to disk between two checks, next to what dbmazz
+/// processed meanwhile; `None` when it didn't spill.
+fn spill_hint(
+    previous: &SlotStats,
+    now: &SlotStats,
+    events_per_second: u64,
+    elapsed: Duration,
+) -> Option<String> {
+    let growth = |now: u64, previous: u64| if now >= previous { now - previous } else { now };
+    let spill_txns = growth(now.spill_txns, previous.spill_txns);
+    let spill_bytes = growth(now.spill_bytes, previous.spill_bytes);
+    if spill_txns == 0 && spill_bytes == 0 {
+        return None;
+    }
+    let total_bytes = growth(now.total_bytes, previous.total_bytes);
+    let share = if total_bytes > 0 {
+        format!(" ({}% of the WAL decoded)", spill_bytes * 100 / total_bytes)
+    } else {
+        String::new()
+    };
+    Some(format!(
+        "{} transaction(s) spilled {} MB to disk in {}s{}, while dbmazz processed {} events/s. \
+         Large transactions reach dbmazz only after their commit; \
+         PGOUTPUT_PROTOCOL_VERSION=2 streams them as they run",
+        spill_txns,
+        spill_bytes / (1024 * 1024),
+        elapsed.as_secs(),
+        share,
+        events_per_second
+    ))
+}
+
+/// Body posted to `WAL_RETENTION_WEBHOOK_URL` when the guard trips
+/// (`wal_retention_breached`) and when the slot is back under the threshold
+/// (`wal_retention_recovered`).
+fn webhook_alert(
+    slot_name: &str,
+    guard: &WalRetentionConfig,
+    usage: &SlotWalUsage,
+    breached: bool,
+) -> serde_json::Value {
+    json!({
+        "event": if breached { "wal_retention_breached" } else { "wal_retention_recovered" },
+        "slot_name": slot_name,
+        "retained_wal_bytes": usage.retained_bytes,
+        "max_bytes": guard.max_bytes,
+        "action": guard.action.to_string(),
+        "restart_lsn": format_pg_lsn(usage.restart_lsn),
+        "confirmed_flush_lsn": format_pg_lsn(usage.confirmed_flush_lsn),
+        "flush_lag_bytes": usage.flush_lag_bytes,
+        "safe_wal_size": usage.safe_wal_size,
+        "wal_status": usage.wal_status,
+        "at": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Posts `alert` to the webhook. Errors leave out the URL, which may carry
+/// a token.
+async fn post_alert(url: &str, http: &HttpClientConfig, alert: &serde_json::Value) -> Result<()> {
+    client_builder(http)?
+        .build()?
+        .post(url)
+        .timeout(Duration::from_secs(10))
+        .json(alert)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(reqwest::Error::without_url)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(retained_bytes: u64, wal_status: &str) -> SlotWalUsage {
+        SlotWalUsage {
+            restart_lsn: 0x1000,
+            confirmed_flush_lsn: 0x1800,
+            retained_bytes,
+            flush_lag_bytes: 0,
+            safe_wal_size: None,
+            wal_status: Some(wal_status.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_breach_on_retained_bytes() {
+        assert!(!usage(999, "reserved").is_breached(1_000));
+        assert!(usage(1_000, "reserved").is_breached(1_000));
+        assert!(usage(5_000, "extended").is_breached(1_000));
+    }
+
+    #[test]
+    fn test_breach_when_slot_unreserved() {
+        // PostgreSQL will invalidate the slot at the next checkpoint
+        assert!(usage(10, "unreserved").is_breached(1_000));
+    }
+
+    #[test]
+    fn test_webhook_alert_body() {
+        let guard = WalRetentionConfig {
+            max_bytes: 1_000,
+            action: WalRetentionAction::Alert,
+            archive_dir: "./wal-archive".to_string(),
+            webhook_url: Some("http://alerts.local/hook".to_string()),
+        };
+        let alert = webhook_alert("dbmazz_slot", &guard, &usage(5_000, "extended"), true);
+        assert_eq!(alert["event"], "wal_retention_breached");
+        assert_eq!(alert["slot_name"], "dbmazz_slot");
+        assert_eq!(alert["retained_wal_bytes"], 5_000);
+        assert_eq!(alert["action"], "alert");
+        assert_eq!(alert["restart_lsn"], "0/1000");
+        assert_eq!(alert["confirmed_flush_lsn"], "0/1800");
+
+        let alert = webhook_alert("dbmazz_slot", &guard, &usage(10, "reserved"), false);
+        assert_eq!(alert["event"], "wal_retention_recovered");
+    }
+
+    #[test]
+    fn test_spill_hint() {
+        let previous = SlotStats {
+            spill_txns: 3,
+            spill_bytes: 10 << 20,
+            total_bytes: 100 << 20,
+            ..Default::default()
+        };
+        assert_eq!(
+            spill_hint(&previous, &previous, 500, Duration::from_secs(30)),
+            None
+        );
+
+        let now = SlotStats {
+            spill_txns: 5,
+            spill_bytes: 74 << 20,
+            total_bytes: 228 << 20,
+            ..previous
+        };
+        let hint = spill_hint(&previous, &now, 500, Duration::from_secs(30)).unwrap();
+        assert!(hint.starts_with(
+            "2 transaction(s) spilled 64 MB to disk in 30s (50% of the WAL decoded), \
+             while dbmazz processed 500 events/s"
+        ));
+        assert_eq!(
+            per_second(74 << 20, 10 << 20, Duration::from_secs(32)),
+            2 << 20
+        );
+        // Reset counters count from 0
+        assert_eq!(per_second(100, 5_000, Duration::from_secs(1)), 100);
+    }
+}
@@ -0,0 +1,100 @@
+//! Caller roles for the gRPC API.
+//!
+//! Every caller is a viewer. A caller presenting `GRPC_OPERATOR_TOKEN` as
+//! `authorization: Bearer <token>` metadata is an operator, which is required
+//! for RPCs that discard data such as `ResetMetrics`. Without a configured
+//! token nobody is an operator and those RPCs are refused.
+
+use tonic::{Request, Status};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Viewer,
+    Operator,
+}
+
+#[derive(Clone, Default)]
+pub struct Authorizer {
+    operator_token: Option<String>,
+}
+
+impl Authorizer {
+    pub fn new(operator_token: Option<String>) -> Self {
+        Self { operator_token }
+    }
+
+    /// Role of the caller, from its `authorization` metadata.
+    pub fn role<T>(&self, request: &Request<T>) -> Role {
+        let Some(expected) = self.operator_token.as_deref() else {
+            return Role::Viewer;
+        };
+        let presented = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match presented {
+            Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+                Role::Operator
+            }
+            _ => Role::Viewer,
+        }
+    }
+
+    /// Fails with `PERMISSION_DENIED` unless the caller is an operator.
+    pub fn require_operator<T>(&self, request: &Request<T>, rpc: &str) -> Result<(), Status> {
+        if self.role(request) == Role::Operator {
+            return Ok(());
+        }
+        if self.operator_token.is_none() {
+            return Err(Status::permission_denied(format!(
+                "{} requires the operator role; set GRPC_OPERATOR_TOKEN to enable it",
+                rpc
+            )));
+        }
+        Err(Status::permission_denied(format!(
+            "{} requires the operator role",
+            rpc
+        )))
+    }
+}
+
+/// Compares tokens without stopping at the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: Option<&str>) -> Request<()> {
+        let mut request = Request::new(());
+        if let Some(value) = authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", value.parse().unwrap());
+        }
+        request
+    }
+
+    #[test]
+    fn test_operator_needs_matching_token() {
+        let auth = Authorizer::new(Some("s3cret".to_string()));
+        assert_eq!(auth.role(&request(Some("Bearer s3cret"))), Role::Operator);
+        assert_eq!(auth.role(&request(Some("Bearer wrong"))), Role::Viewer);
+        assert_eq!(auth.role(&request(Some("s3cret"))), Role::Viewer);
+        assert_eq!(auth.role(&request(None)), Role::Viewer);
+    }
+
+    #[test]
+    fn test_no_operator_without_token() {
+        let auth = Authorizer::default();
+        assert_eq!(auth.role(&request(Some("Bearer "))), Role::Viewer);
+        let status = auth
+            .require_operator(&request(Some("Bearer x")), "ResetMetrics")
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(status.message().contains("GRPC_OPERATOR_TOKEN"));
+    }
+}
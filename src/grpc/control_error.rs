@@ -0,0 +1,193 @@
+//! Why a control RPC was refused, in a form callers can act on.
+//!
+//! Refusals decided by the engine's state (pausing while draining, resuming
+//! a table that isn't paused) are answered in-band, as a `ControlResponse`
+//! with `success: false` and its `error` set. Calls that never got that far
+//! (the pipeline isn't running, an argument is invalid, no answer in time)
+//! fail with a gRPC status whose details hold the same `ControlError`,
+//! protobuf-encoded, so clients read one type either way.
+
+use std::time::Duration;
+
+use prost::Message;
+use tonic::{Code, Status};
+
+use super::services::dbmazz as proto;
+use super::state::CdcState;
+
+/// What kind of refusal, see `ControlErrorCode` in the proto
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// Already as requested, e.g. pausing while paused
+    AlreadyDone,
+    /// Not possible in the current state, e.g. pausing while draining
+    InvalidState,
+    InvalidArgument,
+    /// Needs a setting that is off
+    NotEnabled,
+    /// The pipeline isn't running (yet), or stopped
+    Unavailable,
+    /// The pipeline didn't answer in time; the request is still applied
+    Timeout,
+    /// The pipeline tried and failed
+    Failed,
+}
+
+impl ErrorCode {
+    fn grpc_code(self) -> Code {
+        match self {
+            ErrorCode::AlreadyDone | ErrorCode::InvalidState | ErrorCode::NotEnabled => {
+                Code::FailedPrecondition
+            }
+            ErrorCode::InvalidArgument => Code::InvalidArgument,
+            ErrorCode::Unavailable => Code::Unavailable,
+            ErrorCode::Timeout => Code::DeadlineExceeded,
+            ErrorCode::Failed => Code::Aborted,
+        }
+    }
+
+    fn to_proto(self) -> proto::ControlErrorCode {
+        use proto::ControlErrorCode as P;
+        match self {
+            ErrorCode::AlreadyDone => P::ControlErrorAlreadyDone,
+            ErrorCode::InvalidState => P::ControlErrorInvalidState,
+            ErrorCode::InvalidArgument => P::ControlErrorInvalidArgument,
+            ErrorCode::NotEnabled => P::ControlErrorNotEnabled,
+            ErrorCode::Unavailable => P::ControlErrorUnavailable,
+            ErrorCode::Timeout => P::ControlErrorTimeout,
+            ErrorCode::Failed => P::ControlErrorFailed,
+        }
+    }
+}
+
+/// A refused control call: what happened, and what it was about
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// CDC state when refused
+    pub state: Option<CdcState>,
+    pub table: Option<String>,
+    pub lsn: Option<u64>,
+    /// Suggested wait before retrying
+    pub retry_after: Option<Duration>,
+}
+
+impl ControlError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            state: None,
+            table: None,
+            lsn: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn with_state(mut self, state: CdcState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn with_table(mut self, table: impl Into<String>) -> Self {
+        self.table = Some(table.into());
+        self
+    }
+
+    pub fn with_lsn(mut self, lsn: u64) -> Self {
+        self.lsn = Some(lsn);
+        self
+    }
+
+    pub fn retry_after(mut self, wait: Duration) -> Self {
+        self.retry_after = Some(wait);
+        self
+    }
+
+    /// Whether the same call may succeed later. A timed out call is still
+    /// applied, so it isn't retried.
+    pub fn retryable(&self) -> bool {
+        match self.code {
+            ErrorCode::Unavailable => self.state != Some(CdcState::Stopped),
+            ErrorCode::Failed => true,
+            _ => self.retry_after.is_some(),
+        }
+    }
+
+    pub fn to_proto(&self) -> proto::ControlError {
+        proto::ControlError {
+            code: self.code.to_proto() as i32,
+            state: self
+                .state
+                .map(|s| s.as_str().to_string())
+                .unwrap_or_default(),
+            table: self.table.clone().unwrap_or_default(),
+            lsn: self.lsn.unwrap_or(0),
+            retryable: self.retryable(),
+            retry_after_ms: self.retry_after.map_or(0, |d| d.as_millis() as u64),
+        }
+    }
+
+    /// The in-band answer: `success: false` with the error attached.
+    pub fn response(self) -> proto::ControlResponse {
+        proto::ControlResponse {
+            success: false,
+            error: Some(self.to_proto()),
+            message: self.message,
+        }
+    }
+}
+
+impl From<ControlError> for Status {
+    fn from(error: ControlError) -> Self {
+        let details = error.to_proto().encode_to_vec();
+        Status::with_details(error.code.grpc_code(), error.message, details.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_carries_details() {
+        let error = ControlError::new(ErrorCode::Unavailable, "Pipeline is not running")
+            .with_state(CdcState::Running)
+            .retry_after(Duration::from_secs(1));
+        let status = Status::from(error);
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.message(), "Pipeline is not running");
+
+        let details = proto::ControlError::decode(status.details()).unwrap();
+        assert_eq!(
+            details.code(),
+            proto::ControlErrorCode::ControlErrorUnavailable
+        );
+        assert_eq!(details.state, "running");
+        assert!(details.retryable);
+        assert_eq!(details.retry_after_ms, 1000);
+    }
+
+    #[test]
+    fn test_response_refuses_in_band() {
+        let response = ControlError::new(ErrorCode::AlreadyDone, "orders is already paused")
+            .with_table("public.orders")
+            .with_lsn(0x40)
+            .response();
+        assert!(!response.success);
+        assert_eq!(response.message, "orders is already paused");
+        let error = response.error.unwrap();
+        assert_eq!(
+            error.code(),
+            proto::ControlErrorCode::ControlErrorAlreadyDone
+        );
+        assert_eq!(error.table, "public.orders");
+        assert_eq!(error.lsn, 0x40);
+        assert!(!error.retryable);
+
+        let stopped = ControlError::new(ErrorCode::Unavailable, "Pipeline stopped")
+            .with_state(CdcState::Stopped);
+        assert!(!stopped.retryable());
+    }
+}
@@ -1,38 +1,317 @@
+//! gRPC API of the engine.
+//!
+//! The server is bound before the engine leaves its first setup stage, so
+//! health checks answer while setup runs, and a bad `GRPC_LISTEN` or TLS
+//! file fails startup instead of leaving the engine unreachable.
+//! `ReloadConfig(restart_grpc)` restarts it in place: the TLS files are read
+//! again, calls in flight are answered, open streams get a few seconds to
+//! finish, and the listener is bound anew.
+
+mod auth;
+pub mod control_error;
 mod cpu_metrics;
 mod services;
 pub mod state;
 
-use services::{control_service, health_service, metrics_service, status_service};
+use anyhow::{Context, Result};
+use auth::Authorizer;
+use services::{control_service, health_service, metrics_service, standby_service, status_service};
 use state::SharedState;
+use std::future::Future;
+use std::path::Path;
 use std::sync::Arc;
-use tonic::transport::Server;
+use std::time::Duration;
+use tokio::net::{TcpListener, UnixListener};
+use tokio::sync::{oneshot, watch};
+use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
+use tonic::transport::server::Router;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
 use tonic_reflection::server::Builder as ReflectionBuilder;
-use tracing::info;
+use tracing::{error, info, warn};
+
+use crate::config::{Config, GrpcListen, GrpcTlsConfig};
+use crate::runtime;
 
+pub use services::dbmazz as proto;
+pub use services::relation_from_proto;
 pub use state::{CdcConfig, CdcState, Stage};
 
-/// Starts the gRPC server on the specified port
+/// How long open streams (`TailEvents`, `StreamMetrics`) may hold back a
+/// restart before they are cut
+const RESTART_GRACE: Duration = Duration::from_secs(5);
+
+/// Pause between attempts to bind again after a restart
+const REBIND_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings of the gRPC server (`GRPC_*`).
+#[derive(Clone)]
+pub struct GrpcServerConfig {
+    pub listen: GrpcListen,
+    pub tls: Option<GrpcTlsConfig>,
+    /// Grants the operator role (see [`auth`])
+    pub operator_token: Option<String>,
+}
+
+impl GrpcServerConfig {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            listen: config.grpc_listen.clone(),
+            tls: config.grpc_tls.clone(),
+            operator_token: config.grpc_operator_token.clone(),
+        }
+    }
+}
+
+/// Binds the gRPC server and serves it in the background, restarting it
+/// when asked. Returns once the listener is bound.
 pub async fn start_grpc_server(
-    port: u16,
+    config: GrpcServerConfig,
     shared_state: Arc<SharedState>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let addr = format!("0.0.0.0:{}", port).parse()?;
+) -> Result<()> {
+    let router = router(&config, &shared_state)?;
+    let listener = Listener::bind(&config.listen).await?;
+    info!("gRPC server listening on {}", config.listen);
 
-    info!("gRPC server listening on {}", addr);
+    let restarts = shared_state.grpc_restart.subscribe();
+    runtime::spawn_worker(serve(config, shared_state, restarts, router, listener));
+    Ok(())
+}
 
+async fn serve(
+    config: GrpcServerConfig,
+    shared_state: Arc<SharedState>,
+    mut restarts: watch::Receiver<()>,
+    mut router: Router,
+    mut listener: Listener,
+) {
+    loop {
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let mut serving = tokio::spawn(listener.serve(router, async move {
+            let _ = stop_rx.await;
+        }));
+
+        // A restart whose TLS files can't be used keeps the running server
+        let next = loop {
+            tokio::select! {
+                result = &mut serving => {
+                    match result {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => error!("gRPC server error: {}", e),
+                        Err(e) => error!("gRPC server task failed: {}", e),
+                    }
+                    return;
+                }
+                Ok(()) = restarts.changed() => match router(&config, &shared_state) {
+                    Ok(next) => break next,
+                    Err(e) => error!("Not restarting the gRPC server: {:#}", e),
+                },
+            }
+        };
+
+        info!("Restarting gRPC server");
+        let _ = stop_tx.send(());
+        if tokio::time::timeout(RESTART_GRACE, &mut serving)
+            .await
+            .is_err()
+        {
+            warn!(
+                "gRPC streams still open after {}s, closing them",
+                RESTART_GRACE.as_secs()
+            );
+            serving.abort();
+            let _ = serving.await;
+        }
+
+        router = next;
+        listener = loop {
+            match Listener::bind(&config.listen).await {
+                Ok(listener) => break listener,
+                Err(e) => {
+                    error!("{:#}, retrying", e);
+                    tokio::time::sleep(REBIND_INTERVAL).await;
+                }
+            }
+        };
+        info!("gRPC server listening on {}", config.listen);
+    }
+}
+
+/// The services, behind TLS when configured.
+fn router(config: &GrpcServerConfig, shared_state: &Arc<SharedState>) -> Result<Router> {
     // Configure reflection service so grpcurl works without .proto files
     let reflection_service = ReflectionBuilder::configure()
         .register_encoded_file_descriptor_set(services::dbmazz::FILE_DESCRIPTOR_SET)
         .build_v1()?;
 
-    Server::builder()
+    let authorizer = Authorizer::new(config.operator_token.clone());
+    Ok(server(config.tls.as_ref())?
         .add_service(reflection_service)
         .add_service(health_service(shared_state.clone()))
         .add_service(control_service(shared_state.clone()))
-        .add_service(status_service(shared_state.clone()))
-        .add_service(metrics_service(shared_state.clone()))
-        .serve(addr)
-        .await?;
+        .add_service(status_service(shared_state.clone(), authorizer.clone()))
+        .add_service(metrics_service(shared_state.clone(), authorizer))
+        .add_service(standby_service(shared_state.clone())))
+}
 
-    Ok(())
+/// A server builder with `tls` read from disk and checked.
+fn server(tls: Option<&GrpcTlsConfig>) -> Result<Server> {
+    let Some(tls) = tls else {
+        return Ok(Server::builder());
+    };
+    let read = |path: &str| std::fs::read(path).with_context(|| format!("Failed to read {}", path));
+    let mut config = ServerTlsConfig::new().identity(Identity::from_pem(
+        read(&tls.cert_path)?,
+        read(&tls.key_path)?,
+    ));
+    if let Some(path) = &tls.client_ca_path {
+        config = config.client_ca_root(Certificate::from_pem(read(path)?));
+    }
+    Server::builder()
+        .tls_config(config)
+        .with_context(|| format!("Invalid gRPC TLS certificate or key ({})", tls.cert_path))
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Listener {
+    async fn bind(listen: &GrpcListen) -> Result<Self> {
+        match listen {
+            GrpcListen::Tcp(addr) => TcpListener::bind(addr)
+                .await
+                .map(Listener::Tcp)
+                .with_context(|| format!("Failed to bind the gRPC server to {}", addr)),
+            GrpcListen::Unix(path) => {
+                remove_stale_socket(path)?;
+                UnixListener::bind(path)
+                    .map(Listener::Unix)
+                    .with_context(|| format!("Failed to bind the gRPC server to {}", listen))
+            }
+        }
+    }
+
+    async fn serve(
+        self,
+        router: Router,
+        stop: impl Future<Output = ()> + Send,
+    ) -> Result<(), tonic::transport::Error> {
+        match self {
+            Listener::Tcp(listener) => {
+                router
+                    .serve_with_incoming_shutdown(TcpListenerStream::new(listener), stop)
+                    .await
+            }
+            Listener::Unix(listener) => {
+                router
+                    .serve_with_incoming_shutdown(UnixListenerStream::new(listener), stop)
+                    .await
+            }
+        }
+    }
+}
+
+/// Removes a socket left at `path` by an earlier run, or by this one before
+/// a restart. Anything else at `path` is left alone and fails the bind.
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display())),
+        Ok(_) => anyhow::bail!(
+            "Failed to bind the gRPC server: {} exists and is not a socket",
+            path.display()
+        ),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::proto::health_service_client::HealthServiceClient;
+    use crate::grpc::proto::HealthCheckRequest;
+    use hyper_util::rt::TokioIo;
+    use tonic::transport::{Endpoint, Uri};
+
+    async fn check_health(path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        let channel = Endpoint::from_static("http://localhost")
+            .connect_with_connector(tower::service_fn(move |_: Uri| {
+                let path = path.clone();
+                async move {
+                    let stream = tokio::net::UnixStream::connect(path).await?;
+                    Ok::<_, std::io::Error>(TokioIo::new(stream))
+                }
+            }))
+            .await?;
+        HealthServiceClient::new(channel)
+            .check(HealthCheckRequest {})
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restart_on_unix_socket() {
+        let path = std::env::temp_dir().join(format!("dbmazz-grpc-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // Left behind by an earlier run
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let shared_state = SharedState::new(CdcConfig {
+            flush_size: 1,
+            flush_interval_ms: 1,
+            tables: Vec::new(),
+            slot_name: "slot".to_string(),
+        });
+        let config = GrpcServerConfig {
+            listen: GrpcListen::Unix(path.clone()),
+            tls: None,
+            operator_token: None,
+        };
+        start_grpc_server(config, shared_state.clone())
+            .await
+            .unwrap();
+        check_health(&path).await.unwrap();
+
+        assert!(shared_state.request_grpc_restart());
+        // Served again once the new listener is bound
+        let mut served = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            if check_health(&path).await.is_ok() {
+                served = true;
+                break;
+            }
+        }
+        assert!(served);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_tls_files_are_checked() {
+        let dir = std::env::temp_dir();
+        let cert_path = dir.join(format!("dbmazz-grpc-{}.pem", std::process::id()));
+        let key_path = dir.join(format!("dbmazz-grpc-{}.key", std::process::id()));
+        std::fs::write(&cert_path, "not a certificate").unwrap();
+        let _ = std::fs::remove_file(&key_path);
+        let tls = GrpcTlsConfig {
+            cert_path: cert_path.display().to_string(),
+            key_path: key_path.display().to_string(),
+            client_ca_path: None,
+        };
+        let err = format!("{:#}", server(Some(&tls)).unwrap_err());
+        assert!(err.contains(&tls.key_path), "{}", err);
+
+        // Read, but not a certificate and key
+        std::fs::copy(&cert_path, &key_path).unwrap();
+        assert!(server(Some(&tls)).is_err());
+        assert!(server(None).is_ok());
+
+        let _ = std::fs::remove_file(&cert_path);
+        let _ = std::fs::remove_file(&key_path);
+    }
 }
@@ -2,9 +2,22 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::time::{interval, Duration};
 use tonic::{Request, Response, Status};
+use tracing::info;
 
+use crate::core::pool;
+use crate::grpc::auth::Authorizer;
+use crate::grpc::control_error::{ControlError, ErrorCode};
 use crate::grpc::cpu_metrics::CpuTracker;
-use crate::grpc::state::{CdcState, SharedState, Stage};
+use crate::grpc::state::{
+    CdcState, ErrorRecord, MetricKind, MetricSample, SharedState, Stage, TableListRequest,
+    RECENT_ERRORS_CAPACITY,
+};
+use crate::pipeline::invalidate::InvalidateRequest;
+use crate::pipeline::paused::{TableControlRequest, TablePauseMode};
+use crate::pipeline::peek::{PeekRequest, SampledEvent, DEFAULT_PEEK_LIMIT, MAX_PEEK_LIMIT};
+use crate::resources;
+use crate::source::clock;
+use crate::source::parser::{intern, CdcMessage, Column};
 
 // Include the generated protobuf code
 pub mod dbmazz {
@@ -20,11 +33,19 @@ use dbmazz::{
     cdc_status_service_server::{CdcStatusService, CdcStatusServiceServer},
     health_check_response::ServingStatus,
     health_service_server::{HealthService, HealthServiceServer},
+    metric::Kind as ProtoMetricKind,
+    standby_service_server::{StandbyService, StandbyServiceServer},
     status_response::CdcState as ProtoCdcState,
-    ControlResponse, DrainRequest, HealthCheckRequest, HealthCheckResponse, MetricsRequest,
-    MetricsResponse, PauseRequest, PauseSnapshotRequest, ReloadConfigRequest, ResumeRequest,
-    ResumeSnapshotRequest, StartSnapshotRequest, StatusRequest, StatusResponse, StopRequest,
-    TableSnapshotProgress,
+    ColumnMismatch, ControlResponse, DrainRequest, EffectiveConfigRequest, EffectiveConfigResponse,
+    ErrorEntry, HealthCheckRequest, HealthCheckResponse, InvalidateSinkSchemaRequest,
+    ListMetricsRequest, ListMetricsResponse, Metric, MetricsRequest, MetricsResponse, PauseRequest,
+    PauseSnapshotRequest, PauseTableRequest, PausedTable, PeekBatchRequest, PeekBatchResponse,
+    PeekedColumn, PeekedEvent, RecentErrorsRequest, RecentErrorsResponse, RelationColumn,
+    RelationState, ReloadConfigRequest, ResetMetricsRequest, ResetMetricsResponse, ResumeRequest,
+    ResumeSnapshotRequest, ResumeTableRequest, SetLogLevelRequest, StandbyState,
+    StandbyStateRequest, StartSnapshotRequest, StatusRequest, StatusResponse, StopRequest,
+    TableFreshness, TableSnapshotProgress, TableStatus, TableStatusRequest, TableStatusResponse,
+    TailEventsRequest, TailEventsResponse,
 };
 
 // ============================================================================
@@ -66,6 +87,7 @@ impl HealthService for HealthServiceImpl {
             Stage::Setup => 2,    // STAGE_SETUP
             Stage::Cdc => 3,      // STAGE_CDC
             Stage::Snapshot => 4, // STAGE_SNAPSHOT
+            Stage::Standby => 5,  // STAGE_STANDBY
         };
 
         Ok(Response::new(HealthCheckResponse {
@@ -110,19 +132,20 @@ impl CdcControlService for CdcControlServiceImpl {
             Ok(Response::new(ControlResponse {
                 success: true,
                 message: "CDC paused successfully".to_string(),
+                error: None,
             }))
         } else {
             let current = self.shared_state.state();
-            match current {
-                CdcState::Paused => Ok(Response::new(ControlResponse {
-                    success: false,
-                    message: "CDC is already paused".to_string(),
-                })),
-                _ => Ok(Response::new(ControlResponse {
-                    success: false,
-                    message: format!("Cannot pause CDC in state: {:?}", current),
-                })),
-            }
+            let error = match current {
+                CdcState::Paused => {
+                    ControlError::new(ErrorCode::AlreadyDone, "CDC is already paused")
+                }
+                _ => ControlError::new(
+                    ErrorCode::InvalidState,
+                    format!("Cannot pause CDC in state: {:?}", current),
+                ),
+            };
+            Ok(Response::new(error.with_state(current).response()))
         }
     }
 
@@ -139,19 +162,20 @@ impl CdcControlService for CdcControlServiceImpl {
             Ok(Response::new(ControlResponse {
                 success: true,
                 message: "CDC resumed successfully".to_string(),
+                error: None,
             }))
         } else {
             let current = self.shared_state.state();
-            match current {
-                CdcState::Running => Ok(Response::new(ControlResponse {
-                    success: false,
-                    message: "CDC is already running".to_string(),
-                })),
-                _ => Ok(Response::new(ControlResponse {
-                    success: false,
-                    message: format!("Cannot resume CDC in state: {:?}", current),
-                })),
-            }
+            let error = match current {
+                CdcState::Running => {
+                    ControlError::new(ErrorCode::AlreadyDone, "CDC is already running")
+                }
+                _ => ControlError::new(
+                    ErrorCode::InvalidState,
+                    format!("Cannot resume CDC in state: {:?}", current),
+                ),
+            };
+            Ok(Response::new(error.with_state(current).response()))
         }
     }
 
@@ -168,16 +192,19 @@ impl CdcControlService for CdcControlServiceImpl {
                 Ok(Response::new(ControlResponse {
                     success: true,
                     message: "CDC is draining and will stop".to_string(),
+                    error: None,
                 }))
             }
-            CdcState::Draining => Ok(Response::new(ControlResponse {
-                success: false,
-                message: "CDC is already draining".to_string(),
-            })),
-            CdcState::Stopped => Ok(Response::new(ControlResponse {
-                success: false,
-                message: "CDC is already stopped".to_string(),
-            })),
+            CdcState::Draining => Ok(Response::new(
+                ControlError::new(ErrorCode::AlreadyDone, "CDC is already draining")
+                    .with_state(current)
+                    .response(),
+            )),
+            CdcState::Stopped => Ok(Response::new(
+                ControlError::new(ErrorCode::AlreadyDone, "CDC is already stopped")
+                    .with_state(current)
+                    .response(),
+            )),
         }
     }
 
@@ -188,10 +215,11 @@ impl CdcControlService for CdcControlServiceImpl {
         let req = request.into_inner();
         let current = self.shared_state.state();
         match current {
-            CdcState::Stopped => Ok(Response::new(ControlResponse {
-                success: false,
-                message: "CDC is already stopped".to_string(),
-            })),
+            CdcState::Stopped => Ok(Response::new(
+                ControlError::new(ErrorCode::AlreadyDone, "CDC is already stopped")
+                    .with_state(current)
+                    .response(),
+            )),
             _ => {
                 // Set cleanup flag before triggering shutdown
                 self.shared_state
@@ -208,6 +236,7 @@ impl CdcControlService for CdcControlServiceImpl {
                 Ok(Response::new(ControlResponse {
                     success: true,
                     message,
+                    error: None,
                 }))
             }
         }
@@ -218,6 +247,54 @@ impl CdcControlService for CdcControlServiceImpl {
         request: Request<ReloadConfigRequest>,
     ) -> Result<Response<ControlResponse>, Status> {
         let req = request.into_inner();
+        // Read like TABLES; the publication watcher updates the publication
+        // and the table list with it
+        let tables = req
+            .tables
+            .iter()
+            .map(|t| crate::config::parse_table_name(t))
+            .collect::<anyhow::Result<Vec<String>>>()
+            .map_err(|e| ControlError::new(ErrorCode::InvalidArgument, format!("{:#}", e)))?;
+        if req.restart_grpc {
+            // Checked here as well, so the caller learns of a broken file
+            let tls = self
+                .shared_state
+                .effective_config()
+                .await
+                .and_then(|c| c.grpc_tls);
+            if let Err(e) = super::server(tls.as_ref()) {
+                return Ok(Response::new(
+                    ControlError::new(
+                        ErrorCode::Failed,
+                        format!("Not restarting the gRPC server: {:#}", e),
+                    )
+                    .response(),
+                ));
+            }
+        }
+        if req.drop_unlisted_tables && tables.is_empty() {
+            return Ok(Response::new(
+                ControlError::new(
+                    ErrorCode::InvalidArgument,
+                    "drop_unlisted_tables needs the list of tables to keep",
+                )
+                .response(),
+            ));
+        }
+        let request = TableListRequest {
+            tables: tables.clone(),
+            drop_unlisted: req.drop_unlisted_tables,
+        };
+        if !tables.is_empty() && !self.shared_state.request_tables(request) {
+            return Ok(Response::new(
+                ControlError::new(
+                    ErrorCode::NotEnabled,
+                    "Changing tables needs the publication watcher; \
+                     set PUBLICATION_CHECK_INTERVAL_SECS above 0",
+                )
+                .response(),
+            ));
+        }
         let mut config = self.shared_state.config.write().await;
 
         let mut changes = Vec::new();
@@ -233,21 +310,35 @@ impl CdcControlService for CdcControlServiceImpl {
             changes.push(format!("flush_interval_ms={}", req.flush_interval_ms));
         }
 
-        if !req.tables.is_empty() {
-            config.tables = req.tables.clone();
-            changes.push(format!("tables={:?}", req.tables));
+        if !tables.is_empty() {
+            changes.push(format!(
+                "tables={:?} (the publication is updated in the background{})",
+                tables,
+                if req.drop_unlisted_tables {
+                    ", unlisted tables are dropped"
+                } else {
+                    ", unlisted tables are kept"
+                }
+            ));
+        }
+
+        if req.restart_grpc && self.shared_state.request_grpc_restart() {
+            changes.push("gRPC server restarting".to_string());
         }
 
         if changes.is_empty() {
-            Ok(Response::new(ControlResponse {
-                success: false,
-                message: "No configuration changes provided (use 0 to keep current values)"
-                    .to_string(),
-            }))
+            Ok(Response::new(
+                ControlError::new(
+                    ErrorCode::InvalidArgument,
+                    "No configuration changes provided (use 0 to keep current values)",
+                )
+                .response(),
+            ))
         } else {
             Ok(Response::new(ControlResponse {
                 success: true,
                 message: format!("Configuration reloaded: {}", changes.join(", ")),
+                error: None,
             }))
         }
     }
@@ -260,6 +351,7 @@ impl CdcControlService for CdcControlServiceImpl {
         Ok(Response::new(ControlResponse {
             success: true,
             message: "Snapshot triggered".to_string(),
+            error: None,
         }))
     }
 
@@ -271,12 +363,14 @@ impl CdcControlService for CdcControlServiceImpl {
             return Ok(Response::new(ControlResponse {
                 success: true,
                 message: "No snapshot running".to_string(),
+                error: None,
             }));
         }
         self.shared_state.set_snapshot_paused(true);
         Ok(Response::new(ControlResponse {
             success: true,
             message: "Snapshot paused".to_string(),
+            error: None,
         }))
     }
 
@@ -288,10 +382,205 @@ impl CdcControlService for CdcControlServiceImpl {
         Ok(Response::new(ControlResponse {
             success: true,
             message: "Snapshot resumed".to_string(),
+            error: None,
+        }))
+    }
+
+    async fn invalidate_sink_schema(
+        &self,
+        request: Request<InvalidateSinkSchemaRequest>,
+    ) -> Result<Response<ControlResponse>, Status> {
+        let invalidate_tx = self
+            .shared_state
+            .invalidate_sender()
+            .ok_or_else(|| self.pipeline_unavailable("Pipeline is not running"))?;
+
+        // Read like TABLES: `"UserEvents"` keeps its case, `Orders` is `orders`
+        let requested = request.into_inner().table;
+        let table = Some(requested.clone())
+            .filter(|t| !t.is_empty())
+            .map(|t| crate::config::parse_table_name(&t))
+            .transpose()
+            .map_err(|e| {
+                ControlError::new(ErrorCode::InvalidArgument, format!("{:#}", e))
+                    .with_table(&requested)
+            })?;
+        let (reply, reply_rx) = tokio::sync::oneshot::channel();
+        invalidate_tx
+            .send(InvalidateRequest {
+                table: table.clone(),
+                reply,
+            })
+            .await
+            .map_err(|_| self.pipeline_unavailable("Pipeline is not running"))?;
+        let result = self.await_pipeline(reply_rx, table.as_deref()).await?;
+
+        Ok(Response::new(match result {
+            Ok(tables) if tables.is_empty() => ControlResponse {
+                success: true,
+                message: "No table has had changes yet; nothing to invalidate".to_string(),
+                error: None,
+            },
+            Ok(tables) => ControlResponse {
+                success: true,
+                message: format!("Sink schema re-applied for {}", tables.join(", ")),
+                error: None,
+            },
+            Err(message) => {
+                let error = ControlError::new(ErrorCode::Failed, message);
+                match table {
+                    Some(table) => error.with_table(table),
+                    None => error,
+                }
+                .response()
+            }
+        }))
+    }
+
+    async fn pause_table(
+        &self,
+        request: Request<PauseTableRequest>,
+    ) -> Result<Response<ControlResponse>, Status> {
+        let req = request.into_inner();
+        let mode = match req.mode() {
+            dbmazz::TablePauseMode::TablePauseBuffer => TablePauseMode::Buffer,
+            dbmazz::TablePauseMode::TablePauseSkip => TablePauseMode::Skip,
+        };
+        self.control_table(&req.table, Some(mode)).await
+    }
+
+    async fn resume_table(
+        &self,
+        request: Request<ResumeTableRequest>,
+    ) -> Result<Response<ControlResponse>, Status> {
+        self.control_table(&request.into_inner().table, None).await
+    }
+
+    async fn set_log_level(
+        &self,
+        request: Request<SetLogLevelRequest>,
+    ) -> Result<Response<ControlResponse>, Status> {
+        let req = request.into_inner();
+        let (change, filter) = crate::logging::set_level(&req.target, &req.level)
+            .map_err(|e| ControlError::new(ErrorCode::InvalidArgument, format!("{:#}", e)))?;
+        let target = if change.target.is_empty() {
+            "all targets"
+        } else {
+            change.target.as_str()
+        };
+        let level = if req.level.trim().is_empty() {
+            "the startup level".to_string()
+        } else {
+            req.level.trim().to_lowercase()
+        };
+        info!(
+            "Log level of {} set to {}; filter: {}",
+            target, level, filter
+        );
+
+        let mut message = format!("Log level of {} set to {}", target, level);
+        if req.duration_secs > 0 {
+            message.push_str(&format!(" for {}s", req.duration_secs));
+            let duration = Duration::from_secs(req.duration_secs);
+            tokio::spawn(async move {
+                tokio::time::sleep(duration).await;
+                match crate::logging::revert(&change) {
+                    Ok(Some(filter)) => info!(
+                        "Log level change of {} expired; filter: {}",
+                        change.target, filter
+                    ),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Failed to undo a log level change: {:#}", e),
+                }
+            });
+        }
+        Ok(Response::new(ControlResponse {
+            success: true,
+            message: format!("{} (filter: {})", message, filter),
+            error: None,
+        }))
+    }
+}
+
+impl CdcControlServiceImpl {
+    /// Sends a `PauseTable` (`Some`) or `ResumeTable` (`None`) to the pipeline.
+    async fn control_table(
+        &self,
+        table: &str,
+        pause: Option<TablePauseMode>,
+    ) -> Result<Response<ControlResponse>, Status> {
+        let control_tx = self
+            .shared_state
+            .table_control_sender()
+            .ok_or_else(|| self.pipeline_unavailable("Pipeline is not running"))?;
+        if table.is_empty() {
+            return Err(ControlError::new(ErrorCode::InvalidArgument, "table is required").into());
+        }
+        let table = crate::config::parse_table_name(table).map_err(|e| {
+            ControlError::new(ErrorCode::InvalidArgument, format!("{:#}", e)).with_table(table)
+        })?;
+
+        let (reply, reply_rx) = tokio::sync::oneshot::channel();
+        control_tx
+            .send(TableControlRequest {
+                table: table.clone(),
+                pause,
+                reply,
+            })
+            .await
+            .map_err(|_| self.pipeline_unavailable("Pipeline is not running"))?;
+        let result = self.await_pipeline(reply_rx, Some(&table)).await?;
+
+        Ok(Response::new(match result {
+            Ok(message) => ControlResponse {
+                success: true,
+                message,
+                error: None,
+            },
+            Err(error) => error.response(),
         }))
     }
+
+    /// Waits for the pipeline's answer to a request about `table`.
+    async fn await_pipeline<T>(
+        &self,
+        reply_rx: tokio::sync::oneshot::Receiver<T>,
+        table: Option<&str>,
+    ) -> Result<T, ControlError> {
+        let with_table = |error: ControlError| match table {
+            Some(table) => error.with_table(table),
+            None => error,
+        };
+        match tokio::time::timeout(INVALIDATE_TIMEOUT, reply_rx).await {
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(_)) => Err(with_table(self.pipeline_unavailable("Pipeline stopped"))),
+            Err(_) => Err(with_table(ControlError::new(
+                ErrorCode::Timeout,
+                "Pipeline did not answer; the request is applied once the batch being \
+                 written is done",
+            ))),
+        }
+    }
+
+    /// The pipeline takes requests once CDC is streaming; until then (setup,
+    /// snapshot) a retry gets through, after a stop it never will.
+    fn pipeline_unavailable(&self, message: &str) -> ControlError {
+        let state = self.shared_state.state();
+        let error = ControlError::new(ErrorCode::Unavailable, message).with_state(state);
+        match state {
+            CdcState::Stopped => error,
+            _ => error.retry_after(PIPELINE_RETRY_AFTER),
+        }
+    }
 }
 
+/// How long `InvalidateSinkSchema`, `PauseTable` and `ResumeTable` wait for
+/// the pipeline, which answers between batches
+const INVALIDATE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Retry hint for control calls made before the pipeline runs
+const PIPELINE_RETRY_AFTER: Duration = Duration::from_secs(5);
+
 pub fn control_service(
     shared_state: Arc<SharedState>,
 ) -> CdcControlServiceServer<CdcControlServiceImpl> {
@@ -304,16 +593,23 @@ pub fn control_service(
 
 pub struct CdcStatusServiceImpl {
     shared_state: Arc<SharedState>,
+    authorizer: Authorizer,
 }
 
 impl CdcStatusServiceImpl {
-    pub fn new(shared_state: Arc<SharedState>) -> Self {
-        Self { shared_state }
+    pub fn new(shared_state: Arc<SharedState>, authorizer: Authorizer) -> Self {
+        Self {
+            shared_state,
+            authorizer,
+        }
     }
 }
 
 #[tonic::async_trait]
 impl CdcStatusService for CdcStatusServiceImpl {
+    type TailEventsStream =
+        tokio_stream::wrappers::ReceiverStream<Result<TailEventsResponse, Status>>;
+
     async fn get_status(
         &self,
         _request: Request<StatusRequest>,
@@ -330,14 +626,17 @@ impl CdcStatusService for CdcStatusServiceImpl {
         };
 
         let snapshot_active = stage == Stage::Snapshot;
+        let (skipped_from_lsn, skipped_to_lsn) =
+            self.shared_state.skipped_range().unwrap_or((0, 0));
 
-        // Build per-table progress only when snapshot is active
+        // Build per-table progress only when snapshot is active, labelled
+        // with the sink table name
         let table_progress = if snapshot_active {
             let progress_map = self.shared_state.get_table_progress().await;
             progress_map
                 .into_iter()
                 .map(|(table_name, p)| TableSnapshotProgress {
-                    table_name,
+                    table_name: config.sink.naming.sink_table(&table_name).label(),
                     chunks_total: p.chunks_total,
                     chunks_done: p.chunks_done,
                     rows_synced: p.rows_synced,
@@ -346,6 +645,7 @@ impl CdcStatusService for CdcStatusServiceImpl {
         } else {
             vec![]
         };
+        let limits = resources::limits();
 
         Ok(Response::new(StatusResponse {
             state: proto_state as i32,
@@ -360,14 +660,269 @@ impl CdcStatusService for CdcStatusServiceImpl {
             snapshot_rows_synced: self.shared_state.snapshot_rows_synced(),
             table_progress,
             snapshot_paused: self.shared_state.is_snapshot_paused(),
+            skipped_from_lsn,
+            skipped_to_lsn,
+            retained_wal_bytes: self.shared_state.retained_wal_bytes(),
+            wal_retention_breached: self.shared_state.is_wal_retention_breached(),
+            archive_tee_active: self.shared_state.is_archive_tee_active(),
+            slot_holder_pid: self.shared_state.slot_holder_pid().unwrap_or(0),
+            recent_errors: self
+                .shared_state
+                .recent_errors(STATUS_RECENT_ERRORS)
+                .await
+                .into_iter()
+                .map(error_to_proto)
+                .collect(),
+            total_errors: self.shared_state.total_errors(),
+            table_freshness: self
+                .shared_state
+                .table_freshness()
+                .into_iter()
+                .map(|(table_name, f)| TableFreshness {
+                    table_name,
+                    latency_ms: f.latency_ms,
+                    sla_ms: f.sla_ms.unwrap_or(0),
+                    breached: f.breached,
+                    breaches: f.breaches,
+                })
+                .collect(),
+            sink_circuit_state: self.shared_state.sink_circuit().as_str().to_string(),
+            sink_retries: self.shared_state.sink_retries(),
+            paused_tables: self
+                .shared_state
+                .paused_tables()
+                .into_iter()
+                .map(|p| PausedTable {
+                    table_name: p.diff.table,
+                    sink_table: p.diff.sink_table,
+                    table_missing: p.diff.table_missing,
+                    columns: p
+                        .diff
+                        .columns
+                        .into_iter()
+                        .map(|c| ColumnMismatch {
+                            column: c.column,
+                            expected_type: c.expected_type,
+                            actual_type: c.actual_type.unwrap_or_default(),
+                        })
+                        .collect(),
+                    held_rows: p.held_rows,
+                    sink_error: p.sink_error.unwrap_or_default(),
+                })
+                .collect(),
+            slot_restart_lsn: self.shared_state.slot_restart_lsn(),
+            slot_confirmed_flush_lsn: self.shared_state.slot_confirmed_flush_lsn(),
+            slot_flush_lag_bytes: self.shared_state.slot_flush_lag_bytes(),
+            clock_skew_ms: clock::skew_ms(),
+            future_commit_timestamps: clock::future_commits(),
+            cpu_limit_millicores: limits.cpu_millicores.unwrap_or(0),
+            memory_limit_bytes: limits.memory_bytes.unwrap_or(0),
+            memory_usage_bytes: resources::memory_usage().unwrap_or(0),
+        }))
+    }
+
+    async fn get_recent_errors(
+        &self,
+        request: Request<RecentErrorsRequest>,
+    ) -> Result<Response<RecentErrorsResponse>, Status> {
+        let limit = match request.into_inner().limit {
+            0 => RECENT_ERRORS_CAPACITY,
+            n => n as usize,
+        };
+        let errors = self.shared_state.recent_errors(limit).await;
+        Ok(Response::new(RecentErrorsResponse {
+            errors: errors.into_iter().map(error_to_proto).collect(),
+            total_errors: self.shared_state.total_errors(),
         }))
     }
+
+    async fn peek_batch(
+        &self,
+        request: Request<PeekBatchRequest>,
+    ) -> Result<Response<PeekBatchResponse>, Status> {
+        if request.get_ref().reveal_values {
+            self.authorizer
+                .require_operator(&request, "PeekBatch with reveal_values")?;
+        }
+        let peek_tx = self
+            .shared_state
+            .peek_sender()
+            .ok_or_else(|| Status::unavailable("Pipeline is not running"))?;
+
+        let req = request.into_inner();
+        let (reply, reply_rx) = tokio::sync::oneshot::channel();
+        let peek = PeekRequest {
+            limit: match req.limit {
+                0 => DEFAULT_PEEK_LIMIT,
+                n => (n as usize).min(MAX_PEEK_LIMIT),
+            },
+            table: Some(req.table).filter(|t| !t.is_empty()),
+            reveal_values: req.reveal_values,
+            reply,
+        };
+        peek_tx
+            .send(peek)
+            .await
+            .map_err(|_| Status::unavailable("Pipeline is not running"))?;
+        let sample = tokio::time::timeout(PEEK_TIMEOUT, reply_rx)
+            .await
+            .map_err(|_| {
+                Status::deadline_exceeded(
+                    "Pipeline did not answer; it may be blocked outside the batch loop",
+                )
+            })?
+            .map_err(|_| Status::unavailable("Pipeline stopped"))?;
+
+        Ok(Response::new(PeekBatchResponse {
+            pending_events: sample.pending_events as u64,
+            flushing: sample.flushing,
+            lsn: sample.lsn,
+            values_redacted: sample.values_redacted,
+            events: sample.events.into_iter().map(event_to_proto).collect(),
+        }))
+    }
+
+    async fn tail_events(
+        &self,
+        request: Request<TailEventsRequest>,
+    ) -> Result<Response<Self::TailEventsStream>, Status> {
+        if request.get_ref().reveal_values {
+            self.authorizer
+                .require_operator(&request, "TailEvents with reveal_values")?;
+        }
+        let req = request.into_inner();
+        let values_redacted = !req.reveal_values;
+        let subscription = self
+            .shared_state
+            .tail
+            .subscribe(Some(req.table).filter(|t| !t.is_empty()), req.reveal_values);
+        // Events wait in the subscription's buffer, which never holds the
+        // pipeline back, rather than in the channel
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+
+        tokio::spawn(async move {
+            loop {
+                let next = tokio::select! {
+                    next = subscription.next() => next,
+                    _ = tx.closed() => break,
+                };
+                let message = match next {
+                    Ok(tailed) => Ok(TailEventsResponse {
+                        lsn: tailed.lsn,
+                        dropped_events: tailed.dropped_events,
+                        values_redacted,
+                        events: tailed.events.into_iter().map(event_to_proto).collect(),
+                    }),
+                    Err(overflowed) => Err(Status::resource_exhausted(format!(
+                        "Client fell more than {} events behind (TAIL_OVERFLOW=disconnect)",
+                        overflowed.buffer_events
+                    ))),
+                };
+                let last = message.is_err();
+                if tx.send(message).await.is_err() || last {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(tokio_stream::wrappers::ReceiverStream::new(
+            rx,
+        )))
+    }
+
+    async fn get_table_status(
+        &self,
+        request: Request<TableStatusRequest>,
+    ) -> Result<Response<TableStatusResponse>, Status> {
+        let table = request.into_inner().table;
+        let table = match table.as_str() {
+            "" => None,
+            t => {
+                let name = crate::config::parse_table_name(t)
+                    .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+                Some(if name.contains('.') {
+                    name
+                } else {
+                    format!("public.{}", name)
+                })
+            }
+        };
+        let tables = self.shared_state.table_status(table.as_deref());
+        Ok(Response::new(TableStatusResponse {
+            tables: tables
+                .into_iter()
+                .map(|t| TableStatus {
+                    pause_state: t.pause_state().to_string(),
+                    table_name: t.table,
+                    rows_processed: t.stats.rows_processed,
+                    last_lsn: t.stats.last_lsn,
+                    lag_ms: t.lag_ms.unwrap_or(0),
+                    held_rows: t.pause.map_or(0, |p| p.held_rows),
+                    skipped_rows: t.pause.map_or(0, |p| p.skipped_rows),
+                    needs_resync: t.resync_from_lsn.is_some(),
+                    resync_from_lsn: t.resync_from_lsn.unwrap_or(0),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_effective_config(
+        &self,
+        _request: Request<EffectiveConfigRequest>,
+    ) -> Result<Response<EffectiveConfigResponse>, Status> {
+        let config = self
+            .shared_state
+            .effective_config()
+            .await
+            .ok_or_else(|| Status::unavailable("Engine is not configured yet"))?;
+        Ok(Response::new(EffectiveConfigResponse {
+            config: format!("{:#?}", config),
+            version: crate::state_format::DBMAZZ_VERSION.to_string(),
+        }))
+    }
+}
+
+/// How long `PeekBatch` waits for the pipeline to answer
+const PEEK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors included in every `GetStatus` response
+const STATUS_RECENT_ERRORS: usize = 10;
+
+fn event_to_proto(event: SampledEvent) -> PeekedEvent {
+    PeekedEvent {
+        op: event.op.to_string(),
+        table: event.table,
+        columns: event
+            .columns
+            .into_iter()
+            .map(|c| PeekedColumn {
+                name: c.name,
+                type_oid: c.type_oid,
+                key: c.key,
+                is_null: c.is_null,
+                unchanged_toast: c.unchanged_toast,
+                value: c.value,
+                size_bytes: c.size_bytes as u64,
+            })
+            .collect(),
+    }
+}
+
+fn error_to_proto(record: ErrorRecord) -> ErrorEntry {
+    ErrorEntry {
+        category: record.category.as_str().to_string(),
+        message: record.message,
+        table: record.table.unwrap_or_default(),
+        lsn: record.lsn,
+        timestamp_ms: record.timestamp_ms,
+    }
 }
 
 pub fn status_service(
     shared_state: Arc<SharedState>,
+    authorizer: Authorizer,
 ) -> CdcStatusServiceServer<CdcStatusServiceImpl> {
-    CdcStatusServiceServer::new(CdcStatusServiceImpl::new(shared_state))
+    CdcStatusServiceServer::new(CdcStatusServiceImpl::new(shared_state, authorizer))
 }
 
 // ============================================================================
@@ -376,11 +931,15 @@ pub fn status_service(
 
 pub struct CdcMetricsServiceImpl {
     shared_state: Arc<SharedState>,
+    authorizer: Authorizer,
 }
 
 impl CdcMetricsServiceImpl {
-    pub fn new(shared_state: Arc<SharedState>) -> Self {
-        Self { shared_state }
+    pub fn new(shared_state: Arc<SharedState>, authorizer: Authorizer) -> Self {
+        Self {
+            shared_state,
+            authorizer,
+        }
     }
 }
 
@@ -438,6 +997,7 @@ impl CdcMetricsService for CdcMetricsServiceImpl {
                 // Read process CPU from /proc/[pid]/stat
                 // Consistent between Docker and bare metal
                 let cpu_millicores = cpu_tracker.cpu_millicores();
+                let limits = resources::limits();
 
                 let metrics = MetricsResponse {
                     timestamp,
@@ -449,6 +1009,10 @@ impl CdcMetricsService for CdcMetricsServiceImpl {
                     total_batches_sent: shared_state.batches_sent(),
                     cpu_millicores,
                     replication_lag_ms: shared_state.replication_lag_ms(),
+                    buffer_pool_hit_rate: pool::stats().hit_rate(),
+                    cpu_limit_millicores: limits.cpu_millicores.unwrap_or(0),
+                    memory_limit_bytes: limits.memory_bytes.unwrap_or(0),
+                    memory_usage_bytes: resources::memory_usage().unwrap_or(0),
                 };
 
                 if tx.send(Ok(metrics)).await.is_err() {
@@ -462,10 +1026,246 @@ impl CdcMetricsService for CdcMetricsServiceImpl {
             rx,
         )))
     }
+
+    async fn list_metrics(
+        &self,
+        _request: Request<ListMetricsRequest>,
+    ) -> Result<Response<ListMetricsResponse>, Status> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let metrics = self
+            .shared_state
+            .metric_samples()
+            .into_iter()
+            .map(metric_to_proto)
+            .collect();
+        Ok(Response::new(ListMetricsResponse { timestamp, metrics }))
+    }
+
+    async fn reset_metrics(
+        &self,
+        request: Request<ResetMetricsRequest>,
+    ) -> Result<Response<ResetMetricsResponse>, Status> {
+        self.authorizer.require_operator(&request, "ResetMetrics")?;
+        let reset: Vec<String> = self
+            .shared_state
+            .reset_counters()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        info!("Metrics reset by operator: {}", reset.join(", "));
+        Ok(Response::new(ResetMetricsResponse { reset }))
+    }
+}
+
+fn metric_to_proto(sample: MetricSample) -> Metric {
+    let kind = match sample.kind {
+        MetricKind::Counter => ProtoMetricKind::Counter,
+        MetricKind::Gauge => ProtoMetricKind::Gauge,
+    };
+    Metric {
+        name: sample.name.to_string(),
+        kind: kind as i32,
+        value: sample.value,
+        help: sample.help.to_string(),
+    }
 }
 
 pub fn metrics_service(
     shared_state: Arc<SharedState>,
+    authorizer: Authorizer,
 ) -> CdcMetricsServiceServer<CdcMetricsServiceImpl> {
-    CdcMetricsServiceServer::new(CdcMetricsServiceImpl::new(shared_state))
+    CdcMetricsServiceServer::new(CdcMetricsServiceImpl::new(shared_state, authorizer))
+}
+
+// ============================================================================
+// Standby Service
+// ============================================================================
+
+pub struct StandbyServiceImpl {
+    shared_state: Arc<SharedState>,
+}
+
+impl StandbyServiceImpl {
+    pub fn new(shared_state: Arc<SharedState>) -> Self {
+        Self { shared_state }
+    }
+}
+
+#[tonic::async_trait]
+impl StandbyService for StandbyServiceImpl {
+    type StreamStateStream = tokio_stream::wrappers::ReceiverStream<Result<StandbyState, Status>>;
+
+    async fn stream_state(
+        &self,
+        request: Request<StandbyStateRequest>,
+    ) -> Result<Response<Self::StreamStateStream>, Status> {
+        let request = request.into_inner();
+        let heartbeat = Duration::from_millis(match request.heartbeat_ms {
+            0 => 1000,
+            ms => ms as u64,
+        });
+        let standby_id = if request.standby_id.is_empty() {
+            "unnamed".to_string()
+        } else {
+            request.standby_id
+        };
+        info!("Standby {} connected", standby_id);
+
+        let shared_state = self.shared_state.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+
+        tokio::spawn(async move {
+            let mut handover_rx = shared_state.subscribe_handover();
+            let mut shutdown_rx = shared_state.shutdown_tx.subscribe();
+            let slot_name = shared_state.config.read().await.slot_name.clone();
+            // Relations go out in the first message and then only on change
+            let mut sent_version = None;
+
+            loop {
+                let shutting_down = *shutdown_rx.borrow();
+                let (schema_version, relations) = shared_state.relations().await;
+                let relations = if sent_version == Some(schema_version) {
+                    Vec::new()
+                } else {
+                    relations.iter().filter_map(relation_to_proto).collect()
+                };
+                sent_version = Some(schema_version);
+
+                let message = StandbyState {
+                    slot_name: slot_name.clone(),
+                    confirmed_lsn: shared_state.confirmed_lsn(),
+                    schema_version,
+                    relations,
+                    sent_at_ms: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis() as u64,
+                    // A kept slot is meant to be picked up, by the standby or a restart
+                    stopping: shutting_down && !shared_state.should_skip_slot_cleanup(),
+                };
+                if tx.send(Ok(message)).await.is_err() || shutting_down {
+                    break;
+                }
+
+                tokio::select! {
+                    changed = handover_rx.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                    }
+                    _ = shutdown_rx.changed() => {}
+                    _ = tokio::time::sleep(heartbeat) => {}
+                }
+            }
+            info!("Standby {} disconnected", standby_id);
+        });
+
+        Ok(Response::new(tokio_stream::wrappers::ReceiverStream::new(
+            rx,
+        )))
+    }
+}
+
+pub fn standby_service(shared_state: Arc<SharedState>) -> StandbyServiceServer<StandbyServiceImpl> {
+    StandbyServiceServer::new(StandbyServiceImpl::new(shared_state))
+}
+
+/// Converts a schema cache Relation message for the standby stream.
+pub fn relation_to_proto(message: &CdcMessage) -> Option<RelationState> {
+    let CdcMessage::Relation {
+        id,
+        namespace,
+        name,
+        replica_identity,
+        columns,
+    } = message
+    else {
+        return None;
+    };
+    Some(RelationState {
+        id: *id,
+        namespace: namespace.to_string(),
+        name: name.to_string(),
+        replica_identity: u32::from(*replica_identity),
+        columns: columns
+            .iter()
+            .map(|c| RelationColumn {
+                name: c.name.to_string(),
+                type_id: c.type_id,
+                type_mod: c.type_mod,
+                flags: u32::from(c.flags),
+            })
+            .collect(),
+    })
+}
+
+/// Inverse of [`relation_to_proto`], used by the standby to prime its schema cache.
+pub fn relation_from_proto(relation: RelationState) -> CdcMessage {
+    CdcMessage::Relation {
+        id: relation.id,
+        namespace: intern(&relation.namespace),
+        name: intern(&relation.name),
+        replica_identity: relation.replica_identity as u8,
+        columns: relation
+            .columns
+            .into_iter()
+            .map(|c| Column {
+                flags: c.flags as u8,
+                name: intern(&c.name),
+                type_id: c.type_id,
+                type_mod: c.type_mod,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relation_round_trip() {
+        let relation = CdcMessage::Relation {
+            id: 16384,
+            namespace: "public".into(),
+            name: "orders".into(),
+            replica_identity: b'f',
+            columns: vec![Column {
+                flags: 1,
+                name: "id".into(),
+                type_id: 20,
+                type_mod: -1,
+            }],
+        };
+        let proto = relation_to_proto(&relation).unwrap();
+        assert_eq!(proto.replica_identity, u32::from(b'f'));
+
+        let CdcMessage::Relation {
+            id,
+            replica_identity,
+            columns,
+            ..
+        } = relation_from_proto(proto)
+        else {
+            panic!("expected a Relation message");
+        };
+        assert_eq!(id, 16384);
+        assert_eq!(replica_identity, b'f');
+        assert_eq!(&*columns[0].name, "id");
+        assert_eq!(columns[0].type_mod, -1);
+        assert_eq!(columns[0].flags, 1);
+    }
+
+    #[test]
+    fn test_relation_to_proto_ignores_other_messages() {
+        let begin = CdcMessage::Begin {
+            final_lsn: 0,
+            timestamp: 0,
+            xid: 1,
+        };
+        assert!(relation_to_proto(&begin).is_none());
+    }
 }
@@ -1,7 +1,20 @@
-use std::collections::{BTreeMap, HashMap};
-use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
-use tokio::sync::{watch, RwLock};
+use tokio::sync::{mpsc, watch, RwLock};
+
+use crate::config::Config;
+use crate::core::schema_diff::SchemaDiff;
+use crate::core::{pool, timeout};
+use crate::pipeline::invalidate::InvalidateRequest;
+use crate::pipeline::paused::{TableControlRequest, TablePauseMode};
+use crate::pipeline::peek::PeekRequest;
+use crate::pipeline::schema_cache::RelationVersion;
+use crate::pipeline::tail::TailHub;
+use crate::resources;
+use crate::source::parser::CdcMessage;
+use crate::source::{clock, pg_error};
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -21,6 +34,45 @@ impl CdcState {
             _ => CdcState::Stopped,
         }
     }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CdcState::Running => "running",
+            CdcState::Paused => "paused",
+            CdcState::Draining => "draining",
+            CdcState::Stopped => "stopped",
+        }
+    }
+}
+
+/// Circuit breaker state of the sink, see `SINK_CIRCUIT_BREAKER`
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Batches are written normally
+    Closed = 0,
+    /// The sink kept failing; writes are held off until the open period ends
+    Open = 1,
+    /// A single probe write decides whether to close or re-open
+    HalfOpen = 2,
+}
+
+impl CircuitState {
+    pub fn from_u8(v: u8) -> Self {
+        match v {
+            1 => CircuitState::Open,
+            2 => CircuitState::HalfOpen,
+            _ => CircuitState::Closed,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -29,6 +81,73 @@ pub enum Stage {
     Setup,
     Snapshot,
     Cdc,
+    /// Following an active instance, ready to take over
+    Standby,
+}
+
+/// Number of errors kept for `GetRecentErrors`
+pub const RECENT_ERRORS_CAPACITY: usize = 100;
+
+/// Where a recorded error came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Setup,
+    Source,
+    Sink,
+    Schema,
+    Snapshot,
+    Checkpoint,
+    WalRetention,
+    Freshness,
+    Probe,
+    Spill,
+}
+
+impl ErrorCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::Setup => "setup",
+            ErrorCategory::Source => "source",
+            ErrorCategory::Sink => "sink",
+            ErrorCategory::Schema => "schema",
+            ErrorCategory::Snapshot => "snapshot",
+            ErrorCategory::Checkpoint => "checkpoint",
+            ErrorCategory::WalRetention => "wal_retention",
+            ErrorCategory::Freshness => "freshness",
+            ErrorCategory::Probe => "probe",
+            ErrorCategory::Spill => "spill",
+        }
+    }
+}
+
+/// One entry of the recent errors ring buffer.
+#[derive(Debug, Clone)]
+pub struct ErrorRecord {
+    pub category: ErrorCategory,
+    pub message: String,
+    /// Table involved, when the error concerns a single table
+    pub table: Option<String>,
+    /// WAL position involved, 0 when not applicable
+    pub lsn: u64,
+    /// Unix milliseconds
+    pub timestamp_ms: u64,
+}
+
+/// Whether a metric only grows (until reset) or moves both ways
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+/// One metric value, as listed by `ListMetrics`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSample {
+    /// Same name as on the HTTP `/metrics` endpoint
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub value: f64,
+    pub help: &'static str,
 }
 
 #[derive(Clone)]
@@ -39,6 +158,14 @@ pub struct CdcConfig {
     pub slot_name: String,
 }
 
+/// Milliseconds since the Unix epoch
+pub fn unix_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
 /// Maps relation_id → {(start_pk, end_pk) → hw_lsn} for snapshot deduplication.
 type FinishedChunksMap = HashMap<u32, BTreeMap<(i64, i64), u64>>;
 
@@ -50,8 +177,109 @@ pub struct TableProgress {
     pub rows_synced: u64,
 }
 
+/// Commit-to-sink latency of one table, checked against its freshness SLA.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableFreshness {
+    /// Latency of the oldest transaction in the last batch with changes to the table
+    pub latency_ms: u64,
+    /// `FRESHNESS_SLA` for the table, `None` when it has none
+    pub sla_ms: Option<u64>,
+    /// The last batch was delivered over the SLA
+    pub breached: bool,
+    /// Batches delivered over the SLA
+    pub breaches: u64,
+}
+
+/// A table paused because its sink table no longer matches it, or
+/// quarantined because writing its rows failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PausedTable {
+    /// Empty for a quarantined table
+    pub diff: SchemaDiff,
+    /// Rows of the table held back since it was paused
+    pub held_rows: u64,
+    /// Error writing its rows failed with, for a quarantined table
+    pub sink_error: Option<String>,
+}
+
+/// A table list asked for with `ReloadConfig`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableListRequest {
+    /// Tables to replicate, `schema.table`
+    pub tables: Vec<String>,
+    /// Drop the publication's tables that aren't listed; without it, the
+    /// list only adds tables (`drop_unlisted_tables`)
+    pub drop_unlisted: bool,
+}
+
+/// A table paused with `PauseTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManualPause {
+    pub mode: TablePauseMode,
+    /// Rows held back, with `TablePauseMode::Buffer`
+    pub held_rows: u64,
+    /// Rows dropped, with `TablePauseMode::Skip`
+    pub skipped_rows: u64,
+}
+
+/// Decoding counters of the replication slot from `pg_stat_replication_slots`
+/// (PostgreSQL 14+), cumulative since PostgreSQL last reset them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SlotStats {
+    /// Transactions spilled to disk by the walsender
+    pub spill_txns: u64,
+    /// Times transactions were spilled
+    pub spill_count: u64,
+    pub spill_bytes: u64,
+    /// In-progress transactions streamed (protocol version 2+)
+    pub stream_txns: u64,
+    pub stream_count: u64,
+    pub stream_bytes: u64,
+    /// Transactions and bytes decoded for the slot
+    pub total_txns: u64,
+    pub total_bytes: u64,
+}
+
+/// Rows of a source table written to the sink.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableStats {
+    pub rows_processed: u64,
+    /// WAL position of the last batch that had rows of the table
+    pub last_lsn: u64,
+}
+
+/// What `GetTableStatus` reports about a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStatus {
+    /// Source table, `schema.table`
+    pub table: String,
+    pub stats: TableStats,
+    /// Commit-to-sink latency of its last batch, `None` before the first one
+    pub lag_ms: Option<u64>,
+    pub pause: Option<ManualPause>,
+    /// Paused because its sink table doesn't match
+    pub schema_mismatch: bool,
+    /// WAL position of the first row dropped while it was skipped, when it
+    /// needs a re-sync
+    pub resync_from_lsn: Option<u64>,
+}
+
+impl TableStatus {
+    /// "running", "buffering", "skipping" or "schema_mismatch"
+    pub fn pause_state(&self) -> &'static str {
+        match self.pause.map(|p| p.mode) {
+            Some(TablePauseMode::Buffer) => "buffering",
+            Some(TablePauseMode::Skip) => "skipping",
+            None if self.schema_mismatch => "schema_mismatch",
+            None => "running",
+        }
+    }
+}
+
 pub struct SharedState {
     pub state: AtomicU8,
+    /// Follows `state`; the pipeline waits on it while paused
+    pub state_tx: watch::Sender<CdcState>,
     pub stage: RwLock<Stage>,
     pub stage_detail: RwLock<String>,
     pub setup_error: RwLock<Option<String>>, // Descriptive setup error
@@ -77,6 +305,11 @@ pub struct SharedState {
     pub snapshot_rows_synced: AtomicU64,
     // Signal channel for on-demand snapshot trigger (set by StartSnapshot RPC)
     pub snapshot_trigger: watch::Sender<bool>,
+    /// Table list asked for with `ReloadConfig`, applied to the publication
+    /// by the publication watcher
+    pub requested_tables: watch::Sender<Option<TableListRequest>>,
+    /// Changed by `ReloadConfig(restart_grpc)`; the gRPC server restarts
+    pub grpc_restart: watch::Sender<()>,
     // True while the snapshot worker is running
     pub snapshot_active: AtomicBool,
     // Snapshot error message (set on failure, cleared on new snapshot start)
@@ -91,14 +324,112 @@ pub struct SharedState {
     /// Relation PK column indices: relation_id -> list of column indices that form the PK
     /// Populated from Relation messages by the WAL handler
     pub relation_pk_cols: RwLock<HashMap<u32, Vec<usize>>>,
+    /// WAL range [from, to) skipped at startup by `START_POSITION=now`.
+    /// Both zero when nothing was skipped.
+    pub skipped_from_lsn: AtomicU64,
+    pub skipped_to_lsn: AtomicU64,
+    /// WAL retained by the slot, as last measured by the slot monitor
+    pub retained_wal_bytes: AtomicU64,
+    /// Slot `restart_lsn` and `confirmed_flush_lsn`, and WAL written past the
+    /// latter, as last measured by the slot monitor
+    pub slot_restart_lsn: AtomicU64,
+    pub slot_confirmed_flush_lsn: AtomicU64,
+    pub slot_flush_lag_bytes: AtomicU64,
+    /// `None` before PostgreSQL 14 or before the first measurement
+    pub slot_stats: Mutex<Option<SlotStats>>,
+    /// Bytes spilled to disk per second over the last slot check
+    pub slot_spill_bytes_per_second: AtomicU64,
+    /// True while retained WAL is above the retention guard threshold
+    pub wal_retention_breached: AtomicBool,
+    /// Set by the retention guard: batches go to the local archive, not the sink
+    pub archive_tee_active: AtomicBool,
+    /// PID of the backend holding the slot while startup waits for it (0 = none)
+    pub slot_holder_pid: AtomicU32,
+    /// Last `RECENT_ERRORS_CAPACITY` errors, oldest first
+    pub recent_errors: RwLock<VecDeque<ErrorRecord>>,
+    /// Errors recorded since startup, including those evicted from the buffer
+    pub total_errors: AtomicU64,
+    /// Rows parked in the dead-letter queue since startup
+    pub dead_letters: AtomicU64,
+    /// Size of the dead-letter files, as of the last retention sweep
+    pub dead_letter_bytes: AtomicU64,
+    /// Age of the oldest dead-letter file, as of the last retention sweep
+    pub dead_letter_age_secs: AtomicU64,
+    /// Dead-letter files deleted by the retention limits since startup or
+    /// the last reset
+    pub dead_letter_files_expired: AtomicU64,
+    /// `CircuitState` of the sink
+    pub sink_circuit: AtomicU8,
+    /// Batch writes retried after a sink failure
+    pub sink_retries: AtomicU64,
+    /// Rows the sinks reported as stored
+    pub sink_rows_written: AtomicU64,
+    /// Rows the sinks accepted but didn't store
+    pub sink_rows_filtered: AtomicU64,
+    /// Relation messages known to the pipeline, streamed to standby instances
+    pub relations: RwLock<Vec<CdcMessage>>,
+    /// Versions of their schemas since the checkpoint, stored with it
+    pub relation_versions: RwLock<Vec<RelationVersion>>,
+    /// Bumped every time `relations` changes
+    pub schema_version: AtomicU64,
+    /// Bumped on every confirmed LSN or schema change; standby streams wait on it
+    pub handover_tx: watch::Sender<u64>,
+    /// Commit-to-sink latency by source table (`schema.table`)
+    pub table_freshness: Mutex<HashMap<String, TableFreshness>>,
+    /// Tables paused on a sink schema mismatch, by source table (`schema.table`)
+    pub paused_tables: Mutex<HashMap<String, PausedTable>>,
+    /// Tables paused with `PauseTable`, by source table (`schema.table`)
+    pub manual_pauses: Mutex<HashMap<String, ManualPause>>,
+    /// Tables that had rows dropped while skipped, and the first such LSN
+    pub resync_needed: Mutex<HashMap<String, u64>>,
+    /// Rows written per source table (`schema.table`)
+    pub table_stats: Mutex<HashMap<String, TableStats>>,
+    /// `PeekBatch` requests to the running pipeline; `None` before it starts
+    pub peek_tx: Mutex<Option<mpsc::Sender<PeekRequest>>>,
+    /// `TailEvents` clients, fed with every written batch
+    pub tail: Arc<TailHub>,
+    /// `InvalidateSinkSchema` requests to the running pipeline
+    pub invalidate_tx: Mutex<Option<mpsc::Sender<InvalidateRequest>>>,
+    /// `PauseTable`/`ResumeTable` requests to the running pipeline
+    pub table_control_tx: Mutex<Option<mpsc::Sender<TableControlRequest>>>,
+    /// Configuration the engine started with, for `GetEffectiveConfig`
+    pub effective_config: Mutex<Option<Config>>,
+    /// Tables dropped from the publication (`schema.table`) and the WAL
+    /// position at which that was noticed, waiting for the pipeline
+    pub removed_tables: Mutex<Vec<(String, u64)>>,
+    /// True while `removed_tables` is not empty, checked once per event
+    pub removed_tables_pending: AtomicBool,
+    /// Unix milliseconds at which the probe row still on its way to the sink
+    /// was written (0 = none in flight)
+    pub probe_sent_ms: AtomicU64,
+    /// Write-to-sink latency of the last probe row delivered
+    pub probe_latency_ms: AtomicU64,
+    /// True while the probe latency is over `PROBE_ALERT_SECS`
+    pub probe_breached: AtomicBool,
+    /// Bytes of events spilled to disk and not yet handed to the pipeline
+    pub spill_bytes: AtomicU64,
+    /// Events spilled to disk since startup or the last reset
+    pub spilled_events: AtomicU64,
+    /// How long the oldest spilled event waited for the pipeline, at most
+    pub spill_age_secs: AtomicU64,
+    /// Updates dropped by `SUPPRESS_UNCHANGED` since startup or the last reset
+    pub suppressed_updates: AtomicU64,
+    /// Unchanged TOAST values filled in by `TOAST_HYDRATION` since startup or
+    /// the last reset
+    pub toast_values_hydrated: AtomicU64,
 }
 
 impl SharedState {
     pub fn new(config: CdcConfig) -> Arc<Self> {
         let (shutdown_tx, _) = watch::channel(false);
         let (snapshot_trigger, _) = watch::channel(false);
+        let (requested_tables, _) = watch::channel(None);
+        let (grpc_restart, _) = watch::channel(());
+        let (handover_tx, _) = watch::channel(0);
+        let (state_tx, _) = watch::channel(CdcState::Running);
         Arc::new(Self {
             state: AtomicU8::new(CdcState::Running as u8),
+            state_tx,
             stage: RwLock::new(Stage::Init),
             stage_detail: RwLock::new("Initializing".to_string()),
             setup_error: RwLock::new(None),
@@ -122,12 +453,59 @@ impl SharedState {
             snapshot_chunks_done: AtomicU64::new(0),
             snapshot_rows_synced: AtomicU64::new(0),
             snapshot_trigger,
+            requested_tables,
+            grpc_restart,
             snapshot_active: AtomicBool::new(false),
             snapshot_error: RwLock::new(None),
             snapshot_paused: AtomicBool::new(false),
             table_progress: RwLock::new(HashMap::new()),
             finished_chunks: RwLock::new(HashMap::new()),
             relation_pk_cols: RwLock::new(HashMap::new()),
+            skipped_from_lsn: AtomicU64::new(0),
+            skipped_to_lsn: AtomicU64::new(0),
+            retained_wal_bytes: AtomicU64::new(0),
+            slot_restart_lsn: AtomicU64::new(0),
+            slot_confirmed_flush_lsn: AtomicU64::new(0),
+            slot_flush_lag_bytes: AtomicU64::new(0),
+            slot_stats: Mutex::new(None),
+            slot_spill_bytes_per_second: AtomicU64::new(0),
+            wal_retention_breached: AtomicBool::new(false),
+            archive_tee_active: AtomicBool::new(false),
+            slot_holder_pid: AtomicU32::new(0),
+            recent_errors: RwLock::new(VecDeque::with_capacity(RECENT_ERRORS_CAPACITY)),
+            total_errors: AtomicU64::new(0),
+            dead_letters: AtomicU64::new(0),
+            dead_letter_bytes: AtomicU64::new(0),
+            dead_letter_age_secs: AtomicU64::new(0),
+            dead_letter_files_expired: AtomicU64::new(0),
+            sink_circuit: AtomicU8::new(CircuitState::Closed as u8),
+            sink_retries: AtomicU64::new(0),
+            sink_rows_written: AtomicU64::new(0),
+            sink_rows_filtered: AtomicU64::new(0),
+            relations: RwLock::new(Vec::new()),
+            relation_versions: RwLock::new(Vec::new()),
+            schema_version: AtomicU64::new(0),
+            handover_tx,
+            table_freshness: Mutex::new(HashMap::new()),
+            paused_tables: Mutex::new(HashMap::new()),
+            manual_pauses: Mutex::new(HashMap::new()),
+            resync_needed: Mutex::new(HashMap::new()),
+            table_stats: Mutex::new(HashMap::new()),
+            peek_tx: Mutex::new(None),
+            tail: Arc::new(TailHub::default()),
+            invalidate_tx: Mutex::new(None),
+            table_control_tx: Mutex::new(None),
+            effective_config: Mutex::new(None),
+            removed_tables: Mutex::new(Vec::new()),
+            removed_tables_pending: AtomicBool::new(false),
+            probe_sent_ms: AtomicU64::new(0),
+            probe_latency_ms: AtomicU64::new(0),
+            probe_breached: AtomicBool::new(false),
+            spill_bytes: AtomicU64::new(0),
+            spilled_events: AtomicU64::new(0),
+            spill_age_secs: AtomicU64::new(0),
+            suppressed_updates: AtomicU64::new(0),
+            toast_values_hydrated: AtomicU64::new(0),
         })
     }
 
@@ -145,6 +523,7 @@ impl SharedState {
 
     pub fn confirm_lsn(&self, lsn: u64) {
         self.confirmed_lsn.store(lsn, Ordering::Relaxed);
+        self.handover_tx.send_modify(|v| *v += 1);
     }
 
     pub fn increment_events(&self) {
@@ -160,6 +539,25 @@ impl SharedState {
         self.pending_events.store(count, Ordering::Relaxed);
     }
 
+    /// Reports the spill after `spilled` more events went to disk.
+    pub fn record_spill(&self, bytes: u64, spilled: u64) {
+        self.spill_bytes.store(bytes, Ordering::Relaxed);
+        self.spilled_events.fetch_add(spilled, Ordering::Relaxed);
+    }
+
+    pub fn record_spill_age(&self, age: std::time::Duration) {
+        self.spill_age_secs.store(age.as_secs(), Ordering::Relaxed);
+    }
+
+    pub fn record_suppressed_update(&self) {
+        self.suppressed_updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_toast_hydrated(&self, values: u64) {
+        self.toast_values_hydrated
+            .fetch_add(values, Ordering::Relaxed);
+    }
+
     pub fn current_lsn(&self) -> u64 {
         self.current_lsn.load(Ordering::Relaxed)
     }
@@ -168,6 +566,847 @@ impl SharedState {
         self.confirmed_lsn.load(Ordering::Relaxed)
     }
 
+    /// Records the WAL range skipped by starting from the current position.
+    pub fn set_skipped_range(&self, from_lsn: u64, to_lsn: u64) {
+        self.skipped_from_lsn.store(from_lsn, Ordering::Relaxed);
+        self.skipped_to_lsn.store(to_lsn, Ordering::Relaxed);
+    }
+
+    /// WAL range [from, to) that was never replicated, if any.
+    pub fn skipped_range(&self) -> Option<(u64, u64)> {
+        let from = self.skipped_from_lsn.load(Ordering::Relaxed);
+        let to = self.skipped_to_lsn.load(Ordering::Relaxed);
+        (to > from).then_some((from, to))
+    }
+
+    /// Records a retention guard measurement. Returns true when this call
+    /// changed the breached flag, so callers act once per transition.
+    pub fn set_wal_retention(&self, retained_bytes: u64, breached: bool) -> bool {
+        self.retained_wal_bytes
+            .store(retained_bytes, Ordering::Relaxed);
+        self.wal_retention_breached
+            .swap(breached, Ordering::Relaxed)
+            != breached
+    }
+
+    pub fn retained_wal_bytes(&self) -> u64 {
+        self.retained_wal_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Records where the slot stands, as read from `pg_replication_slots`.
+    pub fn set_slot_position(&self, restart_lsn: u64, confirmed_flush_lsn: u64, flush_lag: u64) {
+        self.slot_restart_lsn.store(restart_lsn, Ordering::Relaxed);
+        self.slot_confirmed_flush_lsn
+            .store(confirmed_flush_lsn, Ordering::Relaxed);
+        self.slot_flush_lag_bytes
+            .store(flush_lag, Ordering::Relaxed);
+    }
+
+    pub fn slot_restart_lsn(&self) -> u64 {
+        self.slot_restart_lsn.load(Ordering::Relaxed)
+    }
+
+    pub fn slot_confirmed_flush_lsn(&self) -> u64 {
+        self.slot_confirmed_flush_lsn.load(Ordering::Relaxed)
+    }
+
+    pub fn slot_flush_lag_bytes(&self) -> u64 {
+        self.slot_flush_lag_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn set_slot_stats(&self, stats: SlotStats, spill_bytes_per_second: u64) {
+        *self.slot_stats.lock() = Some(stats);
+        self.slot_spill_bytes_per_second
+            .store(spill_bytes_per_second, Ordering::Relaxed);
+    }
+
+    pub fn slot_stats(&self) -> Option<SlotStats> {
+        *self.slot_stats.lock()
+    }
+
+    pub fn is_wal_retention_breached(&self) -> bool {
+        self.wal_retention_breached.load(Ordering::Relaxed)
+    }
+
+    /// Switches the pipeline to archive-tee mode. There is no way back: the
+    /// sink has missed the archived batches, so this lasts until restart.
+    pub fn enable_archive_tee(&self) {
+        self.archive_tee_active.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_archive_tee_active(&self) -> bool {
+        self.archive_tee_active.load(Ordering::Relaxed)
+    }
+
+    /// Adds an error to the recent errors buffer, evicting the oldest when full.
+    pub async fn record_error(
+        &self,
+        category: ErrorCategory,
+        message: impl Into<String>,
+        table: Option<&str>,
+        lsn: u64,
+    ) {
+        let record = ErrorRecord {
+            category,
+            message: message.into(),
+            table: table.map(str::to_string),
+            lsn,
+            timestamp_ms: unix_ms(),
+        };
+        let mut errors = self.recent_errors.write().await;
+        if errors.len() == RECENT_ERRORS_CAPACITY {
+            errors.pop_front();
+        }
+        errors.push_back(record);
+        self.total_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Up to `limit` most recent errors, newest first.
+    pub async fn recent_errors(&self, limit: usize) -> Vec<ErrorRecord> {
+        let errors = self.recent_errors.read().await;
+        errors.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub fn total_errors(&self) -> u64 {
+        self.total_errors.load(Ordering::Relaxed)
+    }
+
+    /// Counts rows parked in the dead-letter queue.
+    pub fn add_dead_letters(&self, rows: u64) {
+        self.dead_letters.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    pub fn dead_letters(&self) -> u64 {
+        self.dead_letters.load(Ordering::Relaxed)
+    }
+
+    /// Reports the dead-letter files after a retention sweep that deleted
+    /// `expired` of them.
+    pub fn record_dead_letter_files(&self, bytes: u64, oldest_age_secs: u64, expired: u64) {
+        self.dead_letter_bytes.store(bytes, Ordering::Relaxed);
+        self.dead_letter_age_secs
+            .store(oldest_age_secs, Ordering::Relaxed);
+        self.dead_letter_files_expired
+            .fetch_add(expired, Ordering::Relaxed);
+    }
+
+    /// Records a new sink circuit state and returns the previous one.
+    pub fn set_sink_circuit(&self, state: CircuitState) -> CircuitState {
+        CircuitState::from_u8(self.sink_circuit.swap(state as u8, Ordering::Relaxed))
+    }
+
+    pub fn sink_circuit(&self) -> CircuitState {
+        CircuitState::from_u8(self.sink_circuit.load(Ordering::Relaxed))
+    }
+
+    pub fn increment_sink_retries(&self) {
+        self.sink_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn sink_retries(&self) -> u64 {
+        self.sink_retries.load(Ordering::Relaxed)
+    }
+
+    /// Counts the rows a sink reported for a written batch.
+    pub fn add_sink_rows(&self, written: u64, filtered: u64) {
+        self.sink_rows_written.fetch_add(written, Ordering::Relaxed);
+        self.sink_rows_filtered
+            .fetch_add(filtered, Ordering::Relaxed);
+    }
+
+    pub fn sink_rows_written(&self) -> u64 {
+        self.sink_rows_written.load(Ordering::Relaxed)
+    }
+
+    pub fn sink_rows_filtered(&self) -> u64 {
+        self.sink_rows_filtered.load(Ordering::Relaxed)
+    }
+
+    /// Records the commit-to-sink latency of a batch for `table`. Returns
+    /// true when the table went over or back under its SLA with this batch,
+    /// so callers alert once per transition.
+    pub fn record_table_freshness(
+        &self,
+        table: &str,
+        latency_ms: u64,
+        sla_ms: Option<u64>,
+    ) -> bool {
+        let mut tables = self.table_freshness.lock();
+        let entry = tables.entry(table.to_string()).or_default();
+        let breached = sla_ms.is_some_and(|sla| latency_ms > sla);
+        let changed = entry.breached != breached;
+        entry.latency_ms = latency_ms;
+        entry.sla_ms = sla_ms;
+        entry.breached = breached;
+        if breached {
+            entry.breaches += 1;
+        }
+        changed
+    }
+
+    /// Latest freshness of every table that received changes, by table.
+    pub fn table_freshness(&self) -> Vec<(String, TableFreshness)> {
+        let mut tables: Vec<_> = self
+            .table_freshness
+            .lock()
+            .iter()
+            .map(|(table, freshness)| (table.clone(), freshness.clone()))
+            .collect();
+        tables.sort_by(|a, b| a.0.cmp(&b.0));
+        tables
+    }
+
+    /// Drops the freshness of a table that is no longer replicated.
+    pub fn forget_table_freshness(&self, table: &str) {
+        self.table_freshness.lock().remove(table);
+    }
+
+    /// Marks `diff.table` as paused, or replaces the diff of a paused table.
+    pub fn pause_table(&self, diff: SchemaDiff) {
+        let mut tables = self.paused_tables.lock();
+        match tables.get_mut(&diff.table) {
+            Some(paused) => {
+                paused.diff = diff;
+                paused.sink_error = None;
+            }
+            None => {
+                tables.insert(
+                    diff.table.clone(),
+                    PausedTable {
+                        diff,
+                        held_rows: 0,
+                        sink_error: None,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Lists `table` with the paused tables, quarantined after writing its
+    /// rows failed with `error`.
+    pub fn quarantine_table(&self, table: &str, error: String) {
+        let mut tables = self.paused_tables.lock();
+        let paused = tables
+            .entry(table.to_string())
+            .or_insert_with(|| PausedTable {
+                diff: SchemaDiff {
+                    table: table.to_string(),
+                    sink_table: String::new(),
+                    table_missing: false,
+                    columns: Vec::new(),
+                },
+                held_rows: 0,
+                sink_error: None,
+            });
+        paused.sink_error = Some(error);
+    }
+
+    pub fn set_held_rows(&self, table: &str, held_rows: u64) {
+        if let Some(paused) = self.paused_tables.lock().get_mut(table) {
+            paused.held_rows = held_rows;
+        }
+        if let Some(pause) = self.manual_pauses.lock().get_mut(table) {
+            pause.held_rows = held_rows;
+        }
+    }
+
+    pub fn resume_table(&self, table: &str) {
+        self.paused_tables.lock().remove(table);
+    }
+
+    /// Tables paused on a schema mismatch or quarantined, by table.
+    pub fn paused_tables(&self) -> Vec<PausedTable> {
+        let mut tables: Vec<_> = self.paused_tables.lock().values().cloned().collect();
+        tables.sort_by(|a, b| a.diff.table.cmp(&b.diff.table));
+        tables
+    }
+
+    /// Records a `PauseTable` (`Some`) or `ResumeTable` (`None`) of `table`.
+    pub fn set_manual_pause(&self, table: &str, mode: Option<TablePauseMode>) {
+        let mut pauses = self.manual_pauses.lock();
+        match mode {
+            Some(mode) => {
+                pauses.insert(
+                    table.to_string(),
+                    ManualPause {
+                        mode,
+                        held_rows: 0,
+                        skipped_rows: 0,
+                    },
+                );
+            }
+            None => {
+                pauses.remove(table);
+            }
+        }
+    }
+
+    /// Counts a row of a skipped table dropped at `lsn`, and marks the table
+    /// as needing a re-sync from the first one.
+    pub fn record_skipped_row(&self, table: &str, lsn: u64) {
+        if let Some(pause) = self.manual_pauses.lock().get_mut(table) {
+            pause.skipped_rows += 1;
+        }
+        self.resync_needed
+            .lock()
+            .entry(table.to_string())
+            .or_insert(lsn);
+    }
+
+    /// Counts `rows` of `table` written in the batch confirmed at `lsn`.
+    pub fn record_table_rows(&self, table: &str, rows: u64, lsn: u64) {
+        let mut stats = self.table_stats.lock();
+        let stats = stats.entry(table.to_string()).or_default();
+        stats.rows_processed += rows;
+        stats.last_lsn = lsn;
+    }
+
+    /// Every table with rows written, a pause or a re-sync mark, or only
+    /// `table` (`schema.table`).
+    pub fn table_status(&self, table: Option<&str>) -> Vec<TableStatus> {
+        let stats = self.table_stats.lock().clone();
+        let pauses = self.manual_pauses.lock().clone();
+        let resync = self.resync_needed.lock().clone();
+        let mismatched: HashSet<String> = self.paused_tables.lock().keys().cloned().collect();
+        let freshness = self.table_freshness.lock().clone();
+
+        let names: BTreeSet<&String> = stats
+            .keys()
+            .chain(pauses.keys())
+            .chain(resync.keys())
+            .chain(mismatched.iter())
+            .filter(|name| table.map_or(true, |t| t == name.as_str()))
+            .collect();
+        names
+            .into_iter()
+            .map(|name| TableStatus {
+                table: name.clone(),
+                stats: stats.get(name).copied().unwrap_or_default(),
+                lag_ms: freshness.get(name).map(|f| f.latency_ms),
+                pause: pauses.get(name).copied(),
+                schema_mismatch: mismatched.contains(name),
+                resync_from_lsn: resync.get(name).copied(),
+            })
+            .collect()
+    }
+
+    /// Records the probe row written at `sent_ms` (Unix milliseconds), now
+    /// on its way to the sink.
+    pub fn probe_sent(&self, sent_ms: u64) {
+        self.probe_sent_ms.store(sent_ms, Ordering::Relaxed);
+    }
+
+    /// When the probe row still on its way to the sink was written
+    pub fn probe_in_flight(&self) -> Option<u64> {
+        match self.probe_sent_ms.load(Ordering::Relaxed) {
+            0 => None,
+            sent_ms => Some(sent_ms),
+        }
+    }
+
+    /// Records that the probe row written at `sent_ms` reached the sink. A
+    /// row written before a restart is measured but doesn't stand for the
+    /// one in flight.
+    pub fn record_probe_delivered(&self, sent_ms: u64) {
+        self.probe_latency_ms
+            .store(unix_ms().saturating_sub(sent_ms), Ordering::Relaxed);
+        let _ =
+            self.probe_sent_ms
+                .compare_exchange(sent_ms, 0, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// End-to-end latency measured by the probe: that of the last row
+    /// delivered, or the age of the one in flight once it is older.
+    pub fn probe_latency_ms(&self) -> u64 {
+        let in_flight = self
+            .probe_in_flight()
+            .map_or(0, |sent_ms| unix_ms().saturating_sub(sent_ms));
+        self.probe_latency_ms.load(Ordering::Relaxed).max(in_flight)
+    }
+
+    /// Flags the probe latency as over or back under its threshold. Returns
+    /// true on a transition, so callers alert once.
+    pub fn set_probe_breached(&self, breached: bool) -> bool {
+        self.probe_breached.swap(breached, Ordering::Relaxed) != breached
+    }
+
+    pub fn is_probe_breached(&self) -> bool {
+        self.probe_breached.load(Ordering::Relaxed)
+    }
+
+    /// Tells the pipeline to stop routing `tables` once it reaches `lsn`.
+    pub fn remove_tables(&self, tables: Vec<String>, lsn: u64) {
+        let mut removed = self.removed_tables.lock();
+        removed.extend(tables.into_iter().map(|table| (table, lsn)));
+        self.removed_tables_pending.store(true, Ordering::Release);
+    }
+
+    /// Tables removed from the publication since the last call.
+    pub fn take_removed_tables(&self) -> Vec<(String, u64)> {
+        if !self.removed_tables_pending.swap(false, Ordering::AcqRel) {
+            return Vec::new();
+        }
+        std::mem::take(&mut *self.removed_tables.lock())
+    }
+
+    /// Registers the channel the running pipeline answers `PeekBatch` on.
+    pub fn set_peek_sender(&self, tx: mpsc::Sender<PeekRequest>) {
+        *self.peek_tx.lock() = Some(tx);
+    }
+
+    pub fn peek_sender(&self) -> Option<mpsc::Sender<PeekRequest>> {
+        self.peek_tx.lock().clone()
+    }
+
+    /// Registers the channel the running pipeline answers
+    /// `InvalidateSinkSchema` on.
+    pub fn set_invalidate_sender(&self, tx: mpsc::Sender<InvalidateRequest>) {
+        *self.invalidate_tx.lock() = Some(tx);
+    }
+
+    pub fn invalidate_sender(&self) -> Option<mpsc::Sender<InvalidateRequest>> {
+        self.invalidate_tx.lock().clone()
+    }
+
+    /// Registers the channel the running pipeline answers `PauseTable` and
+    /// `ResumeTable` on.
+    pub fn set_table_control_sender(&self, tx: mpsc::Sender<TableControlRequest>) {
+        *self.table_control_tx.lock() = Some(tx);
+    }
+
+    pub fn table_control_sender(&self) -> Option<mpsc::Sender<TableControlRequest>> {
+        self.table_control_tx.lock().clone()
+    }
+
+    pub fn set_effective_config(&self, config: Config) {
+        *self.effective_config.lock() = Some(config);
+    }
+
+    /// The configuration in use, with what `ReloadConfig` changed since
+    /// startup applied. `None` before the engine was created.
+    pub async fn effective_config(&self) -> Option<Config> {
+        let mut config = self.effective_config.lock().clone()?;
+        let reloaded = self.config.read().await;
+        config.flush_size = reloaded.flush_size;
+        config.flush_interval_ms = reloaded.flush_interval_ms;
+        config.tables = reloaded.tables.clone();
+        Some(config)
+    }
+
+    /// Current value of every metric. Reading does not reset anything, unlike
+    /// `events_last_second()`.
+    pub fn metric_samples(&self) -> Vec<MetricSample> {
+        use MetricKind::{Counter, Gauge};
+        let pool_stats = pool::stats();
+        let freshness = self.table_freshness();
+        let limits = resources::limits();
+        let slot_stats = self.slot_stats();
+        let sample = |name, kind, value: u64, help| MetricSample {
+            name,
+            kind,
+            value: value as f64,
+            help,
+        };
+        let mut samples = vec![
+            sample(
+                "dbmazz_events_processed_total",
+                Counter,
+                self.events_processed(),
+                "Total CDC events processed.",
+            ),
+            sample(
+                "dbmazz_batches_sent_total",
+                Counter,
+                self.batches_sent(),
+                "Total batches sent to sink.",
+            ),
+            sample(
+                "dbmazz_errors_total",
+                Counter,
+                self.total_errors(),
+                "Errors recorded since startup or the last reset.",
+            ),
+            sample(
+                "dbmazz_dead_letters_total",
+                Counter,
+                self.dead_letters(),
+                "Rows the sink rejected, parked in the dead-letter queue.",
+            ),
+            sample(
+                "dbmazz_dead_letter_files_expired_total",
+                Counter,
+                self.dead_letter_files_expired.load(Ordering::Relaxed),
+                "Dead-letter files deleted by DEAD_LETTER_MAX_AGE_SECS or DEAD_LETTER_MAX_MB.",
+            ),
+            sample(
+                "dbmazz_sink_retries_total",
+                Counter,
+                self.sink_retries(),
+                "Batch writes retried after a sink failure.",
+            ),
+            sample(
+                "dbmazz_sink_rows_written_total",
+                Counter,
+                self.sink_rows_written(),
+                "Rows the sink reported as stored.",
+            ),
+            sample(
+                "dbmazz_sink_rows_filtered_total",
+                Counter,
+                self.sink_rows_filtered(),
+                "Rows the sink accepted but filtered out instead of storing.",
+            ),
+            sample(
+                "dbmazz_freshness_sla_breaches_total",
+                Counter,
+                freshness.iter().map(|(_, f)| f.breaches).sum(),
+                "Batches delivered over their table's freshness SLA.",
+            ),
+            sample(
+                "dbmazz_sink_timeouts_total",
+                Counter,
+                timeout::counts().iter().map(|(_, n)| n).sum(),
+                "Sink requests and batches that timed out.",
+            ),
+            sample(
+                "dbmazz_source_errors_total",
+                Counter,
+                pg_error::counts().iter().map(|(_, _, n)| n).sum(),
+                "PostgreSQL errors, classified by SQLSTATE.",
+            ),
+            sample(
+                "dbmazz_future_commit_timestamps_total",
+                Counter,
+                clock::future_commits(),
+                "Commit timestamps ahead of the clock by more than CLOCK_SKEW_WARN_MS.",
+            ),
+            sample(
+                "dbmazz_tail_events_dropped_total",
+                Counter,
+                self.tail.dropped_events(),
+                "Events dropped from the buffers of TailEvents clients that fell behind.",
+            ),
+            sample(
+                "dbmazz_tail_disconnects_total",
+                Counter,
+                self.tail.disconnects(),
+                "TailEvents clients disconnected for falling behind (TAIL_OVERFLOW=disconnect).",
+            ),
+            sample(
+                "dbmazz_spilled_events_total",
+                Counter,
+                self.spilled_events.load(Ordering::Relaxed),
+                "Events spilled to disk while the pipeline was behind (SPILL_DIR).",
+            ),
+            sample(
+                "dbmazz_suppressed_updates_total",
+                Counter,
+                self.suppressed_updates.load(Ordering::Relaxed),
+                "Updates dropped because only ignored columns changed (SUPPRESS_UNCHANGED).",
+            ),
+            sample(
+                "dbmazz_toast_values_hydrated_total",
+                Counter,
+                self.toast_values_hydrated.load(Ordering::Relaxed),
+                "Unchanged TOAST values filled in before the sink (TOAST_HYDRATION).",
+            ),
+            sample(
+                "dbmazz_buffer_pool_hits_total",
+                Counter,
+                pool_stats.hits,
+                "Batch/row buffers reused from the pool.",
+            ),
+            sample(
+                "dbmazz_buffer_pool_misses_total",
+                Counter,
+                pool_stats.misses,
+                "Batch/row buffers newly allocated.",
+            ),
+            sample(
+                "dbmazz_events_per_second",
+                Gauge,
+                self.events_last_second.load(Ordering::Relaxed),
+                "Current event throughput.",
+            ),
+            sample(
+                "dbmazz_replication_lag_ms",
+                Gauge,
+                self.replication_lag_ms(),
+                "Replication lag in milliseconds.",
+            ),
+            MetricSample {
+                name: "dbmazz_clock_skew_ms",
+                kind: Gauge,
+                value: clock::skew_ms() as f64,
+                help: "Local clock minus the source's, measured from keepalives.",
+            },
+            sample(
+                "dbmazz_tail_subscribers",
+                Gauge,
+                self.tail.subscribers() as u64,
+                "Connected TailEvents clients.",
+            ),
+            sample(
+                "dbmazz_pending_events",
+                Gauge,
+                self.pending_events(),
+                "Events buffered in pipeline.",
+            ),
+            sample(
+                "dbmazz_spill_bytes",
+                Gauge,
+                self.spill_bytes.load(Ordering::Relaxed),
+                "Bytes of events spilled to disk, waiting for the pipeline.",
+            ),
+            sample(
+                "dbmazz_spill_oldest_seconds",
+                Gauge,
+                self.spill_age_secs.load(Ordering::Relaxed),
+                "How long the oldest spilled event waited for the pipeline, at most.",
+            ),
+            sample(
+                "dbmazz_dead_letter_bytes",
+                Gauge,
+                self.dead_letter_bytes.load(Ordering::Relaxed),
+                "Size of the dead-letter files (DEAD_LETTER_TARGET=file with retention).",
+            ),
+            sample(
+                "dbmazz_dead_letter_oldest_seconds",
+                Gauge,
+                self.dead_letter_age_secs.load(Ordering::Relaxed),
+                "Age of the oldest dead-letter file (DEAD_LETTER_TARGET=file with retention).",
+            ),
+            sample(
+                "dbmazz_lag_bytes",
+                Gauge,
+                self.current_lsn().saturating_sub(self.confirmed_lsn()),
+                "WAL received but not yet confirmed.",
+            ),
+            sample(
+                "dbmazz_retained_wal_bytes",
+                Gauge,
+                self.retained_wal_bytes(),
+                "WAL retained by the slot (current position - restart_lsn).",
+            ),
+            sample(
+                "dbmazz_slot_flush_lag_bytes",
+                Gauge,
+                self.slot_flush_lag_bytes(),
+                "WAL written past the slot's confirmed_flush_lsn.",
+            ),
+            sample(
+                "dbmazz_slot_restart_lsn",
+                Gauge,
+                self.slot_restart_lsn(),
+                "Oldest WAL position the slot still needs.",
+            ),
+            sample(
+                "dbmazz_slot_confirmed_flush_lsn",
+                Gauge,
+                self.slot_confirmed_flush_lsn(),
+                "Slot position confirmed to PostgreSQL.",
+            ),
+            sample(
+                "dbmazz_wal_retention_breached",
+                Gauge,
+                self.is_wal_retention_breached() as u64,
+                "1 while retained WAL is over WAL_RETENTION_MAX_MB.",
+            ),
+            sample(
+                "dbmazz_memory_limit_bytes",
+                Gauge,
+                limits.memory_bytes.unwrap_or(0),
+                "Container memory limit (cgroup), 0 when unlimited.",
+            ),
+            sample(
+                "dbmazz_memory_usage_bytes",
+                Gauge,
+                resources::memory_usage().unwrap_or(0),
+                "Memory used by the container (cgroup), or the process RSS outside one.",
+            ),
+            sample(
+                "dbmazz_cpu_limit_millicores",
+                Gauge,
+                limits.cpu_millicores.unwrap_or(0),
+                "Container CPU limit (cgroup) in millicores, 0 when unlimited.",
+            ),
+            sample(
+                "dbmazz_memory_bytes",
+                Gauge,
+                self.estimate_memory(),
+                "Estimated memory held by buffered events.",
+            ),
+            sample(
+                "dbmazz_sink_circuit_state",
+                Gauge,
+                self.sink_circuit() as u64,
+                "Sink circuit breaker: 0 closed, 1 open, 2 half-open.",
+            ),
+            sample(
+                "dbmazz_probe_latency_ms",
+                Gauge,
+                self.probe_latency_ms(),
+                "Write-to-sink latency of the read-your-writes probe.",
+            ),
+            sample(
+                "dbmazz_probe_over_threshold",
+                Gauge,
+                self.is_probe_breached() as u64,
+                "1 while the probe latency is over PROBE_ALERT_SECS.",
+            ),
+            sample(
+                "dbmazz_tables_over_freshness_sla",
+                Gauge,
+                freshness.iter().filter(|(_, f)| f.breached).count() as u64,
+                "Tables whose last batch was delivered over their freshness SLA.",
+            ),
+            sample(
+                "dbmazz_paused_tables",
+                Gauge,
+                self.paused_tables.lock().len() as u64,
+                "Tables paused on a sink schema mismatch or quarantined after a sink error.",
+            ),
+        ];
+        // PostgreSQL's own counters, which ResetMetrics doesn't touch
+        if let Some(stats) = slot_stats {
+            samples.extend([
+                sample(
+                    "dbmazz_slot_spill_txns",
+                    Gauge,
+                    stats.spill_txns,
+                    "Transactions the walsender spilled to disk (pg_stat_replication_slots).",
+                ),
+                sample(
+                    "dbmazz_slot_spill_count",
+                    Gauge,
+                    stats.spill_count,
+                    "Times transactions were spilled to disk (pg_stat_replication_slots).",
+                ),
+                sample(
+                    "dbmazz_slot_spill_bytes",
+                    Gauge,
+                    stats.spill_bytes,
+                    "Bytes spilled to disk while decoding (pg_stat_replication_slots).",
+                ),
+                sample(
+                    "dbmazz_slot_spill_bytes_per_second",
+                    Gauge,
+                    self.slot_spill_bytes_per_second.load(Ordering::Relaxed),
+                    "Bytes spilled to disk per second over the last slot check.",
+                ),
+                sample(
+                    "dbmazz_slot_stream_txns",
+                    Gauge,
+                    stats.stream_txns,
+                    "In-progress transactions streamed (pg_stat_replication_slots).",
+                ),
+                sample(
+                    "dbmazz_slot_stream_count",
+                    Gauge,
+                    stats.stream_count,
+                    "Times in-progress transactions were streamed (pg_stat_replication_slots).",
+                ),
+                sample(
+                    "dbmazz_slot_stream_bytes",
+                    Gauge,
+                    stats.stream_bytes,
+                    "Bytes of in-progress transactions streamed (pg_stat_replication_slots).",
+                ),
+                sample(
+                    "dbmazz_slot_total_txns",
+                    Gauge,
+                    stats.total_txns,
+                    "Transactions decoded for the slot (pg_stat_replication_slots).",
+                ),
+                sample(
+                    "dbmazz_slot_total_bytes",
+                    Gauge,
+                    stats.total_bytes,
+                    "Bytes decoded for the slot (pg_stat_replication_slots).",
+                ),
+            ]);
+        }
+        samples
+    }
+
+    /// Zeroes every counter listed by `metric_samples` and returns their
+    /// names. The recent errors buffer is kept.
+    pub fn reset_counters(&self) -> Vec<&'static str> {
+        self.events_processed.store(0, Ordering::Relaxed);
+        self.batches_sent.store(0, Ordering::Relaxed);
+        self.total_errors.store(0, Ordering::Relaxed);
+        self.dead_letters.store(0, Ordering::Relaxed);
+        self.dead_letter_files_expired.store(0, Ordering::Relaxed);
+        self.sink_retries.store(0, Ordering::Relaxed);
+        self.sink_rows_written.store(0, Ordering::Relaxed);
+        self.sink_rows_filtered.store(0, Ordering::Relaxed);
+        self.spilled_events.store(0, Ordering::Relaxed);
+        self.suppressed_updates.store(0, Ordering::Relaxed);
+        self.toast_values_hydrated.store(0, Ordering::Relaxed);
+        for freshness in self.table_freshness.lock().values_mut() {
+            freshness.breaches = 0;
+        }
+        pool::reset_stats();
+        timeout::reset_counts();
+        pg_error::reset_counts();
+        clock::reset_counts();
+        self.tail.reset_counts();
+        self.metric_samples()
+            .into_iter()
+            .filter(|m| m.kind == MetricKind::Counter)
+            .map(|m| m.name)
+            .collect()
+    }
+
+    /// Records the backend holding the slot, or clears it once the slot is ours.
+    pub fn set_slot_holder_pid(&self, pid: Option<u32>) {
+        self.slot_holder_pid
+            .store(pid.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn slot_holder_pid(&self) -> Option<u32> {
+        match self.slot_holder_pid.load(Ordering::Relaxed) {
+            0 => None,
+            pid => Some(pid),
+        }
+    }
+
+    /// Replaces the Relation messages handed over to standby instances.
+    pub async fn publish_relations(&self, relations: Vec<CdcMessage>) {
+        *self.relations.write().await = relations;
+        self.schema_version.fetch_add(1, Ordering::Relaxed);
+        self.handover_tx.send_modify(|v| *v += 1);
+    }
+
+    /// Replaces the relation versions stored with the checkpoint; they take
+    /// effect with the next `publish_relations`.
+    pub async fn set_relation_versions(&self, versions: Vec<RelationVersion>) {
+        *self.relation_versions.write().await = versions;
+    }
+
+    /// Current schema version and the relation versions it stands for.
+    pub async fn relation_versions(&self) -> (u64, Vec<RelationVersion>) {
+        let versions = self.relation_versions.read().await;
+        (
+            self.schema_version.load(Ordering::Relaxed),
+            versions.clone(),
+        )
+    }
+
+    /// Current schema version and the Relation messages it stands for.
+    pub async fn relations(&self) -> (u64, Vec<CdcMessage>) {
+        let relations = self.relations.read().await;
+        (
+            self.schema_version.load(Ordering::Relaxed),
+            relations.clone(),
+        )
+    }
+
+    /// Subscribe to checkpoint and schema changes (for standby streaming).
+    pub fn subscribe_handover(&self) -> watch::Receiver<u64> {
+        self.handover_tx.subscribe()
+    }
+
     pub fn pending_events(&self) -> u64 {
         self.pending_events.load(Ordering::Relaxed)
     }
@@ -214,19 +1453,38 @@ impl SharedState {
         CdcState::from_u8(self.state.load(Ordering::Acquire))
     }
 
+    // Transitions are made under the watch channel's lock, so subscribers
+    // see them in the order they were made
     pub fn set_state(&self, state: CdcState) {
-        self.state.store(state as u8, Ordering::Release);
+        self.state_tx.send_modify(|current| {
+            self.state.store(state as u8, Ordering::Release);
+            *current = state;
+        });
     }
 
     pub fn compare_and_set_state(&self, expected: CdcState, new: CdcState) -> bool {
-        self.state
-            .compare_exchange(
-                expected as u8,
-                new as u8,
-                Ordering::AcqRel,
-                Ordering::Acquire,
-            )
-            .is_ok()
+        let mut swapped = false;
+        self.state_tx.send_if_modified(|current| {
+            swapped = self
+                .state
+                .compare_exchange(
+                    expected as u8,
+                    new as u8,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_ok();
+            if swapped {
+                *current = new;
+            }
+            swapped
+        });
+        swapped
+    }
+
+    /// Subscribe to CDC state transitions (`Pause`, `Resume`, draining).
+    pub fn subscribe_state(&self) -> watch::Receiver<CdcState> {
+        self.state_tx.subscribe()
     }
 
     pub fn set_replication_lag_ms(&self, lag: u64) {
@@ -286,6 +1544,31 @@ impl SharedState {
         self.snapshot_trigger.subscribe()
     }
 
+    pub fn subscribe_requested_tables(&self) -> watch::Receiver<Option<TableListRequest>> {
+        self.requested_tables.subscribe()
+    }
+
+    /// Asks the publication watcher to add the tables of `request` to the
+    /// publication, and to drop the others if it says so. False when no
+    /// watcher runs (`PUBLICATION_CHECK_INTERVAL_SECS=0`).
+    pub fn request_tables(&self, request: TableListRequest) -> bool {
+        if self.requested_tables.receiver_count() == 0 {
+            return false;
+        }
+        self.requested_tables.send_replace(Some(request));
+        true
+    }
+
+    /// Asks the gRPC server to rebind and re-read its TLS files once the
+    /// calls in flight are answered. False when no server runs.
+    pub fn request_grpc_restart(&self) -> bool {
+        if self.grpc_restart.receiver_count() == 0 {
+            return false;
+        }
+        self.grpc_restart.send_replace(());
+        true
+    }
+
     /// Signal that a snapshot should start (called by StartSnapshot gRPC handler).
     pub fn trigger_snapshot(&self) {
         let _ = self.snapshot_trigger.send(true);
@@ -375,6 +1658,166 @@ mod tests {
         })
     }
 
+    #[tokio::test]
+    async fn reset_counters_keeps_gauges() {
+        let state = make_state();
+        state.increment_events();
+        state.increment_batches();
+        state.set_pending(7);
+        state.add_dead_letters(3);
+        state.increment_sink_retries();
+        state.add_sink_rows(10, 2);
+        state.set_sink_circuit(CircuitState::Open);
+        state
+            .record_error(ErrorCategory::Sink, "boom", None, 0)
+            .await;
+
+        let reset = state.reset_counters();
+        assert!(reset.contains(&"dbmazz_events_processed_total"));
+        assert!(!reset.contains(&"dbmazz_pending_events"));
+
+        let value = |name| {
+            state
+                .metric_samples()
+                .into_iter()
+                .find(|m| m.name == name)
+                .unwrap()
+                .value
+        };
+        assert_eq!(value("dbmazz_events_processed_total"), 0.0);
+        assert_eq!(value("dbmazz_batches_sent_total"), 0.0);
+        assert_eq!(value("dbmazz_errors_total"), 0.0);
+        assert_eq!(value("dbmazz_dead_letters_total"), 0.0);
+        assert_eq!(value("dbmazz_sink_retries_total"), 0.0);
+        assert_eq!(value("dbmazz_sink_rows_written_total"), 0.0);
+        assert_eq!(value("dbmazz_sink_rows_filtered_total"), 0.0);
+        assert_eq!(value("dbmazz_pending_events"), 7.0);
+        assert_eq!(value("dbmazz_sink_circuit_state"), 1.0);
+        // Reset only touches counters, not the kept errors
+        assert_eq!(state.recent_errors(10).await.len(), 1);
+    }
+
+    #[test]
+    fn state_transitions_are_signalled() {
+        let state = make_state();
+        let mut rx = state.subscribe_state();
+        // A refused transition changes nothing
+        assert!(!state.compare_and_set_state(CdcState::Paused, CdcState::Running));
+        assert!(!rx.has_changed().unwrap());
+
+        assert!(state.compare_and_set_state(CdcState::Running, CdcState::Paused));
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), CdcState::Paused);
+        state.set_state(CdcState::Draining);
+        assert_eq!(*rx.borrow_and_update(), state.state());
+    }
+
+    #[test]
+    fn skipped_range_only_reported_when_non_empty() {
+        let state = make_state();
+        assert_eq!(state.skipped_range(), None);
+
+        state.set_skipped_range(0x100, 0x100);
+        assert_eq!(state.skipped_range(), None);
+
+        state.set_skipped_range(0x100, 0x2000);
+        assert_eq!(state.skipped_range(), Some((0x100, 0x2000)));
+    }
+
+    #[test]
+    fn wal_retention_reports_transitions_once() {
+        let state = make_state();
+        assert!(!state.set_wal_retention(100, false));
+        assert!(state.set_wal_retention(5_000, true));
+        assert!(!state.set_wal_retention(6_000, true));
+        assert_eq!(state.retained_wal_bytes(), 6_000);
+        assert!(state.is_wal_retention_breached());
+        assert!(state.set_wal_retention(10, false));
+        assert!(!state.is_wal_retention_breached());
+
+        state.set_slot_position(0x1000, 0x1800, 42);
+        assert_eq!(state.slot_restart_lsn(), 0x1000);
+        assert_eq!(state.slot_confirmed_flush_lsn(), 0x1800);
+        let value = |name| {
+            state
+                .metric_samples()
+                .into_iter()
+                .find(|m| m.name == name)
+                .unwrap()
+                .value
+        };
+        assert_eq!(value("dbmazz_slot_flush_lag_bytes"), 42.0);
+        assert_eq!(value("dbmazz_slot_restart_lsn"), 4096.0);
+
+        // Slot statistics are only listed once measured
+        let listed = |name| state.metric_samples().iter().any(|m| m.name == name);
+        assert!(!listed("dbmazz_slot_spill_bytes"));
+        let stats = SlotStats {
+            spill_bytes: 1 << 20,
+            ..Default::default()
+        };
+        state.set_slot_stats(stats, 2048);
+        assert_eq!(value("dbmazz_slot_spill_bytes"), 1048576.0);
+        assert_eq!(value("dbmazz_slot_spill_bytes_per_second"), 2048.0);
+        assert!(!state.reset_counters().contains(&"dbmazz_slot_spill_bytes"));
+    }
+
+    #[tokio::test]
+    async fn recent_errors_keep_newest_entries() {
+        let state = make_state();
+        for i in 0..RECENT_ERRORS_CAPACITY + 5 {
+            state
+                .record_error(ErrorCategory::Sink, format!("error {}", i), None, i as u64)
+                .await;
+        }
+        state
+            .record_error(
+                ErrorCategory::Schema,
+                "ALTER failed",
+                Some("public.users"),
+                0,
+            )
+            .await;
+
+        assert_eq!(state.total_errors(), RECENT_ERRORS_CAPACITY as u64 + 6);
+        assert_eq!(
+            state.recent_errors.read().await.len(),
+            RECENT_ERRORS_CAPACITY
+        );
+
+        let latest = state.recent_errors(2).await;
+        assert_eq!(latest[0].category, ErrorCategory::Schema);
+        assert_eq!(latest[0].table.as_deref(), Some("public.users"));
+        assert_eq!(
+            latest[1].message,
+            format!("error {}", RECENT_ERRORS_CAPACITY + 4)
+        );
+    }
+
+    #[tokio::test]
+    async fn handover_notified_on_checkpoint_and_schema_change() {
+        let state = make_state();
+        let mut rx = state.subscribe_handover();
+        assert!(!rx.has_changed().unwrap());
+
+        state.confirm_lsn(0x1000);
+        assert!(rx.has_changed().unwrap());
+        rx.borrow_and_update();
+
+        let relation = CdcMessage::Relation {
+            id: 1,
+            namespace: "public".into(),
+            name: "users".into(),
+            replica_identity: b'd',
+            columns: vec![],
+        };
+        state.publish_relations(vec![relation]).await;
+        assert!(rx.has_changed().unwrap());
+        let (version, relations) = state.relations().await;
+        assert_eq!(version, 1);
+        assert_eq!(relations.len(), 1);
+    }
+
     // ── Fast-path: no snapshot active ──────────────────────────────
 
     #[tokio::test]
@@ -540,4 +1983,116 @@ mod tests {
         // Relation 2, PK=25, lsn=200 → suppress (200 <= 300)
         assert!(!state.should_emit(2, 200, Some(25)).await);
     }
+
+    #[test]
+    fn probe_latency_covers_the_row_in_flight() {
+        let state = make_state();
+        assert_eq!(state.probe_latency_ms(), 0);
+
+        let sent_ms = unix_ms() - 5_000;
+        state.probe_sent(sent_ms);
+        assert!(state.probe_latency_ms() >= 5_000);
+
+        // A row written before a restart is measured, the one in flight stays
+        state.record_probe_delivered(sent_ms - 1_000);
+        assert_eq!(state.probe_in_flight(), Some(sent_ms));
+        state.record_probe_delivered(sent_ms);
+        assert_eq!(state.probe_in_flight(), None);
+        assert!(state.probe_latency_ms() >= 5_000);
+
+        assert!(state.set_probe_breached(true));
+        assert!(!state.set_probe_breached(true));
+        assert!(state.set_probe_breached(false));
+    }
+
+    #[test]
+    fn freshness_alerts_once_per_transition() {
+        let state = make_state();
+        assert!(!state.record_table_freshness("public.orders", 500, Some(1_000)));
+        assert!(state.record_table_freshness("public.orders", 1_500, Some(1_000)));
+        assert!(!state.record_table_freshness("public.orders", 2_000, Some(1_000)));
+        // Without an SLA a table is tracked but never breached
+        assert!(!state.record_table_freshness("public.users", 90_000, None));
+
+        let tables = state.table_freshness();
+        assert_eq!(tables[0].0, "public.orders");
+        assert_eq!(
+            tables[0].1,
+            TableFreshness {
+                latency_ms: 2_000,
+                sla_ms: Some(1_000),
+                breached: true,
+                breaches: 2,
+            }
+        );
+        assert!(!tables[1].1.breached);
+
+        assert!(state.record_table_freshness("public.orders", 100, Some(1_000)));
+        state.reset_counters();
+        assert_eq!(state.table_freshness()[0].1.breaches, 0);
+    }
+
+    #[test]
+    fn paused_tables_keep_their_held_rows() {
+        let state = make_state();
+        let diff = |table: &str| SchemaDiff::compare(table, "orders", &[], None);
+        state.pause_table(diff("public.orders"));
+        state.set_held_rows("public.orders", 12);
+        // A new diff of a paused table keeps what it holds
+        state.pause_table(diff("public.orders"));
+        state.pause_table(diff("public.items"));
+        state.set_held_rows("public.users", 3);
+
+        let paused = state.paused_tables();
+        assert_eq!(paused.len(), 2);
+        assert_eq!(paused[0].diff.table, "public.items");
+        assert_eq!(paused[1].held_rows, 12);
+
+        state.resume_table("public.orders");
+        assert_eq!(state.paused_tables().len(), 1);
+    }
+
+    #[test]
+    fn table_status_reports_pauses_and_resyncs() {
+        let state = make_state();
+        state.record_table_rows("public.orders", 10, 0x100);
+        state.record_table_rows("public.orders", 5, 0x200);
+        state.set_manual_pause("public.users", Some(TablePauseMode::Skip));
+        state.record_skipped_row("public.users", 0x300);
+        state.record_skipped_row("public.users", 0x400);
+        state.set_manual_pause("public.items", Some(TablePauseMode::Buffer));
+        state.set_held_rows("public.items", 7);
+
+        let tables = state.table_status(None);
+        let names: Vec<&str> = tables.iter().map(|t| t.table.as_str()).collect();
+        assert_eq!(names, vec!["public.items", "public.orders", "public.users"]);
+        assert_eq!(tables[0].pause_state(), "buffering");
+        assert_eq!(tables[0].pause.unwrap().held_rows, 7);
+        assert_eq!(tables[1].pause_state(), "running");
+        assert_eq!(tables[1].stats.rows_processed, 15);
+        assert_eq!(tables[1].stats.last_lsn, 0x200);
+        assert_eq!(tables[2].pause.unwrap().skipped_rows, 2);
+        assert_eq!(tables[2].resync_from_lsn, Some(0x300));
+
+        // The re-sync mark outlives the pause
+        state.set_manual_pause("public.users", None);
+        let users = state.table_status(Some("public.users"));
+        assert_eq!(users[0].pause_state(), "running");
+        assert_eq!(users[0].resync_from_lsn, Some(0x300));
+    }
+
+    #[test]
+    fn requested_tables_need_a_watcher() {
+        let state = make_state();
+        let request = |table: &str| TableListRequest {
+            tables: vec![table.to_string()],
+            drop_unlisted: false,
+        };
+        assert!(!state.request_tables(request("public.orders")));
+
+        let mut watcher = state.subscribe_requested_tables();
+        assert!(state.request_tables(request("public.users")));
+        assert!(watcher.has_changed().unwrap());
+        assert_eq!(*watcher.borrow_and_update(), Some(request("public.users")));
+    }
 }
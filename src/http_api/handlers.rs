@@ -10,15 +10,21 @@ use axum::{
 };
 use serde::Deserialize;
 use serde_json::json;
-use tracing::{error, info, warn};
+use tracing::{error, info};
 
 use super::{HttpAppState, SinkSetupConfig, SourceSetupConfig};
 use crate::config::{
-    Config, PostgresSourceConfig, SinkConfig, SinkType, SourceConfig, SourceType,
-    StarRocksSinkConfig,
+    Config, GrpcListen, PausedTableProbeConfig, PgSessionConfig, PostgresSourceConfig, Publication,
+    SinkConfig, SinkType, SnapshotMethod, SourceConfig, SourceType, StarRocksSinkConfig,
+    StartPosition, DEFAULT_SINK_BATCH_TIMEOUT, DEFAULT_SINK_REQUEST_TIMEOUT,
 };
+use crate::core::{pool, timeout};
 use crate::engine::CdcEngine;
-use crate::grpc::state::{CdcState, Stage};
+use crate::grpc::state::{CdcState, Stage, TableFreshness};
+use crate::resources;
+use crate::source::clock;
+use crate::source::pg_error::{self, PgErrorAction};
+use crate::source::session::PgSession;
 
 // =============================================================================
 // Request types
@@ -64,6 +70,7 @@ pub async fn healthz(State(state): State<Arc<HttpAppState>>) -> impl IntoRespons
             Stage::Setup => "setup",
             Stage::Cdc => "cdc",
             Stage::Snapshot => "snapshot",
+            Stage::Standby => "standby",
         }
     } else {
         "waiting_for_config"
@@ -89,15 +96,11 @@ pub async fn status(State(state): State<Arc<HttpAppState>>) -> impl IntoResponse
             Stage::Setup => "setup",
             Stage::Cdc => "cdc",
             Stage::Snapshot => "snapshot",
+            Stage::Standby => "standby",
         };
-        let cdc_state = s.state();
-        let state_str = match cdc_state {
-            CdcState::Running => "running",
-            CdcState::Paused => "paused",
-            CdcState::Draining => "draining",
-            CdcState::Stopped => "stopped",
-        };
+        let state_str = s.state().as_str();
         let eps = s.events_last_second.load(Ordering::Relaxed);
+        let limits = resources::limits();
 
         Json(json!({
             "engine_running": true,
@@ -108,11 +111,28 @@ pub async fn status(State(state): State<Arc<HttpAppState>>) -> impl IntoResponse
             "events_processed": s.events_processed(),
             "events_per_second": eps,
             "replication_lag_ms": s.replication_lag_ms(),
+            "clock_skew_ms": clock::skew_ms(),
+            "future_commit_timestamps": clock::future_commits(),
             "batches_sent": s.batches_sent(),
             "pending_events": s.pending_events(),
             "current_lsn": format!("0x{:X}", s.current_lsn()),
             "confirmed_lsn": format!("0x{:X}", s.confirmed_lsn()),
+            "slot_restart_lsn": format!("0x{:X}", s.slot_restart_lsn()),
+            "slot_confirmed_flush_lsn": format!("0x{:X}", s.slot_confirmed_flush_lsn()),
+            "retained_wal_bytes": s.retained_wal_bytes(),
+            "slot_flush_lag_bytes": s.slot_flush_lag_bytes(),
+            "wal_retention_breached": s.is_wal_retention_breached(),
             "estimated_memory_bytes": s.estimate_memory(),
+            "cpu_limit_millicores": limits.cpu_millicores,
+            "memory_limit_bytes": limits.memory_bytes,
+            "memory_usage_bytes": resources::memory_usage(),
+            "sink_circuit": s.sink_circuit().as_str(),
+            "paused_tables": s.paused_tables().iter().map(|p| json!({
+                "table": p.diff.table,
+                "diff": p.sink_error.is_none().then(|| p.diff.to_string()),
+                "sink_error": p.sink_error,
+                "held_rows": p.held_rows,
+            })).collect::<Vec<_>>(),
         }))
     } else {
         Json(json!({
@@ -128,7 +148,8 @@ pub async fn prometheus(State(state): State<Arc<HttpAppState>>) -> impl IntoResp
     let engine = state.engine_state.read().await;
     let body = if let Some(ref s) = *engine {
         let eps = s.events_last_second.load(Ordering::Relaxed);
-        format!(
+        let pool_stats = pool::stats();
+        let mut body = format!(
             "# HELP dbmazz_events_processed_total Total CDC events processed.\n\
              # TYPE dbmazz_events_processed_total counter\n\
              dbmazz_events_processed_total {}\n\
@@ -143,13 +164,57 @@ pub async fn prometheus(State(state): State<Arc<HttpAppState>>) -> impl IntoResp
              dbmazz_batches_sent_total {}\n\
              # HELP dbmazz_pending_events Events buffered in pipeline.\n\
              # TYPE dbmazz_pending_events gauge\n\
-             dbmazz_pending_events {}\n",
+             dbmazz_pending_events {}\n\
+             # HELP dbmazz_buffer_pool_hits_total Batch/row buffers reused from the pool.\n\
+             # TYPE dbmazz_buffer_pool_hits_total counter\n\
+             dbmazz_buffer_pool_hits_total {}\n\
+             # HELP dbmazz_buffer_pool_misses_total Batch/row buffers newly allocated.\n\
+             # TYPE dbmazz_buffer_pool_misses_total counter\n\
+             dbmazz_buffer_pool_misses_total {}\n\
+             # HELP dbmazz_dead_letters_total Rows the sink rejected, parked in the dead-letter queue.\n\
+             # TYPE dbmazz_dead_letters_total counter\n\
+             dbmazz_dead_letters_total {}\n\
+             # HELP dbmazz_sink_retries_total Batch writes retried after a sink failure.\n\
+             # TYPE dbmazz_sink_retries_total counter\n\
+             dbmazz_sink_retries_total {}\n\
+             # HELP dbmazz_sink_rows_written_total Rows the sink reported as stored.\n\
+             # TYPE dbmazz_sink_rows_written_total counter\n\
+             dbmazz_sink_rows_written_total {}\n\
+             # HELP dbmazz_sink_rows_filtered_total Rows the sink accepted but filtered out instead of storing.\n\
+             # TYPE dbmazz_sink_rows_filtered_total counter\n\
+             dbmazz_sink_rows_filtered_total {}\n\
+             # HELP dbmazz_sink_circuit_state Sink circuit breaker: 0 closed, 1 open, 2 half-open.\n\
+             # TYPE dbmazz_sink_circuit_state gauge\n\
+             dbmazz_sink_circuit_state {}\n\
+             # HELP dbmazz_probe_latency_ms Write-to-sink latency of the read-your-writes probe.\n\
+             # TYPE dbmazz_probe_latency_ms gauge\n\
+             dbmazz_probe_latency_ms {}\n\
+             # HELP dbmazz_probe_over_threshold 1 while the probe latency is over PROBE_ALERT_SECS.\n\
+             # TYPE dbmazz_probe_over_threshold gauge\n\
+             dbmazz_probe_over_threshold {}\n\
+             # HELP dbmazz_paused_tables Tables paused on a sink schema mismatch or quarantined after a sink error.\n\
+             # TYPE dbmazz_paused_tables gauge\n\
+             dbmazz_paused_tables {}\n",
             s.events_processed(),
             eps,
             s.replication_lag_ms(),
             s.batches_sent(),
             s.pending_events(),
-        )
+            pool_stats.hits,
+            pool_stats.misses,
+            s.dead_letters(),
+            s.sink_retries(),
+            s.sink_rows_written(),
+            s.sink_rows_filtered(),
+            s.sink_circuit() as u8,
+            s.probe_latency_ms(),
+            s.is_probe_breached() as u8,
+            s.paused_tables().len(),
+        );
+        body.push_str(&sink_timeout_metrics(&timeout::counts()));
+        body.push_str(&source_error_metrics(&pg_error::counts()));
+        body.push_str(&table_freshness_metrics(&s.table_freshness()));
+        body
     } else {
         "# dbmazz engine not running\n".to_string()
     };
@@ -164,6 +229,75 @@ pub async fn prometheus(State(state): State<Arc<HttpAppState>>) -> impl IntoResp
     )
 }
 
+/// Timeouts per sink, labelled with the sink name.
+fn sink_timeout_metrics(counts: &[(&str, u64)]) -> String {
+    let mut body = String::from(
+        "# HELP dbmazz_sink_timeouts_total Sink requests and batches that timed out.\n\
+         # TYPE dbmazz_sink_timeouts_total counter\n",
+    );
+    for (sink, n) in counts {
+        body.push_str(&format!(
+            "dbmazz_sink_timeouts_total{{sink=\"{}\"}} {}\n",
+            sink, n
+        ));
+    }
+    body
+}
+
+/// PostgreSQL errors per SQLSTATE, labelled with the code and what was done about it.
+fn source_error_metrics(counts: &[(String, PgErrorAction, u64)]) -> String {
+    let mut body = String::from(
+        "# HELP dbmazz_source_errors_total PostgreSQL errors, classified by SQLSTATE.\n\
+         # TYPE dbmazz_source_errors_total counter\n",
+    );
+    for (code, action, n) in counts {
+        body.push_str(&format!(
+            "dbmazz_source_errors_total{{code=\"{}\",action=\"{}\"}} {}\n",
+            code,
+            action.as_str(),
+            n
+        ));
+    }
+    body
+}
+
+/// Per-table freshness series, labelled with the source table.
+fn table_freshness_metrics(tables: &[(String, TableFreshness)]) -> String {
+    if tables.is_empty() {
+        return String::new();
+    }
+    let mut latency = String::from(
+        "# HELP dbmazz_table_freshness_ms Commit-to-sink latency of the table's last batch.\n\
+         # TYPE dbmazz_table_freshness_ms gauge\n",
+    );
+    let mut sla = String::from(
+        "# HELP dbmazz_table_freshness_sla_ms Freshness SLA of the table.\n\
+         # TYPE dbmazz_table_freshness_sla_ms gauge\n",
+    );
+    let mut breaches = String::from(
+        "# HELP dbmazz_table_freshness_sla_breaches_total Batches delivered over the table's SLA.\n\
+         # TYPE dbmazz_table_freshness_sla_breaches_total counter\n",
+    );
+    for (table, f) in tables {
+        let label = table.replace('\\', "\\\\").replace('"', "\\\"");
+        latency.push_str(&format!(
+            "dbmazz_table_freshness_ms{{table=\"{}\"}} {}\n",
+            label, f.latency_ms
+        ));
+        if let Some(sla_ms) = f.sla_ms {
+            sla.push_str(&format!(
+                "dbmazz_table_freshness_sla_ms{{table=\"{}\"}} {}\n",
+                label, sla_ms
+            ));
+            breaches.push_str(&format!(
+                "dbmazz_table_freshness_sla_breaches_total{{table=\"{}\"}} {}\n",
+                label, f.breaches
+            ));
+        }
+    }
+    latency + &sla + &breaches
+}
+
 pub async fn pause(State(state): State<Arc<HttpAppState>>) -> impl IntoResponse {
     let engine = state.engine_state.read().await;
     match engine.as_ref() {
@@ -246,10 +380,9 @@ async fn test_postgres(
         req.user, req.password, req.host, port, req.database
     );
 
-    let connect_fut = tokio_postgres::connect(&url, tokio_postgres::NoTls);
-    let (client, connection) = match tokio::time::timeout(Duration::from_secs(5), connect_fut).await
-    {
-        Ok(Ok(conn)) => conn,
+    let connect_fut = state.session.try_connect_helper(&url, "PG test");
+    let client = match tokio::time::timeout(Duration::from_secs(5), connect_fut).await {
+        Ok(Ok(client)) => client,
         Ok(Err(e)) => {
             return (
                 StatusCode::OK,
@@ -266,12 +399,6 @@ async fn test_postgres(
         }
     };
 
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            warn!("PG connection error: {}", e);
-        }
-    });
-
     let version = match client.query_one("SELECT version()", &[]).await {
         Ok(row) => row.get::<_, String>(0),
         Err(e) => {
@@ -399,10 +526,9 @@ pub async fn discover_tables(State(state): State<Arc<HttpAppState>>) -> impl Int
         src.user, src.password, src.host, src.port, src.database
     );
 
-    let connect_fut = tokio_postgres::connect(&url, tokio_postgres::NoTls);
-    let (client, connection) = match tokio::time::timeout(Duration::from_secs(5), connect_fut).await
-    {
-        Ok(Ok(c)) => c,
+    let connect_fut = state.session.try_connect_helper(&url, "PG discover");
+    let client = match tokio::time::timeout(Duration::from_secs(5), connect_fut).await {
+        Ok(Ok(client)) => client,
         Ok(Err(e)) => {
             return (
                 StatusCode::OK,
@@ -419,12 +545,6 @@ pub async fn discover_tables(State(state): State<Arc<HttpAppState>>) -> impl Int
         }
     };
 
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            warn!("PG discover connection error: {}", e);
-        }
-    });
-
     let query = r#"
         SELECT t.table_name,
                (SELECT count(*) FROM information_schema.columns c
@@ -522,7 +642,7 @@ pub async fn start_replication(
     };
 
     // Auto-create StarRocks database and tables from PG schema
-    if let Err(e) = ensure_starrocks_tables(&src, &sink, &req.tables).await {
+    if let Err(e) = ensure_starrocks_tables(&state.session, &src, &sink, &req.tables).await {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(
@@ -565,6 +685,7 @@ pub async fn start_replication(
         postgres: Some(PostgresSourceConfig {
             slot_name: slot_name.clone(),
             publication_name: publication_name.clone(),
+            session: PgSessionConfig::for_slot(&slot_name),
         }),
     };
 
@@ -575,12 +696,31 @@ pub async fn start_replication(
         database: sink.database.clone(),
         user: sink.user.clone(),
         password: sink.password.clone(),
-        starrocks: Some(StarRocksSinkConfig {}),
+        starrocks: Some(StarRocksSinkConfig::default()),
+        iceberg: None,
+        delta: None,
+        object_store: None,
+        shared: None,
+        naming: Default::default(),
+        checkpoint_slot: None,
+        null_encoding: Default::default(),
+        bool_encoding: Default::default(),
+        float_format: Default::default(),
+        geometry_format: Default::default(),
+        string_overflow: Default::default(),
+        schema_evolution: Default::default(),
+        request_timeout: DEFAULT_SINK_REQUEST_TIMEOUT,
+        batch_timeout: Some(DEFAULT_SINK_BATCH_TIMEOUT),
+        http: Default::default(),
+        auth: Default::default(),
     };
 
     let config = Config {
         source: source_config,
         sink: sink_config,
+        sink_routes: Vec::new(),
+        table_filter: Default::default(),
+        publications: vec![Publication::named(&publication_name)],
         database_url,
         slot_name,
         publication_name,
@@ -592,11 +732,62 @@ pub async fn start_replication(
         starrocks_pass: sink.password,
         flush_size,
         flush_interval_ms,
+        flush_on_commit: false,
+        max_transaction_events: 100_000,
+        exactly_once: false,
+        parse_workers: 1,
+        pipeline_workers: 1,
+        pipeline_shard: Default::default(),
+        spill: None,
+        pgoutput_protocol_version: 1,
+        streamed_txn_memory_bytes: 256 * 1024 * 1024,
+        streamed_txn_spill_dir: std::env::temp_dir().to_string_lossy().into_owned(),
+        binary_tuples: false,
+        clock_skew_warn_ms: 1000,
+        clock_skew_correction: false,
+        auto_tune_memory: false,
         grpc_port: 50051,
+        grpc_operator_token: None,
+        grpc_listen: GrpcListen::Tcp(([0, 0, 0, 0], 50051).into()),
+        grpc_tls: None,
         do_snapshot: false,
+        snapshot_method: SnapshotMethod::Chunked,
         snapshot_chunk_size: 50_000,
         snapshot_parallel_workers: 2,
         initial_snapshot_only: false,
+        backfill_only: false,
+        publication_check_interval: None,
+        snapshot_new_tables: false,
+        start_position: StartPosition::Checkpoint,
+        slot_monitor_interval_secs: 30,
+        wal_retention: None,
+        standby: None,
+        dead_letter: None,
+        paused_table_probe: Some(PausedTableProbeConfig {
+            initial: Duration::from_secs(60),
+            max: Duration::from_secs(3600),
+        }),
+        paused_table_webhook_url: None,
+        checkpoint_store: Default::default(),
+        sink_retry: Default::default(),
+        slot_takeover: Default::default(),
+        slot_takeover_timeout_secs: 60,
+        schema_export_path: None,
+        sync_column_comments: false,
+        sink_key_columns: Default::default(),
+        auto_create_tables: false,
+        sink_table_models: Default::default(),
+        sink_ddl_template: None,
+        sink_table_ddl_templates: Default::default(),
+        lineage_export_path: None,
+        lineage_catalog_url: None,
+        text_normalization: Default::default(),
+        column_transforms: Default::default(),
+        change_suppression: Default::default(),
+        toast_hydration: Default::default(),
+        freshness_sla: Default::default(),
+        probe: None,
+        tail: Default::default(),
     };
 
     let engine = CdcEngine::new(config);
@@ -674,6 +865,7 @@ fn pg_udt_to_starrocks(
 }
 
 async fn ensure_starrocks_tables(
+    session: &PgSession,
     src: &SourceSetupConfig,
     sink: &SinkSetupConfig,
     tables: &[String],
@@ -683,12 +875,10 @@ async fn ensure_starrocks_tables(
         "postgres://{}:{}@{}:{}/{}",
         src.user, src.password, src.host, src.port, src.database
     );
-    let (pg_client, pg_conn) = tokio_postgres::connect(&pg_url, tokio_postgres::NoTls)
+    let pg_client = session
+        .connect_helper(&pg_url, "PG setup")
         .await
         .map_err(|e| format!("PG connect: {}", e))?;
-    tokio::spawn(async move {
-        let _ = pg_conn.await;
-    });
 
     // 2. Connect to StarRocks (without database — it may not exist yet)
     let sr_opts = mysql_async::OptsBuilder::default()
@@ -11,6 +11,7 @@ use tokio::sync::RwLock;
 use tracing::info;
 
 use crate::grpc::state::SharedState;
+use crate::source::session::PgSession;
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct SourceSetupConfig {
@@ -36,17 +37,22 @@ pub struct HttpAppState {
     pub start_time: Instant,
     pub source_config: RwLock<Option<SourceSetupConfig>>,
     pub sink_config: RwLock<Option<SinkSetupConfig>>,
+    /// Settings of the wizard's PostgreSQL connections: `SOURCE_SSL_*` when
+    /// the engine was configured from the environment, defaults otherwise
+    pub session: PgSession,
 }
 
 pub async fn start_http_server(
     port: u16,
     initial_engine: Option<Arc<SharedState>>,
+    session: PgSession,
 ) -> anyhow::Result<()> {
     let state = Arc::new(HttpAppState {
         engine_state: RwLock::new(initial_engine),
         start_time: Instant::now(),
         source_config: RwLock::new(None),
         sink_config: RwLock::new(None),
+        session,
     });
 
     let app = Router::new()
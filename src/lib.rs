@@ -0,0 +1,165 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! dbmazz: PostgreSQL CDC engine.
+//!
+//! Besides the `dbmazz` binary, the crate can be embedded: build a [`Config`]
+//! (usually with [`Config::from_env`]), hand the engine a sink such as
+//! [`ChannelSink`] with [`CdcEngine::with_sink`] and run it on a tokio runtime.
+//! Batches then reach the caller instead of a database, with the checkpoint
+//! only advancing once they are acknowledged. A sink of one's own implements
+//! [`Sink`], whose arguments and results are exported here as well.
+//!
+//! ```rust,no_run
+//! use dbmazz::{CdcEngine, ChannelSink, Config};
+//!
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     let (sink, mut batches) = ChannelSink::channel(16);
+//!     tokio::spawn(async move {
+//!         while let Some(batch) = batches.recv().await {
+//!             println!("{} changes", batch.records.len());
+//!             batch.ack(Ok(()));
+//!         }
+//!     });
+//!     CdcEngine::new(Config::from_env()?)
+//!         .with_sink(Box::new(sink))
+//!         .run()
+//!         .await
+//! }
+//! ```
+
+#![warn(clippy::all)]
+
+mod checkpoint_store;
+mod config;
+mod connectors;
+mod core;
+#[cfg(feature = "demo")]
+mod demo;
+mod docs;
+mod engine;
+mod grpc;
+#[cfg(feature = "http-api")]
+mod http_api;
+mod logging;
+mod pipeline;
+mod replication;
+mod resources;
+mod runtime;
+mod sink;
+mod source;
+mod state_format;
+mod state_store;
+mod utils;
+
+use anyhow::Result;
+use dotenvy::dotenv;
+#[cfg(feature = "http-api")]
+use tracing::{error, info};
+
+use crate::config::RuntimeConfig;
+#[cfg(feature = "http-api")]
+use crate::source::session::PgSession;
+
+// Embedding: run the engine inside another program, with its own sink
+pub use crate::config::Config;
+pub use crate::connectors::sinks::channel::{ChannelBatch, ChannelSink};
+pub use crate::core::traits::StageFormat;
+pub use crate::core::{CdcRecord, LoadingModel, Sink, SinkCapabilities, SinkResult};
+pub use crate::engine::CdcEngine;
+
+// Public event model shared by embedders and the remote sink protocol
+pub use crate::core::{ColumnValue, SourcePosition, TableRef, Value};
+pub use crate::pipeline::schema_cache::SchemaDelta;
+pub use crate::source::parser::{CdcMessage, Column, Tuple, TupleData};
+
+/// Entry point of the `dbmazz` binary.
+#[doc(hidden)]
+pub fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    // `dbmazz docs` prints to stdout, so its logs go to stderr
+    let docs_command = args.get(1).is_some_and(|a| a == "docs");
+
+    // Initialize tracing subscriber; SetLogLevel changes its filter later
+    logging::init(docs_command);
+
+    dotenv().ok();
+
+    // The runtime is built by hand so its topology can come from the env
+    let runtime_config = RuntimeConfig::from_env();
+    if docs_command {
+        let docs_args = docs::DocsArgs::parse(&args[2..])?;
+        return runtime::build(&runtime_config)?.block_on(docs::run(docs_args));
+    }
+    runtime::build(&runtime_config)?.block_on(run(runtime_config))
+}
+
+async fn run(runtime_config: RuntimeConfig) -> Result<()> {
+    if runtime_config.worker_threads.is_some() || runtime_config.dedicated_wal_thread {
+        tracing::info!(
+            "Runtime: {} worker threads{}",
+            runtime_config
+                .worker_threads
+                .map_or_else(|| "default".to_string(), |n| n.to_string()),
+            if runtime_config.dedicated_wal_thread {
+                ", WAL reader on its own thread"
+            } else {
+                ""
+            }
+        );
+    }
+
+    #[cfg(feature = "demo")]
+    {
+        if std::env::var("DEMO_MODE").unwrap_or_default() == "true" {
+            return demo::run().await;
+        }
+    }
+
+    // When http-api is enabled, support two modes:
+    //   1. Auto-start: env vars present → start engine + HTTP dashboard
+    //   2. Setup mode: env vars missing → start HTTP server only, configure from browser
+    #[cfg(feature = "http-api")]
+    {
+        let http_port: u16 = std::env::var("HTTP_API_PORT")
+            .unwrap_or_else(|_| "8080".to_string())
+            .parse()
+            .unwrap_or(8080);
+
+        match Config::from_env() {
+            Ok(config) => {
+                config.print_banner();
+                let session = PgSession::from_config(&config)?;
+                let engine = CdcEngine::new(config);
+                let shared = engine.shared_state();
+
+                tokio::spawn(async move {
+                    if let Err(e) =
+                        http_api::start_http_server(http_port, Some(shared), session).await
+                    {
+                        error!("HTTP API server error: {}", e);
+                    }
+                });
+
+                return runtime::run_engine(&runtime_config, engine.run()).await;
+            }
+            Err(_) => {
+                info!("No database configuration found — starting in setup mode");
+                info!("Open http://0.0.0.0:{} to configure datasources", http_port);
+                return http_api::start_http_server(http_port, None, PgSession::default())
+                    .await
+                    .map_err(Into::into);
+            }
+        }
+    }
+
+    // Without http-api feature: require env vars (original behavior)
+    #[cfg(not(feature = "http-api"))]
+    {
+        let config = Config::from_env()?;
+        config.print_banner();
+        let engine = CdcEngine::new(config);
+        runtime::run_engine(&runtime_config, engine.run()).await
+    }
+}
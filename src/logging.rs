@@ -0,0 +1,253 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Tracing subscriber and runtime log level changes.
+//!
+//! The filter starts from `RUST_LOG` (`info` when unset) and sits behind a
+//! reload handle, so the `SetLogLevel` RPC can turn on debug logging for one
+//! module, e.g. `source::postgres`, and back off again without a restart
+//! losing the state of an incident.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Context, Result};
+use parking_lot::Mutex;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Top-level modules of this crate, whose targets are `dbmazz::<module>`
+const CRATE_MODULES: &[&str] = &[
+    "checkpoint_store",
+    "config",
+    "connectors",
+    "core",
+    "demo",
+    "docs",
+    "engine",
+    "grpc",
+    "http_api",
+    "logging",
+    "pipeline",
+    "replication",
+    "resources",
+    "runtime",
+    "sink",
+    "source",
+    "state_format",
+    "state_store",
+    "utils",
+];
+
+static FILTER: OnceLock<Mutex<LogFilter>> = OnceLock::new();
+
+struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Directives read from `RUST_LOG` at startup
+    base: String,
+    /// Levels set by `SetLogLevel`, by target; `""` is the default level
+    overrides: BTreeMap<String, LevelFilter>,
+    /// Changes made so far
+    changes: u64,
+    /// Number of the last change to each target, so a timed change is only
+    /// reverted while nothing replaced it
+    last_changes: BTreeMap<String, u64>,
+}
+
+/// A level change, to be undone by [`revert`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelChange {
+    pub target: String,
+    /// The level set before, `None` when the target had none of its own
+    previous: Option<LevelFilter>,
+    change: u64,
+}
+
+/// Installs the global subscriber, writing to stderr when `to_stderr`.
+pub fn init(to_stderr: bool) {
+    let base = std::env::var("RUST_LOG")
+        .ok()
+        .filter(|directives| EnvFilter::try_new(directives).is_ok())
+        .unwrap_or_else(|| "info".to_string());
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(&base));
+    let registry = tracing_subscriber::registry().with(filter);
+    if to_stderr {
+        registry
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .init();
+    } else {
+        registry.with(tracing_subscriber::fmt::layer()).init();
+    }
+    let _ = FILTER.set(Mutex::new(LogFilter {
+        handle,
+        base,
+        overrides: BTreeMap::new(),
+        changes: 0,
+        last_changes: BTreeMap::new(),
+    }));
+}
+
+/// Sets the level of `target` (a module path, `dbmazz::` optional; empty
+/// for the default level), or with `level` empty drops the level set
+/// before so the startup one applies again. Returns the change and the
+/// filter now in effect.
+pub fn set_level(target: &str, level: &str) -> Result<(LevelChange, String)> {
+    let target = qualify_target(target)?;
+    let level = match level.trim() {
+        "" => None,
+        level => Some(LevelFilter::from_str(level).map_err(|_| {
+            anyhow!(
+                "invalid log level '{}': use trace, debug, info, warn, error or off",
+                level
+            )
+        })?),
+    };
+    let filter = FILTER
+        .get()
+        .ok_or_else(|| anyhow!("log levels can't be changed: no reloadable logger installed"))?;
+    let mut filter = filter.lock();
+    let previous = match level {
+        Some(level) => filter.overrides.insert(target.clone(), level),
+        None => filter.overrides.remove(&target),
+    };
+    filter.changes += 1;
+    let change = LevelChange {
+        target,
+        previous,
+        change: filter.changes,
+    };
+    filter
+        .last_changes
+        .insert(change.target.clone(), change.change);
+    let directives = filter.apply()?;
+    Ok((change, directives))
+}
+
+/// Undoes `change` unless its target was changed again since. Returns the
+/// filter now in effect when it was undone.
+pub fn revert(change: &LevelChange) -> Result<Option<String>> {
+    let Some(filter) = FILTER.get() else {
+        return Ok(None);
+    };
+    let mut filter = filter.lock();
+    if filter.last_changes.get(&change.target) != Some(&change.change) {
+        return Ok(None);
+    }
+    match change.previous {
+        Some(level) => filter.overrides.insert(change.target.clone(), level),
+        None => filter.overrides.remove(&change.target),
+    };
+    filter.last_changes.remove(&change.target);
+    filter.apply().map(Some)
+}
+
+impl LogFilter {
+    /// Reloads the filter with the startup directives and the overrides.
+    fn apply(&self) -> Result<String> {
+        let directives = directives(&self.base, &self.overrides);
+        let filter = EnvFilter::try_new(&directives)
+            .with_context(|| format!("invalid log filter '{}'", directives))?;
+        self.handle
+            .reload(filter)
+            .context("failed to reload the log filter")?;
+        Ok(directives)
+    }
+}
+
+/// `target` as tracing names it: modules of this crate get the crate name
+/// in front, other crates' targets are kept.
+fn qualify_target(target: &str) -> Result<String> {
+    let target = target.trim();
+    if target
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, ',' | '=' | '[' | ']' | '{' | '}'))
+    {
+        anyhow::bail!("invalid log target '{}'", target);
+    }
+    let first = target.split("::").next().unwrap_or_default();
+    Ok(if CRATE_MODULES.contains(&first) {
+        format!("dbmazz::{}", target)
+    } else {
+        target.to_string()
+    })
+}
+
+/// The startup directives with the overridden targets replaced
+fn directives(base: &str, overrides: &BTreeMap<String, LevelFilter>) -> String {
+    let directive_target = |directive: &str| {
+        if !directive.contains('=') {
+            return String::new();
+        }
+        let end = directive.find(['=', '[']).unwrap_or(directive.len());
+        directive[..end].to_string()
+    };
+    base.split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty() && !overrides.contains_key(&directive_target(d)))
+        .map(str::to_string)
+        .chain(overrides.iter().map(|(target, level)| {
+            if target.is_empty() {
+                level.to_string().to_lowercase()
+            } else {
+                format!("{}={}", target, level.to_string().to_lowercase())
+            }
+        }))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qualify_target() {
+        assert_eq!(
+            qualify_target("source::postgres").unwrap(),
+            "dbmazz::source::postgres"
+        );
+        assert_eq!(
+            qualify_target("dbmazz::connectors::sinks::starrocks").unwrap(),
+            "dbmazz::connectors::sinks::starrocks"
+        );
+        assert_eq!(qualify_target("tokio_postgres").unwrap(), "tokio_postgres");
+        assert_eq!(qualify_target("").unwrap(), "");
+        assert!(qualify_target("source=debug").is_err());
+        assert!(qualify_target("a,b").is_err());
+    }
+
+    #[test]
+    fn test_crate_modules_cover_lib() {
+        let declared: Vec<&str> = include_str!("lib.rs")
+            .lines()
+            .filter_map(|line| line.strip_prefix("mod ")?.strip_suffix(';'))
+            .collect();
+        assert!(!declared.is_empty());
+        for module in declared {
+            assert!(CRATE_MODULES.contains(&module), "{} is missing", module);
+        }
+    }
+
+    #[test]
+    fn test_directives() {
+        let overrides = BTreeMap::from([
+            ("dbmazz::source::postgres".to_string(), LevelFilter::DEBUG),
+            ("tokio_postgres".to_string(), LevelFilter::OFF),
+        ]);
+        assert_eq!(
+            directives("info,tokio_postgres=warn", &overrides),
+            "info,dbmazz::source::postgres=debug,tokio_postgres=off"
+        );
+
+        // The default level replaces the startup one
+        let overrides = BTreeMap::from([(String::new(), LevelFilter::TRACE)]);
+        assert_eq!(
+            directives("warn, dbmazz=info", &overrides),
+            "dbmazz=info,trace"
+        );
+        assert_eq!(directives("info", &BTreeMap::new()), "info");
+    }
+}
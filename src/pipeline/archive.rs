@@ -0,0 +1,145 @@
+//! Local archive used by the WAL retention guard's `archive_tee` action.
+//!
+//! Once the guard trips, the pipeline stops writing to the sink and appends
+//! every batch here instead, then confirms its LSN so the slot can advance.
+//! Each file is JSON lines: a format stamp (`{"format": 1, "written_by":
+//! "<version>"}`), then `{"lsn": <u64>, "message": <CdcMessage>}` lines,
+//! starting with the Relation messages known at the time it was opened, so
+//! the file can be decoded on its own. Replaying it into the sink is left to
+//! the operator.
+
+use crate::pipeline::schema_cache::SchemaCache;
+use crate::source::parser::CdcMessage;
+use crate::state_format::{FormatStamp, ARCHIVE_FORMAT};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize)]
+struct ArchiveLine<'a> {
+    lsn: u64,
+    message: &'a CdcMessage,
+}
+
+pub struct WalArchive {
+    dir: PathBuf,
+    writer: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl WalArchive {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            writer: None,
+        }
+    }
+
+    /// Path of the file currently being written, if one was opened.
+    #[allow(dead_code)]
+    pub fn path(&self) -> Option<&Path> {
+        self.writer.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// Appends a batch and syncs it to disk before returning, since the
+    /// caller confirms `lsn` to PostgreSQL right after.
+    pub fn append(&mut self, batch: &[CdcMessage], lsn: u64, schema: &SchemaCache) -> Result<()> {
+        if self.writer.is_none() {
+            self.writer = Some(self.open(schema)?);
+        }
+        let Some((path, writer)) = self.writer.as_mut() else {
+            unreachable!("archive writer opened above");
+        };
+
+        for message in batch {
+            serde_json::to_writer(&mut *writer, &ArchiveLine { lsn, message })?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        writer
+            .get_ref()
+            .sync_data()
+            .with_context(|| format!("failed to sync {}", path.display()))?;
+        Ok(())
+    }
+
+    fn open(&self, schema: &SchemaCache) -> Result<(PathBuf, BufWriter<File>)> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let path = self.dir.join(format!(
+            "archive-{}.jsonl",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S")
+        ));
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &FormatStamp::current(ARCHIVE_FORMAT))?;
+        writer.write_all(b"\n")?;
+        for message in schema.relation_messages() {
+            serde_json::to_writer(
+                &mut writer,
+                &ArchiveLine {
+                    lsn: 0,
+                    message: &message,
+                },
+            )?;
+            writer.write_all(b"\n")?;
+        }
+        Ok((path, writer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::{Column, Tuple};
+
+    #[test]
+    fn test_archive_starts_with_known_relations() {
+        let dir = std::env::temp_dir().join(format!("dbmazz-archive-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut schema = SchemaCache::new();
+        schema.update(&CdcMessage::Relation {
+            id: 7,
+            namespace: "public".into(),
+            name: "orders".into(),
+            replica_identity: b'd',
+            columns: vec![Column {
+                flags: 1,
+                name: "id".into(),
+                type_id: 23,
+                type_mod: -1,
+            }],
+        });
+
+        let mut archive = WalArchive::new(&dir);
+        let insert = CdcMessage::Insert {
+            relation_id: 7,
+            tuple: Tuple {
+                cols: vec![],
+                toast_bitmap: 0,
+            },
+        };
+        archive.append(&[insert], 0x1000, &schema).unwrap();
+
+        let contents = fs::read_to_string(archive.path().unwrap()).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["format"], ARCHIVE_FORMAT);
+        assert_eq!(lines[1]["lsn"], 0);
+        assert!(lines[1]["message"]["Relation"].is_object());
+        assert_eq!(lines[2]["lsn"], 0x1000);
+        assert!(lines[2]["message"]["Insert"].is_object());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
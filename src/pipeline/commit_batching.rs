@@ -0,0 +1,261 @@
+//! Transaction-boundary batching (`FLUSH_ON_COMMIT`).
+//!
+//! By default a batch is cut at `FLUSH_SIZE` events or `FLUSH_INTERVAL_MS`,
+//! wherever that falls, so a sink flush can hold half a source transaction.
+//! With `FLUSH_ON_COMMIT`, the pipeline cuts batches only after a Commit:
+//! events of the transaction still open stay pending for the next batch, and
+//! the LSN confirmed is the Commit's. A batch may then run past `FLUSH_SIZE`
+//! until the transaction commits.
+//!
+//! A transaction larger than `MAX_TRANSACTION_EVENTS` is cut anyway, so one
+//! bulk load can't hold the whole of it in memory; that is logged. So is the
+//! other exception: rows batched before a schema change of their table are
+//! flushed before it, inside the transaction if need be.
+//!
+//! `EXACTLY_ONCE` relies on batches ending on commits: transactions are then
+//! never cut for size, and after a crash the first batch is held until it
+//! can be cut at the LSN the interrupted one ended at, so it keeps its ID.
+
+use crate::source::parser::CdcMessage;
+
+/// Where the pending batch can be cut without tearing a transaction.
+#[derive(Debug)]
+pub struct CommitBoundaries {
+    /// Events of the open transaction after which the batch is cut anyway
+    max_transaction_events: usize,
+    /// Events at the head of the batch up to and including its last Commit
+    committed: usize,
+    /// LSN of that Commit
+    committed_lsn: u64,
+    /// Events of the open transaction in the batch
+    open_events: usize,
+    /// A Begin was batched and its Commit not yet
+    in_transaction: bool,
+}
+
+impl CommitBoundaries {
+    pub fn new(max_transaction_events: usize) -> Self {
+        Self {
+            max_transaction_events,
+            committed: 0,
+            committed_lsn: 0,
+            open_events: 0,
+            in_transaction: false,
+        }
+    }
+
+    /// Notes `msg`, just appended at `lsn` to the batch, now `batch_len` long.
+    /// Messages outside a transaction end on a boundary of their own.
+    pub fn push(&mut self, msg: &CdcMessage, batch_len: usize, lsn: u64) {
+        match msg {
+            CdcMessage::Begin { .. } => {
+                self.in_transaction = true;
+                self.open_events = 0;
+            }
+            CdcMessage::Commit { .. } => {
+                self.in_transaction = false;
+                self.open_events = 0;
+                self.committed = batch_len;
+                self.committed_lsn = lsn;
+            }
+            _ if self.in_transaction => self.open_events += 1,
+            _ => {
+                self.committed = batch_len;
+                self.committed_lsn = lsn;
+            }
+        }
+    }
+
+    /// How many events at the head of the batch can be flushed, and the LSN
+    /// to confirm for them: up to the last Commit, or the whole batch once
+    /// the open transaction is over `MAX_TRANSACTION_EVENTS`.
+    pub fn flushable(&self, batch_len: usize, last_lsn: u64) -> (usize, u64) {
+        if self.spills() {
+            (batch_len, last_lsn)
+        } else {
+            (self.committed, self.committed_lsn)
+        }
+    }
+
+    /// Whether the open transaction is too large to wait for its Commit.
+    pub fn spills(&self) -> bool {
+        self.open_events > self.max_transaction_events
+    }
+
+    /// The first `n` events of the batch were flushed. Flushing past the
+    /// last Commit leaves none of the open transaction in the batch.
+    pub fn flushed(&mut self, n: usize) {
+        if n > self.committed {
+            self.open_events = 0;
+        }
+        self.committed = self.committed.saturating_sub(n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::state::{CdcConfig, CdcState, SharedState};
+    use crate::pipeline::schema_cache::{SchemaCache, SchemaDelta};
+    use crate::pipeline::Pipeline;
+    use crate::sink::{BatchResult, Sink};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    fn begin() -> CdcMessage {
+        CdcMessage::Begin {
+            final_lsn: 0,
+            timestamp: 0,
+            xid: 1,
+        }
+    }
+
+    fn commit() -> CdcMessage {
+        CdcMessage::Commit {
+            flags: 0,
+            commit_lsn: 0,
+            end_lsn: 0,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_batches_end_on_commit() {
+        let row = || CdcMessage::Unknown;
+        let mut boundaries = CommitBoundaries::new(3);
+        boundaries.push(&begin(), 1, 10);
+        boundaries.push(&row(), 2, 11);
+        assert_eq!(boundaries.flushable(2, 11), (0, 0));
+        boundaries.push(&commit(), 3, 12);
+        boundaries.push(&begin(), 4, 13);
+        boundaries.push(&row(), 5, 14);
+        assert_eq!(boundaries.flushable(5, 14), (3, 12));
+
+        boundaries.flushed(3);
+        assert_eq!(boundaries.flushable(2, 14).0, 0);
+
+        // Over the limit, the open transaction goes too
+        boundaries.push(&row(), 3, 15);
+        boundaries.push(&row(), 4, 16);
+        assert!(!boundaries.spills());
+        boundaries.push(&row(), 5, 17);
+        assert!(boundaries.spills());
+        assert_eq!(boundaries.flushable(5, 17), (5, 17));
+        boundaries.flushed(5);
+        assert!(!boundaries.spills());
+
+        boundaries.push(&commit(), 1, 18);
+        assert_eq!(boundaries.flushable(1, 18), (1, 18));
+    }
+
+    /// Records the LSN of every batch written
+    struct LsnSink(Arc<Mutex<Vec<u64>>>);
+
+    #[async_trait]
+    impl Sink for LsnSink {
+        async fn push_batch(
+            &mut self,
+            _: &[CdcMessage],
+            _: &SchemaCache,
+            lsn: u64,
+        ) -> Result<BatchResult> {
+            self.0.lock().push(lsn);
+            Ok(BatchResult::default())
+        }
+
+        async fn apply_schema_delta(&self, _: &SchemaDelta) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Batches `msg` as the pipeline does
+    fn push(pipeline: &mut Pipeline, batch: &mut Vec<CdcMessage>, msg: CdcMessage, lsn: u64) {
+        batch.push(msg);
+        let commits = pipeline.commits.as_mut().unwrap();
+        commits.push(batch.last().unwrap(), batch.len(), lsn);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_batch_is_cut_where_it_was() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let (_tx, rx) = mpsc::channel(1);
+        let mut pipeline = Pipeline::new(
+            rx,
+            Box::new(LsnSink(written.clone())),
+            100,
+            Duration::from_secs(1),
+        )
+        .with_commit_boundaries(Some(usize::MAX))
+        .with_replay_end(Some(15));
+
+        let mut batch = Vec::new();
+        push(&mut pipeline, &mut batch, begin(), 10);
+        push(&mut pipeline, &mut batch, CdcMessage::Unknown, 11);
+        push(&mut pipeline, &mut batch, commit(), 12);
+        // A timer flush would end the batch early and give it another ID
+        assert!(pipeline.flush_ready(&mut batch, 12).await);
+        assert!(written.lock().is_empty());
+
+        push(&mut pipeline, &mut batch, begin(), 13);
+        push(&mut pipeline, &mut batch, commit(), 15);
+        assert!(pipeline.flush_ready(&mut batch, 15).await);
+        assert_eq!(*written.lock(), [15]);
+        assert!(batch.is_empty());
+
+        // Then batches are cut as usual
+        push(&mut pipeline, &mut batch, begin(), 16);
+        push(&mut pipeline, &mut batch, commit(), 17);
+        assert!(pipeline.flush_ready(&mut batch, 17).await);
+        assert_eq!(*written.lock(), [15, 17]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_flushes_up_to_the_open_transaction() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let state = SharedState::new(CdcConfig {
+            flush_size: 100,
+            flush_interval_ms: 1000,
+            tables: vec![],
+            slot_name: "test_slot".to_string(),
+        });
+        let (_tx, rx) = mpsc::channel(1);
+        let (feedback_tx, mut feedback_rx) = mpsc::channel(4);
+        let mut pipeline = Pipeline::new(
+            rx,
+            Box::new(LsnSink(written.clone())),
+            100,
+            Duration::from_secs(1),
+        )
+        .with_shared_state(state.clone())
+        .with_feedback_channel(feedback_tx)
+        .with_commit_boundaries(Some(usize::MAX));
+
+        let mut batch = Vec::new();
+        push(&mut pipeline, &mut batch, begin(), 10);
+        push(&mut pipeline, &mut batch, CdcMessage::Unknown, 11);
+        push(&mut pipeline, &mut batch, commit(), 12);
+        push(&mut pipeline, &mut batch, begin(), 13);
+        push(&mut pipeline, &mut batch, CdcMessage::Unknown, 14);
+
+        let mut state_rx = state.subscribe_state();
+        assert!(state.compare_and_set_state(CdcState::Running, CdcState::Paused));
+        let operator = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            // Written and confirmed before waiting, not on resume
+            assert_eq!(*written.lock(), [12]);
+            assert_eq!(feedback_rx.try_recv().unwrap(), 12);
+            assert!(state.compare_and_set_state(CdcState::Paused, CdcState::Running));
+        };
+        let (resumed, ()) = tokio::join!(
+            pipeline.wait_while_paused(&mut batch, 14, &mut state_rx),
+            operator
+        );
+        assert!(resumed);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(*state_rx.borrow(), CdcState::Running);
+    }
+}
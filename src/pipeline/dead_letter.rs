@@ -0,0 +1,542 @@
+//! Dead-letter queue for rows the sink rejects (`DEAD_LETTER_TARGET`).
+//!
+//! When a sink fails a batch with `RejectedData`, the pipeline splits the
+//! batch until the rows at fault are on their own, writes the others, and
+//! parks the rejected rows here before confirming the batch's LSN. The
+//! destination is a local directory of JSON lines files, an S3 prefix (one
+//! JSON lines object per parked batch) or the sink's `_dbmazz_dead_letters`
+//! table. Replaying parked rows is left to the operator.
+//!
+//! Files of the `file` destination are started afresh every hour, so they
+//! can expire: with `DEAD_LETTER_MAX_AGE_SECS` a file is deleted once its
+//! last row is that old, and with `DEAD_LETTER_MAX_MB` the oldest files are
+//! deleted while the directory holds more. The file being written is kept.
+//! The directory is swept once a minute; a file nearing its expiry is
+//! warned about once, so rows that still need replaying can be saved.
+
+use crate::config::{DeadLetterConfig, DeadLetterTarget, HttpClientConfig};
+use crate::connectors::s3::S3Client;
+use crate::core::dead_letter::DeadLetter;
+use crate::pipeline::schema_cache::{SchemaCache, TableSchema};
+use crate::sink::Sink;
+use crate::source::parser::{CdcMessage, Tuple, TupleData};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+/// Age at which the file being written is closed and a new one started
+const FILE_ROTATION: Duration = Duration::from_secs(3600);
+
+/// Time between two retention sweeps of the dead-letter directory
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+enum Destination {
+    File {
+        dir: PathBuf,
+        /// The file being written, and when it was opened
+        writer: Option<(PathBuf, BufWriter<File>, Instant)>,
+        retention: Option<Retention>,
+    },
+    S3 {
+        client: S3Client,
+        /// Objects written by this process, to keep their keys apart
+        written: u64,
+    },
+    Table,
+}
+
+pub struct DeadLetterQueue {
+    destination: Destination,
+    max_rows: usize,
+}
+
+/// Limits on the files of the `file` destination
+struct Retention {
+    max_age: Option<Duration>,
+    max_bytes: Option<u64>,
+    last_sweep: Option<Instant>,
+    /// Files already warned about nearing their expiry
+    warned: HashSet<PathBuf>,
+}
+
+/// Dead-letter files left after a retention sweep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SweepResult {
+    pub bytes: u64,
+    /// Age of the oldest file left
+    pub oldest_age: Duration,
+    /// Files deleted by the sweep
+    pub expired: u64,
+}
+
+impl DeadLetterQueue {
+    /// `request_timeout` and `http` apply to S3 uploads, like to the sink's
+    /// own requests.
+    pub fn new(
+        config: &DeadLetterConfig,
+        request_timeout: Duration,
+        http: &HttpClientConfig,
+    ) -> Result<Self> {
+        let destination = match &config.target {
+            DeadLetterTarget::File { dir } => Destination::File {
+                dir: dir.into(),
+                writer: None,
+                retention: (config.max_age.is_some() || config.max_bytes.is_some()).then(|| {
+                    Retention {
+                        max_age: config.max_age,
+                        max_bytes: config.max_bytes,
+                        last_sweep: None,
+                        warned: HashSet::new(),
+                    }
+                }),
+            },
+            DeadLetterTarget::S3(location) => Destination::S3 {
+                client: S3Client::new(location.clone(), request_timeout, http)?,
+                written: 0,
+            },
+            DeadLetterTarget::Table => Destination::Table,
+        };
+        Ok(Self {
+            destination,
+            max_rows: config.max_rows,
+        })
+    }
+
+    /// Rejected rows parked per batch before the pipeline gives up.
+    pub fn max_rows(&self) -> usize {
+        self.max_rows
+    }
+
+    /// Path of the file currently being written, if one was opened.
+    #[allow(dead_code)]
+    pub fn path(&self) -> Option<&Path> {
+        match &self.destination {
+            Destination::File { writer, .. } => writer.as_ref().map(|(path, _, _)| path.as_path()),
+            _ => None,
+        }
+    }
+
+    /// Parks the rejected rows of one batch. They are durable when this
+    /// returns, since the caller confirms the batch's LSN right after.
+    pub async fn park(
+        &mut self,
+        letters: &[DeadLetter],
+        lsn: u64,
+        sink: &mut (dyn Sink + Send),
+    ) -> Result<()> {
+        if letters.is_empty() {
+            return Ok(());
+        }
+        match &mut self.destination {
+            Destination::File { dir, writer, .. } => {
+                if writer
+                    .as_ref()
+                    .is_some_and(|(_, _, opened)| opened.elapsed() >= FILE_ROTATION)
+                {
+                    *writer = None;
+                }
+                if writer.is_none() {
+                    let (path, file) = open_file(dir)?;
+                    *writer = Some((path, file, Instant::now()));
+                }
+                let Some((path, writer, _)) = writer.as_mut() else {
+                    unreachable!("dead-letter file opened above");
+                };
+                writer.write_all(&to_json_lines(letters)?)?;
+                writer.flush()?;
+                writer
+                    .get_ref()
+                    .sync_data()
+                    .with_context(|| format!("failed to sync {}", path.display()))
+            }
+            Destination::S3 { client, written } => {
+                *written += 1;
+                let now = chrono::Utc::now();
+                let key = client.key(&format!(
+                    "{}/dead-letters-{}-{:016X}-{}.jsonl",
+                    now.format("%Y/%m/%d"),
+                    now.format("%Y%m%dT%H%M%S%.3fZ"),
+                    lsn,
+                    written
+                ));
+                client
+                    .put_object(&key, to_json_lines(letters)?, "application/x-ndjson")
+                    .await
+                    .with_context(|| format!("failed to upload dead letters to {}", key))
+            }
+            Destination::Table => sink.write_dead_letters(letters).await,
+        }
+    }
+
+    /// Deletes the files past `DEAD_LETTER_MAX_AGE_SECS`, then the oldest
+    /// ones while the directory holds more than `DEAD_LETTER_MAX_MB`, and
+    /// warns about files nearing their expiry. `None` without retention
+    /// limits, or when the last sweep was less than a minute ago.
+    pub fn sweep(&mut self) -> Result<Option<SweepResult>> {
+        let Destination::File {
+            dir,
+            writer,
+            retention: Some(retention),
+        } = &mut self.destination
+        else {
+            return Ok(None);
+        };
+        if retention
+            .last_sweep
+            .is_some_and(|at| at.elapsed() < SWEEP_INTERVAL)
+        {
+            return Ok(None);
+        }
+        retention.last_sweep = Some(Instant::now());
+        let current = writer.as_ref().map(|(path, _, _)| path.as_path());
+        sweep_files(dir, current, retention, SystemTime::now()).map(Some)
+    }
+}
+
+fn sweep_files(
+    dir: &Path,
+    current: Option<&Path>,
+    retention: &mut Retention,
+    now: SystemTime,
+) -> Result<SweepResult> {
+    // (path, size, age), oldest first: names start with the time opened
+    let mut files = Vec::new();
+    if dir.exists() {
+        let entries =
+            fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            let is_dead_letters = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("dead-letters-") && name.ends_with(".jsonl"));
+            if !is_dead_letters {
+                continue;
+            }
+            let metadata = fs::metadata(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let modified = metadata.modified().unwrap_or(now);
+            let age = now.duration_since(modified).unwrap_or_default();
+            files.push((path, metadata.len(), age));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut bytes: u64 = files.iter().map(|(_, size, _)| size).sum();
+    let mut expired = 0;
+    let mut kept = Vec::new();
+    for (path, size, age) in files {
+        let too_old = retention.max_age.is_some_and(|max| age >= max);
+        let too_large = retention.max_bytes.is_some_and(|max| bytes > max);
+        if (too_old || too_large) && Some(path.as_path()) != current {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+            info!(
+                "[DLQ] Deleted {} ({})",
+                path.display(),
+                if too_old {
+                    "DEAD_LETTER_MAX_AGE_SECS"
+                } else {
+                    "DEAD_LETTER_MAX_MB"
+                }
+            );
+            bytes -= size;
+            expired += 1;
+            retention.warned.remove(&path);
+            continue;
+        }
+        if let Some(max) = retention.max_age {
+            if age >= max.mul_f64(0.8) && retention.warned.insert(path.clone()) {
+                warn!(
+                    "[DLQ] {} expires in {}s (DEAD_LETTER_MAX_AGE_SECS); \
+                     save it if its rows still need replaying",
+                    path.display(),
+                    max.saturating_sub(age).as_secs()
+                );
+            }
+        }
+        kept.push((path, age));
+    }
+    retention
+        .warned
+        .retain(|path| kept.iter().any(|(p, _)| p == path));
+    Ok(SweepResult {
+        bytes,
+        oldest_age: kept.iter().map(|(_, age)| *age).max().unwrap_or_default(),
+        expired,
+    })
+}
+
+fn open_file(dir: &Path) -> Result<(PathBuf, BufWriter<File>)> {
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join(format!(
+        "dead-letters-{}.jsonl",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S")
+    ));
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    Ok((path, BufWriter::new(file)))
+}
+
+fn to_json_lines(letters: &[DeadLetter]) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    for letter in letters {
+        serde_json::to_writer(&mut body, letter)?;
+        body.push(b'\n');
+    }
+    Ok(body)
+}
+
+/// The dead letter of a rejected row change, `None` for other messages.
+/// `error` is the sink's error for the write that held only this row.
+pub fn dead_letter(
+    msg: &CdcMessage,
+    schema_cache: &SchemaCache,
+    lsn: u64,
+    error: &anyhow::Error,
+) -> Option<DeadLetter> {
+    let (op, relation_id, row, old_row) = match msg {
+        CdcMessage::Insert { relation_id, tuple } => ("insert", *relation_id, tuple, None),
+        CdcMessage::Update {
+            relation_id,
+            old_tuple,
+            new_tuple,
+        } => ("update", *relation_id, new_tuple, old_tuple.as_ref()),
+        CdcMessage::Delete {
+            relation_id,
+            old_tuple: Some(tuple),
+        } => ("delete", *relation_id, tuple, None),
+        _ => return None,
+    };
+    let schema = schema_cache.get(relation_id);
+    Some(DeadLetter {
+        failed_at: chrono::Utc::now(),
+        table: match schema {
+            Some(s) => format!("{}.{}", s.namespace, s.name),
+            None => format!("rel_{}", relation_id),
+        },
+        op,
+        lsn,
+        row: tuple_values(row, schema),
+        old_row: old_row.map(|tuple| tuple_values(tuple, schema)),
+        error: format!("{:#}", error),
+    })
+}
+
+/// Column values of a tuple by name (`col_<n>` when the relation is
+/// unknown), leaving out unchanged TOAST columns.
+fn tuple_values(
+    tuple: &Tuple,
+    schema: Option<&TableSchema>,
+) -> serde_json::Map<String, serde_json::Value> {
+    tuple
+        .cols
+        .iter()
+        .enumerate()
+        .filter_map(|(i, data)| {
+            let column = schema.and_then(|s| s.columns.get(i));
+            let name = column.map_or_else(|| format!("col_{}", i), |c| c.name.to_string());
+            let value = match data {
+                TupleData::Null => serde_json::Value::Null,
+                TupleData::Toast => return None,
+                TupleData::Text(_) | TupleData::Binary(_) => serde_json::Value::String(
+                    data.text(column.map_or(0, |c| c.type_id))
+                        .unwrap_or_default()
+                        .into_owned(),
+                ),
+            };
+            Some((name, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dead_letter::RejectedData;
+    use crate::pipeline::schema_cache::SchemaDelta;
+    use crate::sink::BatchResult;
+    use crate::source::parser::Column;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+
+    fn schema_cache() -> SchemaCache {
+        let mut cache = SchemaCache::new();
+        let column = |name: &str, type_id: u32| Column {
+            flags: 0,
+            name: name.into(),
+            type_id,
+            type_mod: -1,
+        };
+        cache.update(&CdcMessage::Relation {
+            id: 7,
+            namespace: "public".into(),
+            name: "orders".into(),
+            replica_identity: b'd',
+            columns: vec![column("id", 23), column("qty", 23), column("notes", 25)],
+        });
+        cache
+    }
+
+    fn text(s: &str) -> TupleData {
+        TupleData::Text(Bytes::copy_from_slice(s.as_bytes()))
+    }
+
+    struct NoSink;
+
+    #[async_trait]
+    impl Sink for NoSink {
+        async fn push_batch(
+            &mut self,
+            _: &[CdcMessage],
+            _: &SchemaCache,
+            _: u64,
+        ) -> Result<BatchResult> {
+            Ok(BatchResult::default())
+        }
+
+        async fn apply_schema_delta(&self, _: &SchemaDelta) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dead_letter_of_update() {
+        let update = CdcMessage::Update {
+            relation_id: 7,
+            old_tuple: None,
+            new_tuple: Tuple {
+                cols: vec![text("1"), text("-3"), TupleData::Toast],
+                toast_bitmap: 0b100,
+            },
+        };
+        let error = anyhow::Error::new(RejectedData::new("sqlite", "CHECK constraint failed"));
+        let letter = dead_letter(&update, &schema_cache(), 0x1000, &error).unwrap();
+
+        assert_eq!(letter.table, "public.orders");
+        assert_eq!(letter.op, "update");
+        assert_eq!(letter.lsn, 0x1000);
+        assert_eq!(
+            serde_json::Value::Object(letter.row),
+            serde_json::json!({"id": "1", "qty": "-3"})
+        );
+        assert_eq!(letter.old_row, None);
+        assert!(letter.error.contains("CHECK constraint failed"));
+
+        let begin = CdcMessage::Begin {
+            final_lsn: 0,
+            timestamp: 0,
+            xid: 1,
+        };
+        assert!(dead_letter(&begin, &schema_cache(), 0, &error).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_destination_appends_json_lines() {
+        let dir = std::env::temp_dir().join(format!("dbmazz-dlq-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = DeadLetterConfig {
+            target: DeadLetterTarget::File {
+                dir: dir.to_string_lossy().into_owned(),
+            },
+            max_rows: 10,
+            max_age: None,
+            max_bytes: None,
+        };
+        let mut queue =
+            DeadLetterQueue::new(&config, Duration::from_secs(1), &Default::default()).unwrap();
+
+        let delete = CdcMessage::Delete {
+            relation_id: 7,
+            old_tuple: Some(Tuple {
+                cols: vec![text("2"), TupleData::Null, TupleData::Null],
+                toast_bitmap: 0,
+            }),
+        };
+        let error = anyhow::anyhow!("rejected");
+        let letter = dead_letter(&delete, &schema_cache(), 0x2000, &error).unwrap();
+        queue.park(&[], 0x1000, &mut NoSink).await.unwrap();
+        assert!(queue.path().is_none());
+        queue
+            .park(&[letter.clone(), letter], 0x2000, &mut NoSink)
+            .await
+            .unwrap();
+
+        let contents = fs::read_to_string(queue.path().unwrap()).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["op"], "delete");
+        assert_eq!(lines[0]["lsn"], 0x2000);
+        assert_eq!(
+            lines[0]["row"],
+            serde_json::json!({"id": "2", "qty": null, "notes": null})
+        );
+        assert!(lines[0].get("old_row").is_none());
+        // No retention limits, nothing to sweep
+        assert!(queue.sweep().unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sweep_expires_old_and_excess_files() {
+        let dir = std::env::temp_dir().join(format!("dbmazz-dlq-sweep-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, size) in [
+            ("dead-letters-20240101T000000.jsonl", 100),
+            ("dead-letters-20240102T000000.jsonl", 200),
+            ("dead-letters-20240103T000000.jsonl", 300),
+            ("notes.jsonl", 1000),
+        ] {
+            fs::write(dir.join(name), vec![b'x'; size]).unwrap();
+        }
+        let mut retention = Retention {
+            max_age: Some(Duration::from_secs(3600)),
+            max_bytes: Some(550),
+            last_sweep: None,
+            warned: HashSet::new(),
+        };
+
+        // Fresh files: the oldest go until the directory fits
+        let now = SystemTime::now();
+        let result = sweep_files(&dir, None, &mut retention, now).unwrap();
+        assert_eq!(result.expired, 1);
+        assert_eq!(result.bytes, 500);
+        assert!(!dir.join("dead-letters-20240101T000000.jsonl").exists());
+        // ... save the file being written
+        retention.max_bytes = Some(250);
+        let current = dir.join("dead-letters-20240102T000000.jsonl");
+        let result = sweep_files(&dir, Some(&current), &mut retention, now).unwrap();
+        assert_eq!(result.expired, 1);
+        assert_eq!(result.bytes, 200);
+        assert!(current.exists());
+        assert!(!dir.join("dead-letters-20240103T000000.jsonl").exists());
+
+        // Near their expiry, then past it
+        retention.max_bytes = None;
+        let later = now + Duration::from_secs(3000);
+        let result = sweep_files(&dir, None, &mut retention, later).unwrap();
+        assert_eq!(result.expired, 0);
+        assert!(result.oldest_age >= Duration::from_secs(3000));
+        assert!(retention.warned.contains(&current));
+        let result =
+            sweep_files(&dir, None, &mut retention, later + Duration::from_secs(600)).unwrap();
+        assert_eq!(result.expired, 1);
+        assert_eq!(result.bytes, 0);
+        assert!(retention.warned.is_empty());
+        assert!(dir.join("notes.jsonl").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
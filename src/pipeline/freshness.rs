@@ -0,0 +1,253 @@
+//! Per-table freshness (`FRESHNESS_SLA`).
+//!
+//! After each batch reaches the sink, the pipeline measures how long ago the
+//! transactions in it committed on the source, per table. The oldest
+//! transaction of a table in the batch sets its latency. Tables with an SLA
+//! are flagged when that latency is over it, and the transition is logged and
+//! recorded as an error so it shows up in `GetStatus` and `GetRecentErrors`.
+//!
+//! The read-your-writes probe (`PROBE_INTERVAL_SECS`) is timed here too: a
+//! batch carrying its row reports when that row was written.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::grpc::state::{ErrorCategory, SharedState};
+use crate::pipeline::schema_cache::SchemaCache;
+use crate::source::clock::{self, PG_EPOCH_OFFSET_USEC};
+use crate::source::parser::CdcMessage;
+
+/// Milliseconds elapsed since a pgoutput commit timestamp, by the local
+/// clock or, with `CLOCK_SKEW_CORRECTION`, the source's.
+pub fn commit_age_ms(commit_timestamp_us: u64) -> u64 {
+    let now_us = clock::now_unix_us().max(0) as u64;
+    now_us.saturating_sub(commit_timestamp_us + PG_EPOCH_OFFSET_USEC as u64) / 1_000
+}
+
+/// Measures per-table latency and checks it against the configured SLAs.
+pub struct FreshnessTracker {
+    /// SLA by `schema.table` or bare table name
+    slas: HashMap<String, Duration>,
+}
+
+impl FreshnessTracker {
+    pub fn new(slas: HashMap<String, Duration>) -> Self {
+        Self { slas }
+    }
+
+    fn sla_for(&self, namespace: &str, name: &str) -> Option<Duration> {
+        self.slas
+            .get(&format!("{}.{}", namespace, name))
+            .or_else(|| self.slas.get(name))
+            .copied()
+    }
+
+    /// Records the latency of every table with committed changes in `batch`,
+    /// which has just been written to the sink.
+    pub async fn record(
+        &self,
+        batch: &[CdcMessage],
+        schema_cache: &SchemaCache,
+        state: &SharedState,
+    ) {
+        for (relation_id, commit_timestamp_us) in oldest_commits(batch) {
+            let Some(schema) = schema_cache.get(relation_id) else {
+                continue;
+            };
+            let table = format!("{}.{}", schema.namespace, schema.name);
+            let latency_ms = commit_age_ms(commit_timestamp_us);
+            let sla = self.sla_for(&schema.namespace, &schema.name);
+            let sla_ms = sla.map(|s| s.as_millis() as u64);
+            if !state.record_table_freshness(&table, latency_ms, sla_ms) {
+                continue;
+            }
+
+            let sla_ms = sla_ms.unwrap_or_default();
+            if latency_ms <= sla_ms {
+                info!(
+                    "[FRESHNESS] {} back within its SLA: {}ms (SLA {}ms)",
+                    table, latency_ms, sla_ms
+                );
+                continue;
+            }
+            warn!(
+                "[FRESHNESS] {} is over its SLA: {}ms behind the source (SLA {}ms)",
+                table, latency_ms, sla_ms
+            );
+            state
+                .record_error(
+                    ErrorCategory::Freshness,
+                    format!(
+                        "{} delivered {}ms after commit, SLA is {}ms",
+                        table, latency_ms, sla_ms
+                    ),
+                    Some(&table),
+                    0,
+                )
+                .await;
+        }
+    }
+}
+
+/// When the newest probe row in `batch` was written, from its `sent_ms`
+/// column. `table` is the probe table, `schema.table`.
+pub fn probe_sent_ms(batch: &[CdcMessage], schema_cache: &SchemaCache, table: &str) -> Option<u64> {
+    let mut newest = None;
+    for msg in batch {
+        let (relation_id, tuple) = match msg {
+            CdcMessage::Insert { relation_id, tuple } => (relation_id, tuple),
+            CdcMessage::Update {
+                relation_id,
+                new_tuple,
+                ..
+            } => (relation_id, new_tuple),
+            _ => continue,
+        };
+        let Some(schema) = schema_cache.get(*relation_id) else {
+            continue;
+        };
+        if format!("{}.{}", schema.namespace, schema.name) != table {
+            continue;
+        }
+        let Some(idx) = schema.columns.iter().position(|c| &*c.name == "sent_ms") else {
+            continue;
+        };
+        let sent_ms = tuple
+            .cols
+            .get(idx)
+            .and_then(|data| data.text(schema.columns[idx].type_id))
+            .and_then(|t| t.parse::<u64>().ok());
+        if sent_ms.is_some() {
+            newest = newest.max(sent_ms);
+        }
+    }
+    newest
+}
+
+/// Commit timestamp of the oldest transaction per relation in `batch`.
+/// Changes without their transaction's commit in the same batch (a large
+/// transaction split across batches) are not measured.
+fn oldest_commits(batch: &[CdcMessage]) -> HashMap<u32, u64> {
+    let mut oldest = HashMap::new();
+    let mut touched: Vec<u32> = Vec::new();
+    for msg in batch {
+        match msg {
+            CdcMessage::Insert { relation_id, .. }
+            | CdcMessage::Update { relation_id, .. }
+            | CdcMessage::Delete { relation_id, .. } => {
+                if !touched.contains(relation_id) {
+                    touched.push(*relation_id);
+                }
+            }
+            // Commits arrive in order, so the first one seen is the oldest
+            CdcMessage::Commit { timestamp, .. } => {
+                for relation_id in touched.drain(..) {
+                    oldest.entry(relation_id).or_insert(*timestamp);
+                }
+            }
+            _ => {}
+        }
+    }
+    oldest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::{Column, Tuple, TupleData};
+
+    fn insert(relation_id: u32) -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id,
+            tuple: Tuple {
+                cols: Vec::new(),
+                toast_bitmap: 0,
+            },
+        }
+    }
+
+    fn commit(timestamp: u64) -> CdcMessage {
+        CdcMessage::Commit {
+            flags: 0,
+            commit_lsn: 0,
+            end_lsn: 0,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_oldest_commit_per_table() {
+        let batch = [
+            insert(1),
+            commit(100),
+            insert(2),
+            insert(1),
+            commit(200),
+            // Commit in the next batch
+            insert(3),
+        ];
+        let oldest = oldest_commits(&batch);
+        assert_eq!(oldest.len(), 2);
+        assert_eq!(oldest[&1], 100);
+        assert_eq!(oldest[&2], 200);
+    }
+
+    #[test]
+    fn test_probe_row_is_found() {
+        let mut cache = SchemaCache::new();
+        let column = |name: &str| Column {
+            flags: 0,
+            name: name.into(),
+            type_id: 20,
+            type_mod: -1,
+        };
+        cache.update(&CdcMessage::Relation {
+            id: 9,
+            namespace: "public".into(),
+            name: "dbmazz_probe".into(),
+            replica_identity: b'f',
+            columns: vec![column("id"), column("sent_ms")],
+        });
+        let probe = |sent_ms: &str| CdcMessage::Update {
+            relation_id: 9,
+            old_tuple: None,
+            new_tuple: Tuple {
+                cols: vec![
+                    TupleData::Text("1".into()),
+                    TupleData::Text(sent_ms.to_string().into()),
+                ],
+                toast_bitmap: 0,
+            },
+        };
+
+        let batch = [insert(1), probe("1000"), probe("2000"), commit(100)];
+        assert_eq!(
+            probe_sent_ms(&batch, &cache, "public.dbmazz_probe"),
+            Some(2000)
+        );
+        assert_eq!(probe_sent_ms(&batch, &cache, "ops.dbmazz_probe"), None);
+        assert_eq!(
+            probe_sent_ms(&[insert(1), commit(100)], &cache, "public.dbmazz_probe"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sla_lookup_prefers_qualified_name() {
+        let tracker = FreshnessTracker::new(HashMap::from([
+            ("orders".to_string(), Duration::from_secs(60)),
+            ("sales.orders".to_string(), Duration::from_secs(5)),
+        ]));
+        assert_eq!(
+            tracker.sla_for("sales", "orders"),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            tracker.sla_for("public", "orders"),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(tracker.sla_for("public", "users"), None);
+    }
+}
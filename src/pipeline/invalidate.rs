@@ -0,0 +1,121 @@
+//! Sink schema invalidation (`InvalidateSinkSchema`).
+//!
+//! Sinks remember what they learned about their tables: ClickHouse the
+//! sorting keys, SQLite the columns and primary key, all of them whether the
+//! internal tables exist. After a sink table is changed out of band (a column
+//! dropped, the table re-created), that knowledge is stale until a restart.
+//! The gRPC service sends an [`InvalidateRequest`] to the pipeline, which
+//! answers between batches: the sink forgets the table, and the table's full
+//! column list is applied again as a schema delta, so missing columns are
+//! added back.
+
+use tokio::sync::oneshot;
+
+/// An `InvalidateSinkSchema` call waiting for the pipeline.
+pub struct InvalidateRequest {
+    /// Source table (`schema.table` or bare name); `None` for every table
+    pub table: Option<String>,
+    /// Tables whose schema was re-applied, or why it failed
+    pub reply: oneshot::Sender<Result<Vec<String>, String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::schema_cache::{SchemaCache, SchemaDelta};
+    use crate::pipeline::Pipeline;
+    use crate::sink::{BatchResult, Sink};
+    use crate::source::parser::{CdcMessage, Column};
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// Records invalidations and applied deltas
+    #[derive(Default)]
+    struct RecordingSink {
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Sink for RecordingSink {
+        async fn push_batch(
+            &mut self,
+            _: &[CdcMessage],
+            _: &SchemaCache,
+            _: u64,
+        ) -> anyhow::Result<BatchResult> {
+            Ok(BatchResult::default())
+        }
+
+        async fn apply_schema_delta(&self, delta: &SchemaDelta) -> anyhow::Result<()> {
+            self.log.lock().push(format!(
+                "apply {} {}",
+                delta.qualified_name(),
+                delta.added_columns.len()
+            ));
+            Ok(())
+        }
+
+        fn invalidate_schema(&mut self, table: Option<&str>) {
+            self.log
+                .lock()
+                .push(format!("invalidate {}", table.unwrap_or("*")));
+        }
+    }
+
+    fn relation(id: u32, name: &str, columns: &[&str]) -> CdcMessage {
+        CdcMessage::Relation {
+            id,
+            namespace: "public".into(),
+            name: name.into(),
+            replica_identity: b'd',
+            columns: columns
+                .iter()
+                .map(|name| Column {
+                    flags: 0,
+                    name: (*name).into(),
+                    type_id: 25,
+                    type_mod: -1,
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reapply_sink_schema() {
+        let sink = RecordingSink::default();
+        let log = sink.log.clone();
+        let (_tx, rx) = mpsc::channel(1);
+        let mut pipeline = Pipeline::new(rx, Box::new(sink), 100, Duration::from_secs(1))
+            .with_relations(vec![
+                relation(1, "orders", &["id", "total", "note"]),
+                relation(2, "customers", &["id"]),
+            ]);
+
+        let done = pipeline.reapply_sink_schema(Some("orders")).await.unwrap();
+        assert_eq!(done, vec!["public.orders"]);
+        assert_eq!(
+            *log.lock(),
+            vec!["invalidate public.orders", "apply public.orders 3"]
+        );
+
+        log.lock().clear();
+        let done = pipeline.reapply_sink_schema(None).await.unwrap();
+        assert_eq!(done, vec!["public.orders", "public.customers"]);
+        assert_eq!(
+            *log.lock(),
+            vec![
+                "invalidate *",
+                "apply public.orders 3",
+                "apply public.customers 1"
+            ]
+        );
+
+        let err = pipeline.reapply_sink_schema(Some("invoices")).await;
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("no changes since startup"));
+    }
+}
@@ -0,0 +1,445 @@
+//! Column-level lineage (`LINEAGE_EXPORT_PATH`).
+//!
+//! For every replicated table, maps each source column to the sink column it
+//! lands in, with the conversions applied on the way. The map is written as
+//! JSON during setup and rewritten for a table whenever a schema change for it
+//! reaches the sink. With `LINEAGE_CATALOG_URL` set, every written document is
+//! also POSTed there for a data catalog to ingest.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config::{
+    BoolEncoding, ColumnTransforms, Config, FloatFormat, GeometryFormat, SinkType, TableNaming,
+    TextNormalization,
+};
+use crate::connectors::sinks::clickhouse::types::pg_type_to_clickhouse;
+#[cfg(feature = "delta")]
+use crate::connectors::sinks::delta::types as delta_types;
+#[cfg(feature = "iceberg")]
+use crate::connectors::sinks::iceberg::types::IcebergType;
+#[cfg(feature = "object-store")]
+use crate::connectors::sinks::objectstore;
+#[cfg(feature = "sqlite")]
+use crate::connectors::sinks::sqlite::types::pg_type_name_to_affinity;
+use crate::connectors::sinks::starrocks::types::TypeMapper;
+use crate::engine::setup::catalog::SourceTable;
+use crate::engine::setup::starrocks::KEY_VARCHAR;
+#[cfg(feature = "object-store")]
+use crate::sink::adapter::pg_type_to_data_type;
+use crate::sink::adapter::pg_type_value_variant;
+use crate::source::pg_types::PgType;
+
+/// The lineage file: every replicated table, sorted by source name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LineageDocument {
+    pub sink_type: String,
+    /// RFC 3339 time of the last change to the document
+    pub updated_at: String,
+    pub tables: Vec<TableLineage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TableLineage {
+    /// `schema.table` in PostgreSQL
+    pub source_table: String,
+    /// Destination table, `database.table` when it isn't in `SINK_DATABASE`
+    pub sink_table: String,
+    pub columns: Vec<ColumnLineage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ColumnLineage {
+    pub source_column: String,
+    /// `information_schema` type name
+    pub source_type: String,
+    /// Conversions applied to the value, in order
+    pub transforms: Vec<String>,
+    pub sink_column: String,
+    /// Column type in the sink (remote sink: `Value` variant)
+    pub sink_type: String,
+}
+
+/// Builds and writes the lineage file for the configured sink.
+#[derive(Debug, Clone)]
+pub struct LineageExport {
+    path: String,
+    catalog_url: Option<String>,
+    sink_type: SinkType,
+    naming: TableNaming,
+    sink_key_columns: HashMap<String, Vec<String>>,
+    bool_encoding: BoolEncoding,
+    float_format: FloatFormat,
+    geometry_format: GeometryFormat,
+    text_normalization: TextNormalization,
+    column_transforms: ColumnTransforms,
+}
+
+impl LineageExport {
+    /// `None` unless `LINEAGE_EXPORT_PATH` is set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let path = config.lineage_export_path.clone()?;
+        Some(Self {
+            path,
+            catalog_url: config.lineage_catalog_url.clone(),
+            sink_type: config.sink.sink_type.clone(),
+            naming: config.sink.naming.clone(),
+            sink_key_columns: config.sink_key_columns.clone(),
+            bool_encoding: config.sink.bool_encoding,
+            float_format: config.sink.float_format,
+            geometry_format: config.sink.geometry_format,
+            text_normalization: config.text_normalization,
+            column_transforms: config.column_transforms.clone(),
+        })
+    }
+
+    /// Lineage of one table as it is replicated to the configured sink.
+    pub fn table_lineage(&self, table: &SourceTable) -> TableLineage {
+        // An invalid key override already fails setup; here it just means no key
+        let key = table
+            .sink_key(&self.sink_key_columns)
+            .ok()
+            .flatten()
+            .map(|k| k.columns)
+            .unwrap_or_default();
+        let sink_table = sink_table_name(&self.sink_type, &self.naming, &table.qualified_name());
+        let masks = self.column_transforms.for_table(&table.qualified_name());
+
+        let columns = table
+            .columns
+            .iter()
+            .map(|c| ColumnLineage {
+                source_column: format!("{}.{}", table.qualified_name(), c.name),
+                source_type: c.data_type.clone(),
+                // COLUMN_TRANSFORMS masks come first, then the conversions
+                transforms: masks
+                    .and_then(|m| m.get(&c.name))
+                    .map(ToString::to_string)
+                    .into_iter()
+                    .chain(
+                        transforms(
+                            &self.sink_type,
+                            self.bool_encoding,
+                            self.float_format,
+                            self.geometry_format,
+                            self.text_normalization,
+                            c.type_oid,
+                        )
+                        .into_iter()
+                        .map(String::from),
+                    )
+                    .collect(),
+                sink_column: format!("{}.{}", sink_table, c.name),
+                sink_type: sink_column_type(
+                    &self.sink_type,
+                    c.type_oid,
+                    &c.data_type,
+                    key.contains(&c.name),
+                    self.bool_encoding,
+                    self.float_format,
+                    self.geometry_format,
+                ),
+            })
+            .collect();
+
+        TableLineage {
+            source_table: table.qualified_name(),
+            sink_table,
+            columns,
+        }
+    }
+
+    /// Writes the lineage of all replicated tables, replacing the file.
+    pub async fn write(&self, tables: &[SourceTable]) -> Result<()> {
+        let mut document = self.empty_document();
+        document.tables = tables.iter().map(|t| self.table_lineage(t)).collect();
+        document
+            .tables
+            .sort_by(|a, b| a.source_table.cmp(&b.source_table));
+        self.save(&mut document).await?;
+        info!(
+            "  [OK] Column lineage of {} tables exported to {}",
+            document.tables.len(),
+            self.path
+        );
+        Ok(())
+    }
+
+    /// Replaces one table's entry after its schema changed. An unreadable
+    /// file is started over with just this table.
+    pub async fn update(&self, table: &SourceTable) -> Result<()> {
+        let mut document = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!(
+                    "[LINEAGE] {} is not a lineage document, rewriting it: {}",
+                    self.path, e
+                );
+                self.empty_document()
+            }),
+            Err(_) => self.empty_document(),
+        };
+        let lineage = self.table_lineage(table);
+        document
+            .tables
+            .retain(|t| t.source_table != lineage.source_table);
+        document.tables.push(lineage);
+        document
+            .tables
+            .sort_by(|a, b| a.source_table.cmp(&b.source_table));
+        self.save(&mut document).await?;
+        info!(
+            "[LINEAGE] Column lineage of {} updated in {}",
+            table.qualified_name(),
+            self.path
+        );
+        Ok(())
+    }
+
+    fn empty_document(&self) -> LineageDocument {
+        LineageDocument {
+            sink_type: self.sink_type.to_string(),
+            updated_at: String::new(),
+            tables: Vec::new(),
+        }
+    }
+
+    async fn save(&self, document: &mut LineageDocument) -> Result<()> {
+        document.updated_at = chrono::Utc::now().to_rfc3339();
+        let json = serde_json::to_vec_pretty(document)?;
+        tokio::fs::write(&self.path, &json)
+            .await
+            .with_context(|| format!("Failed to write {}", self.path))?;
+
+        // The file is the source of truth; the catalog push is best effort
+        if let Some(url) = &self.catalog_url {
+            if let Err(e) = push_to_catalog(url, json).await {
+                warn!("[LINEAGE] Failed to send lineage to {}: {}", url, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn push_to_catalog(url: &str, json: Vec<u8>) -> Result<()> {
+    reqwest::Client::new()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(json)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Conversions the sink adapter and the sink apply to a column's text value
+/// before it is stored.
+fn transforms(
+    sink_type: &SinkType,
+    bool_encoding: BoolEncoding,
+    float_format: FloatFormat,
+    geometry_format: GeometryFormat,
+    text_normalization: TextNormalization,
+    type_oid: u32,
+) -> Vec<&'static str> {
+    let mut applied = Vec::new();
+    if text_normalization.trims(type_oid) {
+        applied.push("trim_bpchar_padding");
+    }
+    if text_normalization.normalizes(type_oid) {
+        applied.push("normalize_nfc");
+    }
+    match type_oid {
+        790 => applied.push("strip_currency_symbol"),
+        1184 => applied.push("convert_to_utc_without_offset"),
+        oid if crate::source::pg_types::is_array(oid) => applied.push("pg_array_to_json"),
+        oid if crate::source::pg_types::is_composite(oid) => applied.push("pg_record_to_json"),
+        oid if crate::source::geometry::is_geometric(oid) => applied.push(match geometry_format {
+            GeometryFormat::Wkt => "geometry_to_wkt",
+            GeometryFormat::GeoJson => "geometry_to_geojson",
+        }),
+        700 | 701 if float_format.is_custom() => applied.push("format_float"),
+        oid => match crate::source::pg_types::get(oid) {
+            Some(PgType::Hstore) => applied.push("hstore_to_json"),
+            Some(PgType::Range { .. }) => applied.push("range_to_json"),
+            // Comparisons are case-sensitive in the sink
+            Some(PgType::Citext) => applied.push("citext_to_text"),
+            Some(PgType::Ltree) => applied.push("ltree_to_text"),
+            _ => {}
+        },
+    }
+    if type_oid == 16 {
+        match (sink_type, bool_encoding) {
+            (SinkType::Sqlite, _) | (_, BoolEncoding::Int) => applied.push("bool_to_integer"),
+            (_, BoolEncoding::Char) => applied.push("bool_to_char"),
+            _ => {}
+        }
+    }
+    applied
+}
+
+/// Name of a source table in the sink, as shown in logs and `GetStatus`.
+pub(crate) fn sink_table_name(
+    sink_type: &SinkType,
+    naming: &TableNaming,
+    qualified_name: &str,
+) -> String {
+    match sink_type {
+        // Remote sinks receive the source schema and table unchanged
+        SinkType::Remote => qualified_name.to_string(),
+        _ => naming.sink_table(qualified_name).label(),
+    }
+}
+
+/// Sink type of a replicated column, as setup creates it or the remote sink
+/// encodes it.
+pub(crate) fn sink_column_type(
+    sink_type: &SinkType,
+    type_oid: u32,
+    data_type: &str,
+    key: bool,
+    bool_encoding: BoolEncoding,
+    float_format: FloatFormat,
+    geometry_format: GeometryFormat,
+) -> String {
+    match sink_type {
+        SinkType::StarRocks => match TypeMapper::new()
+            .with_bool_encoding(bool_encoding)
+            .pg_type_to_starrocks(type_oid)
+        {
+            "STRING" if key => KEY_VARCHAR.to_string(),
+            t => t.to_string(),
+        },
+        #[cfg(feature = "sqlite")]
+        SinkType::Sqlite => pg_type_name_to_affinity(data_type).to_string(),
+        SinkType::ClickHouse => pg_type_to_clickhouse(type_oid, bool_encoding).to_string(),
+        #[cfg(feature = "iceberg")]
+        SinkType::Iceberg => IcebergType::for_pg_type(type_oid).name(),
+        #[cfg(feature = "delta")]
+        SinkType::Delta => delta_types::type_name(delta_types::column_type(type_oid)),
+        #[cfg(feature = "object-store")]
+        SinkType::ObjectStore => {
+            objectstore::column_type(&pg_type_to_data_type(type_oid, -1)).name()
+        }
+        SinkType::Remote => {
+            pg_type_value_variant(type_oid, bool_encoding, float_format, geometry_format)
+                .to_string()
+        }
+        // Sinks left out of the build never get this far; keep the source type
+        #[allow(unreachable_patterns)]
+        _ => data_type.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SchemaMode;
+    use crate::engine::setup::catalog::SourceColumn;
+
+    fn column(name: &str, data_type: &str, type_oid: u32) -> SourceColumn {
+        SourceColumn {
+            name: name.into(),
+            data_type: data_type.into(),
+            type_oid,
+            nullable: false,
+            comment: None,
+        }
+    }
+
+    fn table(name: &str, columns: Vec<SourceColumn>) -> SourceTable {
+        SourceTable {
+            schema: "sales".into(),
+            name: name.into(),
+            comment: None,
+            primary_key: vec!["code".into()],
+            columns,
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+        }
+    }
+
+    fn export(path: &str, sink_type: SinkType) -> LineageExport {
+        LineageExport {
+            path: path.to_string(),
+            catalog_url: None,
+            sink_type,
+            naming: SchemaMode::Flatten.into(),
+            sink_key_columns: HashMap::new(),
+            bool_encoding: BoolEncoding::Bool,
+            float_format: FloatFormat::default(),
+            geometry_format: GeometryFormat::default(),
+            text_normalization: TextNormalization::default(),
+            column_transforms: ColumnTransforms::default(),
+        }
+    }
+
+    #[test]
+    fn test_table_lineage() {
+        let orders = table(
+            "orders",
+            vec![
+                column("code", "text", 25),
+                column("price", "money", 790),
+                column("paid_at", "timestamp with time zone", 1184),
+            ],
+        );
+        let lineage = export("unused", SinkType::StarRocks).table_lineage(&orders);
+        assert_eq!(lineage.sink_table, "sales__orders");
+
+        let code = &lineage.columns[0];
+        assert_eq!(code.source_column, "sales.orders.code");
+        assert_eq!(code.sink_column, "sales__orders.code");
+        assert_eq!(code.sink_type, KEY_VARCHAR);
+        assert!(code.transforms.is_empty());
+
+        assert_eq!(lineage.columns[1].transforms, ["strip_currency_symbol"]);
+        assert_eq!(
+            lineage.columns[2].transforms,
+            ["convert_to_utc_without_offset"]
+        );
+
+        let remote = export("unused", SinkType::Remote).table_lineage(&orders);
+        assert_eq!(remote.sink_table, "sales.orders");
+        assert_eq!(remote.columns[1].sink_type, "Decimal");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "sqlite")]
+    async fn test_update_replaces_one_table() {
+        let path = std::env::temp_dir().join(format!("dbmazz-lineage-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let export = export(path, SinkType::Sqlite);
+
+        let orders = table("orders", vec![column("code", "text", 25)]);
+        let users = table("users", vec![column("code", "text", 25)]);
+        export.write(&[users.clone(), orders]).await.unwrap();
+
+        let orders = table(
+            "orders",
+            vec![column("code", "text", 25), column("paid", "boolean", 16)],
+        );
+        export.update(&orders).await.unwrap();
+
+        let document: LineageDocument =
+            serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(document.sink_type, "sqlite");
+        assert!(!document.updated_at.is_empty());
+        let tables: Vec<&str> = document
+            .tables
+            .iter()
+            .map(|t| t.source_table.as_str())
+            .collect();
+        assert_eq!(tables, ["sales.orders", "sales.users"]);
+        assert_eq!(document.tables[0].columns.len(), 2);
+        assert_eq!(document.tables[0].columns[1].sink_type, "INTEGER");
+        assert_eq!(
+            document.tables[0].columns[1].transforms,
+            ["bool_to_integer"]
+        );
+        assert_eq!(document.tables[1], export.table_lineage(&users));
+    }
+}
@@ -1,23 +1,112 @@
+pub mod archive;
+pub mod commit_batching;
+pub mod dead_letter;
+pub mod freshness;
+pub mod invalidate;
+pub mod lineage;
+pub mod paused;
+pub mod peek;
+pub mod retry;
 pub mod schema_cache;
+pub mod source_catalog;
+pub mod spill;
+pub mod suppress;
+pub mod tail;
+pub mod toast;
+pub mod transform;
 
-use crate::grpc::state::SharedState;
-use crate::pipeline::schema_cache::SchemaCache;
-use crate::sink::Sink;
+use crate::config::{
+    ChangeSuppression, ColumnTransforms, PausedTableProbeConfig, TableFilter, ToastHydration,
+};
+use crate::core::dead_letter::{is_rejected, DeadLetter};
+use crate::core::sink_error::{is_retryable, is_schema_mismatch};
+use crate::grpc::control_error::{ControlError, ErrorCode};
+use crate::grpc::state::{CdcState, CircuitState, ErrorCategory, SharedState};
+use crate::pipeline::archive::WalArchive;
+use crate::pipeline::commit_batching::CommitBoundaries;
+use crate::pipeline::dead_letter::{dead_letter, DeadLetterQueue};
+use crate::pipeline::freshness::{commit_age_ms, probe_sent_ms, FreshnessTracker};
+use crate::pipeline::invalidate::InvalidateRequest;
+use crate::pipeline::lineage::LineageExport;
+use crate::pipeline::paused::{
+    HeldRows, PauseNotifier, ProbeSchedule, TableControlRequest, TablePauseMode, MAX_HELD_ROWS,
+};
+use crate::pipeline::peek::PeekRequest;
+use crate::pipeline::retry::RetryPolicy;
+use crate::pipeline::schema_cache::{RelationVersion, SchemaCache, SchemaHistory, TableSchema};
+use crate::pipeline::source_catalog::SourceCatalog;
+use crate::pipeline::suppress::ChangeSuppressor;
+use crate::pipeline::toast::ToastHydrator;
+use crate::pipeline::transform::ColumnTransformer;
+use crate::sink::{BatchResult, Sink};
+use crate::source::clock;
 use crate::source::parser::{CdcEvent, CdcMessage};
+use crate::source::session::PgSession;
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
 use tracing::{error, info, warn};
 
 pub struct Pipeline {
     rx: mpsc::Receiver<CdcEvent>,
     schema_cache: SchemaCache,
+    /// Relation versions stored with the checkpoint
+    history: SchemaHistory,
     sink: Box<dyn Sink + Send>,
     batch_size: usize,
     batch_timeout: Duration,
     feedback_tx: Option<mpsc::Sender<u64>>,
     shared_state: Option<Arc<SharedState>>,
     last_commit_timestamp_us: u64,
+    archive: Option<WalArchive>,
+    dead_letters: Option<DeadLetterQueue>,
+    retry: RetryPolicy,
+    circuit: CircuitState,
+    catalog: Option<SourceCatalog>,
+    freshness: FreshnessTracker,
+    /// Table of the read-your-writes probe (`schema.table`)
+    probe_table: Option<String>,
+    lineage: Option<LineageExport>,
+    peek_rx: Option<mpsc::Receiver<PeekRequest>>,
+    invalidate_rx: Option<mpsc::Receiver<InvalidateRequest>>,
+    table_control_rx: Option<mpsc::Receiver<TableControlRequest>>,
+    /// Tables removed from the publication, by the LSN to stop routing them at
+    retiring: Vec<(String, u64)>,
+    /// Relations of removed tables; their changes are dropped until a new
+    /// Relation message brings them back
+    removed_relations: HashSet<u32>,
+    /// Tables whose changes are replicated (`TABLE_INCLUDE`, `TABLE_EXCLUDE`)
+    table_filter: TableFilter,
+    /// Relations of tables the filter leaves out; their changes are dropped
+    excluded_relations: HashSet<u32>,
+    /// Drops and masks columns before the sink (`COLUMN_TRANSFORMS`)
+    transformer: ColumnTransformer,
+    /// Drops updates that only touch ignored columns (`SUPPRESS_UNCHANGED`)
+    suppressor: ChangeSuppressor,
+    /// Fills in unchanged TOAST values (`TOAST_HYDRATION`)
+    toast: ToastHydrator,
+    /// Rows of relations paused on a sink schema mismatch or quarantined
+    paused: HashMap<u32, HeldRows>,
+    /// Last LSN not confirmed because a table is paused
+    withheld_lsn: Option<u64>,
+    /// When the paused relations are probed (`PAUSED_TABLE_PROBE_SECS`)
+    probes: Option<ProbeSchedule>,
+    /// Posts pauses and resumes (`PAUSED_TABLE_WEBHOOK_URL`)
+    pause_notifier: Option<PauseNotifier>,
+    /// Tables paused with `PauseTable`, by `schema.table`
+    table_pauses: HashMap<String, TablePauseMode>,
+    /// The same, by relation id once their Relation message was seen
+    paused_relations: HashMap<u32, TablePauseMode>,
+    /// Cuts batches on Commit messages only (`FLUSH_ON_COMMIT`)
+    commits: Option<CommitBoundaries>,
+    /// LSN of the last batch written
+    flushed_lsn: u64,
+    /// End LSN of the batch a crash interrupted (`EXACTLY_ONCE`); no batch
+    /// is flushed until it can be cut there again, so it keeps its ID
+    replay_end: Option<u64>,
 }
 
 impl Pipeline {
@@ -30,12 +119,40 @@ impl Pipeline {
         Self {
             rx,
             schema_cache: SchemaCache::new(),
+            history: SchemaHistory::default(),
             sink,
             batch_size,
             batch_timeout,
             feedback_tx: None,
             shared_state: None,
             last_commit_timestamp_us: 0,
+            archive: None,
+            dead_letters: None,
+            retry: RetryPolicy::once(),
+            circuit: CircuitState::Closed,
+            catalog: None,
+            freshness: FreshnessTracker::new(HashMap::new()),
+            probe_table: None,
+            lineage: None,
+            peek_rx: None,
+            invalidate_rx: None,
+            table_control_rx: None,
+            retiring: Vec::new(),
+            removed_relations: HashSet::new(),
+            table_filter: TableFilter::default(),
+            excluded_relations: HashSet::new(),
+            transformer: ColumnTransformer::default(),
+            suppressor: ChangeSuppressor::default(),
+            toast: ToastHydrator::default(),
+            paused: HashMap::new(),
+            withheld_lsn: None,
+            probes: None,
+            pause_notifier: None,
+            table_pauses: HashMap::new(),
+            paused_relations: HashMap::new(),
+            commits: None,
+            flushed_lsn: 0,
+            replay_end: None,
         }
     }
 
@@ -45,31 +162,194 @@ impl Pipeline {
         self
     }
 
-    /// Configure the shared state for metrics
+    /// Configure the shared state for metrics, `PeekBatch`,
+    /// `InvalidateSinkSchema`, `PauseTable` and `ResumeTable`
     pub fn with_shared_state(mut self, shared_state: Arc<SharedState>) -> Self {
+        let (peek_tx, peek_rx) = mpsc::channel(4);
+        shared_state.set_peek_sender(peek_tx);
+        self.peek_rx = Some(peek_rx);
+        let (invalidate_tx, invalidate_rx) = mpsc::channel(4);
+        shared_state.set_invalidate_sender(invalidate_tx);
+        self.invalidate_rx = Some(invalidate_rx);
+        let (table_control_tx, table_control_rx) = mpsc::channel(4);
+        shared_state.set_table_control_sender(table_control_tx);
+        self.table_control_rx = Some(table_control_rx);
         self.shared_state = Some(shared_state);
         self
     }
 
+    /// Probe tables paused on a schema mismatch, and quarantine tables whose
+    /// writes fail; `None` to leave mismatches to `InvalidateSinkSchema` and
+    /// stop on failed writes
+    pub fn with_paused_table_probes(mut self, config: Option<PausedTableProbeConfig>) -> Self {
+        self.probes = config.map(ProbeSchedule::new);
+        self
+    }
+
+    /// Post the pausing and resuming of tables paused on a schema mismatch
+    /// or quarantined
+    pub fn with_pause_notifier(mut self, notifier: Option<PauseNotifier>) -> Self {
+        self.pause_notifier = notifier;
+        self
+    }
+
+    /// Configure the archive used once the WAL retention guard enables archive-tee mode
+    pub fn with_archive(mut self, archive: WalArchive) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// Configure where rows rejected by the sink are parked. Without one, a
+    /// rejected batch stops the pipeline like any other sink failure.
+    pub fn with_dead_letter_queue(mut self, queue: Option<DeadLetterQueue>) -> Self {
+        self.dead_letters = queue;
+        self
+    }
+
+    /// Configure how failed batch writes are retried. Without a policy, the
+    /// first failure stops the pipeline.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Configure the source catalog used to add nullability and comments to
+    /// schema deltas
+    pub fn with_source_catalog(mut self, catalog: SourceCatalog) -> Self {
+        self.catalog = Some(catalog);
+        self
+    }
+
+    /// Configure the column lineage file rewritten when a table's schema
+    /// changes. Needs the source catalog.
+    pub fn with_lineage(mut self, lineage: Option<LineageExport>) -> Self {
+        self.lineage = lineage;
+        self
+    }
+
+    /// Configure the per-table freshness SLAs checked after each batch
+    pub fn with_freshness_sla(mut self, slas: HashMap<String, Duration>) -> Self {
+        self.freshness = FreshnessTracker::new(slas);
+        self
+    }
+
+    /// Configure the table of the read-your-writes probe, whose rows are
+    /// reported to the shared state once they reach the sink
+    pub fn with_probe_table(mut self, table: Option<String>) -> Self {
+        self.probe_table = table;
+        self
+    }
+
+    /// Configure which tables' changes reach the sink. The publication may
+    /// hold more tables than are replicated, e.g. when it is shared.
+    pub fn with_table_filter(mut self, table_filter: TableFilter) -> Self {
+        self.table_filter = table_filter;
+        self
+    }
+
+    /// Configure transaction-boundary batching: batches end on a Commit,
+    /// unless a transaction grows past `max_transaction_events`
+    pub fn with_commit_boundaries(mut self, max_transaction_events: Option<usize>) -> Self {
+        self.commits = max_transaction_events.map(CommitBoundaries::new);
+        self
+    }
+
+    /// Configure the end LSN of the batch the sink was writing when the last
+    /// run stopped, to cut the first batch at (`EXACTLY_ONCE`)
+    pub fn with_replay_end(mut self, replay_end: Option<u64>) -> Self {
+        self.replay_end = replay_end;
+        self
+    }
+
+    /// Configure the columns dropped or masked before they reach the sink.
+    /// Set before `with_relations`, which applies them.
+    pub fn with_column_transforms(mut self, transforms: ColumnTransforms) -> Self {
+        self.transformer = ColumnTransformer::new(transforms);
+        self
+    }
+
+    /// Configure the tables whose updates are dropped when only ignored
+    /// columns changed. Set before `with_relations`.
+    pub fn with_change_suppression(mut self, suppression: ChangeSuppression) -> Self {
+        self.suppressor = ChangeSuppressor::new(suppression);
+        self
+    }
+
+    /// Configure how unchanged TOAST values are filled in, reading them back
+    /// from `database_url` over `session` with `fetch`. Set before
+    /// `with_relations`.
+    pub fn with_toast_hydration(
+        mut self,
+        mode: ToastHydration,
+        session: &PgSession,
+        database_url: &str,
+    ) -> Self {
+        self.toast = ToastHydrator::new(mode, session.clone(), database_url);
+        self
+    }
+
+    /// Prime the schema cache with Relation messages handed over by the
+    /// active instance or stored with the checkpoint, so changes for those
+    /// tables decode before PostgreSQL resends their Relation message
+    pub fn with_relations(mut self, relations: Vec<RelationVersion>) -> Self {
+        for version in relations {
+            let mut relation = version.relation.clone();
+            self.history.restore(version);
+            // Checked when PostgreSQL sends it again
+            let _ = self.toast.relation(&relation);
+            self.transformer.apply(&mut relation);
+            self.suppressor.suppresses(&relation);
+            self.schema_cache.update(&relation);
+        }
+        self
+    }
+
+    /// Relation messages of the schema cache as PostgreSQL sent them, for the
+    /// standby handover and the checkpoint
+    fn relation_messages(&self) -> Vec<CdcMessage> {
+        self.transformer
+            .original_relations(self.schema_cache.relation_messages())
+    }
+
+    /// Publishes the schema cache for the standby handover, and its versions
+    /// since the checkpoint to be stored with it
+    async fn publish_schema(&mut self) {
+        let Some(state) = self.shared_state.clone() else {
+            return;
+        };
+        self.history.prune(state.confirmed_lsn());
+        state.set_relation_versions(self.history.versions()).await;
+        state.publish_relations(self.relation_messages()).await;
+    }
+
+    /// True once the WAL retention guard has diverted batches to the archive
+    fn archive_tee_active(&self) -> bool {
+        self.archive.is_some()
+            && self
+                .shared_state
+                .as_ref()
+                .is_some_and(|state| state.is_archive_tee_active())
+    }
+
     pub async fn run(mut self) {
+        self.publish_schema().await;
+
         let mut batch = Vec::with_capacity(self.batch_size);
         let mut interval = tokio::time::interval(self.batch_timeout);
         let mut last_lsn: u64 = 0;
+        let mut state_rx = self
+            .shared_state
+            .as_ref()
+            .map(|state| state.subscribe_state());
 
         loop {
-            // Check if paused before processing
-            if let Some(ref state) = self.shared_state {
-                let current_state = state.state();
-                if current_state == crate::grpc::state::CdcState::Paused {
-                    // Flush pending batch before pausing
-                    if !batch.is_empty() {
-                        if !self.flush_batch(&batch, last_lsn).await {
-                            break; // Stop on flush failure
-                        }
-                        batch.clear();
+            if let Some(rx) = state_rx.as_mut() {
+                if *rx.borrow_and_update() == CdcState::Paused {
+                    if !self.wait_while_paused(&mut batch, last_lsn, rx).await {
+                        break; // Stop on flush failure
                     }
-                    // Sleep while paused
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                    // Ticks missed while paused would all fire at once
+                    interval.reset();
                     continue;
                 }
             }
@@ -77,34 +357,181 @@ impl Pipeline {
             tokio::select! {
                 event_option = self.rx.recv() => {
                     match event_option {
-                        Some(event) => {
+                        Some(mut event) => {
+                            if !self.retire_tables(&mut batch, event.lsn, last_lsn).await {
+                                break; // Stop on flush failure
+                            }
+                            match &event.message {
+                                CdcMessage::Relation { id, namespace, name, columns, .. } => {
+                                    if !self.table_filter.allows(&format!("{}.{}", namespace, name)) {
+                                        self.excluded_relations.insert(*id);
+                                        continue;
+                                    }
+                                    if let Some(catalog) = self.catalog.as_mut() {
+                                        catalog.resolve_types(columns.iter().map(|c| c.type_id)).await;
+                                    }
+                                    self.history.record(&event.message, event.lsn);
+                                    if let Err(e) = self.toast.relation(&event.message) {
+                                        self.stop_on_toast_error(e, &format!("{}.{}", namespace, name), event.lsn).await;
+                                        break;
+                                    }
+                                    self.excluded_relations.remove(id);
+                                    // The table was added back to the publication
+                                    self.removed_relations.remove(id);
+                                    match self.table_pauses.get(&format!("{}.{}", namespace, name)) {
+                                        Some(mode) => self.paused_relations.insert(*id, *mode),
+                                        None => self.paused_relations.remove(id),
+                                    };
+                                }
+                                msg => {
+                                    if row_relation(msg).is_some_and(|id| {
+                                        self.removed_relations.contains(&id)
+                                            || self.excluded_relations.contains(&id)
+                                    }) {
+                                        continue;
+                                    }
+                                }
+                            }
+
+                            // Unchanged TOAST values are filled in before masks apply
+                            if !self.hydrate_toast(&mut event.message, event.lsn).await {
+                                break; // Stop: TOAST_HYDRATION=strict
+                            }
+
+                            // Dropped columns never reach the schema cache, so
+                            // the sink doesn't learn them; masked ones arrive as text
+                            self.transformer.apply(&mut event.message);
+
+                            // Heartbeat-style updates that only touch ignored columns
+                            if self.suppressor.suppresses(&event.message) {
+                                last_lsn = event.lsn;
+                                if let Some(ref state) = self.shared_state {
+                                    state.record_suppressed_update();
+                                }
+                                continue;
+                            }
+
+                            // Rows of a table paused with PauseTable are held or dropped
+                            if let Some((relation_id, mode)) = row_relation(&event.message)
+                                .and_then(|id| self.paused_relations.get(&id).map(|mode| (id, *mode)))
+                            {
+                                last_lsn = event.lsn;
+                                match mode {
+                                    TablePauseMode::Buffer => {
+                                        if !self.hold(relation_id, event.message, event.lsn).await {
+                                            break; // Stop: too many rows held
+                                        }
+                                    }
+                                    TablePauseMode::Skip => self.skip(relation_id, event.lsn),
+                                }
+                                continue;
+                            }
+
+                            // Rows of a paused table wait for its sink table to be fixed
+                            if let Some(relation_id) = row_relation(&event.message)
+                                .filter(|id| self.paused.contains_key(id))
+                            {
+                                last_lsn = event.lsn;
+                                if !self.hold(relation_id, event.message, event.lsn).await {
+                                    break; // Stop: too many rows held
+                                }
+                                continue;
+                            }
+
+                            // Batched rows of a table were decoded against its old
+                            // columns; send them before its new schema takes effect
+                            if let Some(relation_id) = self.schema_cache.changed_relation(&event.message) {
+                                if self.paused.contains_key(&relation_id)
+                                    && !self.resume_before_schema_change(relation_id, event.lsn).await
+                                {
+                                    break; // Stop: held rows can't be written
+                                }
+                                let held = self.sink.held_from().is_some();
+                                if has_rows_of(&batch, relation_id) || held {
+                                    // Rows a sink holds back go out too
+                                    if held {
+                                        self.sink.release_held();
+                                    }
+                                    info!("[SCHEMA] Flushing {} pending events before the schema change of {}",
+                                        batch.len(), self.schema_cache.get_table_name(relation_id).unwrap_or_default());
+                                    if !self.flush_batch(&batch, last_lsn).await {
+                                        break; // Stop on flush failure
+                                    }
+                                    if let Some(commits) = self.commits.as_mut() {
+                                        commits.flushed(batch.len());
+                                    }
+                                    batch.clear();
+                                    if let Some(ref state) = self.shared_state {
+                                        state.set_pending(0);
+                                    }
+                                }
+                            }
+
                             last_lsn = event.lsn; // Update LSN
 
                             // Detect schema changes
-                            if let Some(delta) = self.schema_cache.update(&event.message) {
-                                info!("[SCHEMA] Schema change detected for table {}: {} new columns",
-                                    delta.table_name, delta.added_columns.len());
-                                if let Err(e) = self.sink.apply_schema_delta(&delta).await {
-                                    error!("Schema evolution failed: {}", e);
-                                    // Continue processing - do not stop the pipeline due to DDL errors
+                            if let Some(mut delta) = self.schema_cache.update(&event.message) {
+                                info!("[SCHEMA] Schema change detected for table {}: {} columns",
+                                    delta.table_name, delta.summary());
+                                if !delta.unmatched_columns.is_empty() {
+                                    let names: Vec<&str> = delta.unmatched_columns.iter().map(|c| c.name.as_str()).collect();
+                                    warn!("[SCHEMA] {}: columns {:?} were dropped or renamed in one step, which can't be told apart; \
+                                        the sink keeps them, rename or drop them there by hand",
+                                        delta.qualified_name(), names);
                                 }
+                                if self.archive_tee_active() {
+                                    // The sink is bypassed; the Relation message is archived
+                                    warn!("[SCHEMA] Archive-tee active, not applying schema change to sink");
+                                } else {
+                                    let mut described = None;
+                                    if let Some(catalog) = self.catalog.as_mut() {
+                                        described = catalog.describe(&mut delta).await;
+                                    }
+                                    let applied = self.sink.apply_schema_delta(&delta).await;
+                                    if let (Ok(()), Some(lineage), Some(table)) =
+                                        (&applied, &self.lineage, &described)
+                                    {
+                                        if let Err(e) = lineage.update(table).await {
+                                            warn!("[LINEAGE] Failed to update column lineage of {}: {}",
+                                                delta.qualified_name(), e);
+                                        }
+                                    }
+                                    if let Err(e) = applied {
+                                        error!("Schema evolution failed: {}", e);
+                                        if let Some(ref state) = self.shared_state {
+                                            state.record_error(
+                                                ErrorCategory::Schema,
+                                                format!("Schema evolution failed: {}", e),
+                                                Some(&delta.table_name),
+                                                event.lsn,
+                                            ).await;
+                                        }
+                                        // Continue processing - do not stop the pipeline due to DDL errors
+                                    }
+                                }
+                            }
+                            if matches!(event.message, CdcMessage::Relation { .. }) {
+                                // Keep the standby handover copy of the schema cache current
+                                self.publish_schema().await;
                             }
 
                             // Track the latest commit timestamp for lag calculation
                             if let CdcMessage::Commit { timestamp, .. } = &event.message {
                                 self.last_commit_timestamp_us = *timestamp;
+                                clock::check_commit_timestamp(*timestamp);
                             }
 
                             batch.push(event.message);
+                            if let (Some(commits), Some(msg)) = (self.commits.as_mut(), batch.last()) {
+                                commits.push(msg, batch.len(), last_lsn);
+                            }
 
-                            if batch.len() >= self.batch_size {
-                                if !self.flush_batch(&batch, last_lsn).await {
-                                    break; // Stop on flush failure
-                                }
-                                batch.clear();
-                                if let Some(ref state) = self.shared_state {
-                                    state.set_pending(0);
-                                }
+                            if (batch.len() >= self.batch_size
+                                || self.replay_end == Some(last_lsn)
+                                || self.commits.as_ref().is_some_and(|c| c.spills()))
+                                && !self.flush_ready(&mut batch, last_lsn).await
+                            {
+                                break; // Stop on flush failure
                             }
                         }
                         None => {
@@ -114,35 +541,226 @@ impl Pipeline {
                         }
                     }
                 }
+                Some(request) = next_request(&mut self.peek_rx) => {
+                    request.answer(&batch, &self.schema_cache, false, last_lsn);
+                }
+                Some(request) = next_request(&mut self.invalidate_rx) => {
+                    self.invalidate_sink_schema(request).await;
+                }
+                Some(request) = next_request(&mut self.table_control_rx) => {
+                    self.control_table(request).await;
+                }
+                true = pause_requested(&mut state_rx) => {
+                    // Paused at the top of the loop
+                }
                 _ = interval.tick() => {
-                    if !batch.is_empty() {
-                        if !self.flush_batch(&batch, last_lsn).await {
-                            break; // Stop on flush failure
-                        }
-                        batch.clear();
-                        if let Some(ref state) = self.shared_state {
-                            state.set_pending(0);
-                        }
+                    let idle = batch.is_empty();
+                    if !self.flush_ready(&mut batch, last_lsn).await {
+                        break; // Stop on flush failure
+                    }
+                    // Rows a sink holds back may fall due with nothing new batched
+                    if idle && !self.flush_held().await {
+                        break; // Stop on flush failure
                     }
+                    self.sweep_dead_letters();
+                    self.probe_paused_tables(Instant::now()).await;
                 }
             }
         }
 
-        // Graceful shutdown: flush any remaining batch
+        // Graceful shutdown: flush any remaining batch, and the rows sinks
+        // hold back; an open transaction is streamed again after a restart
+        self.sink.release_held();
         if !batch.is_empty() {
             warn!(
                 "Pipeline stopped with {} pending events in batch",
                 batch.len()
             );
-            self.flush_batch(&batch, last_lsn).await;
+            self.flush_ready(&mut batch, last_lsn).await;
         }
+        self.flush_held().await;
         info!("Pipeline shutdown complete");
     }
 
+    /// Holds the pipeline while CDC is paused, until `Resume` or a drain.
+    /// Everything taken in is written first, with the rows sinks hold back,
+    /// and its checkpoint confirmed: only an open transaction stays pending
+    /// with `FLUSH_ON_COMMIT`. Requests are answered meanwhile. Returns false
+    /// on flush failure.
+    async fn wait_while_paused(
+        &mut self,
+        batch: &mut Vec<CdcMessage>,
+        last_lsn: u64,
+        state_rx: &mut watch::Receiver<CdcState>,
+    ) -> bool {
+        if self.sink.held_from().is_some() {
+            self.sink.release_held();
+        }
+        if !self.flush_ready(batch, last_lsn).await || !self.flush_held().await {
+            return false;
+        }
+        info!(
+            "[PIPELINE] Paused with the checkpoint at LSN 0x{:X}, {} events pending",
+            self.flushed_lsn,
+            batch.len()
+        );
+
+        loop {
+            tokio::select! {
+                changed = state_rx.changed() => {
+                    if changed.is_err() || *state_rx.borrow_and_update() != CdcState::Paused {
+                        break;
+                    }
+                }
+                Some(request) = next_request(&mut self.peek_rx) => {
+                    request.answer(batch, &self.schema_cache, false, last_lsn);
+                }
+                Some(request) = next_request(&mut self.invalidate_rx) => {
+                    self.invalidate_sink_schema(request).await;
+                }
+                Some(request) = next_request(&mut self.table_control_rx) => {
+                    self.control_table(request).await;
+                }
+            }
+        }
+        info!("[PIPELINE] Resumed");
+        true
+    }
+
+    /// Applies the retention limits of the dead-letter files, when due. A
+    /// failed sweep is logged and tried again at the next one.
+    fn sweep_dead_letters(&mut self) {
+        let Some(queue) = self.dead_letters.as_mut() else {
+            return;
+        };
+        match queue.sweep() {
+            Ok(Some(files)) => {
+                if let Some(ref state) = self.shared_state {
+                    state.record_dead_letter_files(
+                        files.bytes,
+                        files.oldest_age.as_secs(),
+                        files.expired,
+                    );
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("[DLQ] Failed to apply the retention limits: {:#}", e),
+        }
+    }
+
+    /// Writes the rows a sink holds back for a larger batch if they are due,
+    /// or all of them after `release_held`. Returns false on flush failure.
+    async fn flush_held(&mut self) -> bool {
+        if self.sink.held_from().is_none() {
+            return true;
+        }
+        self.flush_batch(&[], self.flushed_lsn).await
+    }
+
+    /// Flushes the events of the batch that can go: all of them, or with
+    /// `FLUSH_ON_COMMIT` those up to the last Commit, confirming the Commit's
+    /// LSN. Returns false on flush failure.
+    async fn flush_ready(&mut self, batch: &mut Vec<CdcMessage>, last_lsn: u64) -> bool {
+        let (n, lsn) = match &self.commits {
+            Some(commits) => commits.flushable(batch.len(), last_lsn),
+            None => (batch.len(), last_lsn),
+        };
+        if n == 0 || self.replay_end.is_some_and(|end| lsn < end) {
+            return true;
+        }
+        if self.commits.as_ref().is_some_and(|c| c.spills()) {
+            warn!(
+                "[BATCH] Transaction over MAX_TRANSACTION_EVENTS, flushing {} events before its commit",
+                n
+            );
+        }
+        if !self.flush_batch(&batch[..n], lsn).await {
+            return false;
+        }
+        batch.drain(..n);
+        if let Some(commits) = self.commits.as_mut() {
+            commits.flushed(n);
+        }
+        if let Some(ref state) = self.shared_state {
+            state.set_pending(batch.len() as u64);
+        }
+        true
+    }
+
+    /// Stops routing tables removed from the publication once the stream
+    /// reaches the WAL position where the removal was noticed. Pending rows
+    /// are flushed first, since they need the schemas being forgotten.
+    /// Returns false on flush failure.
+    async fn retire_tables(
+        &mut self,
+        batch: &mut Vec<CdcMessage>,
+        lsn: u64,
+        last_lsn: u64,
+    ) -> bool {
+        let Some(state) = self.shared_state.clone() else {
+            return true;
+        };
+        self.retiring.extend(state.take_removed_tables());
+        if self.retiring.iter().all(|(_, at)| *at > lsn) {
+            return true;
+        }
+
+        let held = self.sink.held_from().is_some();
+        if !batch.is_empty() || held {
+            if held {
+                self.sink.release_held();
+            }
+            if !self.flush_batch(batch, last_lsn).await {
+                return false;
+            }
+            if let Some(commits) = self.commits.as_mut() {
+                commits.flushed(batch.len());
+            }
+            batch.clear();
+            state.set_pending(0);
+        }
+        let (due, waiting) = std::mem::take(&mut self.retiring)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, at)| *at <= lsn);
+        self.retiring = waiting;
+        for (table, _) in due {
+            if let Some(relation_id) = self.schema_cache.remove_table(&table) {
+                self.removed_relations.insert(relation_id);
+                self.history.forget(relation_id);
+            }
+            state.forget_table_freshness(&table);
+            info!(
+                "[PUBLICATION] Stopped routing {}: removed from the publication",
+                table
+            );
+        }
+        self.publish_schema().await;
+        true
+    }
+
     /// Flush batch to sink. Returns true on success, false on failure (pipeline should stop).
     async fn flush_batch(&mut self, batch: &[CdcMessage], lsn: u64) -> bool {
-        match self.sink.push_batch(batch, &self.schema_cache, lsn).await {
-            Ok(_) => {
+        if self.archive_tee_active() {
+            return self.archive_batch(batch, lsn).await;
+        }
+
+        let mut result = self.push_with_retry(batch, lsn).await;
+        if let Err(e) = result {
+            result = if is_schema_mismatch(&e) {
+                self.pause_mismatched(batch, lsn, e).await
+            } else if is_rejected(&e) && self.dead_letters.is_some() {
+                self.isolate_rejected(batch, lsn, e).await
+            } else if self.probes.is_some() {
+                self.quarantine_failing(batch, lsn, e).await
+            } else {
+                Err(e)
+            };
+        }
+
+        match result {
+            Ok(written) => {
+                self.record_batch_result(&written, lsn).await;
+
                 // Emit CDC events to demo broadcast channel (compiled out in production)
                 #[cfg(feature = "demo")]
                 {
@@ -185,26 +803,37 @@ impl Pipeline {
 
                     // Calculate end-to-end replication lag
                     if self.last_commit_timestamp_us > 0 {
-                        const PG_EPOCH_OFFSET_USEC: u64 = 946_684_800_000_000;
-                        let commit_unix_us = self.last_commit_timestamp_us + PG_EPOCH_OFFSET_USEC;
-                        let now_us = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap_or_default()
-                            .as_micros() as u64;
-                        let lag_ms = now_us.saturating_sub(commit_unix_us) / 1_000;
-                        state.set_replication_lag_ms(lag_ms);
+                        state.set_replication_lag_ms(commit_age_ms(self.last_commit_timestamp_us));
+                    }
+
+                    self.freshness
+                        .record(batch, &self.schema_cache, state)
+                        .await;
+                    state.tail.publish(batch, &self.schema_cache, lsn);
+                    record_table_rows(batch, &self.schema_cache, state, lsn);
+                    if let Some(sent_ms) = self
+                        .probe_table
+                        .as_deref()
+                        .and_then(|table| probe_sent_ms(batch, &self.schema_cache, table))
+                    {
+                        state.record_probe_delivered(sent_ms);
                     }
                 }
 
-                // Send LSN to the feedback channel to confirm checkpoint
-                if let Some(ref tx) = self.feedback_tx {
-                    if let Err(e) = tx.send(lsn).await {
-                        error!("Failed to send checkpoint feedback: {}", e);
-                        // This is critical - cannot confirm checkpoint
-                        return false;
+                if let Some(end) = self.replay_end.filter(|end| lsn >= *end) {
+                    if lsn > end {
+                        warn!(
+                            "[BATCH] Replayed batch ended at LSN 0x{:X}, not 0x{:X} as when it was \
+                             interrupted; rows it shares with that batch may be written twice",
+                            lsn, end
+                        );
                     }
+                    self.replay_end = None;
                 }
-                true
+
+                // Send LSN to the feedback channel to confirm checkpoint
+                self.flushed_lsn = lsn;
+                self.send_feedback(lsn).await
             }
             Err(e) => {
                 // CRITICAL: Sink failure (StarRocks down, network error, etc.)
@@ -220,6 +849,14 @@ impl Pipeline {
 
                 // Set CDC state to Stopped to signal error
                 if let Some(ref state) = self.shared_state {
+                    state
+                        .record_error(
+                            ErrorCategory::Sink,
+                            format!("Sink write failed ({} events): {}", batch.len(), e),
+                            None,
+                            lsn,
+                        )
+                        .await;
                     state.set_state(crate::grpc::state::CdcState::Stopped);
                     error!("CRITICAL: CDC state set to Stopped due to sink failure");
                 }
@@ -229,4 +866,991 @@ impl Pipeline {
             }
         }
     }
+
+    /// Feeds what the sink reported about a written batch to the metrics,
+    /// and its warnings to the log and the recent errors.
+    async fn record_batch_result(&self, written: &BatchResult, lsn: u64) {
+        for warning in &written.warnings {
+            warn!("[SINK] Batch at LSN 0x{:X}: {}", lsn, warning);
+        }
+        let Some(ref state) = self.shared_state else {
+            return;
+        };
+        state.add_sink_rows(written.rows_written, written.rows_filtered);
+        for warning in &written.warnings {
+            state
+                .record_error(
+                    ErrorCategory::Sink,
+                    format!("Sink warning: {}", warning),
+                    None,
+                    lsn,
+                )
+                .await;
+        }
+    }
+
+    /// Writes a batch to the sink, retrying retryable failures with backoff.
+    /// Once the attempts run out, the circuit breaker (if enabled) holds the
+    /// batch while the circuit is open and then probes the sink with it,
+    /// until a write succeeds or fails for good. Fails right away on terminal
+    /// errors, and on shutdown while waiting.
+    async fn push_with_retry(
+        &mut self,
+        batch: &[CdcMessage],
+        lsn: u64,
+    ) -> anyhow::Result<BatchResult> {
+        let mut attempt = 1;
+        loop {
+            let err = match self.push(batch, lsn).await {
+                Ok(written) => {
+                    if self.circuit != CircuitState::Closed {
+                        info!("[SINK] Write at LSN 0x{:X} succeeded, circuit closed", lsn);
+                        self.set_circuit(CircuitState::Closed);
+                    }
+                    return Ok(written);
+                }
+                Err(e) if !is_retryable(&e) => return Err(e),
+                Err(e) => e,
+            };
+
+            let wait = if self.circuit == CircuitState::Closed && attempt < self.retry.max_attempts
+            {
+                let wait = self.retry.backoff(attempt);
+                warn!(
+                    "[SINK] Write at LSN 0x{:X} failed (attempt {}/{}), retrying in {:?}: {:#}",
+                    lsn, attempt, self.retry.max_attempts, wait, err
+                );
+                attempt += 1;
+                wait
+            } else if let Some(open_for) = self.retry.circuit_open_for {
+                error!(
+                    "[SINK] Write at LSN 0x{:X} failed, circuit open for {:?}: {:#}",
+                    lsn, open_for, err
+                );
+                if self.circuit == CircuitState::Closed {
+                    if let Some(ref state) = self.shared_state {
+                        state
+                            .record_error(
+                                ErrorCategory::Sink,
+                                format!(
+                                    "Sink write failed after {} attempts, circuit open: {:#}",
+                                    attempt, err
+                                ),
+                                None,
+                                lsn,
+                            )
+                            .await;
+                    }
+                }
+                self.set_circuit(CircuitState::Open);
+                open_for
+            } else {
+                return Err(err.context(format!("Sink write failed after {} attempts", attempt)));
+            };
+
+            if let Some(ref state) = self.shared_state {
+                state.increment_sink_retries();
+            }
+            if !self.wait_for_retry(wait, batch, lsn).await {
+                return Err(err.context("Shutting down while retrying the sink write"));
+            }
+            if self.circuit == CircuitState::Open {
+                info!("[SINK] Circuit half-open, probing the sink");
+                self.set_circuit(CircuitState::HalfOpen);
+            }
+        }
+    }
+
+    /// Sleeps before a retry, answering `PeekBatch` meanwhile. Returns false
+    /// if shutdown was requested.
+    async fn wait_for_retry(&mut self, wait: Duration, batch: &[CdcMessage], lsn: u64) -> bool {
+        let mut shutdown_rx = self
+            .shared_state
+            .as_ref()
+            .map(|s| s.shutdown_tx.subscribe());
+        let sleep = tokio::time::sleep(wait);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return true,
+                Some(request) = next_request(&mut self.peek_rx) => {
+                    request.answer(batch, &self.schema_cache, true, lsn);
+                }
+                // The sink is idle, and a stale schema may be why it fails
+                Some(request) = next_request(&mut self.invalidate_rx) => {
+                    self.invalidate_sink_schema(request).await;
+                }
+                true = shutdown_requested(&mut shutdown_rx) => return false,
+            }
+        }
+    }
+
+    fn set_circuit(&mut self, circuit: CircuitState) {
+        self.circuit = circuit;
+        if let Some(ref state) = self.shared_state {
+            state.set_sink_circuit(circuit);
+        }
+    }
+
+    /// Writes a batch to the sink, answering `PeekBatch` while the sink
+    /// works so a stuck write can be inspected.
+    async fn push(&mut self, batch: &[CdcMessage], lsn: u64) -> anyhow::Result<BatchResult> {
+        let push = self.sink.push_batch(batch, &self.schema_cache, lsn);
+        tokio::pin!(push);
+        loop {
+            tokio::select! {
+                result = &mut push => break result,
+                Some(request) = next_request(&mut self.peek_rx) => {
+                    request.answer(batch, &self.schema_cache, true, lsn);
+                }
+            }
+        }
+    }
+
+    /// Writes a batch the sink rejected without the rows at fault. The batch
+    /// is halved until each rejected part holds a single row change; parts
+    /// are written in order, so a row never overtakes an earlier change of
+    /// the same key. The rejected rows are parked in the dead-letter queue,
+    /// and the results of the parts written are added up.
+    ///
+    /// Fails on any error other than a rejection, and once more rows are
+    /// rejected than `DEAD_LETTER_MAX_ROWS`: then the problem is rarely the
+    /// rows (a column type changed, say), and parking them all would hide it.
+    async fn isolate_rejected(
+        &mut self,
+        batch: &[CdcMessage],
+        lsn: u64,
+        error: anyhow::Error,
+    ) -> anyhow::Result<BatchResult> {
+        let max_rows = self.dead_letters.as_ref().map_or(0, |q| q.max_rows());
+        warn!(
+            "[DLQ] Sink rejected a batch of {} events at LSN 0x{:X}, isolating the rejected rows: {}",
+            batch.len(),
+            lsn,
+            error
+        );
+
+        let mut letters: Vec<DeadLetter> = Vec::new();
+        let mut written = BatchResult::default();
+        // Parts still to write, last one first; a known error means the part
+        // was already rejected as a whole
+        let mut parts = vec![(0, batch.len(), Some(error))];
+        while let Some((start, end, rejected)) = parts.pop() {
+            let part = &batch[start..end];
+            let error = match rejected {
+                Some(error) => error,
+                None => match self.push(part, lsn).await {
+                    Ok(result) => {
+                        written.merge(result);
+                        continue;
+                    }
+                    Err(e) if is_rejected(&e) => e,
+                    Err(e) => return Err(e),
+                },
+            };
+
+            let rows: Vec<usize> = part
+                .iter()
+                .enumerate()
+                .filter(|(_, msg)| row_relation(msg).is_some())
+                .map(|(i, _)| start + i)
+                .collect();
+            if rows.len() > 1 {
+                let mid = rows[rows.len() / 2];
+                parts.push((mid, end, None));
+                parts.push((start, mid, None));
+                continue;
+            }
+
+            // A single row change, plus transaction markers around it
+            let Some(letter) = rows
+                .first()
+                .and_then(|&i| dead_letter(&batch[i], &self.schema_cache, lsn, &error))
+            else {
+                return Err(error);
+            };
+            warn!(
+                "[DLQ] Parking rejected {} on {}: {:#}",
+                letter.op, letter.table, error
+            );
+            letters.push(letter);
+            if letters.len() > max_rows {
+                return Err(error.context(format!(
+                    "more than {} rows of one batch rejected (DEAD_LETTER_MAX_ROWS)",
+                    max_rows
+                )));
+            }
+        }
+
+        if let Some(queue) = self.dead_letters.as_mut() {
+            queue
+                .park(&letters, lsn, self.sink.as_mut())
+                .await
+                .map_err(|e| e.context("failed to park rejected rows"))?;
+        }
+        if let Some(ref state) = self.shared_state {
+            state.add_dead_letters(letters.len() as u64);
+            for letter in &letters {
+                state
+                    .record_error(
+                        ErrorCategory::Sink,
+                        format!("Row parked in the dead-letter queue: {}", letter.error),
+                        Some(&letter.table),
+                        lsn,
+                    )
+                    .await;
+            }
+        }
+        info!(
+            "[DLQ] Parked {} rejected rows of the batch at LSN 0x{:X}, wrote the rest",
+            letters.len(),
+            lsn
+        );
+        Ok(written)
+    }
+
+    /// Writes a batch the sink refused over a schema mismatch without the
+    /// rows of the tables whose sink table differs, and pauses those tables.
+    /// Fails when no table of the batch differs, which includes sinks that
+    /// can't describe their tables.
+    async fn pause_mismatched(
+        &mut self,
+        batch: &[CdcMessage],
+        lsn: u64,
+        error: anyhow::Error,
+    ) -> anyhow::Result<BatchResult> {
+        let mut rest = batch.to_vec();
+        let mut error = error;
+        loop {
+            let mut relations: Vec<u32> = rest.iter().filter_map(row_relation).collect();
+            relations.sort_unstable();
+            relations.dedup();
+
+            let mut diffs = Vec::new();
+            for relation_id in relations {
+                let Some(schema) = self.schema_cache.get(relation_id) else {
+                    continue;
+                };
+                match self.sink.schema_diff(&schema.full_delta()).await {
+                    Ok(Some(diff)) if !diff.is_empty() => diffs.push((relation_id, diff)),
+                    Ok(_) => {}
+                    Err(e) => warn!(
+                        "[SCHEMA] Failed to compare {} with its sink table: {:#}",
+                        schema.name, e
+                    ),
+                }
+            }
+            if diffs.is_empty() {
+                return Err(error.context("no table of the batch differs from its sink table"));
+            }
+
+            for (relation_id, diff) in diffs {
+                let (rows, others) = std::mem::take(&mut rest)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|msg| row_relation(msg) == Some(relation_id));
+                rest = others;
+                let held = self.paused.entry(relation_id).or_default();
+                held.rows.extend(rows);
+                held.lsn = lsn;
+                let held_rows = held.rows.len() as u64;
+                error!(
+                    "[SCHEMA] Pausing {}, its sink table doesn't match: {}",
+                    diff.table, diff
+                );
+                if let Some(probes) = self.probes.as_mut() {
+                    probes.paused(relation_id, Instant::now());
+                }
+                if let Some(notifier) = &self.pause_notifier {
+                    notifier.paused(&diff, held_rows);
+                }
+                if let Some(ref state) = self.shared_state {
+                    state
+                        .record_error(
+                            ErrorCategory::Schema,
+                            format!("Table paused, sink table doesn't match: {}", diff),
+                            Some(&diff.table),
+                            lsn,
+                        )
+                        .await;
+                    let table = diff.table.clone();
+                    state.pause_table(diff);
+                    state.set_held_rows(&table, held_rows);
+                }
+            }
+
+            if !rest.iter().any(|msg| row_relation(msg).is_some()) {
+                return Ok(BatchResult::default());
+            }
+            match self.push_with_retry(&rest, lsn).await {
+                Ok(written) => return Ok(written),
+                Err(e) if is_schema_mismatch(&e) => error = e,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Writes a batch that failed for another reason than a schema mismatch
+    /// again, table by table, and quarantines the tables whose rows still
+    /// fail: their rows are held as for a table paused on a mismatch, and
+    /// each probe writes them again. Fails when every table of the batch
+    /// fails, and for a batch of a single table: then the sink, not a table,
+    /// is taken to be at fault. Nothing is written again on shutdown.
+    async fn quarantine_failing(
+        &mut self,
+        batch: &[CdcMessage],
+        lsn: u64,
+        error: anyhow::Error,
+    ) -> anyhow::Result<BatchResult> {
+        let mut relations: Vec<u32> = batch.iter().filter_map(row_relation).collect();
+        relations.sort_unstable();
+        relations.dedup();
+        let shutting_down = self
+            .shared_state
+            .as_ref()
+            .is_some_and(|state| *state.shutdown_tx.borrow());
+        if relations.len() < 2 || shutting_down {
+            return Err(error);
+        }
+        warn!(
+            "[SINK] Batch of {} events at LSN 0x{:X} failed, writing it table by table: {:#}",
+            batch.len(),
+            lsn,
+            error
+        );
+
+        let mut written = BatchResult::default();
+        let mut failed = Vec::new();
+        for &relation_id in &relations {
+            // The table's rows, with the transaction markers around them
+            let part: Vec<CdcMessage> = batch
+                .iter()
+                .filter(|msg| row_relation(msg).map_or(true, |id| id == relation_id))
+                .cloned()
+                .collect();
+            match self.push(&part, lsn).await {
+                Ok(result) => written.merge(result),
+                Err(e) => failed.push((relation_id, part, e)),
+            }
+        }
+        if failed.len() == relations.len() {
+            return Err(error.context("writing each table of the batch on its own failed too"));
+        }
+
+        for (relation_id, part, e) in failed {
+            let table = self
+                .schema_cache
+                .get(relation_id)
+                .map(|s| s.qualified_name())
+                .unwrap_or_default();
+            let reason = format!("{:#}", e);
+            let held = self.paused.entry(relation_id).or_default();
+            held.rows
+                .extend(part.into_iter().filter(|msg| row_relation(msg).is_some()));
+            held.lsn = lsn;
+            held.sink_error = Some(reason.clone());
+            let held_rows = held.rows.len() as u64;
+            error!(
+                "[SINK] Quarantining {}, writing its rows failed: {}",
+                table, reason
+            );
+            if let Some(probes) = self.probes.as_mut() {
+                probes.paused(relation_id, Instant::now());
+            }
+            if let Some(notifier) = &self.pause_notifier {
+                notifier.quarantined(&table, &reason, held_rows);
+            }
+            if let Some(ref state) = self.shared_state {
+                state
+                    .record_error(
+                        ErrorCategory::Sink,
+                        format!("Table quarantined, writing its rows failed: {}", reason),
+                        Some(&table),
+                        lsn,
+                    )
+                    .await;
+                state.quarantine_table(&table, reason);
+                state.set_held_rows(&table, held_rows);
+            }
+        }
+        Ok(written)
+    }
+
+    /// Holds a row of a paused table. Returns false once the paused tables
+    /// hold more than `MAX_HELD_ROWS` rows: the pipeline stops, and since no
+    /// checkpoint was confirmed meanwhile, the rows are streamed again after
+    /// a restart.
+    async fn hold(&mut self, relation_id: u32, msg: CdcMessage, lsn: u64) -> bool {
+        let held = self.paused.entry(relation_id).or_default();
+        held.rows.push(msg);
+        held.lsn = lsn;
+        let held_rows = held.rows.len() as u64;
+        let table = self
+            .schema_cache
+            .get(relation_id)
+            .map(|s| s.qualified_name())
+            .unwrap_or_default();
+        let total: usize = self.paused.values().map(|h| h.rows.len()).sum();
+
+        let Some(ref state) = self.shared_state else {
+            return total <= MAX_HELD_ROWS;
+        };
+        state.set_held_rows(&table, held_rows);
+        if total <= MAX_HELD_ROWS {
+            return true;
+        }
+        error!(
+            "CRITICAL: Paused tables hold more than {} rows, stopping",
+            MAX_HELD_ROWS
+        );
+        state
+            .record_error(
+                ErrorCategory::Schema,
+                format!(
+                    "Paused tables hold more than {} rows; fix the sink tables and restart",
+                    MAX_HELD_ROWS
+                ),
+                Some(&table),
+                lsn,
+            )
+            .await;
+        state.set_state(crate::grpc::state::CdcState::Stopped);
+        false
+    }
+
+    /// Fills in the unchanged TOAST values of an update. Values that can't be
+    /// are left to the sink with a warning, unless `TOAST_HYDRATION=strict`.
+    /// Returns false when the pipeline must stop.
+    async fn hydrate_toast(&mut self, msg: &mut CdcMessage, lsn: u64) -> bool {
+        let error = match self.toast.hydrate(msg).await {
+            Ok(0) => return true,
+            Ok(n) => {
+                if let Some(ref state) = self.shared_state {
+                    state.record_toast_hydrated(n as u64);
+                }
+                return true;
+            }
+            Err(e) => e,
+        };
+        let table = row_relation(msg)
+            .and_then(|id| self.schema_cache.get(id))
+            .map(|s| s.qualified_name())
+            .unwrap_or_default();
+        if self.toast.is_strict() {
+            self.stop_on_toast_error(error, &table, lsn).await;
+            return false;
+        }
+        warn!(
+            "[TOAST] Unchanged TOAST values left to the sink at LSN 0x{:X}: {:#}",
+            lsn, error
+        );
+        if let Some(ref state) = self.shared_state {
+            state
+                .record_error(
+                    ErrorCategory::Source,
+                    format!("Unchanged TOAST values left to the sink: {:#}", error),
+                    Some(&table),
+                    lsn,
+                )
+                .await;
+        }
+        true
+    }
+
+    async fn stop_on_toast_error(&self, error: anyhow::Error, table: &str, lsn: u64) {
+        error!("CRITICAL: {:#}, stopping", error);
+        if let Some(ref state) = self.shared_state {
+            state
+                .record_error(
+                    ErrorCategory::Source,
+                    format!("{:#}", error),
+                    Some(table),
+                    lsn,
+                )
+                .await;
+            state.set_state(CdcState::Stopped);
+        }
+    }
+
+    /// Writes the held rows of a paused relation once its sink table matches
+    /// again, or of a quarantined one. Returns false while the sink table
+    /// still differs. A single attempt: a failure leaves the table paused.
+    async fn resume_paused(&mut self, relation_id: u32) -> anyhow::Result<bool> {
+        let Some(schema) = self.schema_cache.get(relation_id) else {
+            return Ok(false);
+        };
+        let table = schema.qualified_name();
+        if !self.is_quarantined(relation_id) {
+            if let Some(diff) = self.sink.schema_diff(&schema.full_delta()).await? {
+                if !diff.is_empty() {
+                    if let Some(ref state) = self.shared_state {
+                        state.pause_table(diff);
+                    }
+                    return Ok(false);
+                }
+            }
+        }
+
+        let written = self.write_held(relation_id, &table).await?;
+        if let Some(ref state) = self.shared_state {
+            state.resume_table(&table);
+        }
+        if let Some(probes) = self.probes.as_mut() {
+            probes.resumed(relation_id);
+        }
+        if let Some(notifier) = &self.pause_notifier {
+            notifier.resumed(&table, written);
+        }
+        Ok(true)
+    }
+
+    /// Probes the tables paused on a schema mismatch whose probe is due:
+    /// their schema is applied to the sink again, as `InvalidateSinkSchema`
+    /// does, and those whose sink table then matches get their held rows.
+    /// Quarantined tables due get their held rows written again. A table
+    /// still paused is probed again after twice the delay.
+    async fn probe_paused_tables(&mut self, now: Instant) {
+        let due = match self.probes.as_mut() {
+            Some(probes) => probes.take_due(now),
+            None => return,
+        };
+        for relation_id in due {
+            // Left alone while an operator holds it with PauseTable
+            if self.paused_relations.contains_key(&relation_id) {
+                continue;
+            }
+            let table = match self.schema_cache.get(relation_id) {
+                Some(schema) if self.paused.contains_key(&relation_id) => schema.qualified_name(),
+                // Resumed meanwhile, or its rows were never held
+                _ => {
+                    if let Some(probes) = self.probes.as_mut() {
+                        probes.resumed(relation_id);
+                    }
+                    continue;
+                }
+            };
+            let result = if self.is_quarantined(relation_id) {
+                self.resume_paused(relation_id).await
+            } else {
+                match self.reapply_sink_schema(Some(&table)).await {
+                    Ok(_) => self.resume_paused(relation_id).await,
+                    Err(e) => Err(e),
+                }
+            };
+            let next = self
+                .probes
+                .as_ref()
+                .and_then(|probes| probes.next_delay(relation_id));
+            match (result, next) {
+                (Ok(true), _) => info!("[SCHEMA] Probe of {} succeeded, table resumed", table),
+                (Ok(false), Some(next)) => info!(
+                    "[SCHEMA] {} still doesn't match its sink table, next probe in {}s",
+                    table,
+                    next.as_secs()
+                ),
+                (Err(e), Some(next)) => warn!(
+                    "[SCHEMA] Probe of paused table {} failed, next one in {}s: {:#}",
+                    table,
+                    next.as_secs(),
+                    e
+                ),
+                (_, None) => {}
+            }
+        }
+    }
+
+    /// The relation's rows are held after a sink error, not a mismatch
+    fn is_quarantined(&self, relation_id: u32) -> bool {
+        self.paused
+            .get(&relation_id)
+            .is_some_and(|held| held.sink_error.is_some())
+    }
+
+    /// Writes the rows held for a relation, then confirms the checkpoint
+    /// withheld meanwhile if no other table holds rows. Returns the number
+    /// of rows written; on failure they stay held.
+    async fn write_held(&mut self, relation_id: u32, table: &str) -> anyhow::Result<usize> {
+        let Some(held) = self.paused.remove(&relation_id) else {
+            return Ok(0);
+        };
+        // Rows are held from before the batches confirmed since
+        let lsn = held.lsn.max(self.withheld_lsn.unwrap_or(0));
+        let written = match self.push(&held.rows, lsn).await {
+            Ok(written) => written,
+            Err(e) => {
+                self.paused.insert(relation_id, held);
+                return Err(e.context(format!("Failed to write the held rows of {}", table)));
+            }
+        };
+        self.record_batch_result(&written, lsn).await;
+        info!(
+            "[SCHEMA] Resumed {}, wrote {} held rows",
+            table,
+            held.rows.len()
+        );
+        if let Some(ref state) = self.shared_state {
+            state.set_held_rows(table, 0);
+        }
+        if self.paused.is_empty() {
+            if let Some(lsn) = self.withheld_lsn.take() {
+                self.send_feedback(lsn).await;
+            }
+        }
+        Ok(held.rows.len())
+    }
+
+    /// Drops a row of a table skipped with `PauseTable`.
+    fn skip(&self, relation_id: u32, lsn: u64) {
+        if let (Some(state), Some(schema)) =
+            (&self.shared_state, self.schema_cache.get(relation_id))
+        {
+            state.record_skipped_row(&schema.qualified_name(), lsn);
+        }
+    }
+
+    /// Answers a `PauseTable` or `ResumeTable` call.
+    async fn control_table(&mut self, request: TableControlRequest) {
+        let result = match request.pause {
+            Some(mode) => self.pause_table(&request.table, mode),
+            None => self.resume_table(&request.table).await,
+        };
+        let _ = request.reply.send(result);
+    }
+
+    fn pause_table(&mut self, table: &str, mode: TablePauseMode) -> Result<String, ControlError> {
+        let table = qualified_table(table);
+        if let Some(current) = self.table_pauses.get(&table) {
+            return Err(ControlError::new(
+                ErrorCode::AlreadyDone,
+                format!("{} is already paused ({}); resume it first", table, current),
+            )
+            .with_table(table));
+        }
+        self.table_pauses.insert(table.clone(), mode);
+        if let Some(schema) = self.schema_cache.find(&table) {
+            self.paused_relations.insert(schema.id, mode);
+        }
+        if let Some(ref state) = self.shared_state {
+            state.set_manual_pause(&table, Some(mode));
+        }
+        info!("[TABLE] Paused {} ({})", table, mode);
+        Ok(match mode {
+            TablePauseMode::Buffer => {
+                format!("{} paused; its rows are held until ResumeTable", table)
+            }
+            TablePauseMode::Skip => format!(
+                "{} paused; its rows are dropped and the table will need a re-sync",
+                table
+            ),
+        })
+    }
+
+    async fn resume_table(&mut self, table: &str) -> Result<String, ControlError> {
+        let table = qualified_table(table);
+        let Some(mode) = self.table_pauses.remove(&table) else {
+            return Err(ControlError::new(
+                ErrorCode::InvalidState,
+                format!("{} is not paused", table),
+            )
+            .with_table(table));
+        };
+        let relation_id = self.schema_cache.find(&table).map(|s| s.id);
+        if let Some(relation_id) = relation_id {
+            self.paused_relations.remove(&relation_id);
+        }
+        let mismatched = self
+            .shared_state
+            .as_ref()
+            .is_some_and(|state| state.paused_tables.lock().contains_key(&table));
+
+        let message = match (mode, relation_id) {
+            // Held rows wait for the sink table to be fixed
+            (TablePauseMode::Buffer, _) if mismatched => format!(
+                "{} resumed; its rows stay held until its sink table matches again",
+                table
+            ),
+            (TablePauseMode::Buffer, Some(relation_id)) => {
+                match self.write_held(relation_id, &table).await {
+                    Ok(rows) => format!("{} resumed, {} held rows written", table, rows),
+                    Err(e) => {
+                        self.table_pauses.insert(table.clone(), mode);
+                        self.paused_relations.insert(relation_id, mode);
+                        let lsn = self.paused.get(&relation_id).map_or(0, |held| held.lsn);
+                        return Err(ControlError::new(
+                            ErrorCode::Failed,
+                            format!("{:#}; {} stays paused", e, table),
+                        )
+                        .with_table(table)
+                        .with_lsn(lsn));
+                    }
+                }
+            }
+            (TablePauseMode::Buffer, None) => format!("{} resumed", table),
+            (TablePauseMode::Skip, _) => match self
+                .shared_state
+                .as_ref()
+                .and_then(|state| state.resync_needed.lock().get(&table).copied())
+            {
+                Some(lsn) => format!(
+                    "{} resumed; rows from LSN 0x{:X} were dropped, re-sync the table",
+                    table, lsn
+                ),
+                None => format!("{} resumed, no rows were dropped", table),
+            },
+        };
+        if let Some(ref state) = self.shared_state {
+            state.set_manual_pause(&table, None);
+        }
+        info!("[TABLE] {}", message);
+        Ok(message)
+    }
+
+    /// Resumes every paused table whose sink table was fixed. Tables paused
+    /// with `PauseTable` wait for `ResumeTable`.
+    async fn resume_paused_tables(&mut self) {
+        let relations: Vec<u32> = self
+            .paused
+            .keys()
+            .filter(|id| !self.paused_relations.contains_key(id))
+            .copied()
+            .collect();
+        for relation_id in relations {
+            if let Err(e) = self.resume_paused(relation_id).await {
+                warn!("[SCHEMA] {:#}", e);
+                if let Some(ref state) = self.shared_state {
+                    let table = self.schema_cache.get_table_name(relation_id);
+                    state
+                        .record_error(
+                            ErrorCategory::Schema,
+                            format!("{:#}", e),
+                            table.as_deref(),
+                            0,
+                        )
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// The held rows of a paused relation were decoded against the schema
+    /// about to change, so they go now or never. Returns false, having
+    /// stopped the pipeline, when they can't be written.
+    async fn resume_before_schema_change(&mut self, relation_id: u32, lsn: u64) -> bool {
+        let table = self
+            .schema_cache
+            .get_table_name(relation_id)
+            .unwrap_or_default();
+        let reason = match self.resume_paused(relation_id).await {
+            Ok(true) => return true,
+            Ok(false) => "its sink table still doesn't match".to_string(),
+            Err(e) => format!("{:#}", e),
+        };
+        error!(
+            "CRITICAL: Schema of paused table {} changed while rows are held: {}",
+            table, reason
+        );
+        if let Some(ref state) = self.shared_state {
+            state
+                .record_error(
+                    ErrorCategory::Schema,
+                    format!(
+                        "Schema of {} changed while it was paused ({}); fix the sink table and restart",
+                        table, reason
+                    ),
+                    Some(&table),
+                    lsn,
+                )
+                .await;
+            state.set_state(crate::grpc::state::CdcState::Stopped);
+        }
+        false
+    }
+
+    /// Answers an `InvalidateSinkSchema` call. A caller that gave up is
+    /// ignored; the schema is re-applied all the same, and paused tables
+    /// whose sink table now matches resume.
+    async fn invalidate_sink_schema(&mut self, request: InvalidateRequest) {
+        let result = self.reapply_sink_schema(request.table.as_deref()).await;
+        if !self.paused.is_empty() {
+            self.resume_paused_tables().await;
+        }
+        if let Err(ref e) = result {
+            warn!("[SCHEMA] Sink schema invalidation failed: {:#}", e);
+            if let Some(ref state) = self.shared_state {
+                state
+                    .record_error(
+                        ErrorCategory::Schema,
+                        format!("Sink schema invalidation failed: {:#}", e),
+                        request.table.as_deref(),
+                        0,
+                    )
+                    .await;
+            }
+        }
+        let _ = request.reply.send(result.map_err(|e| format!("{:#}", e)));
+    }
+
+    /// Makes the sink forget the schema of `table` (every table when `None`)
+    /// and applies the table's full column list again. Returns the tables
+    /// done, `schema.table`.
+    async fn reapply_sink_schema(&mut self, table: Option<&str>) -> anyhow::Result<Vec<String>> {
+        let schemas: Vec<TableSchema> = match table {
+            Some(table) => vec![self
+                .schema_cache
+                .find(table)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "{} has had no changes since startup, so nothing about it is cached",
+                        table
+                    )
+                })?
+                .clone()],
+            None => self.schema_cache.schemas().into_iter().cloned().collect(),
+        };
+        if table.is_none() {
+            self.sink.invalidate_schema(None);
+        }
+
+        let mut done = Vec::with_capacity(schemas.len());
+        for schema in schemas {
+            let mut delta = schema.full_delta();
+            let name = delta.qualified_name();
+            if table.is_some() {
+                self.sink.invalidate_schema(Some(&name));
+            }
+            if let Some(catalog) = self.catalog.as_mut() {
+                catalog.describe(&mut delta).await;
+            }
+            self.sink
+                .apply_schema_delta(&delta)
+                .await
+                .with_context(|| format!("Failed to re-apply the schema of {}", name))?;
+            info!(
+                "[SCHEMA] Sink schema of {} invalidated, {} columns re-applied",
+                name,
+                delta.added_columns.len()
+            );
+            done.push(name);
+        }
+        Ok(done)
+    }
+
+    /// Archive-tee mode: append the batch to the local archive instead of the
+    /// sink, then confirm its LSN so the slot releases WAL.
+    async fn archive_batch(&mut self, batch: &[CdcMessage], lsn: u64) -> bool {
+        let Some(archive) = self.archive.as_mut() else {
+            return false;
+        };
+        if let Err(e) = archive.append(batch, lsn, &self.schema_cache) {
+            error!("CRITICAL: WAL archive write failed: {:#}", e);
+            if let Some(ref state) = self.shared_state {
+                state
+                    .record_error(
+                        ErrorCategory::Sink,
+                        format!("WAL archive write failed: {:#}", e),
+                        None,
+                        lsn,
+                    )
+                    .await;
+                state.set_state(crate::grpc::state::CdcState::Stopped);
+            }
+            return false;
+        }
+        self.send_feedback(lsn).await
+    }
+
+    /// Sends a confirmed LSN to the main loop. Returns false if the loop is
+    /// gone. Withheld while a table is paused, so its held rows are streamed
+    /// again after a restart. Never past rows a sink holds back.
+    async fn send_feedback(&mut self, lsn: u64) -> bool {
+        if !self.paused.is_empty() {
+            self.withheld_lsn = Some(lsn);
+            return true;
+        }
+        let lsn = match self.sink.held_from() {
+            Some(0) => return true, // Nothing before the held rows written yet
+            Some(held) => held.min(lsn),
+            None => lsn,
+        };
+        if let Some(ref tx) = self.feedback_tx {
+            if let Err(e) = tx.send(lsn).await {
+                error!("Failed to send checkpoint feedback: {}", e);
+                // This is critical - cannot confirm checkpoint
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Whether `batch` holds a row change of the relation.
+fn has_rows_of(batch: &[CdcMessage], relation_id: u32) -> bool {
+    batch
+        .iter()
+        .any(|msg| row_relation(msg) == Some(relation_id))
+}
+
+/// `schema.table` for a table name from `PauseTable`/`ResumeTable`, which
+/// may be bare
+fn qualified_table(table: &str) -> String {
+    if table.contains('.') {
+        table.to_string()
+    } else {
+        format!("public.{}", table)
+    }
+}
+
+/// Counts the rows of each table in a batch written at `lsn`.
+fn record_table_rows(
+    batch: &[CdcMessage],
+    schema_cache: &SchemaCache,
+    state: &SharedState,
+    lsn: u64,
+) {
+    let mut rows: HashMap<u32, u64> = HashMap::new();
+    for relation_id in batch.iter().filter_map(row_relation) {
+        *rows.entry(relation_id).or_default() += 1;
+    }
+    for (relation_id, count) in rows {
+        if let Some(schema) = schema_cache.get(relation_id) {
+            state.record_table_rows(&schema.qualified_name(), count, lsn);
+        }
+    }
+}
+
+/// Relation of a row change, `None` for other messages.
+fn row_relation(msg: &CdcMessage) -> Option<u32> {
+    match msg {
+        CdcMessage::Insert { relation_id, .. }
+        | CdcMessage::Update { relation_id, .. }
+        | CdcMessage::Delete { relation_id, .. } => Some(*relation_id),
+        _ => None,
+    }
+}
+
+/// Resolves once shutdown is requested; never without a channel.
+async fn shutdown_requested(rx: &mut Option<watch::Receiver<bool>>) -> bool {
+    match rx {
+        Some(rx) => rx.wait_for(|stop| *stop).await.is_ok(),
+        None => std::future::pending().await,
+    }
+}
+
+/// Resolves once CDC is paused; never without a channel.
+async fn pause_requested(rx: &mut Option<watch::Receiver<CdcState>>) -> bool {
+    match rx {
+        Some(rx) => rx
+            .wait_for(|state| *state == CdcState::Paused)
+            .await
+            .is_ok(),
+        None => std::future::pending().await,
+    }
+}
+
+/// Next `PeekBatch` or `InvalidateSinkSchema` request; never resolves
+/// without a channel.
+async fn next_request<T>(rx: &mut Option<mpsc::Receiver<T>>) -> Option<T> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
 }
@@ -0,0 +1,634 @@
+//! Tables paused on a sink schema mismatch or quarantined after a sink error.
+//!
+//! When the sink refuses a batch because a table's columns don't fit (a
+//! column dropped or retyped on the sink, the table gone), stopping the
+//! whole pipeline over one table is the wrong answer. The pipeline asks the
+//! sink how each table of the batch differs from the schema cache, writes
+//! the rest of the batch, and pauses the tables that differ: their rows are
+//! held in memory, and the diff is shown by `GetStatus` and `/status`.
+//!
+//! While a table is paused no checkpoint is confirmed, so the held rows are
+//! streamed again after a restart; the slot retains WAL meanwhile. Once the
+//! sink table is fixed, `InvalidateSinkSchema` re-applies the schema and the
+//! paused tables whose sink table matches again get their held rows, in
+//! order. Past [`MAX_HELD_ROWS`], or when a paused table's source schema
+//! changes under rows decoded against the old one, the pipeline stops.
+//!
+//! Unless `PAUSED_TABLE_PROBE_SECS=0`, a table paused on a mismatch is also
+//! probed: once the delay is up, its schema is applied to the sink again (the
+//! DDL `InvalidateSinkSchema` would run) and, if the sink table then matches,
+//! its held rows are written. A probe that fails doubles the delay, up to
+//! `PAUSED_TABLE_PROBE_MAX_SECS`. Pausing and resuming such a table is posted
+//! to `PAUSED_TABLE_WEBHOOK_URL` when set.
+//!
+//! With probing on, a batch that fails for any other reason, once its
+//! retries are used up, is written again table by table, and the tables
+//! whose rows still fail are quarantined: their rows are held the same way,
+//! and each probe writes them again. When every table of the batch fails,
+//! or the batch has a single table, the sink rather than a table is taken to
+//! be at fault and the pipeline stops as before.
+//!
+//! An operator can also pause a table with `PauseTable`, until `ResumeTable`:
+//!
+//! - [`TablePauseMode::Buffer`] holds its rows the same way, and the
+//!   checkpoint with them. Before a schema change of the table they are
+//!   written anyway, since they were decoded against the old columns; the
+//!   table stays paused.
+//! - [`TablePauseMode::Skip`] drops its rows and lets the checkpoint move on.
+//!   The table is marked as needing a re-sync from the first row dropped,
+//!   which `GetTableStatus` reports until restart.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde_json::json;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::config::{HttpClientConfig, PausedTableProbeConfig};
+use crate::connectors::http::client_builder;
+use crate::core::schema_diff::SchemaDiff;
+use crate::grpc::control_error::ControlError;
+use crate::source::parser::CdcMessage;
+
+/// Rows held across all paused tables before the pipeline stops
+pub const MAX_HELD_ROWS: usize = 100_000;
+
+/// What happens to the rows of a table paused with `PauseTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TablePauseMode {
+    /// Held in memory, and written on resume
+    Buffer,
+    /// Dropped; the table needs a re-sync
+    Skip,
+}
+
+impl fmt::Display for TablePauseMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TablePauseMode::Buffer => write!(f, "buffer"),
+            TablePauseMode::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+/// A `PauseTable` or `ResumeTable` call waiting for the pipeline.
+pub struct TableControlRequest {
+    /// Source table, `schema.table` or a bare name in `public`
+    pub table: String,
+    /// How to pause it; `None` resumes it
+    pub pause: Option<TablePauseMode>,
+    /// What was done, or why it wasn't
+    pub reply: oneshot::Sender<Result<String, ControlError>>,
+}
+
+/// Row changes of a paused table, in WAL order.
+#[derive(Default)]
+pub struct HeldRows {
+    pub rows: Vec<CdcMessage>,
+    /// WAL position of the last row held
+    pub lsn: u64,
+    /// Error writing the rows failed with when the table was quarantined;
+    /// `None` for a table paused on a schema mismatch
+    pub sink_error: Option<String>,
+}
+
+/// When the tables paused on a mismatch or quarantined are probed next.
+pub struct ProbeSchedule {
+    config: PausedTableProbeConfig,
+    /// Next probe, and the delay used to schedule it, by relation id
+    due: HashMap<u32, (Instant, Duration)>,
+}
+
+impl ProbeSchedule {
+    pub fn new(config: PausedTableProbeConfig) -> Self {
+        Self {
+            config,
+            due: HashMap::new(),
+        }
+    }
+
+    /// Schedules the first probe of a relation just paused. A relation
+    /// already scheduled keeps its probe.
+    pub fn paused(&mut self, relation_id: u32, now: Instant) {
+        let delay = self.config.initial;
+        self.due.entry(relation_id).or_insert((now + delay, delay));
+    }
+
+    /// Stops probing a relation that was resumed.
+    pub fn resumed(&mut self, relation_id: u32) {
+        self.due.remove(&relation_id);
+    }
+
+    /// Relations whose probe is due at `now`, in relation order. Each one's
+    /// next probe is scheduled after twice the previous delay, so a failed
+    /// probe backs off; one that succeeds is dropped with `resumed`.
+    pub fn take_due(&mut self, now: Instant) -> Vec<u32> {
+        let max = self.config.max;
+        let mut due: Vec<u32> = self
+            .due
+            .iter_mut()
+            .filter(|(_, (at, _))| *at <= now)
+            .map(|(relation_id, (at, delay))| {
+                *delay = (*delay * 2).min(max);
+                *at = now + *delay;
+                *relation_id
+            })
+            .collect();
+        due.sort_unstable();
+        due
+    }
+
+    /// Delay before the next probe of a relation, if it is scheduled
+    pub fn next_delay(&self, relation_id: u32) -> Option<Duration> {
+        self.due.get(&relation_id).map(|(_, delay)| *delay)
+    }
+}
+
+/// Posts the pausing and resuming of tables paused on a mismatch or
+/// quarantined to `PAUSED_TABLE_WEBHOOK_URL`. Posts are sent from their own task, so a slow
+/// endpoint never holds the pipeline; failures are logged.
+pub struct PauseNotifier {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl PauseNotifier {
+    /// `http` is the sink's proxy configuration, like for the WAL retention
+    /// webhook.
+    pub fn new(url: &str, http: &HttpClientConfig) -> Result<Self> {
+        Ok(Self {
+            url: url.to_string(),
+            client: client_builder(http)?.build()?,
+        })
+    }
+
+    pub fn paused(&self, diff: &SchemaDiff, held_rows: u64) {
+        self.post(paused_event(diff, held_rows));
+    }
+
+    pub fn quarantined(&self, table: &str, error: &str, held_rows: u64) {
+        self.post(quarantined_event(table, error, held_rows));
+    }
+
+    pub fn resumed(&self, table: &str, written_rows: usize) {
+        self.post(resumed_event(table, written_rows));
+    }
+
+    fn post(&self, event: serde_json::Value) {
+        let request = self
+            .client
+            .post(self.url.as_str())
+            .timeout(Duration::from_secs(10))
+            .json(&event);
+        tokio::spawn(async move {
+            // Neither the URL nor reqwest's error (which repeats it) is
+            // logged: the URL may carry a token
+            let status = match request.send().await {
+                Ok(response) if response.status().is_success() => return,
+                Ok(response) => response.status().to_string(),
+                Err(e) if e.is_timeout() => "timed out".to_string(),
+                Err(_) => "no response".to_string(),
+            };
+            warn!(
+                "[SCHEMA] Failed to post {} to PAUSED_TABLE_WEBHOOK_URL: {}",
+                event["event"], status
+            );
+        });
+    }
+}
+
+/// Body posted when a table is paused on a mismatch (`table_paused`)
+fn paused_event(diff: &SchemaDiff, held_rows: u64) -> serde_json::Value {
+    json!({
+        "event": "table_paused",
+        "table": diff.table,
+        "reason": diff.to_string(),
+        "held_rows": held_rows,
+        "at": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Body posted when a table is quarantined after a sink error, with the
+/// error as the reason (`table_paused`)
+fn quarantined_event(table: &str, error: &str, held_rows: u64) -> serde_json::Value {
+    json!({
+        "event": "table_paused",
+        "table": table,
+        "reason": error,
+        "held_rows": held_rows,
+        "at": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Body posted when a paused or quarantined table resumes (`table_resumed`)
+fn resumed_event(table: &str, written_rows: usize) -> serde_json::Value {
+    json!({
+        "event": "table_resumed",
+        "table": table,
+        "written_rows": written_rows,
+        "at": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        paused_event, quarantined_event, resumed_event, ProbeSchedule, TableControlRequest,
+        TablePauseMode,
+    };
+    use crate::config::PausedTableProbeConfig;
+    use crate::core::schema_diff::SchemaDiff;
+    use crate::core::sink_error::SinkError;
+    use crate::grpc::control_error::{ControlError, ErrorCode};
+    use crate::grpc::state::{CdcConfig, SharedState};
+    use crate::pipeline::invalidate::InvalidateRequest;
+    use crate::pipeline::schema_cache::{SchemaCache, SchemaDelta};
+    use crate::pipeline::Pipeline;
+    use crate::sink::{BatchResult, Sink};
+    use crate::source::parser::{CdcMessage, Column, Tuple, TupleData};
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::{mpsc, oneshot};
+    use tokio::time::Instant;
+
+    /// Refuses rows of `public.orders` until `fixed` is set, recording the
+    /// relations of the rows it writes
+    #[derive(Default)]
+    struct OrdersSink {
+        fixed: Arc<AtomicBool>,
+        written: Arc<Mutex<Vec<u32>>>,
+        /// Refuses them with a terminal error rather than a schema mismatch
+        terminal: bool,
+    }
+
+    #[async_trait]
+    impl Sink for OrdersSink {
+        async fn push_batch(
+            &mut self,
+            batch: &[CdcMessage],
+            _: &SchemaCache,
+            _: u64,
+        ) -> anyhow::Result<BatchResult> {
+            let relations: Vec<u32> = batch
+                .iter()
+                .filter_map(|msg| match msg {
+                    CdcMessage::Insert { relation_id, .. } => Some(*relation_id),
+                    _ => None,
+                })
+                .collect();
+            if !self.fixed.load(Ordering::Relaxed) && relations.contains(&1) {
+                if self.terminal {
+                    return Err(SinkError::terminal("test", "value too long for note").into());
+                }
+                return Err(SinkError::schema_mismatch("test", "no such column: note").into());
+            }
+            self.written.lock().extend(relations);
+            Ok(BatchResult::default())
+        }
+
+        async fn apply_schema_delta(&self, _: &SchemaDelta) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn schema_diff(&self, schema: &SchemaDelta) -> anyhow::Result<Option<SchemaDiff>> {
+            let expected: Vec<(String, String)> = schema
+                .added_columns
+                .iter()
+                .map(|c| (c.name.clone(), "TEXT".to_string()))
+                .collect();
+            let mut actual = expected.clone();
+            if schema.table_name == "orders" && !self.fixed.load(Ordering::Relaxed) {
+                actual.retain(|(name, _)| name != "note");
+            }
+            Ok(Some(SchemaDiff::compare(
+                &schema.qualified_name(),
+                &schema.table_name,
+                &expected,
+                Some(&actual),
+            )))
+        }
+    }
+
+    fn relation(id: u32, name: &str, columns: &[&str]) -> CdcMessage {
+        CdcMessage::Relation {
+            id,
+            namespace: "public".into(),
+            name: name.into(),
+            replica_identity: b'd',
+            columns: columns
+                .iter()
+                .map(|name| Column {
+                    flags: 0,
+                    name: (*name).into(),
+                    type_id: 25,
+                    type_mod: -1,
+                })
+                .collect(),
+        }
+    }
+
+    fn insert(relation_id: u32) -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id,
+            tuple: Tuple {
+                cols: vec![TupleData::Text(Bytes::from_static(b"1"))],
+                toast_bitmap: 0,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_table_is_paused_until_fixed() {
+        let sink = OrdersSink::default();
+        let fixed = sink.fixed.clone();
+        let written = sink.written.clone();
+        let state = SharedState::new(CdcConfig {
+            flush_size: 100,
+            flush_interval_ms: 1000,
+            tables: vec!["public.orders".to_string()],
+            slot_name: "test_slot".to_string(),
+        });
+        let (_tx, rx) = mpsc::channel(1);
+        let (feedback_tx, mut feedback_rx) = mpsc::channel(4);
+        let mut pipeline = Pipeline::new(rx, Box::new(sink), 100, Duration::from_secs(1))
+            .with_shared_state(state.clone())
+            .with_feedback_channel(feedback_tx)
+            .with_relations(vec![
+                relation(1, "orders", &["id", "note"]),
+                relation(2, "customers", &["id"]),
+            ]);
+
+        // Customers are written, orders held, and nothing confirmed
+        assert!(
+            pipeline
+                .flush_batch(&[insert(1), insert(2), insert(1)], 0x10)
+                .await
+        );
+        assert_eq!(*written.lock(), vec![2]);
+        assert!(feedback_rx.try_recv().is_err());
+        let paused = state.paused_tables();
+        assert_eq!(paused.len(), 1);
+        assert_eq!(
+            paused[0].diff.to_string(),
+            "public.orders -> orders: missing column note (TEXT)"
+        );
+        assert_eq!(paused[0].held_rows, 2);
+
+        // Still broken: the table stays paused
+        let (reply, _) = oneshot::channel();
+        pipeline
+            .invalidate_sink_schema(InvalidateRequest { table: None, reply })
+            .await;
+        assert_eq!(state.paused_tables().len(), 1);
+
+        fixed.store(true, Ordering::Relaxed);
+        let (reply, _) = oneshot::channel();
+        pipeline
+            .invalidate_sink_schema(InvalidateRequest { table: None, reply })
+            .await;
+        assert!(state.paused_tables().is_empty());
+        assert_eq!(*written.lock(), vec![2, 1, 1]);
+        assert_eq!(feedback_rx.try_recv().unwrap(), 0x10);
+    }
+
+    async fn control(
+        pipeline: &mut Pipeline,
+        table: &str,
+        pause: Option<TablePauseMode>,
+    ) -> Result<String, ControlError> {
+        let (reply, reply_rx) = oneshot::channel();
+        pipeline
+            .control_table(TableControlRequest {
+                table: table.to_string(),
+                pause,
+                reply,
+            })
+            .await;
+        reply_rx.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_table_paused_by_operator() {
+        let sink = OrdersSink::default();
+        sink.fixed.store(true, Ordering::Relaxed);
+        let written = sink.written.clone();
+        let state = SharedState::new(CdcConfig {
+            flush_size: 100,
+            flush_interval_ms: 1000,
+            tables: vec!["public.orders".to_string()],
+            slot_name: "test_slot".to_string(),
+        });
+        let (_tx, rx) = mpsc::channel(1);
+        let mut pipeline = Pipeline::new(rx, Box::new(sink), 100, Duration::from_secs(1))
+            .with_shared_state(state.clone())
+            .with_relations(vec![
+                relation(1, "orders", &["id", "note"]),
+                relation(2, "customers", &["id"]),
+            ]);
+
+        // Buffered rows are written on resume
+        let buffer = Some(TablePauseMode::Buffer);
+        assert!(control(&mut pipeline, "orders", buffer).await.is_ok());
+        let refused = control(&mut pipeline, "public.orders", buffer)
+            .await
+            .unwrap_err();
+        assert!(refused.message.contains("already paused"));
+        assert_eq!(refused.code, ErrorCode::AlreadyDone);
+        assert_eq!(refused.table.as_deref(), Some("public.orders"));
+        assert!(pipeline.hold(1, insert(1), 0x20).await);
+        assert!(pipeline.hold(1, insert(1), 0x30).await);
+        let status = state.table_status(Some("public.orders"));
+        assert_eq!(status[0].pause_state(), "buffering");
+        assert_eq!(status[0].pause.unwrap().held_rows, 2);
+        let resumed = control(&mut pipeline, "orders", None).await.unwrap();
+        assert_eq!(resumed, "public.orders resumed, 2 held rows written");
+        assert_eq!(*written.lock(), vec![1, 1]);
+        assert_eq!(
+            state.table_status(Some("public.orders"))[0].pause_state(),
+            "running"
+        );
+
+        // Skipped rows are dropped and the table marked for a re-sync
+        let skip = Some(TablePauseMode::Skip);
+        assert!(control(&mut pipeline, "customers", skip).await.is_ok());
+        pipeline.skip(2, 0x40);
+        pipeline.skip(2, 0x50);
+        let resumed = control(&mut pipeline, "customers", None).await.unwrap();
+        assert!(resumed.contains("rows from LSN 0x40 were dropped"));
+        let status = state.table_status(Some("public.customers"));
+        assert_eq!(status[0].resync_from_lsn, Some(0x40));
+        let refused = control(&mut pipeline, "customers", None).await.unwrap_err();
+        assert_eq!(refused.code, ErrorCode::InvalidState);
+    }
+
+    fn probe_config() -> PausedTableProbeConfig {
+        PausedTableProbeConfig {
+            initial: Duration::from_secs(60),
+            max: Duration::from_secs(200),
+        }
+    }
+
+    #[test]
+    fn test_probes_back_off() {
+        let start = Instant::now();
+        let mut probes = ProbeSchedule::new(probe_config());
+        probes.paused(1, start);
+        probes.paused(2, start + Duration::from_secs(30));
+        // Pausing again keeps the probe scheduled
+        probes.paused(1, start + Duration::from_secs(50));
+
+        assert!(probes.take_due(start + Duration::from_secs(59)).is_empty());
+        let at = start + Duration::from_secs(60);
+        assert_eq!(probes.take_due(at), vec![1]);
+        assert_eq!(probes.next_delay(1), Some(Duration::from_secs(120)));
+        let at = at + Duration::from_secs(120);
+        assert_eq!(probes.take_due(at), vec![1, 2]);
+        assert_eq!(probes.next_delay(1), Some(Duration::from_secs(200)));
+        assert_eq!(probes.next_delay(2), Some(Duration::from_secs(120)));
+
+        probes.resumed(1);
+        assert_eq!(probes.next_delay(1), None);
+        assert_eq!(probes.take_due(at + Duration::from_secs(3600)), vec![2]);
+    }
+
+    #[test]
+    fn test_notification_bodies() {
+        let diff = SchemaDiff::compare(
+            "public.orders",
+            "orders",
+            &[("note".to_string(), "TEXT".to_string())],
+            Some(&[][..]),
+        );
+        let event = paused_event(&diff, 2);
+        assert_eq!(event["event"], "table_paused");
+        assert_eq!(event["table"], "public.orders");
+        assert_eq!(
+            event["reason"],
+            "public.orders -> orders: missing column note (TEXT)"
+        );
+        assert_eq!(event["held_rows"], 2);
+
+        let event = quarantined_event("public.orders", "test sink: value too long", 2);
+        assert_eq!(event["event"], "table_paused");
+        assert_eq!(event["reason"], "test sink: value too long");
+        assert_eq!(event["held_rows"], 2);
+
+        let event = resumed_event("public.orders", 2);
+        assert_eq!(event["event"], "table_resumed");
+        assert_eq!(event["written_rows"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_table_is_probed_until_fixed() {
+        let sink = OrdersSink::default();
+        let fixed = sink.fixed.clone();
+        let written = sink.written.clone();
+        let state = SharedState::new(CdcConfig {
+            flush_size: 100,
+            flush_interval_ms: 1000,
+            tables: vec!["public.orders".to_string()],
+            slot_name: "test_slot".to_string(),
+        });
+        let (_tx, rx) = mpsc::channel(1);
+        let (feedback_tx, mut feedback_rx) = mpsc::channel(4);
+        let mut pipeline = Pipeline::new(rx, Box::new(sink), 100, Duration::from_secs(1))
+            .with_shared_state(state.clone())
+            .with_feedback_channel(feedback_tx)
+            .with_paused_table_probes(Some(probe_config()))
+            .with_relations(vec![relation(1, "orders", &["id", "note"])]);
+
+        let paused_at = Instant::now();
+        assert!(pipeline.flush_batch(&[insert(1)], 0x10).await);
+        assert_eq!(state.paused_tables().len(), 1);
+
+        // Not due yet, then due but still broken: the next probe backs off
+        pipeline.probe_paused_tables(paused_at).await;
+        pipeline
+            .probe_paused_tables(paused_at + Duration::from_secs(61))
+            .await;
+        assert_eq!(state.paused_tables().len(), 1);
+        assert!(written.lock().is_empty());
+
+        fixed.store(true, Ordering::Relaxed);
+        pipeline
+            .probe_paused_tables(paused_at + Duration::from_secs(120))
+            .await;
+        assert_eq!(state.paused_tables().len(), 1);
+        pipeline
+            .probe_paused_tables(paused_at + Duration::from_secs(200))
+            .await;
+        assert!(state.paused_tables().is_empty());
+        assert_eq!(*written.lock(), vec![1]);
+        assert_eq!(feedback_rx.try_recv().unwrap(), 0x10);
+    }
+
+    #[tokio::test]
+    async fn test_failing_table_is_quarantined_and_probed() {
+        let sink = OrdersSink {
+            terminal: true,
+            ..Default::default()
+        };
+        let fixed = sink.fixed.clone();
+        let written = sink.written.clone();
+        let state = SharedState::new(CdcConfig {
+            flush_size: 100,
+            flush_interval_ms: 1000,
+            tables: vec!["public.orders".to_string()],
+            slot_name: "test_slot".to_string(),
+        });
+        let (_tx, rx) = mpsc::channel(1);
+        let (feedback_tx, mut feedback_rx) = mpsc::channel(4);
+        let mut pipeline = Pipeline::new(rx, Box::new(sink), 100, Duration::from_secs(1))
+            .with_shared_state(state.clone())
+            .with_feedback_channel(feedback_tx)
+            .with_paused_table_probes(Some(probe_config()))
+            .with_relations(vec![
+                relation(1, "orders", &["id", "note"]),
+                relation(2, "customers", &["id"]),
+            ]);
+
+        // Customers are written, orders quarantined, and nothing confirmed
+        let paused_at = Instant::now();
+        assert!(
+            pipeline
+                .flush_batch(&[insert(1), insert(2), insert(1)], 0x10)
+                .await
+        );
+        assert_eq!(*written.lock(), vec![2]);
+        assert!(feedback_rx.try_recv().is_err());
+        let paused = state.paused_tables();
+        assert_eq!(paused.len(), 1);
+        assert_eq!(paused[0].diff.table, "public.orders");
+        assert_eq!(
+            paused[0].sink_error.as_deref(),
+            Some("test sink: value too long for note")
+        );
+        assert_eq!(paused[0].held_rows, 2);
+
+        // A probe that fails keeps it quarantined; the next one writes its rows
+        pipeline
+            .probe_paused_tables(paused_at + Duration::from_secs(61))
+            .await;
+        assert_eq!(state.paused_tables().len(), 1);
+        fixed.store(true, Ordering::Relaxed);
+        pipeline
+            .probe_paused_tables(paused_at + Duration::from_secs(200))
+            .await;
+        assert!(state.paused_tables().is_empty());
+        assert_eq!(*written.lock(), vec![2, 1, 1]);
+        assert_eq!(feedback_rx.try_recv().unwrap(), 0x10);
+
+        // A failing batch of a single table stops the pipeline
+        fixed.store(false, Ordering::Relaxed);
+        assert!(!pipeline.flush_batch(&[insert(1)], 0x20).await);
+        assert!(state.paused_tables().is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+//! In-flight batch inspection (`PeekBatch`).
+//!
+//! The gRPC service sends a [`PeekRequest`] to the pipeline, which answers
+//! between events, or while a flush is in progress, with a sample of the batch
+//! it is holding. Column values are redacted to their size unless an operator
+//! asked to reveal them.
+
+use tokio::sync::oneshot;
+
+use crate::pipeline::schema_cache::SchemaCache;
+use crate::source::parser::{CdcMessage, Tuple, TupleData};
+
+/// Events returned when the request doesn't set a limit
+pub const DEFAULT_PEEK_LIMIT: usize = 10;
+/// Upper bound on events returned by one request
+pub const MAX_PEEK_LIMIT: usize = 100;
+/// Revealed values longer than this are cut
+const MAX_VALUE_CHARS: usize = 256;
+
+/// A `PeekBatch` call waiting for the pipeline.
+pub struct PeekRequest {
+    /// Events to return, already clamped to [`MAX_PEEK_LIMIT`]
+    pub limit: usize,
+    /// Only events of this table (`schema.table` or bare name)
+    pub table: Option<String>,
+    /// Return column values instead of their size (operator only)
+    pub reveal_values: bool,
+    pub reply: oneshot::Sender<BatchSample>,
+}
+
+impl PeekRequest {
+    /// Answers with a sample of `batch`. A caller that gave up is ignored.
+    pub fn answer(
+        self,
+        batch: &[CdcMessage],
+        schema_cache: &SchemaCache,
+        flushing: bool,
+        lsn: u64,
+    ) {
+        let sample = BatchSample {
+            pending_events: batch.len(),
+            flushing,
+            lsn,
+            values_redacted: !self.reveal_values,
+            events: sample_events(
+                batch,
+                schema_cache,
+                self.limit,
+                self.table.as_deref(),
+                self.reveal_values,
+            ),
+        };
+        let _ = self.reply.send(sample);
+    }
+}
+
+/// What the pipeline is holding when a `PeekBatch` call arrives.
+#[derive(Debug, Clone, Default)]
+pub struct BatchSample {
+    /// Messages in the batch, including transaction boundaries
+    pub pending_events: usize,
+    /// The batch is being written to the sink
+    pub flushing: bool,
+    /// LSN of the last message added to the batch
+    pub lsn: u64,
+    pub values_redacted: bool,
+    /// Oldest first
+    pub events: Vec<SampledEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledEvent {
+    /// `insert`, `update` or `delete`
+    pub op: &'static str,
+    /// `schema.table`, or `rel_<id>` when the relation is unknown
+    pub table: String,
+    /// New row for inserts and updates, old key or row for deletes
+    pub columns: Vec<SampledColumn>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledColumn {
+    pub name: String,
+    pub type_oid: u32,
+    pub key: bool,
+    pub is_null: bool,
+    pub unchanged_toast: bool,
+    /// Text value when revealed, else empty
+    pub value: String,
+    pub size_bytes: usize,
+}
+
+pub(crate) fn sample_events(
+    batch: &[CdcMessage],
+    schema_cache: &SchemaCache,
+    limit: usize,
+    table: Option<&str>,
+    reveal_values: bool,
+) -> Vec<SampledEvent> {
+    batch
+        .iter()
+        .filter_map(|msg| match msg {
+            CdcMessage::Insert { relation_id, tuple } => Some(("insert", *relation_id, tuple)),
+            CdcMessage::Update {
+                relation_id,
+                new_tuple,
+                ..
+            } => Some(("update", *relation_id, new_tuple)),
+            CdcMessage::Delete {
+                relation_id,
+                old_tuple: Some(tuple),
+            } => Some(("delete", *relation_id, tuple)),
+            _ => None,
+        })
+        .filter_map(|(op, relation_id, tuple)| {
+            let schema = schema_cache.get(relation_id);
+            let name = match schema {
+                Some(s) => format!("{}.{}", s.namespace, s.name),
+                None => format!("rel_{}", relation_id),
+            };
+            if let Some(wanted) = table {
+                let bare = schema.map(|s| &*s.name);
+                if name != wanted && bare != Some(wanted) {
+                    return None;
+                }
+            }
+            let columns = match schema {
+                Some(s) => s
+                    .columns
+                    .iter()
+                    .zip(&tuple.cols)
+                    .map(|(col, data)| {
+                        sample_column(&col.name, col.type_id, col.is_key(), data, reveal_values)
+                    })
+                    .collect(),
+                None => unnamed_columns(tuple, reveal_values),
+            };
+            Some(SampledEvent {
+                op,
+                table: name,
+                columns,
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+/// Columns of a tuple whose Relation message hasn't been seen.
+fn unnamed_columns(tuple: &Tuple, reveal_values: bool) -> Vec<SampledColumn> {
+    tuple
+        .cols
+        .iter()
+        .enumerate()
+        .map(|(i, data)| sample_column(&format!("col_{}", i), 0, false, data, reveal_values))
+        .collect()
+}
+
+fn sample_column(
+    name: &str,
+    type_oid: u32,
+    key: bool,
+    data: &TupleData,
+    reveal_values: bool,
+) -> SampledColumn {
+    let mut column = SampledColumn {
+        name: name.to_string(),
+        type_oid,
+        key,
+        is_null: false,
+        unchanged_toast: false,
+        value: String::new(),
+        size_bytes: 0,
+    };
+    match data {
+        TupleData::Null => column.is_null = true,
+        TupleData::Toast => column.unchanged_toast = true,
+        TupleData::Text(bytes) | TupleData::Binary(bytes) => {
+            column.size_bytes = bytes.len();
+            if reveal_values {
+                column.value = data
+                    .text(type_oid)
+                    .unwrap_or_default()
+                    .chars()
+                    .take(MAX_VALUE_CHARS)
+                    .collect();
+            }
+        }
+    }
+    column
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::Column;
+    use bytes::Bytes;
+    use std::sync::Arc;
+
+    fn schema_cache() -> SchemaCache {
+        let mut cache = SchemaCache::new();
+        let column = |name: &str, type_id: u32, flags: u8| Column {
+            flags,
+            name: Arc::from(name),
+            type_id,
+            type_mod: -1,
+        };
+        cache.update(&CdcMessage::Relation {
+            id: 1,
+            namespace: Arc::from("public"),
+            name: Arc::from("users"),
+            replica_identity: b'd',
+            columns: vec![column("id", 23, 1), column("email", 25, 0)],
+        });
+        cache
+    }
+
+    fn insert(relation_id: u32, email: TupleData) -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id,
+            tuple: Tuple {
+                cols: vec![TupleData::Text(Bytes::from_static(b"7")), email],
+                toast_bitmap: 0,
+            },
+        }
+    }
+
+    fn request(
+        reveal_values: bool,
+        table: Option<&str>,
+    ) -> (PeekRequest, oneshot::Receiver<BatchSample>) {
+        let (reply, rx) = oneshot::channel();
+        let request = PeekRequest {
+            limit: DEFAULT_PEEK_LIMIT,
+            table: table.map(str::to_string),
+            reveal_values,
+            reply,
+        };
+        (request, rx)
+    }
+
+    #[test]
+    fn test_values_are_redacted_by_default() {
+        let batch = [
+            insert(1, TupleData::Text(Bytes::from_static(b"ana@example.com"))),
+            insert(1, TupleData::Null),
+        ];
+        let (peek, mut rx) = request(false, None);
+        peek.answer(&batch, &schema_cache(), true, 0x10);
+
+        let sample = rx.try_recv().unwrap();
+        assert_eq!(sample.pending_events, 2);
+        assert!(sample.flushing);
+        assert!(sample.values_redacted);
+        let email = &sample.events[0].columns[1];
+        assert_eq!(sample.events[0].table, "public.users");
+        assert_eq!(email.name, "email");
+        assert_eq!(email.size_bytes, 15);
+        assert!(email.value.is_empty());
+        assert!(sample.events[0].columns[0].key);
+        assert!(sample.events[1].columns[1].is_null);
+    }
+
+    #[test]
+    fn test_reveal_and_table_filter() {
+        let batch = [
+            insert(2, TupleData::Toast),
+            insert(1, TupleData::Text(Bytes::from_static(b"ana@example.com"))),
+        ];
+        let (peek, mut rx) = request(true, Some("users"));
+        peek.answer(&batch, &schema_cache(), false, 0);
+
+        let sample = rx.try_recv().unwrap();
+        assert!(!sample.values_redacted);
+        assert_eq!(sample.events.len(), 1);
+        assert_eq!(sample.events[0].columns[1].value, "ana@example.com");
+
+        // Unknown relations are still shown, with positional column names
+        let (peek, mut rx) = request(false, None);
+        peek.answer(&batch, &schema_cache(), false, 0);
+        let sample = rx.try_recv().unwrap();
+        assert_eq!(sample.events[0].table, "rel_2");
+        assert_eq!(sample.events[0].columns[1].name, "col_1");
+        assert!(sample.events[0].columns[1].unchanged_toast);
+    }
+}
@@ -0,0 +1,242 @@
+//! Retries of batches the sink failed to write (`SINK_RETRY_*`).
+//!
+//! A failed batch is written again after an exponential backoff, as long as
+//! the error is retryable (see `core::sink_error`). Once the attempts run
+//! out, the circuit breaker opens: the pipeline holds the batch, and with it
+//! the stream, for `SINK_CIRCUIT_OPEN_SECS`, then probes the sink with one
+//! more attempt. A success closes the circuit, a failure re-opens it. The
+//! state is published in `SharedState` for `GetStatus` and `/metrics`.
+//!
+//! The StarRocks, ClickHouse and remote sinks send a request up to 3 times
+//! within one attempt, so their batches are sent up to 3 × `max_attempts`
+//! times before the attempts run out.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::config::SinkRetryConfig;
+
+/// When and how often a failed batch is written again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts before giving up or opening the circuit, at least 1
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// How long the circuit stays open; `None` stops the pipeline instead
+    pub circuit_open_for: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// A single attempt and no circuit breaker: the first failure stops the
+    /// pipeline.
+    pub fn once() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            circuit_open_for: None,
+        }
+    }
+
+    /// Wait after failed attempt `attempt` (1-based): the initial backoff
+    /// doubled per attempt up to the maximum, of which a random half is
+    /// taken off so retries of several instances spread out.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        exp / 2 + jitter(exp / 2)
+    }
+}
+
+impl From<&SinkRetryConfig> for RetryPolicy {
+    fn from(config: &SinkRetryConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            initial_backoff: config.initial_backoff,
+            max_backoff: config.max_backoff,
+            circuit_open_for: config.circuit_breaker.then_some(config.circuit_open_for),
+        }
+    }
+}
+
+/// A random duration in `[0, max]`.
+fn jitter(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::sink_error::SinkError;
+    use crate::grpc::state::{CdcConfig, CircuitState, SharedState};
+    use crate::pipeline::schema_cache::{SchemaCache, SchemaDelta};
+    use crate::pipeline::Pipeline;
+    use crate::sink::{BatchResult, Sink};
+    use crate::source::parser::CdcMessage;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    /// Fails the first `failures` writes, with a terminal error if `terminal`
+    struct FlakySink {
+        failures: u32,
+        terminal: bool,
+        calls: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl Sink for FlakySink {
+        async fn push_batch(
+            &mut self,
+            _: &[CdcMessage],
+            _: &SchemaCache,
+            _: u64,
+        ) -> anyhow::Result<BatchResult> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call >= self.failures {
+                Ok(BatchResult {
+                    rows_written: 8,
+                    rows_filtered: 2,
+                    warnings: vec!["2 rows filtered".to_string()],
+                    ..Default::default()
+                })
+            } else if self.terminal {
+                Err(SinkError::terminal("test", "authentication failed").into())
+            } else {
+                Err(anyhow::anyhow!("connection refused"))
+            }
+        }
+
+        async fn apply_schema_delta(&self, _: &SchemaDelta) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn flaky_pipeline(
+        failures: u32,
+        terminal: bool,
+        policy: RetryPolicy,
+    ) -> (Pipeline, Arc<AtomicU32>, Arc<SharedState>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let sink = FlakySink {
+            failures,
+            terminal,
+            calls: calls.clone(),
+        };
+        let state = SharedState::new(CdcConfig {
+            flush_size: 100,
+            flush_interval_ms: 100,
+            tables: vec![],
+            slot_name: "test".into(),
+        });
+        let (_tx, rx) = mpsc::channel(1);
+        let pipeline = Pipeline::new(rx, Box::new(sink), 100, Duration::from_secs(1))
+            .with_shared_state(state.clone())
+            .with_retry_policy(policy);
+        (pipeline, calls, state)
+    }
+
+    fn policy(max_attempts: u32, circuit_open_for: Option<Duration>) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+            circuit_open_for,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retryable_failures_are_retried() {
+        let (mut pipeline, calls, state) = flaky_pipeline(2, false, policy(3, None));
+        pipeline.push_with_retry(&[], 0x10).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(state.sink_retries(), 2);
+
+        let (mut pipeline, calls, _) = flaky_pipeline(5, false, policy(3, None));
+        let err = pipeline.push_with_retry(&[], 0x10).await.unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(format!("{:#}", err).contains("after 3 attempts: connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_result_of_retried_write_is_recorded() {
+        let (mut pipeline, _, state) = flaky_pipeline(1, false, policy(3, None));
+        assert!(pipeline.flush_batch(&[], 0x10).await);
+        assert_eq!(state.sink_rows_written(), 8);
+        assert_eq!(state.sink_rows_filtered(), 2);
+        // The failed attempt is only a retry; the warning is recorded
+        let errors = state.recent_errors(10).await;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Sink warning: 2 rows filtered");
+        assert_eq!(errors[0].lsn, 0x10);
+    }
+
+    #[tokio::test]
+    async fn test_terminal_failure_is_not_retried() {
+        let (mut pipeline, calls, state) =
+            flaky_pipeline(1, true, policy(5, Some(Duration::from_millis(5))));
+        pipeline.push_with_retry(&[], 0x10).await.unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(state.sink_retries(), 0);
+        assert_eq!(state.sink_circuit(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_and_closes_on_recovery() {
+        // 2 attempts, then the circuit opens; probes fail twice more
+        let (mut pipeline, calls, state) =
+            flaky_pipeline(4, false, policy(2, Some(Duration::from_millis(5))));
+        pipeline.push_with_retry(&[], 0x10).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+        assert_eq!(state.sink_retries(), 4);
+        assert_eq!(state.sink_circuit(), CircuitState::Closed);
+        // Opening is recorded once, not on every failed probe
+        assert_eq!(state.total_errors(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_interrupts_open_circuit() {
+        let (mut pipeline, _, state) =
+            flaky_pipeline(u32::MAX, false, policy(1, Some(Duration::from_secs(3600))));
+        state.shutdown_tx.send_replace(true);
+        let err = pipeline.push_with_retry(&[], 0x10).await.unwrap_err();
+        assert!(err.to_string().contains("Shutting down"));
+        assert_eq!(state.sink_circuit(), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_backoff_doubles_within_bounds() {
+        let policy = RetryPolicy::from(&SinkRetryConfig {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            circuit_breaker: true,
+            circuit_open_for: Duration::from_secs(30),
+        });
+        for (attempt, exp) in [(1, 100), (2, 200), (3, 400), (4, 500), (40, 500)] {
+            let delay = policy.backoff(attempt);
+            assert!(delay >= Duration::from_millis(exp / 2), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(exp), "{:?}", delay);
+        }
+        assert_eq!(policy.circuit_open_for, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_once_and_disabled_breaker() {
+        assert_eq!(RetryPolicy::once().max_attempts, 1);
+        assert_eq!(RetryPolicy::once().backoff(1), Duration::ZERO);
+
+        let policy = RetryPolicy::from(&SinkRetryConfig {
+            circuit_breaker: false,
+            ..Default::default()
+        });
+        assert_eq!(policy.circuit_open_for, None);
+        assert_eq!(policy.max_attempts, 5);
+    }
+}
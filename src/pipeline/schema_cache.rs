@@ -1,23 +1,126 @@
 use crate::source::parser::{CdcMessage, Column};
 use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
+/// Schema of one relation. Names are interned by the parser and shared with
+/// every record built from it.
 #[derive(Debug, Clone)]
 pub struct TableSchema {
     #[allow(dead_code)]
     pub id: u32,
     #[allow(dead_code)]
-    pub namespace: String,
-    pub name: String,
+    pub namespace: Arc<str>,
+    pub name: Arc<str>,
+    pub replica_identity: u8,
     pub columns: Vec<Column>,
 }
 
-#[derive(Debug, Clone)]
+impl TableSchema {
+    /// `namespace.name`
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.namespace, self.name)
+    }
+
+    /// A delta adding every column of the table. Sinks apply deltas
+    /// idempotently, so this re-creates whatever the sink table is missing.
+    pub fn full_delta(&self) -> SchemaDelta {
+        SchemaDelta {
+            table_name: self.name.to_string(),
+            namespace: self.namespace.to_string(),
+            added_columns: self
+                .columns
+                .iter()
+                .map(|c| AddedColumn {
+                    name: c.name.to_string(),
+                    pg_type_id: c.type_id,
+                    type_mod: c.type_mod,
+                })
+                .collect(),
+            ..SchemaDelta::default()
+        }
+    }
+}
+
+/// Columns of a table changed since its previous Relation message.
+///
+/// JSON shape: `{"table_name":"orders","namespace":"public","added_columns":[{"name":"note","pg_type_id":25,"type_mod":-1}],"dropped_columns":[{"name":"memo","pg_type_id":25,"type_mod":-1}],"renamed_columns":[{"old_name":"qty","new_name":"quantity"}],"retyped_columns":[{"name":"total","old_type_id":23,"pg_type_id":1700,"type_mod":-1}],"columns":[{"name":"note","nullable":true,"comment":"Free text"}]}`
+///
+/// Every field but `table_name` and `added_columns` is optional on input,
+/// so deltas written by older versions still deserialize.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SchemaDelta {
     pub table_name: String,
+    /// Source schema of the table
+    #[serde(default)]
+    pub namespace: String,
+    /// Columns new to the table, renamed ones included under their new name
+    /// so that sinks which don't rename still get a column to write to
     pub added_columns: Vec<AddedColumn>,
+    /// Columns no longer in the table
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped_columns: Vec<AddedColumn>,
+    /// Columns that took a new name. pgoutput does not say so: a column is
+    /// taken as renamed when it is the only change at its position and kept
+    /// its type, so a column dropped and another of the same type added in
+    /// its place in one step look the same.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub renamed_columns: Vec<RenamedColumn>,
+    /// Columns gone from the table in a delta that both drops and renames:
+    /// a drop shifts the columns after it, so which one was dropped and
+    /// which renamed can't be told by position. Sinks keep them as they are.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unmatched_columns: Vec<AddedColumn>,
+    /// Columns whose type (or type modifier) changed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retyped_columns: Vec<RetypedColumn>,
+    /// Nullability and comment of every column, read from the source catalog
+    /// before the delta is applied. Empty when the catalog could not be read.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<ColumnAttributes>,
 }
 
-#[derive(Debug, Clone)]
+impl SchemaDelta {
+    /// `namespace.table_name`, or the bare name when the namespace is unknown
+    pub fn qualified_name(&self) -> String {
+        if self.namespace.is_empty() {
+            self.table_name.clone()
+        } else {
+            format!("{}.{}", self.namespace, self.table_name)
+        }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnAttributes> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Whether the delta changes columns other than adding them
+    pub fn has_other_changes(&self) -> bool {
+        !self.dropped_columns.is_empty()
+            || !self.renamed_columns.is_empty()
+            || !self.retyped_columns.is_empty()
+    }
+
+    /// `"2 added, 1 dropped"`, for logs
+    pub fn summary(&self) -> String {
+        let renamed = self.renamed_columns.len();
+        let counts = [
+            (self.added_columns.len().saturating_sub(renamed), "added"),
+            (self.dropped_columns.len(), "dropped"),
+            (renamed, "renamed"),
+            (self.unmatched_columns.len(), "dropped or renamed"),
+            (self.retyped_columns.len(), "retyped"),
+        ];
+        counts
+            .iter()
+            .filter(|(n, _)| *n > 0)
+            .map(|(n, kind)| format!("{} {}", n, kind))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddedColumn {
     #[allow(dead_code)]
     pub name: String,
@@ -27,6 +130,251 @@ pub struct AddedColumn {
     pub type_mod: i32,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenamedColumn {
+    pub old_name: String,
+    pub new_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetypedColumn {
+    pub name: String,
+    pub old_type_id: u32,
+    pub pg_type_id: u32,
+    pub type_mod: i32,
+}
+
+/// Catalog attributes of a source column that pgoutput does not carry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnAttributes {
+    pub name: String,
+    pub nullable: bool,
+    /// `COMMENT ON COLUMN` text, if any
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// One version of a relation's schema: its Relation message as PostgreSQL
+/// sent it, numbered per relation OID, and the LSN it took effect at (0 when
+/// it predates the stream, e.g. handed over by the active instance).
+///
+/// JSON shape: `{"version":2,"lsn":23456,"relation":{"Relation":{"id":16384,...}}}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationVersion {
+    pub version: u32,
+    pub lsn: u64,
+    pub relation: CdcMessage,
+}
+
+impl RelationVersion {
+    /// First version of a relation known from before the stream
+    pub fn initial(relation: CdcMessage) -> Self {
+        Self {
+            version: 1,
+            lsn: 0,
+            relation,
+        }
+    }
+
+    pub fn relation_id(&self) -> Option<u32> {
+        match &self.relation {
+            CdcMessage::Relation { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+/// Schema versions of each relation from the one in effect at the checkpoint
+/// on. They are stored with the checkpoint, so that after a restart the rows
+/// streamed again decode against the columns they were written with, even
+/// when the table changed after the checkpoint.
+#[derive(Default)]
+pub struct SchemaHistory {
+    versions: HashMap<u32, Vec<RelationVersion>>,
+}
+
+impl SchemaHistory {
+    /// Records a Relation message seen at `lsn`. One matching the relation's
+    /// last version, as pgoutput resends them, isn't a new version.
+    pub fn record(&mut self, msg: &CdcMessage, lsn: u64) {
+        let CdcMessage::Relation { id, .. } = msg else {
+            return;
+        };
+        let versions = self.versions.entry(*id).or_default();
+        let last = versions.last();
+        if last.is_some_and(|v| same_schema(&v.relation, msg)) {
+            return;
+        }
+        let version = last.map_or(1, |v| v.version + 1);
+        versions.push(RelationVersion {
+            version,
+            lsn,
+            relation: msg.clone(),
+        });
+    }
+
+    /// Adds a version read back from the checkpoint store, as it was.
+    pub fn restore(&mut self, version: RelationVersion) {
+        if let Some(id) = version.relation_id() {
+            self.versions.entry(id).or_default().push(version);
+        }
+    }
+
+    /// Drops the versions a later one replaced at or before `checkpoint_lsn`.
+    pub fn prune(&mut self, checkpoint_lsn: u64) {
+        for versions in self.versions.values_mut() {
+            let superseded = versions
+                .iter()
+                .skip(1)
+                .take_while(|v| v.lsn <= checkpoint_lsn)
+                .count();
+            versions.drain(..superseded);
+        }
+    }
+
+    pub fn forget(&mut self, id: u32) {
+        self.versions.remove(&id);
+    }
+
+    /// Every version, in relation id then version order
+    pub fn versions(&self) -> Vec<RelationVersion> {
+        let mut ids: Vec<&u32> = self.versions.keys().collect();
+        ids.sort();
+        ids.into_iter()
+            .flat_map(|id| self.versions[id].iter().cloned())
+            .collect()
+    }
+}
+
+/// The version of each relation in effect at `lsn`: the last one to take
+/// effect up to it, or the first of a relation only seen after it. An `lsn`
+/// of 0 (the slot's position) is past every version.
+pub fn schema_at(versions: Vec<RelationVersion>, lsn: u64) -> Vec<RelationVersion> {
+    let mut by_relation: HashMap<u32, Vec<RelationVersion>> = HashMap::new();
+    for version in versions {
+        if let Some(id) = version.relation_id() {
+            by_relation.entry(id).or_default().push(version);
+        }
+    }
+    let mut current: Vec<RelationVersion> = by_relation
+        .into_values()
+        .filter_map(|mut versions| {
+            versions.sort_by_key(|v| v.version);
+            let in_effect = versions
+                .iter()
+                .rposition(|v| lsn == 0 || v.lsn <= lsn)
+                .unwrap_or(0);
+            versions.into_iter().nth(in_effect)
+        })
+        .collect();
+    current.sort_by_key(|v| v.relation_id());
+    current
+}
+
+/// Whether two Relation messages describe the same table and columns
+fn same_schema(a: &CdcMessage, b: &CdcMessage) -> bool {
+    let (
+        CdcMessage::Relation {
+            namespace,
+            name,
+            replica_identity,
+            columns,
+            ..
+        },
+        CdcMessage::Relation {
+            namespace: other_namespace,
+            name: other_name,
+            replica_identity: other_identity,
+            columns: other_columns,
+            ..
+        },
+    ) = (a, b)
+    else {
+        return false;
+    };
+    let column = |c: &Column| (c.flags, c.name.clone(), c.type_id, c.type_mod);
+    namespace == other_namespace
+        && name == other_name
+        && replica_identity == other_identity
+        && columns.len() == other_columns.len()
+        && columns
+            .iter()
+            .zip(other_columns)
+            .all(|(a, b)| column(a) == column(b))
+}
+
+/// How the columns `prev` became `next`, matched by name, and by position
+/// for renames.
+fn column_changes(prev: &[Column], next: &[Column]) -> SchemaDelta {
+    let column = |c: &Column| AddedColumn {
+        name: c.name.to_string(),
+        pg_type_id: c.type_id,
+        type_mod: c.type_mod,
+    };
+    let prev_names: HashSet<&Arc<str>> = prev.iter().map(|c| &c.name).collect();
+    let next_names: HashSet<&Arc<str>> = next.iter().map(|c| &c.name).collect();
+
+    // A column gone from a position taken by a new column of the same type
+    let renamed_columns: Vec<RenamedColumn> = prev
+        .iter()
+        .zip(next)
+        .filter(|(old, new)| {
+            !next_names.contains(&old.name)
+                && !prev_names.contains(&new.name)
+                && (old.type_id, old.type_mod) == (new.type_id, new.type_mod)
+        })
+        .map(|(old, new)| RenamedColumn {
+            old_name: old.name.to_string(),
+            new_name: new.name.to_string(),
+        })
+        .collect();
+    let renamed_from = |name: &str| renamed_columns.iter().any(|r| r.old_name == name);
+    let gone: Vec<&Column> = prev
+        .iter()
+        .filter(|c| !next_names.contains(&c.name))
+        .collect();
+    let dropped_columns: Vec<AddedColumn> = gone
+        .iter()
+        .filter(|c| !renamed_from(&c.name))
+        .map(|c| column(c))
+        .collect();
+    // With [id, a, b] -> [id, c], a dropped and b renamed to c looks like a
+    // renamed to c and b dropped: neither is applied
+    let (renamed_columns, dropped_columns, unmatched_columns) =
+        if !renamed_columns.is_empty() && !dropped_columns.is_empty() {
+            let gone = gone.iter().map(|c| column(c)).collect();
+            (Vec::new(), Vec::new(), gone)
+        } else {
+            (renamed_columns, dropped_columns, Vec::new())
+        };
+
+    let retyped_columns = next
+        .iter()
+        .filter_map(|new| {
+            let old = prev.iter().find(|old| old.name == new.name)?;
+            ((old.type_id, old.type_mod) != (new.type_id, new.type_mod)).then(|| RetypedColumn {
+                name: new.name.to_string(),
+                old_type_id: old.type_id,
+                pg_type_id: new.type_id,
+                type_mod: new.type_mod,
+            })
+        })
+        .collect();
+
+    SchemaDelta {
+        added_columns: next
+            .iter()
+            .filter(|c| !prev_names.contains(&c.name))
+            .map(column)
+            .collect(),
+        dropped_columns,
+        renamed_columns,
+        unmatched_columns,
+        retyped_columns,
+        ..SchemaDelta::default()
+    }
+}
+
 pub struct SchemaCache {
     cache: HashMap<u32, TableSchema>,
 }
@@ -43,27 +391,15 @@ impl SchemaCache {
             id,
             namespace,
             name,
+            replica_identity,
             columns,
-            ..
         } = msg
         {
-            // Get previous schema (if exists)
-            let prev_columns: HashSet<String> = self
+            // Only a table seen before has changed
+            let delta = self
                 .cache
                 .get(id)
-                .map(|s| s.columns.iter().map(|c| c.name.clone()).collect())
-                .unwrap_or_default();
-
-            // Detect new columns
-            let added: Vec<AddedColumn> = columns
-                .iter()
-                .filter(|c| !prev_columns.contains(&c.name))
-                .map(|c| AddedColumn {
-                    name: c.name.clone(),
-                    pg_type_id: c.type_id,
-                    type_mod: c.type_mod,
-                })
-                .collect();
+                .map(|prev| column_changes(&prev.columns, columns));
 
             // Update cache
             self.cache.insert(
@@ -72,28 +408,325 @@ impl SchemaCache {
                     id: *id,
                     namespace: namespace.clone(),
                     name: name.clone(),
+                    replica_identity: *replica_identity,
                     columns: columns.clone(),
                 },
             );
 
-            // Return delta if there are new columns
-            // Only return if prev_columns is not empty (not the first time we see this table)
-            if !added.is_empty() && !prev_columns.is_empty() {
+            if let Some(delta) =
+                delta.filter(|d| !d.added_columns.is_empty() || d.has_other_changes())
+            {
                 return Some(SchemaDelta {
-                    table_name: name.clone(),
-                    added_columns: added,
+                    table_name: name.to_string(),
+                    namespace: namespace.to_string(),
+                    ..delta
                 });
             }
         }
         None
     }
 
+    /// Id of the relation whose cached column layout (names, types or order)
+    /// `msg` would replace. `None` for anything else, including the first
+    /// Relation message of a table and unchanged ones pgoutput resends.
+    pub fn changed_relation(&self, msg: &CdcMessage) -> Option<u32> {
+        let CdcMessage::Relation { id, columns, .. } = msg else {
+            return None;
+        };
+        let cached = self.cache.get(id)?;
+        let layout = |c: &Column| (c.name.clone(), c.type_id, c.type_mod);
+        let unchanged = cached.columns.len() == columns.len()
+            && cached
+                .columns
+                .iter()
+                .zip(columns)
+                .all(|(a, b)| layout(a) == layout(b));
+        (!unchanged).then_some(*id)
+    }
+
     pub fn get(&self, id: u32) -> Option<&TableSchema> {
         self.cache.get(&id)
     }
 
+    /// Relation messages equivalent to the cached schemas, in relation id order
+    pub fn relation_messages(&self) -> Vec<CdcMessage> {
+        self.schemas()
+            .into_iter()
+            .map(|s| CdcMessage::Relation {
+                id: s.id,
+                namespace: s.namespace.clone(),
+                name: s.name.clone(),
+                replica_identity: s.replica_identity,
+                columns: s.columns.clone(),
+            })
+            .collect()
+    }
+
+    /// Forgets the table named `schema.table`, returning its relation id.
+    pub fn remove_table(&mut self, qualified: &str) -> Option<u32> {
+        let id = self
+            .cache
+            .values()
+            .find(|s| format!("{}.{}", s.namespace, s.name) == qualified)?
+            .id;
+        self.cache.remove(&id);
+        Some(id)
+    }
+
+    /// Schema of a table named `schema.table`, or by its bare name in `public`
+    pub fn find(&self, table: &str) -> Option<&TableSchema> {
+        let (namespace, name) = table.split_once('.').unwrap_or(("public", table));
+        self.cache
+            .values()
+            .find(|s| &*s.namespace == namespace && &*s.name == name)
+    }
+
+    /// Every cached schema, in relation id order
+    pub fn schemas(&self) -> Vec<&TableSchema> {
+        let mut schemas: Vec<&TableSchema> = self.cache.values().collect();
+        schemas.sort_by_key(|s| s.id);
+        schemas
+    }
+
     #[allow(dead_code)]
     pub fn get_table_name(&self, id: u32) -> Option<String> {
-        self.cache.get(&id).map(|s| s.name.clone())
+        self.cache.get(&id).map(|s| s.name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_without_catalog_fields_deserializes() {
+        let delta: SchemaDelta = serde_json::from_str(
+            r#"{"table_name":"orders","added_columns":[{"name":"note","pg_type_id":25,"type_mod":-1}]}"#,
+        )
+        .unwrap();
+        assert_eq!(delta.qualified_name(), "orders");
+        assert!(delta.columns.is_empty());
+        assert!(!serde_json::to_string(&delta)
+            .unwrap()
+            .contains("\"columns\""));
+    }
+
+    #[test]
+    fn test_changed_relation() {
+        let relation = |columns: &[(&str, u32)]| CdcMessage::Relation {
+            id: 7,
+            namespace: "public".into(),
+            name: "orders".into(),
+            replica_identity: b'd',
+            columns: columns
+                .iter()
+                .map(|(name, type_id)| Column {
+                    flags: 0,
+                    name: (*name).into(),
+                    type_id: *type_id,
+                    type_mod: -1,
+                })
+                .collect(),
+        };
+        let mut cache = SchemaCache::new();
+        let v1 = relation(&[("id", 23), ("total", 1700)]);
+        assert_eq!(cache.changed_relation(&v1), None);
+        cache.update(&v1);
+        assert_eq!(cache.changed_relation(&v1), None);
+
+        // Added, dropped and retyped columns all change the layout
+        assert_eq!(
+            cache.changed_relation(&relation(&[("id", 23), ("total", 1700), ("note", 25)])),
+            Some(7)
+        );
+        assert_eq!(cache.changed_relation(&relation(&[("id", 23)])), Some(7));
+        assert_eq!(
+            cache.changed_relation(&relation(&[("id", 20), ("total", 1700)])),
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn test_delta_carries_namespace() {
+        let relation = |columns: &[&str]| CdcMessage::Relation {
+            id: 1,
+            namespace: "sales".into(),
+            name: "orders".into(),
+            replica_identity: b'd',
+            columns: columns
+                .iter()
+                .map(|name| Column {
+                    flags: 0,
+                    name: (*name).into(),
+                    type_id: 25,
+                    type_mod: -1,
+                })
+                .collect(),
+        };
+        let mut cache = SchemaCache::new();
+        assert!(cache.update(&relation(&["id"])).is_none());
+        let delta = cache.update(&relation(&["id", "note"])).unwrap();
+        assert_eq!(delta.qualified_name(), "sales.orders");
+        assert_eq!(delta.added_columns.len(), 1);
+
+        let full = cache.get(1).unwrap().full_delta();
+        assert_eq!(full.qualified_name(), "sales.orders");
+        let names: Vec<&str> = full.added_columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["id", "note"]);
+    }
+
+    #[test]
+    fn test_delta_reports_dropped_renamed_and_retyped_columns() {
+        let relation = |columns: &[(&str, u32)]| CdcMessage::Relation {
+            id: 1,
+            namespace: "public".into(),
+            name: "orders".into(),
+            replica_identity: b'd',
+            columns: columns
+                .iter()
+                .map(|(name, type_id)| Column {
+                    flags: 0,
+                    name: (*name).into(),
+                    type_id: *type_id,
+                    type_mod: -1,
+                })
+                .collect(),
+        };
+        let mut cache = SchemaCache::new();
+        cache.update(&relation(&[
+            ("id", 23),
+            ("qty", 23),
+            ("memo", 25),
+            ("total", 23),
+        ]));
+
+        // qty renamed in place, memo dropped, total retyped, in two steps
+        let delta = cache
+            .update(&relation(&[("id", 23), ("qty", 23), ("total", 23)]))
+            .unwrap();
+        assert_eq!(delta.dropped_columns[0].name, "memo");
+        assert!(delta.renamed_columns.is_empty());
+        let delta = cache
+            .update(&relation(&[("id", 23), ("quantity", 23), ("total", 1700)]))
+            .unwrap();
+        let added: Vec<&str> = delta
+            .added_columns
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(added, ["quantity"]);
+        assert_eq!(
+            delta.renamed_columns,
+            [RenamedColumn {
+                old_name: "qty".to_string(),
+                new_name: "quantity".to_string(),
+            }]
+        );
+        assert!(delta.dropped_columns.is_empty());
+        assert_eq!(
+            delta.retyped_columns,
+            [RetypedColumn {
+                name: "total".to_string(),
+                old_type_id: 23,
+                pg_type_id: 1700,
+                type_mod: -1,
+            }]
+        );
+        assert_eq!(delta.summary(), "1 renamed, 1 retyped");
+
+        // A drop shifting the columns after it is not a rename
+        let delta = cache
+            .update(&relation(&[("id", 23), ("total", 1700)]))
+            .unwrap();
+        assert!(delta.renamed_columns.is_empty());
+        assert_eq!(delta.dropped_columns[0].name, "quantity");
+        assert!(cache
+            .update(&relation(&[("id", 23), ("total", 1700)]))
+            .is_none());
+
+        // [id, a, b] -> [id, c]: a dropped and b renamed to c, or a renamed
+        // to c and b dropped, can't be told apart
+        cache.update(&relation(&[("id", 23), ("a", 23), ("b", 23)]));
+        let delta = cache.update(&relation(&[("id", 23), ("c", 23)])).unwrap();
+        assert!(delta.renamed_columns.is_empty());
+        assert!(delta.dropped_columns.is_empty());
+        let unmatched: Vec<&str> = delta
+            .unmatched_columns
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(unmatched, ["a", "b"]);
+        assert_eq!(delta.added_columns[0].name, "c");
+        assert_eq!(delta.summary(), "1 added, 2 dropped or renamed");
+    }
+
+    #[test]
+    fn test_remove_table() {
+        let relation = |id: u32, namespace: &str| CdcMessage::Relation {
+            id,
+            namespace: namespace.into(),
+            name: "orders".into(),
+            replica_identity: b'd',
+            columns: Vec::new(),
+        };
+        let mut cache = SchemaCache::new();
+        cache.update(&relation(1, "public"));
+        cache.update(&relation(2, "sales"));
+
+        assert_eq!(cache.find("sales.orders").map(|s| s.id), Some(2));
+        assert_eq!(cache.find("orders").map(|s| s.id), Some(1));
+        assert!(cache.find("other.orders").is_none());
+
+        assert_eq!(cache.remove_table("sales.orders"), Some(2));
+        assert_eq!(cache.remove_table("sales.orders"), None);
+        assert!(cache.get(1).is_some());
+        assert_eq!(cache.relation_messages().len(), 1);
+    }
+
+    #[test]
+    fn test_schema_history() {
+        let relation = |columns: &[&str]| CdcMessage::Relation {
+            id: 1,
+            namespace: "public".into(),
+            name: "orders".into(),
+            replica_identity: b'd',
+            columns: columns
+                .iter()
+                .map(|name| Column {
+                    flags: 0,
+                    name: (*name).into(),
+                    type_id: 25,
+                    type_mod: -1,
+                })
+                .collect(),
+        };
+        let mut history = SchemaHistory::default();
+        history.record(&relation(&["id"]), 0x100);
+        // Resent as it was
+        history.record(&relation(&["id"]), 0x180);
+        history.record(&relation(&["id", "note"]), 0x200);
+        history.record(&relation(&["id", "note", "total"]), 0x300);
+        let versions = history.versions();
+        let numbered: Vec<(u32, u64)> = versions.iter().map(|v| (v.version, v.lsn)).collect();
+        assert_eq!(numbered, [(1, 0x100), (2, 0x200), (3, 0x300)]);
+
+        let columns = |lsn| match &schema_at(versions.clone(), lsn)[0].relation {
+            CdcMessage::Relation { columns, .. } => columns.len(),
+            _ => 0,
+        };
+        assert_eq!(columns(0x250), 2);
+        assert_eq!(columns(0x300), 3);
+        // First seen after the checkpoint, or resuming from the slot
+        assert_eq!(columns(0x50), 1);
+        assert_eq!(columns(0), 3);
+
+        // Versions replaced by the checkpoint are dropped
+        history.prune(0x250);
+        let numbered: Vec<u32> = history.versions().iter().map(|v| v.version).collect();
+        assert_eq!(numbered, [2, 3]);
+        history.prune(0x300);
+        assert_eq!(history.versions().len(), 1);
+        history.forget(1);
+        assert!(history.versions().is_empty());
     }
 }
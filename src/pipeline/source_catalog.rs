@@ -0,0 +1,105 @@
+//! Source catalog lookups for schema deltas.
+//!
+//! pgoutput Relation messages carry column names and types only. Before a
+//! delta is handed to the sink, the pipeline reads the table's nullability and
+//! comments from the PostgreSQL catalog so the sink can mirror them.
+//! `COLUMN_TRANSFORMS` are applied to what is read, as to the setup's view.
+//! Array, composite, domain and enum types the columns use are read here
+//! too, so they convert and map to sink types (see `source::pg_types`).
+
+use tokio_postgres::Client;
+use tracing::warn;
+
+use crate::config::ColumnTransforms;
+use crate::engine::setup::catalog::{describe_table, SourceTable};
+use crate::engine::setup::postgres::create_postgres_client;
+use crate::pipeline::schema_cache::{ColumnAttributes, SchemaDelta};
+use crate::source::pg_types;
+use crate::source::session::PgSession;
+
+/// Lazily connected catalog client, reconnected after any failure.
+pub struct SourceCatalog {
+    session: PgSession,
+    database_url: String,
+    transforms: ColumnTransforms,
+    client: Option<Client>,
+}
+
+impl SourceCatalog {
+    pub fn new(session: &PgSession, database_url: &str, transforms: ColumnTransforms) -> Self {
+        Self {
+            session: session.clone(),
+            database_url: database_url.to_string(),
+            transforms,
+            client: None,
+        }
+    }
+
+    /// Fills `delta.columns` from the catalog and returns the table as
+    /// described. On failure the delta is left as is: columns are still
+    /// added, only nullability and comments are not propagated.
+    pub async fn describe(&mut self, delta: &mut SchemaDelta) -> Option<SourceTable> {
+        let client = self.connect().await?;
+
+        match describe_table(client, &delta.qualified_name()).await {
+            Ok(mut table) => {
+                table.apply_column_transforms(&self.transforms);
+                delta.columns = column_attributes(&table);
+                Some(table)
+            }
+            Err(e) => {
+                warn!(
+                    "[SCHEMA] Cannot describe {} in the source catalog: {}",
+                    delta.qualified_name(),
+                    e
+                );
+                self.client = None;
+                None
+            }
+        }
+    }
+
+    /// Reads the types of a Relation's columns that aren't known yet. On
+    /// failure their values stay strings until the next Relation names them.
+    pub async fn resolve_types(&mut self, type_ids: impl IntoIterator<Item = u32>) {
+        let type_ids: Vec<u32> = type_ids
+            .into_iter()
+            .filter(|oid| pg_types::needs_lookup(*oid))
+            .collect();
+        if type_ids.is_empty() {
+            return;
+        }
+        let Some(client) = self.connect().await else {
+            return;
+        };
+        if let Err(e) = pg_types::lookup(client, &type_ids).await {
+            warn!("[SCHEMA] Cannot read column types {:?}: {:#}", type_ids, e);
+            self.client = None;
+        }
+    }
+
+    async fn connect(&mut self) -> Option<&Client> {
+        if self.client.as_ref().map_or(true, |c| c.is_closed()) {
+            match create_postgres_client(&self.session, &self.database_url).await {
+                Ok(client) => self.client = Some(client),
+                Err(e) => {
+                    warn!("[SCHEMA] Cannot read source catalog: {}", e);
+                    return None;
+                }
+            }
+        }
+        self.client.as_ref()
+    }
+}
+
+fn column_attributes(table: &SourceTable) -> Vec<ColumnAttributes> {
+    table
+        .columns
+        .iter()
+        .map(|c| ColumnAttributes {
+            name: c.name.clone(),
+            nullable: c.nullable,
+            comment: c.comment.clone(),
+        })
+        .collect()
+}
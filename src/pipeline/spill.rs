@@ -0,0 +1,516 @@
+//! Disk spill between the WAL reader and the pipeline (`SPILL_DIR`).
+//!
+//! The WAL reader hands events to the pipeline over a bounded channel. When
+//! the sink is slow the channel fills up and the reader waits, and while it
+//! waits it answers no keepalives, so PostgreSQL ends the connection after
+//! `wal_sender_timeout`. With a spill directory, [`relay`] sits between the
+//! two. It passes events straight on while the pipeline keeps up. Once the
+//! pipeline's channel is full it appends them to segment files instead, and
+//! feeds them back in order as the pipeline catches up; events read
+//! meanwhile are spilled behind them. A segment is deleted once read.
+//!
+//! Spilled events are confirmed to PostgreSQL only once the sink wrote them,
+//! like events held in memory, so the files are scratch: a restart deletes
+//! them and the slot streams their changes again. Once they add up to
+//! `SPILL_MAX_MB` the relay stops reading and the WAL reader waits as it
+//! would without a spill.
+//!
+//! With `SPILL_MAX_AGE_SECS`, the relay also watches the age of the oldest
+//! segment. It warns once the segment reaches 80% of the limit, and stops
+//! replication when it passes it: a pipeline that hasn't caught up in that
+//! long is stuck rather than behind. Nothing is lost; the restart deletes
+//! the segments and the slot streams their changes again.
+//!
+//! Each segment is a sequence of frames: the length of the encoded event as
+//! a little-endian `u32`, then the event in bincode.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{error, info, warn};
+
+use crate::config::SpillConfig;
+use crate::grpc::state::{CdcState, ErrorCategory, SharedState};
+use crate::source::parser::CdcEvent;
+
+/// Spilled events, in segment files read back oldest first.
+pub struct SpillQueue {
+    dir: PathBuf,
+    max_bytes: u64,
+    segment_bytes: u64,
+    max_age: Option<Duration>,
+    /// Segments with events not read yet, oldest first
+    segments: VecDeque<Segment>,
+    /// Writer of the last segment; closed once the segment is full, or
+    /// when reading reaches it
+    writer: Option<BufWriter<File>>,
+    /// Reader of the first segment
+    reader: Option<BufReader<File>>,
+    next_segment: u64,
+    /// Size of the segments on disk
+    bytes: u64,
+    /// Events not read yet
+    events: u64,
+}
+
+struct Segment {
+    path: PathBuf,
+    bytes: u64,
+    /// Events not read yet
+    events: u64,
+    /// When its first event was spilled
+    started: Instant,
+}
+
+impl SpillQueue {
+    /// Opens the queue in `config.dir`, deleting the segments of an earlier
+    /// run: their changes were never confirmed, so the slot sends them again.
+    pub fn open(config: &SpillConfig) -> Result<Self> {
+        let dir = PathBuf::from(&config.dir);
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let entries =
+            fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if is_segment(&path) {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+        Ok(Self {
+            dir,
+            max_bytes: config.max_bytes,
+            segment_bytes: config.segment_bytes,
+            max_age: config.max_age,
+            segments: VecDeque::new(),
+            writer: None,
+            reader: None,
+            next_segment: 0,
+            bytes: 0,
+            events: 0,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events == 0
+    }
+
+    /// Whether the segments reached `SPILL_MAX_MB`
+    pub fn is_full(&self) -> bool {
+        self.bytes >= self.max_bytes
+    }
+
+    /// Size of the segments on disk
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Time since the oldest segment with events not read yet was started:
+    /// how long the oldest spilled event waited, at most.
+    pub fn oldest_age(&self) -> Duration {
+        self.segments
+            .front()
+            .map_or(Duration::ZERO, |s| s.started.elapsed())
+    }
+
+    /// Appends an event behind those spilled before it.
+    pub fn push(&mut self, event: &CdcEvent) -> Result<()> {
+        let frame = bincode::serialize(event).context("Failed to encode a spilled event")?;
+        let room = matches!(self.segments.back(), Some(s) if s.bytes < self.segment_bytes);
+        if self.writer.is_none() || !room {
+            self.start_segment()?;
+        }
+        let (Some(writer), Some(segment)) = (self.writer.as_mut(), self.segments.back_mut()) else {
+            unreachable!("segment started above");
+        };
+        writer
+            .write_all(&(frame.len() as u32).to_le_bytes())
+            .and_then(|()| writer.write_all(&frame))
+            .with_context(|| format!("Failed to write {}", segment.path.display()))?;
+        let size = 4 + frame.len() as u64;
+        segment.bytes += size;
+        segment.events += 1;
+        self.bytes += size;
+        self.events += 1;
+        Ok(())
+    }
+
+    /// Takes the oldest spilled event, `None` when none is left.
+    pub fn pop(&mut self) -> Result<Option<CdcEvent>> {
+        let last = self.segments.len() == 1;
+        let Some(segment) = self.segments.front_mut() else {
+            return Ok(None);
+        };
+        if self.reader.is_none() {
+            // The segment being written is closed before it is read
+            if last {
+                if let Some(mut writer) = self.writer.take() {
+                    writer
+                        .flush()
+                        .with_context(|| format!("Failed to write {}", segment.path.display()))?;
+                }
+            }
+            let file = File::open(&segment.path)
+                .with_context(|| format!("Failed to open {}", segment.path.display()))?;
+            self.reader = Some(BufReader::new(file));
+        }
+        let Some(reader) = self.reader.as_mut() else {
+            unreachable!("reader opened above");
+        };
+
+        let mut len = [0u8; 4];
+        let mut frame = Vec::new();
+        reader
+            .read_exact(&mut len)
+            .and_then(|()| {
+                frame.resize(u32::from_le_bytes(len) as usize, 0);
+                reader.read_exact(&mut frame)
+            })
+            .with_context(|| format!("Failed to read {}", segment.path.display()))?;
+        let event: CdcEvent = bincode::deserialize(&frame)
+            .with_context(|| format!("Corrupt spilled event in {}", segment.path.display()))?;
+        segment.events -= 1;
+        self.events -= 1;
+
+        if segment.events == 0 {
+            self.reader = None;
+            fs::remove_file(&segment.path)
+                .with_context(|| format!("Failed to remove {}", segment.path.display()))?;
+            self.bytes -= segment.bytes;
+            self.segments.pop_front();
+        }
+        Ok(Some(event))
+    }
+
+    fn start_segment(&mut self) -> Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush().context("Failed to write a spill segment")?;
+        }
+        let path = self
+            .dir
+            .join(format!("spill-{:010}.seg", self.next_segment));
+        self.next_segment += 1;
+        let file =
+            File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        self.writer = Some(BufWriter::new(file));
+        self.segments.push_back(Segment {
+            path,
+            bytes: 0,
+            events: 0,
+            started: Instant::now(),
+        });
+        Ok(())
+    }
+}
+
+fn is_segment(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("spill-") && name.ends_with(".seg"))
+}
+
+/// Passes events from the WAL reader (`input`) to the pipeline (`output`),
+/// through `queue` whenever the pipeline's channel is full. Once the reader
+/// hangs up, what is spilled still goes to the pipeline. A queue that can't
+/// be written or read stops replication.
+pub async fn relay(
+    mut input: mpsc::Receiver<CdcEvent>,
+    output: mpsc::Sender<CdcEvent>,
+    mut queue: SpillQueue,
+    shared_state: Arc<SharedState>,
+) {
+    if let Err(e) = forward(&mut input, &output, &mut queue, &shared_state).await {
+        error!("CRITICAL: Spill queue failed: {:#}", e);
+        shared_state
+            .record_error(
+                ErrorCategory::Spill,
+                format!("Spill queue failed: {:#}", e),
+                None,
+                0,
+            )
+            .await;
+        shared_state.set_state(CdcState::Stopped);
+    }
+}
+
+async fn forward(
+    input: &mut mpsc::Receiver<CdcEvent>,
+    output: &mpsc::Sender<CdcEvent>,
+    queue: &mut SpillQueue,
+    shared_state: &SharedState,
+) -> Result<()> {
+    let mut full = false;
+    let mut aging = false;
+    // Checks the age of the spill while neither side moves
+    let mut check = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        if queue.is_empty() {
+            aging = false;
+            let Some(event) = input.recv().await else {
+                return Ok(());
+            };
+            match output.try_send(event) {
+                Ok(()) => {}
+                Err(TrySendError::Full(event)) => {
+                    warn!(
+                        "[SPILL] Pipeline is behind, spilling events to {}",
+                        queue.dir.display()
+                    );
+                    queue.push(&event)?;
+                    shared_state.record_spill(queue.bytes(), 1);
+                }
+                Err(TrySendError::Closed(_)) => return Ok(()),
+            }
+            continue;
+        }
+
+        if queue.is_full() != full {
+            full = queue.is_full();
+            if full {
+                warn!("[SPILL] SPILL_MAX_MB reached, the WAL reader waits for the pipeline");
+            }
+        }
+        check_age(queue, &mut aging, shared_state).await?;
+        tokio::select! {
+            // Spilled events go first, so the pipeline gets them in WAL order
+            permit = output.reserve() => {
+                let Ok(permit) = permit else {
+                    return Ok(()); // The pipeline stopped
+                };
+                if let Some(event) = queue.pop()? {
+                    permit.send(event);
+                }
+                shared_state.record_spill(queue.bytes(), 0);
+                if queue.is_empty() {
+                    info!("[SPILL] Pipeline caught up with the spilled events");
+                    shared_state.record_spill_age(Duration::ZERO);
+                }
+            }
+            _ = check.tick() => {}
+            event = input.recv(), if !full => match event {
+                Some(event) => {
+                    queue.push(&event)?;
+                    shared_state.record_spill(queue.bytes(), 1);
+                }
+                None => {
+                    while let Some(event) = queue.pop()? {
+                        if output.send(event).await.is_err() {
+                            break;
+                        }
+                    }
+                    shared_state.record_spill(queue.bytes(), 0);
+                    shared_state.record_spill_age(Duration::ZERO);
+                    return Ok(());
+                }
+            },
+        }
+    }
+}
+
+/// Reports the age of the spill, alerting once when it nears
+/// `SPILL_MAX_AGE_SECS` (`aging`), and fails once it passes it.
+async fn check_age(queue: &SpillQueue, aging: &mut bool, shared_state: &SharedState) -> Result<()> {
+    let age = queue.oldest_age();
+    shared_state.record_spill_age(age);
+    let Some(max_age) = queue.max_age else {
+        return Ok(());
+    };
+    if age >= max_age {
+        anyhow::bail!(
+            "spilled events waited more than SPILL_MAX_AGE_SECS ({}s) for the pipeline; \
+             a restart deletes them and the slot streams their changes again",
+            max_age.as_secs()
+        );
+    }
+    if !*aging && age >= max_age.mul_f64(0.8) {
+        *aging = true;
+        let message = format!(
+            "Spilled events waited {}s for the pipeline, replication stops at \
+             SPILL_MAX_AGE_SECS ({}s)",
+            age.as_secs(),
+            max_age.as_secs()
+        );
+        warn!("[SPILL] {}", message);
+        shared_state
+            .record_error(ErrorCategory::Spill, message, None, 0)
+            .await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::state::CdcConfig;
+    use crate::source::parser::{CdcMessage, Tuple, TupleData};
+    use bytes::Bytes;
+    use std::sync::atomic::Ordering;
+
+    fn spill_config(name: &str) -> SpillConfig {
+        let dir =
+            std::env::temp_dir().join(format!("dbmazz-spill-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        SpillConfig {
+            dir: dir.display().to_string(),
+            max_bytes: 1024 * 1024,
+            // A few events per segment
+            segment_bytes: 100,
+            max_age: None,
+        }
+    }
+
+    fn event(lsn: u64) -> CdcEvent {
+        CdcEvent {
+            lsn,
+            message: CdcMessage::Insert {
+                relation_id: 1,
+                tuple: Tuple {
+                    // Not UTF-8
+                    cols: vec![TupleData::Text(Bytes::from(vec![0xff, lsn as u8]))],
+                    toast_bitmap: 0,
+                },
+            },
+        }
+    }
+
+    fn segments(dir: &str) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter(|e| is_segment(&e.as_ref().unwrap().path()))
+            .count()
+    }
+
+    #[test]
+    fn test_events_come_back_in_order() {
+        let config = spill_config("order");
+        let mut queue = SpillQueue::open(&config).unwrap();
+        assert!(queue.pop().unwrap().is_none());
+
+        let mut lsns = Vec::new();
+        for lsn in 1..=10 {
+            queue.push(&event(lsn)).unwrap();
+        }
+        assert!(segments(&config.dir) > 1);
+        for _ in 0..4 {
+            lsns.push(queue.pop().unwrap().unwrap().lsn);
+        }
+        for lsn in 11..=15 {
+            queue.push(&event(lsn)).unwrap();
+        }
+        while let Some(event) = queue.pop().unwrap() {
+            let CdcMessage::Insert { tuple, .. } = &event.message else {
+                panic!("not an insert: {:?}", event.message);
+            };
+            assert!(
+                matches!(&tuple.cols[0], TupleData::Text(b) if b[..] == [0xff, event.lsn as u8])
+            );
+            lsns.push(event.lsn);
+        }
+        assert_eq!(lsns, (1..=15).collect::<Vec<u64>>());
+
+        // Read segments are deleted
+        assert!(queue.is_empty());
+        assert_eq!(queue.bytes(), 0);
+        assert_eq!(segments(&config.dir), 0);
+
+        let _ = fs::remove_dir_all(&config.dir);
+    }
+
+    #[test]
+    fn test_segments_of_earlier_run_are_deleted() {
+        let config = spill_config("restart");
+        let mut queue = SpillQueue::open(&config).unwrap();
+        queue.push(&event(1)).unwrap();
+        queue.push(&event(2)).unwrap();
+        drop(queue);
+        let other = Path::new(&config.dir).join("notes.txt");
+        fs::write(&other, "kept").unwrap();
+
+        let mut queue = SpillQueue::open(&config).unwrap();
+        assert_eq!(segments(&config.dir), 0);
+        assert!(other.exists());
+        assert!(queue.pop().unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&config.dir);
+    }
+
+    #[tokio::test]
+    async fn test_reader_never_waits_for_the_pipeline() {
+        let config = spill_config("relay");
+        let shared_state = SharedState::new(CdcConfig {
+            flush_size: 1,
+            flush_interval_ms: 1,
+            tables: Vec::new(),
+            slot_name: "slot".to_string(),
+        });
+        let (tx, input) = mpsc::channel(1);
+        let (output, mut rx) = mpsc::channel(2);
+        let task = tokio::spawn(relay(
+            input,
+            output,
+            SpillQueue::open(&config).unwrap(),
+            shared_state.clone(),
+        ));
+
+        // Far more than both channels hold, with nobody reading
+        for lsn in 1..=50 {
+            tokio::time::timeout(std::time::Duration::from_secs(5), tx.send(event(lsn)))
+                .await
+                .expect("the reader waited")
+                .unwrap();
+        }
+        drop(tx);
+
+        let mut lsns = Vec::new();
+        while let Some(event) = rx.recv().await {
+            lsns.push(event.lsn);
+        }
+        assert_eq!(lsns, (1..=50).collect::<Vec<u64>>());
+        task.await.unwrap();
+        assert!(shared_state.spilled_events.load(Ordering::Relaxed) > 0);
+        assert_eq!(shared_state.spill_bytes.load(Ordering::Relaxed), 0);
+        assert_eq!(segments(&config.dir), 0);
+
+        let _ = fs::remove_dir_all(&config.dir);
+    }
+
+    #[tokio::test]
+    async fn test_stale_spill_stops_replication() {
+        let mut config = spill_config("age");
+        config.max_age = Some(std::time::Duration::from_millis(1500));
+        let shared_state = SharedState::new(CdcConfig {
+            flush_size: 1,
+            flush_interval_ms: 1,
+            tables: Vec::new(),
+            slot_name: "slot".to_string(),
+        });
+        let (tx, input) = mpsc::channel(1);
+        // Nobody reads the pipeline's side
+        let (output, _rx) = mpsc::channel(1);
+        let task = tokio::spawn(relay(
+            input,
+            output,
+            SpillQueue::open(&config).unwrap(),
+            shared_state.clone(),
+        ));
+        for lsn in 1..=3 {
+            tx.send(event(lsn)).await.unwrap();
+        }
+
+        tokio::time::timeout(std::time::Duration::from_secs(10), task)
+            .await
+            .expect("the relay kept waiting")
+            .unwrap();
+        assert_eq!(shared_state.state(), CdcState::Stopped);
+        assert!(shared_state.spill_age_secs.load(Ordering::Relaxed) >= 1);
+
+        let _ = fs::remove_dir_all(&config.dir);
+    }
+}
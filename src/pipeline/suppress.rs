@@ -0,0 +1,385 @@
+//! Suppression of updates that change nothing the sink cares about
+//! (`SUPPRESS_UNCHANGED`).
+//!
+//! Some applications touch rows without changing them, bumping an
+//! `updated_at` or `last_seen` column on every heartbeat. For the tables
+//! listed, an update is dropped before it is batched when none of its
+//! columns but the ignored ones changed. Key columns are always compared.
+//!
+//! With `REPLICA IDENTITY FULL` the update carries the old row to compare
+//! with. Otherwise a hash of the compared values of each row inserted or
+//! updated is remembered by its key, up to `SUPPRESS_MAX_ROWS` rows, and
+//! forgotten all at once when that fills up. So the first update of a row
+//! since startup always goes through, as do updates of tables without a key
+//! and updates leaving an unchanged TOASTed value out of a compared column.
+//!
+//! Comparing happens after `COLUMN_TRANSFORMS`: a change to a dropped column
+//! alone is suppressed too, the sink having nothing to write for it.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use tracing::{debug, warn};
+
+use crate::config::ChangeSuppression;
+use crate::source::parser::{CdcMessage, Column, Tuple, TupleData};
+
+/// How the updates of one relation are compared.
+struct RelationPlan {
+    /// Whether each column is compared, in order
+    compared: Arc<[bool]>,
+    /// The old row comes with every update (`REPLICA IDENTITY FULL`)
+    full_identity: bool,
+    /// Rows can be told apart by their key
+    keyed: bool,
+    /// Whether each column is part of the key
+    key: Arc<[bool]>,
+}
+
+/// Applies `SUPPRESS_UNCHANGED` to the messages of the pipeline.
+#[derive(Default)]
+pub struct ChangeSuppressor {
+    config: ChangeSuppression,
+    /// Plans of the relations of tables with suppression
+    plans: HashMap<u32, RelationPlan>,
+    /// Hash of the compared values of each remembered row, by relation and
+    /// hash of the row's key
+    rows: HashMap<(u32, u64), u64>,
+}
+
+impl ChangeSuppressor {
+    pub fn new(config: ChangeSuppression) -> Self {
+        Self {
+            config,
+            plans: HashMap::new(),
+            rows: HashMap::new(),
+        }
+    }
+
+    /// Whether `msg` is an update changing none of the compared columns of
+    /// its row, to be dropped. Relation messages and the other changes are
+    /// kept, and update what is known of their table or row.
+    pub fn suppresses(&mut self, msg: &CdcMessage) -> bool {
+        if self.config.is_empty() {
+            return false;
+        }
+        match msg {
+            CdcMessage::Relation {
+                id,
+                namespace,
+                name,
+                replica_identity,
+                columns,
+            } => {
+                self.relation(
+                    *id,
+                    &format!("{}.{}", namespace, name),
+                    *replica_identity,
+                    columns,
+                );
+                false
+            }
+            CdcMessage::Insert { relation_id, tuple } => {
+                if let Some(plan) = self.plans.get(relation_id) {
+                    if !plan.full_identity && plan.keyed {
+                        let (key, values) = (plan.key.clone(), plan.compared.clone());
+                        self.remember(*relation_id, &key, &values, tuple);
+                    }
+                }
+                false
+            }
+            CdcMessage::Update {
+                relation_id,
+                old_tuple,
+                new_tuple,
+            } => self.update(*relation_id, old_tuple.as_ref(), new_tuple),
+            CdcMessage::Delete {
+                relation_id,
+                old_tuple: Some(old),
+            } => {
+                if let Some(plan) = self.plans.get(relation_id) {
+                    if !plan.full_identity && plan.keyed {
+                        let key = hash_columns(&plan.key, old);
+                        self.rows.remove(&(*relation_id, key));
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn relation(&mut self, id: u32, table: &str, replica_identity: u8, columns: &[Column]) {
+        // Rows remembered against the old columns can't be compared anymore
+        self.rows.retain(|(relation_id, _), _| *relation_id != id);
+        let Some(ignored) = self.config.for_table(table) else {
+            self.plans.remove(&id);
+            return;
+        };
+        for column in ignored {
+            if !columns.iter().any(|c| &*c.name == column) {
+                warn!(
+                    "[SUPPRESS] SUPPRESS_UNCHANGED names {}.{}, which has no such column",
+                    table, column
+                );
+            }
+        }
+        let full_identity = replica_identity == b'f';
+        // With REPLICA IDENTITY FULL every column is flagged as key
+        let key: Arc<[bool]> = columns
+            .iter()
+            .map(|c| !full_identity && c.is_key())
+            .collect();
+        let compared = columns
+            .iter()
+            .zip(key.iter())
+            .map(|(c, &key)| key || !ignored.iter().any(|i| *i == *c.name))
+            .collect();
+        self.plans.insert(
+            id,
+            RelationPlan {
+                compared,
+                full_identity,
+                keyed: key.iter().any(|&k| k),
+                key,
+            },
+        );
+    }
+
+    fn update(&mut self, relation_id: u32, old: Option<&Tuple>, new: &Tuple) -> bool {
+        let Some(plan) = self.plans.get(&relation_id) else {
+            return false;
+        };
+        if plan.full_identity {
+            return old.is_some_and(|old| unchanged(&plan.compared, old, new));
+        }
+        if !plan.keyed {
+            return false;
+        }
+        let (key, compared) = (plan.key.clone(), plan.compared.clone());
+        // Sent when the key changed: the row moves to its new key
+        if let Some(old) = old {
+            self.rows.remove(&(relation_id, hash_columns(&key, old)));
+        }
+        match self.remember(relation_id, &key, &compared, new) {
+            Some(previous) => old.is_none() && previous,
+            None => false,
+        }
+    }
+
+    /// Remembers the compared values of `tuple` by its key. Returns whether
+    /// they are the ones remembered before, `None` when the row can't be
+    /// remembered or wasn't.
+    fn remember(
+        &mut self,
+        relation_id: u32,
+        key: &[bool],
+        compared: &[bool],
+        tuple: &Tuple,
+    ) -> Option<bool> {
+        let row = (relation_id, hash_columns(key, tuple));
+        // An unchanged TOASTed value isn't sent, so the row can't be hashed
+        let toasted = compared
+            .iter()
+            .zip(&tuple.cols)
+            .any(|(&compared, value)| compared && matches!(value, TupleData::Toast));
+        if toasted {
+            self.rows.remove(&row);
+            return None;
+        }
+        if self.rows.len() >= self.config.max_rows && !self.rows.contains_key(&row) {
+            debug!(
+                "[SUPPRESS] {} rows remembered, forgetting them",
+                self.rows.len()
+            );
+            self.rows.clear();
+        }
+        let values = hash_columns(compared, tuple);
+        self.rows
+            .insert(row, values)
+            .map(|previous| previous == values)
+    }
+
+    /// Rows remembered to compare updates against
+    #[cfg(test)]
+    fn remembered(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+/// Whether the compared columns of `new` hold the values of `old`. An
+/// unchanged TOASTed value isn't sent in the new row.
+fn unchanged(compared: &[bool], old: &Tuple, new: &Tuple) -> bool {
+    old.cols.len() == new.cols.len()
+        && compared
+            .iter()
+            .zip(old.cols.iter().zip(&new.cols))
+            .filter(|&(&compared, _)| compared)
+            .all(|(_, values)| match values {
+                (_, TupleData::Toast) => true,
+                (TupleData::Text(old), TupleData::Text(new)) => old == new,
+                (TupleData::Binary(old), TupleData::Binary(new)) => old == new,
+                (TupleData::Null, TupleData::Null) => true,
+                _ => false,
+            })
+}
+
+/// Hash of the values of the selected columns of `tuple`.
+fn hash_columns(selected: &[bool], tuple: &Tuple) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    tuple.cols.len().hash(&mut hasher);
+    for (_, value) in selected
+        .iter()
+        .zip(&tuple.cols)
+        .filter(|&(&selected, _)| selected)
+    {
+        match value {
+            TupleData::Null => 0u8.hash(&mut hasher),
+            TupleData::Text(bytes) => {
+                1u8.hash(&mut hasher);
+                bytes.hash(&mut hasher);
+            }
+            TupleData::Toast => 2u8.hash(&mut hasher),
+            TupleData::Binary(bytes) => {
+                3u8.hash(&mut hasher);
+                bytes.hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn suppressor(ignored: &[&str], max_rows: usize) -> ChangeSuppressor {
+        ChangeSuppressor::new(ChangeSuppression {
+            tables: HashMap::from([(
+                "users".to_string(),
+                ignored.iter().map(|c| c.to_string()).collect(),
+            )]),
+            max_rows,
+        })
+    }
+
+    fn relation(replica_identity: u8) -> CdcMessage {
+        let flags = if replica_identity == b'f' { 1 } else { 0 };
+        let column = |flags, name: &str| Column {
+            flags,
+            name: name.into(),
+            type_id: 25,
+            type_mod: -1,
+        };
+        CdcMessage::Relation {
+            id: 1,
+            namespace: "public".into(),
+            name: "users".into(),
+            replica_identity,
+            columns: vec![
+                column(1, "id"),
+                column(flags, "name"),
+                column(flags, "updated_at"),
+            ],
+        }
+    }
+
+    fn row(id: &str, name: &str, updated_at: &str) -> Tuple {
+        let text = |s: &str| match s {
+            "~" => TupleData::Toast,
+            s => TupleData::Text(Bytes::from(s.to_string())),
+        };
+        Tuple {
+            cols: vec![text(id), text(name), text(updated_at)],
+            toast_bitmap: 0,
+        }
+    }
+
+    fn update(old: Option<Tuple>, new: Tuple) -> CdcMessage {
+        CdcMessage::Update {
+            relation_id: 1,
+            old_tuple: old,
+            new_tuple: new,
+        }
+    }
+
+    #[test]
+    fn test_remembered_rows() {
+        let mut s = suppressor(&["updated_at"], 100);
+        assert!(!s.suppresses(&relation(b'd')));
+
+        // The first update of a row since startup goes through
+        assert!(!s.suppresses(&update(None, row("1", "ann", "t1"))));
+        assert!(s.suppresses(&update(None, row("1", "ann", "t2"))));
+        assert!(!s.suppresses(&update(None, row("1", "bob", "t3"))));
+        assert!(s.suppresses(&update(None, row("1", "bob", "t4"))));
+
+        // Inserted rows are remembered, deleted ones forgotten
+        let insert = CdcMessage::Insert {
+            relation_id: 1,
+            tuple: row("2", "cy", "t1"),
+        };
+        assert!(!s.suppresses(&insert));
+        assert!(s.suppresses(&update(None, row("2", "cy", "t2"))));
+        let delete = CdcMessage::Delete {
+            relation_id: 1,
+            old_tuple: Some(row("2", "", "")),
+        };
+        assert!(!s.suppresses(&delete));
+        assert!(!s.suppresses(&update(None, row("2", "cy", "t3"))));
+
+        // A row moving to another key goes through and is remembered there
+        assert!(!s.suppresses(&update(Some(row("1", "", "")), row("3", "bob", "t5"))));
+        assert!(!s.suppresses(&update(None, row("1", "bob", "t6"))));
+        assert!(s.suppresses(&update(None, row("3", "bob", "t6"))));
+
+        // A compared value left out can't be told unchanged
+        assert!(!s.suppresses(&update(None, row("3", "~", "t7"))));
+        assert!(!s.suppresses(&update(None, row("3", "bob", "t8"))));
+
+        // A new Relation message forgets the rows of the table
+        assert!(!s.suppresses(&relation(b'd')));
+        assert_eq!(s.remembered(), 0);
+        assert!(!s.suppresses(&update(None, row("3", "bob", "t9"))));
+    }
+
+    #[test]
+    fn test_full_identity_compares_old_row() {
+        let mut s = suppressor(&["updated_at"], 100);
+        s.suppresses(&relation(b'f'));
+
+        assert!(s.suppresses(&update(Some(row("1", "ann", "t1")), row("1", "ann", "t2"))));
+        assert!(s.suppresses(&update(Some(row("1", "ann", "t1")), row("1", "~", "t2"))));
+        assert!(!s.suppresses(&update(Some(row("1", "ann", "t1")), row("1", "bob", "t1"))));
+        assert!(!s.suppresses(&update(Some(row("1", "ann", "t1")), row("2", "ann", "t1"))));
+        assert_eq!(s.remembered(), 0);
+    }
+
+    #[test]
+    fn test_other_tables_and_memory_limit() {
+        let mut s = suppressor(&[], 2);
+        let mut orders = relation(b'd');
+        if let CdcMessage::Relation { name, .. } = &mut orders {
+            *name = "orders".into();
+        }
+        s.suppresses(&orders);
+        assert!(!s.suppresses(&update(None, row("1", "ann", "t1"))));
+        assert!(!s.suppresses(&update(None, row("1", "ann", "t1"))));
+
+        s.suppresses(&relation(b'd'));
+        // Every column is compared
+        assert!(!s.suppresses(&update(None, row("1", "ann", "t1"))));
+        assert!(!s.suppresses(&update(None, row("1", "ann", "t2"))));
+        assert!(s.suppresses(&update(None, row("1", "ann", "t2"))));
+
+        // A third row makes room by forgetting the others
+        s.suppresses(&update(None, row("2", "bob", "t1")));
+        assert_eq!(s.remembered(), 2);
+        s.suppresses(&update(None, row("3", "cy", "t1")));
+        assert_eq!(s.remembered(), 1);
+        assert!(!s.suppresses(&update(None, row("1", "ann", "t2"))));
+    }
+}
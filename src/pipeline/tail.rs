@@ -0,0 +1,281 @@
+//! Live stream of written changes (`TailEvents`).
+//!
+//! After every batch the sink accepted, the pipeline hands the batch to the
+//! [`TailHub`], which copies its events into the buffer of each subscribed
+//! client without waiting on any of them. A client that reads slower than
+//! changes arrive fills its buffer of `TAIL_BUFFER_EVENTS`; it then loses its
+//! oldest events or, with `TAIL_OVERFLOW=disconnect`, is cut off. Either way
+//! replication goes on at its own pace.
+
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+use crate::config::{TailConfig, TailOverflow};
+use crate::pipeline::peek::{sample_events, SampledEvent};
+use crate::pipeline::schema_cache::SchemaCache;
+use crate::source::parser::CdcMessage;
+
+/// Clients of `TailEvents` and their buffers.
+#[derive(Default)]
+pub struct TailHub {
+    config: Mutex<TailConfig>,
+    subscribers: Mutex<Vec<Arc<Subscriber>>>,
+    next_id: AtomicU64,
+    /// Events dropped from full buffers
+    dropped: AtomicU64,
+    /// Clients disconnected for falling behind
+    disconnected: AtomicU64,
+}
+
+struct Subscriber {
+    id: u64,
+    /// Only events of this table (`schema.table` or bare name)
+    table: Option<String>,
+    reveal_values: bool,
+    buffer: Mutex<Buffer>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct Buffer {
+    /// Events and the LSN of their batch, oldest first
+    events: VecDeque<(u64, SampledEvent)>,
+    /// Events dropped since the client last read
+    dropped: u64,
+    /// The client fell behind with `TAIL_OVERFLOW=disconnect`
+    overflowed: bool,
+}
+
+/// Events of one batch, as handed to a client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TailedEvents {
+    /// LSN of the batch the events were written in
+    pub lsn: u64,
+    /// Events dropped for this client before these
+    pub dropped_events: u64,
+    pub events: Vec<SampledEvent>,
+}
+
+/// A client fell more than its buffer behind with `TAIL_OVERFLOW=disconnect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflowed {
+    pub buffer_events: usize,
+}
+
+impl TailHub {
+    /// Applies `TAIL_BUFFER_EVENTS` and `TAIL_OVERFLOW` to clients
+    /// subscribing from now on, and to buffers filled from now on.
+    pub fn configure(&self, config: TailConfig) {
+        *self.config.lock() = config;
+    }
+
+    pub fn subscribe(self: &Arc<Self>, table: Option<String>, reveal_values: bool) -> Subscription {
+        let subscriber = Arc::new(Subscriber {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            table,
+            reveal_values,
+            buffer: Mutex::new(Buffer::default()),
+            notify: Notify::new(),
+        });
+        self.subscribers.lock().push(subscriber.clone());
+        Subscription {
+            hub: self.clone(),
+            subscriber,
+        }
+    }
+
+    /// Copies the events of a written batch to every client. Never waits:
+    /// a full buffer loses its oldest events or marks its client overflowed.
+    pub fn publish(&self, batch: &[CdcMessage], schema_cache: &SchemaCache, lsn: u64) {
+        let subscribers = self.subscribers.lock();
+        if subscribers.is_empty() {
+            return;
+        }
+        let config = *self.config.lock();
+        for subscriber in subscribers.iter() {
+            let events = sample_events(
+                batch,
+                schema_cache,
+                usize::MAX,
+                subscriber.table.as_deref(),
+                subscriber.reveal_values,
+            );
+            if events.is_empty() {
+                continue;
+            }
+            let mut buffer = subscriber.buffer.lock();
+            if buffer.overflowed {
+                continue;
+            }
+            buffer.events.extend(events.into_iter().map(|e| (lsn, e)));
+            let excess = buffer.events.len().saturating_sub(config.buffer_events);
+            if excess > 0 {
+                match config.overflow {
+                    TailOverflow::DropOldest => {
+                        buffer.events.drain(..excess);
+                        buffer.dropped += excess as u64;
+                        self.dropped.fetch_add(excess as u64, Ordering::Relaxed);
+                    }
+                    TailOverflow::Disconnect => {
+                        buffer.events.clear();
+                        buffer.overflowed = true;
+                        self.disconnected.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            drop(buffer);
+            subscriber.notify.notify_one();
+        }
+    }
+
+    /// Connected clients
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.lock().len()
+    }
+
+    /// Events dropped from full client buffers
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Clients disconnected for falling behind
+    pub fn disconnects(&self) -> u64 {
+        self.disconnected.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_counts(&self) {
+        self.dropped.store(0, Ordering::Relaxed);
+        self.disconnected.store(0, Ordering::Relaxed);
+    }
+
+    fn unsubscribe(&self, id: u64) {
+        self.subscribers.lock().retain(|s| s.id != id);
+    }
+}
+
+/// A `TailEvents` client; unsubscribes when dropped.
+pub struct Subscription {
+    hub: Arc<TailHub>,
+    subscriber: Arc<Subscriber>,
+}
+
+impl Subscription {
+    /// Waits for the next events, all from the same batch.
+    pub async fn next(&self) -> Result<TailedEvents, Overflowed> {
+        loop {
+            {
+                let mut buffer = self.subscriber.buffer.lock();
+                if buffer.overflowed {
+                    return Err(Overflowed {
+                        buffer_events: self.hub.config.lock().buffer_events,
+                    });
+                }
+                if let Some(&(lsn, _)) = buffer.events.front() {
+                    let count = buffer.events.iter().take_while(|(l, _)| *l == lsn).count();
+                    let events = buffer.events.drain(..count).map(|(_, e)| e).collect();
+                    return Ok(TailedEvents {
+                        lsn,
+                        dropped_events: std::mem::take(&mut buffer.dropped),
+                        events,
+                    });
+                }
+            }
+            // A publish since the check left a permit, so this returns at once
+            self.subscriber.notify.notified().await;
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.hub.unsubscribe(self.subscriber.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::{Column, Tuple, TupleData};
+    use bytes::Bytes;
+
+    fn schema_cache() -> SchemaCache {
+        let mut cache = SchemaCache::new();
+        cache.update(&CdcMessage::Relation {
+            id: 1,
+            namespace: Arc::from("public"),
+            name: Arc::from("users"),
+            replica_identity: b'd',
+            columns: vec![Column {
+                flags: 1,
+                name: Arc::from("id"),
+                type_id: 23,
+                type_mod: -1,
+            }],
+        });
+        cache
+    }
+
+    fn inserts(n: usize) -> Vec<CdcMessage> {
+        (0..n)
+            .map(|i| CdcMessage::Insert {
+                relation_id: 1,
+                tuple: Tuple {
+                    cols: vec![TupleData::Text(Bytes::from(i.to_string()))],
+                    toast_bitmap: 0,
+                },
+            })
+            .collect()
+    }
+
+    fn hub(buffer_events: usize, overflow: TailOverflow) -> Arc<TailHub> {
+        let hub = Arc::new(TailHub::default());
+        hub.configure(TailConfig {
+            buffer_events,
+            overflow,
+        });
+        hub
+    }
+
+    #[tokio::test]
+    async fn test_slow_client_loses_oldest_events() {
+        let hub = hub(3, TailOverflow::DropOldest);
+        let client = hub.subscribe(None, true);
+        let cache = schema_cache();
+
+        hub.publish(&inserts(2), &cache, 0x10);
+        hub.publish(&inserts(2), &cache, 0x20);
+
+        // The first event of 0x10 made room for the batch of 0x20
+        let first = client.next().await.unwrap();
+        assert_eq!(first.lsn, 0x10);
+        assert_eq!(first.dropped_events, 1);
+        assert_eq!(first.events.len(), 1);
+        assert_eq!(first.events[0].columns[0].value, "1");
+        let second = client.next().await.unwrap();
+        assert_eq!((second.lsn, second.dropped_events), (0x20, 0));
+        assert_eq!(second.events.len(), 2);
+        assert_eq!(hub.dropped_events(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_slow_client_is_disconnected() {
+        let hub = hub(3, TailOverflow::Disconnect);
+        let slow = hub.subscribe(None, false);
+        let filtered = hub.subscribe(Some("orders".to_string()), false);
+        assert_eq!(hub.subscribers(), 2);
+
+        hub.publish(&inserts(4), &schema_cache(), 0x10);
+        assert_eq!(slow.next().await, Err(Overflowed { buffer_events: 3 }));
+        assert_eq!(hub.disconnects(), 1);
+
+        // Events of other tables don't count against a client's buffer
+        let pending = tokio::time::timeout(std::time::Duration::from_millis(10), filtered.next());
+        assert!(pending.await.is_err());
+
+        drop(slow);
+        assert_eq!(hub.subscribers(), 1);
+    }
+}
@@ -0,0 +1,385 @@
+//! Unchanged TOAST values (`TOAST_HYDRATION`).
+//!
+//! pgoutput leaves a large value out of an update that didn't change it and
+//! flags it as unchanged TOAST instead. Sinks writing whole rows have nothing
+//! to write for such a column, and those writing partial rows depend on the
+//! row they already hold. Hydration fills the values in before the update is
+//! batched, ahead of `COLUMN_TRANSFORMS` so that masks apply to them:
+//!
+//! - from the old row, which comes with every update of a table with
+//!   `REPLICA IDENTITY FULL`;
+//! - with `fetch`, otherwise from the source table, read by the row's key
+//!   over a connection of its own. That is the row's current value, which
+//!   may be newer than the update; the changes in between follow in the
+//!   stream. The values of a row deleted since stay unchanged.
+//!
+//! With `strict` nothing is read back: the Relation message of a table
+//! without `REPLICA IDENTITY FULL` stops the pipeline.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use tokio_postgres::Client;
+
+use crate::config::ToastHydration;
+use crate::engine::setup::postgres::create_postgres_client;
+use crate::source::parser::{CdcMessage, Column, Tuple, TupleData};
+use crate::source::session::PgSession;
+use crate::utils::quote_ident;
+
+/// What is known of a relation to read its rows back.
+struct RelationInfo {
+    /// `schema.table`
+    table: String,
+    columns: Vec<Column>,
+}
+
+/// Applies `TOAST_HYDRATION` to the messages of the pipeline.
+#[derive(Default)]
+pub struct ToastHydrator {
+    mode: ToastHydration,
+    session: PgSession,
+    database_url: String,
+    /// Relations as PostgreSQL sent them, before `COLUMN_TRANSFORMS`
+    relations: HashMap<u32, RelationInfo>,
+    /// Lazily connected, reconnected after any failure
+    client: Option<Client>,
+}
+
+impl ToastHydrator {
+    pub fn new(mode: ToastHydration, session: PgSession, database_url: &str) -> Self {
+        Self {
+            mode,
+            session,
+            database_url: database_url.to_string(),
+            relations: HashMap::new(),
+            client: None,
+        }
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.mode == ToastHydration::Strict
+    }
+
+    /// Remembers a Relation message. With `strict`, fails for a table
+    /// without `REPLICA IDENTITY FULL`.
+    pub fn relation(&mut self, msg: &CdcMessage) -> Result<()> {
+        let CdcMessage::Relation {
+            id,
+            namespace,
+            name,
+            replica_identity,
+            columns,
+        } = msg
+        else {
+            return Ok(());
+        };
+        if self.mode == ToastHydration::Off {
+            return Ok(());
+        }
+        let table = format!("{}.{}", namespace, name);
+        if self.mode == ToastHydration::Strict && *replica_identity != b'f' {
+            anyhow::bail!(
+                "{} has REPLICA IDENTITY {}, so the old rows of its updates lack their \
+                 unchanged TOAST values (TOAST_HYDRATION=strict). \
+                 Run: ALTER TABLE {} REPLICA IDENTITY FULL;",
+                table,
+                identity_name(*replica_identity),
+                quote_ident(&table)
+            );
+        }
+        self.relations.insert(
+            *id,
+            RelationInfo {
+                table,
+                columns: columns.clone(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Fills the unchanged TOAST values of an update in and returns how many
+    /// were. Fails when some can't be, leaving those unchanged.
+    pub async fn hydrate(&mut self, msg: &mut CdcMessage) -> Result<usize> {
+        let CdcMessage::Update {
+            relation_id,
+            old_tuple,
+            new_tuple,
+        } = msg
+        else {
+            return Ok(0);
+        };
+        if self.mode == ToastHydration::Off || !new_tuple.cols.iter().any(is_toast) {
+            return Ok(0);
+        }
+
+        let filled = old_tuple
+            .as_ref()
+            .map_or(0, |old| fill_from_old(new_tuple, old));
+        let missing: Vec<usize> = (0..new_tuple.cols.len())
+            .filter(|&idx| is_toast(&new_tuple.cols[idx]))
+            .collect();
+        if missing.is_empty() {
+            return Ok(filled);
+        }
+
+        let relation = self
+            .relations
+            .get(relation_id)
+            .with_context(|| format!("no Relation message for relation {}", relation_id))?;
+        let columns = missing_names(relation, &missing);
+        if self.mode != ToastHydration::Fetch {
+            anyhow::bail!(
+                "{}: the old row lacks the unchanged TOAST values of {}",
+                relation.table,
+                columns
+            );
+        }
+        let table = relation.table.clone();
+        let query = select_by_key(relation, &missing, new_tuple, old_tuple.as_ref())
+            .with_context(|| format!("{}: cannot read {} back", table, columns))?;
+
+        let client = self.connect().await?;
+        let row = match client.query_opt(&query, &[]).await {
+            Ok(row) => row,
+            Err(e) => {
+                self.client = None;
+                return Err(anyhow::Error::new(e)
+                    .context(format!("{}: failed to read {} back", table, columns)));
+            }
+        };
+        let Some(row) = row else {
+            anyhow::bail!(
+                "{}: the row was deleted before {} could be read back",
+                table,
+                columns
+            );
+        };
+        for (i, &idx) in missing.iter().enumerate() {
+            let value: Option<String> = row.get(i);
+            set_value(
+                new_tuple,
+                idx,
+                value.map_or(TupleData::Null, |v| TupleData::Text(Bytes::from(v))),
+            );
+        }
+        Ok(filled + missing.len())
+    }
+
+    async fn connect(&mut self) -> Result<&Client> {
+        if self.client.as_ref().map_or(true, |c| c.is_closed()) {
+            let client = create_postgres_client(&self.session, &self.database_url)
+                .await
+                .context("failed to connect to the source to read TOAST values")?;
+            self.client = Some(client);
+        }
+        Ok(self.client.as_ref().expect("connected above"))
+    }
+}
+
+fn is_toast(value: &TupleData) -> bool {
+    matches!(value, TupleData::Toast)
+}
+
+/// Copies the values `new` leaves unchanged from `old`. Only values are
+/// copied: the old row of a table without `REPLICA IDENTITY FULL` holds its
+/// key, with NULLs for the other columns.
+fn fill_from_old(new: &mut Tuple, old: &Tuple) -> usize {
+    let mut filled = 0;
+    for idx in 0..new.cols.len() {
+        if !is_toast(&new.cols[idx]) {
+            continue;
+        }
+        if let Some(value @ (TupleData::Text(_) | TupleData::Binary(_))) = old.cols.get(idx) {
+            set_value(new, idx, value.clone());
+            filled += 1;
+        }
+    }
+    filled
+}
+
+fn set_value(tuple: &mut Tuple, idx: usize, value: TupleData) {
+    tuple.cols[idx] = value;
+    if idx < 64 {
+        tuple.toast_bitmap &= !(1u64 << idx);
+    }
+}
+
+/// `SELECT` of the `missing` columns of the row, as text, by its key
+/// columns' values in the new row, or the old one for unchanged keys.
+fn select_by_key(
+    relation: &RelationInfo,
+    missing: &[usize],
+    new: &Tuple,
+    old: Option<&Tuple>,
+) -> Result<String> {
+    if new.cols.len() != relation.columns.len() {
+        anyhow::bail!(
+            "the row has {} columns, its Relation message {}",
+            new.cols.len(),
+            relation.columns.len()
+        );
+    }
+    let selected: Vec<String> = missing
+        .iter()
+        .map(|&idx| format!("{}::text", quote_ident(&relation.columns[idx].name)))
+        .collect();
+
+    let mut conditions = Vec::new();
+    for (idx, column) in relation.columns.iter().enumerate() {
+        if !column.is_key() {
+            continue;
+        }
+        let value = [Some(new), old]
+            .into_iter()
+            .flatten()
+            .find_map(|tuple| tuple.cols.get(idx)?.text(column.type_id))
+            .with_context(|| format!("no value for key column {}", column.name))?;
+        conditions.push(format!(
+            "{} = {}",
+            quote_ident(&column.name),
+            literal(&value)
+        ));
+    }
+    if conditions.is_empty() {
+        anyhow::bail!("the table has no replica identity key");
+    }
+
+    Ok(format!(
+        "SELECT {} FROM {} WHERE {}",
+        selected.join(", "),
+        quote_ident(&relation.table),
+        conditions.join(" AND ")
+    ))
+}
+
+/// A string literal the column's type is inferred for, whatever
+/// `standard_conforming_strings` is.
+fn literal(value: &str) -> String {
+    format!("E'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+}
+
+fn missing_names(relation: &RelationInfo, missing: &[usize]) -> String {
+    missing
+        .iter()
+        .filter_map(|&idx| relation.columns.get(idx))
+        .map(|c| c.name.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn identity_name(replica_identity: u8) -> &'static str {
+    match replica_identity {
+        b'd' => "DEFAULT",
+        b'n' => "NOTHING",
+        b'i' => "USING INDEX",
+        b'f' => "FULL",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relation(replica_identity: u8) -> CdcMessage {
+        let column = |flags, name: &str, type_id| Column {
+            flags,
+            name: name.into(),
+            type_id,
+            type_mod: -1,
+        };
+        CdcMessage::Relation {
+            id: 1,
+            namespace: "public".into(),
+            name: "Docs".into(),
+            replica_identity,
+            columns: vec![
+                column(1, "id", 23),
+                column(0, "title", 25),
+                column(0, "body", 3802),
+            ],
+        }
+    }
+
+    fn row(values: &[&str]) -> Tuple {
+        let mut toast_bitmap = 0;
+        let cols = values
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| match *value {
+                "~" => {
+                    toast_bitmap |= 1 << idx;
+                    TupleData::Toast
+                }
+                "" => TupleData::Null,
+                value => TupleData::Text(Bytes::from(value.to_string())),
+            })
+            .collect();
+        Tuple { cols, toast_bitmap }
+    }
+
+    fn update(old: Option<Tuple>, new: Tuple) -> CdcMessage {
+        CdcMessage::Update {
+            relation_id: 1,
+            old_tuple: old,
+            new_tuple: new,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filled_from_the_old_row() {
+        let mut hydrator = ToastHydrator::new(ToastHydration::Strict, PgSession::default(), "");
+        hydrator.relation(&relation(b'f')).unwrap();
+
+        let mut msg = update(Some(row(&["1", "a", r#"{"k": 1}"#])), row(&["1", "b", "~"]));
+        assert_eq!(hydrator.hydrate(&mut msg).await.unwrap(), 1);
+        let CdcMessage::Update { new_tuple, .. } = &msg else {
+            unreachable!()
+        };
+        assert_eq!(new_tuple.cols[2].as_str(), Some(r#"{"k": 1}"#));
+        assert!(!new_tuple.has_toast());
+
+        // Without the value in the old row, strict mode fails
+        let mut msg = update(None, row(&["1", "b", "~"]));
+        let e = hydrator.hydrate(&mut msg).await.unwrap_err();
+        assert!(e.to_string().contains("unchanged TOAST values of body"));
+
+        // Off leaves them to the sink
+        let mut hydrator = ToastHydrator::new(ToastHydration::Off, PgSession::default(), "");
+        hydrator.relation(&relation(b'd')).unwrap();
+        let mut msg = update(None, row(&["1", "b", "~"]));
+        assert_eq!(hydrator.hydrate(&mut msg).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_strict_needs_full_identity() {
+        let mut hydrator = ToastHydrator::new(ToastHydration::Strict, PgSession::default(), "");
+        let e = hydrator.relation(&relation(b'd')).unwrap_err().to_string();
+        assert!(e.contains("public.Docs has REPLICA IDENTITY DEFAULT"));
+        assert!(e.contains(r#"ALTER TABLE "public"."Docs" REPLICA IDENTITY FULL"#));
+
+        let mut hydrator = ToastHydrator::new(ToastHydration::Fetch, PgSession::default(), "");
+        assert!(hydrator.relation(&relation(b'd')).is_ok());
+    }
+
+    #[test]
+    fn test_select_by_key() {
+        let mut hydrator = ToastHydrator::new(ToastHydration::Fetch, PgSession::default(), "");
+        hydrator.relation(&relation(b'd')).unwrap();
+        let relation = &hydrator.relations[&1];
+
+        let query = select_by_key(relation, &[2], &row(&["it's", "b", "~"]), None).unwrap();
+        assert_eq!(
+            query,
+            r#"SELECT "body"::text FROM "public"."Docs" WHERE "id" = E'it''s'"#
+        );
+
+        // A key left unchanged comes from the old row
+        let old = row(&["7", "", ""]);
+        let query = select_by_key(relation, &[1, 2], &row(&["~", "~", "~"]), Some(&old)).unwrap();
+        assert!(query.ends_with(r#"WHERE "id" = E'7'"#));
+        assert!(select_by_key(relation, &[2], &row(&["~", "b", "~"]), None).is_err());
+    }
+}
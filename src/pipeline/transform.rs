@@ -0,0 +1,302 @@
+//! Column filtering and masking (`COLUMN_TRANSFORMS`).
+//!
+//! PII columns are dropped or masked before anything reaches the sink. A
+//! Relation message of a table with transforms is rewritten before the schema
+//! cache sees it: dropped columns are taken out and masked ones become `text`,
+//! so the sink never creates a column it shouldn't have. Each row of the table
+//! is then rewritten to match, from the column layout PostgreSQL sent.
+//!
+//! The original Relation message is kept, and it is what gets handed over to
+//! a standby or stored with the checkpoint: rows streamed after a restart
+//! still have every column and are rewritten once it is read back.
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use tracing::warn;
+
+use crate::config::{ColumnTransform, ColumnTransforms};
+use crate::source::parser::{CdcMessage, Tuple, TupleData};
+
+/// How the rows of one relation are rewritten.
+struct RelationPlan {
+    /// The Relation message as PostgreSQL sent it
+    original: CdcMessage,
+    /// Transform of each column of `original`, in order
+    columns: Vec<Option<ColumnTransform>>,
+    /// Type of each column of `original`, to read binary values masked
+    type_ids: Vec<u32>,
+}
+
+/// Applies `COLUMN_TRANSFORMS` to the messages of the pipeline.
+#[derive(Default)]
+pub struct ColumnTransformer {
+    transforms: ColumnTransforms,
+    plans: HashMap<u32, RelationPlan>,
+}
+
+impl ColumnTransformer {
+    pub fn new(transforms: ColumnTransforms) -> Self {
+        Self {
+            transforms,
+            plans: HashMap::new(),
+        }
+    }
+
+    /// Rewrites `msg` for the sink: a Relation message loses its dropped
+    /// columns, and rows of a table with transforms lose or mask theirs.
+    pub fn apply(&mut self, msg: &mut CdcMessage) {
+        if self.transforms.is_empty() {
+            return;
+        }
+        if matches!(msg, CdcMessage::Relation { .. }) {
+            self.relation(msg);
+            return;
+        }
+        match msg {
+            CdcMessage::Insert { relation_id, tuple } => {
+                if let Some(plan) = self.plans.get(&*relation_id) {
+                    self.tuple(plan, tuple);
+                }
+            }
+            CdcMessage::Update {
+                relation_id,
+                old_tuple,
+                new_tuple,
+            } => {
+                if let Some(plan) = self.plans.get(&*relation_id) {
+                    if let Some(old) = old_tuple {
+                        self.tuple(plan, old);
+                    }
+                    self.tuple(plan, new_tuple);
+                }
+            }
+            CdcMessage::Delete {
+                relation_id,
+                old_tuple: Some(old),
+            } => {
+                if let Some(plan) = self.plans.get(&*relation_id) {
+                    self.tuple(plan, old);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `relations` (from the schema cache) with the transformed ones replaced
+    /// by the messages PostgreSQL sent.
+    pub fn original_relations(&self, relations: Vec<CdcMessage>) -> Vec<CdcMessage> {
+        relations
+            .into_iter()
+            .map(|relation| match &relation {
+                CdcMessage::Relation { id, .. } => self
+                    .plans
+                    .get(id)
+                    .map_or(relation, |plan| plan.original.clone()),
+                _ => relation,
+            })
+            .collect()
+    }
+
+    fn relation(&mut self, msg: &mut CdcMessage) {
+        let CdcMessage::Relation {
+            id,
+            namespace,
+            name,
+            columns,
+            ..
+        } = msg
+        else {
+            return;
+        };
+        let table = format!("{}.{}", namespace, name);
+        let Some(transforms) = self.transforms.for_table(&table) else {
+            self.plans.remove(id);
+            return;
+        };
+        for column in transforms.keys() {
+            if !columns.iter().any(|c| &*c.name == column) {
+                warn!(
+                    "[TRANSFORM] COLUMN_TRANSFORMS names {}.{}, which has no such column",
+                    table, column
+                );
+            }
+        }
+
+        let original = msg.clone();
+        let CdcMessage::Relation { id, columns, .. } = msg else {
+            return;
+        };
+        let plan: Vec<Option<ColumnTransform>> = columns
+            .iter()
+            .map(|c| transforms.get(&*c.name).copied())
+            .collect();
+        let type_ids = columns.iter().map(|c| c.type_id).collect();
+        let mut transform = plan.iter();
+        columns.retain(|_| transform.next() != Some(&Some(ColumnTransform::Drop)));
+        for column in columns.iter_mut() {
+            if transforms.contains_key(&*column.name) {
+                column.type_id = 25;
+                column.type_mod = -1;
+            }
+        }
+        self.plans.insert(
+            *id,
+            RelationPlan {
+                original,
+                columns: plan,
+                type_ids,
+            },
+        );
+    }
+
+    fn tuple(&self, plan: &RelationPlan, tuple: &mut Tuple) {
+        let mut cols = Vec::with_capacity(tuple.cols.len());
+        let mut toast_bitmap = 0u64;
+        for (idx, (value, transform)) in std::mem::take(&mut tuple.cols)
+            .into_iter()
+            .zip(plan.columns.iter().chain(std::iter::repeat(&None)))
+            .enumerate()
+        {
+            let value = match (transform, value) {
+                (Some(ColumnTransform::Drop), _) => continue,
+                // Masked columns are text, whatever the format they came in
+                (Some(mask), value @ (TupleData::Text(_) | TupleData::Binary(_))) => {
+                    let type_id = plan.type_ids.get(idx).copied().unwrap_or(0);
+                    let text = value.text(type_id).unwrap_or_default();
+                    let masked = mask.mask(&text, &self.transforms.hash_salt);
+                    TupleData::Text(Bytes::from(masked.unwrap_or_default().into_owned()))
+                }
+                (_, value) => value,
+            };
+            if tuple.is_toast_column(idx) && cols.len() < 64 {
+                toast_bitmap |= 1u64 << cols.len();
+            }
+            cols.push(value);
+        }
+        tuple.cols = cols;
+        tuple.toast_bitmap = toast_bitmap;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::Column;
+
+    fn relation() -> CdcMessage {
+        let column = |name: &str, flags, type_id| Column {
+            flags,
+            name: name.into(),
+            type_id,
+            type_mod: -1,
+        };
+        CdcMessage::Relation {
+            id: 7,
+            namespace: "public".into(),
+            name: "users".into(),
+            replica_identity: b'd',
+            columns: vec![
+                column("id", 1, 23),
+                column("ssn", 0, 25),
+                column("email", 0, 1043),
+                column("phone", 0, 25),
+            ],
+        }
+    }
+
+    fn text(value: &str) -> TupleData {
+        TupleData::Text(Bytes::copy_from_slice(value.as_bytes()))
+    }
+
+    fn users_transformer() -> ColumnTransformer {
+        ColumnTransformer::new(ColumnTransforms {
+            tables: HashMap::from([(
+                "users".to_string(),
+                HashMap::from([
+                    ("ssn".to_string(), ColumnTransform::Drop),
+                    ("email".to_string(), ColumnTransform::Redact),
+                    ("phone".to_string(), ColumnTransform::Truncate(3)),
+                ]),
+            )]),
+            hash_salt: String::new(),
+        })
+    }
+
+    #[test]
+    fn test_relation_and_rows_are_rewritten() {
+        let mut transformer = users_transformer();
+        let mut msg = relation();
+        transformer.apply(&mut msg);
+        let CdcMessage::Relation { columns, .. } = &msg else {
+            panic!("not a relation");
+        };
+        let columns: Vec<(&str, u32)> = columns.iter().map(|c| (&*c.name, c.type_id)).collect();
+        assert_eq!(columns, vec![("id", 23), ("email", 25), ("phone", 25)]);
+
+        let mut insert = CdcMessage::Insert {
+            relation_id: 7,
+            tuple: Tuple {
+                cols: vec![
+                    text("1"),
+                    text("123-45-6789"),
+                    text("a@b.c"),
+                    text("5551234"),
+                ],
+                toast_bitmap: 0,
+            },
+        };
+        transformer.apply(&mut insert);
+        let CdcMessage::Insert { tuple, .. } = &insert else {
+            panic!("not an insert");
+        };
+        let values: Vec<String> = tuple
+            .cols
+            .iter()
+            .map(|c| match c {
+                TupleData::Text(b) => String::from_utf8_lossy(b).into_owned(),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(values, vec!["1", "REDACTED", "555"]);
+
+        // Unchanged TOAST values stay unchanged, at their new position
+        let mut update = CdcMessage::Update {
+            relation_id: 7,
+            old_tuple: None,
+            new_tuple: Tuple {
+                cols: vec![text("1"), TupleData::Null, text("x@y.z"), TupleData::Toast],
+                toast_bitmap: 1 << 3,
+            },
+        };
+        transformer.apply(&mut update);
+        let CdcMessage::Update { new_tuple, .. } = &update else {
+            panic!("not an update");
+        };
+        assert_eq!(new_tuple.cols.len(), 3);
+        assert!(matches!(new_tuple.cols[2], TupleData::Toast));
+        assert_eq!(new_tuple.toast_bitmap, 1 << 2);
+    }
+
+    #[test]
+    fn test_original_relations_are_handed_over() {
+        let mut transformer = users_transformer();
+        let mut msg = relation();
+        transformer.apply(&mut msg);
+
+        let handed_over = transformer.original_relations(vec![msg]);
+        let CdcMessage::Relation { columns, .. } = &handed_over[0] else {
+            panic!("not a relation");
+        };
+        assert_eq!(columns.len(), 4);
+
+        // Read back on restart, it is rewritten again
+        let mut restored = handed_over[0].clone();
+        let mut transformer = users_transformer();
+        transformer.apply(&mut restored);
+        let CdcMessage::Relation { columns, .. } = &restored else {
+            panic!("not a relation");
+        };
+        assert_eq!(columns.len(), 3);
+    }
+}
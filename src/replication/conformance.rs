@@ -0,0 +1,256 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! pgoutput protocol conformance tests.
+//!
+//! The fixtures in `fixtures/` are replication byte streams, one CopyData
+//! frame per line in hex (`*.hex`). Each stream is replayed through the frame
+//! parser and the pgoutput decoder, and the decoded frames are compared with
+//! `*.messages.golden`. The v1 stream is also run through the pipeline into a
+//! recording sink and compared with `*.records.golden`, which covers the
+//! schema cache, TOAST handling and value conversion. The v2 stream checks
+//! which events a streamed transaction hands the pipeline.
+//!
+//! After an intended change in decoding, rewrite the golden files with
+//! `UPDATE_GOLDEN=1 cargo test conformance` and review the diff.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use super::streaming::{Decoded, TransactionStreams};
+use super::wal_handler::{parse_replication_message, parse_xlog_data, WalMessage};
+use crate::core::{CdcRecord, LoadingModel, Sink, SinkCapabilities, SinkResult};
+use crate::pipeline::schema_cache::SchemaDelta;
+use crate::pipeline::Pipeline;
+use crate::sink::NewSinkAdapter;
+use crate::source::parser::{CdcEvent, CdcMessage};
+
+fn fixture_path(name: &str) -> String {
+    format!(
+        "{}/src/replication/fixtures/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    )
+}
+
+fn read_fixture(name: &str) -> String {
+    std::fs::read_to_string(fixture_path(name))
+        .unwrap_or_else(|e| panic!("cannot read fixture {}: {}", name, e))
+}
+
+/// Frames of a `.hex` fixture. Blank lines and `#` comments are skipped,
+/// whitespace inside a frame is ignored.
+fn frames(name: &str) -> Vec<Bytes> {
+    read_fixture(name)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let hex: String = line.split_whitespace().collect();
+            let frame = hex::decode(&hex)
+                .unwrap_or_else(|e| panic!("{}: bad frame {:?}: {}", name, line, e));
+            Bytes::from(frame)
+        })
+        .collect()
+}
+
+/// Decodes every frame of a fixture: one JSON line per frame, and the events
+/// the replication loop would send to the pipeline.
+fn decode(name: &str) -> (Vec<String>, Vec<CdcEvent>) {
+    let mut lines = Vec::new();
+    let mut events = Vec::new();
+    let mut streams = TransactionStreams::default();
+    for mut frame in frames(name) {
+        let line = match parse_replication_message(&mut frame) {
+            Some(WalMessage::XLogData { lsn, data }) => {
+                let in_block = streams.in_block(&data);
+                match parse_xlog_data(&data, lsn, in_block) {
+                    Ok(Some(decoded)) => {
+                        let line = decoded_line(lsn, &decoded);
+                        for message in streams.accept(decoded).unwrap() {
+                            let message = message.unwrap();
+                            events.push(CdcEvent { lsn, message });
+                        }
+                        line
+                    }
+                    Ok(None) => "null".to_string(),
+                    Err(e) => json!({ "Error": e.to_string() }).to_string(),
+                }
+            }
+            Some(WalMessage::KeepAlive {
+                lsn,
+                reply_requested,
+                ..
+            }) => json!({ "KeepAlive": { "lsn": lsn, "reply_requested": reply_requested } })
+                .to_string(),
+            Some(WalMessage::Unknown(tag)) => json!({ "UnknownFrame": tag }).to_string(),
+            None => "null".to_string(),
+        };
+        lines.push(line);
+    }
+    (lines, events)
+}
+
+/// JSON line of a decoded frame: the event for a message, else the frame
+/// with its LSN.
+fn decoded_line(lsn: u64, decoded: &Decoded) -> String {
+    match decoded {
+        Decoded::Message(message) => serde_json::to_string(&CdcEvent {
+            lsn,
+            message: message.clone(),
+        })
+        .unwrap(),
+        Decoded::Streamed { xid, message } => format!(
+            r#"{{"lsn":{},"xid":{},"message":{}}}"#,
+            lsn,
+            xid,
+            serde_json::to_string(message).unwrap()
+        ),
+        Decoded::Stream(stream) => format!(
+            r#"{{"lsn":{},"stream":{}}}"#,
+            lsn,
+            serde_json::to_string(stream).unwrap()
+        ),
+    }
+}
+
+/// Compares `actual` with a golden file line by line, or rewrites the file
+/// when `UPDATE_GOLDEN` is set.
+fn assert_golden(name: &str, actual: &[String]) {
+    let actual = actual.join("\n") + "\n";
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(fixture_path(name), &actual).unwrap();
+        return;
+    }
+    let expected = read_fixture(name);
+    for (i, (got, want)) in actual.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(got, want, "{} differs at line {}", name, i + 1);
+    }
+    assert_eq!(
+        actual.lines().count(),
+        expected.lines().count(),
+        "{} has a different number of lines",
+        name
+    );
+}
+
+/// Keeps every record and schema delta it receives as a JSON line.
+struct RecordingSink {
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl Sink for RecordingSink {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            supports_upsert: true,
+            supports_delete: true,
+            supports_schema_evolution: true,
+            supports_transactions: false,
+            loading_model: LoadingModel::Streaming,
+            min_batch_size: None,
+            max_batch_size: None,
+            optimal_flush_interval_ms: 1000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn write_batch(&mut self, records: Vec<CdcRecord>) -> Result<SinkResult> {
+        let mut log = self.log.lock();
+        for record in &records {
+            log.push(serde_json::to_string(record)?);
+        }
+        Ok(SinkResult {
+            records_written: records.len(),
+            bytes_written: 0,
+            last_position: None,
+            ..Default::default()
+        })
+    }
+
+    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+        let delta = serde_json::to_string(delta)?;
+        self.log
+            .lock()
+            .push(format!("{{\"SchemaDelta\":{}}}", delta));
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `events` through a pipeline into a [`RecordingSink`] and returns
+/// what the sink received.
+async fn run_pipeline(events: Vec<CdcEvent>) -> Vec<String> {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let sink = NewSinkAdapter::new(Box::new(RecordingSink { log: log.clone() }));
+    let (tx, rx) = mpsc::channel(events.len().max(1));
+    for event in events {
+        tx.send(event).await.unwrap();
+    }
+    drop(tx);
+
+    // Batches are only cut by schema changes and the final flush, so record
+    // positions don't depend on timing
+    Pipeline::new(rx, Box::new(sink), 10_000, Duration::from_secs(3600))
+        .run()
+        .await;
+    // The pipeline dropped the sink and its handle on the log
+    Arc::try_unwrap(log).unwrap().into_inner()
+}
+
+#[test]
+fn test_pgoutput_v1_messages() {
+    let (lines, _) = decode("pgoutput_v1.hex");
+    assert_golden("pgoutput_v1.messages.golden", &lines);
+}
+
+#[tokio::test]
+async fn test_pgoutput_v1_pipeline_records() {
+    let (_, events) = decode("pgoutput_v1.hex");
+    let records = run_pipeline(events).await;
+    assert_golden("pgoutput_v1.records.golden", &records);
+}
+
+#[test]
+fn test_pgoutput_v2_messages() {
+    let (lines, events) = decode("pgoutput_v2.hex");
+    assert_golden("pgoutput_v2.messages.golden", &lines);
+
+    // The streamed transaction reaches the pipeline whole, at its commit
+    let messages: Vec<(u64, &CdcMessage)> = events.iter().map(|e| (e.lsn, &e.message)).collect();
+    assert_eq!(messages.len(), 3);
+    assert!(messages.iter().all(|(lsn, _)| *lsn == 0x2000180));
+    assert!(matches!(messages[0].1, CdcMessage::Begin { xid: 900, .. }));
+    assert!(matches!(
+        messages[1].1,
+        CdcMessage::Insert {
+            relation_id: 16385,
+            ..
+        }
+    ));
+    assert!(matches!(
+        messages[2].1,
+        CdcMessage::Commit {
+            end_lsn: 0x20001b0,
+            ..
+        }
+    ));
+}
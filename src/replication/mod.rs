@@ -1,6 +1,14 @@
 // Copyright 2025
 // Licensed under the Elastic License v2.0
 
+#[cfg(test)]
+mod conformance;
+mod parse_stage;
+mod start_cutoff;
+mod streaming;
 mod wal_handler;
 
+pub use parse_stage::ParseStage;
+pub use start_cutoff::StartCutoff;
+pub use streaming::TransactionStreams;
 pub use wal_handler::{handle_keepalive, handle_xlog_data, parse_replication_message, WalMessage};
@@ -0,0 +1,239 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Parallel pgoutput parsing (`PARSE_WORKERS` > 1).
+//!
+//! The replication reader hands raw XLogData frames to [`ParseStage`] instead
+//! of decoding them inline. The stage groups whatever frames are queued into
+//! chunks and decodes up to `PARSE_WORKERS` chunks at once on the main runtime's
+//! worker threads. Decoded chunks are forwarded strictly in the order they
+//! were read, so every transaction reaches the pipeline exactly as with inline
+//! parsing, and the per-message side effects (start cutoff, snapshot
+//! deduplication, holding streamed transactions) still run one message at a
+//! time.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::start_cutoff::StartCutoff;
+use super::streaming::{Decoded, TransactionStreams};
+use super::wal_handler::{forward_message, parse_xlog_data};
+use crate::grpc::state::SharedState;
+use crate::runtime;
+use crate::source::parser::CdcEvent;
+
+/// Frames decoded by one parser task at most
+const MAX_CHUNK_FRAMES: usize = 256;
+/// Raw frames the reader can queue ahead of the parsers
+const FRAME_QUEUE_CAPACITY: usize = 8192;
+
+/// LSN, data, and whether the frame is inside a stream block
+type Frame = (u64, Bytes, bool);
+
+/// Handle to the parser tasks, owned by the replication loop.
+pub struct ParseStage {
+    frames_tx: mpsc::Sender<Frame>,
+    /// Frames pushed but not yet forwarded to the pipeline
+    in_flight: Arc<AtomicUsize>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl ParseStage {
+    /// Starts the stage. It owns the start cutoff from here on, since the
+    /// cutoff must see messages in WAL order.
+    pub fn spawn(
+        workers: usize,
+        tx: mpsc::Sender<CdcEvent>,
+        shared_state: Arc<SharedState>,
+        flush_size: usize,
+        start_cutoff: Option<StartCutoff>,
+        streams: TransactionStreams,
+    ) -> Self {
+        let (frames_tx, frames_rx) = mpsc::channel(FRAME_QUEUE_CAPACITY);
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let task = tokio::spawn(run(
+            frames_rx,
+            workers.max(1),
+            Forwarder {
+                tx,
+                shared_state,
+                flush_size,
+                start_cutoff,
+                streams,
+                in_flight: in_flight.clone(),
+            },
+        ));
+        Self {
+            frames_tx,
+            in_flight,
+            task,
+        }
+    }
+
+    /// Queues one XLogData frame, waiting while the queue is full. `in_block`
+    /// is [`TransactionStreams::in_block`] of the reader.
+    pub async fn push(&self, lsn: u64, data: Bytes, in_block: bool) -> Result<()> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        self.frames_tx
+            .send((lsn, data, in_block))
+            .await
+            .map_err(|_| anyhow!("parse stage stopped"))
+    }
+
+    /// True when every pushed frame has been forwarded to the pipeline.
+    pub fn is_idle(&self) -> bool {
+        self.in_flight.load(Ordering::Relaxed) == 0
+    }
+
+    /// Resolves when the stage stops, with the error that stopped it.
+    pub async fn finished(&mut self) -> Result<()> {
+        (&mut self.task).await.context("parse stage panicked")?
+    }
+}
+
+/// Sequential half of the stage: everything after decoding.
+struct Forwarder {
+    tx: mpsc::Sender<CdcEvent>,
+    shared_state: Arc<SharedState>,
+    flush_size: usize,
+    start_cutoff: Option<StartCutoff>,
+    streams: TransactionStreams,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Forwarder {
+    async fn forward(&mut self, lsn: u64, parsed: Result<Option<Decoded>>) -> Result<()> {
+        if let Some(decoded) = parsed? {
+            for cdc_msg in self.streams.accept(decoded)? {
+                forward_message(
+                    cdc_msg?,
+                    lsn,
+                    &self.tx,
+                    &self.shared_state,
+                    self.flush_size,
+                    &mut self.start_cutoff,
+                )
+                .await?;
+            }
+        }
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+async fn run(
+    frames_rx: mpsc::Receiver<Frame>,
+    workers: usize,
+    mut forwarder: Forwarder,
+) -> Result<()> {
+    // Take whatever is queued, so a quiet stream still forwards each frame
+    // right away and a busy one is decoded in large chunks
+    let chunks = stream::unfold(frames_rx, |mut rx| async move {
+        let first = rx.recv().await?;
+        let mut chunk = Vec::with_capacity(MAX_CHUNK_FRAMES);
+        chunk.push(first);
+        while chunk.len() < MAX_CHUNK_FRAMES {
+            match rx.try_recv() {
+                Ok(frame) => chunk.push(frame),
+                Err(_) => break,
+            }
+        }
+        Some((chunk, rx))
+    });
+
+    // `buffered` yields results in submission order while up to `workers`
+    // chunks decode in parallel
+    let mut decoded = chunks
+        .map(|chunk| runtime::spawn_worker(async move { decode_chunk(chunk) }))
+        .buffered(workers);
+
+    while let Some(chunk) = decoded.next().await {
+        for (lsn, parsed) in chunk.context("parser task panicked")? {
+            forwarder.forward(lsn, parsed).await?;
+        }
+    }
+    Ok(())
+}
+
+fn decode_chunk(chunk: Vec<Frame>) -> Vec<(u64, Result<Option<Decoded>>)> {
+    chunk
+        .into_iter()
+        .map(|(lsn, data, in_block)| (lsn, parse_xlog_data(&data, lsn, in_block)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::state::CdcConfig;
+    use crate::source::parser::CdcMessage;
+
+    fn make_state() -> Arc<SharedState> {
+        SharedState::new(CdcConfig {
+            flush_size: 1000,
+            flush_interval_ms: 5000,
+            tables: vec!["public.orders".to_string()],
+            slot_name: "dbmazz_slot".to_string(),
+        })
+    }
+
+    /// pgoutput Begin message with `xid`
+    fn begin_frame(xid: u32) -> Bytes {
+        let mut data = vec![b'B'];
+        data.extend_from_slice(&0u64.to_be_bytes());
+        data.extend_from_slice(&0u64.to_be_bytes());
+        data.extend_from_slice(&xid.to_be_bytes());
+        Bytes::from(data)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_forwards_in_read_order() {
+        let (tx, mut rx) = mpsc::channel(10_000);
+        let stage = ParseStage::spawn(4, tx, make_state(), 5000, None, Default::default());
+
+        for xid in 0..2_000u32 {
+            stage
+                .push(u64::from(xid), begin_frame(xid), false)
+                .await
+                .unwrap();
+        }
+
+        for expected in 0..2_000u32 {
+            let event = rx.recv().await.unwrap();
+            assert_eq!(event.lsn, u64::from(expected));
+            match event.message {
+                CdcMessage::Begin { xid, .. } => assert_eq!(xid, expected),
+                other => panic!("unexpected message: {:?}", other),
+            }
+        }
+        // The counter drops right after the last send
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !stage.is_idle() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_parse_error_stops_stage() {
+        let (tx, _rx) = mpsc::channel(16);
+        let mut stage = ParseStage::spawn(2, tx, make_state(), 8, None, Default::default());
+
+        // Insert with a bad tuple marker
+        let mut data = vec![b'I'];
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.push(b'X');
+        stage.push(0x10, Bytes::from(data), false).await.unwrap();
+
+        let err = stage.finished().await.unwrap_err();
+        assert!(err.to_string().contains("0x10"));
+    }
+}
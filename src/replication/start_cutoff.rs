@@ -0,0 +1,164 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Time-based start position (`START_POSITION=<timestamp>`).
+//!
+//! A logical slot cannot be positioned by time, so streaming starts from the
+//! usual checkpoint/slot position and whole transactions are dropped until
+//! the first one whose commit timestamp (carried by pgoutput `Begin`) is at
+//! or after the cutoff. The LSN of that transaction is the resolved start
+//! position; from then on every message is forwarded.
+
+use chrono::{DateTime, Utc};
+
+use crate::source::parser::CdcMessage;
+
+/// PostgreSQL epoch (2000-01-01 00:00:00 UTC) in Unix microseconds
+const PG_EPOCH_OFFSET_USEC: i64 = 946_684_800_000_000;
+
+/// What to do with a message while the cutoff is active
+#[derive(Debug, PartialEq, Eq)]
+pub enum CutoffDecision {
+    /// Pass the message on to the pipeline
+    Forward,
+    /// Message belongs to a transaction committed before the cutoff
+    Drop,
+    /// First transaction at or after the cutoff: forward it and stop filtering
+    Reached,
+}
+
+/// Drops transactions committed before a requested start time.
+pub struct StartCutoff {
+    cutoff: DateTime<Utc>,
+    /// `cutoff` in pgoutput timestamp units (microseconds since 2000-01-01)
+    cutoff_pg_usec: u64,
+    in_skipped_txn: bool,
+    first_skipped_lsn: Option<u64>,
+    skipped_txns: u64,
+}
+
+impl StartCutoff {
+    pub fn new(cutoff: DateTime<Utc>) -> Self {
+        let cutoff_pg_usec = (cutoff.timestamp_micros() - PG_EPOCH_OFFSET_USEC).max(0) as u64;
+        Self {
+            cutoff,
+            cutoff_pg_usec,
+            in_skipped_txn: false,
+            first_skipped_lsn: None,
+            skipped_txns: 0,
+        }
+    }
+
+    pub fn cutoff(&self) -> DateTime<Utc> {
+        self.cutoff
+    }
+
+    /// LSN of the first dropped transaction, if any was dropped
+    pub fn first_skipped_lsn(&self) -> Option<u64> {
+        self.first_skipped_lsn
+    }
+
+    pub fn skipped_txns(&self) -> u64 {
+        self.skipped_txns
+    }
+
+    /// Classifies one decoded message received at `lsn`.
+    pub fn decide(&mut self, msg: &CdcMessage, lsn: u64) -> CutoffDecision {
+        match msg {
+            CdcMessage::Begin { timestamp, .. } => {
+                if *timestamp >= self.cutoff_pg_usec {
+                    return CutoffDecision::Reached;
+                }
+                self.in_skipped_txn = true;
+                self.first_skipped_lsn.get_or_insert(lsn);
+                self.skipped_txns += 1;
+                CutoffDecision::Drop
+            }
+            // Relation messages feed the schema cache, which later transactions rely on
+            CdcMessage::Relation { .. } => CutoffDecision::Forward,
+            CdcMessage::Commit { .. } if self.in_skipped_txn => {
+                self.in_skipped_txn = false;
+                CutoffDecision::Drop
+            }
+            CdcMessage::Insert { .. } | CdcMessage::Update { .. } | CdcMessage::Delete { .. }
+                if self.in_skipped_txn =>
+            {
+                CutoffDecision::Drop
+            }
+            _ => CutoffDecision::Forward,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::Tuple;
+
+    fn begin(cutoff: &StartCutoff, offset_usec: i64) -> CdcMessage {
+        CdcMessage::Begin {
+            final_lsn: 0,
+            timestamp: (cutoff.cutoff_pg_usec as i64 + offset_usec) as u64,
+            xid: 1,
+        }
+    }
+
+    fn insert() -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id: 1,
+            tuple: Tuple {
+                cols: vec![],
+                toast_bitmap: 0,
+            },
+        }
+    }
+
+    fn commit() -> CdcMessage {
+        CdcMessage::Commit {
+            flags: 0,
+            commit_lsn: 0,
+            end_lsn: 0,
+            timestamp: 0,
+        }
+    }
+
+    fn relation() -> CdcMessage {
+        CdcMessage::Relation {
+            id: 1,
+            namespace: "public".into(),
+            name: "orders".into(),
+            replica_identity: b'f',
+            columns: vec![],
+        }
+    }
+
+    #[test]
+    fn test_cutoff_in_pg_epoch() {
+        let cutoff = StartCutoff::new("2000-01-01T00:00:01Z".parse().unwrap());
+        assert_eq!(cutoff.cutoff_pg_usec, 1_000_000);
+
+        // Before the PostgreSQL epoch clamps to zero
+        let cutoff = StartCutoff::new("1999-12-31T00:00:00Z".parse().unwrap());
+        assert_eq!(cutoff.cutoff_pg_usec, 0);
+    }
+
+    #[test]
+    fn test_drops_transactions_before_cutoff() {
+        let mut cutoff = StartCutoff::new("2025-06-01T12:00:00Z".parse().unwrap());
+
+        let old = begin(&cutoff, -1);
+        assert_eq!(cutoff.decide(&old, 100), CutoffDecision::Drop);
+        assert_eq!(cutoff.decide(&relation(), 110), CutoffDecision::Forward);
+        assert_eq!(cutoff.decide(&insert(), 120), CutoffDecision::Drop);
+        assert_eq!(cutoff.decide(&commit(), 130), CutoffDecision::Drop);
+
+        let old = begin(&cutoff, -500);
+        assert_eq!(cutoff.decide(&old, 200), CutoffDecision::Drop);
+        assert_eq!(cutoff.decide(&commit(), 210), CutoffDecision::Drop);
+
+        let new = begin(&cutoff, 0);
+        assert_eq!(cutoff.decide(&new, 300), CutoffDecision::Reached);
+        assert_eq!(cutoff.first_skipped_lsn(), Some(100));
+        assert_eq!(cutoff.skipped_txns(), 2);
+    }
+}
@@ -0,0 +1,550 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! In-progress transactions streamed by pgoutput
+//! (`PGOUTPUT_PROTOCOL_VERSION` 2 and up).
+//!
+//! With protocol version 1 PostgreSQL sends a transaction once it commits;
+//! until then the walsender keeps it in `logical_decoding_work_mem` and
+//! spills the rest to disk, and a bulk load reaches dbmazz all at once after
+//! its commit. With version 2 and `streaming 'on'`, a transaction past
+//! `logical_decoding_work_mem` is streamed while it runs, in blocks between
+//! Stream Start and Stream Stop, interleaved with other transactions.
+//!
+//! Its changes are decoded as they arrive and held by [`TransactionStreams`]
+//! until Stream Commit forwards them to the pipeline as one Begin .. Commit
+//! transaction, or Stream Abort drops them (only those of the subtransaction,
+//! when a subtransaction aborts). They are forwarded with the LSN of the
+//! Stream Commit frame, so the LSNs the pipeline sees keep increasing and no
+//! checkpoint is confirmed inside an uncommitted transaction.
+//!
+//! Held changes stay in memory up to `STREAMED_TXN_MEMORY_MB` over all
+//! transactions. Past that, the changes of the transaction growing are
+//! appended to a file of its own in the spill directory, and read back one by
+//! one as the commit forwards them. Each file is a sequence of frames: the
+//! length of the encoded change as a little-endian `u32`, then the xid of its
+//! subtransaction and the change in bincode. Files are deleted once the
+//! transaction commits or aborts, and at startup: the slot streams their
+//! transactions again.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::{debug, info, warn};
+
+use crate::source::parser::{CdcMessage, StreamMessage};
+
+/// In-memory limit of held changes without a configured one
+const DEFAULT_MEMORY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Numbers the spill files of this process
+static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// What one XLogData frame decodes to.
+#[derive(Debug)]
+pub enum Decoded {
+    /// A message of a transaction sent at its commit, or of none
+    Message(CdcMessage),
+    /// A change of an in-progress transaction, `xid` being its own or its
+    /// subtransaction's
+    Streamed { xid: u32, message: CdcMessage },
+    /// Start, end, commit or abort of a streamed transaction
+    Stream(StreamMessage),
+}
+
+/// Stream blocks and the changes of streamed transactions not committed yet.
+pub struct TransactionStreams {
+    /// A Stream Start was read and its Stream Stop not yet
+    in_block: bool,
+    /// Transaction of the stream block being decoded
+    current: Option<u32>,
+    /// Changes held for each streamed transaction
+    held: HashMap<u32, Held>,
+    /// Encoded size of the changes held in memory, over all transactions
+    memory_bytes: u64,
+    /// Size past which changes are spilled
+    max_memory_bytes: u64,
+    /// Where spill files go
+    spill_dir: PathBuf,
+}
+
+/// The changes of one streamed transaction.
+#[derive(Default)]
+struct Held {
+    /// Changes spilled to disk, older than those in memory
+    spilled: Option<SpillFile>,
+    /// Changes in memory, with the xid of their subtransaction and their
+    /// encoded size
+    changes: Vec<(u32, CdcMessage)>,
+    bytes: u64,
+    /// Subtransactions rolled back; their spilled changes are skipped when
+    /// read back
+    aborted: HashSet<u32>,
+}
+
+/// A transaction's spill file, deleted when dropped.
+struct SpillFile {
+    path: PathBuf,
+    /// Open until the transaction commits
+    writer: Option<BufWriter<File>>,
+    /// Changes written and not read back yet
+    changes: u64,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        self.writer = None;
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove streamed transaction file {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Messages to send to the pipeline, in order. Changes of a spilled
+/// transaction are read from disk as they are taken.
+#[derive(Default)]
+pub struct Forward {
+    /// Begin of a committed transaction, or a message passed on as is
+    first: Option<CdcMessage>,
+    spilled: Option<SpilledChanges>,
+    changes: std::vec::IntoIter<(u32, CdcMessage)>,
+    end: Option<CdcMessage>,
+}
+
+struct SpilledChanges {
+    reader: BufReader<File>,
+    file: SpillFile,
+    aborted: HashSet<u32>,
+}
+
+impl Forward {
+    fn one(message: CdcMessage) -> Self {
+        Self {
+            first: Some(message),
+            ..Self::default()
+        }
+    }
+}
+
+impl Iterator for Forward {
+    type Item = Result<CdcMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(first) = self.first.take() {
+            return Some(Ok(first));
+        }
+        while let Some(spilled) = self.spilled.as_mut() {
+            if spilled.file.changes == 0 {
+                self.spilled = None;
+                break;
+            }
+            spilled.file.changes -= 1;
+            match read_change(&mut spilled.reader, &spilled.file.path) {
+                Ok((xid, message)) if is_kept(&spilled.aborted, xid, &message) => {
+                    return Some(Ok(message))
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.spilled = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+        if let Some((_, message)) = self.changes.next() {
+            return Some(Ok(message));
+        }
+        self.end.take().map(Ok)
+    }
+}
+
+impl Default for TransactionStreams {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_BYTES, std::env::temp_dir())
+    }
+}
+
+impl TransactionStreams {
+    /// Holds up to `max_memory_bytes` of changes in memory, spilling the
+    /// rest to `spill_dir`.
+    pub fn new(max_memory_bytes: u64, spill_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            in_block: false,
+            current: None,
+            held: HashMap::new(),
+            memory_bytes: 0,
+            max_memory_bytes,
+            spill_dir: spill_dir.into(),
+        }
+    }
+
+    /// Deletes the spill files of an earlier run from `dir`.
+    pub fn clear_spill_dir(dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let entries =
+            fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if is_spill_file(&path) {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+                info!("Removed streamed transaction file {}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the frame `data`, the next one in read order, is inside a
+    /// stream block, where messages carry an xid. Decided ahead of decoding
+    /// so frames can be decoded in parallel.
+    pub fn in_block(&mut self, data: &[u8]) -> bool {
+        match data.first() {
+            Some(b'S') => {
+                self.in_block = true;
+                false
+            }
+            Some(b'E') => {
+                self.in_block = false;
+                false
+            }
+            _ => self.in_block,
+        }
+    }
+
+    /// Takes the next decoded frame in WAL order and returns the messages to
+    /// send to the pipeline now: those of a transaction streamed in full
+    /// once it commits.
+    pub fn accept(&mut self, decoded: Decoded) -> Result<Forward> {
+        let stream = match decoded {
+            Decoded::Message(message) => return Ok(Forward::one(message)),
+            Decoded::Streamed { xid, message } => {
+                let txn = self.current.ok_or_else(|| {
+                    anyhow!(
+                        "change of transaction {} streamed outside a stream block",
+                        xid
+                    )
+                })?;
+                self.hold(txn, xid, message)?;
+                return Ok(Forward::default());
+            }
+            Decoded::Stream(stream) => stream,
+        };
+        match stream {
+            StreamMessage::Start { xid, first_segment } => {
+                // A transaction streamed again from its start, after a
+                // reconnect, replaces what was held of it
+                if first_segment {
+                    self.release(xid);
+                    self.held.insert(xid, Held::default());
+                }
+                self.current = Some(xid);
+            }
+            StreamMessage::Stop => self.current = None,
+            StreamMessage::Commit {
+                xid,
+                flags,
+                commit_lsn,
+                end_lsn,
+                timestamp,
+            } => {
+                let mut held = self.release(xid).unwrap_or_default();
+                let spilled = match held.spilled.take() {
+                    Some(mut file) => {
+                        if let Some(mut writer) = file.writer.take() {
+                            writer.flush().with_context(|| {
+                                format!("Failed to write {}", file.path.display())
+                            })?;
+                        }
+                        let reader = File::open(&file.path)
+                            .with_context(|| format!("Failed to open {}", file.path.display()))?;
+                        Some(SpilledChanges {
+                            file,
+                            reader: BufReader::new(reader),
+                            aborted: std::mem::take(&mut held.aborted),
+                        })
+                    }
+                    None => None,
+                };
+                debug!(
+                    "Streamed transaction {} committed with {} messages, {} spilled",
+                    xid,
+                    held.changes.len(),
+                    spilled.as_ref().map_or(0, |s| s.file.changes)
+                );
+                return Ok(Forward {
+                    first: Some(CdcMessage::Begin {
+                        final_lsn: commit_lsn,
+                        timestamp,
+                        xid,
+                    }),
+                    spilled,
+                    changes: std::mem::take(&mut held.changes).into_iter(),
+                    end: Some(CdcMessage::Commit {
+                        flags,
+                        commit_lsn,
+                        end_lsn,
+                        timestamp,
+                    }),
+                });
+            }
+            StreamMessage::Abort { xid, subxid } if xid == subxid => {
+                self.release(xid);
+            }
+            StreamMessage::Abort { xid, subxid } => {
+                if let Some(held) = self.held.get_mut(&xid) {
+                    // Relation messages describe a table for the changes
+                    // after them, whichever subtransaction they came in
+                    let aborted = HashSet::from([subxid]);
+                    let mut kept = 0;
+                    held.changes.retain(|(change_xid, message)| {
+                        let keep = is_kept(&aborted, *change_xid, message);
+                        if keep {
+                            kept += change_size(message);
+                        }
+                        keep
+                    });
+                    self.memory_bytes -= held.bytes - kept;
+                    held.bytes = kept;
+                    if held.spilled.is_some() {
+                        held.aborted.insert(subxid);
+                    }
+                }
+            }
+        }
+        Ok(Forward::default())
+    }
+
+    /// Holds a change of `txn`, spilling the transaction's changes in memory
+    /// once all held changes are past the limit.
+    fn hold(&mut self, txn: u32, xid: u32, message: CdcMessage) -> Result<()> {
+        let size = change_size(&message);
+        let held = self.held.entry(txn).or_default();
+        held.changes.push((xid, message));
+        held.bytes += size;
+        self.memory_bytes += size;
+        if self.memory_bytes <= self.max_memory_bytes {
+            return Ok(());
+        }
+
+        if held.spilled.is_none() {
+            let path = self.spill_dir.join(format!(
+                "streamed-{}-{}.txn",
+                txn,
+                NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+            ));
+            let file = File::create(&path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            info!(
+                "Streamed transaction {} is past STREAMED_TXN_MEMORY_MB, spilling it to {}",
+                txn,
+                path.display()
+            );
+            held.spilled = Some(SpillFile {
+                path,
+                writer: Some(BufWriter::new(file)),
+                changes: 0,
+            });
+        }
+        let Some(file) = held.spilled.as_mut() else {
+            unreachable!("spill file created above");
+        };
+        let Some(writer) = file.writer.as_mut() else {
+            unreachable!("spill file written until the commit");
+        };
+        for change in held.changes.drain(..) {
+            let frame = bincode::serialize(&change).context("Failed to encode a held change")?;
+            writer
+                .write_all(&(frame.len() as u32).to_le_bytes())
+                .and_then(|()| writer.write_all(&frame))
+                .with_context(|| format!("Failed to write {}", file.path.display()))?;
+            file.changes += 1;
+        }
+        self.memory_bytes -= held.bytes;
+        held.bytes = 0;
+        Ok(())
+    }
+
+    /// Takes what is held of `txn` out of the memory accounting.
+    fn release(&mut self, txn: u32) -> Option<Held> {
+        let held = self.held.remove(&txn)?;
+        self.memory_bytes -= held.bytes;
+        Some(held)
+    }
+}
+
+/// Whether a change survives the rollback of the `aborted` subtransactions
+fn is_kept(aborted: &HashSet<u32>, xid: u32, message: &CdcMessage) -> bool {
+    !aborted.contains(&xid) || matches!(message, CdcMessage::Relation { .. })
+}
+
+/// Size of a held change, as spilled
+fn change_size(message: &CdcMessage) -> u64 {
+    4 + bincode::serialized_size(message).unwrap_or(0)
+}
+
+fn read_change(reader: &mut BufReader<File>, path: &Path) -> Result<(u32, CdcMessage)> {
+    let mut len = [0u8; 4];
+    let mut frame = Vec::new();
+    reader
+        .read_exact(&mut len)
+        .and_then(|()| {
+            frame.resize(u32::from_le_bytes(len) as usize, 0);
+            reader.read_exact(&mut frame)
+        })
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    bincode::deserialize(&frame)
+        .with_context(|| format!("Corrupt streamed change in {}", path.display()))
+}
+
+fn is_spill_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("streamed-") && name.ends_with(".txn"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::Tuple;
+
+    fn insert(relation_id: u32) -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id,
+            tuple: Tuple {
+                cols: vec![],
+                toast_bitmap: 0,
+            },
+        }
+    }
+
+    fn streamed(xid: u32, relation_id: u32) -> Decoded {
+        Decoded::Streamed {
+            xid,
+            message: insert(relation_id),
+        }
+    }
+
+    fn start(xid: u32, first_segment: bool) -> Decoded {
+        Decoded::Stream(StreamMessage::Start { xid, first_segment })
+    }
+
+    fn kinds(forward: Result<Forward>) -> Vec<String> {
+        forward
+            .unwrap()
+            .map(|msg| match msg.unwrap() {
+                CdcMessage::Begin { xid, .. } => format!("begin {}", xid),
+                CdcMessage::Insert { relation_id, .. } => format!("insert {}", relation_id),
+                CdcMessage::Commit { commit_lsn, .. } => format!("commit {:X}", commit_lsn),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    fn stop() -> Decoded {
+        Decoded::Stream(StreamMessage::Stop)
+    }
+
+    fn commit(xid: u32) -> Decoded {
+        Decoded::Stream(StreamMessage::Commit {
+            xid,
+            flags: 0,
+            commit_lsn: 0x200,
+            end_lsn: 0x230,
+            timestamp: 0,
+        })
+    }
+
+    fn abort(xid: u32, subxid: u32) -> Decoded {
+        Decoded::Stream(StreamMessage::Abort { xid, subxid })
+    }
+
+    #[test]
+    fn test_streamed_transaction_is_forwarded_on_commit() {
+        let mut streams = TransactionStreams::default();
+
+        assert!(!streams.in_block(b"S"));
+        assert!(streams.in_block(b"I"));
+        assert!(!streams.in_block(b"E"));
+        assert!(!streams.in_block(b"B"));
+
+        // Two blocks of xid 900, with a transaction committing in between
+        assert!(kinds(streams.accept(start(900, true))).is_empty());
+        assert!(kinds(streams.accept(streamed(900, 1))).is_empty());
+        assert!(kinds(streams.accept(streamed(901, 2))).is_empty());
+        kinds(streams.accept(stop()));
+        let other = kinds(streams.accept(Decoded::Message(insert(3))));
+        assert_eq!(other, vec!["insert 3"]);
+        kinds(streams.accept(start(900, false)));
+        kinds(streams.accept(streamed(902, 4)));
+        kinds(streams.accept(stop()));
+
+        // Subtransaction 902 rolls back
+        assert!(kinds(streams.accept(abort(900, 902))).is_empty());
+        assert_eq!(
+            kinds(streams.accept(commit(900))),
+            vec!["begin 900", "insert 1", "insert 2", "commit 200"]
+        );
+
+        // An aborted transaction is dropped, and a change outside a block is an error
+        kinds(streams.accept(start(910, true)));
+        kinds(streams.accept(streamed(910, 1)));
+        kinds(streams.accept(stop()));
+        kinds(streams.accept(abort(910, 910)));
+        assert!(streams.held.is_empty());
+        assert_eq!(streams.memory_bytes, 0);
+        assert!(streams.accept(streamed(910, 1)).is_err());
+    }
+
+    #[test]
+    fn test_held_changes_past_the_limit_are_spilled() {
+        let dir = std::env::temp_dir().join(format!("dbmazz-streamed-test-{}", std::process::id()));
+        TransactionStreams::clear_spill_dir(&dir).unwrap();
+        let files = || fs::read_dir(&dir).unwrap().count();
+        // Room for two changes
+        let mut streams = TransactionStreams::new(2 * change_size(&insert(1)), &dir);
+
+        kinds(streams.accept(start(900, true)));
+        kinds(streams.accept(streamed(900, 1)));
+        kinds(streams.accept(streamed(901, 2)));
+        assert_eq!(files(), 0);
+        kinds(streams.accept(streamed(902, 3)));
+        assert_eq!(files(), 1);
+        assert_eq!(streams.memory_bytes, 0);
+        kinds(streams.accept(streamed(902, 4)));
+        kinds(streams.accept(streamed(900, 5)));
+        kinds(streams.accept(stop()));
+        assert_eq!(streams.memory_bytes, 2 * change_size(&insert(1)));
+
+        // Rolled back changes are skipped on disk and in memory
+        kinds(streams.accept(abort(900, 902)));
+        assert_eq!(streams.memory_bytes, change_size(&insert(1)));
+        assert_eq!(
+            kinds(streams.accept(commit(900))),
+            vec![
+                "begin 900",
+                "insert 1",
+                "insert 2",
+                "insert 5",
+                "commit 200"
+            ]
+        );
+        assert_eq!(streams.memory_bytes, 0);
+        assert_eq!(files(), 0);
+
+        // The file of an aborted transaction goes too
+        let mut streams = TransactionStreams::new(0, &dir);
+        kinds(streams.accept(start(910, true)));
+        kinds(streams.accept(streamed(910, 1)));
+        assert_eq!(files(), 1);
+        kinds(streams.accept(abort(910, 910)));
+        assert_eq!(files(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -2,8 +2,10 @@ use anyhow::{anyhow, Result};
 use bytes::{Buf, Bytes};
 use futures::SinkExt;
 use tokio::sync::mpsc;
-use tracing::error;
+use tracing::{error, info};
 
+use super::start_cutoff::{CutoffDecision, StartCutoff};
+use super::streaming::{Decoded, TransactionStreams};
 use crate::grpc::state::SharedState;
 use crate::source::parser::{CdcEvent, CdcMessage, PgOutputParser};
 use crate::source::postgres::build_standby_status_update;
@@ -13,8 +15,12 @@ use crate::source::postgres::build_standby_status_update;
 pub enum WalMessage {
     /// XLogData: WAL data with LSN
     XLogData { lsn: u64, data: Bytes },
-    /// KeepAlive: Keep-alive message with LSN
-    KeepAlive { lsn: u64, reply_requested: bool },
+    /// KeepAlive: Keep-alive message with LSN and the server clock when sent
+    KeepAlive {
+        lsn: u64,
+        server_time: i64,
+        reply_requested: bool,
+    },
     /// Unknown type
     Unknown(u8),
 }
@@ -49,11 +55,12 @@ pub fn parse_replication_message(bytes: &mut Bytes) -> Option<WalMessage> {
                 return None;
             }
             let wal_end = bytes.get_u64();
-            let _timestamp = bytes.get_u64();
+            let server_time = bytes.get_i64();
             let reply_requested = bytes.get_u8() == 1;
 
             Some(WalMessage::KeepAlive {
                 lsn: wal_end,
+                server_time,
                 reply_requested,
             })
         }
@@ -62,100 +69,159 @@ pub fn parse_replication_message(bytes: &mut Bytes) -> Option<WalMessage> {
 }
 
 /// Process XLogData data
+///
+/// `start_cutoff` is set while a time-based start position has not been
+/// reached yet; it is cleared once the first transaction past it arrives.
 pub async fn handle_xlog_data(
     data: Bytes,
     lsn: u64,
     tx: &mpsc::Sender<CdcEvent>,
     shared_state: &SharedState,
     flush_size: usize,
+    start_cutoff: &mut Option<StartCutoff>,
+    streams: &mut TransactionStreams,
 ) -> Result<()> {
     // Update LSN in SharedState
     shared_state.update_lsn(lsn);
 
+    let in_block = streams.in_block(&data);
+    match parse_xlog_data(&data, lsn, in_block)? {
+        Some(Decoded::Message(cdc_msg)) => {
+            forward_message(cdc_msg, lsn, tx, shared_state, flush_size, start_cutoff).await
+        }
+        Some(decoded) => {
+            for cdc_msg in streams.accept(decoded)? {
+                forward_message(cdc_msg?, lsn, tx, shared_state, flush_size, start_cutoff).await?;
+            }
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Decodes the pgoutput message carried by one XLogData frame. `in_block`
+/// tells whether the frame is inside a stream block (see
+/// [`TransactionStreams::in_block`]).
+pub fn parse_xlog_data(data: &Bytes, lsn: u64, in_block: bool) -> Result<Option<Decoded>> {
     if data.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
 
     let pgoutput_tag = data[0];
     let pgoutput_body = data.slice(1..);
 
-    match PgOutputParser::parse(pgoutput_tag, pgoutput_body) {
-        Ok(Some(cdc_msg)) => {
-            // Side-effects before forwarding to pipeline:
-            match &cdc_msg {
-                // Update relation PK column index cache (for snapshot deduplication)
-                CdcMessage::Relation { id, columns, .. } => {
-                    let pk_indices: Vec<usize> = columns
-                        .iter()
-                        .enumerate()
-                        .filter(|(_, col)| col.is_key())
-                        .map(|(i, _)| i)
-                        .collect();
-                    let mut cache = shared_state.relation_pk_cols.write().await;
-                    cache.insert(*id, pk_indices);
+    let decoded = match PgOutputParser::parse_stream(pgoutput_tag, pgoutput_body.clone()) {
+        Ok(None) if in_block => PgOutputParser::parse_streamed(pgoutput_tag, pgoutput_body)
+            .map(|(xid, msg)| msg.map(|message| Decoded::Streamed { xid, message })),
+        Ok(None) => {
+            PgOutputParser::parse(pgoutput_tag, pgoutput_body).map(|msg| msg.map(Decoded::Message))
+        }
+        stream => stream.map(|msg| msg.map(Decoded::Stream)),
+    };
+    decoded.map_err(|e| {
+        // CRITICAL: Parse error means WAL data is corrupted or protocol mismatch.
+        // We MUST halt replication to prevent data loss. Advancing LSN without
+        // processing the event would permanently lose this change.
+        anyhow!(
+            "WAL parse error at LSN 0x{:X} (tag={}): {}. Halting to prevent data loss.",
+            lsn,
+            pgoutput_tag as char,
+            e
+        )
+    })
+}
+
+/// Applies the start cutoff and snapshot deduplication to a decoded message
+/// and sends it to the pipeline. Messages must arrive in WAL order.
+pub async fn forward_message(
+    cdc_msg: CdcMessage,
+    lsn: u64,
+    tx: &mpsc::Sender<CdcEvent>,
+    shared_state: &SharedState,
+    flush_size: usize,
+    start_cutoff: &mut Option<StartCutoff>,
+) -> Result<()> {
+    // Time-based start: drop transactions committed before the cutoff
+    if let Some(cutoff) = start_cutoff.as_mut() {
+        match cutoff.decide(&cdc_msg, lsn) {
+            CutoffDecision::Drop => return Ok(()),
+            CutoffDecision::Forward => {}
+            CutoffDecision::Reached => {
+                info!(
+                    "Start position {} resolved to LSN 0x{:X} ({} earlier transactions skipped)",
+                    cutoff.cutoff().to_rfc3339(),
+                    lsn,
+                    cutoff.skipped_txns()
+                );
+                if let Some(first) = cutoff.first_skipped_lsn() {
+                    shared_state.set_skipped_range(first, lsn);
                 }
-                // Logical messages (LW/HW watermarks) are informational only for the
-                // WAL consumer — deduplication state is managed by the snapshot worker.
-                // Just skip these messages — do not forward to the pipeline.
-                CdcMessage::LogicalMessage { .. } => {
+                *start_cutoff = None;
+            }
+        }
+    }
+
+    // Side-effects before forwarding to pipeline:
+    match &cdc_msg {
+        // Update relation PK column index cache (for snapshot deduplication)
+        CdcMessage::Relation { id, columns, .. } => {
+            let pk_indices: Vec<usize> = columns
+                .iter()
+                .enumerate()
+                .filter(|(_, col)| col.is_key())
+                .map(|(i, _)| i)
+                .collect();
+            let mut cache = shared_state.relation_pk_cols.write().await;
+            cache.insert(*id, pk_indices);
+        }
+        // Logical messages (LW/HW watermarks) are informational only for the
+        // WAL consumer — deduplication state is managed by the snapshot worker.
+        // Just skip these messages — do not forward to the pipeline.
+        CdcMessage::LogicalMessage { .. } => {
+            return Ok(());
+        }
+        // For row changes during an active snapshot: check should_emit()
+        CdcMessage::Insert { relation_id, tuple } => {
+            if shared_state.is_snapshot_active() {
+                let pk = extract_int_pk(shared_state, *relation_id, &tuple.cols).await;
+                if !shared_state.should_emit(*relation_id, lsn, pk).await {
                     return Ok(());
                 }
-                // For row changes during an active snapshot: check should_emit()
-                CdcMessage::Insert { relation_id, tuple } => {
-                    if shared_state.is_snapshot_active() {
-                        let pk = extract_int_pk(shared_state, *relation_id, &tuple.cols).await;
-                        if !shared_state.should_emit(*relation_id, lsn, pk).await {
-                            return Ok(());
-                        }
-                    }
-                }
-                CdcMessage::Update {
-                    relation_id,
-                    new_tuple,
-                    ..
-                } => {
-                    if shared_state.is_snapshot_active() {
-                        let pk = extract_int_pk(shared_state, *relation_id, &new_tuple.cols).await;
-                        if !shared_state.should_emit(*relation_id, lsn, pk).await {
-                            return Ok(());
-                        }
-                    }
-                }
-                CdcMessage::Delete { .. } => {
-                    // Deletes are always emitted during snapshot (conservative approach)
+            }
+        }
+        CdcMessage::Update {
+            relation_id,
+            new_tuple,
+            ..
+        } => {
+            if shared_state.is_snapshot_active() {
+                let pk = extract_int_pk(shared_state, *relation_id, &new_tuple.cols).await;
+                if !shared_state.should_emit(*relation_id, lsn, pk).await {
+                    return Ok(());
                 }
-                _ => {}
             }
+        }
+        CdcMessage::Delete { .. } => {
+            // Deletes are always emitted during snapshot (conservative approach)
+        }
+        _ => {}
+    }
 
-            let event = CdcEvent {
-                lsn,
-                message: cdc_msg,
-            };
+    let event = CdcEvent {
+        lsn,
+        message: cdc_msg,
+    };
 
-            shared_state.increment_events();
+    shared_state.increment_events();
 
-            // Update pending events count
-            let capacity = tx.capacity();
-            let pending = (flush_size * 2).saturating_sub(capacity);
-            shared_state.set_pending(pending as u64);
+    // Update pending events count
+    let capacity = tx.capacity();
+    let pending = (flush_size * 2).saturating_sub(capacity);
+    shared_state.set_pending(pending as u64);
 
-            if let Err(e) = tx.send(event).await {
-                error!("Failed to send to pipeline: {}", e);
-                return Err(e.into());
-            }
-        }
-        Ok(None) => {}
-        Err(e) => {
-            // CRITICAL: Parse error means WAL data is corrupted or protocol mismatch.
-            // We MUST halt replication to prevent data loss. Advancing LSN without
-            // processing the event would permanently lose this change.
-            return Err(anyhow!(
-                "WAL parse error at LSN 0x{:X} (tag={}): {}. Halting to prevent data loss.",
-                lsn,
-                pgoutput_tag as char,
-                e
-            ));
-        }
+    if let Err(e) = tx.send(event).await {
+        error!("Failed to send to pipeline: {}", e);
+        return Err(e.into());
     }
 
     Ok(())
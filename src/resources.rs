@@ -0,0 +1,134 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! CPU and memory limits of the container dbmazz runs in.
+//!
+//! Docker and Kubernetes limits are cgroup settings: `cpu.max` and
+//! `memory.max` with cgroup v2, `cpu.cfs_quota_us` and
+//! `memory.limit_in_bytes` with v1. They are read with the memory the cgroup
+//! uses and reported in `GetStatus`, `/status` and the metrics. Outside a
+//! container, or without a limit, the limits are `None` and the memory used
+//! is the process RSS.
+//!
+//! With `AUTO_TUNE_MEMORY=true`, the default batch size (and so the event
+//! channel, twice as large) is derived from the memory limit, so a small
+//! container doesn't buffer more than it holds.
+
+use std::fs;
+
+/// cgroup v2 unified hierarchy, as mounted in a container
+const CGROUP_V2: &str = "/sys/fs/cgroup";
+/// cgroup v1 controllers
+const CGROUP_V1_CPU: &str = "/sys/fs/cgroup/cpu";
+const CGROUP_V1_MEMORY: &str = "/sys/fs/cgroup/memory";
+
+/// cgroup v1 reports "no limit" as a huge page-aligned number
+const V1_UNLIMITED: u64 = 1 << 62;
+
+/// Buffered events are estimated at 1 KB each, like `estimate_memory`
+const BYTES_PER_EVENT: u64 = 1024;
+
+/// Limits the cgroup puts on dbmazz, `None` when unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// 1000 = one core
+    pub cpu_millicores: Option<u64>,
+    pub memory_bytes: Option<u64>,
+}
+
+/// Reads the limits of the cgroup dbmazz runs in.
+pub fn limits() -> Limits {
+    let cpu_millicores = match fs::read_to_string(format!("{}/cpu.max", CGROUP_V2)) {
+        Ok(max) => parse_cpu_max(&max),
+        Err(_) => {
+            let quota = fs::read_to_string(format!("{}/cpu.cfs_quota_us", CGROUP_V1_CPU));
+            let period = fs::read_to_string(format!("{}/cpu.cfs_period_us", CGROUP_V1_CPU));
+            match (quota, period) {
+                (Ok(quota), Ok(period)) => parse_cfs_quota(&quota, &period),
+                _ => None,
+            }
+        }
+    };
+    let memory_bytes = fs::read_to_string(format!("{}/memory.max", CGROUP_V2))
+        .or_else(|_| fs::read_to_string(format!("{}/memory.limit_in_bytes", CGROUP_V1_MEMORY)))
+        .ok()
+        .and_then(|limit| parse_memory_limit(&limit));
+    Limits {
+        cpu_millicores,
+        memory_bytes,
+    }
+}
+
+/// Memory used by the cgroup, or by the process outside a container.
+pub fn memory_usage() -> Option<u64> {
+    fs::read_to_string(format!("{}/memory.current", CGROUP_V2))
+        .or_else(|_| fs::read_to_string(format!("{}/memory.usage_in_bytes", CGROUP_V1_MEMORY)))
+        .ok()
+        .and_then(|usage| usage.trim().parse().ok())
+        .or_else(process_rss)
+}
+
+/// Resident set size from `/proc/self/statm` (second field, in pages).
+fn process_rss() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf(_SC_PAGESIZE) has no preconditions
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * page_size.max(0) as u64)
+}
+
+/// `cpu.max`: `<quota> <period>` in microseconds, or `max <period>`.
+fn parse_cpu_max(max: &str) -> Option<u64> {
+    let mut fields = max.split_whitespace();
+    let quota: u64 = fields.next()?.parse().ok()?;
+    let period: u64 = fields.next()?.parse().ok()?;
+    (period > 0).then(|| quota * 1000 / period)
+}
+
+/// `cpu.cfs_quota_us` is -1 without a limit.
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<u64> {
+    let quota: i64 = quota.trim().parse().ok()?;
+    let period: u64 = period.trim().parse().ok()?;
+    (quota > 0 && period > 0).then(|| quota as u64 * 1000 / period)
+}
+
+/// `memory.max` is `max` without a limit; v1 reports a huge number instead.
+fn parse_memory_limit(limit: &str) -> Option<u64> {
+    let bytes: u64 = limit.trim().parse().ok()?;
+    (bytes < V1_UNLIMITED).then_some(bytes)
+}
+
+/// Batch size that keeps buffered events within a quarter of `memory_bytes`.
+/// A batch is buffered about three times over: the batch being built and the
+/// event channel in front of it, twice its size. Never above `default`, and
+/// at least 100 events.
+pub fn flush_size_for_memory(memory_bytes: u64, default: usize) -> usize {
+    let events = memory_bytes / 4 / (3 * BYTES_PER_EVENT);
+    (events as usize).clamp(100, default.max(100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_cpu_max("200000 100000\n"), Some(2000));
+        assert_eq!(parse_cpu_max("50000 100000"), Some(500));
+        assert_eq!(parse_cpu_max("max 100000"), None);
+        assert_eq!(parse_cfs_quota("150000\n", "100000\n"), Some(1500));
+        assert_eq!(parse_cfs_quota("-1", "100000"), None);
+
+        assert_eq!(parse_memory_limit("536870912\n"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_memory_limit("max\n"), None);
+        assert_eq!(parse_memory_limit("9223372036854771712"), None);
+    }
+
+    #[test]
+    fn test_flush_size_for_memory() {
+        // 64 MB: 16 MB for about 3 batches of 1 KB events
+        assert_eq!(flush_size_for_memory(64 << 20, 10_000), 5_461);
+        assert_eq!(flush_size_for_memory(8 << 30, 10_000), 10_000);
+        assert_eq!(flush_size_for_memory(1 << 20, 10_000), 100);
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Tokio runtime topology.
+//!
+//! By default everything runs on one multi-threaded runtime. With
+//! `RUNTIME_DEDICATED_WAL_THREAD=true` the replication loop (WAL reads,
+//! keepalive replies, LSN feedback) runs on a single-threaded runtime on its
+//! own OS thread, while the pipeline, sinks, parser tasks, snapshot workers and
+//! the gRPC server are spawned onto the main runtime with [`spawn_worker`].
+//! Heavy sink encoding then cannot delay keepalive replies or stall the reader.
+
+use std::future::Future;
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::config::RuntimeConfig;
+
+/// Name of the dedicated WAL reader thread
+pub const WAL_THREAD_NAME: &str = "dbmazz-wal";
+
+/// Handle to the main runtime, set once it is built
+static WORKER_RUNTIME: OnceLock<Handle> = OnceLock::new();
+
+/// Builds the main multi-threaded runtime.
+pub fn build(config: &RuntimeConfig) -> Result<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all().thread_name(config.thread_name.clone());
+    if let Some(threads) = config.worker_threads {
+        builder.worker_threads(threads);
+    }
+    let runtime = builder.build().context("failed to build tokio runtime")?;
+    let _ = WORKER_RUNTIME.set(runtime.handle().clone());
+    Ok(runtime)
+}
+
+/// Spawns a task on the main runtime, also when called from the WAL thread.
+pub fn spawn_worker<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match WORKER_RUNTIME.get() {
+        Some(handle) => handle.spawn(future),
+        None => tokio::spawn(future),
+    }
+}
+
+/// Runs the engine, on the dedicated WAL thread when configured.
+pub async fn run_engine<F>(config: &RuntimeConfig, engine: F) -> Result<()>
+where
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    if !config.dedicated_wal_thread {
+        return engine.await;
+    }
+
+    let (done_tx, done_rx) = oneshot::channel();
+    std::thread::Builder::new()
+        .name(WAL_THREAD_NAME.to_string())
+        .spawn(move || {
+            let result = Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .context("failed to build WAL reader runtime")
+                .and_then(|runtime| runtime.block_on(engine));
+            let _ = done_tx.send(result);
+        })
+        .context("failed to start WAL reader thread")?;
+
+    done_rx.await.context("WAL reader thread panicked")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dedicated_wal_thread: bool) -> RuntimeConfig {
+        RuntimeConfig {
+            worker_threads: Some(2),
+            thread_name: "dbmazz-test".to_string(),
+            dedicated_wal_thread,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_engine_on_dedicated_thread() {
+        let (tx, rx) = oneshot::channel();
+        run_engine(&config(true), async move {
+            let _ = tx.send(std::thread::current().name().map(str::to_string));
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(rx.await.unwrap().as_deref(), Some(WAL_THREAD_NAME));
+    }
+
+    #[tokio::test]
+    async fn test_run_engine_inline_propagates_error() {
+        let err = run_engine(&config(false), async { anyhow::bail!("boom") })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "boom");
+    }
+}
@@ -40,13 +40,29 @@
 
 use anyhow::Result;
 use async_trait::async_trait;
+use std::time::Duration;
+use tracing::{debug, warn};
 
+use crate::config::{
+    BoolEncoding, FloatFormat, GeometryFormat, NonFiniteFloats, NullEncoding, TextNormalization,
+};
+use crate::core::dead_letter::DeadLetter;
+use crate::core::pool::{COLUMN_BUFFERS, RECORD_BUFFERS};
+use crate::core::schema_diff::SchemaDiff;
+use crate::core::timeout::{self, with_timeout};
 use crate::core::{
-    CdcRecord, ColumnDef, ColumnValue, DataType, Sink as CoreSink, SinkCapabilities,
-    SourcePosition, TableRef, Value,
+    BatchId, CdcRecord, ColumnDef, ColumnValue, DataType, Sink as CoreSink, SinkCapabilities,
+    SinkCheckpoint, SourcePosition, TableRef, Value,
 };
 use crate::pipeline::schema_cache::{SchemaCache, SchemaDelta, TableSchema};
+use crate::sink::{BatchResult, Batching};
+use crate::source::geometry;
 use crate::source::parser::{CdcMessage, TupleData};
+use crate::source::pg_types::{self, PgType};
+
+/// Attempts at a batch whose write timed out; other failures are not retried
+/// here, sinks retry their own requests
+const MAX_BATCH_ATTEMPTS: u32 = 2;
 
 /// Adapter that wraps a new `core::Sink` to implement the legacy `sink::Sink` trait.
 ///
@@ -54,12 +70,116 @@ use crate::source::parser::{CdcMessage, TupleData};
 /// without requiring immediate changes to the pipeline itself.
 pub struct NewSinkAdapter {
     inner: Box<dyn CoreSink>,
+    null_encoding: NullEncoding,
+    bool_encoding: BoolEncoding,
+    float_format: FloatFormat,
+    geometry_format: GeometryFormat,
+    text_normalization: TextNormalization,
+    /// Limit on writing and committing one batch, `None` for no limit
+    batch_timeout: Option<Duration>,
+    /// Batching set for the sink's route, over what the sink asks for
+    batching: Option<Batching>,
+    /// Slot batches are identified under (`EXACTLY_ONCE=true`), and the LSN
+    /// the next batch starts after
+    exactly_once: Option<(String, u64)>,
 }
 
 impl NewSinkAdapter {
     /// Create a new adapter wrapping a core::Sink
     pub fn new(sink: Box<dyn CoreSink>) -> Self {
-        Self { inner: sink }
+        Self {
+            inner: sink,
+            null_encoding: NullEncoding::Null,
+            bool_encoding: BoolEncoding::Bool,
+            float_format: FloatFormat::default(),
+            geometry_format: GeometryFormat::default(),
+            text_normalization: TextNormalization::default(),
+            batch_timeout: None,
+            batching: None,
+            exactly_once: None,
+        }
+    }
+
+    /// How NULL columns are handed to the sink (`SINK_NULL_ENCODING`)
+    pub fn with_null_encoding(mut self, null_encoding: NullEncoding) -> Self {
+        self.null_encoding = null_encoding;
+        self
+    }
+
+    /// How boolean values are handed to the sink (`SINK_BOOLEAN_ENCODING`)
+    pub fn with_bool_encoding(mut self, bool_encoding: BoolEncoding) -> Self {
+        self.bool_encoding = bool_encoding;
+        self
+    }
+
+    /// How float values are handed to the sink (`SINK_FLOAT_*`)
+    pub fn with_float_format(mut self, float_format: FloatFormat) -> Self {
+        self.float_format = float_format;
+        self
+    }
+
+    /// How geometric and PostGIS values are handed to the sink
+    /// (`SINK_GEOMETRY_FORMAT`)
+    pub fn with_geometry_format(mut self, geometry_format: GeometryFormat) -> Self {
+        self.geometry_format = geometry_format;
+        self
+    }
+
+    /// `char(n)` trimming and NFC normalization of text values
+    pub fn with_text_normalization(mut self, text_normalization: TextNormalization) -> Self {
+        self.text_normalization = text_normalization;
+        self
+    }
+
+    /// Limit on writing and committing one batch (`SINK_BATCH_TIMEOUT_SECS`)
+    pub fn with_batch_timeout(mut self, batch_timeout: Option<Duration>) -> Self {
+        self.batch_timeout = batch_timeout;
+        self
+    }
+
+    /// Batching of the sink's route (`SINK_ROUTE_<NAME>_FLUSH_SIZE`,
+    /// `SINK_ROUTE_<NAME>_FLUSH_INTERVAL_MS`)
+    pub fn with_batching(mut self, batching: Option<Batching>) -> Self {
+        self.batching = batching;
+        self
+    }
+
+    /// Identifies every batch to the sink from now on (`EXACTLY_ONCE=true`),
+    /// and returns the slot's checkpoint row in the sink. The first batch
+    /// starts after that row's LSN, or after `start_lsn` when that is later.
+    pub async fn resume_exactly_once(
+        &mut self,
+        slot: &str,
+        start_lsn: u64,
+    ) -> Result<Option<SinkCheckpoint>> {
+        let checkpoint = self.inner.read_checkpoint(slot).await?;
+        let first_lsn = checkpoint.map_or(start_lsn, |c| c.lsn.max(start_lsn));
+        self.exactly_once = Some((slot.to_string(), first_lsn));
+        Ok(checkpoint)
+    }
+
+    /// Writes and commits converted records as one batch, aborting it on
+    /// failure. Past the batch timeout the write is dropped, which cancels
+    /// the sink's request in flight.
+    async fn write_records(&mut self, records: Vec<CdcRecord>, lsn: u64) -> Result<BatchResult> {
+        let name = self.inner.name();
+        let batch_timeout = self.batch_timeout;
+        let inner = &mut self.inner;
+
+        inner.begin_batch().await?;
+        let result = with_timeout(name, "batch", batch_timeout, async {
+            let written = inner.write_batch(records).await?;
+            inner.commit_batch(&SourcePosition::Lsn(lsn)).await?;
+            Ok(BatchResult::from(written))
+        })
+        .await;
+        if result.is_err() {
+            let abort = with_timeout(name, "batch abort", batch_timeout, inner.abort_batch());
+            if let Err(abort_err) = abort.await {
+                warn!("[SINK] {}: failed to abort batch: {}", name, abort_err);
+            }
+        }
+        result
     }
 
     /// Get the capabilities of the underlying sink
@@ -85,7 +205,8 @@ impl NewSinkAdapter {
         schema_cache: &SchemaCache,
         lsn: u64,
     ) -> Vec<CdcRecord> {
-        let mut records = Vec::with_capacity(batch.len());
+        // Returned to the pool by the sink once written
+        let mut records = RECORD_BUFFERS.take(batch.len());
         let position = SourcePosition::Lsn(lsn);
 
         for msg in batch {
@@ -120,7 +241,13 @@ impl NewSinkAdapter {
             } => {
                 let column_defs = columns
                     .iter()
-                    .map(|c| ColumnDef::new(c.name.clone(), pg_type_to_data_type(c.type_id), true))
+                    .map(|c| {
+                        ColumnDef::new(
+                            c.name.to_string(),
+                            pg_type_to_data_type(c.type_id, c.type_mod),
+                            true,
+                        )
+                    })
                     .collect();
 
                 Some(CdcRecord::SchemaChange {
@@ -132,7 +259,7 @@ impl NewSinkAdapter {
 
             CdcMessage::Insert { relation_id, tuple } => {
                 let schema = schema_cache.get(*relation_id)?;
-                let columns = self.tuple_to_column_values(tuple, schema);
+                let columns = self.tuple_to_column_values(tuple, schema, true);
 
                 Some(CdcRecord::Insert {
                     table: TableRef::new(Some(schema.namespace.clone()), schema.name.clone()),
@@ -147,10 +274,10 @@ impl NewSinkAdapter {
                 new_tuple,
             } => {
                 let schema = schema_cache.get(*relation_id)?;
-                let new_columns = self.tuple_to_column_values(new_tuple, schema);
+                let new_columns = self.tuple_to_column_values(new_tuple, schema, true);
                 let old_columns = old_tuple
                     .as_ref()
-                    .map(|t| self.tuple_to_column_values(t, schema));
+                    .map(|t| self.tuple_to_column_values(t, schema, false));
 
                 Some(CdcRecord::Update {
                     table: TableRef::new(Some(schema.namespace.clone()), schema.name.clone()),
@@ -166,7 +293,7 @@ impl NewSinkAdapter {
             } => {
                 let schema = schema_cache.get(*relation_id)?;
                 let old = old_tuple.as_ref()?;
-                let columns = self.tuple_to_column_values(old, schema);
+                let columns = self.tuple_to_column_values(old, schema, false);
 
                 Some(CdcRecord::Delete {
                     table: TableRef::new(Some(schema.namespace.clone()), schema.name.clone()),
@@ -184,50 +311,76 @@ impl NewSinkAdapter {
         }
     }
 
-    /// Convert a Tuple to ColumnValue vector using schema info
+    /// Convert a Tuple to ColumnValue vector using schema info.
+    ///
+    /// NULLs follow `null_encoding`. Type defaults only replace NULLs in a
+    /// new row image: in an old image or a delete, NULL usually just means
+    /// the column isn't part of the replica identity.
     fn tuple_to_column_values(
         &self,
         tuple: &crate::source::parser::Tuple,
         schema: &TableSchema,
+        new_image: bool,
     ) -> Vec<ColumnValue> {
-        schema
-            .columns
-            .iter()
-            .zip(tuple.cols.iter())
-            .map(|(col, data)| {
-                let value = match data {
-                    TupleData::Null => Value::Null,
-                    TupleData::Toast => Value::Unchanged,
-                    TupleData::Text(bytes) => {
-                        let text = String::from_utf8_lossy(bytes);
-                        self.convert_pg_value(&text, col.type_id)
-                    }
-                };
-                ColumnValue::new(col.name.clone(), value)
-            })
-            .collect()
+        // Next to unchanged TOAST values a missing column means "unchanged"
+        let omit_nulls = self.null_encoding == NullEncoding::Omit
+            && !tuple.cols.iter().any(|c| matches!(c, TupleData::Toast));
+        let default_nulls = self.null_encoding == NullEncoding::TypeDefault && new_image;
+
+        let mut values = COLUMN_BUFFERS.take(schema.columns.len());
+        values.extend(
+            schema
+                .columns
+                .iter()
+                .zip(tuple.cols.iter())
+                .filter_map(|(col, data)| {
+                    let value = match data {
+                        TupleData::Null if omit_nulls => return None,
+                        TupleData::Null if default_nulls => {
+                            self.convert_pg_value(null_default_text(col.type_id), col.type_id)
+                        }
+                        TupleData::Null => Value::Null,
+                        TupleData::Toast => Value::Unchanged,
+                        TupleData::Text(bytes) => {
+                            let text = String::from_utf8_lossy(bytes);
+                            self.convert_pg_value(&text, col.type_id)
+                        }
+                        TupleData::Binary(bytes) => self.convert_pg_binary(bytes, col.type_id),
+                    };
+                    Some(ColumnValue::new(col.name.clone(), value))
+                }),
+        );
+        values
     }
 
     /// Convert a PostgreSQL text value to a generic Value based on type OID
-    fn convert_pg_value(&self, text: &str, pg_type_id: u32) -> Value {
-        use crate::utils::{normalize_timestamptz, parse_pg_array, strip_money_symbol};
+    pub(super) fn convert_pg_value(&self, text: &str, pg_type_id: u32) -> Value {
+        use crate::utils::{normalize_timestamptz, strip_money_symbol};
 
+        let text: &str = &self.text_normalization.apply(text, pg_type_id);
         match pg_type_id {
             // Boolean
             16 => {
                 let is_true = matches!(text.to_lowercase().as_str(), "t" | "true" | "1");
-                Value::Bool(is_true)
+                match self.bool_encoding {
+                    BoolEncoding::Bool => Value::Bool(is_true),
+                    BoolEncoding::Int => Value::Int64(i64::from(is_true)),
+                    BoolEncoding::Char => {
+                        Value::String(if is_true { "t" } else { "f" }.to_string())
+                    }
+                }
             }
             // Integer types (INT2, INT4, INT8)
             21 | 23 | 20 => text
                 .parse::<i64>()
                 .map(Value::Int64)
                 .unwrap_or_else(|_| Value::String(text.to_string())),
-            // Float types (FLOAT4, FLOAT8)
-            700 | 701 => text
-                .parse::<f64>()
-                .map(Value::Float64)
-                .unwrap_or_else(|_| Value::String(text.to_string())),
+            // Float types (FLOAT4, FLOAT8); a custom format makes them
+            // decimal text
+            700 | 701 => match text.parse::<f64>() {
+                Ok(f) => self.float_value(f, || text.to_string()),
+                Err(_) => Value::String(text.to_string()),
+            },
             // Money - strip currency symbol
             790 => Value::Decimal(strip_money_symbol(text)),
             // NUMERIC/DECIMAL - keep as string for precision
@@ -240,20 +393,78 @@ impl NewSinkAdapter {
             114 | 3802 => Value::Json(text.to_string()),
             // UUID
             2950 => Value::Uuid(text.to_string()),
-            // Integer arrays
-            1005 | 1007 | 1016 => Value::Json(parse_pg_array(text, "int")),
-            // Float arrays
-            1021 | 1022 => Value::Json(parse_pg_array(text, "float")),
-            // Text/varchar arrays
-            1009 | 1015 => Value::Json(parse_pg_array(text, "text")),
-            // Default: string
-            _ => Value::String(text.to_string()),
+            // Arrays of any element type and dimension; composites, hstore
+            // and ranges as objects
+            oid if pg_types::is_json(oid) => Value::Json(pg_types::value_json(text, oid)),
+            // Geometric and PostGIS types as WKT or GeoJSON; what doesn't
+            // parse is passed on as PostgreSQL wrote it
+            oid if geometry::is_geometric(oid) => {
+                match geometry::convert(text, oid, self.geometry_format) {
+                    Some(converted) if self.geometry_format == GeometryFormat::GeoJson => {
+                        Value::Json(converted)
+                    }
+                    Some(converted) => Value::String(converted),
+                    None => Value::String(text.to_string()),
+                }
+            }
+            // Domains convert as their base type; anything else is a string
+            oid => match pg_types::base_type(oid) {
+                base if base != oid => self.convert_pg_value(text, base),
+                _ => Value::String(text.to_string()),
+            },
+        }
+    }
+
+    /// Convert a PostgreSQL binary-format value (`SOURCE_BINARY_TUPLES`).
+    /// Numbers, UUIDs and timestamps are read as they are; other types go
+    /// through their text, as `convert_pg_value` takes it.
+    pub(super) fn convert_pg_binary(&self, bytes: &[u8], pg_type_id: u32) -> Value {
+        use crate::source::binary;
+
+        let value = match pg_type_id {
+            21 | 23 | 20 => binary::read_int(bytes, pg_type_id).map(Value::Int64),
+            700 | 701 => binary::read_float(bytes).map(|f| {
+                self.float_value(f, || {
+                    binary::to_text(pg_type_id, bytes)
+                        .map(|text| text.into_owned())
+                        .unwrap_or_default()
+                })
+            }),
+            1700 => binary::numeric_text(bytes).map(Value::Decimal),
+            1114 => binary::timestamp_text(bytes).map(Value::String),
+            1184 => binary::timestamptz_utc_text(bytes).map(Value::String),
+            2950 => binary::uuid_text(bytes).map(Value::Uuid),
+            _ => binary::to_text(pg_type_id, bytes)
+                .map(|text| self.convert_pg_value(&text, pg_type_id)),
+        };
+        value.unwrap_or_else(|e| {
+            warn!(
+                "Undecodable binary value of type {}, written as hex: {:#}",
+                pg_type_id, e
+            );
+            Value::String(format!("\\x{}", hex::encode(bytes)))
+        })
+    }
+
+    /// A float after `SINK_FLOAT_*`; `text` spells a non-finite one.
+    fn float_value(&self, f: f64, text: impl FnOnce() -> String) -> Value {
+        if f.is_finite() {
+            return match self.float_format.text(f) {
+                Some(formatted) => Value::Decimal(formatted),
+                None => Value::Float64(f),
+            };
+        }
+        match self.float_format.non_finite {
+            NonFiniteFloats::Keep => Value::Float64(f),
+            NonFiniteFloats::Null => Value::Null,
+            // NaN, Infinity, -Infinity
+            NonFiniteFloats::String => Value::String(text()),
         }
     }
 }
 
-/// Convert PostgreSQL type OID to generic DataType
-fn pg_type_to_data_type(pg_type_id: u32) -> DataType {
+/// Convert PostgreSQL type OID and modifier to generic DataType
+pub(crate) fn pg_type_to_data_type(pg_type_id: u32, type_mod: i32) -> DataType {
     match pg_type_id {
         16 => DataType::Boolean,
         21 => DataType::Int16,
@@ -267,11 +478,85 @@ fn pg_type_to_data_type(pg_type_id: u32) -> DataType {
         },
         1114 => DataType::Timestamp,
         1184 => DataType::TimestampTz,
-        25 | 1043 | 1042 => DataType::String,
+        // The modifier of varchar(n) and char(n) is n plus the 4-byte
+        // header size, -1 without a length
+        1043 | 1042 => DataType::String {
+            max_len: u32::try_from(type_mod.saturating_sub(4))
+                .ok()
+                .filter(|n| *n > 0),
+        },
         114 | 3802 => DataType::Jsonb,
         2950 => DataType::Uuid,
         17 => DataType::Bytes,
-        _ => DataType::String,
+        oid => match pg_types::get(oid) {
+            Some(PgType::Enum { labels }) => DataType::Enum {
+                labels: labels.iter().map(|l| l.to_string()).collect(),
+            },
+            // A domain's length limit is the domain's, not the column's
+            Some(PgType::Domain { base }) => pg_type_to_data_type(base, -1),
+            Some(PgType::Composite { .. } | PgType::Hstore | PgType::Range { .. }) => {
+                DataType::Jsonb
+            }
+            _ => DataType::String { max_len: None },
+        },
+    }
+}
+
+/// PostgreSQL text of a type's zero value, written instead of NULL with
+/// `SINK_NULL_ENCODING=default`. The CDC path converts it like any other
+/// value; the snapshot loads it as is.
+pub(crate) fn null_default_text(pg_type_id: u32) -> &'static str {
+    match pg_type_id {
+        16 => "false",
+        21 | 23 | 20 | 700 | 701 | 790 | 1700 => "0",
+        114 | 3802 => "{}",
+        oid if pg_types::is_array(oid) => "{}",
+        oid if matches!(pg_types::get(oid), Some(PgType::Range { .. })) => "empty",
+        1082 => "1970-01-01",
+        1083 => "00:00:00",
+        1114 => "1970-01-01 00:00:00",
+        1184 => "1970-01-01 00:00:00+00",
+        2950 => "00000000-0000-0000-0000-000000000000",
+        oid => match pg_types::base_type(oid) {
+            base if base != oid => null_default_text(base),
+            _ => "",
+        },
+    }
+}
+
+/// `Value` variant that `convert_pg_value` produces for a type OID, as seen
+/// in the remote sink payload (`dbmazz docs`). Text that doesn't parse as
+/// the expected number or geometry falls back to `String`, as may NaN and
+/// infinities.
+pub(crate) fn pg_type_value_variant(
+    pg_type_id: u32,
+    bool_encoding: BoolEncoding,
+    float_format: FloatFormat,
+    geometry_format: GeometryFormat,
+) -> &'static str {
+    match pg_type_id {
+        16 => match bool_encoding {
+            BoolEncoding::Bool => "Bool",
+            BoolEncoding::Int => "Int64",
+            BoolEncoding::Char => "String",
+        },
+        21 | 23 | 20 => "Int64",
+        700 | 701 if float_format.text(0.0).is_some() => "Decimal",
+        700 | 701 => "Float64",
+        790 | 1700 => "Decimal",
+        114 | 3802 => "Json",
+        oid if pg_types::is_json(oid) => "Json",
+        oid if geometry::is_geometric(oid) => match geometry_format {
+            GeometryFormat::Wkt => "String",
+            GeometryFormat::GeoJson => "Json",
+        },
+        2950 => "Uuid",
+        oid => match pg_types::base_type(oid) {
+            base if base != oid => {
+                pg_type_value_variant(base, bool_encoding, float_format, geometry_format)
+            }
+            _ => "String",
+        },
     }
 }
 
@@ -282,61 +567,317 @@ impl crate::sink::Sink for NewSinkAdapter {
         batch: &[CdcMessage],
         schema_cache: &SchemaCache,
         lsn: u64,
-    ) -> Result<()> {
+    ) -> Result<BatchResult> {
         if batch.is_empty() {
-            return Ok(());
+            return Ok(BatchResult::default());
+        }
+
+        // Every attempt at the batch carries the same ID
+        if let Some((slot, first_lsn)) = &self.exactly_once {
+            self.inner.identify_batch(&BatchId {
+                slot: slot.clone(),
+                first_lsn: *first_lsn,
+                last_lsn: lsn,
+            });
         }
 
-        // Convert legacy CdcMessage to new CdcRecord format
-        let records = self.convert_batch(batch, schema_cache, lsn);
+        let mut attempt = 1;
+        loop {
+            // Convert legacy CdcMessage to new CdcRecord format
+            let records = self.convert_batch(batch, schema_cache, lsn);
+
+            if records.is_empty() {
+                return Ok(BatchResult::default());
+            }
 
-        if records.is_empty() {
-            return Ok(());
+            // Write using the new sink, as one transaction on sinks that have them.
+            // A timed-out batch was aborted and can be written again whole.
+            match self.write_records(records, lsn).await {
+                Err(e) if timeout::is_timeout(&e) && attempt < MAX_BATCH_ATTEMPTS => {
+                    warn!(
+                        "[SINK] {}: {} (attempt {}/{}), retrying the batch",
+                        self.inner.name(),
+                        e,
+                        attempt,
+                        MAX_BATCH_ATTEMPTS
+                    );
+                    attempt += 1;
+                }
+                result => {
+                    if let (Ok(written), Some((slot, first_lsn))) =
+                        (&result, self.exactly_once.as_mut())
+                    {
+                        debug!(
+                            "[SINK] {}: batch {:X}..{:X} of slot {} written under labels {:?}",
+                            self.inner.name(),
+                            first_lsn,
+                            lsn,
+                            slot,
+                            written.labels
+                        );
+                        *first_lsn = lsn;
+                    }
+                    return result;
+                }
+            }
         }
+    }
 
-        // Write using the new sink
-        let _result = self.inner.write_batch(records).await?;
+    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+        self.inner.apply_schema_delta(delta).await
+    }
+
+    fn invalidate_schema(&mut self, table: Option<&str>) {
+        self.inner.invalidate_schema(table)
+    }
 
-        Ok(())
+    async fn schema_diff(&self, schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        let name = self.inner.name();
+        with_timeout(
+            name,
+            "schema lookup",
+            self.batch_timeout,
+            self.inner.schema_diff(schema),
+        )
+        .await
     }
 
-    async fn apply_schema_delta(&self, _delta: &SchemaDelta) -> Result<()> {
-        // Schema evolution in the new sink is handled via SchemaChange records
-        // The new sink doesn't have a separate apply_schema_delta method.
-        // For now, we just return Ok - the schema changes are embedded in the record stream.
-        //
-        // TODO: If we need DDL support, we could:
-        // 1. Add an optional schema_evolution method to core::Sink
-        // 2. Or handle DDL in the connector's write_batch when it sees SchemaChange records
-        Ok(())
+    async fn write_dead_letters(&mut self, letters: &[DeadLetter]) -> Result<()> {
+        let name = self.inner.name();
+        with_timeout(
+            name,
+            "dead-letter write",
+            self.batch_timeout,
+            self.inner.write_dead_letters(letters),
+        )
+        .await
+    }
+
+    fn batching(&self) -> Option<Batching> {
+        self.batching
+            .or_else(|| self.inner.capabilities().micro_batch.then(Batching::micro))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
 
     #[test]
     fn test_pg_type_to_data_type() {
-        assert_eq!(pg_type_to_data_type(16), DataType::Boolean);
-        assert_eq!(pg_type_to_data_type(21), DataType::Int16);
-        assert_eq!(pg_type_to_data_type(23), DataType::Int32);
-        assert_eq!(pg_type_to_data_type(20), DataType::Int64);
-        assert_eq!(pg_type_to_data_type(700), DataType::Float32);
-        assert_eq!(pg_type_to_data_type(701), DataType::Float64);
-        assert_eq!(pg_type_to_data_type(1114), DataType::Timestamp);
-        assert_eq!(pg_type_to_data_type(25), DataType::String);
-        assert_eq!(pg_type_to_data_type(3802), DataType::Jsonb);
-        assert_eq!(pg_type_to_data_type(2950), DataType::Uuid);
+        assert_eq!(pg_type_to_data_type(16, -1), DataType::Boolean);
+        assert_eq!(pg_type_to_data_type(21, -1), DataType::Int16);
+        assert_eq!(pg_type_to_data_type(23, -1), DataType::Int32);
+        assert_eq!(pg_type_to_data_type(20, -1), DataType::Int64);
+        assert_eq!(pg_type_to_data_type(700, -1), DataType::Float32);
+        assert_eq!(pg_type_to_data_type(701, -1), DataType::Float64);
+        assert_eq!(pg_type_to_data_type(1114, -1), DataType::Timestamp);
+        assert_eq!(
+            pg_type_to_data_type(25, -1),
+            DataType::String { max_len: None }
+        );
+        assert_eq!(pg_type_to_data_type(3802, -1), DataType::Jsonb);
+        assert_eq!(pg_type_to_data_type(2950, -1), DataType::Uuid);
+        // varchar(20), varchar and char(1)
+        assert_eq!(
+            pg_type_to_data_type(1043, 24),
+            DataType::String { max_len: Some(20) }
+        );
+        assert_eq!(
+            pg_type_to_data_type(1043, -1),
+            DataType::String { max_len: None }
+        );
+        assert_eq!(
+            pg_type_to_data_type(1042, 5),
+            DataType::String { max_len: Some(1) }
+        );
         // Unknown type defaults to String
-        assert_eq!(pg_type_to_data_type(99999), DataType::String);
+        assert_eq!(
+            pg_type_to_data_type(99999, -1),
+            DataType::String { max_len: None }
+        );
     }
 
     #[test]
-    fn test_convert_pg_value_bool() {
-        let adapter = NewSinkAdapter {
-            inner: Box::new(MockSink),
+    fn test_enum_and_domain_types() {
+        // mood AS ENUM ('happy', 'sad'), a domain over it, and one over int4
+        pg_types::register(
+            91_001,
+            PgType::Enum {
+                labels: vec![Arc::from("happy"), Arc::from("sad")].into(),
+            },
+        );
+        pg_types::register(91_002, PgType::Domain { base: 91_001 });
+        pg_types::register(91_003, PgType::Domain { base: 23 });
+
+        let mood = DataType::Enum {
+            labels: vec!["happy".to_string(), "sad".to_string()],
         };
+        assert_eq!(pg_type_to_data_type(91_001, -1), mood);
+        assert_eq!(pg_type_to_data_type(91_002, -1), mood);
+        assert_eq!(pg_type_to_data_type(91_003, -1), DataType::Int32);
+
+        let adapter = NewSinkAdapter::new(Box::new(MockSink));
+        assert_eq!(
+            adapter.convert_pg_value("sad", 91_002),
+            Value::String("sad".to_string())
+        );
+        assert_eq!(adapter.convert_pg_value("42", 91_003), Value::Int64(42));
+        assert_eq!(null_default_text(91_003), "0");
+        assert_eq!(
+            pg_type_value_variant(
+                91_003,
+                BoolEncoding::Bool,
+                FloatFormat::default(),
+                GeometryFormat::default()
+            ),
+            "Int64"
+        );
+    }
+
+    #[test]
+    fn test_composite_types() {
+        // address AS (street text, zip int4), and a domain over it
+        pg_types::register(
+            91_004,
+            PgType::Composite {
+                fields: vec![(Arc::from("street"), 25), (Arc::from("zip"), 23)].into(),
+            },
+        );
+        pg_types::register(91_005, PgType::Domain { base: 91_004 });
+
+        assert_eq!(pg_type_to_data_type(91_004, -1), DataType::Jsonb);
+        assert_eq!(pg_type_to_data_type(91_005, -1), DataType::Jsonb);
+
+        let adapter = NewSinkAdapter::new(Box::new(MockSink));
+        for oid in [91_004, 91_005] {
+            assert_eq!(
+                adapter.convert_pg_value(r#"("1 Main St",12345)"#, oid),
+                Value::Json(r#"{"street":"1 Main St","zip":12345}"#.to_string())
+            );
+        }
+        assert_eq!(
+            pg_type_value_variant(
+                91_004,
+                BoolEncoding::Bool,
+                FloatFormat::default(),
+                GeometryFormat::default()
+            ),
+            "Json"
+        );
+    }
+
+    #[test]
+    fn test_hstore_and_range_types() {
+        // hstore, citext, and a range over a domain over int8
+        pg_types::register(91_006, PgType::Hstore);
+        pg_types::register(91_007, PgType::Citext);
+        pg_types::register(91_008, PgType::Domain { base: 20 });
+        pg_types::register(91_009, PgType::Range { subtype: 91_008 });
+
+        let adapter = NewSinkAdapter::new(Box::new(MockSink));
+        assert_eq!(
+            adapter.convert_pg_value(r#""a"=>"1", "b"=>NULL"#, 91_006),
+            Value::Json(r#"{"a":"1","b":null}"#.to_string())
+        );
+        assert_eq!(
+            adapter.convert_pg_value("[5,)", 91_009),
+            Value::Json(r#"{"lower":5,"upper":null,"bounds":"[)"}"#.to_string())
+        );
+        assert_eq!(
+            adapter.convert_pg_value("Hello", 91_007),
+            Value::String("Hello".to_string())
+        );
+        for oid in [91_006, 91_009, 3910] {
+            assert_eq!(pg_type_to_data_type(oid, -1), DataType::Jsonb);
+        }
+        assert_eq!(
+            pg_type_to_data_type(91_007, -1),
+            DataType::String { max_len: None }
+        );
+        // NULL as the zero value: an empty hstore and an empty range
+        assert_eq!(
+            adapter.convert_pg_value(null_default_text(91_006), 91_006),
+            Value::Json("{}".to_string())
+        );
+        assert_eq!(
+            adapter.convert_pg_value(null_default_text(3912), 3912),
+            Value::Json(r#"{"lower":null,"upper":null,"bounds":"empty"}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_geometry_format() {
+        let wkt = NewSinkAdapter::new(Box::new(MockSink));
+        let geojson =
+            NewSinkAdapter::new(Box::new(MockSink)).with_geometry_format(GeometryFormat::GeoJson);
+
+        assert_eq!(
+            wkt.convert_pg_value("(1,2)", 600),
+            Value::String("POINT(1 2)".to_string())
+        );
+        assert_eq!(
+            geojson.convert_pg_value("(1,2)", 600),
+            Value::Json(r#"{"type":"Point","coordinates":[1,2]}"#.to_string())
+        );
+        // Not a point: passed on unchanged
+        assert_eq!(
+            geojson.convert_pg_value("(1,2,3)", 600),
+            Value::String("(1,2,3)".to_string())
+        );
+        for (adapter, variant) in [(&wkt, "String"), (&geojson, "Json")] {
+            assert_eq!(
+                pg_type_value_variant(
+                    604,
+                    BoolEncoding::Bool,
+                    FloatFormat::default(),
+                    adapter.geometry_format
+                ),
+                variant
+            );
+        }
+    }
+
+    #[test]
+    fn test_value_variant_matches_conversion() {
+        let adapter = NewSinkAdapter::new(Box::new(MockSink));
+        let samples = [
+            (16, "t"),
+            (23, "42"),
+            (701, "1.5"),
+            (790, "$1.00"),
+            (1700, "3.14"),
+            (3802, "{}"),
+            (2950, "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"),
+            (1007, "{1,2}"),
+            (1000, "{{t,f},{f,NULL}}"),
+            (1184, "2024-01-01 00:00:00+00"),
+            (17, "\\x00"),
+            (600, "(1,2)"),
+            (3904, "[1,10)"),
+        ];
+        for (oid, text) in samples {
+            let json = serde_json::to_value(adapter.convert_pg_value(text, oid)).unwrap();
+            let variant = json.as_object().unwrap().keys().next().unwrap().clone();
+            assert_eq!(
+                variant,
+                pg_type_value_variant(
+                    oid,
+                    BoolEncoding::Bool,
+                    FloatFormat::default(),
+                    GeometryFormat::default()
+                ),
+                "type {}",
+                oid
+            );
+        }
+    }
+
+    #[test]
+    fn test_convert_pg_value_bool() {
+        let adapter = NewSinkAdapter::new(Box::new(MockSink));
 
         assert!(matches!(
             adapter.convert_pg_value("t", 16),
@@ -361,11 +902,48 @@ mod tests {
     }
 
     #[test]
-    fn test_convert_pg_value_int() {
-        let adapter = NewSinkAdapter {
-            inner: Box::new(MockSink),
+    fn test_convert_pg_value_bool_encoding() {
+        let encoded = |bool_encoding: BoolEncoding, text: &str| {
+            let adapter = NewSinkAdapter::new(Box::new(MockSink)).with_bool_encoding(bool_encoding);
+            let value = adapter.convert_pg_value(text, 16);
+            let variant = serde_json::to_value(&value).unwrap();
+            assert!(
+                variant
+                    .get(pg_type_value_variant(
+                        16,
+                        bool_encoding,
+                        FloatFormat::default(),
+                        GeometryFormat::default()
+                    ))
+                    .is_some(),
+                "{:?}",
+                value
+            );
+            variant
         };
 
+        assert_eq!(
+            encoded(BoolEncoding::Int, "t"),
+            serde_json::json!({ "Int64": 1 })
+        );
+        assert_eq!(
+            encoded(BoolEncoding::Int, "f"),
+            serde_json::json!({ "Int64": 0 })
+        );
+        assert_eq!(
+            encoded(BoolEncoding::Char, "true"),
+            serde_json::json!({ "String": "t" })
+        );
+        assert_eq!(
+            encoded(BoolEncoding::Bool, "f"),
+            serde_json::json!({ "Bool": false })
+        );
+    }
+
+    #[test]
+    fn test_convert_pg_value_int() {
+        let adapter = NewSinkAdapter::new(Box::new(MockSink));
+
         assert!(matches!(
             adapter.convert_pg_value("42", 23),
             Value::Int64(42)
@@ -383,9 +961,7 @@ mod tests {
 
     #[test]
     fn test_convert_pg_value_float() {
-        let adapter = NewSinkAdapter {
-            inner: Box::new(MockSink),
-        };
+        let adapter = NewSinkAdapter::new(Box::new(MockSink));
 
         if let Value::Float64(f) = adapter.convert_pg_value("3.5", 701) {
             assert!((f - 3.5).abs() < 0.001);
@@ -394,6 +970,386 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_float_format() {
+        let adapter = |float_format: FloatFormat| {
+            NewSinkAdapter::new(Box::new(MockSink)).with_float_format(float_format)
+        };
+
+        let plain = adapter(FloatFormat {
+            notation: crate::config::FloatNotation::Plain,
+            ..Default::default()
+        });
+        assert_eq!(
+            plain.convert_pg_value("1e-07", 701),
+            Value::Decimal("0.0000001".to_string())
+        );
+        let fixed = adapter(FloatFormat {
+            decimals: Some(3),
+            non_finite: NonFiniteFloats::String,
+            ..Default::default()
+        });
+        assert_eq!(
+            fixed.convert_pg_value("2.5e+20", 701),
+            Value::Decimal("250000000000000000000.000".to_string())
+        );
+        assert_eq!(
+            fixed.convert_pg_value("-Infinity", 700),
+            Value::String("-Infinity".to_string())
+        );
+        let nulls = adapter(FloatFormat {
+            non_finite: NonFiniteFloats::Null,
+            ..Default::default()
+        });
+        assert_eq!(nulls.convert_pg_value("NaN", 701), Value::Null);
+        assert_eq!(nulls.convert_pg_value("1e-07", 701), Value::Float64(1e-7));
+        assert_eq!(
+            pg_type_value_variant(
+                701,
+                BoolEncoding::Bool,
+                fixed.float_format,
+                GeometryFormat::default()
+            ),
+            "Decimal"
+        );
+    }
+
+    #[test]
+    fn test_convert_pg_binary() {
+        let adapter = NewSinkAdapter::new(Box::new(MockSink));
+        assert_eq!(
+            adapter.convert_pg_binary(&(-7i16).to_be_bytes(), 21),
+            Value::Int64(-7)
+        );
+        assert_eq!(
+            adapter.convert_pg_binary(&2.5f64.to_be_bytes(), 701),
+            Value::Float64(2.5)
+        );
+        // 1.50
+        assert_eq!(
+            adapter.convert_pg_binary(&[0, 2, 0, 0, 0, 0, 0, 2, 0, 1, 0x13, 0x88], 1700),
+            Value::Decimal("1.50".to_string())
+        );
+        // 2000-01-01 00:00:01.5 UTC
+        assert_eq!(
+            adapter.convert_pg_binary(&1_500_000i64.to_be_bytes(), 1184),
+            Value::String("2000-01-01 00:00:01.500000".to_string())
+        );
+        assert_eq!(
+            adapter.convert_pg_binary(&[1], 16),
+            adapter.convert_pg_value("t", 16)
+        );
+        assert_eq!(
+            adapter.convert_pg_binary(b"AB", 1043),
+            Value::String("AB".to_string())
+        );
+
+        let strings = adapter.with_float_format(FloatFormat {
+            non_finite: NonFiniteFloats::String,
+            ..Default::default()
+        });
+        assert_eq!(
+            strings.convert_pg_binary(&f64::NEG_INFINITY.to_be_bytes(), 701),
+            Value::String("-Infinity".to_string())
+        );
+        // A type without decoder keeps its bytes
+        assert_eq!(
+            strings.convert_pg_binary(&[0xab], 1186),
+            Value::String("\\xab".to_string())
+        );
+    }
+
+    #[test]
+    fn test_text_normalization() {
+        let plain = NewSinkAdapter::new(Box::new(MockSink));
+        let normalized =
+            NewSinkAdapter::new(Box::new(MockSink)).with_text_normalization(TextNormalization {
+                trim_bpchar: true,
+                nfc: true,
+            });
+        let text = |adapter: &NewSinkAdapter, text: &str, oid: u32| match adapter
+            .convert_pg_value(text, oid)
+        {
+            Value::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        };
+
+        assert_eq!(text(&plain, "AB  ", 1042), "AB  ");
+        assert_eq!(text(&normalized, "AB  ", 1042), "AB");
+        // Only char(n) is padding; trailing blanks of text are data
+        assert_eq!(text(&normalized, "AB  ", 25), "AB  ");
+
+        // "e" + combining acute accent composes to "é"
+        assert_eq!(text(&plain, "caf\u{65}\u{301}", 1043), "caf\u{65}\u{301}");
+        assert_eq!(text(&normalized, "caf\u{65}\u{301}", 1043), "caf\u{e9}");
+        assert_eq!(text(&normalized, "caf\u{65}\u{301}", 1042), "caf\u{e9}");
+    }
+
+    #[test]
+    fn test_null_encoding() {
+        use crate::source::parser::{Column, Tuple};
+        use bytes::Bytes;
+
+        let mut cache = SchemaCache::new();
+        let column = |name: &str, type_id: u32| Column {
+            flags: 0,
+            name: Arc::from(name),
+            type_id,
+            type_mod: -1,
+        };
+        cache.update(&CdcMessage::Relation {
+            id: 1,
+            namespace: Arc::from("public"),
+            name: Arc::from("orders"),
+            replica_identity: b'd',
+            columns: vec![
+                column("id", 23, 1),
+                column("total", 1700, 0),
+                column("note", 25, 0),
+            ],
+        });
+        let tuple = |note: TupleData| Tuple {
+            cols: vec![
+                TupleData::Text(Bytes::from_static(b"1")),
+                TupleData::Null,
+                note,
+            ],
+            toast_bitmap: 0,
+        };
+        let insert = CdcMessage::Insert {
+            relation_id: 1,
+            tuple: tuple(TupleData::Null),
+        };
+        let position = SourcePosition::Lsn(0);
+        let columns = |encoding: NullEncoding, msg: &CdcMessage| {
+            let adapter = NewSinkAdapter::new(Box::new(MockSink)).with_null_encoding(encoding);
+            match adapter.convert_message(msg, &cache, &position) {
+                Some(CdcRecord::Insert { columns, .. })
+                | Some(CdcRecord::Update {
+                    new_columns: columns,
+                    ..
+                })
+                | Some(CdcRecord::Delete { columns, .. }) => columns
+                    .iter()
+                    .map(|c| serde_json::to_value(&c.value).unwrap())
+                    .collect::<Vec<_>>(),
+                _ => panic!("expected a row"),
+            }
+        };
+
+        let nulls = columns(NullEncoding::Null, &insert);
+        assert_eq!(nulls[1], serde_json::json!("Null"));
+        assert_eq!(columns(NullEncoding::Omit, &insert).len(), 1);
+        let defaults = columns(NullEncoding::TypeDefault, &insert);
+        assert_eq!(defaults[1], serde_json::json!({ "Decimal": "0" }));
+        assert_eq!(defaults[2], serde_json::json!({ "String": "" }));
+
+        // Nothing is omitted next to unchanged TOAST values
+        let partial = CdcMessage::Update {
+            relation_id: 1,
+            old_tuple: None,
+            new_tuple: tuple(TupleData::Toast),
+        };
+        assert_eq!(columns(NullEncoding::Omit, &partial).len(), 3);
+
+        // Deletes keep NULL rather than invent values
+        let delete = CdcMessage::Delete {
+            relation_id: 1,
+            old_tuple: Some(tuple(TupleData::Null)),
+        };
+        assert_eq!(
+            columns(NullEncoding::TypeDefault, &delete)[2],
+            serde_json::json!("Null")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_batch_wraps_write_in_batch_hooks() {
+        use crate::sink::Sink as LegacySink;
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut adapter = NewSinkAdapter::new(Box::new(RecordingSink {
+            calls: calls.clone(),
+            fail_writes: false,
+            hung_writes: 0,
+        }));
+        let batch = [CdcMessage::Begin {
+            final_lsn: 0,
+            timestamp: 0,
+            xid: 7,
+        }];
+        adapter
+            .push_batch(&batch, &SchemaCache::new(), 0x42)
+            .await
+            .unwrap();
+        assert_eq!(*calls.lock(), vec!["begin", "write", "commit LSN:66"]);
+
+        calls.lock().clear();
+        let mut failing = NewSinkAdapter::new(Box::new(RecordingSink {
+            calls: calls.clone(),
+            fail_writes: true,
+            hung_writes: 0,
+        }));
+        assert!(failing
+            .push_batch(&batch, &SchemaCache::new(), 0x42)
+            .await
+            .is_err());
+        assert_eq!(*calls.lock(), vec!["begin", "write", "abort"]);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_batch_is_aborted_and_retried() {
+        use crate::sink::Sink as LegacySink;
+
+        let batch = [CdcMessage::Begin {
+            final_lsn: 0,
+            timestamp: 0,
+            xid: 7,
+        }];
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let sink = |hung_writes| {
+            NewSinkAdapter::new(Box::new(RecordingSink {
+                calls: calls.clone(),
+                fail_writes: false,
+                hung_writes,
+            }))
+            .with_batch_timeout(Some(Duration::from_millis(20)))
+        };
+
+        // One hung write: aborted, then written again
+        sink(1)
+            .push_batch(&batch, &SchemaCache::new(), 0x42)
+            .await
+            .unwrap();
+        assert_eq!(
+            *calls.lock(),
+            vec!["begin", "write", "abort", "begin", "write", "commit LSN:66"]
+        );
+
+        // Hung on every attempt: gives up with a timeout error
+        calls.lock().clear();
+        let err = sink(u32::MAX)
+            .push_batch(&batch, &SchemaCache::new(), 0x42)
+            .await
+            .unwrap_err();
+        assert!(timeout::is_timeout(&err));
+        assert_eq!(calls.lock().len(), 3 * MAX_BATCH_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_identified_by_lsn_range() {
+        use crate::sink::Sink as LegacySink;
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut adapter = NewSinkAdapter::new(Box::new(RecordingSink {
+            calls: calls.clone(),
+            fail_writes: false,
+            hung_writes: 1,
+        }))
+        .with_batch_timeout(Some(Duration::from_millis(20)));
+        let checkpoint = adapter
+            .resume_exactly_once("dbmazz_slot", 0x10)
+            .await
+            .unwrap();
+        assert_eq!(checkpoint.map(|c| c.lsn), Some(0x30));
+
+        let batch = [CdcMessage::Begin {
+            final_lsn: 0,
+            timestamp: 0,
+            xid: 7,
+        }];
+        for lsn in [0x42, 0x50] {
+            adapter
+                .push_batch(&batch, &SchemaCache::new(), lsn)
+                .await
+                .unwrap();
+        }
+        // The timed-out first attempt and its retry carry the same ID; the
+        // next batch starts where the first ended
+        let ids: Vec<String> = calls
+            .lock()
+            .iter()
+            .filter(|c| c.starts_with("batch") || c.as_str() == "abort")
+            .cloned()
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "batch dbmazz_dbmazz_slot_30_42",
+                "abort",
+                "batch dbmazz_dbmazz_slot_42_50"
+            ]
+        );
+    }
+
+    /// Records the order of batch hook calls
+    struct RecordingSink {
+        calls: Arc<Mutex<Vec<String>>>,
+        fail_writes: bool,
+        /// Writes that never finish before one succeeds
+        hung_writes: u32,
+    }
+
+    #[async_trait]
+    impl CoreSink for RecordingSink {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn capabilities(&self) -> SinkCapabilities {
+            MockSink.capabilities()
+        }
+
+        async fn validate_connection(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn begin_batch(&mut self) -> Result<()> {
+            self.calls.lock().push("begin".to_string());
+            Ok(())
+        }
+
+        async fn write_batch(
+            &mut self,
+            _records: Vec<CdcRecord>,
+        ) -> Result<crate::core::SinkResult> {
+            self.calls.lock().push("write".to_string());
+            if self.fail_writes {
+                anyhow::bail!("write failed");
+            }
+            if self.hung_writes > 0 {
+                self.hung_writes -= 1;
+                std::future::pending::<()>().await;
+            }
+            MockSink.write_batch(Vec::new()).await
+        }
+
+        async fn commit_batch(&mut self, checkpoint: &SourcePosition) -> Result<()> {
+            self.calls.lock().push(format!("commit {}", checkpoint));
+            Ok(())
+        }
+
+        async fn abort_batch(&mut self) -> Result<()> {
+            self.calls.lock().push("abort".to_string());
+            Ok(())
+        }
+
+        fn identify_batch(&mut self, id: &BatchId) {
+            self.calls.lock().push(format!("batch {}", id));
+        }
+
+        async fn read_checkpoint(&self, _slot: &str) -> Result<Option<SinkCheckpoint>> {
+            Ok(Some(SinkCheckpoint {
+                lsn: 0x30,
+                pending_lsn: None,
+            }))
+        }
+
+        async fn close(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
     // Mock sink for testing
     struct MockSink;
 
@@ -413,6 +1369,7 @@ mod tests {
                 min_batch_size: None,
                 max_batch_size: None,
                 optimal_flush_interval_ms: 1000,
+                micro_batch: false,
             }
         }
 
@@ -428,6 +1385,7 @@ mod tests {
                 records_written: 0,
                 bytes_written: 0,
                 last_position: None,
+                ..Default::default()
             })
         }
 
@@ -1,9 +1,18 @@
 pub mod adapter;
+pub mod parallel;
+pub mod router;
 
+#[cfg(test)]
+mod roundtrip;
+
+use crate::core::dead_letter::DeadLetter;
+use crate::core::schema_diff::SchemaDiff;
+use crate::core::SinkResult;
 use crate::pipeline::schema_cache::{SchemaCache, SchemaDelta};
 use crate::source::parser::CdcMessage;
 use anyhow::Result;
 use async_trait::async_trait;
+use std::time::Duration;
 
 /// Legacy Sink trait for backward compatibility
 ///
@@ -12,14 +21,130 @@ use async_trait::async_trait;
 /// to bridge with the legacy pipeline.
 #[async_trait]
 pub trait Sink: Send + Sync {
+    /// Writes a batch ending at `lsn`, and reports what the sink did with it.
     async fn push_batch(
         &mut self,
         batch: &[CdcMessage],
         schema_cache: &SchemaCache,
         lsn: u64,
-    ) -> Result<()>;
+    ) -> Result<BatchResult>;
 
+    /// Applies a source schema change; see `core::Sink::apply_schema_delta`.
     async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()>;
+
+    /// Forgets the sink's cached destination schema of `table`
+    /// (`schema.table`), or of every table when `None`.
+    fn invalidate_schema(&mut self, _table: Option<&str>) {}
+
+    /// How the sink table of `schema`'s table differs from its source
+    /// columns; `None` when the sink can't tell.
+    async fn schema_diff(&self, _schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        Ok(None)
+    }
+
+    /// Appends rejected rows to the sink's dead-letter table.
+    async fn write_dead_letters(&mut self, _letters: &[DeadLetter]) -> Result<()> {
+        anyhow::bail!("this sink has no dead-letter table")
+    }
+
+    /// Batching the sink asks for instead of the pipeline's `FLUSH_SIZE`
+    /// and `FLUSH_INTERVAL_MS`; `None` to take the pipeline's.
+    fn batching(&self) -> Option<Batching> {
+        None
+    }
+
+    /// While the sink holds rows back for a larger batch, the LSN of the
+    /// last batch written before them: the checkpoint may not pass it.
+    fn held_from(&self) -> Option<u64> {
+        None
+    }
+
+    /// Makes the next `push_batch` write every row held back.
+    fn release_held(&mut self) {}
+}
+
+/// What the sinks reported about a batch they wrote. Fed to the metrics
+/// and the recent errors; a batch written in parts (routes, pipeline
+/// workers, rows held back) adds up the results of its parts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchResult {
+    /// Rows the sinks stored
+    pub rows_written: u64,
+    /// Rows the sinks accepted but didn't store
+    pub rows_filtered: u64,
+    /// IDs the sinks gave their writes, e.g. Stream Load labels in
+    /// exactly-once mode
+    pub labels: Vec<String>,
+    /// Problems that didn't fail the batch, e.g. a load not yet visible
+    pub warnings: Vec<String>,
+}
+
+impl BatchResult {
+    /// Adds the result of another part of the batch.
+    pub fn merge(&mut self, other: BatchResult) {
+        self.rows_written += other.rows_written;
+        self.rows_filtered += other.rows_filtered;
+        self.labels.extend(other.labels);
+        self.warnings.extend(other.warnings);
+    }
+
+    /// The result of another part, with its warnings prefixed by `context`
+    /// (a route or worker) the way errors of that part are.
+    pub fn with_context(mut self, context: impl std::fmt::Display) -> Self {
+        for warning in &mut self.warnings {
+            *warning = format!("{}: {}", context, warning);
+        }
+        self
+    }
+}
+
+impl From<SinkResult> for BatchResult {
+    fn from(result: SinkResult) -> Self {
+        Self {
+            rows_written: result.records_written as u64,
+            rows_filtered: result.rows_filtered as u64,
+            labels: result.labels,
+            warnings: result.warnings,
+        }
+    }
+}
+
+impl std::iter::FromIterator<BatchResult> for BatchResult {
+    fn from_iter<I: IntoIterator<Item = BatchResult>>(results: I) -> Self {
+        let mut total = BatchResult::default();
+        for result in results {
+            total.merge(result);
+        }
+        total
+    }
+}
+
+/// Batch size and delay a sink asks for; each `None` keeps the pipeline's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Batching {
+    pub max_events: Option<usize>,
+    pub max_delay: Option<Duration>,
+}
+
+impl Batching {
+    /// Every event written on its own, for sinks with `micro_batch`
+    pub fn micro() -> Self {
+        Self {
+            max_events: Some(1),
+            max_delay: None,
+        }
+    }
+
+    /// The batch size and delay this comes to with the pipeline's
+    /// `max_events` and `max_delay` where it has none.
+    pub fn resolve(self, max_events: usize, max_delay: Duration) -> (usize, Duration) {
+        (
+            self.max_events.unwrap_or(max_events),
+            self.max_delay.unwrap_or(max_delay),
+        )
+    }
 }
 
 pub use adapter::NewSinkAdapter;
+pub use parallel::ParallelSink;
+pub use router::{Route, SinkRouter};
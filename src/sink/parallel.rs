@@ -0,0 +1,501 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Parallel sink workers (`PIPELINE_WORKERS`).
+//!
+//! One pipeline task decodes and batches the changes of every table, and a
+//! single sink connection writes them one table after the other. With
+//! workers the pipeline's sink is a [`ParallelSink`]: a sink per worker, each
+//! with its own connection. Every batch is sharded by table, or with
+//! `PIPELINE_SHARD_BY=key` by a hash of each row's replica identity key, and
+//! the workers write their shards at the same time. The rows of a table (or
+//! of a key) always go to the same worker, so they are written in order.
+//!
+//! A batch is written once every worker wrote its shard, and only then
+//! confirmed; the checkpoint doesn't pass rows a worker's sink holds back
+//! either. When a worker fails the whole batch is retried, so workers that
+//! wrote their shard get it again, as with `SINK_ROUTES`.
+//!
+//! An update changing a row's key may move the row to another worker. When
+//! sharding by key, the rows before such an update are written first, then
+//! the update by the worker of the old key, then the rows after it, so the
+//! old and the new key are never written at the same time. Each of these
+//! writes starts with the Begin of the transaction it is in.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use parking_lot::Mutex;
+
+use crate::config::PipelineShard;
+use crate::core::dead_letter::DeadLetter;
+use crate::core::schema_diff::SchemaDiff;
+use crate::pipeline::schema_cache::{SchemaCache, SchemaDelta, TableSchema};
+use crate::sink::{BatchResult, Batching, Sink};
+use crate::source::parser::{CdcMessage, Tuple, TupleData};
+
+/// Shards each batch between several sinks of the same destination.
+pub struct ParallelSink {
+    workers: Vec<Box<dyn Sink + Send>>,
+    shard: PipelineShard,
+    /// Worker of each table (`schema.table`), handed out in turn as tables
+    /// are first seen
+    tables: Mutex<HashMap<String, usize>>,
+}
+
+/// Where a message of a batch goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Worker(usize),
+    /// An update moving a row from the key of this worker to another one
+    KeyChange(usize),
+    /// To every worker: a Relation message when sharding by key
+    Every,
+    /// To every worker that writes rows: transaction boundaries, keepalives
+    Boundary,
+}
+
+/// The messages of a batch for one worker.
+#[derive(Default)]
+struct Shard {
+    messages: Vec<CdcMessage>,
+    /// Holds more than transaction boundaries
+    changed: bool,
+}
+
+impl ParallelSink {
+    /// Shards batches between `workers`, which must write to the same
+    /// destination.
+    pub fn new(workers: Vec<Box<dyn Sink + Send>>, shard: PipelineShard) -> Self {
+        assert!(!workers.is_empty(), "ParallelSink needs a worker");
+        Self {
+            workers,
+            shard,
+            tables: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Worker writing `table` (`schema.table`) when sharding by table, and
+    /// the rows of its tables without a key when sharding by key.
+    fn table_worker(&self, table: &str) -> usize {
+        let mut tables = self.tables.lock();
+        let next = tables.len() % self.workers.len();
+        *tables.entry(table.to_string()).or_insert(next)
+    }
+
+    /// Worker of a row of `schema` with `tuple`'s key.
+    fn key_worker(&self, schema: &TableSchema, tuple: &Tuple) -> usize {
+        let mut hasher = DefaultHasher::new();
+        schema.id.hash(&mut hasher);
+        for (column, value) in schema.columns.iter().zip(&tuple.cols) {
+            if !column.is_key() {
+                continue;
+            }
+            match value {
+                TupleData::Text(bytes) | TupleData::Binary(bytes) => bytes.hash(&mut hasher),
+                TupleData::Null | TupleData::Toast => 0u8.hash(&mut hasher),
+            }
+        }
+        (hasher.finish() % self.workers.len() as u64) as usize
+    }
+
+    /// Target of `msg`. `tables` caches the worker of each relation within
+    /// a batch.
+    fn target(
+        &self,
+        msg: &CdcMessage,
+        schema_cache: &SchemaCache,
+        tables: &mut HashMap<u32, usize>,
+    ) -> Target {
+        let relation_id = match msg {
+            CdcMessage::Relation {
+                namespace, name, ..
+            } => {
+                return match self.shard {
+                    PipelineShard::Table => {
+                        Target::Worker(self.table_worker(&format!("{}.{}", namespace, name)))
+                    }
+                    PipelineShard::Key => Target::Every,
+                };
+            }
+            CdcMessage::Insert { relation_id, .. }
+            | CdcMessage::Update { relation_id, .. }
+            | CdcMessage::Delete { relation_id, .. } => *relation_id,
+            _ => return Target::Boundary,
+        };
+        // Rows of an unknown relation are dropped by any sink
+        let Some(schema) = schema_cache.get(relation_id) else {
+            return Target::Worker(0);
+        };
+        let table = *tables
+            .entry(relation_id)
+            .or_insert_with(|| self.table_worker(&schema.qualified_name()));
+        if self.shard == PipelineShard::Table || !schema.columns.iter().any(|c| c.is_key()) {
+            return Target::Worker(table);
+        }
+        match msg {
+            CdcMessage::Insert { tuple, .. } => Target::Worker(self.key_worker(schema, tuple)),
+            CdcMessage::Update {
+                old_tuple: Some(old),
+                new_tuple,
+                ..
+            } if key_changed(schema, old, new_tuple) => {
+                Target::KeyChange(self.key_worker(schema, old))
+            }
+            CdcMessage::Update { new_tuple, .. } => {
+                Target::Worker(self.key_worker(schema, new_tuple))
+            }
+            CdcMessage::Delete {
+                old_tuple: Some(old),
+                ..
+            } => Target::Worker(self.key_worker(schema, old)),
+            _ => Target::Worker(table),
+        }
+    }
+
+    /// Worker a schema change or lookup of `table` goes to when one will do.
+    fn schema_worker(&self, table: &str) -> usize {
+        match self.shard {
+            PipelineShard::Table => self.table_worker(table),
+            PipelineShard::Key => 0,
+        }
+    }
+
+    /// Writes the shards with changes at the same time, then empties every
+    /// shard but for `begin`, the Begin of the transaction still open.
+    async fn write(
+        &mut self,
+        shards: &mut [Shard],
+        begin: Option<&CdcMessage>,
+        schema_cache: &SchemaCache,
+        lsn: u64,
+    ) -> Result<BatchResult> {
+        let writes = self
+            .workers
+            .iter_mut()
+            .zip(shards.iter())
+            .enumerate()
+            .filter(|(_, (_, shard))| shard.changed)
+            .map(|(i, (worker, shard))| async move {
+                let worker_name = format!("pipeline worker {}", i);
+                match worker.push_batch(&shard.messages, schema_cache, lsn).await {
+                    Ok(result) => Ok(result.with_context(worker_name)),
+                    Err(e) => Err(e.context(worker_name)),
+                }
+            });
+        let results = join_all(writes).await;
+        for shard in shards.iter_mut() {
+            *shard = Shard::default();
+            shard.messages.extend(begin.cloned());
+        }
+        results.into_iter().collect()
+    }
+}
+
+/// Whether an update's old key differs from its new one. Unchanged TOASTed
+/// values are sent as such in the new row.
+fn key_changed(schema: &TableSchema, old: &Tuple, new: &Tuple) -> bool {
+    schema
+        .columns
+        .iter()
+        .zip(old.cols.iter().zip(&new.cols))
+        .filter(|(column, _)| column.is_key())
+        .any(|(_, values)| match values {
+            (_, TupleData::Toast) => false,
+            (TupleData::Text(old), TupleData::Text(new)) => old != new,
+            (TupleData::Binary(old), TupleData::Binary(new)) => old != new,
+            (TupleData::Null, TupleData::Null) => false,
+            _ => true,
+        })
+}
+
+#[async_trait]
+impl Sink for ParallelSink {
+    async fn push_batch(
+        &mut self,
+        batch: &[CdcMessage],
+        schema_cache: &SchemaCache,
+        lsn: u64,
+    ) -> Result<BatchResult> {
+        let mut shards: Vec<Shard> = (0..self.workers.len()).map(|_| Shard::default()).collect();
+        let mut tables: HashMap<u32, usize> = HashMap::new();
+        let mut result = BatchResult::default();
+        // Begin of the transaction the batch is in at `msg`
+        let mut begin = None;
+        for msg in batch {
+            match msg {
+                CdcMessage::Begin { .. } => begin = Some(msg),
+                CdcMessage::Commit { .. } => begin = None,
+                _ => {}
+            }
+            match self.target(msg, schema_cache, &mut tables) {
+                Target::Worker(i) => {
+                    shards[i].messages.push(msg.clone());
+                    shards[i].changed = true;
+                }
+                Target::KeyChange(i) => {
+                    result.merge(self.write(&mut shards, begin, schema_cache, lsn).await?);
+                    shards[i].messages.push(msg.clone());
+                    shards[i].changed = true;
+                    result.merge(self.write(&mut shards, begin, schema_cache, lsn).await?);
+                }
+                target => {
+                    for shard in &mut shards {
+                        shard.messages.push(msg.clone());
+                        shard.changed |= target == Target::Every;
+                    }
+                }
+            }
+        }
+        result.merge(self.write(&mut shards, None, schema_cache, lsn).await?);
+        Ok(result)
+    }
+
+    /// When sharding by key every worker writes the table, so each applies
+    /// the change; the first one to do so alters the sink table.
+    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+        let workers = match self.shard {
+            PipelineShard::Table => {
+                let i = self.table_worker(&delta.qualified_name());
+                i..i + 1
+            }
+            PipelineShard::Key => 0..self.workers.len(),
+        };
+        for i in workers {
+            self.workers[i]
+                .apply_schema_delta(delta)
+                .await
+                .with_context(|| format!("pipeline worker {}", i))?;
+        }
+        Ok(())
+    }
+
+    fn invalidate_schema(&mut self, table: Option<&str>) {
+        for worker in &mut self.workers {
+            worker.invalidate_schema(table);
+        }
+    }
+
+    async fn schema_diff(&self, schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        let i = self.schema_worker(&schema.qualified_name());
+        self.workers[i].schema_diff(schema).await
+    }
+
+    async fn write_dead_letters(&mut self, letters: &[DeadLetter]) -> Result<()> {
+        self.workers[0].write_dead_letters(letters).await
+    }
+
+    /// Workers are sinks of one destination, configured alike
+    fn batching(&self) -> Option<Batching> {
+        self.workers[0].batching()
+    }
+
+    /// The checkpoint stays behind the oldest row any worker holds back.
+    fn held_from(&self) -> Option<u64> {
+        self.workers.iter().filter_map(|w| w.held_from()).min()
+    }
+
+    fn release_held(&mut self) {
+        for worker in &mut self.workers {
+            worker.release_held();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::Column;
+    use bytes::Bytes;
+    use std::sync::Arc;
+
+    /// Records what each worker is handed
+    struct RecordingSink {
+        worker: usize,
+        log: Arc<Mutex<Vec<(usize, String)>>>,
+    }
+
+    #[async_trait]
+    impl Sink for RecordingSink {
+        async fn push_batch(
+            &mut self,
+            batch: &[CdcMessage],
+            _: &SchemaCache,
+            _: u64,
+        ) -> Result<BatchResult> {
+            let key = |tuple: &Tuple| match &tuple.cols[0] {
+                TupleData::Text(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                _ => "?".to_string(),
+            };
+            let kinds: Vec<String> = batch
+                .iter()
+                .map(|msg| match msg {
+                    CdcMessage::Begin { .. } => "begin".to_string(),
+                    CdcMessage::Commit { .. } => "commit".to_string(),
+                    CdcMessage::Relation { .. } => "relation".to_string(),
+                    CdcMessage::Insert {
+                        relation_id, tuple, ..
+                    } => format!("insert {}:{}", relation_id, key(tuple)),
+                    CdcMessage::Update {
+                        old_tuple: Some(old),
+                        new_tuple,
+                        ..
+                    } => format!("update {}>{}", key(old), key(new_tuple)),
+                    CdcMessage::Update { new_tuple, .. } => format!("update {}", key(new_tuple)),
+                    _ => "other".to_string(),
+                })
+                .collect();
+            self.log.lock().push((self.worker, kinds.join(" ")));
+            Ok(BatchResult::default())
+        }
+
+        async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+            self.log
+                .lock()
+                .push((self.worker, format!("apply {}", delta.qualified_name())));
+            Ok(())
+        }
+    }
+
+    fn parallel(shard: PipelineShard, log: &Arc<Mutex<Vec<(usize, String)>>>) -> ParallelSink {
+        let workers = (0..2)
+            .map(|worker| {
+                Box::new(RecordingSink {
+                    worker,
+                    log: log.clone(),
+                }) as Box<dyn Sink + Send>
+            })
+            .collect();
+        ParallelSink::new(workers, shard)
+    }
+
+    fn relation(id: u32, name: &str) -> CdcMessage {
+        CdcMessage::Relation {
+            id,
+            namespace: "public".into(),
+            name: name.into(),
+            replica_identity: b'd',
+            columns: vec![Column {
+                flags: 1,
+                name: "id".into(),
+                type_id: 23,
+                type_mod: -1,
+            }],
+        }
+    }
+
+    fn tuple(key: u32) -> Tuple {
+        Tuple {
+            cols: vec![TupleData::Text(Bytes::from(key.to_string()))],
+            toast_bitmap: 0,
+        }
+    }
+
+    fn insert(relation_id: u32, key: u32) -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id,
+            tuple: tuple(key),
+        }
+    }
+
+    fn begin() -> CdcMessage {
+        CdcMessage::Begin {
+            final_lsn: 0x20,
+            timestamp: 0,
+            xid: 7,
+        }
+    }
+
+    fn commit() -> CdcMessage {
+        CdcMessage::Commit {
+            flags: 0,
+            commit_lsn: 0x20,
+            end_lsn: 0x28,
+            timestamp: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tables_are_spread_over_workers() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut sink = parallel(PipelineShard::Table, &log);
+        let mut cache = SchemaCache::new();
+        cache.update(&relation(1, "orders"));
+        cache.update(&relation(2, "users"));
+
+        let batch = vec![begin(), insert(1, 1), insert(2, 1), insert(1, 2), commit()];
+        sink.push_batch(&batch, &cache, 0x28).await.unwrap();
+        assert_eq!(
+            *log.lock(),
+            vec![
+                (0, "begin insert 1:1 insert 1:2 commit".to_string()),
+                (1, "begin insert 2:1 commit".to_string())
+            ]
+        );
+
+        // A table stays with its worker, which alone applies its schema changes
+        log.lock().clear();
+        let batch = vec![begin(), insert(2, 3), commit()];
+        sink.push_batch(&batch, &cache, 0x30).await.unwrap();
+        sink.apply_schema_delta(&cache.get(2).unwrap().full_delta())
+            .await
+            .unwrap();
+        assert_eq!(
+            *log.lock(),
+            vec![
+                (1, "begin insert 2:3 commit".to_string()),
+                (1, "apply public.users".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_key_changes_are_written_alone() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut sink = parallel(PipelineShard::Key, &log);
+        let mut cache = SchemaCache::new();
+        cache.update(&relation(1, "orders"));
+        let schema = cache.get(1).unwrap();
+        // Keys written by different workers
+        let a = 1;
+        let b = (2..100)
+            .find(|&k| sink.key_worker(schema, &tuple(k)) != sink.key_worker(schema, &tuple(a)))
+            .unwrap();
+        let worker = |key| sink.key_worker(schema, &tuple(key));
+        let (worker_a, worker_b) = (worker(a), worker(b));
+
+        let batch = vec![
+            begin(),
+            insert(1, a),
+            insert(1, b),
+            CdcMessage::Update {
+                relation_id: 1,
+                old_tuple: Some(tuple(a)),
+                new_tuple: tuple(b + 100),
+            },
+            CdcMessage::Update {
+                relation_id: 1,
+                old_tuple: None,
+                new_tuple: tuple(b),
+            },
+            commit(),
+        ];
+        sink.push_batch(&batch, &cache, 0x28).await.unwrap();
+        let mut log = log.lock().clone();
+        // Written at the same time, in either order
+        log[..2].sort();
+        let mut expected = vec![
+            (worker_a, format!("begin insert 1:{}", a)),
+            (worker_b, format!("begin insert 1:{}", b)),
+        ];
+        expected.sort();
+        // The writes after the first one start with the transaction's Begin
+        expected.push((worker_a, format!("begin update {}>{}", a, b + 100)));
+        expected.push((worker_b, format!("begin update {} commit", b)));
+        assert_eq!(log, expected);
+    }
+}
@@ -0,0 +1,474 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Property tests for value round trips.
+//!
+//! For every `DataType`, values are generated, written the way PostgreSQL's
+//! text output functions write them, converted by `convert_pg_value` and
+//! then encoded for StarRocks, ClickHouse and, with the `sqlite` feature,
+//! SQLite. Each test asserts that the original value comes back, or that it
+//! changes only in the documented way:
+//!
+//! - `real` is widened to `f64` from its shortest decimal text, so it equals
+//!   the original once narrowed back to `f32`.
+//! - `money` loses its currency symbol and digit grouping.
+//! - `timestamptz` is shifted to UTC and loses its offset.
+//! - `bytea` stays in PostgreSQL's hex text form (`\x...`).
+//! - NaN and infinities have no JSON literal: StarRocks gets NULL and
+//!   ClickHouse the strings `nan`/`inf`. Inside float arrays they keep their
+//!   PostgreSQL text as strings.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use proptest::prelude::*;
+#[cfg(feature = "sqlite")]
+use rusqlite::types::Value as SqlValue;
+use serde_json::json;
+
+use super::adapter::NewSinkAdapter;
+use crate::connectors::sinks::clickhouse::types::value_to_json as clickhouse_json;
+#[cfg(feature = "sqlite")]
+use crate::connectors::sinks::sqlite::types::value_to_sql;
+use crate::connectors::sinks::starrocks::types::TypeMapper;
+use crate::core::{CdcRecord, DataType, Sink, SinkCapabilities, SinkResult, Value};
+
+/// Sink that is never written to; the adapter only converts values here.
+struct NoopSink;
+
+#[async_trait]
+impl Sink for NoopSink {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            supports_upsert: true,
+            supports_delete: true,
+            supports_schema_evolution: false,
+            supports_transactions: false,
+            loading_model: crate::core::LoadingModel::Streaming,
+            min_batch_size: None,
+            max_batch_size: None,
+            optimal_flush_interval_ms: 1000,
+            micro_batch: false,
+        }
+    }
+
+    async fn validate_connection(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn write_batch(&mut self, _records: Vec<CdcRecord>) -> Result<SinkResult> {
+        unreachable!("round trip tests don't write")
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Type OID each `DataType` is generated as.
+fn type_oid(data_type: &DataType) -> u32 {
+    match data_type {
+        DataType::Boolean => 16,
+        DataType::Int16 => 21,
+        DataType::Int32 => 23,
+        DataType::Int64 => 20,
+        DataType::Float32 => 700,
+        DataType::Float64 => 701,
+        DataType::Decimal { .. } => 1700,
+        DataType::String { .. } => 1043,
+        DataType::Text => 25,
+        DataType::Bytes => 17,
+        DataType::Json => 114,
+        DataType::Jsonb => 3802,
+        DataType::Uuid => 2950,
+        DataType::Date => 1082,
+        DataType::Time => 1083,
+        DataType::Timestamp => 1114,
+        DataType::TimestampTz => 1184,
+        // No fixed OID; unregistered enum OIDs convert like text
+        DataType::Enum { .. } => 25,
+    }
+}
+
+fn convert(text: &str, data_type: &DataType) -> Value {
+    NewSinkAdapter::new(Box::new(NoopSink)).convert_pg_value(text, type_oid(data_type))
+}
+
+fn starrocks_json(value: &Value) -> serde_json::Value {
+    TypeMapper::new().value_to_json(value)
+}
+
+/// `float4out`/`float8out` with the default `extra_float_digits`: the
+/// shortest text that reads back as the same value, in exponent form when
+/// the decimal exponent is below -4 or at least the type's precision (15 or
+/// 6 digits).
+fn pg_float_text(shortest_exp: String, display: String, precision: i32) -> String {
+    let (mantissa, exp) = shortest_exp.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if exp < -4 || exp >= precision {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exp.abs())
+    } else {
+        display
+    }
+}
+
+fn pg_float8_text(f: f64) -> String {
+    match f {
+        f if f.is_nan() => "NaN".to_string(),
+        f if f.is_infinite() => if f > 0.0 { "Infinity" } else { "-Infinity" }.to_string(),
+        f => pg_float_text(format!("{:e}", f), f.to_string(), 15),
+    }
+}
+
+fn pg_float4_text(f: f32) -> String {
+    pg_float_text(format!("{:e}", f), f.to_string(), 6)
+}
+
+/// `cash_out` with the `C`/`en_US` (`-$1,234.56`) or a `de_DE`-style
+/// (`-1.234,56 €`) `lc_monetary`.
+fn pg_money_text(cents: i64, german: bool) -> String {
+    let units = (cents.unsigned_abs() / 100).to_string();
+    let (group, decimal) = if german { ('.', ',') } else { (',', '.') };
+    let mut grouped = String::new();
+    for (i, digit) in units.chars().enumerate() {
+        if i > 0 && (units.len() - i) % 3 == 0 {
+            grouped.push(group);
+        }
+        grouped.push(digit);
+    }
+    let sign = if cents < 0 { "-" } else { "" };
+    let number = format!("{}{}{:02}", grouped, decimal, cents.unsigned_abs() % 100);
+    if german {
+        format!("{}{} €", sign, number)
+    } else {
+        format!("{}${}", sign, number)
+    }
+}
+
+/// `timestamp_out`: microseconds only when there are any, without trailing
+/// zeros.
+fn pg_timestamp_text(ts: &NaiveDateTime) -> String {
+    let text = ts.format("%Y-%m-%d %H:%M:%S%.6f").to_string();
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// `timestamptz_out` in a session with a fixed UTC offset: `+05`, or
+/// `+05:30` when the offset has minutes.
+fn pg_timestamptz_text(ts: &DateTime<FixedOffset>) -> String {
+    let offset = ts.offset().local_minus_utc();
+    let sign = if offset < 0 { '-' } else { '+' };
+    let (hours, minutes) = (offset.abs() / 3600, offset.abs() % 3600 / 60);
+    let mut text = format!(
+        "{}{}{:02}",
+        pg_timestamp_text(&ts.naive_local()),
+        sign,
+        hours
+    );
+    if minutes != 0 {
+        text.push_str(&format!(":{:02}", minutes));
+    }
+    text
+}
+
+/// `array_out` for a one-dimensional array of text-like elements: elements
+/// that are empty, spell NULL, or contain a delimiter, brace, quote,
+/// backslash or whitespace are quoted, with `"` and `\` escaped.
+fn pg_array_text(elements: &[Option<String>]) -> String {
+    let elements: Vec<String> = elements
+        .iter()
+        .map(|elem| match elem {
+            None => "NULL".to_string(),
+            Some(s)
+                if s.is_empty()
+                    || s.eq_ignore_ascii_case("NULL")
+                    || s.chars().any(|c| {
+                        matches!(c, '{' | '}' | ',' | '"' | '\\')
+                            || matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c')
+                    }) =>
+            {
+                format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            Some(s) => s.clone(),
+        })
+        .collect();
+    format!("{{{}}}", elements.join(","))
+}
+
+/// Text PostgreSQL can store: anything but NUL.
+fn pg_string() -> impl Strategy<Value = String> {
+    any::<String>().prop_map(|s| s.replace('\0', ""))
+}
+
+/// JSON documents without floats, whose text form isn't unique.
+fn json_document() -> impl Strategy<Value = serde_json::Value> {
+    let leaf = prop_oneof![
+        Just(serde_json::Value::Null),
+        any::<bool>().prop_map(serde_json::Value::from),
+        any::<i64>().prop_map(serde_json::Value::from),
+        pg_string().prop_map(serde_json::Value::from),
+    ];
+    leaf.prop_recursive(3, 32, 6, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..6).prop_map(serde_json::Value::from),
+            prop::collection::btree_map(pg_string(), inner, 0..6)
+                .prop_map(|map| serde_json::Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+/// Timestamps in the years PostgreSQL writes without a BC suffix and
+/// chrono formats with four digits, a day away from either end so any UTC
+/// offset stays in range.
+fn naive_timestamp() -> impl Strategy<Value = NaiveDateTime> {
+    // 0001-01-02 to 9999-12-30, and a microsecond
+    (-62_135_510_400i64..253_402_214_400, 0u32..1_000_000).prop_map(|(secs, micros)| {
+        DateTime::from_timestamp(secs, micros * 1000)
+            .unwrap()
+            .naive_utc()
+    })
+}
+
+proptest! {
+    #[test]
+    fn prop_boolean(b in any::<bool>(), long in any::<bool>()) {
+        let text = match (b, long) {
+            (true, false) => "t",
+            (false, false) => "f",
+            (true, true) => "true",
+            (false, true) => "false",
+        };
+        let value = convert(text, &DataType::Boolean);
+        prop_assert_eq!(&value, &Value::Bool(b));
+        prop_assert_eq!(starrocks_json(&value), json!(b));
+        prop_assert_eq!(clickhouse_json(&value), json!(b));
+        #[cfg(feature = "sqlite")]
+        prop_assert_eq!(value_to_sql(&value), SqlValue::Integer(i64::from(b)));
+    }
+
+    #[test]
+    fn prop_integers(n in any::<i64>(), width in 0..3usize) {
+        // Narrowed to the column's width
+        let (data_type, n) = match width {
+            0 => (DataType::Int16, i64::from(n as i16)),
+            1 => (DataType::Int32, i64::from(n as i32)),
+            _ => (DataType::Int64, n),
+        };
+        let value = convert(&n.to_string(), &data_type);
+        prop_assert_eq!(&value, &Value::Int64(n));
+        prop_assert_eq!(starrocks_json(&value), json!(n));
+        prop_assert_eq!(clickhouse_json(&value), json!(n));
+        #[cfg(feature = "sqlite")]
+        prop_assert_eq!(value_to_sql(&value), SqlValue::Integer(n));
+    }
+
+    #[test]
+    fn prop_float8(f in any::<f64>()) {
+        let text = pg_float8_text(f);
+        let value = convert(&text, &DataType::Float64);
+        let Value::Float64(got) = value else {
+            return Err(TestCaseError::fail(format!("{} gave {:?}", text, value)));
+        };
+        if f.is_nan() {
+            prop_assert!(got.is_nan());
+        } else {
+            // Bit for bit, including -0
+            prop_assert_eq!(got.to_bits(), f.to_bits(), "{}", text);
+        }
+
+        if f.is_finite() {
+            prop_assert_eq!(starrocks_json(&value).as_f64(), Some(f));
+            prop_assert_eq!(clickhouse_json(&value).as_f64(), Some(f));
+            #[cfg(feature = "sqlite")]
+            prop_assert_eq!(value_to_sql(&value), SqlValue::Real(f));
+        } else {
+            prop_assert_eq!(starrocks_json(&value), serde_json::Value::Null);
+            let expected = if f.is_nan() { "nan" } else if f > 0.0 { "inf" } else { "-inf" };
+            prop_assert_eq!(clickhouse_json(&value), json!(expected));
+        }
+    }
+
+    #[test]
+    fn prop_float4(f in any::<f32>().prop_filter("finite", |f| f.is_finite())) {
+        let text = pg_float4_text(f);
+        let value = convert(&text, &DataType::Float32);
+        let Value::Float64(got) = value else {
+            return Err(TestCaseError::fail(format!("{} gave {:?}", text, value)));
+        };
+        prop_assert_eq!((got as f32).to_bits(), f.to_bits(), "{}", text);
+        prop_assert_eq!(starrocks_json(&value).as_f64(), Some(got));
+    }
+
+    #[test]
+    fn prop_numeric(
+        negative in any::<bool>(),
+        int in "(0|[1-9][0-9]{0,30})",
+        frac in proptest::option::of("[0-9]{1,20}"),
+    ) {
+        let text = format!(
+            "{}{}{}",
+            if negative { "-" } else { "" },
+            int,
+            frac.map(|f| format!(".{}", f)).unwrap_or_default()
+        );
+        let data_type = DataType::Decimal { precision: 38, scale: 9 };
+        let value = convert(&text, &data_type);
+        // Kept as text: trailing zeros and digits beyond f64 survive
+        prop_assert_eq!(&value, &Value::Decimal(text.clone()));
+        prop_assert_eq!(starrocks_json(&value), json!(text));
+        prop_assert_eq!(clickhouse_json(&value), json!(text));
+        #[cfg(feature = "sqlite")]
+        prop_assert_eq!(value_to_sql(&value), SqlValue::Text(text));
+    }
+
+    #[test]
+    fn prop_money(cents in any::<i64>(), german in any::<bool>()) {
+        let text = pg_money_text(cents, german);
+        let expected = format!(
+            "{}{}.{:02}",
+            if cents < 0 { "-" } else { "" },
+            cents.unsigned_abs() / 100,
+            cents.unsigned_abs() % 100
+        );
+        let value = NewSinkAdapter::new(Box::new(NoopSink)).convert_pg_value(&text, 790);
+        prop_assert_eq!(&value, &Value::Decimal(expected.clone()), "{}", text);
+        prop_assert_eq!(starrocks_json(&value), json!(expected));
+    }
+
+    #[test]
+    fn prop_text(s in pg_string(), varchar in any::<bool>()) {
+        let data_type = if varchar {
+            DataType::String { max_len: None }
+        } else {
+            DataType::Text
+        };
+        let value = convert(&s, &data_type);
+        prop_assert_eq!(&value, &Value::String(s.clone()));
+        prop_assert_eq!(starrocks_json(&value), json!(s));
+        prop_assert_eq!(clickhouse_json(&value), json!(s));
+        #[cfg(feature = "sqlite")]
+        prop_assert_eq!(value_to_sql(&value), SqlValue::Text(s));
+    }
+
+    #[test]
+    fn prop_bytea(bytes in prop::collection::vec(any::<u8>(), 0..64)) {
+        let text = format!("\\x{}", hex::encode(&bytes));
+        let value = convert(&text, &DataType::Bytes);
+        prop_assert_eq!(&value, &Value::String(text.clone()));
+        let Value::String(hex_text) = &value else { unreachable!() };
+        prop_assert_eq!(hex::decode(&hex_text[2..]).unwrap(), bytes);
+    }
+
+    #[test]
+    fn prop_json(doc in json_document(), jsonb in any::<bool>()) {
+        let data_type = if jsonb { DataType::Jsonb } else { DataType::Json };
+        let text = doc.to_string();
+        let value = convert(&text, &data_type);
+        prop_assert_eq!(&value, &Value::Json(text.clone()));
+        prop_assert_eq!(starrocks_json(&value), doc.clone());
+        // ClickHouse parses arrays only; other documents go to String columns
+        if doc.is_array() {
+            prop_assert_eq!(clickhouse_json(&value), doc);
+        } else {
+            prop_assert_eq!(clickhouse_json(&value), json!(text));
+        }
+    }
+
+    #[test]
+    fn prop_uuid(bytes in any::<[u8; 16]>()) {
+        let hex = hex::encode(bytes);
+        let text = format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        );
+        let value = convert(&text, &DataType::Uuid);
+        prop_assert_eq!(&value, &Value::Uuid(text.clone()));
+        prop_assert_eq!(starrocks_json(&value), json!(text));
+        prop_assert_eq!(clickhouse_json(&value), json!(text));
+    }
+
+    #[test]
+    fn prop_date_and_time(ts in naive_timestamp()) {
+        for (text, data_type) in [
+            (ts.format("%Y-%m-%d").to_string(), DataType::Date),
+            (pg_timestamp_text(&ts)[11..].to_string(), DataType::Time),
+            (pg_timestamp_text(&ts), DataType::Timestamp),
+        ] {
+            let value = convert(&text, &data_type);
+            prop_assert_eq!(&value, &Value::String(text.clone()));
+            prop_assert_eq!(starrocks_json(&value), json!(text));
+        }
+    }
+
+    #[test]
+    fn prop_timestamptz(ts in naive_timestamp(), quarter_hours in -48i32..=56) {
+        let offset = FixedOffset::east_opt(quarter_hours * 15 * 60).unwrap();
+        let local = ts.and_utc().with_timezone(&offset);
+        let text = pg_timestamptz_text(&local);
+        let value = convert(&text, &DataType::TimestampTz);
+
+        // Same instant, in UTC without the offset
+        let format = if text.contains('.') { "%Y-%m-%d %H:%M:%S%.6f" } else { "%Y-%m-%d %H:%M:%S" };
+        let expected = ts.format(format).to_string();
+        prop_assert_eq!(&value, &Value::String(expected), "{}", text);
+    }
+
+    #[test]
+    fn prop_int_array(elements in prop::collection::vec(proptest::option::of(any::<i64>()), 0..16)) {
+        let text = pg_array_text(
+            &elements.iter().map(|e| e.map(|n| n.to_string())).collect::<Vec<_>>(),
+        );
+        let value = NewSinkAdapter::new(Box::new(NoopSink)).convert_pg_value(&text, 1016);
+        prop_assert_eq!(starrocks_json(&value), json!(elements));
+        prop_assert_eq!(clickhouse_json(&value), json!(elements));
+    }
+
+    #[test]
+    fn prop_float_array(elements in prop::collection::vec(proptest::option::of(any::<f64>()), 0..16)) {
+        let text = pg_array_text(
+            &elements.iter().map(|e| e.map(pg_float8_text)).collect::<Vec<_>>(),
+        );
+        let value = NewSinkAdapter::new(Box::new(NoopSink)).convert_pg_value(&text, 1022);
+        let json = starrocks_json(&value);
+        let got = json.as_array().unwrap();
+        prop_assert_eq!(got.len(), elements.len());
+        for (got, want) in got.iter().zip(&elements) {
+            match want {
+                None => prop_assert!(got.is_null()),
+                Some(f) if f.is_finite() => prop_assert_eq!(got.as_f64(), Some(*f)),
+                // Non-finite values are kept as their PostgreSQL text
+                Some(f) => prop_assert_eq!(got, &json!(pg_float8_text(*f))),
+            }
+        }
+    }
+
+    #[test]
+    fn prop_text_array(elements in prop::collection::vec(proptest::option::of(pg_string()), 0..8)) {
+        let text = pg_array_text(&elements);
+        let value = NewSinkAdapter::new(Box::new(NoopSink)).convert_pg_value(&text, 1009);
+        prop_assert_eq!(starrocks_json(&value), json!(elements), "{}", text);
+        prop_assert_eq!(clickhouse_json(&value), json!(elements));
+    }
+
+    #[test]
+    fn prop_timestamp_value(micros in -62_135_596_800_000_000i64..253_402_300_800_000_000) {
+        // Value::Timestamp is written with microseconds, before and after 1970
+        let value = Value::Timestamp(micros);
+        let expected = DateTime::from_timestamp_micros(micros)
+            .unwrap()
+            .format("%Y-%m-%d %H:%M:%S%.6f")
+            .to_string();
+        prop_assert_eq!(starrocks_json(&value), json!(expected));
+        prop_assert_eq!(clickhouse_json(&value), json!(expected));
+        #[cfg(feature = "sqlite")]
+        prop_assert_eq!(value_to_sql(&value), SqlValue::Integer(micros));
+    }
+}
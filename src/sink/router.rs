@@ -0,0 +1,590 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Routing of tables to several sinks (`SINK_ROUTES`).
+//!
+//! The pipeline writes every batch to one legacy sink. With routes
+//! configured that sink is a [`SinkRouter`]: it splits the batch by table and
+//! hands each sink the changes of its own tables, along with the transaction
+//! boundaries, one sink after the other. The batch is written once every
+//! part is. When a part fails the whole batch is retried, so sinks that took
+//! their part already get it again, as when a single sink fails half way
+//! through a batch.
+//!
+//! Sinks may ask for their own batching (a `micro_batch` sink, or
+//! `SINK_ROUTE_<NAME>_FLUSH_SIZE`/`_FLUSH_INTERVAL_MS`). The pipeline cuts
+//! batches for the sink asking for the smallest, and the router holds the
+//! parts of sinks asking for larger ones until they add up to their batch
+//! size or are as old as their delay. The checkpoint stays behind the
+//! oldest row held back.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use crate::config::TablePattern;
+use crate::core::dead_letter::DeadLetter;
+use crate::core::schema_diff::SchemaDiff;
+use crate::pipeline::schema_cache::{SchemaCache, SchemaDelta};
+use crate::sink::{BatchResult, Batching, Sink};
+use crate::source::parser::CdcMessage;
+
+/// A sink that the tables matching any of `tables` are written to.
+pub struct Route {
+    pub name: String,
+    pub tables: Vec<TablePattern>,
+    pub sink: Box<dyn Sink + Send>,
+}
+
+/// Writes each table to the sink of the first route matching it, and the
+/// tables of no route to the default sink.
+pub struct SinkRouter {
+    default: Box<dyn Sink + Send>,
+    routes: Vec<Route>,
+    /// Batch size and delay of each sink, by target
+    batching: Vec<(usize, Duration)>,
+    /// Parts held back for each sink, by target
+    held: Vec<HeldPart>,
+    /// LSN of the last batch pushed
+    last_lsn: u64,
+    /// The next push writes every part held back
+    release: bool,
+}
+
+/// Changes held back for a sink asking for larger batches than the
+/// pipeline's.
+#[derive(Default)]
+struct HeldPart {
+    messages: Vec<CdcMessage>,
+    rows: usize,
+    /// When the first of them was held
+    since: Option<Instant>,
+    /// LSN of the batch before the first of them
+    from_lsn: u64,
+}
+
+impl SinkRouter {
+    pub fn new(default: Box<dyn Sink + Send>, routes: Vec<Route>) -> Self {
+        let targets = routes.len() + 1;
+        Self {
+            default,
+            routes,
+            batching: Vec::new(),
+            held: (0..targets).map(|_| HeldPart::default()).collect(),
+            last_lsn: 0,
+            release: false,
+        }
+    }
+
+    /// Resolves the batching each sink asks for against the pipeline's
+    /// `FLUSH_SIZE` and `FLUSH_INTERVAL_MS`. Without it no part is held.
+    pub fn with_pipeline_batching(mut self, flush_size: usize, flush_interval: Duration) -> Self {
+        let resolve = |sink: &dyn Sink| {
+            sink.batching()
+                .unwrap_or_default()
+                .resolve(flush_size, flush_interval)
+        };
+        self.batching = std::iter::once(resolve(self.default.as_ref()))
+            .chain(self.routes.iter().map(|route| resolve(route.sink.as_ref())))
+            .collect();
+        self
+    }
+
+    /// Batch size and delay the pipeline cuts batches at: the smallest any
+    /// sink asks for.
+    fn pipeline_batching(&self) -> Option<(usize, Duration)> {
+        let events = self.batching.iter().map(|b| b.0).min()?;
+        let delay = self.batching.iter().map(|b| b.1).min()?;
+        Some((events, delay))
+    }
+
+    /// Whether the parts of sink `target` are held until they add up to its
+    /// batching, larger than the pipeline's
+    fn holds(&self, target: usize) -> bool {
+        self.pipeline_batching()
+            .is_some_and(|pipeline| self.batching[target] != pipeline)
+    }
+
+    /// Sink of `table` (`schema.table`): 0 for the default sink, `i + 1`
+    /// for route `i`.
+    fn target(&self, table: &str) -> usize {
+        self.routes
+            .iter()
+            .position(|route| route.tables.iter().any(|p| p.matches(table)))
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Sink number `target`, with the name errors are reported under.
+    fn sink(&mut self, target: usize) -> (Option<&str>, &mut (dyn Sink + Send)) {
+        match target {
+            0 => (None, self.default.as_mut()),
+            i => {
+                let route = &mut self.routes[i - 1];
+                (Some(&route.name), route.sink.as_mut())
+            }
+        }
+    }
+
+    /// Writes `part` to sink number `target`.
+    async fn write(
+        &mut self,
+        target: usize,
+        part: &[CdcMessage],
+        schema_cache: &SchemaCache,
+        lsn: u64,
+    ) -> Result<BatchResult> {
+        match self.sink(target) {
+            (None, sink) => sink.push_batch(part, schema_cache, lsn).await,
+            (Some(route), sink) => {
+                let route = format!("sink route {}", route);
+                match sink.push_batch(part, schema_cache, lsn).await {
+                    Ok(result) => Ok(result.with_context(route)),
+                    Err(e) => Err(e.context(route)),
+                }
+            }
+        }
+    }
+}
+
+fn is_row(msg: &CdcMessage) -> bool {
+    matches!(
+        msg,
+        CdcMessage::Insert { .. } | CdcMessage::Update { .. } | CdcMessage::Delete { .. }
+    )
+}
+
+#[async_trait]
+impl Sink for SinkRouter {
+    async fn push_batch(
+        &mut self,
+        batch: &[CdcMessage],
+        schema_cache: &SchemaCache,
+        lsn: u64,
+    ) -> Result<BatchResult> {
+        let mut parts: Vec<Vec<CdcMessage>> = vec![Vec::new(); self.routes.len() + 1];
+        // Routes without changes in this batch are skipped
+        let mut changed = vec![false; parts.len()];
+        let mut targets: HashMap<u32, usize> = HashMap::new();
+        for msg in batch {
+            let target = match msg {
+                CdcMessage::Relation {
+                    namespace, name, ..
+                } => Some(self.target(&format!("{}.{}", namespace, name))),
+                CdcMessage::Insert { relation_id, .. }
+                | CdcMessage::Update { relation_id, .. }
+                | CdcMessage::Delete { relation_id, .. } => {
+                    Some(*targets.entry(*relation_id).or_insert_with(|| {
+                        // Rows of an unknown relation are dropped by any sink
+                        schema_cache.get(*relation_id).map_or(0, |schema| {
+                            self.target(&format!("{}.{}", schema.namespace, schema.name))
+                        })
+                    }))
+                }
+                _ => None,
+            };
+            match target {
+                Some(target) => {
+                    parts[target].push(msg.clone());
+                    changed[target] = true;
+                }
+                // Transaction boundaries and keepalives go to every sink
+                None => {
+                    for part in &mut parts {
+                        part.push(msg.clone());
+                    }
+                }
+            }
+        }
+
+        // The default sink takes every batch, so its checkpoint keeps moving
+        changed[0] = true;
+        let mut result = BatchResult::default();
+        for (target, part) in parts.iter().enumerate() {
+            if changed[target] && !self.holds(target) {
+                result.merge(self.write(target, part, schema_cache, lsn).await?);
+            }
+        }
+
+        // Parts of sinks with larger batches are held until due. When one
+        // fails its part is taken back out, as the batch will be retried.
+        let now = Instant::now();
+        for (target, part) in parts.into_iter().enumerate() {
+            if !self.holds(target) {
+                continue;
+            }
+            let (max_events, max_delay) = self.batching[target];
+            let held = &mut self.held[target];
+            let mut messages = std::mem::take(&mut held.messages);
+            let (held_len, held_rows, held_since) = (messages.len(), held.rows, held.since);
+            // Transaction boundaries alone are not worth holding
+            if part
+                .iter()
+                .any(|msg| is_row(msg) || matches!(msg, CdcMessage::Relation { .. }))
+            {
+                if messages.is_empty() {
+                    held.since = Some(now);
+                    held.from_lsn = self.last_lsn;
+                }
+                held.rows += part.iter().filter(|msg| is_row(msg)).count();
+                messages.extend(part);
+            }
+            let due = self.release
+                || held.rows >= max_events
+                || held.since.is_some_and(|since| now - since >= max_delay);
+            if !due || messages.is_empty() {
+                held.messages = messages;
+                continue;
+            }
+            match self.write(target, &messages, schema_cache, lsn).await {
+                Ok(written) => {
+                    result.merge(written);
+                    self.held[target] = HeldPart::default();
+                }
+                Err(e) => {
+                    messages.truncate(held_len);
+                    let held = &mut self.held[target];
+                    held.messages = messages;
+                    held.rows = held_rows;
+                    held.since = held_since;
+                    return Err(e);
+                }
+            }
+        }
+        self.last_lsn = lsn;
+        self.release = false;
+        Ok(result)
+    }
+
+    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+        match self.target(&delta.qualified_name()) {
+            0 => self.default.apply_schema_delta(delta).await,
+            i => {
+                let route = &self.routes[i - 1];
+                route
+                    .sink
+                    .apply_schema_delta(delta)
+                    .await
+                    .with_context(|| format!("sink route {}", route.name))
+            }
+        }
+    }
+
+    fn invalidate_schema(&mut self, table: Option<&str>) {
+        match table {
+            Some(table) => {
+                let target = self.target(table);
+                self.sink(target).1.invalidate_schema(Some(table));
+            }
+            None => {
+                self.default.invalidate_schema(None);
+                for route in &mut self.routes {
+                    route.sink.invalidate_schema(None);
+                }
+            }
+        }
+    }
+
+    async fn schema_diff(&self, schema: &SchemaDelta) -> Result<Option<SchemaDiff>> {
+        match self.target(&schema.qualified_name()) {
+            0 => self.default.schema_diff(schema).await,
+            i => {
+                let route = &self.routes[i - 1];
+                route
+                    .sink
+                    .schema_diff(schema)
+                    .await
+                    .with_context(|| format!("sink route {}", route.name))
+            }
+        }
+    }
+
+    /// Rejected rows of every sink are parked in the default sink's
+    /// dead-letter table.
+    async fn write_dead_letters(&mut self, letters: &[DeadLetter]) -> Result<()> {
+        self.default.write_dead_letters(letters).await
+    }
+
+    fn batching(&self) -> Option<Batching> {
+        self.pipeline_batching().map(|(events, delay)| Batching {
+            max_events: Some(events),
+            max_delay: Some(delay),
+        })
+    }
+
+    fn held_from(&self) -> Option<u64> {
+        self.held
+            .iter()
+            .filter(|held| !held.messages.is_empty())
+            .map(|held| held.from_lsn)
+            .min()
+    }
+
+    fn release_held(&mut self) {
+        self.release = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::{Column, Tuple};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    /// Records what each sink is handed
+    struct RecordingSink {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        batching: Option<Batching>,
+    }
+
+    #[async_trait]
+    impl Sink for RecordingSink {
+        async fn push_batch(
+            &mut self,
+            batch: &[CdcMessage],
+            _: &SchemaCache,
+            _: u64,
+        ) -> Result<BatchResult> {
+            let kinds: Vec<&str> = batch
+                .iter()
+                .map(|msg| match msg {
+                    CdcMessage::Begin { .. } => "begin",
+                    CdcMessage::Commit { .. } => "commit",
+                    CdcMessage::Relation { .. } => "relation",
+                    CdcMessage::Insert { .. } => "insert",
+                    _ => "other",
+                })
+                .collect();
+            self.log
+                .lock()
+                .push(format!("{}: {}", self.name, kinds.join(" ")));
+            let rows_written = kinds.iter().filter(|kind| **kind == "insert").count() as u64;
+            Ok(BatchResult {
+                rows_written,
+                labels: vec![format!("{}_{}", self.name, rows_written)],
+                warnings: vec!["slow".to_string()],
+                ..Default::default()
+            })
+        }
+
+        async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+            self.log
+                .lock()
+                .push(format!("{}: apply {}", self.name, delta.qualified_name()));
+            Ok(())
+        }
+
+        fn invalidate_schema(&mut self, table: Option<&str>) {
+            self.log.lock().push(format!(
+                "{}: invalidate {}",
+                self.name,
+                table.unwrap_or("*")
+            ));
+        }
+
+        fn batching(&self) -> Option<Batching> {
+            self.batching
+        }
+    }
+
+    fn relation(id: u32, namespace: &str, name: &str) -> CdcMessage {
+        CdcMessage::Relation {
+            id,
+            namespace: namespace.into(),
+            name: name.into(),
+            replica_identity: b'd',
+            columns: vec![Column {
+                flags: 1,
+                name: "id".into(),
+                type_id: 23,
+                type_mod: -1,
+            }],
+        }
+    }
+
+    fn insert(relation_id: u32) -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id,
+            tuple: Tuple {
+                cols: vec![],
+                toast_bitmap: 0,
+            },
+        }
+    }
+
+    fn begin() -> CdcMessage {
+        CdcMessage::Begin {
+            final_lsn: 0x20,
+            timestamp: 0,
+            xid: 7,
+        }
+    }
+
+    fn commit() -> CdcMessage {
+        CdcMessage::Commit {
+            flags: 0,
+            commit_lsn: 0x20,
+            end_lsn: 0x28,
+            timestamp: 0,
+        }
+    }
+
+    fn router(log: &Arc<Mutex<Vec<String>>>) -> SinkRouter {
+        let sink = |name| {
+            Box::new(RecordingSink {
+                name,
+                log: log.clone(),
+                batching: None,
+            })
+        };
+        SinkRouter::new(
+            sink("default"),
+            vec![Route {
+                name: "audit".into(),
+                tables: vec![TablePattern::new("SINK_ROUTE_AUDIT_TABLES", r"audit\..*").unwrap()],
+                sink: sink("audit"),
+            }],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_split_by_table() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut router = router(&log);
+        let mut cache = SchemaCache::new();
+        let orders = relation(1, "public", "orders");
+        let events = relation(2, "audit", "events");
+        cache.update(&orders);
+        cache.update(&events);
+
+        let batch = vec![begin(), insert(1), insert(2), insert(1), commit()];
+        let result = router.push_batch(&batch, &cache, 0x28).await.unwrap();
+        assert_eq!(
+            *log.lock(),
+            vec![
+                "default: begin insert insert commit",
+                "audit: begin insert commit"
+            ]
+        );
+        // The results of both sinks add up; route warnings name the route
+        assert_eq!(result.rows_written, 3);
+        assert_eq!(result.labels, ["default_2", "audit_1"]);
+        assert_eq!(result.warnings, ["slow", "sink route audit: slow"]);
+
+        // A sink without changes in the batch is left alone, except the default one
+        log.lock().clear();
+        let batch = vec![begin(), events.clone(), insert(2), commit()];
+        router.push_batch(&batch, &cache, 0x30).await.unwrap();
+        router
+            .push_batch(&[begin(), commit()], &cache, 0x38)
+            .await
+            .unwrap();
+        assert_eq!(
+            *log.lock(),
+            vec![
+                "default: begin commit",
+                "audit: begin relation insert commit",
+                "default: begin commit"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_schema_changes_follow_the_table() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut router = router(&log);
+        let mut cache = SchemaCache::new();
+        cache.update(&relation(2, "audit", "events"));
+        let delta = cache.get(2).unwrap().full_delta();
+
+        router.apply_schema_delta(&delta).await.unwrap();
+        router.invalidate_schema(Some("orders"));
+        router.invalidate_schema(None);
+        assert_eq!(
+            *log.lock(),
+            vec![
+                "audit: apply audit.events",
+                "default: invalidate orders",
+                "default: invalidate *",
+                "audit: invalidate *"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_routes_batch_on_their_own() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let sink = |name, batching| {
+            Box::new(RecordingSink {
+                name,
+                log: log.clone(),
+                batching,
+            })
+        };
+        let hour = Duration::from_secs(3600);
+        let mut router = SinkRouter::new(
+            sink("default", Some(Batching::micro())),
+            vec![Route {
+                name: "warehouse".into(),
+                tables: vec![
+                    TablePattern::new("SINK_ROUTE_WAREHOUSE_TABLES", r"audit\..*").unwrap(),
+                ],
+                sink: sink(
+                    "warehouse",
+                    Some(Batching {
+                        max_events: Some(3),
+                        max_delay: None,
+                    }),
+                ),
+            }],
+        )
+        .with_pipeline_batching(1000, hour);
+        // The pipeline cuts batches for the micro-batch sink
+        assert_eq!(
+            router.batching(),
+            Some(Batching {
+                max_events: Some(1),
+                max_delay: Some(hour),
+            })
+        );
+        let mut cache = SchemaCache::new();
+        cache.update(&relation(1, "public", "orders"));
+        cache.update(&relation(2, "audit", "events"));
+
+        let batch = vec![begin(), insert(2), insert(1), commit()];
+        router.push_batch(&batch, &cache, 0x28).await.unwrap();
+        assert_eq!(router.held_from(), Some(0));
+        let batch = vec![begin(), insert(2), commit()];
+        router.push_batch(&batch, &cache, 0x30).await.unwrap();
+        router.push_batch(&batch, &cache, 0x38).await.unwrap();
+        assert_eq!(router.held_from(), None);
+        assert_eq!(
+            *log.lock(),
+            vec![
+                "default: begin insert commit",
+                "default: begin commit",
+                "default: begin commit",
+                "warehouse: begin insert commit begin insert commit begin insert commit"
+            ]
+        );
+
+        // Held rows go out early on release
+        log.lock().clear();
+        router.push_batch(&batch, &cache, 0x40).await.unwrap();
+        assert_eq!(router.held_from(), Some(0x38));
+        router.release_held();
+        router.push_batch(&[], &cache, 0x40).await.unwrap();
+        assert_eq!(router.held_from(), None);
+        assert_eq!(
+            *log.lock(),
+            vec![
+                "default: begin commit",
+                "default: ",
+                "warehouse: begin insert commit"
+            ]
+        );
+    }
+}
@@ -0,0 +1,636 @@
+//! Decoding of binary-format tuple values (`SOURCE_BINARY_TUPLES`).
+//!
+//! With `binary 'true'` pgoutput sends each value in its type's send format
+//! instead of its text. Numbers, UUIDs and timestamps are then read straight
+//! off the wire instead of being printed by PostgreSQL and parsed again by
+//! the sink adapter, which matters on wide tables. Other consumers of a
+//! value (masking, dead letters, `PeekEvents`) get it back as PostgreSQL
+//! would have printed it, with timestamps in UTC.
+//!
+//! PostgreSQL sends every type it can in binary, so only the types listed in
+//! [`is_supported`] can be replicated this way; setup checks the columns of
+//! the replicated tables before streaming starts.
+
+use std::borrow::Cow;
+use std::fmt::{Display, LowerExp, Write};
+
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+/// Whether values of `type_id` can be decoded: the built-in scalar types
+/// below, and arrays of them.
+pub fn is_supported(type_id: u32) -> bool {
+    is_supported_scalar(type_id) || array_element(type_id).is_some()
+}
+
+fn is_supported_scalar(type_id: u32) -> bool {
+    matches!(
+        type_id,
+        16 | 17
+            | 18
+            | 19
+            | 20
+            | 21
+            | 23
+            | 25
+            | 26
+            | 114
+            | 142
+            | 700
+            | 701
+            | 1042
+            | 1043
+            | 1082
+            | 1083
+            | 1114
+            | 1184
+            | 1700
+            | 2950
+            | 3802
+    )
+}
+
+/// Element type of the array types of the supported scalar types.
+fn array_element(type_id: u32) -> Option<u32> {
+    Some(match type_id {
+        1000 => 16,
+        1001 => 17,
+        1002 => 18,
+        1003 => 19,
+        1016 => 20,
+        1005 => 21,
+        1007 => 23,
+        1009 => 25,
+        1028 => 26,
+        199 => 114,
+        143 => 142,
+        1021 => 700,
+        1022 => 701,
+        1014 => 1042,
+        1015 => 1043,
+        1182 => 1082,
+        1183 => 1083,
+        1115 => 1114,
+        1185 => 1184,
+        1231 => 1700,
+        2951 => 2950,
+        3807 => 3802,
+        _ => return None,
+    })
+}
+
+/// The value as PostgreSQL prints it, timestamps with time zone in UTC.
+pub fn to_text(type_id: u32, bytes: &[u8]) -> Result<Cow<'_, str>> {
+    let text = match type_id {
+        // Text-like types send their text
+        18 | 19 | 25 | 114 | 142 | 1042 | 1043 => return text(bytes),
+        3802 => match bytes.split_first() {
+            Some((1, json)) => return text(json),
+            _ => bail!("unsupported jsonb version"),
+        },
+        16 => match bytes {
+            [0] => "f".to_string(),
+            [_] => "t".to_string(),
+            _ => bail!("bool of {} bytes", bytes.len()),
+        },
+        17 => format!("\\x{}", hex::encode(bytes)),
+        20 | 21 | 23 | 26 => read_int(bytes, type_id)?.to_string(),
+        700 => float_text(f32::from_be_bytes(fixed(bytes)?), 6),
+        701 => float_text(f64::from_be_bytes(fixed(bytes)?), 15),
+        1082 => date_text(bytes)?,
+        1083 => time_text(bytes)?,
+        1114 => timestamp_text(bytes)?,
+        1184 => match timestamp_utc(bytes)? {
+            Timestamp::At(at) => format!("{}+00{}", format_timestamp(at, true), bc(at)),
+            infinite => infinite.to_string(),
+        },
+        1700 => numeric_text(bytes)?,
+        2950 => uuid_text(bytes)?,
+        _ => match array_element(type_id) {
+            Some(element_type) => array_text(bytes, element_type)?,
+            None => bail!("no binary decoder for type {}", type_id),
+        },
+    };
+    Ok(Cow::Owned(text))
+}
+
+/// An `int2`, `int4`, `int8` or `oid`.
+pub fn read_int(bytes: &[u8], type_id: u32) -> Result<i64> {
+    Ok(match type_id {
+        21 => i16::from_be_bytes(fixed(bytes)?).into(),
+        23 => i32::from_be_bytes(fixed(bytes)?).into(),
+        26 => u32::from_be_bytes(fixed(bytes)?).into(),
+        _ => i64::from_be_bytes(fixed(bytes)?),
+    })
+}
+
+/// A `float4` or `float8`.
+pub fn read_float(bytes: &[u8]) -> Result<f64> {
+    match bytes.len() {
+        4 => Ok(f32::from_be_bytes(fixed(bytes)?).into()),
+        _ => Ok(f64::from_be_bytes(fixed(bytes)?)),
+    }
+}
+
+/// A `numeric` in decimal notation, with its scale, or `NaN`, `Infinity`,
+/// `-Infinity`.
+pub fn numeric_text(bytes: &[u8]) -> Result<String> {
+    let header = bytes.get(..8).context("numeric header cut short")?;
+    let ndigits = u16::from_be_bytes([header[0], header[1]]) as usize;
+    let weight = i16::from_be_bytes([header[2], header[3]]) as i32;
+    let sign = u16::from_be_bytes([header[4], header[5]]);
+    let dscale = u16::from_be_bytes([header[6], header[7]]) as usize;
+    let digits: Vec<i16> = bytes[8..]
+        .chunks_exact(2)
+        .map(|d| i16::from_be_bytes([d[0], d[1]]))
+        .collect();
+    if digits.len() != ndigits {
+        bail!("numeric with {} of {} digits", digits.len(), ndigits);
+    }
+    let mut text = match sign {
+        0x0000 => String::new(),
+        0x4000 => "-".to_string(),
+        0xC000 => return Ok("NaN".to_string()),
+        0xD000 => return Ok("Infinity".to_string()),
+        0xF000 => return Ok("-Infinity".to_string()),
+        _ => bail!("numeric sign {:#x}", sign),
+    };
+    // Base-10000 digits; the first one has the weight `weight`
+    let digit = |i: i32| {
+        usize::try_from(i)
+            .ok()
+            .and_then(|i| digits.get(i))
+            .copied()
+            .unwrap_or(0)
+    };
+    if weight < 0 {
+        text.push('0');
+    } else {
+        let _ = write!(text, "{}", digit(0));
+        for i in 1..=weight {
+            let _ = write!(text, "{:04}", digit(i));
+        }
+    }
+    if dscale > 0 {
+        text.push('.');
+        let start = text.len();
+        let mut i = weight + 1;
+        while text.len() - start < dscale {
+            let _ = write!(text, "{:04}", digit(i));
+            i += 1;
+        }
+        text.truncate(start + dscale);
+    }
+    Ok(text)
+}
+
+/// A `uuid`, hyphenated.
+pub fn uuid_text(bytes: &[u8]) -> Result<String> {
+    let bytes: [u8; 16] = fixed(bytes)?;
+    let hex = hex::encode(bytes);
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+/// A `timestamp` as PostgreSQL prints it.
+pub fn timestamp_text(bytes: &[u8]) -> Result<String> {
+    Ok(match timestamp_utc(bytes)? {
+        Timestamp::At(at) => format!("{}{}", format_timestamp(at, true), bc(at)),
+        infinite => infinite.to_string(),
+    })
+}
+
+/// A `timestamptz` in UTC without offset, its fraction in microseconds
+/// when it has one, the way the text path normalizes it.
+pub fn timestamptz_utc_text(bytes: &[u8]) -> Result<String> {
+    Ok(match timestamp_utc(bytes)? {
+        Timestamp::At(at) => format!("{}{}", format_timestamp(at, false), bc(at)),
+        infinite => infinite.to_string(),
+    })
+}
+
+enum Timestamp {
+    At(NaiveDateTime),
+    Infinity,
+    NegativeInfinity,
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Timestamp::At(at) => write!(f, "{}", at),
+            Timestamp::Infinity => write!(f, "infinity"),
+            Timestamp::NegativeInfinity => write!(f, "-infinity"),
+        }
+    }
+}
+
+fn pg_epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2000, 1, 1)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .expect("valid date")
+}
+
+/// Microseconds since the PostgreSQL epoch
+fn timestamp_utc(bytes: &[u8]) -> Result<Timestamp> {
+    Ok(match i64::from_be_bytes(fixed(bytes)?) {
+        i64::MAX => Timestamp::Infinity,
+        i64::MIN => Timestamp::NegativeInfinity,
+        micros => Timestamp::At(
+            pg_epoch()
+                .checked_add_signed(Duration::microseconds(micros))
+                .context("timestamp out of range")?,
+        ),
+    })
+}
+
+/// `YYYY-MM-DD HH:MM:SS`, then the fraction trimmed of its trailing zeros
+/// (`trim`) or in microseconds. Years BC count from 1, see [`bc`].
+fn format_timestamp(at: NaiveDateTime, trim: bool) -> String {
+    let mut text = format!(
+        "{:04}-{}",
+        year_of_era(at.year()),
+        at.format("%m-%d %H:%M:%S")
+    );
+    push_fraction(&mut text, at.and_utc().timestamp_subsec_micros(), trim);
+    text
+}
+
+fn year_of_era(year: i32) -> i32 {
+    if year <= 0 {
+        1 - year
+    } else {
+        year
+    }
+}
+
+fn bc(at: NaiveDateTime) -> &'static str {
+    if at.year() <= 0 {
+        " BC"
+    } else {
+        ""
+    }
+}
+
+fn push_fraction(text: &mut String, micros: u32, trim: bool) {
+    if micros == 0 {
+        return;
+    }
+    let fraction = format!("{:06}", micros);
+    text.push('.');
+    text.push_str(if trim {
+        fraction.trim_end_matches('0')
+    } else {
+        &fraction
+    });
+}
+
+/// Days since the PostgreSQL epoch
+fn date_text(bytes: &[u8]) -> Result<String> {
+    Ok(match i32::from_be_bytes(fixed(bytes)?) {
+        i32::MAX => "infinity".to_string(),
+        i32::MIN => "-infinity".to_string(),
+        days => {
+            let date = pg_epoch()
+                .date()
+                .checked_add_signed(Duration::days(days.into()))
+                .context("date out of range")?;
+            let bc = if date.year() <= 0 { " BC" } else { "" };
+            format!(
+                "{:04}-{}{}",
+                year_of_era(date.year()),
+                date.format("%m-%d"),
+                bc
+            )
+        }
+    })
+}
+
+/// Microseconds since midnight
+fn time_text(bytes: &[u8]) -> Result<String> {
+    let micros = i64::from_be_bytes(fixed(bytes)?);
+    // 24:00:00 is a valid time
+    if micros == 86_400_000_000 {
+        return Ok("24:00:00".to_string());
+    }
+    let secs = u32::try_from(micros.div_euclid(1_000_000)).context("time out of range")?;
+    let time =
+        NaiveTime::from_num_seconds_from_midnight_opt(secs, 0).context("time out of range")?;
+    let mut text = time.format("%H:%M:%S").to_string();
+    push_fraction(&mut text, micros.rem_euclid(1_000_000) as u32, true);
+    Ok(text)
+}
+
+/// Shortest text that reads back as `f`, in exponent notation past
+/// `max_exponent` digits or below 1e-4, as PostgreSQL prints floats.
+fn float_text<F: Display + LowerExp + Into<f64> + Copy>(f: F, max_exponent: i32) -> String {
+    let value: f64 = f.into();
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    let abs = value.abs();
+    if abs == 0.0 || (abs >= 1e-4 && abs < 10f64.powi(max_exponent)) {
+        return f.to_string();
+    }
+    let text = format!("{:e}", f);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let exponent: i32 = exponent.parse().unwrap_or(0);
+            let sign = if exponent < 0 { '-' } else { '+' };
+            format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+        }
+        None => text,
+    }
+}
+
+/// An array of `element_type` as PostgreSQL prints it, e.g. `{1,NULL,3}` or
+/// `{{a,"b c"}}`.
+fn array_text(bytes: &[u8], element_type: u32) -> Result<String> {
+    let mut input = bytes;
+    let ndim = read_i32(&mut input)?;
+    let _has_nulls = read_i32(&mut input)?;
+    let sent_type = read_i32(&mut input)? as u32;
+    if sent_type != element_type {
+        bail!(
+            "array of type {} where type {} was expected",
+            sent_type,
+            element_type
+        );
+    }
+    if ndim == 0 {
+        return Ok("{}".to_string());
+    }
+    if !(1..=MAX_ARRAY_DIMENSIONS).contains(&ndim) {
+        bail!("array of {} dimensions", ndim);
+    }
+    let mut dims = Vec::with_capacity(ndim as usize);
+    for _ in 0..ndim {
+        let len = read_i32(&mut input)?;
+        let lower_bound = read_i32(&mut input)?;
+        dims.push((
+            usize::try_from(len).context("negative array length")?,
+            lower_bound,
+        ));
+    }
+
+    let mut text = String::new();
+    // Bounds other than 1 are spelled out
+    if dims.iter().any(|&(_, lower)| lower != 1) {
+        for &(len, lower) in &dims {
+            let upper = i64::from(lower) + len as i64 - 1;
+            let _ = write!(text, "[{}:{}]", lower, upper);
+        }
+        text.push('=');
+    }
+    // Every element takes at least its 4-byte length, so a count the
+    // remaining bytes can't hold is refused before allocating for it
+    let total = dims
+        .iter()
+        .try_fold(1usize, |total, &(len, _)| total.checked_mul(len))
+        .filter(|&total| total <= input.len() / 4)
+        .context("array longer than its value")?;
+    let mut elements = Vec::with_capacity(total);
+    for _ in 0..total {
+        let len = read_i32(&mut input)?;
+        if len < 0 {
+            elements.push(None);
+            continue;
+        }
+        let value = input
+            .get(..len as usize)
+            .context("array element cut short")?;
+        input = &input[len as usize..];
+        elements.push(Some(to_text(element_type, value)?));
+    }
+    let mut elements = elements.into_iter();
+    write_dimension(&mut text, &dims, &mut elements);
+    Ok(text)
+}
+
+/// `MAXDIM` of PostgreSQL
+const MAX_ARRAY_DIMENSIONS: i32 = 6;
+
+fn read_i32(input: &mut &[u8]) -> Result<i32> {
+    let value: [u8; 4] = fixed(input.get(..4).context("array cut short")?)?;
+    *input = &input[4..];
+    Ok(i32::from_be_bytes(value))
+}
+
+fn write_dimension<'a>(
+    text: &mut String,
+    dims: &[(usize, i32)],
+    elements: &mut impl Iterator<Item = Option<Cow<'a, str>>>,
+) {
+    text.push('{');
+    for i in 0..dims[0].0 {
+        if i > 0 {
+            text.push(',');
+        }
+        if dims.len() > 1 {
+            write_dimension(text, &dims[1..], elements);
+            continue;
+        }
+        match elements.next().flatten() {
+            None => text.push_str("NULL"),
+            Some(element) => push_array_element(text, &element),
+        }
+    }
+    text.push('}');
+}
+
+/// Quotes an element that would otherwise not read back as itself.
+fn push_array_element(text: &mut String, element: &str) {
+    let quote = element.is_empty()
+        || element.eq_ignore_ascii_case("NULL")
+        || element
+            .chars()
+            .any(|c| matches!(c, '{' | '}' | ',' | '"' | '\\') || c.is_ascii_whitespace());
+    if !quote {
+        text.push_str(element);
+        return;
+    }
+    text.push('"');
+    for c in element.chars() {
+        if c == '"' || c == '\\' {
+            text.push('\\');
+        }
+        text.push(c);
+    }
+    text.push('"');
+}
+
+fn text(bytes: &[u8]) -> Result<Cow<'_, str>> {
+    Ok(Cow::Borrowed(
+        std::str::from_utf8(bytes).context("text value is not UTF-8")?,
+    ))
+}
+
+fn fixed<const N: usize>(bytes: &[u8]) -> Result<[u8; N]> {
+    bytes
+        .try_into()
+        .with_context(|| format!("expected {} bytes, got {}", N, bytes.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric(weight: i16, sign: u16, dscale: u16, digits: &[i16]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(digits.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&sign.to_be_bytes());
+        bytes.extend_from_slice(&dscale.to_be_bytes());
+        for digit in digits {
+            bytes.extend_from_slice(&digit.to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(to_text(23, &(-42i32).to_be_bytes()).unwrap(), "-42");
+        assert_eq!(read_int(&7i16.to_be_bytes(), 21).unwrap(), 7);
+        assert_eq!(read_int(&i64::MAX.to_be_bytes(), 20).unwrap(), i64::MAX);
+        assert!(read_int(&7i16.to_be_bytes(), 23).is_err());
+
+        assert_eq!(to_text(701, &1.5f64.to_be_bytes()).unwrap(), "1.5");
+        assert_eq!(to_text(701, &1e20f64.to_be_bytes()).unwrap(), "1e+20");
+        assert_eq!(to_text(701, &1.5e-5f64.to_be_bytes()).unwrap(), "1.5e-05");
+        assert_eq!(to_text(700, &0.1f32.to_be_bytes()).unwrap(), "0.1");
+        assert_eq!(to_text(700, &1e7f32.to_be_bytes()).unwrap(), "1e+07");
+        assert_eq!(to_text(701, &f64::NAN.to_be_bytes()).unwrap(), "NaN");
+        assert_eq!(read_float(&0.25f32.to_be_bytes()).unwrap(), 0.25);
+
+        // 12345.6789, -0.00012 and 100 with two decimals
+        assert_eq!(
+            numeric_text(&numeric(1, 0, 4, &[1, 2345, 6789])).unwrap(),
+            "12345.6789"
+        );
+        assert_eq!(
+            numeric_text(&numeric(-1, 0x4000, 5, &[1, 2000])).unwrap(),
+            "-0.00012"
+        );
+        assert_eq!(numeric_text(&numeric(0, 0, 2, &[100])).unwrap(), "100.00");
+        assert_eq!(numeric_text(&numeric(1, 0, 0, &[1])).unwrap(), "10000");
+        assert_eq!(numeric_text(&numeric(0, 0, 0, &[])).unwrap(), "0");
+        assert_eq!(numeric_text(&numeric(0, 0xC000, 0, &[])).unwrap(), "NaN");
+        assert!(numeric_text(&numeric(0, 0, 0, &[1])[..9]).is_err());
+    }
+
+    #[test]
+    fn test_dates_and_times() {
+        let micros = |us: i64| us.to_be_bytes();
+        // 2024-03-01 12:30:05.25 UTC
+        let at = 762_611_405_250_000i64;
+        assert_eq!(
+            timestamp_text(&micros(at)).unwrap(),
+            "2024-03-01 12:30:05.25"
+        );
+        assert_eq!(
+            timestamptz_utc_text(&micros(at)).unwrap(),
+            "2024-03-01 12:30:05.250000"
+        );
+        assert_eq!(
+            to_text(1184, &micros(at - 250_000)).unwrap(),
+            "2024-03-01 12:30:05+00"
+        );
+        assert_eq!(timestamp_text(&micros(i64::MAX)).unwrap(), "infinity");
+        assert_eq!(
+            timestamp_text(&micros(-63_082_368_000_000_000)).unwrap(),
+            "0001-12-31 00:00:00 BC"
+        );
+
+        assert_eq!(to_text(1082, &0i32.to_be_bytes()).unwrap(), "2000-01-01");
+        assert_eq!(to_text(1082, &(-1i32).to_be_bytes()).unwrap(), "1999-12-31");
+        assert_eq!(
+            to_text(1083, &micros(45_296_500_000)).unwrap(),
+            "12:34:56.5"
+        );
+    }
+
+    #[test]
+    fn test_other_types() {
+        let uuid = hex::decode("a0eebc999c0b4ef8bb6d6bb9bd380a11").unwrap();
+        assert_eq!(
+            to_text(2950, &uuid).unwrap(),
+            "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"
+        );
+        assert_eq!(to_text(16, &[1]).unwrap(), "t");
+        assert_eq!(to_text(17, &[0xde, 0xad]).unwrap(), "\\xdead");
+        assert_eq!(to_text(3802, b"\x01{\"a\": 1}").unwrap(), "{\"a\": 1}");
+        assert_eq!(to_text(1043, "café".as_bytes()).unwrap(), "café");
+        assert!(to_text(1186, &[0; 16]).is_err());
+    }
+
+    #[test]
+    fn test_arrays() {
+        let array = |element_type: u32, dims: &[i32], elements: &[Option<&[u8]>]| {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&(dims.len() as i32).to_be_bytes());
+            bytes.extend_from_slice(&1i32.to_be_bytes());
+            bytes.extend_from_slice(&element_type.to_be_bytes());
+            for len in dims {
+                bytes.extend_from_slice(&len.to_be_bytes());
+                bytes.extend_from_slice(&1i32.to_be_bytes());
+            }
+            for element in elements {
+                match element {
+                    Some(value) => {
+                        bytes.extend_from_slice(&(value.len() as i32).to_be_bytes());
+                        bytes.extend_from_slice(value);
+                    }
+                    None => bytes.extend_from_slice(&(-1i32).to_be_bytes()),
+                }
+            }
+            bytes
+        };
+        let (one, three) = (1i32.to_be_bytes(), 3i32.to_be_bytes());
+        assert_eq!(
+            to_text(
+                1007,
+                &array(23, &[3], &[Some(&one[..]), None, Some(&three[..])])
+            )
+            .unwrap(),
+            "{1,NULL,3}"
+        );
+        assert_eq!(
+            to_text(
+                1009,
+                &array(
+                    25,
+                    &[2, 2],
+                    &[Some(&b"a"[..]), Some(b"b c"), Some(b"null"), Some(b"\"")]
+                )
+            )
+            .unwrap(),
+            r#"{{a,"b c"},{"null","\""}}"#
+        );
+        assert_eq!(to_text(1007, &array(23, &[], &[])).unwrap(), "{}");
+
+        // Malformed headers fail instead of allocating or overflowing
+        let one_element = [Some(&one[..])];
+        assert!(to_text(1007, &array(25, &[1], &one_element)).is_err());
+        assert!(to_text(1007, &array(23, &[1; 7], &one_element)).is_err());
+        assert!(to_text(1007, &array(23, &[i32::MAX, i32::MAX], &one_element)).is_err());
+        assert!(to_text(1007, &array(23, &[2], &one_element)).is_err());
+        let mut negative_ndim = array(23, &[], &[]);
+        negative_ndim[..4].copy_from_slice(&(-1i32).to_be_bytes());
+        assert!(to_text(1007, &negative_ndim).is_err());
+        let mut high_bound = array(23, &[1], &one_element);
+        high_bound[16..20].copy_from_slice(&i32::MAX.to_be_bytes());
+        assert_eq!(
+            to_text(1007, &high_bound).unwrap(),
+            "[2147483647:2147483647]={1}"
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Clock skew between dbmazz and the source.
+//!
+//! Replication lag and freshness are the local time minus the commit
+//! timestamp the source wrote, and standby status updates carry the local
+//! time. A host whose clock is off (NTP misconfigured or not running) shows
+//! lag that is inflated, or zero when commits appear to come from the future.
+//!
+//! Every keepalive the source sends carries its clock at send time. The skew
+//! is the local receive time minus that, so it includes the network delay.
+//! Past `CLOCK_SKEW_WARN_MS` it is logged once per crossing, and commit
+//! timestamps further ahead of the local clock than that are counted. With
+//! `CLOCK_SKEW_CORRECTION`, [`now_unix_us`] subtracts the skew, so lag,
+//! freshness and status updates follow the source's clock instead.
+
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn};
+
+/// PostgreSQL epoch (2000-01-01 00:00:00 UTC) in Unix microseconds
+pub const PG_EPOCH_OFFSET_USEC: i64 = 946_684_800_000_000;
+
+/// Commits from the future are logged at most this often
+const FUTURE_COMMIT_WARN_INTERVAL_US: i64 = 60_000_000;
+
+/// Local minus source clock, as of the last keepalive
+static SKEW_US: AtomicI64 = AtomicI64::new(0);
+/// Skew past which it is reported, 0 = never
+static WARN_US: AtomicU64 = AtomicU64::new(1_000_000);
+/// Apply the skew to the local clock
+static CORRECT: AtomicBool = AtomicBool::new(false);
+/// The skew is over `WARN_US`
+static SKEWED: AtomicBool = AtomicBool::new(false);
+/// Commit timestamps ahead of the local clock since startup or the last reset
+static FUTURE_COMMITS: AtomicU64 = AtomicU64::new(0);
+/// When the last commit from the future was logged, Unix microseconds
+static LAST_FUTURE_WARN_US: AtomicI64 = AtomicI64::new(0);
+
+/// Set by the engine from `CLOCK_SKEW_WARN_MS` and `CLOCK_SKEW_CORRECTION`.
+pub fn configure(warn_ms: u64, correct: bool) {
+    WARN_US.store(warn_ms * 1_000, Ordering::Relaxed);
+    CORRECT.store(correct, Ordering::Relaxed);
+}
+
+fn local_unix_us() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as i64
+}
+
+/// Current time in Unix microseconds: the local clock, or the source's as
+/// estimated from the skew with `CLOCK_SKEW_CORRECTION`.
+pub fn now_unix_us() -> i64 {
+    if CORRECT.load(Ordering::Relaxed) {
+        local_unix_us() - SKEW_US.load(Ordering::Relaxed)
+    } else {
+        local_unix_us()
+    }
+}
+
+/// Notes the source clock sent in a keepalive, in PostgreSQL microseconds.
+pub fn observe_server_time(server_time: i64) {
+    if server_time == 0 {
+        return;
+    }
+    let skew = local_unix_us() - (server_time + PG_EPOCH_OFFSET_USEC);
+    record_skew(skew);
+}
+
+fn record_skew(skew_us: i64) {
+    SKEW_US.store(skew_us, Ordering::Relaxed);
+    let warn_us = WARN_US.load(Ordering::Relaxed);
+    let skewed = warn_us > 0 && skew_us.unsigned_abs() > warn_us;
+    if SKEWED.swap(skewed, Ordering::Relaxed) == skewed {
+        return;
+    }
+    if skewed {
+        warn!(
+            "Local clock is {} ms {} the source's (limit {} ms): lag and freshness are off by as much{}",
+            skew_us.unsigned_abs() / 1_000,
+            if skew_us > 0 { "ahead of" } else { "behind" },
+            warn_us / 1_000,
+            if CORRECT.load(Ordering::Relaxed) {
+                "; correcting with the source clock"
+            } else {
+                "; set CLOCK_SKEW_CORRECTION=true to use the source clock"
+            }
+        );
+    } else {
+        info!(
+            "Local clock back within {} ms of the source's",
+            warn_us / 1_000
+        );
+    }
+}
+
+/// Local minus source clock in milliseconds, as of the last keepalive.
+pub fn skew_ms() -> i64 {
+    SKEW_US.load(Ordering::Relaxed) / 1_000
+}
+
+/// Checks a commit timestamp (PostgreSQL microseconds) against the clock and
+/// counts it when it is further ahead than `CLOCK_SKEW_WARN_MS`.
+pub fn check_commit_timestamp(commit_timestamp: u64) {
+    let warn_us = WARN_US.load(Ordering::Relaxed) as i64;
+    if warn_us == 0 {
+        return;
+    }
+    let now = now_unix_us();
+    let ahead_us = commit_timestamp as i64 + PG_EPOCH_OFFSET_USEC - now;
+    if ahead_us <= warn_us {
+        return;
+    }
+    FUTURE_COMMITS.fetch_add(1, Ordering::Relaxed);
+    let last = LAST_FUTURE_WARN_US.load(Ordering::Relaxed);
+    if now - last >= FUTURE_COMMIT_WARN_INTERVAL_US
+        && LAST_FUTURE_WARN_US
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        warn!(
+            "Commit timestamp {} ms ahead of the clock: the source or local clock is wrong",
+            ahead_us / 1_000
+        );
+    }
+}
+
+/// Commit timestamps ahead of the clock since startup or the last reset.
+pub fn future_commits() -> u64 {
+    FUTURE_COMMITS.load(Ordering::Relaxed)
+}
+
+pub fn reset_counts() {
+    FUTURE_COMMITS.store(0, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pg_now() -> i64 {
+        local_unix_us() - PG_EPOCH_OFFSET_USEC
+    }
+
+    #[test]
+    fn test_skew_and_correction() {
+        configure(1_000, false);
+
+        // Source clock 5s behind: local is ahead, commits look 5s old
+        observe_server_time(pg_now() - 5_000_000);
+        assert!((4_990..5_100).contains(&skew_ms()));
+        assert!(SKEWED.load(Ordering::Relaxed));
+        assert!((local_unix_us() - now_unix_us()).abs() < 100_000);
+
+        configure(1_000, true);
+        assert!((local_unix_us() - now_unix_us() - 5_000_000).abs() < 100_000);
+
+        // Source clock 10s ahead: its commits are in the future of the local clock
+        configure(1_000, false);
+        observe_server_time(pg_now() + 10_000_000);
+        assert!(skew_ms() < -9_900);
+        let before = future_commits();
+        check_commit_timestamp((pg_now() + 10_000_000) as u64);
+        check_commit_timestamp(pg_now() as u64);
+        assert_eq!(future_commits(), before + 1);
+
+        // Corrected, the same commit is on time
+        configure(1_000, true);
+        check_commit_timestamp((pg_now() + 10_000_000) as u64);
+        assert_eq!(future_commits(), before + 1);
+
+        observe_server_time(pg_now());
+        assert!(!SKEWED.load(Ordering::Relaxed));
+        configure(1_000, false);
+    }
+}
@@ -0,0 +1,461 @@
+//! Geometric and PostGIS values as WKT or GeoJSON (`SINK_GEOMETRY_FORMAT`).
+//!
+//! The built-in `point`, `lseg`, `box`, `path` and `polygon` types are
+//! parsed from their text. PostGIS `geometry` and `geography` values arrive
+//! as hex EWKB, the extension's text output; their OIDs are only known once
+//! `pg_types` has read them from the catalog, so without the extension
+//! nothing is converted. `line` (an infinite line) and `circle` have no
+//! simple-features equivalent and stay strings, as does anything that
+//! doesn't parse: curves, surfaces and other exotic PostGIS types.
+//!
+//! Both formats drop the SRID, as `ST_AsText` and `ST_AsGeoJSON` do.
+//! GeoJSON positions also drop the M ordinate.
+
+use std::fmt::Write;
+
+use crate::config::GeometryFormat;
+use crate::source::pg_types::{self, PgType};
+
+/// A position: x, y, then z and m when the geometry has them
+type Coord = Vec<f64>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Geometry {
+    /// No ordinates for `POINT EMPTY`
+    Point(Coord),
+    LineString(Vec<Coord>),
+    Polygon(Vec<Vec<Coord>>),
+    MultiPoint(Vec<Coord>),
+    MultiLineString(Vec<Vec<Coord>>),
+    MultiPolygon(Vec<Vec<Vec<Coord>>>),
+    Collection(Vec<Geometry>),
+}
+
+/// Ordinates beyond x and y
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Dims {
+    z: bool,
+    m: bool,
+}
+
+impl Dims {
+    fn count(self) -> usize {
+        2 + self.z as usize + self.m as usize
+    }
+}
+
+/// Whether values of `oid` are converted (see the module doc).
+pub fn is_geometric(oid: u32) -> bool {
+    matches!(oid, 600 | 601 | 602 | 603 | 604) || is_postgis(oid)
+}
+
+/// Whether `oid` is PostGIS `geometry` or `geography`.
+pub fn is_postgis(oid: u32) -> bool {
+    matches!(pg_types::get(oid), Some(PgType::Geometry))
+}
+
+/// `text`, a value of geometric type `oid`, as WKT or GeoJSON text. `None`
+/// when it doesn't parse.
+pub fn convert(text: &str, oid: u32, format: GeometryFormat) -> Option<String> {
+    let (geometry, dims) = match oid {
+        600 => (Geometry::Point(points(text, 1)?.pop()?), Dims::default()),
+        601 => (Geometry::LineString(points(text, 2)?), Dims::default()),
+        603 => {
+            // Upper right corner first
+            let corners = points(text, 2)?;
+            let (x1, y1, x2, y2) = (corners[1][0], corners[1][1], corners[0][0], corners[0][1]);
+            let ring = vec![
+                vec![x1, y1],
+                vec![x2, y1],
+                vec![x2, y2],
+                vec![x1, y2],
+                vec![x1, y1],
+            ];
+            (Geometry::Polygon(vec![ring]), Dims::default())
+        }
+        // An open path is `[...]`; a closed one `(...)` ends where it starts
+        602 => {
+            let mut path = points(text, 0)?;
+            if !text.trim_start().starts_with('[') {
+                path.push(path.first()?.clone());
+            }
+            (Geometry::LineString(path), Dims::default())
+        }
+        604 => {
+            let mut ring = points(text, 0)?;
+            ring.push(ring.first()?.clone());
+            (Geometry::Polygon(vec![ring]), Dims::default())
+        }
+        _ => {
+            let bytes = hex::decode(text.trim()).ok()?;
+            let mut reader = Ewkb {
+                bytes: &bytes,
+                pos: 0,
+            };
+            let parsed = reader.geometry(0)?;
+            (reader.pos == bytes.len()).then_some(parsed)?
+        }
+    };
+    let mut out = String::with_capacity(text.len());
+    match format {
+        GeometryFormat::Wkt => write_wkt(&mut out, &geometry, dims),
+        GeometryFormat::GeoJson => write_geojson(&mut out, &geometry)?,
+    }
+    Some(out)
+}
+
+/// The (x, y) pairs of a built-in geometric value's text, `expected` of
+/// them unless 0.
+fn points(text: &str, expected: usize) -> Option<Vec<Coord>> {
+    let numbers = text
+        .split(|c: char| matches!(c, '(' | ')' | '[' | ']' | ','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<f64>().ok())
+        .collect::<Option<Vec<f64>>>()?;
+    if numbers.is_empty() || numbers.len() % 2 != 0 {
+        return None;
+    }
+    let points: Vec<Coord> = numbers.chunks(2).map(<[f64]>::to_vec).collect();
+    (expected == 0 || points.len() == expected).then_some(points)
+}
+
+/// Extended WKB reader: PostGIS' EWKB (SRID and Z/M flags in the type's high
+/// bits) and ISO WKB (Z/M as 1000s of the type).
+struct Ewkb<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+/// Nesting of collections beyond which a value isn't converted
+const MAX_DEPTH: usize = 32;
+
+impl Ewkb<'_> {
+    fn geometry(&mut self, depth: usize) -> Option<(Geometry, Dims)> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let little_endian = match self.take(1)?[0] {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        let raw = self.u32(little_endian)?;
+        let mut dims = Dims {
+            z: raw & 0x8000_0000 != 0,
+            m: raw & 0x4000_0000 != 0,
+        };
+        if raw & 0x2000_0000 != 0 {
+            self.u32(little_endian)?; // SRID
+        }
+        let code = raw & 0x0FFF_FFFF;
+        match code / 1000 {
+            0 => {}
+            1 => dims.z = true,
+            2 => dims.m = true,
+            3 => (dims.z, dims.m) = (true, true),
+            _ => return None,
+        }
+        let read = |r: &mut Self| {
+            let n = r.count(little_endian)?;
+            (0..n)
+                .map(|_| r.coord(little_endian, dims))
+                .collect::<Option<Vec<_>>>()
+        };
+        let rings = |r: &mut Self| {
+            let n = r.count(little_endian)?;
+            (0..n).map(|_| read(r)).collect::<Option<Vec<_>>>()
+        };
+        let geometry = match code % 1000 {
+            1 => {
+                let coord = self.coord(little_endian, dims)?;
+                // PostGIS writes POINT EMPTY as NaN ordinates
+                Geometry::Point(if coord.iter().all(|v| v.is_nan()) {
+                    Vec::new()
+                } else {
+                    coord
+                })
+            }
+            2 => Geometry::LineString(read(self)?),
+            3 => Geometry::Polygon(rings(self)?),
+            kind @ 4..=7 => {
+                let n = self.count(little_endian)?;
+                let parts = (0..n)
+                    .map(|_| self.geometry(depth + 1).map(|(g, _)| g))
+                    .collect::<Option<Vec<_>>>()?;
+                match kind {
+                    4 => Geometry::MultiPoint(
+                        parts
+                            .into_iter()
+                            .map(|g| match g {
+                                Geometry::Point(c) => Some(c),
+                                _ => None,
+                            })
+                            .collect::<Option<_>>()?,
+                    ),
+                    5 => Geometry::MultiLineString(
+                        parts
+                            .into_iter()
+                            .map(|g| match g {
+                                Geometry::LineString(l) => Some(l),
+                                _ => None,
+                            })
+                            .collect::<Option<_>>()?,
+                    ),
+                    6 => Geometry::MultiPolygon(
+                        parts
+                            .into_iter()
+                            .map(|g| match g {
+                                Geometry::Polygon(p) => Some(p),
+                                _ => None,
+                            })
+                            .collect::<Option<_>>()?,
+                    ),
+                    _ => Geometry::Collection(parts),
+                }
+            }
+            // Curves, surfaces, TINs
+            _ => return None,
+        };
+        Some((geometry, dims))
+    }
+
+    fn take(&mut self, n: usize) -> Option<&[u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Option<u32> {
+        let bytes: [u8; 4] = self.take(4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// An element count, bounded by what is left to read
+    fn count(&mut self, little_endian: bool) -> Option<usize> {
+        let n = self.u32(little_endian)? as usize;
+        (n <= self.bytes.len() - self.pos).then_some(n)
+    }
+
+    fn coord(&mut self, little_endian: bool, dims: Dims) -> Option<Coord> {
+        (0..dims.count())
+            .map(|_| {
+                let bytes: [u8; 8] = self.take(8)?.try_into().ok()?;
+                Some(if little_endian {
+                    f64::from_le_bytes(bytes)
+                } else {
+                    f64::from_be_bytes(bytes)
+                })
+            })
+            .collect()
+    }
+}
+
+fn write_wkt(out: &mut String, geometry: &Geometry, dims: Dims) {
+    let (name, empty) = match geometry {
+        Geometry::Point(c) => ("POINT", c.is_empty()),
+        Geometry::LineString(l) => ("LINESTRING", l.is_empty()),
+        Geometry::Polygon(p) => ("POLYGON", p.is_empty()),
+        Geometry::MultiPoint(p) => ("MULTIPOINT", p.is_empty()),
+        Geometry::MultiLineString(l) => ("MULTILINESTRING", l.is_empty()),
+        Geometry::MultiPolygon(p) => ("MULTIPOLYGON", p.is_empty()),
+        Geometry::Collection(g) => ("GEOMETRYCOLLECTION", g.is_empty()),
+    };
+    out.push_str(name);
+    match (dims.z, dims.m) {
+        (true, true) => out.push_str(" ZM"),
+        (true, false) => out.push_str(" Z"),
+        (false, true) => out.push_str(" M"),
+        (false, false) => {}
+    }
+    if empty {
+        out.push_str(" EMPTY");
+        return;
+    }
+    if dims != Dims::default() {
+        out.push(' ');
+    }
+    match geometry {
+        Geometry::Point(c) => wkt_list(out, std::slice::from_ref(c), wkt_coord),
+        Geometry::LineString(l) => wkt_list(out, l, wkt_coord),
+        Geometry::Polygon(p) => wkt_list(out, p, |out, ring| wkt_list(out, ring, wkt_coord)),
+        Geometry::MultiPoint(p) => wkt_list(out, p, |out, c| {
+            wkt_list(out, std::slice::from_ref(c), wkt_coord)
+        }),
+        Geometry::MultiLineString(l) => {
+            wkt_list(out, l, |out, line| wkt_list(out, line, wkt_coord))
+        }
+        Geometry::MultiPolygon(p) => wkt_list(out, p, |out, polygon| {
+            wkt_list(out, polygon, |out, ring| wkt_list(out, ring, wkt_coord))
+        }),
+        Geometry::Collection(g) => wkt_list(out, g, |out, member| write_wkt(out, member, dims)),
+    }
+}
+
+fn wkt_list<T>(out: &mut String, items: &[T], write: impl Fn(&mut String, &T)) {
+    out.push('(');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write(out, item);
+    }
+    out.push(')');
+}
+
+fn wkt_coord(out: &mut String, coord: &Coord) {
+    for (i, v) in coord.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let _ = write!(out, "{}", v);
+    }
+}
+
+/// `None` for ordinates JSON can't hold (NaN, infinities).
+fn write_geojson(out: &mut String, geometry: &Geometry) -> Option<()> {
+    let (kind, member) = match geometry {
+        Geometry::Point(_) => ("Point", "coordinates"),
+        Geometry::LineString(_) => ("LineString", "coordinates"),
+        Geometry::Polygon(_) => ("Polygon", "coordinates"),
+        Geometry::MultiPoint(_) => ("MultiPoint", "coordinates"),
+        Geometry::MultiLineString(_) => ("MultiLineString", "coordinates"),
+        Geometry::MultiPolygon(_) => ("MultiPolygon", "coordinates"),
+        Geometry::Collection(_) => ("GeometryCollection", "geometries"),
+    };
+    let _ = write!(out, r#"{{"type":"{}","{}":"#, kind, member);
+    match geometry {
+        Geometry::Point(c) => json_coord(out, c)?,
+        Geometry::LineString(l) | Geometry::MultiPoint(l) => json_list(out, l, json_coord)?,
+        Geometry::Polygon(p) | Geometry::MultiLineString(p) => {
+            json_list(out, p, |out, ring| json_list(out, ring, json_coord))?
+        }
+        Geometry::MultiPolygon(p) => json_list(out, p, |out, polygon| {
+            json_list(out, polygon, |out, ring| json_list(out, ring, json_coord))
+        })?,
+        Geometry::Collection(g) => json_list(out, g, write_geojson)?,
+    }
+    out.push('}');
+    Some(())
+}
+
+fn json_list<T>(
+    out: &mut String,
+    items: &[T],
+    write: impl Fn(&mut String, &T) -> Option<()>,
+) -> Option<()> {
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write(out, item)?;
+    }
+    out.push(']');
+    Some(())
+}
+
+/// x, y and z; an empty point has no position
+fn json_coord(out: &mut String, coord: &Coord) -> Option<()> {
+    json_list(out, &coord[..coord.len().min(3)], |out, v| {
+        v.is_finite().then(|| {
+            let _ = write!(out, "{}", v);
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wkt(text: &str, oid: u32) -> Option<String> {
+        convert(text, oid, GeometryFormat::Wkt)
+    }
+
+    fn geojson(text: &str, oid: u32) -> serde_json::Value {
+        serde_json::from_str(&convert(text, oid, GeometryFormat::GeoJson).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_types() {
+        use serde_json::json as j;
+
+        assert_eq!(wkt("(1.5,-2)", 600).unwrap(), "POINT(1.5 -2)");
+        assert_eq!(wkt("[(0,0),(3,4)]", 601).unwrap(), "LINESTRING(0 0,3 4)");
+        assert_eq!(
+            wkt("(2,3),(0,1)", 603).unwrap(),
+            "POLYGON((0 1,2 1,2 3,0 3,0 1))"
+        );
+        assert_eq!(wkt("[(0,0),(1,1)]", 602).unwrap(), "LINESTRING(0 0,1 1)");
+        assert_eq!(
+            wkt("((0,0),(1,1))", 602).unwrap(),
+            "LINESTRING(0 0,1 1,0 0)"
+        );
+        assert_eq!(
+            wkt("((0,0),(4,0),(4,4))", 604).unwrap(),
+            "POLYGON((0 0,4 0,4 4,0 0))"
+        );
+        assert_eq!(
+            geojson("(1.5,-2)", 600),
+            j!({"type": "Point", "coordinates": [1.5, -2]})
+        );
+        assert_eq!(
+            geojson("((0,0),(4,0),(4,4))", 604),
+            j!({"type": "Polygon", "coordinates": [[[0, 0], [4, 0], [4, 4], [0, 0]]]})
+        );
+        // line and circle aren't converted; broken text isn't either
+        assert!(!is_geometric(628) && !is_geometric(718));
+        assert_eq!(wkt("(1,2,3)", 600), None);
+        assert_eq!(wkt("(a,b)", 600), None);
+        assert_eq!(convert("(1,NaN)", 600, GeometryFormat::GeoJson), None);
+    }
+
+    #[test]
+    fn test_postgis_ewkb() {
+        use serde_json::json as j;
+
+        pg_types::register(93_001, PgType::Geometry);
+        assert!(is_geometric(93_001));
+
+        // SRID=4326;POINT(1 2), little endian
+        let point = "0101000020E6100000000000000000F03F0000000000000040";
+        assert_eq!(wkt(point, 93_001).unwrap(), "POINT(1 2)");
+        assert_eq!(
+            geojson(point, 93_001),
+            j!({"type": "Point", "coordinates": [1, 2]})
+        );
+        // LINESTRING Z (0 0 1,1 1 2), big endian, no SRID
+        let line = concat!(
+            "0080000002",
+            "00000002",
+            "000000000000000000000000000000003FF0000000000000",
+            "3FF00000000000003FF00000000000004000000000000000"
+        );
+        assert_eq!(wkt(line, 93_001).unwrap(), "LINESTRING Z (0 0 1,1 1 2)");
+        assert_eq!(
+            geojson(line, 93_001),
+            j!({"type": "LineString", "coordinates": [[0, 0, 1], [1, 1, 2]]})
+        );
+        // GEOMETRYCOLLECTION(POINT(1 2),POINT EMPTY)
+        let collection = concat!(
+            "010700000002000000",
+            "0101000000000000000000F03F0000000000000040",
+            "0101000000000000000000F87F000000000000F87F"
+        );
+        assert_eq!(
+            wkt(collection, 93_001).unwrap(),
+            "GEOMETRYCOLLECTION(POINT(1 2),POINT EMPTY)"
+        );
+        // MULTIPOINT(1 2)
+        let multipoint = "0104000000010000000101000000000000000000F03F0000000000000040";
+        assert_eq!(wkt(multipoint, 93_001).unwrap(), "MULTIPOINT((1 2))");
+        // Truncated, trailing bytes, a curve
+        assert_eq!(wkt(&point[..40], 93_001), None);
+        assert_eq!(wkt(&format!("{}00", point), 93_001), None);
+        assert_eq!(wkt("01080000000000000000", 93_001), None);
+    }
+}
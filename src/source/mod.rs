@@ -1,2 +1,8 @@
+pub mod binary;
+pub mod clock;
+pub mod geometry;
 pub mod parser;
+pub mod pg_error;
+pub mod pg_types;
 pub mod postgres;
+pub mod session;
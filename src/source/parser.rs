@@ -1,10 +1,17 @@
 use anyhow::{anyhow, Result};
 use bytes::{Buf, Bytes};
 use memchr::memchr;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use simdutf8::basic::from_utf8;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+
+use crate::source::binary;
 
 /// Wrapper que incluye LSN del WAL para checkpointing
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CdcEvent {
     pub lsn: u64, // LSN del WAL donde ocurrió este evento
     pub message: CdcMessage,
@@ -13,8 +20,12 @@ pub struct CdcEvent {
 /// pgoutput WAL protocol messages. Fields are parsed from the wire format and
 /// stored even if not all are currently consumed — they are part of the spec
 /// and will be used as the feature set expands.
+///
+/// Serializes externally tagged, e.g. `{"Insert":{"relation_id":16384,"tuple":{...}}}`;
+/// `Unknown` becomes the bare string `"Unknown"`. The shape is part of the
+/// public event model, see `docs/event-model.md`.
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CdcMessage {
     Begin {
         final_lsn: u64,
@@ -29,8 +40,8 @@ pub enum CdcMessage {
     },
     Relation {
         id: u32,
-        namespace: String,
-        name: String,
+        namespace: Arc<str>,
+        name: Arc<str>,
         replica_identity: u8,
         columns: Vec<Column>,
     },
@@ -61,10 +72,36 @@ pub enum CdcMessage {
     Unknown,
 }
 
-#[derive(Debug, Clone)]
+/// Messages framing the in-progress transactions pgoutput streams with
+/// protocol version 2 and up (`streaming 'on'`). The messages between a
+/// Start and a Stop belong to transaction `xid` and carry the xid of their
+/// (sub)transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamMessage {
+    Start {
+        xid: u32,
+        first_segment: bool,
+    },
+    Stop,
+    Commit {
+        xid: u32,
+        flags: u8,
+        commit_lsn: u64,
+        end_lsn: u64,
+        timestamp: u64,
+    },
+    /// Abort of the whole transaction when `subxid == xid`, else of one of
+    /// its subtransactions
+    Abort {
+        xid: u32,
+        subxid: u32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Column {
     pub flags: u8,
-    pub name: String,
+    pub name: Arc<str>,
     pub type_id: u32,
     pub type_mod: i32,
 }
@@ -78,7 +115,7 @@ impl Column {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tuple {
     pub cols: Vec<TupleData>,
     /// SIMD-friendly bitmap tracking which columns are TOAST (up to 64 columns).
@@ -141,13 +178,63 @@ impl Iterator for ToastIterator {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TupleData {
     Null,
-    Text(Bytes), // Zero-copy: Holds reference to original buffer if possible (Bytes is RefCounted)
+    // Zero-copy: Holds reference to original buffer if possible (Bytes is RefCounted)
+    #[serde(with = "text_bytes")]
+    Text(Bytes),
+    /// Value in its type's binary send format (`SOURCE_BINARY_TUPLES`)
+    #[serde(with = "hex_bytes")]
+    Binary(Bytes),
     Toast,
 }
 
+/// Serializes pgoutput text-format values as JSON strings instead of byte
+/// arrays. PostgreSQL sends them in the database encoding (UTF-8 in practice);
+/// anything else is replaced lossily. Binary formats (spill files) keep the
+/// bytes as they are.
+mod text_bytes {
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(value);
+        }
+        serializer.serialize_str(&String::from_utf8_lossy(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Bytes::deserialize(deserializer);
+        }
+        String::deserialize(deserializer).map(Bytes::from)
+    }
+}
+
+/// Serializes binary-format values as hex strings in JSON (WAL archive,
+/// remote sink payloads), as bytes in binary formats (spill files).
+mod hex_bytes {
+    use bytes::Bytes;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(value);
+        }
+        serializer.serialize_str(&hex::encode(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Bytes::deserialize(deserializer);
+        }
+        let text = String::deserialize(deserializer)?;
+        hex::decode(text).map(Bytes::from).map_err(D::Error::custom)
+    }
+}
+
 impl TupleData {
     /// Returns the text content as a `&str` for `Text` variants, or `None` otherwise.
     pub fn as_str(&self) -> Option<&str> {
@@ -156,6 +243,92 @@ impl TupleData {
             _ => None,
         }
     }
+
+    /// The value as PostgreSQL prints it, for a column of type `type_id`.
+    /// A binary value without decoder for its type comes out as its bytes in
+    /// hex, `\x...`. `None` for NULLs and unchanged TOAST values.
+    pub fn text(&self, type_id: u32) -> Option<Cow<'_, str>> {
+        match self {
+            TupleData::Text(bytes) => Some(String::from_utf8_lossy(bytes)),
+            TupleData::Binary(bytes) => Some(
+                binary::to_text(type_id, bytes)
+                    .unwrap_or_else(|_| Cow::Owned(format!("\\x{}", hex::encode(bytes)))),
+            ),
+            TupleData::Null | TupleData::Toast => None,
+        }
+    }
+}
+
+/// Returns the shared copy of a table, schema or column name.
+///
+/// Names are interned process-wide, so every Relation message for a table (and
+/// every column called `id`) points at the same allocation, and events built
+/// from the schema cache only bump a reference count. The set only grows with
+/// the names of replicated tables.
+pub fn intern(name: &str) -> Arc<str> {
+    static NAMES: OnceLock<Mutex<HashSet<Arc<str>>>> = OnceLock::new();
+    let mut names = NAMES.get_or_init(Default::default).lock();
+    if let Some(interned) = names.get(name) {
+        return interned.clone();
+    }
+    let interned: Arc<str> = Arc::from(name);
+    names.insert(interned.clone());
+    interned
+}
+
+/// Bounds-checked reads for message bodies. A message cut short (by a proxy,
+/// or a provider that mangles frames) is a parse error instead of a panic in
+/// `Buf::get_*`.
+trait ReadExt {
+    fn read_u8(&mut self) -> Result<u8>;
+    fn read_u16(&mut self) -> Result<u16>;
+    fn read_u32(&mut self) -> Result<u32>;
+    fn read_i32(&mut self) -> Result<i32>;
+    fn read_u64(&mut self) -> Result<u64>;
+    fn read_bytes(&mut self, len: usize) -> Result<Bytes>;
+}
+
+impl ReadExt for Bytes {
+    fn read_u8(&mut self) -> Result<u8> {
+        need(self, 1)?;
+        Ok(self.get_u8())
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        need(self, 2)?;
+        Ok(self.get_u16())
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        need(self, 4)?;
+        Ok(self.get_u32())
+    }
+
+    fn read_i32(&mut self) -> Result<i32> {
+        need(self, 4)?;
+        Ok(self.get_i32())
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        need(self, 8)?;
+        Ok(self.get_u64())
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Bytes> {
+        need(self, len)?;
+        Ok(self.split_to(len))
+    }
+}
+
+fn need(data: &Bytes, len: usize) -> Result<()> {
+    if data.remaining() < len {
+        return Err(anyhow!(
+            "message truncated: {} bytes expected, {} left",
+            len,
+            data.remaining()
+        ));
+    }
+    Ok(())
 }
 
 pub struct PgOutputParser;
@@ -175,6 +348,39 @@ impl PgOutputParser {
         }
     }
 
+    /// Decodes a stream control message; `None` for any other tag.
+    pub fn parse_stream(tag: u8, mut body: Bytes) -> Result<Option<StreamMessage>> {
+        let data = &mut body;
+        let msg = match tag {
+            b'S' => StreamMessage::Start {
+                xid: data.read_u32()?,
+                first_segment: data.read_u8()? == 1,
+            },
+            b'E' => StreamMessage::Stop,
+            b'c' => StreamMessage::Commit {
+                xid: data.read_u32()?,
+                flags: data.read_u8()?,
+                commit_lsn: data.read_u64()?,
+                end_lsn: data.read_u64()?,
+                timestamp: data.read_u64()?,
+            },
+            // Protocol version 4 may append the abort LSN and time
+            b'A' => StreamMessage::Abort {
+                xid: data.read_u32()?,
+                subxid: data.read_u32()?,
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(msg))
+    }
+
+    /// Decodes a message inside a stream block: the xid of its
+    /// (sub)transaction, then the message as outside a block.
+    pub fn parse_streamed(tag: u8, mut body: Bytes) -> Result<(u32, Option<CdcMessage>)> {
+        let xid = body.read_u32()?;
+        Ok((xid, Self::parse(tag, body)?))
+    }
+
     fn parse_begin(data: &mut Bytes) -> Result<Option<CdcMessage>> {
         if data.len() < 20 {
             return Ok(None);
@@ -210,17 +416,17 @@ impl PgOutputParser {
             return Err(anyhow!("EOF in relation"));
         }
         let id = data.get_u32();
-        let namespace = Self::read_string(data)?;
-        let name = Self::read_string(data)?;
-        let replica_identity = data.get_u8();
-        let num_columns = data.get_u16();
+        let namespace = Self::read_name(data)?;
+        let name = Self::read_name(data)?;
+        let replica_identity = data.read_u8()?;
+        let num_columns = data.read_u16()?;
 
         let mut columns = Vec::with_capacity(num_columns as usize);
         for _ in 0..num_columns {
-            let flags = data.get_u8();
-            let name = Self::read_string(data)?;
-            let type_id = data.get_u32();
-            let type_mod = data.get_i32();
+            let flags = data.read_u8()?;
+            let name = Self::read_name(data)?;
+            let type_id = data.read_u32()?;
+            let type_mod = data.read_i32()?;
             columns.push(Column {
                 flags,
                 name,
@@ -239,8 +445,8 @@ impl PgOutputParser {
     }
 
     fn parse_insert(data: &mut Bytes) -> Result<Option<CdcMessage>> {
-        let relation_id = data.get_u32();
-        let char_n = data.get_u8(); // 'N'
+        let relation_id = data.read_u32()?;
+        let char_n = data.read_u8()?; // 'N'
         if char_n != b'N' {
             return Err(anyhow!("Expected 'N'"));
         }
@@ -249,12 +455,12 @@ impl PgOutputParser {
     }
 
     fn parse_update(data: &mut Bytes) -> Result<Option<CdcMessage>> {
-        let relation_id = data.get_u32();
+        let relation_id = data.read_u32()?;
         let mut old_tuple = None;
-        let mut tag = data.get_u8();
+        let mut tag = data.read_u8()?;
         if tag == b'K' || tag == b'O' {
             old_tuple = Some(Self::read_tuple(data)?);
-            tag = data.get_u8();
+            tag = data.read_u8()?;
         }
         if tag != b'N' {
             return Err(anyhow!("Expected 'N'"));
@@ -268,8 +474,8 @@ impl PgOutputParser {
     }
 
     fn parse_delete(data: &mut Bytes) -> Result<Option<CdcMessage>> {
-        let relation_id = data.get_u32();
-        let tag = data.get_u8();
+        let relation_id = data.read_u32()?;
+        let tag = data.read_u8()?;
         let old_tuple = if tag == b'K' || tag == b'O' {
             Some(Self::read_tuple(data)?)
         } else {
@@ -305,9 +511,9 @@ impl PgOutputParser {
     }
 
     fn parse_keepalive(data: &mut Bytes) -> Result<Option<CdcMessage>> {
-        let wal_end = data.get_u64();
-        let timestamp = data.get_u64();
-        let reply_requested = data.get_u8() != 0;
+        let wal_end = data.read_u64()?;
+        let timestamp = data.read_u64()?;
+        let reply_requested = data.read_u8()? != 0;
         Ok(Some(CdcMessage::KeepAlive {
             wal_end,
             timestamp,
@@ -316,6 +522,15 @@ impl PgOutputParser {
     }
 
     fn read_string(data: &mut Bytes) -> Result<String> {
+        Self::read_str(data, |s| s.to_string())
+    }
+
+    /// Reads an identifier and interns it.
+    fn read_name(data: &mut Bytes) -> Result<Arc<str>> {
+        Self::read_str(data, intern)
+    }
+
+    fn read_str<T>(data: &mut Bytes, f: impl FnOnce(&str) -> T) -> Result<T> {
         // Use memchr for SIMD search of null terminator
         let len = match memchr(0, data) {
             Some(i) => i,
@@ -327,16 +542,16 @@ impl PgOutputParser {
 
         // Use simdutf8 for validation (faster than std)
         let s = from_utf8(&bytes)?;
-        Ok(s.to_string())
+        Ok(f(s))
     }
 
     fn read_tuple(data: &mut Bytes) -> Result<Tuple> {
-        let num_cols = data.get_u16();
+        let num_cols = data.read_u16()?;
         let mut cols = Vec::with_capacity(num_cols as usize);
         let mut toast_bitmap: u64 = 0;
 
         for idx in 0..num_cols {
-            let tag = data.get_u8();
+            let tag = data.read_u8()?;
             match tag {
                 b'n' => cols.push(TupleData::Null),
                 b'u' => {
@@ -347,10 +562,14 @@ impl PgOutputParser {
                     }
                 }
                 b't' => {
-                    let len = data.get_u32() as usize;
-                    let val = data.split_to(len); // Zero-copy slice
+                    let len = data.read_u32()? as usize;
+                    let val = data.read_bytes(len)?; // Zero-copy slice
                     cols.push(TupleData::Text(val));
                 }
+                b'b' => {
+                    let len = data.read_u32()? as usize;
+                    cols.push(TupleData::Binary(data.read_bytes(len)?));
+                }
                 _ => return Err(anyhow!("Unknown column tag {}", tag)),
             }
         }
@@ -358,3 +577,154 @@ impl PgOutputParser {
         Ok(Tuple { cols, toast_bitmap })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cdc_message_json_shape() {
+        let msg = CdcMessage::Insert {
+            relation_id: 16384,
+            tuple: Tuple {
+                cols: vec![
+                    TupleData::Text(Bytes::from_static(b"42")),
+                    TupleData::Null,
+                    TupleData::Toast,
+                ],
+                toast_bitmap: 0b100,
+            },
+        };
+
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Insert":{"relation_id":16384,"tuple":{"cols":[{"Text":"42"},"Null","Toast"],"toast_bitmap":4}}}"#
+        );
+
+        let back: CdcMessage = serde_json::from_str(&json).unwrap();
+        match back {
+            CdcMessage::Insert { relation_id, tuple } => {
+                assert_eq!(relation_id, 16384);
+                assert_eq!(tuple.cols[0].as_str(), Some("42"));
+                assert!(tuple.is_toast_column(2));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_binary_tuple_values() {
+        let mut body = Vec::new();
+        body.extend_from_slice(&16384u32.to_be_bytes());
+        body.push(b'N');
+        body.extend_from_slice(&2u16.to_be_bytes());
+        body.push(b'b');
+        body.extend_from_slice(&4u32.to_be_bytes());
+        body.extend_from_slice(&42i32.to_be_bytes());
+        body.push(b't');
+        body.extend_from_slice(&2u32.to_be_bytes());
+        body.extend_from_slice(b"ok");
+
+        let msg = PgOutputParser::parse(b'I', Bytes::from(body))
+            .unwrap()
+            .unwrap();
+        let CdcMessage::Insert { tuple, .. } = &msg else {
+            panic!("unexpected message: {:?}", msg);
+        };
+        assert!(matches!(&tuple.cols[0], TupleData::Binary(b) if b[..] == [0, 0, 0, 42]));
+        assert_eq!(tuple.cols[0].text(23).as_deref(), Some("42"));
+        assert_eq!(tuple.cols[0].text(1186).as_deref(), Some("\\x0000002a"));
+        assert_eq!(tuple.cols[1].text(25).as_deref(), Some("ok"));
+
+        // Hex in JSON, so archived changes read back as they were
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.contains(r#"{"Binary":"0000002a"}"#), "{}", json);
+        let back: CdcMessage = serde_json::from_str(&json).unwrap();
+        let CdcMessage::Insert { tuple, .. } = back else {
+            panic!("unexpected message");
+        };
+        assert_eq!(tuple.cols[0].text(23).as_deref(), Some("42"));
+    }
+
+    fn relation_body(columns: &[&str]) -> Bytes {
+        let mut body = Vec::new();
+        body.extend_from_slice(&16384u32.to_be_bytes());
+        body.extend_from_slice(b"public\0orders\0");
+        body.push(b'd');
+        body.extend_from_slice(&(columns.len() as u16).to_be_bytes());
+        for name in columns {
+            body.push(1);
+            body.extend_from_slice(name.as_bytes());
+            body.push(0);
+            body.extend_from_slice(&23u32.to_be_bytes());
+            body.extend_from_slice(&(-1i32).to_be_bytes());
+        }
+        Bytes::from(body)
+    }
+
+    #[test]
+    fn test_relation_names_are_interned() {
+        let parse = |columns: &[&str]| match PgOutputParser::parse(b'R', relation_body(columns)) {
+            Ok(Some(CdcMessage::Relation {
+                namespace,
+                name,
+                columns,
+                ..
+            })) => (namespace, name, columns),
+            other => panic!("unexpected parse result: {:?}", other),
+        };
+
+        let (ns1, name1, cols1) = parse(&["id", "status"]);
+        let (ns2, name2, cols2) = parse(&["id", "status", "note"]);
+        assert_eq!(&*name1, "orders");
+        assert!(Arc::ptr_eq(&ns1, &ns2));
+        assert!(Arc::ptr_eq(&name1, &name2));
+        assert!(Arc::ptr_eq(&cols1[0].name, &cols2[0].name));
+        assert_eq!(&*cols2[2].name, "note");
+        assert!(Arc::ptr_eq(&intern("status"), &cols1[1].name));
+    }
+
+    #[test]
+    fn test_truncated_messages_are_errors() {
+        let mut update = Vec::new();
+        update.extend_from_slice(&16384u32.to_be_bytes());
+        update.push(b'K');
+        update.extend_from_slice(&2u16.to_be_bytes());
+        update.extend_from_slice(b"t\0\0\0\x0242n");
+        update.push(b'N');
+        update.extend_from_slice(&2u16.to_be_bytes());
+        update.extend_from_slice(b"t\0\0\0\x0243u");
+
+        for (tag, body) in [
+            (b'U', Bytes::from(update)),
+            (b'R', relation_body(&["id", "status"])),
+        ] {
+            assert!(PgOutputParser::parse(tag, body.clone()).unwrap().is_some());
+            // Every cut, including inside a length-prefixed value, is an error
+            for len in 0..body.len() {
+                assert!(
+                    PgOutputParser::parse(tag, body.slice(..len)).is_err(),
+                    "{} cut at {} bytes",
+                    tag as char,
+                    len
+                );
+            }
+        }
+
+        // A value length past the end of the message
+        let mut insert = Vec::new();
+        insert.extend_from_slice(&16384u32.to_be_bytes());
+        insert.extend_from_slice(b"N\0\x01t\xff\xff\xff\xff42");
+        let err = PgOutputParser::parse(b'I', Bytes::from(insert)).unwrap_err();
+        assert!(err.to_string().contains("truncated"));
+    }
+
+    #[test]
+    fn test_unit_variant_json_shape() {
+        assert_eq!(
+            serde_json::to_string(&CdcMessage::Unknown).unwrap(),
+            r#""Unknown""#
+        );
+    }
+}
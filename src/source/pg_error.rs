@@ -0,0 +1,322 @@
+//! Classification of PostgreSQL errors.
+//!
+//! Errors are mapped by SQLSTATE to what the caller should do next: open a
+//! new connection (the server shut down, restarted or dropped us), wait and
+//! retry on the same connection (the server is overloaded or a statement lost
+//! a race), or give up. Each code has its own first delay, doubled per
+//! attempt. Classified errors are counted by code for `/metrics`, like the
+//! sink timeouts.
+
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use tracing::warn;
+
+/// Longest wait between two attempts
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// What to do about an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PgErrorAction {
+    /// The connection is gone: open a new one, then retry
+    Reconnect,
+    /// The connection is fine: wait, then retry the statement
+    Retry,
+    /// Retrying won't help (bad credentials, missing objects, SQL errors...)
+    Fatal,
+}
+
+impl PgErrorAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PgErrorAction::Reconnect => "reconnect",
+            PgErrorAction::Retry => "retry",
+            PgErrorAction::Fatal => "fatal",
+        }
+    }
+}
+
+/// SQLSTATE, condition name, action and first delay of the codes handled
+/// specially. Any other code is fatal.
+const RULES: &[(&str, &str, PgErrorAction, Duration)] = &[
+    // Server shutting down or restarting (failover, restart, crash recovery)
+    (
+        "57P01",
+        "admin_shutdown",
+        PgErrorAction::Reconnect,
+        Duration::from_secs(1),
+    ),
+    (
+        "57P02",
+        "crash_shutdown",
+        PgErrorAction::Reconnect,
+        Duration::from_secs(5),
+    ),
+    (
+        "57P03",
+        "cannot_connect_now",
+        PgErrorAction::Reconnect,
+        Duration::from_secs(5),
+    ),
+    // Connection exceptions
+    (
+        "08000",
+        "connection_exception",
+        PgErrorAction::Reconnect,
+        Duration::from_secs(1),
+    ),
+    (
+        "08003",
+        "connection_does_not_exist",
+        PgErrorAction::Reconnect,
+        Duration::from_secs(1),
+    ),
+    (
+        "08006",
+        "connection_failure",
+        PgErrorAction::Reconnect,
+        Duration::from_secs(1),
+    ),
+    (
+        "08001",
+        "sqlclient_unable_to_establish_sqlconnection",
+        PgErrorAction::Reconnect,
+        Duration::from_secs(2),
+    ),
+    (
+        "08004",
+        "sqlserver_rejected_establishment_of_sqlconnection",
+        PgErrorAction::Reconnect,
+        Duration::from_secs(2),
+    ),
+    // Out of resources: give the server room before trying again
+    (
+        "53300",
+        "too_many_connections",
+        PgErrorAction::Retry,
+        Duration::from_secs(10),
+    ),
+    (
+        "53200",
+        "out_of_memory",
+        PgErrorAction::Retry,
+        Duration::from_secs(5),
+    ),
+    (
+        "53100",
+        "disk_full",
+        PgErrorAction::Retry,
+        Duration::from_secs(30),
+    ),
+    // Lost a race with another transaction
+    (
+        "40001",
+        "serialization_failure",
+        PgErrorAction::Retry,
+        Duration::from_millis(100),
+    ),
+    (
+        "40P01",
+        "deadlock_detected",
+        PgErrorAction::Retry,
+        Duration::from_millis(100),
+    ),
+    (
+        "55P03",
+        "lock_not_available",
+        PgErrorAction::Retry,
+        Duration::from_secs(1),
+    ),
+    // statement_timeout or pg_cancel_backend
+    (
+        "57014",
+        "query_canceled",
+        PgErrorAction::Retry,
+        Duration::from_secs(1),
+    ),
+];
+
+/// Code used for errors without SQLSTATE: I/O errors and closed connections
+const CONNECTION_CODE: &str = "connection";
+
+/// Classified errors since startup or the last `ResetMetrics`, by code
+static ERRORS: Mutex<BTreeMap<String, (PgErrorAction, u64)>> = Mutex::new(BTreeMap::new());
+
+/// How an error was classified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgErrorClass {
+    /// SQLSTATE, `connection` for transport errors or `client` for errors
+    /// raised by the driver itself
+    pub code: String,
+    /// Condition name, empty when the code has no rule
+    pub name: &'static str,
+    pub action: PgErrorAction,
+    /// Wait before the first retry
+    pub delay: Duration,
+}
+
+impl PgErrorClass {
+    pub fn is_retryable(&self) -> bool {
+        self.action != PgErrorAction::Fatal
+    }
+
+    /// Wait before retry number `attempt` (1-based): the code's delay,
+    /// doubled per attempt, at most 30s.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.delay
+            .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+            .min(MAX_DELAY)
+    }
+}
+
+impl fmt::Display for PgErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "{}, {}", self.code, self.action.as_str())
+        } else {
+            write!(f, "{} {}, {}", self.code, self.name, self.action.as_str())
+        }
+    }
+}
+
+/// Classifies `err` without counting it.
+pub fn classify(err: &tokio_postgres::Error) -> PgErrorClass {
+    match err.code() {
+        Some(state) => classify_code(state.code()),
+        None if err.is_closed() || has_io_source(err) => PgErrorClass {
+            code: CONNECTION_CODE.to_string(),
+            name: "",
+            action: PgErrorAction::Reconnect,
+            delay: Duration::from_secs(1),
+        },
+        None => PgErrorClass {
+            code: "client".to_string(),
+            name: "",
+            action: PgErrorAction::Fatal,
+            delay: Duration::ZERO,
+        },
+    }
+}
+
+fn classify_code(code: &str) -> PgErrorClass {
+    match RULES.iter().find(|(rule, ..)| *rule == code) {
+        Some((_, name, action, delay)) => PgErrorClass {
+            code: code.to_string(),
+            name,
+            action: *action,
+            delay: *delay,
+        },
+        None => PgErrorClass {
+            code: code.to_string(),
+            name: "",
+            action: PgErrorAction::Fatal,
+            delay: Duration::ZERO,
+        },
+    }
+}
+
+fn has_io_source(err: &tokio_postgres::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(e) = source {
+        if e.is::<std::io::Error>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Classifies `err` and counts it.
+pub fn record(err: &tokio_postgres::Error) -> PgErrorClass {
+    let class = classify(err);
+    ERRORS
+        .lock()
+        .entry(class.code.clone())
+        .or_insert((class.action, 0))
+        .1 += 1;
+    class
+}
+
+/// The PostgreSQL error `err` wraps, if any.
+pub fn find(err: &anyhow::Error) -> Option<&tokio_postgres::Error> {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<tokio_postgres::Error>())
+}
+
+/// Runs `operation` up to `max_attempts` times, waiting between attempts as
+/// the failing code asks. Fatal errors are returned right away. Callers that
+/// need a new connection on `Reconnect` open it inside `operation`.
+pub async fn retry<T, F, Fut>(
+    what: &str,
+    max_attempts: u32,
+    mut operation: F,
+) -> Result<T, tokio_postgres::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, tokio_postgres::Error>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                let class = record(&e);
+                if !class.is_retryable() || attempt >= max_attempts {
+                    return Err(e);
+                }
+                let delay = class.delay(attempt);
+                warn!(
+                    "{} failed ({}): {}; retrying in {:?} (attempt {}/{})",
+                    what, class, e, delay, attempt, max_attempts
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Classified errors by code, with their action, sorted by code.
+pub fn counts() -> Vec<(String, PgErrorAction, u64)> {
+    ERRORS
+        .lock()
+        .iter()
+        .map(|(code, (action, n))| (code.clone(), *action, *n))
+        .collect()
+}
+
+/// Zeroes the per-code counters (`ResetMetrics`).
+pub fn reset_counts() {
+    for (_, count) in ERRORS.lock().values_mut() {
+        *count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_code() {
+        let shutdown = classify_code("57P01");
+        assert_eq!(shutdown.action, PgErrorAction::Reconnect);
+        assert_eq!(shutdown.to_string(), "57P01 admin_shutdown, reconnect");
+
+        let crowded = classify_code("53300");
+        assert_eq!(crowded.action, PgErrorAction::Retry);
+        assert_eq!(crowded.delay(1), Duration::from_secs(10));
+        assert_eq!(crowded.delay(2), Duration::from_secs(20));
+        assert_eq!(crowded.delay(5), MAX_DELAY);
+
+        assert_eq!(classify_code("08006").action, PgErrorAction::Reconnect);
+        assert_eq!(classify_code("40P01").delay(1), Duration::from_millis(100));
+
+        // Auth failures and SQL errors are not worth retrying
+        assert!(!classify_code("28P01").is_retryable());
+        assert!(!classify_code("42P01").is_retryable());
+        assert_eq!(classify_code("42P01").to_string(), "42P01, fatal");
+    }
+}
@@ -0,0 +1,1030 @@
+//! Array, composite, domain, enum, range and extension types of the source,
+//! by OID.
+//!
+//! pgoutput names a column's type by OID only. Built-in array and range types
+//! are known by their OID; other types (enums, domains, composites, ranges,
+//! arrays of them, extension types, and built-in types missing from the
+//! tables below) are looked up in `pg_type`
+//! during setup and when a Relation message first names them, along with
+//! whatever they are made of, and kept for the life of the process. OIDs
+//! don't change while the type exists, and a type can't be dropped while a
+//! column uses it. Enum labels are those at first sight: adding a label
+//! sends no Relation message.
+//!
+//! Domains convert and map to sink types as their base type. Enums map to
+//! `DataType::Enum`, whose labels sinks may use for dictionary columns.
+//! PostGIS types are converted by `source::geometry`. Extension types are
+//! known by name, since their OIDs differ from one database to the next:
+//! `hstore` values become JSON objects of strings, `citext` and `ltree` ones
+//! stay strings.
+//!
+//! Array values become JSON arrays, nested for multidimensional arrays, and
+//! composite values JSON objects of their attributes, read from
+//! `pg_attribute`. Elements and attributes keep their JSON type: booleans,
+//! integers, floats, `numeric` and `json`/`jsonb` documents stay typed,
+//! arrays and composites nest, anything else is a string. Like enum labels,
+//! attributes are those at first sight: attributes added to the type later
+//! are left out. Range values become `{"lower", "upper", "bounds"}` objects,
+//! whose bounds convert like elements of the range's subtype: `null` when
+//! unbounded, and `"bounds": "empty"` for an empty range.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use tokio_postgres::Client;
+
+use crate::utils::{is_json_number, json_quote_into, normalize_timestamptz, strip_money_symbol};
+
+/// What a type is made of, as far as its values' text is concerned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgType {
+    /// Array of `element`, whose values are separated by `delimiter` (`,`
+    /// for every built-in type but `box`)
+    Array { element: u32, delimiter: char },
+    /// Composite type, with the name and type of each attribute in order
+    Composite { fields: Arc<[(Arc<str>, u32)]> },
+    /// Domain over `base`
+    Domain { base: u32 },
+    /// Enum, with its labels in sort order
+    Enum { labels: Arc<[Arc<str>]> },
+    /// PostGIS `geometry` or `geography`
+    Geometry,
+    /// `hstore`, whose values are objects of strings
+    Hstore,
+    /// Range over `subtype`
+    Range { subtype: u32 },
+    /// `citext`, case-insensitive in PostgreSQL but not in the sink
+    Citext,
+    /// `ltree` label path
+    Ltree,
+    /// Any other type; its values are strings
+    Scalar,
+}
+
+impl PgType {
+    /// Types this one is made of
+    fn parts(&self) -> Vec<u32> {
+        match self {
+            PgType::Array { element, .. } => vec![*element],
+            PgType::Composite { fields } => fields.iter().map(|(_, oid)| *oid).collect(),
+            PgType::Domain { base } => vec![*base],
+            PgType::Range { subtype } => vec![*subtype],
+            PgType::Enum { .. }
+            | PgType::Geometry
+            | PgType::Hstore
+            | PgType::Citext
+            | PgType::Ltree
+            | PgType::Scalar => Vec::new(),
+        }
+    }
+}
+
+/// Types read from `pg_type` since startup
+static TYPES: Mutex<BTreeMap<u32, PgType>> = Mutex::new(BTreeMap::new());
+
+/// Nesting beyond which values are written as strings: composites of
+/// composites of arrays and so on
+const MAX_DEPTH: usize = 16;
+
+/// Element type of a built-in array type.
+fn builtin_array(oid: u32) -> Option<u32> {
+    Some(match oid {
+        1000 => 16,   // bool
+        1001 => 17,   // bytea
+        1002 => 18,   // char
+        1003 => 19,   // name
+        1005 => 21,   // int2
+        1007 => 23,   // int4
+        1009 => 25,   // text
+        1028 => 26,   // oid
+        1011 => 28,   // xid
+        199 => 114,   // json
+        1014 => 1042, // bpchar
+        1015 => 1043, // varchar
+        1016 => 20,   // int8
+        1017 => 600,  // point
+        1018 => 601,  // lseg
+        1019 => 602,  // path
+        1020 => 603,  // box
+        1027 => 604,  // polygon
+        1021 => 700,  // float4
+        1022 => 701,  // float8
+        651 => 650,   // cidr
+        1040 => 829,  // macaddr
+        775 => 774,   // macaddr8
+        1041 => 869,  // inet
+        791 => 790,   // money
+        1182 => 1082, // date
+        1183 => 1083, // time
+        1115 => 1114, // timestamp
+        1185 => 1184, // timestamptz
+        1187 => 1186, // interval
+        1270 => 1266, // timetz
+        1231 => 1700, // numeric
+        1561 => 1560, // bit
+        1563 => 1562, // varbit
+        2951 => 2950, // uuid
+        3807 => 3802, // jsonb
+        4073 => 4072, // jsonpath
+        143 => 142,   // xml
+        3643 => 3614, // tsvector
+        3645 => 3615, // tsquery
+        3905 => 3904, // int4range
+        3907 => 3906, // numrange
+        3909 => 3908, // tsrange
+        3911 => 3910, // tstzrange
+        3913 => 3912, // daterange
+        3927 => 3926, // int8range
+        _ => return None,
+    })
+}
+
+/// Subtype of a built-in range type.
+fn builtin_range(oid: u32) -> Option<u32> {
+    Some(match oid {
+        3904 => 23,   // int4range
+        3906 => 1700, // numrange
+        3908 => 1114, // tsrange
+        3910 => 1184, // tstzrange
+        3912 => 1082, // daterange
+        3926 => 20,   // int8range
+        _ => return None,
+    })
+}
+
+/// The type `oid` is, when it is a built-in array or range or was read from
+/// the catalog.
+pub fn get(oid: u32) -> Option<PgType> {
+    if let Some(element) = builtin_array(oid) {
+        let delimiter = if element == 603 { ';' } else { ',' };
+        return Some(PgType::Array { element, delimiter });
+    }
+    if let Some(subtype) = builtin_range(oid) {
+        return Some(PgType::Range { subtype });
+    }
+    TYPES.lock().get(&oid).cloned()
+}
+
+/// Whether values of `oid` are arrays, converted to JSON arrays.
+pub fn is_array(oid: u32) -> bool {
+    matches!(get(oid), Some(PgType::Array { .. }))
+}
+
+/// Whether values of `oid` are composites, converted to JSON objects.
+pub fn is_composite(oid: u32) -> bool {
+    matches!(get(oid), Some(PgType::Composite { .. }))
+}
+
+/// Whether values of `oid` are `hstore` or ranges, converted to JSON objects.
+pub fn is_hstore_or_range(oid: u32) -> bool {
+    matches!(get(oid), Some(PgType::Hstore | PgType::Range { .. }))
+}
+
+/// Whether `value_json` converts values of `oid`: arrays, composites,
+/// `hstore` and ranges.
+pub fn is_json(oid: u32) -> bool {
+    matches!(
+        get(oid),
+        Some(
+            PgType::Array { .. } | PgType::Composite { .. } | PgType::Hstore | PgType::Range { .. }
+        )
+    )
+}
+
+/// The type a domain is over, through domains over domains; `oid` itself
+/// for any other type.
+pub fn base_type(oid: u32) -> u32 {
+    let types = TYPES.lock();
+    let mut oid = oid;
+    for _ in 0..MAX_DEPTH {
+        match types.get(&oid) {
+            Some(PgType::Domain { base }) => oid = *base,
+            _ => break,
+        }
+    }
+    oid
+}
+
+/// Labels of an enum type (or a domain over one).
+pub fn enum_labels(oid: u32) -> Option<Arc<[Arc<str>]>> {
+    match TYPES.lock().get(&base_type(oid)) {
+        Some(PgType::Enum { labels }) => Some(labels.clone()),
+        _ => None,
+    }
+}
+
+/// Whether `oid` still has to be read from the catalog.
+pub fn needs_lookup(oid: u32) -> bool {
+    oid != 0
+        && builtin_array(oid).is_none()
+        && builtin_range(oid).is_none()
+        && !TYPES.lock().contains_key(&oid)
+}
+
+pub fn register(oid: u32, pg_type: PgType) {
+    TYPES.lock().insert(oid, pg_type);
+}
+
+/// Reads `oids` from `pg_type`, then the types they are made of, and
+/// registers them all. Types that don't exist (anymore) are registered as
+/// scalars, so they aren't looked up again. Returns the types registered.
+pub async fn lookup(client: &Client, oids: &[u32]) -> Result<usize> {
+    let mut pending: Vec<u32> = oids.iter().copied().filter(|o| needs_lookup(*o)).collect();
+    pending.sort_unstable();
+    pending.dedup();
+    let mut registered = 0;
+    for _ in 0..MAX_DEPTH {
+        if pending.is_empty() {
+            break;
+        }
+        let found = read_types(client, &pending).await?;
+        let mut next = Vec::new();
+        for oid in pending {
+            let pg_type = found.get(&oid).cloned().unwrap_or(PgType::Scalar);
+            next.extend(pg_type.parts().into_iter().filter(|o| needs_lookup(*o)));
+            register(oid, pg_type);
+            registered += 1;
+        }
+        next.sort_unstable();
+        next.dedup();
+        pending = next;
+    }
+    Ok(registered)
+}
+
+async fn read_types(client: &Client, oids: &[u32]) -> Result<BTreeMap<u32, PgType>> {
+    // The delimiter of an array's text is its element type's
+    let rows = client
+        .query(
+            "SELECT t.oid, t.typtype::text, t.typcategory::text, t.typelem, \
+                    COALESCE(e.typdelim, t.typdelim)::text, t.typbasetype, t.typrelid, \
+                    t.typname::text, r.rngsubtype \
+             FROM pg_type t \
+             LEFT JOIN pg_type e ON e.oid = t.typelem \
+             LEFT JOIN pg_range r ON r.rngtypid = t.oid \
+             WHERE t.oid = ANY($1::oid[])",
+            &[&oids],
+        )
+        .await
+        .context("Failed to read pg_type")?;
+    let mut types = BTreeMap::new();
+    let mut composites = Vec::new();
+    let mut enums = Vec::new();
+    for row in rows {
+        let oid: u32 = row.get(0);
+        let typtype: String = row.get(1);
+        let category: String = row.get(2);
+        let element: u32 = row.get(3);
+        let delimiter: String = row.get(4);
+        let pg_type = if category == "A" && element != 0 {
+            PgType::Array {
+                element,
+                delimiter: delimiter.chars().next().unwrap_or(','),
+            }
+        } else if typtype == "c" {
+            composites.push((oid, row.get::<_, u32>(6)));
+            continue;
+        } else if typtype == "d" {
+            PgType::Domain { base: row.get(5) }
+        } else if typtype == "e" {
+            enums.push(oid);
+            continue;
+        } else if typtype == "r" {
+            match row.get::<_, Option<u32>>(8) {
+                Some(subtype) => PgType::Range { subtype },
+                None => PgType::Scalar,
+            }
+        } else {
+            // Extension types, by name: their OIDs differ between databases
+            match row.get::<_, &str>(7) {
+                "geometry" | "geography" => PgType::Geometry,
+                "hstore" => PgType::Hstore,
+                "citext" => PgType::Citext,
+                "ltree" => PgType::Ltree,
+                _ => PgType::Scalar,
+            }
+        };
+        types.insert(oid, pg_type);
+    }
+
+    if !composites.is_empty() {
+        let relids: Vec<u32> = composites.iter().map(|(_, relid)| *relid).collect();
+        let rows = client
+            .query(
+                "SELECT attrelid, attname::text, atttypid FROM pg_attribute \
+                 WHERE attrelid = ANY($1::oid[]) AND attnum > 0 AND NOT attisdropped \
+                 ORDER BY attrelid, attnum",
+                &[&relids],
+            )
+            .await
+            .context("Failed to read the attributes of composite types")?;
+        let mut fields: BTreeMap<u32, Vec<(Arc<str>, u32)>> = BTreeMap::new();
+        for row in rows {
+            let name: String = row.get(1);
+            fields
+                .entry(row.get(0))
+                .or_default()
+                .push((name.into(), row.get(2)));
+        }
+        for (oid, relid) in composites {
+            let fields = fields.remove(&relid).unwrap_or_default();
+            types.insert(
+                oid,
+                PgType::Composite {
+                    fields: fields.into(),
+                },
+            );
+        }
+    }
+
+    if !enums.is_empty() {
+        let rows = client
+            .query(
+                "SELECT enumtypid, enumlabel::text FROM pg_enum \
+                 WHERE enumtypid = ANY($1::oid[]) \
+                 ORDER BY enumtypid, enumsortorder",
+                &[&enums],
+            )
+            .await
+            .context("Failed to read enum labels")?;
+        let mut labels: BTreeMap<u32, Vec<Arc<str>>> = BTreeMap::new();
+        for row in rows {
+            let label: String = row.get(1);
+            labels.entry(row.get(0)).or_default().push(label.into());
+        }
+        for oid in enums {
+            let labels = labels.remove(&oid).unwrap_or_default();
+            types.insert(
+                oid,
+                PgType::Enum {
+                    labels: labels.into(),
+                },
+            );
+        }
+    }
+    Ok(types)
+}
+
+/// JSON text of an array, composite, `hstore` or range value of type `oid`.
+/// Text that isn't such a literal becomes a JSON string.
+pub fn value_json(text: &str, oid: u32) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    write_value(&mut out, text, oid, 0);
+    out
+}
+
+/// Writes `text`, a value of type `oid`, as JSON.
+fn write_value(out: &mut String, text: &str, oid: u32, depth: usize) {
+    if depth < MAX_DEPTH {
+        match get(oid) {
+            Some(PgType::Array { element, delimiter }) => {
+                if let Some(items) = parse_array(text, delimiter) {
+                    write_items(out, &items, element, depth);
+                    return;
+                }
+            }
+            Some(PgType::Composite { fields }) => {
+                if let Some(values) = parse_record(text) {
+                    write_record(out, &fields, &values, depth);
+                    return;
+                }
+            }
+            Some(PgType::Hstore) => {
+                if let Some(pairs) = parse_hstore(text) {
+                    write_hstore(out, &pairs);
+                    return;
+                }
+            }
+            Some(PgType::Range { subtype }) => {
+                if let Some(range) = parse_range(text) {
+                    write_range(out, &range, subtype, depth);
+                    return;
+                }
+            }
+            Some(PgType::Domain { base }) => return write_value(out, text, base, depth + 1),
+            Some(
+                PgType::Enum { .. }
+                | PgType::Geometry
+                | PgType::Citext
+                | PgType::Ltree
+                | PgType::Scalar,
+            )
+            | None => {}
+        }
+    }
+    write_scalar(out, text, oid);
+}
+
+fn write_items(out: &mut String, items: &[ArrayItem], element: u32, depth: usize) {
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        match item {
+            ArrayItem::Null => out.push_str("null"),
+            ArrayItem::Value(text) => write_value(out, text, element, depth + 1),
+            ArrayItem::Nested(items) => write_items(out, items, element, depth),
+        }
+    }
+    out.push(']');
+}
+
+fn write_record(
+    out: &mut String,
+    fields: &[(Arc<str>, u32)],
+    values: &[Option<String>],
+    depth: usize,
+) {
+    out.push('{');
+    for (i, ((name, oid), value)) in fields.iter().zip(values).enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        json_quote_into(out, name);
+        out.push(':');
+        match value {
+            Some(text) => write_value(out, text, *oid, depth + 1),
+            None => out.push_str("null"),
+        }
+    }
+    out.push('}');
+}
+
+fn write_hstore(out: &mut String, pairs: &[(String, Option<String>)]) {
+    out.push('{');
+    for (i, (key, value)) in pairs.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        json_quote_into(out, key);
+        out.push(':');
+        match value {
+            Some(value) => json_quote_into(out, value),
+            None => out.push_str("null"),
+        }
+    }
+    out.push('}');
+}
+
+fn write_range(out: &mut String, range: &RangeValue, subtype: u32, depth: usize) {
+    let RangeValue::Bounds {
+        lower,
+        upper,
+        bounds,
+    } = range
+    else {
+        out.push_str(r#"{"lower":null,"upper":null,"bounds":"empty"}"#);
+        return;
+    };
+    for (key, bound) in [(r#"{"lower":"#, lower), (r#","upper":"#, upper)] {
+        out.push_str(key);
+        match bound {
+            Some(text) => write_value(out, text, subtype, depth + 1),
+            None => out.push_str("null"),
+        }
+    }
+    out.push_str(r#","bounds":"#);
+    json_quote_into(out, bounds);
+    out.push('}');
+}
+
+/// A scalar element: typed where JSON has the type, a string otherwise.
+fn write_scalar(out: &mut String, text: &str, oid: u32) {
+    match oid {
+        16 => match text {
+            "t" | "true" => out.push_str("true"),
+            "f" | "false" => out.push_str("false"),
+            _ => json_quote_into(out, text),
+        },
+        // Rust accepts numbers JSON doesn't ("+1", "007", ".5"): those are
+        // written in canonical form
+        21 | 23 | 20 | 26 => match text.parse::<i64>() {
+            Ok(_) if is_json_number(text) => out.push_str(text),
+            Ok(n) => out.push_str(&n.to_string()),
+            Err(_) => json_quote_into(out, text),
+        },
+        700 | 701 => match text.parse::<f64>() {
+            Ok(f) if f.is_finite() && is_json_number(text) => out.push_str(text),
+            Ok(f) if f.is_finite() => out.push_str(&f.to_string()),
+            _ => json_quote_into(out, text),
+        },
+        // Digits as PostgreSQL wrote them; NaN and infinities as strings
+        1700 if is_json_number(text) => out.push_str(text),
+        114 | 3802 if serde_json::from_str::<serde::de::IgnoredAny>(text).is_ok() => {
+            out.push_str(text)
+        }
+        790 => json_quote_into(out, &strip_money_symbol(text)),
+        1184 => json_quote_into(out, &normalize_timestamptz(text)),
+        _ => json_quote_into(out, text),
+    }
+}
+
+/// An element of an array literal
+#[derive(Debug, PartialEq, Eq)]
+enum ArrayItem {
+    Null,
+    Value(String),
+    /// A sub-array of a multidimensional array
+    Nested(Vec<ArrayItem>),
+}
+
+/// Parses an array literal (`{a,"b c",NULL}`, `{{1,2},{3,4}}`, or with
+/// explicit bounds `[0:1]={1,2}`). `None` when `text` isn't one.
+fn parse_array(text: &str, delimiter: char) -> Option<Vec<ArrayItem>> {
+    let text = text.trim();
+    // Bounds only matter to PostgreSQL; JSON arrays start at 0
+    let text = match text.strip_prefix('[') {
+        Some(_) => text.split_once('=')?.1.trim_start(),
+        None => text,
+    };
+    let mut chars = text.strip_prefix('{')?.chars().peekable();
+    let items = parse_level(&mut chars, delimiter)?;
+    chars.all(char::is_whitespace).then_some(items)
+}
+
+/// Parses the items of one level, after its `{`, up to and including its `}`.
+fn parse_level(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    delimiter: char,
+) -> Option<Vec<ArrayItem>> {
+    let mut items = Vec::new();
+    skip_whitespace(chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return Some(items);
+    }
+    loop {
+        skip_whitespace(chars);
+        let item = match chars.peek()? {
+            '{' => {
+                chars.next();
+                ArrayItem::Nested(parse_level(chars, delimiter)?)
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next()? {
+                        '\\' => value.push(chars.next()?),
+                        '"' => break,
+                        c => value.push(c),
+                    }
+                }
+                ArrayItem::Value(value)
+            }
+            _ => {
+                let mut value = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == delimiter || c == '}' {
+                        break;
+                    }
+                    chars.next();
+                    if c == '\\' {
+                        value.push(chars.next()?);
+                    } else {
+                        value.push(c);
+                    }
+                }
+                let value = value.trim_end();
+                if value.eq_ignore_ascii_case("NULL") {
+                    ArrayItem::Null
+                } else {
+                    ArrayItem::Value(value.to_string())
+                }
+            }
+        };
+        items.push(item);
+        skip_whitespace(chars);
+        match chars.next()? {
+            '}' => return Some(items),
+            c if c == delimiter => {}
+            _ => return None,
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// Parses a composite literal (`(1,"a b",)`) into its attributes, `None`
+/// for NULL ones. `None` when `text` isn't one.
+fn parse_record(text: &str) -> Option<Vec<Option<String>>> {
+    let inner = text.trim().strip_prefix('(')?.strip_suffix(')')?;
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // A doubled quote inside quotes is a quote
+            '"' if in_quotes && chars.next_if_eq(&'"').is_some() => current.push('"'),
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            '\\' => current.push(chars.next()?),
+            ',' if !in_quotes => {
+                let value = std::mem::take(&mut current);
+                values.push((quoted || !value.is_empty()).then_some(value));
+                quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if in_quotes {
+        return None;
+    }
+    values.push((quoted || !current.is_empty()).then_some(current));
+    Some(values)
+}
+
+/// Parses an `hstore` literal (`"a"=>"1", "b"=>NULL`) into its pairs, in
+/// order. `None` when `text` isn't one.
+fn parse_hstore(text: &str) -> Option<Vec<(String, Option<String>)>> {
+    let mut pairs = Vec::new();
+    let mut chars = text.chars().peekable();
+    skip_whitespace(&mut chars);
+    if chars.peek().is_none() {
+        return Some(pairs);
+    }
+    loop {
+        let (key, _) = parse_hstore_token(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next()? != '=' || chars.next()? != '>' {
+            return None;
+        }
+        let (value, quoted) = parse_hstore_token(&mut chars)?;
+        let value = (quoted || !value.eq_ignore_ascii_case("NULL")).then_some(value);
+        pairs.push((key, value));
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => return Some(pairs),
+            Some(',') => {}
+            Some(_) => return None,
+        }
+    }
+}
+
+/// A key or value of an `hstore` literal, and whether it was quoted.
+fn parse_hstore_token(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<(String, bool)> {
+    skip_whitespace(chars);
+    let mut token = String::new();
+    if chars.next_if_eq(&'"').is_some() {
+        loop {
+            match chars.next()? {
+                '\\' => token.push(chars.next()?),
+                '"' => return Some((token, true)),
+                c => token.push(c),
+            }
+        }
+    }
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == ',' || c == '=' {
+            break;
+        }
+        chars.next();
+        token.push(if c == '\\' { chars.next()? } else { c });
+    }
+    (!token.is_empty()).then_some((token, false))
+}
+
+/// A range literal
+#[derive(Debug, PartialEq, Eq)]
+enum RangeValue {
+    Empty,
+    /// `None` for an unbounded side; `bounds` is how the range's text
+    /// starts and ends, `[)` for example
+    Bounds {
+        lower: Option<String>,
+        upper: Option<String>,
+        bounds: String,
+    },
+}
+
+/// Parses a range literal (`[1,10)`, `(,"2024-01-01 00:00:00"]`, `empty`).
+/// `None` when `text` isn't one.
+fn parse_range(text: &str) -> Option<RangeValue> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("empty") {
+        return Some(RangeValue::Empty);
+    }
+    let open = text.chars().next().filter(|c| matches!(c, '[' | '('))?;
+    let close = text.chars().last().filter(|c| matches!(c, ']' | ')'))?;
+    let inner = &text[1..text.len() - 1];
+
+    let mut bounds: Vec<Option<String>> = Vec::with_capacity(2);
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // A doubled quote inside quotes is a quote
+            '"' if in_quotes && chars.next_if_eq(&'"').is_some() => current.push('"'),
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            '\\' => current.push(chars.next()?),
+            ',' if !in_quotes => {
+                let bound = std::mem::take(&mut current);
+                bounds.push((quoted || !bound.is_empty()).then_some(bound));
+                quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if in_quotes || bounds.len() != 1 {
+        return None;
+    }
+    bounds.push((quoted || !current.is_empty()).then_some(current));
+    let upper = bounds.pop()?;
+    let lower = bounds.pop()?;
+    Some(RangeValue::Bounds {
+        lower,
+        upper,
+        bounds: format!("{}{}", open, close),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(text: &str, oid: u32) -> serde_json::Value {
+        let out = value_json(text, oid);
+        serde_json::from_str(&out).unwrap_or_else(|e| panic!("{}: {}", out, e))
+    }
+
+    #[test]
+    fn test_arrays_of_int() {
+        assert_eq!(value_json("{1,2,3}", 1016), "[1,2,3]");
+        assert_eq!(value_json("{-10,0,42}", 1016), "[-10,0,42]");
+        assert_eq!(value_json("{}", 1016), "[]");
+        assert_eq!(value_json("{NULL,1,2}", 1016), "[null,1,2]");
+    }
+
+    #[test]
+    fn test_arrays_of_float() {
+        assert_eq!(value_json("{1.5,2.3,3.0}", 1022), "[1.5,2.3,3.0]");
+        assert_eq!(value_json("{NaN}", 1022), "[\"NaN\"]");
+        assert_eq!(value_json("{NULL,1.0}", 1022), "[null,1.0]");
+        assert_eq!(value_json("{1e-3,-0.5}", 1022), "[1e-3,-0.5]");
+        // Not JSON syntax as is
+        assert_eq!(value_json("{.5,5.,+2}", 1022), "[0.5,5,2]");
+        assert_eq!(value_json("{+5,007}", 1016), "[5,7]");
+        assert_eq!(
+            value_json("{infinity,1e999}", 1022),
+            "[\"infinity\",\"1e999\"]"
+        );
+    }
+
+    #[test]
+    fn test_arrays_of_text() {
+        assert_eq!(value_json("{hello,world}", 1009), "[\"hello\",\"world\"]");
+        assert_eq!(value_json("{}", 1009), "[]");
+        assert_eq!(value_json("{NULL}", 1009), "[null]");
+        // Quoted elements are strings, even "NULL" and ""
+        assert_eq!(
+            value_json(r#"{"NULL",null,""}"#, 1009),
+            r#"["NULL",null,""]"#
+        );
+        assert_eq!(value_json(r#"{""}"#, 1009), r#"[""]"#);
+        assert_eq!(
+            value_json(r#"{"a,b","c\"d","e\\f"}"#, 1009),
+            r#"["a,b","c\"d","e\\f"]"#
+        );
+        // Not an array: one JSON string, control characters escaped
+        assert_eq!(value_json("a\"b\nc", 1009), "\"a\\\"b\\nc\"");
+    }
+
+    #[test]
+    fn test_builtin_arrays() {
+        use serde_json::json as j;
+
+        assert_eq!(json("{t,f,NULL}", 1000), j!([true, false, null]));
+        assert_eq!(json("{1,-2,007}", 1007), j!([1, -2, 7]));
+        assert_eq!(value_json("{1.5,NaN}", 1022), "[1.5,\"NaN\"]");
+        assert_eq!(
+            value_json("{3.14159265358979323846,NaN}", 1231),
+            "[3.14159265358979323846,\"NaN\"]"
+        );
+        assert_eq!(
+            json("{a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11}", 2951),
+            j!(["a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"])
+        );
+        assert_eq!(
+            json(r#"{"2024-03-01 12:00:00+02"}"#, 1185),
+            j!(["2024-03-01 10:00:00"])
+        );
+        assert_eq!(
+            json(r#"{"{\"a\": [1, 2]}","not json",NULL}"#, 3807),
+            j!([{"a": [1, 2]}, "not json", null])
+        );
+        assert_eq!(
+            json("{(0,0),(1,1);(2,2),(3,3)}", 1020),
+            j!(["(0,0),(1,1)", "(2,2),(3,3)"])
+        );
+        // Not an array literal
+        assert_eq!(json("oops", 1007), j!("oops"));
+        assert_eq!(json("{1,2", 1007), j!("{1,2"));
+    }
+
+    #[test]
+    fn test_multidimensional_arrays() {
+        use serde_json::json as j;
+
+        assert_eq!(json("{{1,2},{3,4}}", 1007), j!([[1, 2], [3, 4]]));
+        assert_eq!(
+            json(r#"{{{"a",NULL}},{{b,c}}}"#, 1009),
+            j!([[["a", null]], [["b", "c"]]])
+        );
+        assert_eq!(json("[0:1]={7,8}", 1016), j!([7, 8]));
+        assert_eq!(json("[1:1][2:3]={{t,f}}", 1000), j!([[true, false]]));
+    }
+
+    #[test]
+    fn test_catalog_types() {
+        use serde_json::json as j;
+
+        // An enum, a domain over numeric, a composite with an array attribute
+        register(
+            90_001,
+            PgType::Enum {
+                labels: vec![Arc::from("happy"), Arc::from("sad")].into(),
+            },
+        );
+        register(
+            90_002,
+            PgType::Array {
+                element: 90_001,
+                delimiter: ',',
+            },
+        );
+        register(90_003, PgType::Domain { base: 1700 });
+        register(
+            90_004,
+            PgType::Array {
+                element: 90_003,
+                delimiter: ',',
+            },
+        );
+        register(
+            90_005,
+            PgType::Composite {
+                fields: vec![
+                    (Arc::from("id"), 23),
+                    (Arc::from("label"), 25),
+                    (Arc::from("tags"), 1009),
+                    (Arc::from("price"), 90_003),
+                ]
+                .into(),
+            },
+        );
+        register(
+            90_006,
+            PgType::Array {
+                element: 90_005,
+                delimiter: ',',
+            },
+        );
+        assert!(!needs_lookup(90_006));
+        assert!(needs_lookup(90_007));
+        assert!(!needs_lookup(1000));
+
+        assert_eq!(json("{happy,sad}", 90_002), j!(["happy", "sad"]));
+        assert_eq!(json("{1.50,NULL}", 90_004), j!([1.50, null]));
+        assert_eq!(
+            json(
+                r#"{"(1,\"a \"\"b\"\"\",\"{x,y}\",2.5)","(2,,{},)",NULL}"#,
+                90_006
+            ),
+            j!([
+                {"id": 1, "label": "a \"b\"", "tags": ["x", "y"], "price": 2.5},
+                {"id": 2, "label": null, "tags": [], "price": null},
+                null
+            ])
+        );
+        assert!(is_array(90_006));
+        assert!(!is_array(90_005));
+        assert!(is_composite(90_005));
+
+        // A composite column on its own, with a NULL attribute
+        assert_eq!(
+            json(r#"(7,"x y",{},)"#, 90_005),
+            j!({"id": 7, "label": "x y", "tags": [], "price": null})
+        );
+        // Composites in composites
+        register(
+            90_010,
+            PgType::Composite {
+                fields: vec![(Arc::from("item"), 90_005), (Arc::from("qty"), 21)].into(),
+            },
+        );
+        assert_eq!(
+            json(r#"("(1,a,{b},0.5)",3)"#, 90_010),
+            j!({"item": {"id": 1, "label": "a", "tags": ["b"], "price": 0.5}, "qty": 3})
+        );
+
+        // Domains over domains resolve to the base type
+        register(90_008, PgType::Domain { base: 90_001 });
+        register(90_009, PgType::Domain { base: 90_008 });
+        assert_eq!(base_type(90_009), 90_001);
+        assert_eq!(base_type(90_003), 1700);
+        assert_eq!(base_type(23), 23);
+        assert_eq!(
+            enum_labels(90_009).as_deref(),
+            Some(&[Arc::from("happy"), Arc::from("sad")][..])
+        );
+        assert_eq!(enum_labels(90_003), None);
+    }
+
+    #[test]
+    fn test_hstore() {
+        use serde_json::json as j;
+
+        register(90_011, PgType::Hstore);
+        assert_eq!(
+            json(r#""a"=>"1", "b c"=>"x\"y", "n"=>NULL, "s"=>"NULL""#, 90_011),
+            j!({"a": "1", "b c": "x\"y", "n": null, "s": "NULL"})
+        );
+        assert_eq!(json("", 90_011), j!({}));
+        // Unquoted, as hstore input accepts
+        assert_eq!(json("k => v,z=>null", 90_011), j!({"k": "v", "z": null}));
+        // Not an hstore literal
+        assert_eq!(json(r#""a"=>"#, 90_011), j!(r#""a"=>"#));
+        assert_eq!(json(r#""a" "b""#, 90_011), j!(r#""a" "b""#));
+
+        // Arrays of hstore
+        register(
+            90_012,
+            PgType::Array {
+                element: 90_011,
+                delimiter: ',',
+            },
+        );
+        assert_eq!(
+            json(r#"{"\"a\"=>\"1\"",NULL}"#, 90_012),
+            j!([{"a": "1"}, null])
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        use serde_json::json as j;
+
+        assert_eq!(
+            json("[1,10)", 3904),
+            j!({"lower": 1, "upper": 10, "bounds": "[)"})
+        );
+        assert_eq!(
+            json("(,3.5]", 3906),
+            j!({"lower": null, "upper": 3.5, "bounds": "(]"})
+        );
+        assert_eq!(
+            json(
+                r#"["2024-03-01 12:00:00+02","2024-03-02 00:00:00+00")"#,
+                3910
+            ),
+            j!({"lower": "2024-03-01 10:00:00", "upper": "2024-03-02 00:00:00", "bounds": "[)"})
+        );
+        assert_eq!(
+            json("[2024-01-01,infinity)", 3912),
+            j!({"lower": "2024-01-01", "upper": "infinity", "bounds": "[)"})
+        );
+        assert_eq!(
+            json("empty", 3926),
+            j!({"lower": null, "upper": null, "bounds": "empty"})
+        );
+        // A range of a type read from the catalog, with a quote in a bound
+        register(90_013, PgType::Range { subtype: 25 });
+        assert_eq!(
+            json(r#"["a ""b""",c\,d]"#, 90_013),
+            j!({"lower": "a \"b\"", "upper": "c,d", "bounds": "[]"})
+        );
+        assert!(!needs_lookup(3912));
+        assert!(is_json(3912) && is_hstore_or_range(3912));
+        assert!(!is_json(25));
+        // Not a range literal
+        assert_eq!(json("[1,2,3)", 3904), j!("[1,2,3)"));
+        assert_eq!(json("1,2", 3904), j!("1,2"));
+    }
+
+    #[test]
+    fn test_parse_record() {
+        assert_eq!(
+            parse_record(r#"(1,"",,"a,b")"#),
+            Some(vec![
+                Some("1".to_string()),
+                Some(String::new()),
+                None,
+                Some("a,b".to_string())
+            ])
+        );
+        assert_eq!(parse_record("1,2"), None);
+    }
+}
@@ -1,22 +1,17 @@
 use anyhow::{Context, Result};
 use bytes::{BufMut, Bytes, BytesMut};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio_postgres::{Client, Config, CopyBothDuplex, NoTls};
-use tracing::{error, info, warn};
+use tokio_postgres::error::SqlState;
+use tokio_postgres::{Client, CopyBothDuplex, SimpleQueryMessage};
+use tracing::{info, warn};
 
-use crate::utils::validate_sql_identifier;
+use super::clock::{self, PG_EPOCH_OFFSET_USEC};
+use super::session::PgSession;
+use crate::utils::{parse_pg_lsn, quote_ident, validate_sql_identifier};
 
-/// PostgreSQL epoch: 2000-01-01 00:00:00 UTC
-/// Difference from Unix epoch in microseconds
-const PG_EPOCH_OFFSET_USEC: i64 = 946_684_800_000_000;
-
-/// Generates timestamp in PostgreSQL format (microseconds since 2000-01-01)
+/// Generates timestamp in PostgreSQL format (microseconds since 2000-01-01),
+/// corrected for clock skew with `CLOCK_SKEW_CORRECTION`
 pub fn pg_timestamp() -> i64 {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-    let usec = now.as_micros() as i64;
-    usec - PG_EPOCH_OFFSET_USEC
+    clock::now_unix_us() - PG_EPOCH_OFFSET_USEC
 }
 
 /// Builds StandbyStatusUpdate message to confirm LSN to PostgreSQL
@@ -39,28 +34,61 @@ pub fn build_standby_status_update(lsn: u64) -> Bytes {
     buf.freeze()
 }
 
+/// `START_REPLICATION` was refused because another connection is already
+/// streaming from the slot (SQLSTATE 55006, "replication slot is active for PID")
+#[derive(Debug)]
+pub struct SlotInUseError {
+    pub slot_name: String,
+    /// Server message, which names the PID holding the slot
+    pub message: String,
+}
+
+impl std::fmt::Display for SlotInUseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "replication slot {} is in use by another connection: {}",
+            self.slot_name, self.message
+        )
+    }
+}
+
+impl std::error::Error for SlotInUseError {}
+
+/// Snapshot exported by `CREATE_REPLICATION_SLOT ... EXPORT_SNAPSHOT`. It
+/// shows the database exactly as of `consistent_point`, where the slot's
+/// stream begins, and stays importable (`SET TRANSACTION SNAPSHOT`) until the
+/// replication connection runs its next command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedSnapshot {
+    pub name: String,
+    pub consistent_point: u64,
+}
+
 pub struct PostgresSource {
     client: Client,
+    /// Settings the replication and helper connections are opened with
+    session: PgSession,
     slot_name: String,
     publication_name: String,
+    /// pgoutput `proto_version`; 2 and up stream in-progress transactions
+    protocol_version: u32,
+    /// Ask for values in their binary format (`binary 'true'`)
+    binary_tuples: bool,
 }
 
 impl PostgresSource {
-    pub async fn new(pg_config: &str, slot_name: String, publication_name: String) -> Result<Self> {
-        // Clean URL of replication parameters if they exist
-        let clean_url = pg_config
-            .replace("?replication=database", "")
-            .replace("&replication=database", "")
-            .replace("replication=database&", "");
+    pub async fn new(
+        session: &PgSession,
+        pg_config: &str,
+        slot_name: String,
+        publication_name: String,
+    ) -> Result<Self> {
+        let clean_url = strip_replication(pg_config);
 
         // Step 1: Create replication slot on normal connection (without replication mode)
         {
-            let (slot_client, slot_connection) = tokio_postgres::connect(&clean_url, NoTls).await?;
-            let slot_handle = tokio::spawn(async move {
-                if let Err(e) = slot_connection.await {
-                    error!("Slot connection error: {}", e);
-                }
-            });
+            let slot_client = session.connect_helper(&clean_url, "Slot").await?;
 
             // Validate slot name before using in SQL
             validate_sql_identifier(&slot_name).context("invalid replication slot name")?;
@@ -74,30 +102,112 @@ impl PostgresSource {
                 .await; // Ignore errors (slot may already exist)
 
             drop(slot_client);
-            let _ = slot_handle.await;
         }
 
         // Step 2: Create replication connection
-        let mut config: Config = clean_url.parse()?;
+        Self::connect(session, &clean_url, slot_name, publication_name).await
+    }
+
+    /// Opens the replication connection and creates the slot on it with an
+    /// exported snapshot. Returns no snapshot when the slot already exists:
+    /// its stream started before now, so there is nothing consistent to copy.
+    pub async fn new_exporting_snapshot(
+        session: &PgSession,
+        pg_config: &str,
+        slot_name: String,
+        publication_name: String,
+    ) -> Result<(Self, Option<ExportedSnapshot>)> {
+        validate_sql_identifier(&slot_name).context("invalid replication slot name")?;
+        let source = Self::connect(
+            session,
+            &strip_replication(pg_config),
+            slot_name,
+            publication_name,
+        )
+        .await?;
+
+        let query = format!(
+            "CREATE_REPLICATION_SLOT {} LOGICAL pgoutput EXPORT_SNAPSHOT",
+            source.slot_name
+        );
+        let messages = match source.client.simple_query(&query).await {
+            Ok(messages) => messages,
+            Err(e) if e.code() == Some(&SqlState::DUPLICATE_OBJECT) => {
+                info!(
+                    "Replication slot {} already exists, no snapshot exported",
+                    source.slot_name
+                );
+                return Ok((source, None));
+            }
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context(format!(
+                    "failed to create replication slot {}",
+                    source.slot_name
+                )))
+            }
+        };
+        let row = messages
+            .into_iter()
+            .find_map(|m| match m {
+                SimpleQueryMessage::Row(row) => Some(row),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("CREATE_REPLICATION_SLOT returned no row"))?;
+        let consistent_point = row.get(1).unwrap_or_default();
+        let snapshot = ExportedSnapshot {
+            consistent_point: parse_pg_lsn(consistent_point).ok_or_else(|| {
+                anyhow::anyhow!("failed to parse consistent point '{}'", consistent_point)
+            })?,
+            name: row
+                .get(2)
+                .ok_or_else(|| anyhow::anyhow!("CREATE_REPLICATION_SLOT exported no snapshot"))?
+                .to_string(),
+        };
+        info!(
+            "Replication slot {} created at {} with snapshot {}",
+            source.slot_name, consistent_point, snapshot.name
+        );
+        Ok((source, Some(snapshot)))
+    }
+
+    async fn connect(
+        session: &PgSession,
+        clean_url: &str,
+        slot_name: String,
+        publication_name: String,
+    ) -> Result<Self> {
+        let mut config = session.connection_config(clean_url)?;
 
         // The Materialize fork has this method
         config.replication_mode(tokio_postgres::config::ReplicationMode::Logical);
 
-        let (client, connection) = config.connect(NoTls).await?;
-
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                error!("Replication connection error: {}", e);
-            }
-        });
+        let client = session.connect(&config, "Replication").await?;
 
         Ok(Self {
             client,
+            session: session.clone(),
             slot_name,
             publication_name,
+            protocol_version: 1,
+            binary_tuples: false,
         })
     }
 
+    /// pgoutput protocol version to request (`PGOUTPUT_PROTOCOL_VERSION`).
+    /// From 2 on, in-progress transactions are streamed (`streaming 'on'`);
+    /// version 2 needs PostgreSQL 14, 3 needs 15 and 4 needs 16.
+    pub fn with_protocol_version(mut self, protocol_version: u32) -> Self {
+        self.protocol_version = protocol_version;
+        self
+    }
+
+    /// Stream values in their binary send format (`SOURCE_BINARY_TUPLES`),
+    /// PostgreSQL 14 and up.
+    pub fn with_binary_tuples(mut self, binary_tuples: bool) -> Self {
+        self.binary_tuples = binary_tuples;
+        self
+    }
+
     #[allow(dead_code)]
     pub async fn start_replication(&self) -> Result<CopyBothDuplex<Bytes>> {
         self.start_replication_from(0).await
@@ -113,22 +223,45 @@ impl PostgresSource {
 
         // Validate identifiers before interpolating into SQL
         validate_sql_identifier(&self.slot_name).context("invalid replication slot name")?;
-        validate_sql_identifier(&self.publication_name).context("invalid publication name")?;
+        // Several publications are comma-separated; the slot streams the
+        // tables of all of them
+        for publication in self.publication_name.split(',') {
+            validate_sql_identifier(publication).context("invalid publication name")?;
+        }
 
+        let streaming = if self.protocol_version >= 2 {
+            ", streaming 'on'"
+        } else {
+            ""
+        };
+        let binary = if self.binary_tuples {
+            ", binary 'true'"
+        } else {
+            ""
+        };
         let query = format!(
-            "START_REPLICATION SLOT {} LOGICAL {} (proto_version '1', publication_names '{}')",
-            self.slot_name, lsn_str, self.publication_name
+            "START_REPLICATION SLOT {} LOGICAL {} (proto_version '{}'{}{}, publication_names '{}')",
+            self.slot_name,
+            lsn_str,
+            self.protocol_version,
+            streaming,
+            binary,
+            self.publication_name
         );
 
         info!("Starting replication from LSN: {}", lsn_str);
 
-        let stream = self
-            .client
-            .copy_both_simple(&query)
-            .await
-            .context("Failed to start replication")?;
-
-        Ok(stream)
+        match self.client.copy_both_simple(&query).await {
+            Ok(stream) => Ok(stream),
+            Err(e) if e.code() == Some(&SqlState::OBJECT_IN_USE) => Err(SlotInUseError {
+                slot_name: self.slot_name.clone(),
+                message: e
+                    .as_db_error()
+                    .map_or_else(|| e.to_string(), |db| db.message().to_string()),
+            }
+            .into()),
+            Err(e) => Err(anyhow::Error::new(e).context("Failed to start replication")),
+        }
     }
 
     /// Validates that tables have REPLICA IDENTITY FULL
@@ -138,33 +271,30 @@ impl PostgresSource {
     ///
     /// With REPLICA IDENTITY DEFAULT, only the PK is received in DELETEs, which
     /// is insufficient for partitioned tables.
+    ///
+    /// `tables` are spelled as in the catalog (see `config::parse_table_name`),
+    /// so `UserEvents` is looked up as is rather than folded to lowercase.
     #[allow(dead_code)]
     pub async fn validate_replica_identity(&self, tables: &[String]) -> Result<()> {
         // Create a normal connection (not replication) for queries
         let clean_url = self.clean_url();
-        let (client, connection) = tokio_postgres::connect(&clean_url, NoTls).await?;
-
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                error!("Validation connection error: {}", e);
-            }
-        });
+        let client = self
+            .session
+            .connect_helper(&clean_url, "Validation")
+            .await?;
 
         for table in tables {
             // Parse schema.table if qualified
-            let parts: Vec<&str> = table.split('.').collect();
-            let table_name = if parts.len() > 1 { parts[1] } else { parts[0] };
+            let (schema, table_name) = table.split_once('.').unwrap_or(("public", table));
+            let quoted = quote_ident(table);
 
             let row = client
                 .query_one(
                     "SELECT c.relreplident, c.relname 
                      FROM pg_class c 
                      JOIN pg_namespace n ON c.relnamespace = n.oid 
-                     WHERE c.relname = $1 AND n.nspname = COALESCE($2, 'public')",
-                    &[
-                        &table_name,
-                        &if parts.len() > 1 { parts[0] } else { "public" },
-                    ],
+                     WHERE c.relname = $1 AND n.nspname = $2",
+                    &[&table_name, &schema],
                 )
                 .await
                 .with_context(|| {
@@ -182,7 +312,7 @@ impl PostgresSource {
                 'd' => {
                     warn!("Table '{}' has REPLICA IDENTITY DEFAULT", relname);
                     warn!("    This may cause issues with soft deletes in StarRocks.");
-                    warn!("    Run: ALTER TABLE {} REPLICA IDENTITY FULL;", table);
+                    warn!("    Run: ALTER TABLE {} REPLICA IDENTITY FULL;", quoted);
                     // Don't fail, just warn - let the user decide
                 }
                 'n' => {
@@ -191,7 +321,7 @@ impl PostgresSource {
                         This is not supported for CDC. \
                         Run: ALTER TABLE {} REPLICA IDENTITY FULL;",
                         relname,
-                        table
+                        quoted
                     ));
                 }
                 'i' => {
@@ -216,10 +346,13 @@ impl PostgresSource {
         // This function assumes PostgresSource was created with a valid URL
         // In a real scenario, you should store the original URL
         // For now, this is a placeholder that would need the URL from env
-        std::env::var("SOURCE_URL")
-            .unwrap_or_default()
-            .replace("?replication=database", "")
-            .replace("&replication=database", "")
-            .replace("replication=database&", "")
+        strip_replication(&std::env::var("SOURCE_URL").unwrap_or_default())
     }
 }
+
+/// `url` without its `replication=database` parameter.
+fn strip_replication(url: &str) -> String {
+    url.replace("?replication=database", "")
+        .replace("&replication=database", "")
+        .replace("replication=database&", "")
+}
@@ -0,0 +1,281 @@
+//! Session parameters of dbmazz's PostgreSQL connections.
+//!
+//! Every connection identifies itself with `application_name` (by default
+//! `dbmazz-<slot>`), so DBAs can find and manage dbmazz in
+//! `pg_stat_activity`. Helper connections (setup, checkpoints, snapshot,
+//! monitoring) also get `statement_timeout` and
+//! `idle_in_transaction_session_timeout`, so a stuck query or lock wait
+//! fails instead of holding the source. The timeouts are set with `SET` after
+//! connecting rather than as startup options, which poolers such as PgBouncer
+//! reject.
+//!
+//! All connections, replication included, use the TLS settings of
+//! `SOURCE_SSL_*` (libpq's `sslmode` semantics): `require` encrypts without
+//! checking the certificate, `verify-ca` checks it against the system CAs and
+//! `SOURCE_SSL_ROOT_CERT`, and `verify-full` also checks the host name. The
+//! HTTP API's setup wizard connects the same way; in setup mode, with no
+//! configuration to read, it uses the defaults. Only the demo mode's
+//! connections to its bundled database are plain.
+//!
+//! The settings live in a [`PgSession`] each engine builds from its own
+//! configuration and passes to everything that connects, so engines
+//! embedded in one process don't share them.
+
+use std::future::Future;
+
+use anyhow::{Context, Result};
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode};
+use postgres_openssl::MakeTlsConnector;
+use tokio_postgres::config::SslMode;
+use tokio_postgres::{Client, Config, NoTls};
+use tracing::error;
+
+use super::pg_error;
+use crate::config::{self, split_ssl_params, PgSessionConfig, PgSslMode, PgTlsConfig};
+
+/// Connection attempts before the error is returned
+const CONNECT_ATTEMPTS: u32 = 4;
+
+/// Session parameters and TLS connector the connections of one engine are
+/// opened with. Built once from `SOURCE_*`/`PG_*` and handed to everything
+/// that connects, so engines embedded in the same process keep their own
+/// settings. Cheap to clone.
+#[derive(Clone, Default)]
+pub struct PgSession {
+    params: PgSessionConfig,
+    /// `None` with `SOURCE_SSL_MODE=disable`
+    tls: Option<MakeTlsConnector>,
+}
+
+impl std::fmt::Debug for PgSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PgSession")
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PgSession {
+    /// Session for `params`. Fails when the certificate or key files can't be
+    /// loaded.
+    pub fn new(params: PgSessionConfig) -> Result<Self> {
+        let tls = tls_connector(&params.tls)?;
+        Ok(Self { params, tls })
+    }
+
+    /// Session of the PostgreSQL source of `config`, the defaults for other
+    /// sources.
+    pub fn from_config(config: &config::Config) -> Result<Self> {
+        match &config.source.postgres {
+            Some(pg) => Self::new(pg.session.clone()),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Connection settings for `url`, with the configured `application_name`
+    /// and TLS mode. Pass them to [`PgSession::connect`].
+    pub fn connection_config(&self, url: &str) -> Result<Config, tokio_postgres::Error> {
+        let (url, _) = split_ssl_params(url);
+        let mut config: Config = url.parse()?;
+        config.application_name(&self.params.application_name);
+        config.ssl_mode(driver_ssl_mode(self.params.tls.mode));
+        Ok(config)
+    }
+
+    /// Opens a connection with `config` over the configured TLS. Retryable
+    /// failures are retried; errors of the open connection are logged under
+    /// `label`. Used as is for the replication connection, which gets no
+    /// timeouts.
+    pub async fn connect(
+        &self,
+        config: &Config,
+        label: &str,
+    ) -> Result<Client, tokio_postgres::Error> {
+        // Ride out restarts, failovers and full connection slots
+        self.open(config, label, CONNECT_ATTEMPTS).await
+    }
+
+    /// Opens a helper (non-replication) connection to `url` and applies the
+    /// session timeouts.
+    pub async fn connect_helper(
+        &self,
+        url: &str,
+        label: &str,
+    ) -> Result<Client, tokio_postgres::Error> {
+        self.helper(url, label, CONNECT_ATTEMPTS).await
+    }
+
+    /// As [`PgSession::connect_helper`], in a single attempt: for checking
+    /// connection details, where a wrong host should fail at once.
+    pub async fn try_connect_helper(
+        &self,
+        url: &str,
+        label: &str,
+    ) -> Result<Client, tokio_postgres::Error> {
+        self.helper(url, label, 1).await
+    }
+
+    async fn helper(
+        &self,
+        url: &str,
+        label: &str,
+        attempts: u32,
+    ) -> Result<Client, tokio_postgres::Error> {
+        let config = self.connection_config(url)?;
+        let client = self.open(&config, label, attempts).await?;
+
+        let statements = session_statements(&self.params);
+        if !statements.is_empty() {
+            client.batch_execute(&statements).await?;
+        }
+        Ok(client)
+    }
+
+    async fn open(
+        &self,
+        config: &Config,
+        label: &str,
+        attempts: u32,
+    ) -> Result<Client, tokio_postgres::Error> {
+        let what = format!("{} connection", label);
+        let label = label.to_string();
+        match &self.tls {
+            Some(tls) => {
+                let (client, connection) =
+                    pg_error::retry(&what, attempts, || config.connect(tls.clone())).await?;
+                spawn_connection(connection, label);
+                Ok(client)
+            }
+            None => {
+                let (client, connection) =
+                    pg_error::retry(&what, attempts, || config.connect(NoTls)).await?;
+                spawn_connection(connection, label);
+                Ok(client)
+            }
+        }
+    }
+}
+
+/// TLS connector for `tls`, `None` when TLS is disabled.
+fn tls_connector(tls: &PgTlsConfig) -> Result<Option<MakeTlsConnector>> {
+    if tls.mode == PgSslMode::Disable {
+        return Ok(None);
+    }
+    // Starts out trusting the system CAs
+    let mut builder = SslConnector::builder(SslMethod::tls_client())?;
+    if tls.mode.verifies_certificate() {
+        builder.set_verify(SslVerifyMode::PEER);
+    } else {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+    if let Some(ca) = &tls.root_cert {
+        builder
+            .set_ca_file(ca)
+            .with_context(|| format!("failed to load SOURCE_SSL_ROOT_CERT {}", ca))?;
+    }
+    if let (Some(cert), Some(key)) = (&tls.cert, &tls.key) {
+        builder
+            .set_certificate_chain_file(cert)
+            .with_context(|| format!("failed to load SOURCE_SSL_CERT {}", cert))?;
+        builder
+            .set_private_key_file(key, SslFiletype::PEM)
+            .with_context(|| format!("failed to load SOURCE_SSL_KEY {}", key))?;
+        builder
+            .check_private_key()
+            .context("SOURCE_SSL_KEY does not match SOURCE_SSL_CERT")?;
+    }
+
+    let mut connector = MakeTlsConnector::new(builder.build());
+    if tls.mode != PgSslMode::VerifyFull {
+        connector.set_callback(|config, _host| {
+            config.set_verify_hostname(false);
+            Ok(())
+        });
+    }
+    Ok(Some(connector))
+}
+
+fn driver_ssl_mode(mode: PgSslMode) -> SslMode {
+    match mode {
+        PgSslMode::Disable => SslMode::Disable,
+        PgSslMode::Prefer => SslMode::Prefer,
+        PgSslMode::Require | PgSslMode::VerifyCa | PgSslMode::VerifyFull => SslMode::Require,
+    }
+}
+
+fn spawn_connection<F>(connection: F, label: String)
+where
+    F: Future<Output = Result<(), tokio_postgres::Error>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            error!("{} connection error: {}", label, e);
+        }
+    });
+}
+
+/// `SET` statements for the timeouts of a helper connection.
+fn session_statements(session: &PgSessionConfig) -> String {
+    [
+        ("statement_timeout", session.statement_timeout),
+        (
+            "idle_in_transaction_session_timeout",
+            session.idle_in_transaction_timeout,
+        ),
+    ]
+    .iter()
+    .filter_map(|(name, timeout)| timeout.map(|t| format!("SET {} = {}", name, t.as_millis())))
+    .collect::<Vec<_>>()
+    .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_session_statements() {
+        let session = PgSessionConfig::for_slot("orders");
+        assert_eq!(
+            session_statements(&session),
+            "SET statement_timeout = 300000; SET idle_in_transaction_session_timeout = 60000"
+        );
+
+        let session = PgSessionConfig {
+            statement_timeout: None,
+            idle_in_transaction_timeout: Some(Duration::from_millis(1500)),
+            ..session
+        };
+        assert_eq!(
+            session_statements(&session),
+            "SET idle_in_transaction_session_timeout = 1500"
+        );
+
+        let session = PgSessionConfig {
+            idle_in_transaction_timeout: None,
+            ..session
+        };
+        assert!(session_statements(&session).is_empty());
+    }
+
+    #[test]
+    fn test_tls_connector() {
+        assert!(tls_connector(&PgTlsConfig::default()).unwrap().is_none());
+
+        let require = PgTlsConfig {
+            mode: PgSslMode::Require,
+            ..PgTlsConfig::default()
+        };
+        assert!(tls_connector(&require).unwrap().is_some());
+        assert_eq!(driver_ssl_mode(PgSslMode::VerifyFull), SslMode::Require);
+
+        let missing_ca = PgTlsConfig {
+            mode: PgSslMode::VerifyCa,
+            root_cert: Some("/nonexistent/ca.pem".to_string()),
+            ..PgTlsConfig::default()
+        };
+        let err = tls_connector(&missing_ca).unwrap_err();
+        assert!(err.to_string().contains("SOURCE_SSL_ROOT_CERT"));
+    }
+}
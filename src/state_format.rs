@@ -0,0 +1,100 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Format versions of the state dbmazz persists.
+//!
+//! Checkpoints (whichever `CHECKPOINT_STORE` holds them), the schema cache
+//! stored with them and WAL archive files are stamped with the version of
+//! their format and the dbmazz version that wrote them. State in an older
+//! format is migrated as it is read. State in a newer format is refused: an
+//! older binary can't know what the newer one changed, and misreading a
+//! checkpoint after a rollback would silently skip or repeat changes.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Format of checkpoints and of the schema cache stored with them.
+///
+/// - 0: not stamped; the schema cache is a bare array of Relation messages
+/// - 1: stamped; the schema cache is `{"format": 1, "relations": [...]}`
+/// - 2: each relation of the schema cache is `{"version", "lsn", "relation"}`,
+///   from the version in effect at the checkpoint on
+pub const CHECKPOINT_FORMAT: u32 = 2;
+
+/// Format of WAL archive files, stamped on their first line.
+///
+/// - 1: a stamp line, then one `{"lsn": ..., "message": ...}` per line
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+/// Version of this binary
+pub const DBMAZZ_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Format version and writer of a piece of persisted state. Both are absent
+/// from state written before formats were versioned, which is format 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatStamp {
+    #[serde(default)]
+    pub format: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written_by: Option<String>,
+}
+
+impl FormatStamp {
+    /// Stamp for state this binary writes in `format`.
+    pub fn current(format: u32) -> Self {
+        Self {
+            format,
+            written_by: Some(DBMAZZ_VERSION.to_string()),
+        }
+    }
+
+    /// Fails when `what` is in a format newer than `supported`, with what to
+    /// do about it.
+    pub fn check(&self, what: &str, supported: u32) -> Result<()> {
+        if self.format <= supported {
+            return Ok(());
+        }
+        anyhow::bail!(
+            "{} is in format {}, written by dbmazz {}, but dbmazz {} reads up to format {}. \
+             Refusing to use it: run dbmazz {} or later again, or, to start over from the \
+             replication slot's position, remove it",
+            what,
+            self.format,
+            self.written_by.as_deref().unwrap_or("(unknown)"),
+            DBMAZZ_VERSION,
+            supported,
+            self.written_by
+                .as_deref()
+                .unwrap_or("(the version that wrote it)"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_newer_format_is_refused() {
+        let unstamped: FormatStamp = serde_json::from_str(r#"{"slot":"s","lsn":1}"#).unwrap();
+        assert_eq!(unstamped, FormatStamp::default());
+        assert!(unstamped.check("Checkpoint", CHECKPOINT_FORMAT).is_ok());
+        assert!(FormatStamp::current(CHECKPOINT_FORMAT)
+            .check("Checkpoint", CHECKPOINT_FORMAT)
+            .is_ok());
+
+        let newer = FormatStamp {
+            format: CHECKPOINT_FORMAT + 1,
+            written_by: Some("9.0.0".to_string()),
+        };
+        let err = newer
+            .check("Checkpoint of slot dbmazz_slot", CHECKPOINT_FORMAT)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with(&format!(
+            "Checkpoint of slot dbmazz_slot is in format {}, written by dbmazz 9.0.0",
+            CHECKPOINT_FORMAT + 1
+        )));
+        assert!(err.contains("run dbmazz 9.0.0 or later again"));
+    }
+}
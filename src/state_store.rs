@@ -2,31 +2,45 @@
 // Licensed under the Elastic License v2.0
 
 use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tokio_postgres::{Client, NoTls};
-use tracing::error;
+use tokio_postgres::Client;
+use tracing::warn;
 
+use crate::checkpoint_store::{CheckpointStamp, CheckpointStore};
+use crate::pipeline::schema_cache::RelationVersion;
+use crate::source::parser::CdcMessage;
+use crate::source::pg_error::{self, PgErrorAction};
+use crate::source::session::PgSession;
+use crate::state_format::{FormatStamp, CHECKPOINT_FORMAT};
+
+/// Attempts to save a checkpoint before the error halts replication
+const CHECKPOINT_ATTEMPTS: u32 = 3;
+
+/// Checkpoint store in the `dbmazz_checkpoints` table of the source
+/// (`CHECKPOINT_STORE=postgres`)
 #[derive(Clone)]
 pub struct StateStore {
     client: Arc<Mutex<Client>>,
+    session: PgSession,
+    /// Non-replication URL, to reconnect after the connection is lost
+    url: String,
+    /// Schema version last written by `save_relations`
+    saved_schema_version: Arc<AtomicU64>,
 }
 
 impl StateStore {
-    pub async fn new(database_url: &str) -> Result<Self> {
+    pub async fn new(session: &PgSession, database_url: &str) -> Result<Self> {
         // Create regular connection (non-replication) for checkpoints
         let clean_url = database_url
             .replace("?replication=database", "")
             .replace("&replication=database", "")
             .replace("replication=database&", "");
 
-        let (client, connection) = tokio_postgres::connect(&clean_url, NoTls).await?;
-
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                error!("StateStore connection error: {}", e);
-            }
-        });
+        let client = session.connect_helper(&clean_url, "StateStore").await?;
 
         // Create checkpoints table
         client
@@ -40,32 +54,288 @@ impl StateStore {
             )
             .await?;
 
+        // Relation messages seen by the pipeline, so DML that arrives after a
+        // restart but before PostgreSQL re-sends them can still be decoded
+        client
+            .execute(
+                "ALTER TABLE dbmazz_checkpoints ADD COLUMN IF NOT EXISTS schema_cache JSONB",
+                &[],
+            )
+            .await?;
+
+        // Format of the row and the dbmazz version that wrote it; NULL in rows
+        // written before formats were versioned
+        client
+            .batch_execute(
+                "ALTER TABLE dbmazz_checkpoints ADD COLUMN IF NOT EXISTS state_format INTEGER;
+                 ALTER TABLE dbmazz_checkpoints ADD COLUMN IF NOT EXISTS written_by TEXT;",
+            )
+            .await?;
+
+        // See CheckpointStamp::slot_follows_checkpoint; NULL in rows written
+        // by older binaries
+        client
+            .execute(
+                "ALTER TABLE dbmazz_checkpoints ADD COLUMN IF NOT EXISTS slot_follows_checkpoint BOOLEAN",
+                &[],
+            )
+            .await?;
+
         Ok(Self {
             client: Arc::new(Mutex::new(client)),
+            session: session.clone(),
+            url: clean_url,
+            saved_schema_version: Arc::new(AtomicU64::new(0)),
         })
     }
+}
 
-    pub async fn save_checkpoint(&self, slot: &str, lsn: u64) -> Result<()> {
+#[async_trait]
+impl CheckpointStore for StateStore {
+    /// Saves the slot's checkpoint. A lost connection is reopened and a
+    /// retryable error retried, up to `CHECKPOINT_ATTEMPTS` attempts.
+    async fn save_checkpoint(&self, slot: &str, lsn: u64) -> Result<()> {
+        let stamp = FormatStamp::current(CHECKPOINT_FORMAT);
+        let format = stamp.format as i32;
+        let mut client = self.client.lock().await;
+        let mut attempt = 1;
+        loop {
+            let err = match client
+                .execute(
+                    "INSERT INTO dbmazz_checkpoints
+                     (slot_name, lsn, state_format, written_by, slot_follows_checkpoint)
+                 VALUES ($1, $2, $3, $4, TRUE)
+                 ON CONFLICT (slot_name) DO UPDATE
+                 SET lsn = $2, state_format = $3, written_by = $4,
+                     slot_follows_checkpoint = TRUE, updated_at = NOW()",
+                    &[&slot, &(lsn as i64), &format, &stamp.written_by],
+                )
+                .await
+            {
+                Ok(_) => return Ok(()),
+                Err(e) => e,
+            };
+            let class = pg_error::record(&err);
+            if !class.is_retryable() || attempt >= CHECKPOINT_ATTEMPTS {
+                return Err(err.into());
+            }
+            let delay = class.delay(attempt);
+            warn!(
+                "Checkpoint save failed ({}): {}; retrying in {:?} (attempt {}/{})",
+                class, err, delay, attempt, CHECKPOINT_ATTEMPTS
+            );
+            tokio::time::sleep(delay).await;
+            if class.action == PgErrorAction::Reconnect || client.is_closed() {
+                *client = self.session.connect_helper(&self.url, "StateStore").await?;
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Refuses a checkpoint written in a newer format.
+    async fn load_checkpoint(&self, slot: &str) -> Result<Option<u64>> {
+        let client = self.client.lock().await;
+        let row = client
+            .query_opt(
+                "SELECT lsn, state_format, written_by FROM dbmazz_checkpoints WHERE slot_name = $1",
+                &[&slot],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let stamp = FormatStamp {
+            format: row.get::<_, Option<i32>>(1).unwrap_or(0) as u32,
+            written_by: row.get(2),
+        };
+        stamp.check(
+            &format!("Checkpoint of slot {} in dbmazz_checkpoints", slot),
+            CHECKPOINT_FORMAT,
+        )?;
+        Ok(Some(row.get::<_, i64>(0) as u64))
+    }
+
+    async fn checkpoint_stamp(&self, slot: &str) -> Result<Option<CheckpointStamp>> {
+        let client = self.client.lock().await;
+        let row = client
+            .query_opt(
+                "SELECT state_format, written_by, slot_follows_checkpoint
+                 FROM dbmazz_checkpoints WHERE slot_name = $1",
+                &[&slot],
+            )
+            .await?;
+        Ok(row.map(|row| CheckpointStamp {
+            stamp: FormatStamp {
+                format: row.get::<_, Option<i32>>(0).unwrap_or(0) as u32,
+                written_by: row.get(1),
+            },
+            slot_follows_checkpoint: row.get::<_, Option<bool>>(2).unwrap_or(false),
+        }))
+    }
+
+    /// Does nothing until the slot has a checkpoint row.
+    async fn save_relations(
+        &self,
+        slot: &str,
+        version: u64,
+        relations: &[RelationVersion],
+    ) -> Result<()> {
+        if relations.is_empty() || self.saved_schema_version.load(Ordering::Relaxed) == version {
+            return Ok(());
+        }
+        let cache = encode_relations(relations)?;
         let client = self.client.lock().await;
         client
             .execute(
-                "INSERT INTO dbmazz_checkpoints (slot_name, lsn) VALUES ($1, $2)
-             ON CONFLICT (slot_name) DO UPDATE SET lsn = $2, updated_at = NOW()",
-                &[&slot, &(lsn as i64)],
+                "UPDATE dbmazz_checkpoints SET schema_cache = $2 WHERE slot_name = $1",
+                &[&slot, &cache],
             )
             .await?;
+        self.saved_schema_version.store(version, Ordering::Relaxed);
         Ok(())
     }
 
-    pub async fn load_checkpoint(&self, slot: &str) -> Result<Option<u64>> {
+    async fn load_relations(&self, slot: &str) -> Result<Vec<RelationVersion>> {
         let client = self.client.lock().await;
         let row = client
             .query_opt(
-                "SELECT lsn FROM dbmazz_checkpoints WHERE slot_name = $1",
+                "SELECT schema_cache FROM dbmazz_checkpoints WHERE slot_name = $1",
                 &[&slot],
             )
             .await?;
+        let Some(cache) = row.and_then(|r| r.get::<_, Option<serde_json::Value>>(0)) else {
+            return Ok(Vec::new());
+        };
+        relations_stamp(&cache).check(
+            &format!("Schema cache of slot {} in dbmazz_checkpoints", slot),
+            CHECKPOINT_FORMAT,
+        )?;
+        match decode_relations(cache) {
+            Ok(relations) => Ok(relations),
+            Err(e) => {
+                warn!("Ignoring unreadable schema cache for slot {}: {}", slot, e);
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+/// The schema cache as stored, from format 1 on
+#[derive(Serialize, Deserialize)]
+struct StoredRelations<T> {
+    #[serde(flatten)]
+    stamp: FormatStamp,
+    relations: Vec<T>,
+}
+
+pub(crate) fn encode_relations(relations: &[RelationVersion]) -> Result<serde_json::Value> {
+    let relations: Vec<&RelationVersion> = relations
+        .iter()
+        .filter(|v| v.relation_id().is_some())
+        .collect();
+    Ok(serde_json::to_value(StoredRelations {
+        stamp: FormatStamp::current(CHECKPOINT_FORMAT),
+        relations,
+    })?)
+}
+
+/// Format of a stored schema cache; a bare array is format 0.
+pub(crate) fn relations_stamp(cache: &serde_json::Value) -> FormatStamp {
+    if cache.is_object() {
+        FormatStamp::deserialize(cache).unwrap_or_default()
+    } else {
+        FormatStamp::default()
+    }
+}
+
+/// Reads a schema cache of format 0 or later; check `relations_stamp` first.
+/// Relations stored before they were versioned are their first version.
+pub(crate) fn decode_relations(cache: serde_json::Value) -> Result<Vec<RelationVersion>> {
+    let relations: Vec<RelationVersion> = if cache.is_array() {
+        serde_json::from_value::<Vec<CdcMessage>>(cache)?
+            .into_iter()
+            .map(RelationVersion::initial)
+            .collect()
+    } else if relations_stamp(&cache).format < 2 {
+        serde_json::from_value::<StoredRelations<CdcMessage>>(cache)?
+            .relations
+            .into_iter()
+            .map(RelationVersion::initial)
+            .collect()
+    } else {
+        serde_json::from_value::<StoredRelations<RelationVersion>>(cache)?.relations
+    };
+    Ok(relations
+        .into_iter()
+        .filter(|v| v.relation_id().is_some())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::Column;
+
+    #[test]
+    fn test_relations_roundtrip() {
+        let relation = CdcMessage::Relation {
+            id: 16384,
+            namespace: Arc::from("public"),
+            name: Arc::from("orders"),
+            replica_identity: b'd',
+            columns: vec![Column {
+                flags: 1,
+                name: Arc::from("id"),
+                type_id: 23,
+                type_mod: -1,
+            }],
+        };
+        let messages = [
+            RelationVersion {
+                version: 3,
+                lsn: 0x2000,
+                relation: relation.clone(),
+            },
+            RelationVersion::initial(CdcMessage::Unknown),
+        ];
+
+        let cache = encode_relations(&messages).unwrap();
+        assert_eq!(cache["relations"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            relations_stamp(&cache),
+            FormatStamp::current(CHECKPOINT_FORMAT)
+        );
+        let relations = decode_relations(cache).unwrap();
+        assert_eq!(relations.len(), 1);
+        assert_eq!((relations[0].version, relations[0].lsn), (3, 0x2000));
+        match &relations[0].relation {
+            CdcMessage::Relation {
+                id, name, columns, ..
+            } => {
+                assert_eq!(*id, 16384);
+                assert_eq!(&**name, "orders");
+                assert_eq!(columns[0].type_id, 23);
+                assert!(columns[0].is_key());
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        assert!(decode_relations(serde_json::json!({"not": "a list"})).is_err());
+
+        // Format 0 was a bare array
+        let unstamped = serde_json::to_value(vec![relation.clone()]).unwrap();
+        assert_eq!(relations_stamp(&unstamped).format, 0);
+        assert_eq!(decode_relations(unstamped).unwrap().len(), 1);
+
+        // Format 1 stored the Relation messages alone
+        let unversioned = serde_json::json!({"format": 1, "relations": [relation]});
+        let relations = decode_relations(unversioned).unwrap();
+        assert_eq!((relations[0].version, relations[0].lsn), (1, 0));
 
-        Ok(row.map(|r| r.get::<_, i64>(0) as u64))
+        let newer = serde_json::json!({"format": CHECKPOINT_FORMAT + 1, "relations": {}});
+        assert!(relations_stamp(&newer)
+            .check("Schema cache", CHECKPOINT_FORMAT)
+            .is_err());
     }
 }
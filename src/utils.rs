@@ -9,6 +9,22 @@
 
 use anyhow::{anyhow, Result};
 
+/// FNV-1a 64-bit offset basis, the hash of no bytes
+pub const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Folds `bytes` into a 64-bit FNV-1a hash. Stable across builds and
+/// platforms, unlike `std`'s hasher, so it can name things that outlive the
+/// process.
+#[inline]
+pub fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
 /// Validates that a SQL identifier (table name, schema name, column name, etc.)
 /// contains only safe characters.
 ///
@@ -103,98 +119,82 @@ pub fn validate_sql_identifier(name: &str) -> Result<&str> {
     Ok(name)
 }
 
-// --- PostgreSQL value conversion utilities ---
-
-/// Parse PostgreSQL array text format into a JSON array string.
-///
-/// PostgreSQL arrays use `{elem1,elem2,...}` format. This converts
-/// to JSON array format `[elem1,elem2,...]`.
-pub fn parse_pg_array(text: &str, element_type: &str) -> String {
-    let trimmed = text.trim();
-
-    if trimmed == "{}" {
-        return "[]".to_string();
+/// Quote a PostgreSQL identifier to prevent SQL injection.
+/// Wraps in double quotes and escapes embedded double quotes, so the case of
+/// mixed-case names is kept.
+/// Handles schema-qualified names like "public.orders" → "public"."orders".
+pub fn quote_ident(name: &str) -> String {
+    if name.contains('.') {
+        name.split('.')
+            .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(".")
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
     }
+}
 
-    let inner = match trimmed.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
-        Some(s) => s,
-        None => {
-            return format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
-        }
-    };
-
-    let elements = parse_pg_array_elements(inner);
-
-    let mut out = String::with_capacity(text.len() + 2);
-    out.push('[');
+/// Escapes a value for a single-quoted StarRocks/MySQL string literal.
+pub fn escape_sql_string(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
 
-    for (i, elem) in elements.iter().enumerate() {
-        if i > 0 {
-            out.push(',');
-        }
+// --- PostgreSQL LSN utilities ---
 
-        if elem.eq_ignore_ascii_case("NULL") {
-            out.push_str("null");
-        } else {
-            match element_type {
-                "int" => {
-                    if elem.parse::<i64>().is_ok() {
-                        out.push_str(elem);
-                    } else {
-                        json_quote_into(&mut out, elem);
-                    }
-                }
-                "float" => match elem.parse::<f64>() {
-                    Ok(f) if f.is_finite() => out.push_str(elem),
-                    _ => json_quote_into(&mut out, elem),
-                },
-                _ => {
-                    json_quote_into(&mut out, elem);
-                }
-            }
-        }
+/// Parse a PostgreSQL LSN string like "0/1234AB" into a u64.
+pub fn parse_pg_lsn(s: &str) -> Option<u64> {
+    let parts: Vec<&str> = s.split('/').collect();
+    if parts.len() != 2 {
+        return None;
     }
+    let hi = u64::from_str_radix(parts[0], 16).ok()?;
+    let lo = u64::from_str_radix(parts[1], 16).ok()?;
+    Some((hi << 32) | lo)
+}
 
-    out.push(']');
-    out
+/// Format a u64 LSN in PostgreSQL's "X/Y" notation.
+pub fn format_pg_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF)
 }
 
-fn parse_pg_array_elements(inner: &str) -> Vec<String> {
-    let mut elements = Vec::new();
-    let mut current = String::new();
-    let mut in_quotes = false;
-    let mut chars = inner.chars();
-
-    while let Some(ch) = chars.next() {
-        if in_quotes {
-            if ch == '\\' {
-                if let Some(next) = chars.next() {
-                    current.push(next);
-                }
-            } else if ch == '"' {
-                in_quotes = false;
-            } else {
-                current.push(ch);
-            }
-        } else {
-            match ch {
-                '"' => in_quotes = true,
-                ',' => {
-                    elements.push(std::mem::take(&mut current));
-                }
-                _ => current.push(ch),
-            }
-        }
-    }
+// --- PostgreSQL value conversion utilities ---
 
-    if !current.is_empty() || !elements.is_empty() {
-        elements.push(current);
+/// True if `s` is a number literal in JSON syntax: optional minus, no
+/// leading zeros, digits on both sides of the dot, optional exponent.
+pub(crate) fn is_json_number(s: &str) -> bool {
+    fn digits(b: &[u8]) -> usize {
+        b.iter().take_while(|c| c.is_ascii_digit()).count()
     }
 
-    elements
+    let b = s.as_bytes();
+    let mut i = usize::from(b.first() == Some(&b'-'));
+    let int = digits(&b[i..]);
+    if int == 0 || (int > 1 && b[i] == b'0') {
+        return false;
+    }
+    i += int;
+    if b.get(i) == Some(&b'.') {
+        let frac = digits(&b[i + 1..]);
+        if frac == 0 {
+            return false;
+        }
+        i += 1 + frac;
+    }
+    if matches!(b.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(b.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        let exp = digits(&b[i..]);
+        if exp == 0 {
+            return false;
+        }
+        i += exp;
+    }
+    i == b.len()
 }
 
-fn json_quote_into(out: &mut String, s: &str) {
+pub(crate) fn json_quote_into(out: &mut String, s: &str) {
     out.push('"');
     for ch in s.chars() {
         match ch {
@@ -260,15 +260,38 @@ pub fn strip_money_symbol(text: &str) -> String {
         .filter(|c| c.is_ascii_digit() || *c == '-' || *c == '.' || *c == ',')
         .collect();
 
-    let has_dot = cleaned.contains('.');
-    let has_comma = cleaned.contains(',');
+    // The decimal separator depends on lc_monetary ("1,234.56" or
+    // "1.234,56"): it is the last separator, unless that one repeats, in
+    // which case they are all thousands separators ("1.234.567")
+    let decimal = match (cleaned.rfind('.'), cleaned.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(dot.max(comma)),
+        (Some(dot), None) if cleaned.matches('.').count() == 1 => Some(dot),
+        (None, Some(comma)) if cleaned.matches(',').count() == 1 => Some(comma),
+        _ => None,
+    };
 
-    if has_dot && has_comma {
-        cleaned.replace(',', "")
-    } else if has_comma && !has_dot {
-        cleaned.replacen(',', ".", 1)
-    } else {
-        cleaned
+    cleaned
+        .char_indices()
+        .filter_map(|(i, c)| match c {
+            '.' | ',' if Some(i) == decimal => Some('.'),
+            '.' | ',' => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// Serializes `value` to JSON bytes on the sink hot path.
+///
+/// Built with `--features simd-json`, this uses sonic-rs instead of
+/// serde_json. Both produce the same JSON; only the encoder differs.
+pub fn to_json_vec<T: serde::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    #[cfg(feature = "simd-json")]
+    {
+        Ok(sonic_rs::to_vec(value)?)
+    }
+    #[cfg(not(feature = "simd-json"))]
+    {
+        Ok(serde_json::to_vec(value)?)
     }
 }
 
@@ -350,28 +373,22 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_pg_array_int() {
-        assert_eq!(parse_pg_array("{1,2,3}", "int"), "[1,2,3]");
-        assert_eq!(parse_pg_array("{-10,0,42}", "int"), "[-10,0,42]");
-        assert_eq!(parse_pg_array("{}", "int"), "[]");
-        assert_eq!(parse_pg_array("{NULL,1,2}", "int"), "[null,1,2]");
-    }
-
-    #[test]
-    fn test_parse_pg_array_float() {
-        assert_eq!(parse_pg_array("{1.5,2.3,3.0}", "float"), "[1.5,2.3,3.0]");
-        assert_eq!(parse_pg_array("{NaN}", "float"), "[\"NaN\"]");
-        assert_eq!(parse_pg_array("{NULL,1.0}", "float"), "[null,1.0]");
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("orders"), "\"orders\"");
+        assert_eq!(
+            quote_ident("public.UserEvents"),
+            "\"public\".\"UserEvents\""
+        );
+        assert_eq!(quote_ident("My \"Table\""), "\"My \"\"Table\"\"\"");
     }
 
     #[test]
-    fn test_parse_pg_array_text() {
-        assert_eq!(
-            parse_pg_array("{hello,world}", "text"),
-            "[\"hello\",\"world\"]"
-        );
-        assert_eq!(parse_pg_array("{}", "text"), "[]");
-        assert_eq!(parse_pg_array("{NULL}", "text"), "[null]");
+    fn test_pg_lsn_round_trip() {
+        assert_eq!(parse_pg_lsn("0/0"), Some(0));
+        assert_eq!(parse_pg_lsn("16/B374D848"), Some(0x16_B374_D848));
+        assert_eq!(format_pg_lsn(0x16_B374_D848), "16/B374D848");
+        assert_eq!(parse_pg_lsn(&format_pg_lsn(u64::MAX)), Some(u64::MAX));
+        assert_eq!(parse_pg_lsn("not-an-lsn"), None);
     }
 
     #[test]
@@ -406,5 +423,20 @@ mod tests {
         assert_eq!(strip_money_symbol("-$100.00"), "-100.00");
         assert_eq!(strip_money_symbol("€99,95"), "99.95");
         assert_eq!(strip_money_symbol("42.50"), "42.50");
+        assert_eq!(strip_money_symbol("1.234,56 €"), "1234.56");
+        assert_eq!(strip_money_symbol("$1,234,567"), "1234567");
+        assert_eq!(strip_money_symbol("1.234.567"), "1234567");
+    }
+
+    #[test]
+    fn test_to_json_vec_matches_serde_json() {
+        let value = serde_json::json!([
+            {"id": 1, "name": "caf\u{e9} \"quoted\"", "price": 9.5, "deleted": false, "note": null},
+            {"id": -2, "name": "", "price": 0.1, "deleted": true, "note": "line\nbreak"},
+        ]);
+        assert_eq!(
+            to_json_vec(&value).unwrap(),
+            serde_json::to_vec(&value).unwrap()
+        );
     }
 }